@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::annotate::matches_pattern;
+use crate::hierarchy::is_blackbox;
+use crate::progress::pass_span;
+use crate::{Bit, Module, Netlist};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelinkError {
+    UnknownModule(String),
+    /// The ports of the blackbox no longer match those of the body.
+    InterfaceChanged(String),
+}
+
+impl fmt::Display for RelinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::InterfaceChanged(module) => write!(f, "interface of module {:?} changed since it was abstracted", module),
+        }
+    }
+}
+
+impl std::error::Error for RelinkError {}
+
+/// Whether two modules have ports of the same names, directions and widths.
+fn same_interface(a: &Module, b: &Module) -> bool {
+    a.ports.len() == b.ports.len()
+        && a.ports.iter().all(|(name, port)| {
+            b.ports.get(name).is_some_and(|other| other.direction == port.direction && other.bits.len() == port.bits.len())
+        })
+}
+
+/// The blackbox of `module`, keeping its attributes and the nets of its ports.
+fn abstracted(module: &Module) -> Module {
+    let mut blackbox = module.to_blackbox();
+    let mut attributes = module.attributes.clone();
+    attributes.extend(std::mem::take(&mut blackbox.attributes));
+    blackbox.attributes = attributes;
+    let ports: HashSet<&Bit> = module.ports.values().flat_map(|port| port.bits.iter()).collect();
+    let nets = module.nets.iter().filter(|(_, net)| net.bits.iter().all(|bit| ports.contains(bit) || !matches!(bit, Bit::Signal(_))));
+    blackbox.nets = nets.map(|(name, net)| (name.clone(), net.clone())).collect();
+    blackbox
+}
+
+impl Netlist {
+    /// Replaces the body of every module whose name matches one of `patterns`, exactly or as
+    /// a prefix when the pattern ends in `*`, by a blackbox with the same interface, to shrink
+    /// the design for analysis. Returns the bodies taken out, to put back with
+    /// [`Netlist::relink_modules`]. Modules already blackboxes are left alone.
+    pub fn abstract_modules(&mut self, patterns: &[&str]) -> IndexMap<String, Module> {
+        pass_span!("abstract_modules", modules = self.modules.len(), patterns = patterns.len());
+        let mut bodies = IndexMap::new();
+        for (name, module) in self.modules.iter_mut() {
+            if !is_blackbox(module) && patterns.iter().any(|pattern| matches_pattern(pattern, name)) {
+                let blackbox = abstracted(module);
+                bodies.insert(name.to_string(), std::mem::replace(module, blackbox));
+            }
+        }
+        bodies
+    }
+
+    /// Puts back bodies taken out by [`Netlist::abstract_modules`]. Fails without changing
+    /// anything when a module is gone or its ports changed in the meantime.
+    pub fn relink_modules(&mut self, bodies: IndexMap<String, Module>) -> Result<(), RelinkError> {
+        pass_span!("relink_modules", modules = bodies.len());
+        for (name, body) in bodies.iter() {
+            let module = self.modules.get(name).ok_or_else(|| RelinkError::UnknownModule(name.clone()))?;
+            if !same_interface(module, body) {
+                return Err(RelinkError::InterfaceChanged(name.clone()));
+            }
+        }
+        for (name, body) in bodies {
+            self.modules[&name] = body;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abstract_modules() {
+        let original = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let mut netlist = original.clone();
+        let bodies = netlist.abstract_modules(&["test_a*"]);
+        assert!(!bodies.is_empty() && bodies.keys().all(|name| name.starts_with("test_a")));
+        for name in bodies.keys() {
+            let module = &netlist.modules[name];
+            assert!(is_blackbox(module) && module.cells.is_empty());
+            assert_eq!(module.ports.len(), original.modules[name].ports.len());
+            let attributes: Vec<&String> = module.attributes.keys().collect();
+            let kept: Vec<&String> = original.modules[name].attributes.keys().collect();
+            assert_eq!(attributes[..attributes.len() - 1], kept);
+            assert_eq!(attributes.last().unwrap().as_str(), "blackbox");
+        }
+        assert!(netlist.abstract_modules(&["test_a*"]).is_empty());
+
+        let mut changed = netlist.clone();
+        let name = bodies.keys().next().unwrap();
+        changed.modules[name].ports.clear();
+        assert_eq!(changed.relink_modules(bodies.clone()), Err(RelinkError::InterfaceChanged(name.clone())));
+        netlist.relink_modules(bodies).unwrap();
+        assert_eq!(netlist, original);
+    }
+}
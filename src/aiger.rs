@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+
+use crate::batch::REGISTERS;
+use crate::gates::function;
+use crate::{Bit, Direction, IdString, Module};
+
+/// Encoding of an AIGER file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AigerFormat {
+    /// The `aag` text format.
+    Ascii,
+    /// The compact `aig` format, with AND gates delta encoded.
+    #[default]
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AigerError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    /// A bit on a combinational loop, named by its net.
+    CombinationalLoop(String),
+}
+
+impl fmt::Display for AigerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot export cell {} of type {}", cell, module),
+            Self::CombinationalLoop(bit) => write!(f, "combinational loop through {}", bit),
+        }
+    }
+}
+
+impl std::error::Error for AigerError {}
+
+/// Literals are `2 * variable`, plus one when inverted. Variable 0 is constant false.
+const FALSE: usize = 0;
+const TRUE: usize = 1;
+
+struct Graph<'a> {
+    module: &'a Module,
+    literals: HashMap<Bit, usize>,
+    drivers: HashMap<Bit, (Vec<Bit>, Vec<bool>)>,
+    visiting: HashSet<Bit>,
+    /// Variable of the first AND gate.
+    first: usize,
+    ands: Vec<(usize, usize)>,
+    strash: HashMap<(usize, usize), usize>,
+}
+
+impl Graph<'_> {
+    fn and(&mut self, a: usize, b: usize) -> usize {
+        let (a, b) = (a.max(b), a.min(b));
+        if b == FALSE || a == b ^ 1 {
+            return FALSE;
+        }
+        if b == TRUE || a == b {
+            return a;
+        }
+        if let Some(literal) = self.strash.get(&(a, b)) {
+            return *literal;
+        }
+        let literal = 2 * (self.first + self.ands.len());
+        self.ands.push((a, b));
+        self.strash.insert((a, b), literal);
+        literal
+    }
+
+    fn mux(&mut self, select: usize, high: usize, low: usize) -> usize {
+        match (high, low) {
+            _ if high == low => low,
+            (TRUE, FALSE) => select,
+            (FALSE, TRUE) => select ^ 1,
+            (_, FALSE) => self.and(select, high),
+            (FALSE, _) => self.and(select ^ 1, low),
+            (TRUE, _) => self.and(select ^ 1, low ^ 1) ^ 1,
+            (_, TRUE) => self.and(select, high ^ 1) ^ 1,
+            _ => {
+                let (high, low) = (self.and(select, high), self.and(select ^ 1, low));
+                self.and(high ^ 1, low ^ 1) ^ 1
+            }
+        }
+    }
+
+    /// Decomposes a truth table by Shannon expansion on the last input.
+    fn table(&mut self, inputs: &[usize], table: &[bool]) -> usize {
+        let Some((select, rest)) = inputs.split_last() else {
+            return if table[0] { TRUE } else { FALSE };
+        };
+        let (low, high) = table.split_at(table.len() / 2);
+        let (low, high) = (self.table(rest, low), self.table(rest, high));
+        self.mux(*select, high, low)
+    }
+
+    fn literal(&mut self, bit: Bit) -> Result<usize, AigerError> {
+        match bit {
+            Bit::_1 => return Ok(TRUE),
+            Bit::Signal(_) => {}
+            _ => return Ok(FALSE),
+        }
+        if let Some(literal) = self.literals.get(&bit) {
+            return Ok(*literal);
+        }
+        if !self.visiting.insert(bit) {
+            return Err(AigerError::CombinationalLoop(self.module.bit_name(&bit)));
+        }
+        let (inputs, table) = self.drivers[&bit].clone();
+        let inputs = inputs.into_iter().map(|input| self.literal(input)).collect::<Result<Vec<_>, _>>()?;
+        let literal = self.table(&inputs, &table);
+        self.visiting.remove(&bit);
+        self.literals.insert(bit, literal);
+        Ok(literal)
+    }
+}
+
+/// Appends `value` as a 7-bit variable length integer, least significant group first.
+fn encode(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl Module {
+    /// Writes the module as an and-inverter graph in AIGER 1.9. Gates and LUTs are
+    /// decomposed into AND gates and inverters, and registers on one implicit clock become
+    /// latches, reset to their `init` value or left uninitialized. Input port bits are the
+    /// inputs, followed by every other bit read but not driven.
+    pub fn to_aiger(&self, format: AigerFormat) -> Result<Vec<u8>, AigerError> {
+        let mut drivers = HashMap::new();
+        let mut registers = Vec::new();
+        let mut read = Vec::new();
+        for (name, cell) in self.cells.iter() {
+            if let Some((inputs, output, table)) = function(cell) {
+                read.extend(inputs.iter().copied());
+                drivers.insert(output, (inputs, table));
+            } else if let Some((_, d, q)) = REGISTERS.iter().find(|(module, _, _)| *module == cell.module)
+                && let (Some(d), Some(q)) = (cell.connections.get(*d), cell.connections.get(*q))
+            {
+                read.extend(d.iter().copied());
+                registers.extend(d.iter().copied().zip(q.iter().copied()));
+            } else {
+                return Err(AigerError::Unsupported { cell: name.to_string(), module: cell.module.to_string() });
+            }
+        }
+
+        let mut inputs: Vec<(Bit, String)> = Vec::new();
+        for (name, port) in self.ports.iter().filter(|(_, port)| port.direction == Direction::Input) {
+            for (index, bit) in port.bits.iter().enumerate() {
+                let symbol = match port.bits.len() {
+                    1 => name.to_string(),
+                    _ => format!("{}[{}]", name, index),
+                };
+                inputs.push((*bit, symbol));
+            }
+        }
+        let mut defined: HashSet<Bit> = inputs.iter().map(|(bit, _)| *bit).chain(registers.iter().map(|(_, q)| *q)).collect();
+        defined.extend(drivers.keys());
+        let outputs: Vec<(&IdString, &Vec<Bit>)> =
+            self.ports.iter().filter(|(_, port)| port.direction != Direction::Input).map(|(name, port)| (name, &port.bits)).collect();
+        read.extend(outputs.iter().flat_map(|(_, bits)| bits.iter().copied()));
+        for bit in read.iter() {
+            if matches!(bit, Bit::Signal(_)) && defined.insert(*bit) {
+                inputs.push((*bit, self.bit_name(bit)));
+            }
+        }
+
+        let mut graph = Graph {
+            module: self,
+            literals: HashMap::new(),
+            drivers,
+            visiting: HashSet::new(),
+            first: 1 + inputs.len() + registers.len(),
+            ands: Vec::new(),
+            strash: HashMap::new(),
+        };
+        for (variable, (bit, _)) in inputs.iter().enumerate() {
+            graph.literals.entry(*bit).or_insert(2 * (variable + 1));
+        }
+        for (index, (_, q)) in registers.iter().enumerate() {
+            graph.literals.insert(*q, 2 * (1 + inputs.len() + index));
+        }
+        let initial = self.initial_values();
+        let mut latches = Vec::new();
+        for (index, (d, q)) in registers.iter().enumerate() {
+            let init = match initial.get(q) {
+                Some(value) => *value as usize,
+                None => 2 * (1 + inputs.len() + index),
+            };
+            latches.push((graph.literal(*d)?, init, self.bit_name(q)));
+        }
+        let mut output_literals = Vec::new();
+        for (name, bits) in outputs.iter() {
+            for (index, bit) in bits.iter().enumerate() {
+                let symbol = match bits.len() {
+                    1 => name.to_string(),
+                    _ => format!("{}[{}]", name, index),
+                };
+                output_literals.push((graph.literal(*bit)?, symbol));
+            }
+        }
+
+        let (i, l, a) = (inputs.len(), latches.len(), graph.ands.len());
+        let mut bytes = Vec::new();
+        let header = match format {
+            AigerFormat::Ascii => "aag",
+            AigerFormat::Binary => "aig",
+        };
+        writeln!(bytes, "{} {} {} {} {} {}", header, i + l + a, i, l, output_literals.len(), a).unwrap();
+        if format == AigerFormat::Ascii {
+            for variable in 1..=i {
+                writeln!(bytes, "{}", 2 * variable).unwrap();
+            }
+        }
+        for (index, (next, init, _)) in latches.iter().enumerate() {
+            let literal = 2 * (1 + i + index);
+            match format {
+                AigerFormat::Ascii => write!(bytes, "{} {}", literal, next).unwrap(),
+                AigerFormat::Binary => write!(bytes, "{}", next).unwrap(),
+            }
+            match *init {
+                FALSE => writeln!(bytes).unwrap(),
+                init => writeln!(bytes, " {}", init).unwrap(),
+            }
+        }
+        for (literal, _) in output_literals.iter() {
+            writeln!(bytes, "{}", literal).unwrap();
+        }
+        for (index, (a, b)) in graph.ands.iter().enumerate() {
+            let literal = 2 * (graph.first + index);
+            match format {
+                AigerFormat::Ascii => writeln!(bytes, "{} {} {}", literal, a, b).unwrap(),
+                AigerFormat::Binary => {
+                    encode(&mut bytes, literal - a);
+                    encode(&mut bytes, a - b);
+                }
+            }
+        }
+        for (index, (_, symbol)) in inputs.iter().enumerate() {
+            writeln!(bytes, "i{} {}", index, symbol).unwrap();
+        }
+        for (index, (_, _, symbol)) in latches.iter().enumerate() {
+            writeln!(bytes, "l{} {}", index, symbol).unwrap();
+        }
+        for (index, (_, symbol)) in output_literals.iter().enumerate() {
+            writeln!(bytes, "o{} {}", index, symbol).unwrap();
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    /// `y = a ^ b`, registered into `q` which starts at 1.
+    fn design() -> Module {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let [a, b] = ["a", "b"].map(|name| builder.input(name, 1).unwrap());
+        let [y, q] = ["y", "q"].map(|name| builder.output(name, 1).unwrap());
+        builder.cell("xor", CellBuilder::new("$_XOR_").input("A", a).input("B", b).output("Y", y.clone())).unwrap();
+        builder.cell("reg", CellBuilder::new("$_DFF_P_").input("C", clk).input("D", y).output("Q", q)).unwrap();
+        let mut module = builder.build();
+        module.nets["q"].attributes.insert("init".to_string(), "1".into());
+        module
+    }
+
+    #[test]
+    fn test_to_aiger() {
+        let module = design();
+        let ascii = String::from_utf8(module.to_aiger(AigerFormat::Ascii).unwrap()).unwrap();
+        let expected = "aag 7 3 1 2 3
+2
+4
+6
+8 15 1
+15
+8
+10 6 5
+12 7 4
+14 13 11
+i0 clk
+i1 a
+i2 b
+l0 q
+o0 y
+o1 q
+";
+        assert_eq!(ascii, expected);
+
+        let binary = module.to_aiger(AigerFormat::Binary).unwrap();
+        let expected =
+            [b"aig 7 3 1 2 3\n15 1\n15\n8\n".as_slice(), &[4, 1, 5, 3, 1, 2], b"i0 clk\ni1 a\ni2 b\nl0 q\no0 y\no1 q\n"].concat();
+        assert_eq!(binary, expected);
+
+        let mut module = design();
+        module.cells["xor"].module = "$xor".into();
+        assert!(matches!(module.to_aiger(AigerFormat::Ascii), Err(AigerError::Unsupported { .. })));
+    }
+}
@@ -0,0 +1,172 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::progress::pass_span;
+use crate::{Bit, IdString, Module, Net, Netlist};
+
+/// How to pick the name that survives among nets covering the same bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasPolicy {
+    /// Public names over hidden and `$`-prefixed ones, then the fewest hierarchy levels, then
+    /// the shortest name.
+    #[default]
+    PreferPublic,
+    /// The first name in file order.
+    First,
+}
+
+/// What happens to the names that lose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasAction {
+    /// Drop the aliases from `netnames`.
+    #[default]
+    Remove,
+    /// Drop the aliases, but list them in the `aliases` attribute of the canonical net,
+    /// separated by spaces. Names already listed there, or on the aliases themselves, are kept.
+    Annotate,
+}
+
+/// Canonical net name to the aliases that were folded into it, per module.
+pub type AliasReport = IndexMap<String, IndexMap<String, Vec<String>>>;
+
+fn rank(name: &str, net: &Net) -> (bool, bool, usize, usize) {
+    (net.hide_name, name.starts_with('$'), name.matches('.').count(), name.len())
+}
+
+/// Names in the `aliases` attribute of `net`, as written by [`AliasAction::Annotate`].
+fn listed_aliases(net: &Net) -> Vec<String> {
+    match net.attributes.get("aliases") {
+        Some(serde_json::Value::String(names)) => names.split_whitespace().map(str::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl Module {
+    /// Groups `netnames` covering identical bit vectors, returning the canonical name and its
+    /// aliases for every group with more than one name. Nets naming a port are never aliases.
+    pub fn find_aliases(&self, policy: AliasPolicy) -> IndexMap<String, Vec<String>> {
+        let mut groups: IndexMap<&Vec<Bit>, Vec<&IdString>> = IndexMap::new();
+        for (name, net) in self.nets.iter() {
+            if net.bits.iter().any(|bit| matches!(bit, Bit::Signal(_))) {
+                groups.entry(&net.bits).or_default().push(name);
+            }
+        }
+        let mut aliases = IndexMap::new();
+        for (_, mut names) in groups.into_iter().filter(|(_, names)| names.len() > 1) {
+            if policy == AliasPolicy::PreferPublic {
+                names.sort_by_key(|name| rank(name, &self.nets[*name]));
+            }
+            if let Some(index) = names.iter().position(|name| self.ports.contains_key(*name)) {
+                let port = names.remove(index);
+                names.insert(0, port);
+            }
+            let canonical = names.remove(0).clone();
+            let others: Vec<String> = names.into_iter().filter(|name| !self.ports.contains_key(*name)).map(String::from).collect();
+            if !others.is_empty() {
+                aliases.insert(canonical.to_string(), others);
+            }
+        }
+        aliases
+    }
+
+    /// Keeps one name per group found by [`Module::find_aliases`], returning what was folded.
+    pub fn consolidate_aliases(&mut self, policy: AliasPolicy, action: AliasAction) -> IndexMap<String, Vec<String>> {
+        pass_span!("consolidate_aliases", cells = self.cells.len(), nets = self.nets.len());
+        let aliases = self.find_aliases(policy);
+        for (canonical, names) in aliases.iter() {
+            let mut listed: Vec<String> = listed_aliases(&self.nets[canonical]);
+            for name in names {
+                let net = self.nets.shift_remove(name).unwrap();
+                listed.push(name.clone());
+                listed.extend(listed_aliases(&net));
+            }
+            if action == AliasAction::Annotate {
+                let mut seen = std::collections::HashSet::new();
+                listed.retain(|name| name != canonical && seen.insert(name.clone()));
+                let net = self.nets.get_mut(canonical).unwrap();
+                net.attributes.insert("aliases".to_string(), serde_json::Value::String(listed.join(" ")));
+            }
+        }
+        aliases
+    }
+}
+
+impl Netlist {
+    /// Runs [`Module::consolidate_aliases`] on every module.
+    pub fn consolidate_aliases(&mut self, policy: AliasPolicy, action: AliasAction) -> AliasReport {
+        pass_span!("consolidate_aliases", modules = self.modules.len());
+        self.modules
+            .iter_mut()
+            .map(|(name, module)| {
+                pass_span!("module", module = name.as_str());
+                (name.to_string(), module.consolidate_aliases(policy, action))
+            })
+            .filter(|(_, aliases)| !aliases.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn aliased() -> Netlist {
+        Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "y": { "direction": "output", "bits": [2, 3] }
+                    },
+                    "netnames": {
+                        "$auto$alumacc.cc:485:replace_alu$12": { "hide_name": 1, "bits": [4, 5] },
+                        "u0.sum": { "bits": [4, 5] },
+                        "sum": { "bits": [4, 5] },
+                        "y": { "bits": [2, 3] },
+                        "u0.y": { "bits": [2, 3] },
+                        "zero": { "bits": ["0", "0"] },
+                        "also_zero": { "bits": ["0", "0"] }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_aliases() {
+        let netlist = aliased();
+        let top = &netlist.modules["top"];
+        let aliases = top.find_aliases(AliasPolicy::PreferPublic);
+        assert_eq!(aliases["sum"], ["u0.sum", "$auto$alumacc.cc:485:replace_alu$12"]);
+        assert_eq!(aliases["y"], ["u0.y"]);
+        assert_eq!(aliases.len(), 2);
+
+        let aliases = top.find_aliases(AliasPolicy::First);
+        assert_eq!(aliases["$auto$alumacc.cc:485:replace_alu$12"], ["u0.sum", "sum"]);
+    }
+
+    #[test]
+    fn test_consolidate_aliases() {
+        let mut netlist = aliased();
+        let report = netlist.consolidate_aliases(AliasPolicy::PreferPublic, AliasAction::Annotate);
+        assert_eq!(report["top"].len(), 2);
+
+        let top = &netlist.modules["top"];
+        assert_eq!(top.nets.keys().collect::<Vec<_>>(), ["sum", "y", "zero", "also_zero"]);
+        assert_eq!(top.nets["sum"].attributes["aliases"], json!("u0.sum $auto$alumacc.cc:485:replace_alu$12"));
+    }
+
+    #[test]
+    fn test_annotate_merges_existing_aliases() {
+        let mut netlist = aliased();
+        let top = netlist.modules.get_mut("top").unwrap();
+        top.nets["sum"].attributes.insert("aliases".to_string(), json!("old.sum"));
+        top.nets["u0.sum"].attributes.insert("aliases".to_string(), json!("older.sum sum"));
+        top.consolidate_aliases(AliasPolicy::PreferPublic, AliasAction::Annotate);
+        assert_eq!(top.nets["sum"].attributes["aliases"], json!("old.sum u0.sum older.sum $auto$alumacc.cc:485:replace_alu$12"));
+    }
+}
@@ -0,0 +1,143 @@
+use serde_json::Value;
+
+use crate::{Cell, Netlist};
+
+/// Whether `name` matches `pattern`, exactly or as a prefix when the pattern ends in `*`.
+pub(crate) fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Whether `name` matches `pattern`, which matches everything when unset.
+fn matches(pattern: &Option<String>, name: &str) -> bool {
+    pattern.as_deref().is_none_or(|pattern| matches_pattern(pattern, name))
+}
+
+/// Cells to edit in bulk. Every pattern that is set must match, exactly or as a prefix when it
+/// ends in `*`; the default selects every cell of every module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellSelector {
+    pub module: Option<String>,
+    pub name: Option<String>,
+    pub cell_type: Option<String>,
+    /// Only cells that already have this attribute.
+    pub attribute: Option<String>,
+}
+
+impl CellSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn module(mut self, pattern: &str) -> Self {
+        self.module = Some(pattern.to_string());
+        self
+    }
+
+    pub fn name(mut self, pattern: &str) -> Self {
+        self.name = Some(pattern.to_string());
+        self
+    }
+
+    pub fn cell_type(mut self, pattern: &str) -> Self {
+        self.cell_type = Some(pattern.to_string());
+        self
+    }
+
+    pub fn with_attribute(mut self, key: &str) -> Self {
+        self.attribute = Some(key.to_string());
+        self
+    }
+
+    pub fn matches(&self, module: &str, name: &str, cell: &Cell) -> bool {
+        matches(&self.module, module)
+            && matches(&self.name, name)
+            && matches(&self.cell_type, &cell.module)
+            && self.attribute.as_ref().is_none_or(|key| cell.attributes.contains_key(key))
+    }
+}
+
+/// One attribute change of a bulk edit; `None` when the attribute is or becomes unset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeEdit {
+    pub module: String,
+    pub cell: String,
+    pub key: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl Netlist {
+    /// The edits that setting `key` to `value`, or removing it when `value` is `None`, on the
+    /// selected cells would make, without making them. Cells already in that state are left out.
+    pub fn preview_attribute_where(&self, selector: &CellSelector, key: &str, value: Option<&Value>) -> Vec<AttributeEdit> {
+        let mut edits = Vec::new();
+        for (module_name, module) in self.modules.iter() {
+            for (name, cell) in module.cells.iter().filter(|(name, cell)| selector.matches(module_name, name, cell)) {
+                let old = cell.attributes.get(key);
+                if old != value {
+                    edits.push(AttributeEdit {
+                        module: module_name.to_string(),
+                        cell: name.to_string(),
+                        key: key.to_string(),
+                        old: old.cloned(),
+                        new: value.cloned(),
+                    });
+                }
+            }
+        }
+        edits
+    }
+
+    /// Sets `key` to `value` on every selected cell, returning the edits made.
+    pub fn set_attribute_where(&mut self, selector: &CellSelector, key: &str, value: impl Into<Value>) -> Vec<AttributeEdit> {
+        let edits = self.preview_attribute_where(selector, key, Some(&value.into()));
+        self.apply_attribute_edits(&edits);
+        edits
+    }
+
+    /// Removes `key` from every selected cell, returning the edits made.
+    pub fn remove_attribute_where(&mut self, selector: &CellSelector, key: &str) -> Vec<AttributeEdit> {
+        let edits = self.preview_attribute_where(selector, key, None);
+        self.apply_attribute_edits(&edits);
+        edits
+    }
+
+    /// Makes edits, such as a reviewed preview. Edits of cells that no longer exist are skipped.
+    pub fn apply_attribute_edits(&mut self, edits: &[AttributeEdit]) {
+        for edit in edits {
+            let Some(cell) = self.modules.get_mut(&edit.module).and_then(|module| module.cells.get_mut(&edit.cell)) else {
+                continue;
+            };
+            match &edit.new {
+                Some(value) => _ = cell.attributes.insert(edit.key.clone(), value.clone()),
+                None => _ = cell.attributes.shift_remove(&edit.key),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_where() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let selector = CellSelector::new().module("test_*").cell_type("$_AND_");
+        let preview = netlist.preview_attribute_where(&selector, "x-placer:region", Some(&"north".into()));
+        assert_eq!(preview.len(), 1);
+        assert_eq!((preview[0].module.as_str(), preview[0].old.as_ref()), ("test_and", None));
+        assert!(netlist.modules["test_and"].cells[0].attributes.is_empty());
+
+        assert_eq!(netlist.set_attribute_where(&selector, "x-placer:region", "north"), preview);
+        assert_eq!(netlist.modules["test_and"].cells[0].attributes["x-placer:region"], "north");
+        assert!(netlist.set_attribute_where(&selector, "x-placer:region", "north").is_empty());
+
+        let removed = netlist.remove_attribute_where(&CellSelector::new().with_attribute("x-placer:region"), "x-placer:region");
+        assert_eq!(removed.len(), 1);
+        assert!(netlist.modules["test_and"].cells[0].attributes.is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use crate::{Bit, Cell, Direction, IdString, Memory, Module, Net, Port};
+
+macro_rules! handle {
+    ($name:ident) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(u32);
+
+        impl $name {
+            pub fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+
+handle!(PortId);
+handle!(CellId);
+handle!(NetId);
+
+/// Named objects stored by index. Removed slots are never reused, so a handle to a removed
+/// object stays invalid instead of pointing at a newer one.
+#[derive(Debug, Clone)]
+struct Slots<T> {
+    items: Vec<Option<(IdString, T)>>,
+    names: HashMap<IdString, u32>,
+}
+
+impl<T> Slots<T> {
+    fn new(items: IndexMap<IdString, T>) -> Self {
+        let names = items.keys().enumerate().map(|(index, name)| (name.clone(), index as u32)).collect();
+        Self { items: items.into_iter().map(Some).collect(), names }
+    }
+
+    fn get(&self, index: u32) -> Option<(&str, &T)> {
+        self.items.get(index as usize)?.as_ref().map(|(name, item)| (name.as_str(), item))
+    }
+
+    fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        self.items.get_mut(index as usize)?.as_mut().map(|(_, item)| item)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (u32, &str, &T)> {
+        self.items.iter().enumerate().filter_map(|(index, item)| item.as_ref().map(|(name, item)| (index as u32, name.as_str(), item)))
+    }
+
+    fn insert(&mut self, name: &str, item: T) -> Option<u32> {
+        if self.names.contains_key(name) {
+            return None;
+        }
+        let index = self.items.len() as u32;
+        self.items.push(Some((name.into(), item)));
+        self.names.insert(name.into(), index);
+        Some(index)
+    }
+
+    fn remove(&mut self, index: u32) -> Option<(IdString, T)> {
+        let (name, item) = self.items.get_mut(index as usize)?.take()?;
+        self.names.remove(&name);
+        Some((name, item))
+    }
+
+    /// Fails if the handle is stale or `name` is taken by another object.
+    fn rename(&mut self, index: u32, name: &str) -> bool {
+        match self.names.get(name) {
+            Some(other) => *other == index,
+            None => {
+                let Some((old, _)) = self.items.get_mut(index as usize).and_then(Option::as_mut) else {
+                    return false;
+                };
+                self.names.remove(old);
+                *old = name.into();
+                self.names.insert(name.into(), index);
+                true
+            }
+        }
+    }
+
+    fn into_map(self) -> IndexMap<IdString, T> {
+        self.items.into_iter().flatten().collect()
+    }
+}
+
+/// A module with its ports, cells and nets stored in arenas and addressed by handles. Handles
+/// stay valid across renames and removals of other objects, and lookups do not hash names.
+#[derive(Debug, Clone)]
+pub struct ModuleArena {
+    pub attributes: IndexMap<String, serde_json::Value>,
+    pub memories: IndexMap<IdString, Memory>,
+    ports: Slots<Port>,
+    cells: Slots<Cell>,
+    nets: Slots<Net>,
+    extra: IndexMap<String, serde_json::Value>,
+}
+
+macro_rules! accessors {
+    ($slots:ident, $id:ident, $item:ty, $find:ident, $get:ident, $get_mut:ident, $name:ident, $iter:ident, $add:ident, $remove:ident, $rename:ident) => {
+        pub fn $find(&self, name: &str) -> Option<$id> {
+            self.$slots.names.get(name).copied().map($id)
+        }
+
+        pub fn $get(&self, id: $id) -> Option<&$item> {
+            self.$slots.get(id.0).map(|(_, item)| item)
+        }
+
+        pub fn $get_mut(&mut self, id: $id) -> Option<&mut $item> {
+            self.$slots.get_mut(id.0)
+        }
+
+        pub fn $name(&self, id: $id) -> Option<&str> {
+            self.$slots.get(id.0).map(|(name, _)| name)
+        }
+
+        pub fn $iter(&self) -> impl Iterator<Item = ($id, &str, &$item)> {
+            self.$slots.iter().map(|(index, name, item)| ($id(index), name, item))
+        }
+
+        /// Returns `None` if the name is taken.
+        pub fn $add(&mut self, name: &str, item: $item) -> Option<$id> {
+            self.$slots.insert(name, item).map($id)
+        }
+
+        pub fn $remove(&mut self, id: $id) -> Option<(IdString, $item)> {
+            self.$slots.remove(id.0)
+        }
+
+        /// Returns `false` if the handle is stale or the name is taken.
+        pub fn $rename(&mut self, id: $id, name: &str) -> bool {
+            self.$slots.rename(id.0, name)
+        }
+    };
+}
+
+impl ModuleArena {
+    accessors!(ports, PortId, Port, port_id, port, port_mut, port_name, ports, add_port, remove_port, rename_port);
+    accessors!(cells, CellId, Cell, cell_id, cell, cell_mut, cell_name, cells, add_cell, remove_cell, rename_cell);
+    accessors!(nets, NetId, Net, net_id, net, net_mut, net_name, nets, add_net, remove_net, rename_net);
+
+    /// The cell driving every bit, from output ports of cells.
+    pub fn drivers(&self) -> HashMap<Bit, CellId> {
+        let mut drivers = HashMap::new();
+        for (id, _, cell) in self.cells() {
+            for (port, bits) in cell.connections.iter() {
+                if cell.port_directions.get(port) == Some(&Direction::Output) {
+                    bits.iter().filter(|bit| matches!(bit, Bit::Signal(_))).for_each(|bit| _ = drivers.insert(*bit, id));
+                }
+            }
+        }
+        drivers
+    }
+
+    /// Cells reading every bit, once per cell, in handle order.
+    pub fn loads(&self) -> HashMap<Bit, Vec<CellId>> {
+        let mut loads: HashMap<Bit, Vec<CellId>> = HashMap::new();
+        for (id, _, cell) in self.cells() {
+            for (port, bits) in cell.connections.iter() {
+                if cell.port_directions.get(port) != Some(&Direction::Output) {
+                    for bit in bits.iter().filter(|bit| matches!(bit, Bit::Signal(_))) {
+                        let cells = loads.entry(*bit).or_default();
+                        if cells.last() != Some(&id) {
+                            cells.push(id);
+                        }
+                    }
+                }
+            }
+        }
+        loads
+    }
+}
+
+impl From<Module> for ModuleArena {
+    fn from(module: Module) -> Self {
+        Self {
+            attributes: module.attributes,
+            memories: module.memories,
+            ports: Slots::new(module.ports),
+            cells: Slots::new(module.cells),
+            nets: Slots::new(module.nets),
+            extra: module.extra,
+        }
+    }
+}
+
+impl From<ModuleArena> for Module {
+    /// Objects keep their original order, objects added to the arena come last.
+    fn from(arena: ModuleArena) -> Self {
+        Self {
+            attributes: arena.attributes,
+            ports: arena.ports.into_map(),
+            cells: arena.cells.into_map(),
+            memories: arena.memories,
+            nets: arena.nets.into_map(),
+            extra: arena.extra,
+            allocated: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    #[test]
+    fn test_arena_roundtrip() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let module = netlist.modules["adder"].clone();
+        let mut arena = ModuleArena::from(module.clone());
+
+        let a = arena.port_id("a").unwrap();
+        assert_eq!(arena.port(a).unwrap().bits.len(), 17);
+        let (first, name, _) = arena.cells().next().unwrap();
+        let name = name.to_string();
+        assert!(arena.rename_cell(first, "renamed"));
+        assert_eq!(arena.cell_id("renamed"), Some(first));
+        assert_eq!(arena.cell_id(&name), None);
+        assert!(!arena.rename_cell(first, arena.cell_name(CellId(1)).unwrap().to_string().as_str()));
+
+        let drivers = arena.drivers();
+        let loads = arena.loads();
+        let output = arena.cell(first).unwrap().connections["Y"][0];
+        assert_eq!(drivers[&output], first);
+        for input in arena.cell(first).unwrap().connections["A"].iter() {
+            assert!(loads[input].contains(&first));
+        }
+
+        let (_, removed) = arena.remove_cell(first).unwrap();
+        assert!(arena.cell(first).is_none());
+        assert!(!arena.rename_cell(first, "again"));
+        let added = arena.add_cell("readded", removed).unwrap();
+        assert_ne!(added, first);
+
+        let back = Module::from(arena);
+        assert_eq!(back.cells.len(), module.cells.len());
+        assert_eq!(back.cells.keys().last().unwrap(), "readded");
+        assert_eq!(back.ports.keys().collect::<Vec<_>>(), module.ports.keys().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::builder::{TRUE, constant};
+use crate::param::{ParamValue, is_constant};
+use crate::{Bit, Cell, Memory, Module, Net};
+
+/// A range of HDL source as Yosys records it in `src`, `file:line.column-line.column`. Lines
+/// and columns count from 1; locations of a single line or point leave out the rest.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    pub file: String,
+    pub start_line: usize,
+    pub start_column: Option<usize>,
+    pub end_line: usize,
+    pub end_column: Option<usize>,
+}
+
+impl FromStr for SourceSpan {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid source location {:?}", text);
+        let (file, range) = text.rsplit_once(':').filter(|(file, _)| !file.is_empty()).ok_or_else(invalid)?;
+        let position = |position: &str| -> Result<(usize, Option<usize>), String> {
+            match position.split_once('.') {
+                Some((line, column)) => Ok((line.parse().map_err(|_| invalid())?, Some(column.parse().map_err(|_| invalid())?))),
+                None => Ok((position.parse().map_err(|_| invalid())?, None)),
+            }
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (position(start)?, position(end)?),
+            None => (position(range)?, position(range)?),
+        };
+        Ok(Self { file: file.to_string(), start_line: start.0, start_column: start.1, end_line: end.0, end_column: end.1 })
+    }
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let position = |line: usize, column: Option<usize>| match column {
+            Some(column) => format!("{}.{}", line, column),
+            None => line.to_string(),
+        };
+        let start = position(self.start_line, self.start_column);
+        let end = position(self.end_line, self.end_column);
+        match start == end {
+            true => write!(f, "{}:{}", self.file, start),
+            false => write!(f, "{}:{}-{}", self.file, start, end),
+        }
+    }
+}
+
+/// Typed access to the attributes Yosys knows, decoding the ways the JSON backend writes them:
+/// integers as 32-bit strings of binary digits or as numbers, strings that look like binary
+/// digits with a trailing space, and flags as any nonzero value.
+pub trait Attrs {
+    fn attrs(&self) -> &IndexMap<String, Value>;
+
+    fn attrs_mut(&mut self) -> &mut IndexMap<String, Value>;
+
+    fn attr(&self, name: &str) -> Option<ParamValue> {
+        self.attrs().get(name).and_then(ParamValue::from_value)
+    }
+
+    fn set_attr(&mut self, name: &str, value: &ParamValue) {
+        self.attrs_mut().insert(name.to_string(), value.into());
+    }
+
+    /// Whether the attribute is set to anything but zero, as `get_bool_attribute` reads it.
+    fn flag(&self, name: &str) -> bool {
+        self.attr(name).is_some_and(|value| value.as_u64() != Some(0))
+    }
+
+    /// Sets the attribute to 1 the way Yosys writes it, or removes it.
+    fn set_flag(&mut self, name: &str, value: bool) {
+        if value {
+            self.attrs_mut().insert(name.to_string(), TRUE.into());
+        } else {
+            self.attrs_mut().shift_remove(name);
+        }
+    }
+
+    fn int(&self, name: &str) -> Option<i64> {
+        self.attr(name)?.as_i64()
+    }
+
+    /// Written as 32 binary digits, as Yosys writes integer attributes.
+    fn set_int(&mut self, name: &str, value: i32) {
+        self.attrs_mut().insert(name.to_string(), format!("{:032b}", value).into());
+    }
+
+    /// The attribute as text, without the space marking text that looks like binary digits.
+    fn string(&self, name: &str) -> Option<&str> {
+        let text = self.attrs().get(name)?.as_str()?;
+        Some(text.strip_suffix(' ').filter(|digits| is_constant(digits)).unwrap_or(text))
+    }
+
+    fn set_string(&mut self, name: &str, value: &str) {
+        self.set_attr(name, &ParamValue::String(value.to_string()));
+    }
+
+    fn keep(&self) -> bool {
+        self.flag("keep")
+    }
+
+    fn set_keep(&mut self, value: bool) {
+        self.set_flag("keep", value);
+    }
+
+    fn top(&self) -> bool {
+        self.flag("top")
+    }
+
+    fn set_top(&mut self, value: bool) {
+        self.set_flag("top", value);
+    }
+
+    fn blackbox(&self) -> bool {
+        self.flag("blackbox")
+    }
+
+    fn set_blackbox(&mut self, value: bool) {
+        self.set_flag("blackbox", value);
+    }
+
+    fn whitebox(&self) -> bool {
+        self.flag("whitebox")
+    }
+
+    fn keep_hierarchy(&self) -> bool {
+        self.flag("keep_hierarchy")
+    }
+
+    fn full_case(&self) -> bool {
+        self.flag("full_case")
+    }
+
+    fn parallel_case(&self) -> bool {
+        self.flag("parallel_case")
+    }
+
+    /// The source location, `file:line.column-line.column`, several separated by `|` where
+    /// objects were merged.
+    fn src(&self) -> Option<&str> {
+        self.string("src")
+    }
+
+    /// The source locations, skipping those that do not parse.
+    fn src_spans(&self) -> Vec<SourceSpan> {
+        self.src().map(|src| src.split('|').filter_map(|span| span.parse().ok()).collect()).unwrap_or_default()
+    }
+
+    fn set_src(&mut self, value: &str) {
+        self.set_string("src", value);
+    }
+
+    /// The hierarchical name before flattening, instance names outermost first.
+    fn hdlname(&self) -> Option<Vec<&str>> {
+        self.string("hdlname").map(|name| name.split(' ').collect())
+    }
+
+    /// The initial value, least significant bit first. Numbers are read as 32 bits.
+    fn init(&self) -> Option<Vec<Bit>> {
+        match self.attr("init")? {
+            ParamValue::BitVector(bits) => Some(bits),
+            ParamValue::Int(value) => Some(constant(value as u64, 32)),
+            ParamValue::String(_) => None,
+        }
+    }
+
+    fn set_init(&mut self, bits: &[Bit]) {
+        self.set_attr("init", &ParamValue::BitVector(bits.to_vec()));
+    }
+}
+
+macro_rules! attrs_in {
+    ($($type:ty),*) => {
+        $(
+            impl Attrs for $type {
+                fn attrs(&self) -> &IndexMap<String, Value> {
+                    &self.attributes
+                }
+
+                fn attrs_mut(&mut self) -> &mut IndexMap<String, Value> {
+                    &mut self.attributes
+                }
+            }
+        )*
+    };
+}
+
+attrs_in!(Module, Cell, Memory, Net);
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_attrs() {
+        let mut net = Net::new(vec![Bit::Signal(2), Bit::Signal(3)]);
+        net.attributes = serde_json::from_value(json!({
+            "keep": "00000000000000000000000000000001",
+            "full_case": 0,
+            "parallel_case": "00000000000000000000000000000000",
+            "src": "a.v:3.5-3.9|b.v:1.1-1.4",
+            "hdlname": "u_core reg",
+            "init": "x1",
+            "mode": "0101 ",
+            "depth": "11111111111111111111111111111110"
+        }))
+        .unwrap();
+        assert!(net.keep() && !net.full_case() && !net.parallel_case() && !net.top());
+        let spans = net.src_spans();
+        assert_eq!(spans.iter().map(ToString::to_string).collect::<Vec<_>>(), ["a.v:3.5-3.9", "b.v:1.1-1.4"]);
+        assert_eq!((spans[0].start_line, spans[0].start_column, spans[0].end_column), (3, Some(5), Some(9)));
+        assert_eq!(net.hdlname(), Some(vec!["u_core", "reg"]));
+        assert_eq!(net.init(), Some(vec![Bit::_1, Bit::X]));
+        assert_eq!(net.string("mode"), Some("0101"));
+        assert_eq!(net.int("depth"), Some(-2));
+
+        net.set_keep(false);
+        net.set_top(true);
+        net.set_string("mode", "1100");
+        net.set_int("depth", 5);
+        net.set_init(&[Bit::_0, Bit::_1]);
+        assert!(!net.attributes.contains_key("keep") && net.top());
+        assert_eq!(net.attributes["mode"], "1100 ");
+        assert_eq!(net.int("depth"), Some(5));
+        assert_eq!(net.attributes["init"], "10");
+        let mut cell = Cell::new("$_AND_");
+        cell.attributes.insert("init".to_string(), json!(5));
+        assert_eq!(cell.init().unwrap()[..3], [Bit::_1, Bit::_0, Bit::_1]);
+    }
+
+    #[test]
+    fn test_source_span() {
+        let span: SourceSpan = "C:/rtl/counter.v:14".parse().unwrap();
+        assert_eq!(
+            span,
+            SourceSpan { file: "C:/rtl/counter.v".to_string(), start_line: 14, start_column: None, end_line: 14, end_column: None }
+        );
+        assert_eq!(span.to_string(), "C:/rtl/counter.v:14");
+        assert_eq!("top.v:3.1-9.10".parse::<SourceSpan>().unwrap().to_string(), "top.v:3.1-9.10");
+        assert!("top.v".parse::<SourceSpan>().is_err() && "top.v:3.x".parse::<SourceSpan>().is_err());
+        let mut module = crate::Module::default();
+        module.set_src("counter.v:14.3-14.27|bad|top.v:3.1-9.10");
+        assert_eq!(module.src_spans().len(), 2);
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::gates::{cycle_bits, eval_table, function, gate};
+use crate::{Bit, Direction, Module};
+
+/// Independent input vectors simulated at once, one per bit of a word.
+pub const LANES: usize = 64;
+
+/// Registers stepped by [`BatchSimulator::step`], all on one implicit clock.
+pub(crate) const REGISTERS: &[(&str, &str, &str)] =
+    &[("$_DFF_P_", "D", "Q"), ("$_DFF_N_", "D", "Q"), ("$_FF_", "D", "Q"), ("$dff", "D", "Q"), ("$ff", "D", "Q")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchSimError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    /// Bits on a combinational loop, named by their nets.
+    CombinationalLoop(Vec<String>),
+    UnknownPort(String),
+}
+
+impl fmt::Display for BatchSimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot simulate cell {} of type {}", cell, module),
+            Self::CombinationalLoop(bits) => write!(f, "combinational loop through {}", bits.join(", ")),
+            Self::UnknownPort(port) => write!(f, "no input port {}", port),
+        }
+    }
+}
+
+impl std::error::Error for BatchSimError {}
+
+enum Operation {
+    Gate(fn(&[u64]) -> u64),
+    /// Truth table of a LUT, evaluated as a sum of minterms.
+    Table(Vec<bool>),
+}
+
+struct Node {
+    inputs: Vec<Bit>,
+    output: Bit,
+    operation: Operation,
+}
+
+impl Node {
+    fn eval(&self, inputs: &[u64]) -> u64 {
+        match &self.operation {
+            Operation::Gate(eval) => eval(inputs),
+            Operation::Table(table) => eval_table(table, inputs),
+        }
+    }
+}
+
+/// Bit-parallel simulator for gate-level modules, evaluating [`LANES`] input vectors with
+/// one word operation per gate. Bit `n` of every value belongs to vector `n`.
+pub struct BatchSimulator {
+    ports: IndexMap<String, (Direction, Vec<Bit>)>,
+    /// Gates and LUTs in topological order.
+    nodes: Vec<Node>,
+    /// Data input and output of every register.
+    registers: Vec<(Bit, Bit)>,
+    values: HashMap<Bit, u64>,
+    toggles: HashMap<Bit, u64>,
+}
+
+impl BatchSimulator {
+    /// Prepares a simulation with every bit 0 in all lanes, except registers with an `init`
+    /// value.
+    pub fn new(module: &Module) -> Result<Self, BatchSimError> {
+        let order = module.topo_sort_cells().map_err(|cycles| BatchSimError::CombinationalLoop(cycle_bits(module, &cycles)))?;
+        let mut nodes = Vec::new();
+        let mut registers = Vec::new();
+        for (name, cell) in order.into_iter().map(|name| (name, &module.cells[name])) {
+            if let Some(gate) = gate(&cell.module)
+                && let Some((inputs, output, _)) = function(cell)
+            {
+                nodes.push(Node { inputs, output, operation: Operation::Gate(gate.eval) });
+            } else if let Some((inputs, output, table)) = function(cell) {
+                nodes.push(Node { inputs, output, operation: Operation::Table(table) });
+            } else if let Some((_, d, q)) = REGISTERS.iter().find(|(module, _, _)| *module == cell.module)
+                && let (Some(d), Some(q)) = (cell.connections.get(*d), cell.connections.get(*q))
+            {
+                registers.extend(d.iter().copied().zip(q.iter().copied()));
+            } else {
+                return Err(BatchSimError::Unsupported { cell: name.to_string(), module: cell.module.to_string() });
+            }
+        }
+
+        let mut values: HashMap<Bit, u64> =
+            module.initial_values().into_iter().map(|(bit, value)| (bit, if value { u64::MAX } else { 0 })).collect();
+        values.insert(Bit::_1, u64::MAX);
+        let ports = module.ports.iter().map(|(name, port)| (name.to_string(), (port.direction.clone(), port.bits.clone()))).collect();
+        let mut simulator = Self { ports, nodes, registers, values, toggles: HashMap::new() };
+        simulator.eval();
+        simulator.toggles.clear();
+        Ok(simulator)
+    }
+
+    fn value(&self, bit: &Bit) -> u64 {
+        self.values.get(bit).copied().unwrap_or(0)
+    }
+
+    fn update(&mut self, bit: Bit, value: u64) {
+        let changed = self.value(&bit) ^ value;
+        if changed != 0 {
+            *self.toggles.entry(bit).or_default() += changed.count_ones() as u64;
+            self.values.insert(bit, value);
+        }
+    }
+
+    /// Drives an input port, one word of lanes per port bit. Takes effect on the next
+    /// [`eval`](Self::eval) or [`step`](Self::step).
+    pub fn set(&mut self, port: &str, lanes: &[u64]) -> Result<(), BatchSimError> {
+        let bits = match self.ports.get(port) {
+            Some((direction, bits)) if *direction != Direction::Output => bits.clone(),
+            _ => return Err(BatchSimError::UnknownPort(port.to_string())),
+        };
+        for (bit, value) in bits.into_iter().zip(lanes.iter().copied().chain(std::iter::repeat(0))) {
+            self.update(bit, value);
+        }
+        Ok(())
+    }
+
+    /// Lanes of every bit of a port, least significant bit first.
+    pub fn get(&self, port: &str) -> Option<Vec<u64>> {
+        self.ports.get(port).map(|(_, bits)| bits.iter().map(|bit| self.value(bit)).collect())
+    }
+
+    /// Settles the combinational logic.
+    pub fn eval(&mut self) {
+        for index in 0..self.nodes.len() {
+            let node = &self.nodes[index];
+            let inputs: Vec<u64> = node.inputs.iter().map(|bit| self.value(bit)).collect();
+            let (output, value) = (node.output, node.eval(&inputs));
+            self.update(output, value);
+        }
+    }
+
+    /// Settles the logic, clocks every register once and settles the logic again.
+    pub fn step(&mut self) {
+        self.eval();
+        let next: Vec<(Bit, u64)> = self.registers.iter().map(|(d, q)| (*q, self.value(d))).collect();
+        for (q, value) in next {
+            self.update(q, value);
+        }
+        self.eval();
+    }
+
+    /// Number of value changes of every bit so far, summed over the lanes. Bits missing from
+    /// the map never toggled.
+    pub fn toggle_counts(&self) -> &HashMap<Bit, u64> {
+        &self.toggles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, IdString, Netlist, Port};
+
+    #[test]
+    fn test_batch_adder() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/mult.json").unwrap()).unwrap();
+        let module = &netlist.modules["mult"];
+        let mut simulator = BatchSimulator::new(module).unwrap();
+        let width = |port: &str| module.ports[port].bits.len();
+        let inputs: Vec<&IdString> =
+            module.ports.iter().filter(|(_, port)| port.direction == Direction::Input).map(|(name, _)| name).collect();
+        let output = module.ports.iter().find(|(_, port)| port.direction == Direction::Output).unwrap().0;
+
+        // Lane n multiplies n by n + 1.
+        let operand = |offset: u64, bits: usize| -> Vec<u64> {
+            (0..bits)
+                .map(|bit| (0..LANES as u64).filter(|lane| (lane + offset) >> bit & 1 == 1).fold(0, |word, lane| word | 1 << lane))
+                .collect()
+        };
+        simulator.set(inputs[0], &operand(0, width(inputs[0]))).unwrap();
+        simulator.set(inputs[1], &operand(1, width(inputs[1]))).unwrap();
+        simulator.eval();
+        let product = simulator.get(output).unwrap();
+        for lane in 0..LANES as u64 {
+            let value = product.iter().enumerate().fold(0u64, |value, (bit, word)| value | (word >> lane & 1) << bit);
+            let mask = if width(output) >= 64 { u64::MAX } else { (1 << width(output)) - 1 };
+            assert_eq!(value, (lane * (lane + 1)) & mask);
+        }
+        assert!(!simulator.toggle_counts().is_empty());
+    }
+
+    #[test]
+    fn test_batch_step() {
+        let mut module = Module::new();
+        module.cells.insert(
+            "inv".into(),
+            Cell::new("$_NOT_").with_connection("A", Direction::Input, vec![Bit::Signal(2)]).with_connection(
+                "Y",
+                Direction::Output,
+                vec![Bit::Signal(3)],
+            ),
+        );
+        module.cells.insert(
+            "ff".into(),
+            Cell::new("$_DFF_P_").with_connection("D", Direction::Input, vec![Bit::Signal(3)]).with_connection(
+                "Q",
+                Direction::Output,
+                vec![Bit::Signal(2)],
+            ),
+        );
+        module.ports.insert("q".into(), Port::new(Direction::Output, vec![Bit::Signal(2)]));
+        let mut simulator = BatchSimulator::new(&module).unwrap();
+        for _ in 0..3 {
+            simulator.step();
+        }
+        assert_eq!(simulator.get("q").unwrap(), [u64::MAX]);
+        assert_eq!(simulator.toggle_counts()[&Bit::Signal(2)], 3 * LANES as u64);
+
+        module.cells["ff"].module = "$_NOT_".into();
+        module.cells["ff"].connections = IndexMap::from([("A".into(), vec![Bit::Signal(3)]), ("Y".into(), vec![Bit::Signal(2)])]);
+        assert!(matches!(BatchSimulator::new(&module), Err(BatchSimError::CombinationalLoop(_))));
+    }
+}
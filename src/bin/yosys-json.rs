@@ -0,0 +1,189 @@
+//! Quick inspection of Yosys JSON netlists from the command line.
+
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::process::ExitCode;
+
+use indexmap::IndexSet;
+use yosys_json_netlist::{IdString, Netlist};
+use yosys_json_netlist::dot::DotOptions;
+use yosys_json_netlist::hierarchy::Tops;
+use yosys_json_netlist::structural::EquivResult;
+
+const USAGE: &str = "usage: yosys-json <command> [arguments]
+
+commands:
+  stats <netlist> [--top <module>]...      print statistics like `yosys stat`
+  validate <netlist>                       list lint issues, failing if there are any
+  dot <netlist> <module>                   write a module as a Graphviz graph
+  flatten <netlist> [--top <module>]...    write the flattened top modules as JSON
+  diff <netlist> <netlist>                 compare modules up to renaming of cells and nets
+  grep <netlist> <text>                    list modules, ports, cells and nets whose name contains text
+  hierarchy <netlist>                      print the module hierarchy below the top modules
+
+A netlist of `-` is read from standard input.";
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn read(path: &str) -> Result<Netlist> {
+    let netlist = match path {
+        "-" => Netlist::from_reader(io::stdin().lock())?,
+        path => Netlist::from_reader(BufReader::new(File::open(path).map_err(|error| format!("{}: {}", path, error))?))?,
+    };
+    Ok(netlist)
+}
+
+/// Splits `--top <module>` options from the positional arguments.
+fn tops(args: &[String]) -> Result<(Vec<&str>, Tops)> {
+    let (mut positional, mut tops) = (Vec::new(), Vec::new());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => tops.push(args.next().ok_or("--top needs a module name")?.clone()),
+            arg => positional.push(arg),
+        }
+    }
+    Ok((positional, if tops.is_empty() { Tops::Auto } else { Tops::Named(tops) }))
+}
+
+fn hierarchy(netlist: &Netlist, out: &mut impl Write) -> Result<()> {
+    let graph = netlist.module_graph();
+    let mut stack: Vec<(&str, usize, usize)> = netlist.top_modules().into_iter().rev().map(|top| (top, 1, 0)).collect();
+    let mut path: Vec<&str> = Vec::new();
+    while let Some((module, count, depth)) = stack.pop() {
+        path.truncate(depth);
+        let recursive = path.contains(&module);
+        let suffix = match (count, recursive) {
+            (_, true) => " (recursive)".to_string(),
+            (1, _) => String::new(),
+            (count, _) => format!(" x{}", count),
+        };
+        writeln!(out, "{}{}{}", "  ".repeat(depth), module, suffix)?;
+        if !recursive {
+            path.push(module);
+            let children = graph.children.get(module).into_iter().flatten().rev();
+            stack.extend(children.map(|(child, count)| (child.as_str(), *count, depth + 1)));
+        }
+    }
+    Ok(())
+}
+
+fn run(args: &[String], out: &mut impl Write) -> Result<bool> {
+    let (command, args) = args.split_first().ok_or(USAGE)?;
+    let (positional, tops) = tops(args)?;
+    let arity = |count: usize| match positional.len() == count {
+        true => Ok(()),
+        false => Err(format!("{} takes {} arguments\n\n{}", command, count, USAGE)),
+    };
+    match command.as_str() {
+        "stats" => {
+            arity(1)?;
+            write!(out, "{}", read(positional[0])?.stats_for(&tops)?)?;
+        }
+        "validate" => {
+            arity(1)?;
+            let issues = read(positional[0])?.validate_tops(&tops)?;
+            for issue in issues.iter() {
+                writeln!(out, "{}", issue)?;
+            }
+            return Ok(issues.is_empty());
+        }
+        "dot" => {
+            arity(2)?;
+            let netlist = read(positional[0])?;
+            let module = netlist.modules.get(positional[1]).ok_or_else(|| format!("no module {}", positional[1]))?;
+            write!(out, "{}", module.to_dot(&DotOptions::default()))?;
+        }
+        "flatten" => {
+            arity(1)?;
+            read(positional[0])?.flatten_tops(&tops)?.to_writer(&mut *out)?;
+            writeln!(out)?;
+        }
+        "diff" => {
+            arity(2)?;
+            let (left, right) = (read(positional[0])?, read(positional[1])?);
+            let mut same = true;
+            let names: IndexSet<&IdString> = left.modules.keys().chain(right.modules.keys()).collect();
+            for name in names {
+                let difference = match (left.modules.get(name), right.modules.get(name)) {
+                    (Some(_), None) => Some(format!("only in {}", positional[0])),
+                    (None, Some(_)) => Some(format!("only in {}", positional[1])),
+                    (Some(a), Some(b)) => match a.structurally_equivalent(b) {
+                        EquivResult::Equivalent(_) => None,
+                        EquivResult::Different(difference) => Some(difference.to_string()),
+                    },
+                    (None, None) => None,
+                };
+                if let Some(difference) = difference {
+                    writeln!(out, "{}: {}", name, difference)?;
+                    same = false;
+                }
+            }
+            return Ok(same);
+        }
+        "grep" => {
+            arity(2)?;
+            let (netlist, text) = (read(positional[0])?, positional[1]);
+            let mut found = false;
+            for (name, module) in netlist.modules.iter() {
+                let mut hits: Vec<(&str, &str, String)> = Vec::new();
+                hits.extend(module.ports.iter().map(|(port, p)| ("port", port.as_str(), format!("{:?}", p.direction).to_lowercase())));
+                hits.extend(module.cells.iter().map(|(cell, c)| ("cell", cell.as_str(), c.module.to_string())));
+                hits.extend(module.nets.iter().map(|(net, n)| ("net", net.as_str(), format!("{} bits", n.bits.len()))));
+                if name.contains(text) {
+                    writeln!(out, "module {}", name)?;
+                    found = true;
+                }
+                for (kind, item, detail) in hits.into_iter().filter(|(_, item, _)| item.contains(text)) {
+                    writeln!(out, "{} {}.{} ({})", kind, name, item, detail)?;
+                    found = true;
+                }
+            }
+            return Ok(found);
+        }
+        "hierarchy" => {
+            arity(1)?;
+            hierarchy(&read(positional[0])?, out)?;
+        }
+        "help" | "--help" | "-h" => writeln!(out, "{}", USAGE)?,
+        command => return Err(format!("unknown command {}\n\n{}", command, USAGE).into()),
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let result = run(&args, &mut out).and_then(|success| Ok(out.flush().map(|_| success)?));
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("yosys-json: {}", error);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(args: &[&str]) -> (bool, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut out = Vec::new();
+        let success = run(&args, &mut out).unwrap();
+        (success, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_commands() {
+        let (success, stats) = output(&["stats", "testdata/modules.json", "--top", "test_and"]);
+        assert!(success && stats.contains("Number of cells:"));
+        assert_eq!(output(&["diff", "testdata/modules.json", "testdata/modules.json"]), (true, String::new()));
+        let (found, hits) = output(&["grep", "testdata/modules.json", "test_or"]);
+        assert!(found && hits.starts_with("module test_or\n"));
+        assert!(output(&["hierarchy", "testdata/adder.json"]).1.lines().count() > 0);
+        assert!(run(&["stats".to_string()], &mut Vec::new()).is_err());
+    }
+}
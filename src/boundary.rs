@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::builder::{PortBuilder, TRUE};
+use crate::progress::pass_span;
+use crate::{Bit, Cell, Direction, Module, Net, Netlist, Port};
+
+/// Test clock input added to the module.
+pub const TCK_PORT: &str = "tck";
+/// Test mode select input added to the module.
+pub const TMS_PORT: &str = "tms";
+/// Test data input added to the module, feeding the first boundary cell.
+pub const TDI_PORT: &str = "tdi";
+/// Test data output added to the module, driven by the TAP controller.
+pub const TDO_PORT: &str = "tdo";
+
+/// Inputs and outputs of the TAP controller instance, which decodes the TAP state machine.
+const TAP_INPUTS: &[&str] = &["TCK", "TMS", "TDI", "BSR_TDO"];
+const TAP_OUTPUTS: &[&str] = &["TDO", "SHIFT_DR", "CAPTURE_DR", "UPDATE_DR", "MODE"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoundaryScanError {
+    UnknownModule(String),
+    PortExists(String),
+    /// The TAP controller module exists but lacks a port the hookup needs.
+    TapPort {
+        tap: String,
+        port: String,
+    },
+    /// A cell port on an input whose direction is unknown, so it may be a load or a driver.
+    UnknownDirection {
+        cell: String,
+        port: String,
+    },
+}
+
+impl fmt::Display for BoundaryScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::PortExists(port) => write!(f, "port {:?} already exists", port),
+            Self::TapPort { tap, port } => write!(f, "TAP controller {:?} has no port {:?}", tap, port),
+            Self::UnknownDirection { cell, port } => write!(f, "unknown direction of port {:?} of cell {:?}", port, cell),
+        }
+    }
+}
+
+impl std::error::Error for BoundaryScanError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundaryScanOptions {
+    /// Module of the TAP controller, added as a blackbox when the netlist has none.
+    pub tap: String,
+    /// Ports left without boundary cells, such as clocks.
+    pub exclude: Vec<String>,
+}
+
+impl Default for BoundaryScanOptions {
+    fn default() -> Self {
+        Self { tap: "TAP_CONTROLLER".to_string(), exclude: Vec::new() }
+    }
+}
+
+/// One cell of the boundary register, on bit `index` of `port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundaryCell {
+    pub port: String,
+    pub index: usize,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundaryScanReport {
+    pub module: String,
+    /// Boundary cells in shift order, from TDI to TDO.
+    pub cells: Vec<BoundaryCell>,
+    /// Inout ports, which are left without boundary cells.
+    pub skipped: Vec<String>,
+    /// Every port of the scanned module, with its direction and width.
+    pub ports: Vec<(String, Direction, usize)>,
+}
+
+impl BoundaryScanReport {
+    /// A BSDL-like description: the ports, the TAP pins and the boundary register, numbered
+    /// from 0 next to TDO as BSDL does.
+    pub fn to_bsdl(&self) -> String {
+        let mut bsdl = String::new();
+        let entity = &self.module;
+        writeln!(bsdl, "entity {} is", entity).unwrap();
+        let ports: Vec<String> = (self.ports.iter())
+            .map(|(name, direction, width)| {
+                let mode = match direction {
+                    Direction::Input => "in",
+                    Direction::Output => "out",
+                    Direction::InOut => "inout",
+                };
+                match width {
+                    1 => format!("{} : {} bit", name, mode),
+                    width => format!("{} : {} bit_vector(0 to {})", name, mode, width - 1),
+                }
+            })
+            .collect();
+        writeln!(bsdl, "  port ({});", ports.join("; ")).unwrap();
+        for (attribute, port) in
+            [("TAP_SCAN_CLOCK", TCK_PORT), ("TAP_SCAN_MODE", TMS_PORT), ("TAP_SCAN_IN", TDI_PORT), ("TAP_SCAN_OUT", TDO_PORT)]
+        {
+            writeln!(bsdl, "  attribute {} of {} : signal is true;", attribute, port).unwrap();
+        }
+        writeln!(bsdl, "  attribute BOUNDARY_LENGTH of {} : entity is {};", entity, self.cells.len()).unwrap();
+        writeln!(bsdl, "  attribute BOUNDARY_REGISTER of {} : entity is", entity).unwrap();
+        let last = self.cells.len();
+        for (number, cell) in self.cells.iter().rev().enumerate() {
+            let function = if cell.direction == Direction::Input { "input" } else { "output2" };
+            let separator = if number + 1 == last { "\";" } else { ",\" &" };
+            writeln!(bsdl, "    \"{} (BC_1, {}({}), {}, X){}", number, cell.port, cell.index, function, separator).unwrap();
+        }
+        writeln!(bsdl, "end {};", entity).unwrap();
+        bsdl
+    }
+}
+
+fn add_cell(module: &mut Module, cell_type: &str, connections: &[(&str, Direction, Bit)]) {
+    let cell = (connections.iter())
+        .fold(Cell::new(cell_type), |cell, (port, direction, bit)| cell.with_connection(port, direction.clone(), vec![*bit]));
+    let mut name = format!("$bs${}", module.cells.len());
+    while module.cells.contains_key(&name) {
+        name.push('_');
+    }
+    module.cells.insert(name.into(), cell);
+}
+
+impl Netlist {
+    /// Inserts an IEEE 1149.1 style boundary register around the ports of `module`: on every
+    /// bit a capture and shift flop, an update flop and a multiplexer that hands the pin to
+    /// the update flop while the TAP controller asserts `MODE`. Adds the TAP pins as ports and
+    /// an instance of the TAP controller, which decodes TMS and drives TDO, reading the end of
+    /// the chain on `BSR_TDO`. Inouts are skipped.
+    pub fn insert_boundary_scan(&mut self, module: &str, options: &BoundaryScanOptions) -> Result<BoundaryScanReport, BoundaryScanError> {
+        pass_span!("insert_boundary_scan", module);
+        let target = self.modules.get(module).ok_or_else(|| BoundaryScanError::UnknownModule(module.to_string()))?;
+        for port in [TCK_PORT, TMS_PORT, TDI_PORT, TDO_PORT] {
+            if target.ports.contains_key(port) || target.nets.contains_key(port) {
+                return Err(BoundaryScanError::PortExists(port.to_string()));
+            }
+        }
+        if let Some(tap) = self.modules.get(&options.tap)
+            && let Some(port) = TAP_INPUTS.iter().chain(TAP_OUTPUTS).find(|port| !tap.ports.contains_key(**port))
+        {
+            return Err(BoundaryScanError::TapPort { tap: options.tap.clone(), port: port.to_string() });
+        }
+        let inputs: HashSet<Bit> = (target.ports.iter())
+            .filter(|(name, port)| port.direction == Direction::Input && !options.exclude.iter().any(|excluded| *name == excluded))
+            .flat_map(|(_, port)| port.bits.iter().copied())
+            .collect();
+        if let Some((cell, port)) = target.undirected_port(&inputs) {
+            return Err(BoundaryScanError::UnknownDirection { cell, port });
+        }
+        if !self.modules.contains_key(&options.tap) {
+            let mut builder = Module::builder();
+            builder.attribute("blackbox", TRUE);
+            for (ports, direction) in [(TAP_INPUTS, Direction::Input), (TAP_OUTPUTS, Direction::Output)] {
+                ports.iter().for_each(|port| _ = builder.port(PortBuilder::new(port, direction.clone(), 1)).unwrap());
+            }
+            self.modules.insert(options.tap.as_str().into(), builder.build());
+        }
+
+        let target = &mut self.modules[module];
+        let mut report = BoundaryScanReport { module: module.to_string(), cells: Vec::new(), skipped: Vec::new(), ports: Vec::new() };
+        let [tck, tms, tdi, tdo, chain_out, shift, capture, update, mode, clock_dr] = target.alloc_bits(10).try_into().unwrap();
+        let mut scan = tdi;
+        let ports: Vec<String> =
+            target.ports.keys().filter(|port| !options.exclude.iter().any(|excluded| *port == excluded)).map(String::from).collect();
+        for name in ports {
+            let port = target.ports[&name].clone();
+            if port.direction == Direction::InOut {
+                report.skipped.push(name);
+                continue;
+            }
+            let fresh_bits = target.alloc_bits(port.bits.len());
+            let mut core = Vec::new();
+            for (index, (bit, new)) in port.bits.iter().zip(fresh_bits).enumerate() {
+                // Input cells sit between the pad and the loads, output cells between the
+                // driver and the pad, which becomes a new bit.
+                let (system, driven) = (*bit, new);
+                if port.direction == Direction::Input {
+                    target.move_loads(*bit, new);
+                    core.push(new);
+                } else {
+                    target.ports[&name].bits[index] = new;
+                    core.push(*bit);
+                }
+                let [d, captured, updated] = target.alloc_bits(3).try_into().unwrap();
+                add_cell(
+                    target,
+                    "$_MUX_",
+                    &[
+                        ("A", Direction::Input, system),
+                        ("B", Direction::Input, scan),
+                        ("S", Direction::Input, shift),
+                        ("Y", Direction::Output, d),
+                    ],
+                );
+                add_cell(
+                    target,
+                    "$_DFFE_PP_",
+                    &[
+                        ("C", Direction::Input, tck),
+                        ("D", Direction::Input, d),
+                        ("E", Direction::Input, clock_dr),
+                        ("Q", Direction::Output, captured),
+                    ],
+                );
+                add_cell(
+                    target,
+                    "$_DFFE_NP_",
+                    &[
+                        ("C", Direction::Input, tck),
+                        ("D", Direction::Input, captured),
+                        ("E", Direction::Input, update),
+                        ("Q", Direction::Output, updated),
+                    ],
+                );
+                add_cell(
+                    target,
+                    "$_MUX_",
+                    &[
+                        ("A", Direction::Input, system),
+                        ("B", Direction::Input, updated),
+                        ("S", Direction::Input, mode),
+                        ("Y", Direction::Output, driven),
+                    ],
+                );
+                scan = captured;
+                report.cells.push(BoundaryCell { port: name.clone(), index: index + port.offset, direction: port.direction.clone() });
+            }
+            if let Some(net) = target.nets.get_mut(&name) {
+                net.bits = target.ports[&name].bits.clone();
+            }
+            let mut net = Net::new(core);
+            net.hide_name = true;
+            target.nets.insert(format!("$bs${}$core", name).into(), net);
+        }
+        add_cell(target, "$_OR_", &[("A", Direction::Input, capture), ("B", Direction::Input, shift), ("Y", Direction::Output, clock_dr)]);
+        add_cell(target, "$_BUF_", &[("A", Direction::Input, scan), ("Y", Direction::Output, chain_out)]);
+
+        for (name, direction, bit) in [
+            (TCK_PORT, Direction::Input, tck),
+            (TMS_PORT, Direction::Input, tms),
+            (TDI_PORT, Direction::Input, tdi),
+            (TDO_PORT, Direction::Output, tdo),
+        ] {
+            target.ports.insert(name.into(), Port::new(direction, vec![bit]));
+            target.nets.insert(name.into(), Net::new(vec![bit]));
+        }
+        let hookup = [
+            ("TCK", tck),
+            ("TMS", tms),
+            ("TDI", tdi),
+            ("BSR_TDO", chain_out),
+            ("TDO", tdo),
+            ("SHIFT_DR", shift),
+            ("CAPTURE_DR", capture),
+            ("UPDATE_DR", update),
+            ("MODE", mode),
+        ];
+        let mut tap = Cell::new(&options.tap);
+        for (port, bit) in hookup {
+            let direction = if TAP_INPUTS.contains(&port) { Direction::Input } else { Direction::Output };
+            tap = tap.with_connection(port, direction, vec![bit]);
+        }
+        target.cells.insert("$bs$tap".into(), tap);
+        for (name, bit) in [("shift_dr", shift), ("capture_dr", capture), ("update_dr", update), ("mode", mode)] {
+            let mut net = Net::new(vec![bit]);
+            net.hide_name = true;
+            target.nets.insert(format!("$bs${}", name).into(), net);
+        }
+        report.ports = target.ports.iter().map(|(name, port)| (name.to_string(), port.direction.clone(), port.bits.len())).collect();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, NetlistBuilder};
+    use crate::validate::LintIssue;
+
+    #[test]
+    fn test_insert_boundary_scan() {
+        let mut builder = Module::builder();
+        let [clk, a] = [("clk", 1), ("a", 2)].map(|(name, width)| builder.input(name, width).unwrap());
+        let y = builder.output("y", 1).unwrap();
+        builder.cell("and", CellBuilder::new("$_AND_").input("A", vec![a[0]]).input("B", vec![a[1]]).output("Y", y.clone())).unwrap();
+        builder.cell("ff", CellBuilder::new("$_DFF_P_").input("C", clk).input("D", y.clone()).output("Q", vec![Bit::X])).unwrap();
+        let mut netlist = NetlistBuilder::new("test").top("top", builder).unwrap().build();
+
+        let options = BoundaryScanOptions { exclude: vec!["clk".to_string()], ..BoundaryScanOptions::default() };
+        let report = netlist.insert_boundary_scan("top", &options).unwrap();
+        assert_eq!(report.cells.iter().map(|cell| (cell.port.as_str(), cell.index)).collect::<Vec<_>>(), [("a", 0), ("a", 1), ("y", 0)]);
+        let top = &netlist.modules["top"];
+        assert_eq!(top.cells.len(), 2 + 3 * 4 + 3);
+        assert!(top.cells["and"].connections["A"] != top.ports["a"].bits[..1]);
+        assert!(top.ports["y"].bits != y && top.cells["ff"].connections["D"] == y);
+        assert!(netlist.modules.contains_key("TAP_CONTROLLER"));
+        let issues = netlist.validate();
+        assert!(issues.iter().all(
+            |issue| !matches!(issue, LintIssue::MultipleDrivers { module, .. } | LintIssue::UndrivenLoad { module, .. } if module == "top")
+        ));
+
+        let bsdl = report.to_bsdl();
+        assert!(bsdl.contains("attribute BOUNDARY_LENGTH of top : entity is 3;"));
+        assert!(bsdl.contains("\"0 (BC_1, y(0), output2, X),\" &"));
+        assert!(bsdl.contains("\"2 (BC_1, a(0), input, X)\";"));
+        assert_eq!(netlist.insert_boundary_scan("top", &options), Err(BoundaryScanError::PortExists(TCK_PORT.to_string())));
+    }
+}
@@ -0,0 +1,523 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+
+use crate::batch::REGISTERS;
+use crate::gates::function;
+use crate::memory::{MEMORY_CELLS, parameter};
+use crate::{Bit, Cell, Direction, Module};
+
+/// How memory cells are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryModel {
+    /// One state per word, read and written through multiplexers over the addresses.
+    #[default]
+    Expand,
+    /// One array state per memory, read and written with array operations, so the solver
+    /// reasons about the contents as an uninterpreted array. Keeps problems with large RAMs
+    /// small.
+    Array,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BtorOptions {
+    pub memories: MemoryModel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BtorError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    /// A bit on a combinational loop, named by its net.
+    CombinationalLoop(String),
+}
+
+impl fmt::Display for BtorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot export cell {} of type {}", cell, module),
+            Self::CombinationalLoop(bit) => write!(f, "combinational loop through {}", bit),
+        }
+    }
+}
+
+impl std::error::Error for BtorError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Sort {
+    Bits(usize),
+    Array(usize, usize),
+}
+
+enum Driver<'a> {
+    Function(&'a str, Vec<Bit>, Vec<bool>),
+    /// Memory, read port and data bit.
+    Read(usize, usize, usize),
+}
+
+/// A memory cell with asynchronous read ports, written on the implicit clock.
+struct Mem<'a> {
+    cell: &'a Cell,
+    width: usize,
+    size: usize,
+    abits: usize,
+    offset: usize,
+    /// The array state, or the state of every word.
+    states: Vec<usize>,
+    /// Data node of every read port evaluated so far.
+    reads: HashMap<usize, usize>,
+}
+
+impl Mem<'_> {
+    fn port(&self, name: &str, port: usize, width: usize) -> Vec<Bit> {
+        self.cell.connections.get(name).map(|bits| bits.iter().skip(port * width).take(width).copied().collect()).unwrap_or_default()
+    }
+}
+
+struct Writer<'a> {
+    module: &'a Module,
+    options: &'a BtorOptions,
+    text: String,
+    next: usize,
+    sorts: HashMap<Sort, usize>,
+    nodes: HashMap<Bit, usize>,
+    drivers: HashMap<Bit, Driver<'a>>,
+    visiting: HashSet<Bit>,
+    memories: Vec<Mem<'a>>,
+}
+
+/// BTOR2 symbols cannot contain whitespace.
+fn symbol(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+impl<'a> Writer<'a> {
+    fn line(&mut self, line: impl fmt::Display) -> usize {
+        let id = self.next;
+        self.next += 1;
+        writeln!(self.text, "{} {}", id, line).unwrap();
+        id
+    }
+
+    fn sort(&mut self, sort: Sort) -> usize {
+        if let Some(id) = self.sorts.get(&sort) {
+            return *id;
+        }
+        let id = match sort {
+            Sort::Bits(width) => self.line(format_args!("sort bitvec {}", width)),
+            Sort::Array(index, element) => {
+                let (index, element) = (self.sort(Sort::Bits(index)), self.sort(Sort::Bits(element)));
+                self.line(format_args!("sort array {} {}", index, element))
+            }
+        };
+        self.sorts.insert(sort, id);
+        id
+    }
+
+    fn op(&mut self, op: &str, width: usize, operands: &[usize]) -> usize {
+        let sort = self.sort(Sort::Bits(width));
+        let operands: Vec<String> = operands.iter().map(usize::to_string).collect();
+        self.line(format_args!("{} {} {}", op, sort, operands.join(" ")))
+    }
+
+    /// A constant from its bits, least significant first.
+    fn constant(&mut self, bits: &[bool]) -> usize {
+        let sort = self.sort(Sort::Bits(bits.len()));
+        let digits: String = bits.iter().rev().map(|bit| if *bit { '1' } else { '0' }).collect();
+        self.line(format_args!("const {} {}", sort, digits))
+    }
+
+    fn number(&mut self, value: usize, width: usize) -> usize {
+        let sort = self.sort(Sort::Bits(width));
+        self.line(format_args!("constd {} {}", sort, value))
+    }
+
+    /// Concatenates single bit nodes, least significant first.
+    fn vector(&mut self, bits: &[usize]) -> usize {
+        let mut vector = bits[0];
+        for (index, bit) in bits.iter().enumerate().skip(1) {
+            vector = self.op("concat", index + 1, &[*bit, vector]);
+        }
+        vector
+    }
+
+    fn vector_of(&mut self, bits: &[Bit]) -> Result<usize, BtorError> {
+        let nodes = bits.iter().map(|bit| self.node(*bit)).collect::<Result<Vec<_>, _>>()?;
+        Ok(self.vector(&nodes))
+    }
+
+    fn slice(&mut self, vector: usize, width: usize, bit: usize) -> usize {
+        match width {
+            1 => vector,
+            _ => {
+                let sort = self.sort(Sort::Bits(1));
+                self.line(format_args!("slice {} {} {} {}", sort, vector, bit, bit))
+            }
+        }
+    }
+
+    fn input(&mut self, width: usize, name: &str) -> usize {
+        let sort = self.sort(Sort::Bits(width));
+        self.line(format_args!("input {} {}", sort, symbol(name)))
+    }
+
+    fn state(&mut self, sort: Sort, name: &str) -> usize {
+        let sort = self.sort(sort);
+        self.line(format_args!("state {} {}", sort, symbol(name)))
+    }
+
+    /// Lowers a truth table to a tree of `ite` nodes.
+    fn table(&mut self, inputs: &[usize], table: &[bool]) -> Result<usize, BtorError> {
+        let Some((select, rest)) = inputs.split_last() else {
+            return self.node(if table[0] { Bit::_1 } else { Bit::_0 });
+        };
+        let (low, high) = table.split_at(table.len() / 2);
+        let (low, high) = (self.table(rest, low)?, self.table(rest, high)?);
+        Ok(match low == high {
+            true => low,
+            false => self.op("ite", 1, &[*select, high, low]),
+        })
+    }
+
+    fn function(&mut self, module: &str, inputs: &[usize], table: &[bool]) -> Result<usize, BtorError> {
+        let binary = match module {
+            "$_BUF_" => return Ok(inputs[0]),
+            "$_NOT_" => return Ok(self.op("not", 1, inputs)),
+            "$_MUX_" => return Ok(self.op("ite", 1, &[inputs[2], inputs[1], inputs[0]])),
+            "$_ANDNOT_" | "$_ORNOT_" => {
+                let inverted = self.op("not", 1, &inputs[1..]);
+                return Ok(self.op(if module == "$_ANDNOT_" { "and" } else { "or" }, 1, &[inputs[0], inverted]));
+            }
+            "$_AND_" => "and",
+            "$_OR_" => "or",
+            "$_XOR_" => "xor",
+            "$_NAND_" => "nand",
+            "$_NOR_" => "nor",
+            "$_XNOR_" => "xnor",
+            _ => return self.table(inputs, table),
+        };
+        Ok(self.op(binary, 1, inputs))
+    }
+
+    fn node(&mut self, bit: Bit) -> Result<usize, BtorError> {
+        if let Some(node) = self.nodes.get(&bit) {
+            return Ok(*node);
+        }
+        let node = match (bit, self.drivers.remove(&bit)) {
+            (Bit::_1, _) => {
+                let sort = self.sort(Sort::Bits(1));
+                self.line(format_args!("one {}", sort))
+            }
+            (Bit::Signal(_), None) => self.input(1, &self.module.bit_name(&bit)),
+            (Bit::Signal(_), Some(driver)) => {
+                if !self.visiting.insert(bit) {
+                    return Err(BtorError::CombinationalLoop(self.module.bit_name(&bit)));
+                }
+                let node = match driver {
+                    Driver::Function(module, inputs, table) => {
+                        let inputs = inputs.iter().map(|input| self.node(*input)).collect::<Result<Vec<_>, _>>()?;
+                        self.function(module, &inputs, &table)?
+                    }
+                    Driver::Read(memory, port, bit) => {
+                        let data = self.read(memory, port)?;
+                        self.slice(data, self.memories[memory].width, bit)
+                    }
+                };
+                self.visiting.remove(&bit);
+                node
+            }
+            // Undefined and high impedance bits are modelled as 0.
+            _ => {
+                let sort = self.sort(Sort::Bits(1));
+                self.line(format_args!("zero {}", sort))
+            }
+        };
+        self.nodes.insert(bit, node);
+        Ok(node)
+    }
+
+    /// The address of a memory port, relative to the first word.
+    fn address(&mut self, memory: usize, port: &str, index: usize) -> Result<usize, BtorError> {
+        let Mem { abits, offset, .. } = self.memories[memory];
+        let bits = self.memories[memory].port(port, index, abits);
+        let address = self.vector_of(&bits)?;
+        Ok(match offset {
+            0 => address,
+            offset => {
+                let offset = self.number(offset, abits);
+                self.op("sub", abits, &[address, offset])
+            }
+        })
+    }
+
+    /// Reads `address` from the array or words given by `contents`.
+    fn lookup(&mut self, memory: usize, contents: &[usize], address: usize) -> usize {
+        let Mem { width, abits, .. } = self.memories[memory];
+        if self.options.memories == MemoryModel::Array {
+            return self.op("read", width, &[contents[0], address]);
+        }
+        let mut data = contents[0];
+        for (index, word) in contents.iter().enumerate().skip(1) {
+            let constant = self.number(index, abits);
+            let selected = self.op("eq", 1, &[address, constant]);
+            data = self.op("ite", width, &[selected, *word, data]);
+        }
+        data
+    }
+
+    fn read(&mut self, memory: usize, port: usize) -> Result<usize, BtorError> {
+        if let Some(data) = self.memories[memory].reads.get(&port) {
+            return Ok(*data);
+        }
+        let address = self.address(memory, "RD_ADDR", port)?;
+        let states = self.memories[memory].states.clone();
+        let data = self.lookup(memory, &states, address);
+        self.memories[memory].reads.insert(port, data);
+        Ok(data)
+    }
+
+    fn add_memory(&mut self, name: &str, cell: &'a Cell) -> Result<(), BtorError> {
+        let unsupported = || BtorError::Unsupported { cell: name.to_string(), module: cell.module.to_string() };
+        let get = |key: &str| parameter(cell, key).ok_or_else(unsupported);
+        let (width, size, abits, read_ports) = (get("WIDTH")?, get("SIZE")?, get("ABITS")?, get("RD_PORTS")?);
+        if parameter(cell, "RD_CLK_ENABLE").unwrap_or(0) != 0 || width == 0 || size == 0 || abits == 0 {
+            return Err(unsupported());
+        }
+        let contents = cell.memory_contents().ok_or_else(unsupported)?;
+        let states = match self.options.memories {
+            MemoryModel::Array => {
+                let state = self.state(Sort::Array(abits, width), name);
+                let defined: Vec<(usize, Vec<bool>)> = (0..size)
+                    .filter_map(|address| Some((address, contents.bits(address).into_iter().collect::<Option<Vec<bool>>>()?)))
+                    .collect();
+                if !defined.is_empty() {
+                    let sort = self.sort(Sort::Array(abits, width));
+                    let zero = self.constant(&vec![false; width]);
+                    let mut initial = self.state(Sort::Array(abits, width), &format!("{}#init", name));
+                    self.line(format_args!("init {} {} {}", sort, initial, zero));
+                    self.line(format_args!("next {} {} {}", sort, initial, initial));
+                    for (address, word) in defined.into_iter().filter(|(_, word)| word.contains(&true)) {
+                        let (address, word) = (self.number(address, abits), self.constant(&word));
+                        initial = self.op_array("write", abits, width, &[initial, address, word]);
+                    }
+                    self.line(format_args!("init {} {} {}", sort, state, initial));
+                }
+                vec![state]
+            }
+            MemoryModel::Expand => {
+                let sort = self.sort(Sort::Bits(width));
+                let mut states = Vec::new();
+                for address in 0..size {
+                    let state = self.state(Sort::Bits(width), &format!("{}[{}]", name, address));
+                    if let Some(word) = contents.bits(address).into_iter().collect::<Option<Vec<bool>>>() {
+                        let word = self.constant(&word);
+                        self.line(format_args!("init {} {} {}", sort, state, word));
+                    }
+                    states.push(state);
+                }
+                states
+            }
+        };
+        let memory = self.memories.len();
+        for port in 0..read_ports {
+            let data = cell.connections.get("RD_DATA").map(|bits| bits.iter().skip(port * width).take(width)).into_iter().flatten();
+            for (bit, data) in data.enumerate() {
+                self.drivers.insert(*data, Driver::Read(memory, port, bit));
+            }
+        }
+        self.memories.push(Mem { cell, width, size, abits, offset: parameter(cell, "OFFSET").unwrap_or(0), states, reads: HashMap::new() });
+        Ok(())
+    }
+
+    fn op_array(&mut self, op: &str, index: usize, element: usize, operands: &[usize]) -> usize {
+        let sort = self.sort(Sort::Array(index, element));
+        let operands: Vec<String> = operands.iter().map(usize::to_string).collect();
+        self.line(format_args!("{} {} {}", op, sort, operands.join(" ")))
+    }
+
+    /// Applies the write ports in order, later ports winning, and sets the next contents.
+    fn write_memory(&mut self, memory: usize) -> Result<(), BtorError> {
+        let Mem { cell, width, size, abits, .. } = self.memories[memory];
+        let mut contents = self.memories[memory].states.clone();
+        for port in 0..parameter(cell, "WR_PORTS").unwrap_or(0) {
+            let address = self.address(memory, "WR_ADDR", port)?;
+            let data = self.vector_of(&self.memories[memory].port("WR_DATA", port, width))?;
+            let enable = self.vector_of(&self.memories[memory].port("WR_EN", port, width))?;
+            let disabled = self.op("not", width, &[enable]);
+            let written = self.op("and", width, &[data, enable]);
+            match self.options.memories {
+                MemoryModel::Array => {
+                    let old = self.lookup(memory, &contents, address);
+                    let kept = self.op("and", width, &[old, disabled]);
+                    let merged = self.op("or", width, &[written, kept]);
+                    contents[0] = self.op_array("write", abits, width, &[contents[0], address, merged]);
+                }
+                MemoryModel::Expand => {
+                    for (index, word) in contents.iter_mut().enumerate().take(size) {
+                        let constant = self.number(index, abits);
+                        let selected = self.op("eq", 1, &[address, constant]);
+                        let kept = self.op("and", width, &[*word, disabled]);
+                        let merged = self.op("or", width, &[written, kept]);
+                        *word = self.op("ite", width, &[selected, merged, *word]);
+                    }
+                }
+            }
+        }
+        let sort = match self.options.memories {
+            MemoryModel::Array => self.sort(Sort::Array(abits, width)),
+            MemoryModel::Expand => self.sort(Sort::Bits(width)),
+        };
+        for (state, next) in self.memories[memory].states.clone().into_iter().zip(contents) {
+            self.line(format_args!("next {} {} {}", sort, state, next));
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    /// Writes the module as a BTOR2 model checking problem. Gates, LUTs, registers on one
+    /// implicit clock and memories with asynchronous read ports are supported. `$assert`
+    /// cells become bad state properties and `$assume` cells constraints.
+    pub fn to_btor(&self, options: &BtorOptions) -> Result<String, BtorError> {
+        let mut writer = Writer {
+            module: self,
+            options,
+            text: String::new(),
+            next: 1,
+            sorts: HashMap::new(),
+            nodes: HashMap::new(),
+            drivers: HashMap::new(),
+            visiting: HashSet::new(),
+            memories: Vec::new(),
+        };
+
+        for (name, port) in self.ports.iter().filter(|(_, port)| port.direction == Direction::Input && !port.bits.is_empty()) {
+            let input = writer.input(port.bits.len(), name);
+            for (index, bit) in port.bits.iter().enumerate() {
+                let node = writer.slice(input, port.bits.len(), index);
+                writer.nodes.entry(*bit).or_insert(node);
+            }
+        }
+        let initial = self.initial_values();
+        let mut registers = Vec::new();
+        let mut properties = Vec::new();
+        for (name, cell) in self.cells.iter() {
+            if let Some((inputs, output, table)) = function(cell) {
+                writer.drivers.insert(output, Driver::Function(&cell.module, inputs, table));
+            } else if let Some((_, d, q)) = REGISTERS.iter().find(|(module, _, _)| *module == cell.module)
+                && let (Some(d), Some(q)) = (cell.connections.get(*d), cell.connections.get(*q))
+            {
+                let sort = writer.sort(Sort::Bits(1));
+                for (d, q) in d.iter().zip(q) {
+                    let state = writer.state(Sort::Bits(1), &self.bit_name(q));
+                    if let Some(value) = initial.get(q) {
+                        let value = writer.node(if *value { Bit::_1 } else { Bit::_0 })?;
+                        writer.line(format_args!("init {} {} {}", sort, state, value));
+                    }
+                    writer.nodes.insert(*q, state);
+                    registers.push((*d, state));
+                }
+            } else if MEMORY_CELLS.contains(&cell.module.as_str()) {
+                writer.add_memory(name, cell)?;
+            } else if cell.module == "$assert" || cell.module == "$assume" {
+                properties.push(cell);
+            } else {
+                return Err(BtorError::Unsupported { cell: name.to_string(), module: cell.module.to_string() });
+            }
+        }
+
+        let sort = writer.sort(Sort::Bits(1));
+        for (d, state) in registers {
+            let next = writer.node(d)?;
+            writer.line(format_args!("next {} {} {}", sort, state, next));
+        }
+        for memory in 0..writer.memories.len() {
+            writer.write_memory(memory)?;
+        }
+        for cell in properties {
+            let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied().unwrap_or(Bit::_1);
+            let (condition, enable) = (writer.node(bit("A"))?, writer.node(bit("EN"))?);
+            let violated = writer.op("not", 1, &[condition]);
+            match cell.module.as_str() {
+                "$assert" => {
+                    let bad = writer.op("and", 1, &[enable, violated]);
+                    writer.line(format_args!("bad {}", bad));
+                }
+                _ => {
+                    let disabled = writer.op("not", 1, &[enable]);
+                    let holds = writer.op("or", 1, &[disabled, condition]);
+                    writer.line(format_args!("constraint {}", holds));
+                }
+            }
+        }
+        for (name, port) in self.ports.iter().filter(|(_, port)| port.direction == Direction::Output && !port.bits.is_empty()) {
+            let output = writer.vector_of(&port.bits)?;
+            writer.line(format_args!("output {} {}", output, symbol(name)));
+        }
+        Ok(writer.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Port;
+
+    fn memory_module() -> Module {
+        let bits = |bits: &[u64]| bits.iter().map(|bit| Bit::Signal(*bit)).collect::<Vec<_>>();
+        let mut module = Module::new();
+        module.ports.insert("addr".into(), Port::new(Direction::Input, bits(&[2, 3])));
+        module.ports.insert("data".into(), Port::new(Direction::Input, bits(&[4, 5])));
+        module.ports.insert("we".into(), Port::new(Direction::Input, bits(&[6])));
+        module.ports.insert("q".into(), Port::new(Direction::Output, bits(&[7, 8])));
+        let ram = Cell::new("$mem_v2")
+            .with_parameter("WIDTH", 2)
+            .with_parameter("SIZE", 4)
+            .with_parameter("ABITS", 2)
+            .with_parameter("OFFSET", 0)
+            .with_parameter("RD_PORTS", 1)
+            .with_parameter("WR_PORTS", 1)
+            .with_parameter("RD_CLK_ENABLE", "0")
+            .with_parameter("INIT", "xxxxxx01")
+            .with_connection("RD_ADDR", Direction::Input, bits(&[2, 3]))
+            .with_connection("RD_DATA", Direction::Output, bits(&[7, 8]))
+            .with_connection("WR_ADDR", Direction::Input, bits(&[2, 3]))
+            .with_connection("WR_DATA", Direction::Input, bits(&[4, 5]))
+            .with_connection("WR_EN", Direction::Input, bits(&[6, 6]));
+        module.cells.insert("ram".into(), ram);
+        let assert =
+            Cell::new("$assert").with_connection("A", Direction::Input, bits(&[9])).with_connection("EN", Direction::Input, vec![Bit::_1]);
+        module.cells.insert("check".into(), assert);
+        let nand = Cell::new("$_NAND_")
+            .with_connection("A", Direction::Input, bits(&[7]))
+            .with_connection("B", Direction::Input, bits(&[8]))
+            .with_connection("Y", Direction::Output, bits(&[9]));
+        module.cells.insert("nand".into(), nand);
+        module
+    }
+
+    #[test]
+    fn test_btor_memories() {
+        let module = memory_module();
+        let expanded = module.to_btor(&BtorOptions::default()).unwrap();
+        assert!(!expanded.contains("array"));
+        assert_eq!(expanded.lines().filter(|line| line.contains(" state ")).count(), 4);
+        assert_eq!(expanded.lines().filter(|line| line.contains(" next ")).count(), 4);
+        assert_eq!(expanded.lines().filter(|line| line.contains(" init ")).count(), 1);
+
+        let array = module.to_btor(&BtorOptions { memories: MemoryModel::Array }).unwrap();
+        assert!(array.contains("sort array"));
+        assert_eq!(array.lines().filter(|line| line.contains(" read ")).count(), 2);
+        assert!(array.contains(" write "));
+        assert!(array.lines().any(|line| line.contains(" bad ")));
+        assert!(array.lines().any(|line| line.ends_with(" q")));
+
+        let mut sync = module.clone();
+        sync.cells["ram"].parameters.insert("RD_CLK_ENABLE".to_string(), "1".into());
+        assert_eq!(
+            sync.to_btor(&BtorOptions::default()),
+            Err(BtorError::Unsupported { cell: "ram".to_string(), module: "$mem_v2".to_string() })
+        );
+    }
+}
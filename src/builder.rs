@@ -0,0 +1,300 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{Bit, Cell, Direction, Module, Net, Netlist, Port};
+
+/// Yosys writes boolean attributes as 32 bit constants.
+pub(crate) const TRUE: &str = "00000000000000000000000000000001";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// A port, net or cell name is used twice.
+    Duplicate(String),
+    UnknownNet(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(name) => write!(f, "{} is declared twice", name),
+            Self::UnknownNet(name) => write!(f, "no net {}", name),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Bits of a constant, least significant first.
+pub fn constant(value: u64, width: usize) -> Vec<Bit> {
+    (0..width).map(|bit| if bit < 64 && value >> bit & 1 == 1 { Bit::_1 } else { Bit::_0 }).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct NetlistBuilder {
+    netlist: Netlist,
+}
+
+impl NetlistBuilder {
+    pub fn new(creator: &str) -> Self {
+        Self { netlist: Netlist::new(creator) }
+    }
+
+    pub fn module(mut self, name: &str, module: ModuleBuilder) -> Result<Self, BuildError> {
+        if self.netlist.modules.contains_key(name) {
+            return Err(BuildError::Duplicate(name.to_string()));
+        }
+        self.netlist.modules.insert(name.into(), module.build());
+        Ok(self)
+    }
+
+    /// Adds a module marked as the top of the design.
+    pub fn top(self, name: &str, mut module: ModuleBuilder) -> Result<Self, BuildError> {
+        module.module.attributes.insert("top".to_string(), Value::from(TRUE));
+        self.module(name, module)
+    }
+
+    pub fn build(self) -> Netlist {
+        self.netlist
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PortBuilder {
+    name: String,
+    port: Port,
+}
+
+impl PortBuilder {
+    pub fn new(name: &str, direction: Direction, width: usize) -> Self {
+        Self { name: name.to_string(), port: Port::new(direction, vec![Bit::X; width]) }
+    }
+
+    pub fn input(name: &str, width: usize) -> Self {
+        Self::new(name, Direction::Input, width)
+    }
+
+    pub fn output(name: &str, width: usize) -> Self {
+        Self::new(name, Direction::Output, width)
+    }
+
+    pub fn inout(name: &str, width: usize) -> Self {
+        Self::new(name, Direction::InOut, width)
+    }
+
+    pub fn signed(mut self) -> Self {
+        self.port.signed = true;
+        self
+    }
+
+    /// Index of the least significant bit, as in `[offset+width-1:offset]`.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.port.offset = offset;
+        self
+    }
+
+    /// Declares the range ascending, as in `[offset:offset+width-1]`.
+    pub fn upto(mut self) -> Self {
+        self.port.upto = 1;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Connection {
+    Bits(Vec<Bit>),
+    Net(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CellBuilder {
+    cell: Cell,
+    connections: Vec<(String, Direction, Connection)>,
+}
+
+impl CellBuilder {
+    pub fn new(module: &str) -> Self {
+        Self { cell: Cell::new(module), connections: Vec::new() }
+    }
+
+    pub fn parameter(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.cell.parameters.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn attribute(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.cell.attributes.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn connect(mut self, port: &str, direction: Direction, bits: Vec<Bit>) -> Self {
+        self.connections.push((port.to_string(), direction, Connection::Bits(bits)));
+        self
+    }
+
+    /// Connects `port` to every bit of the net called `net` in the module.
+    pub fn connect_net(mut self, port: &str, direction: Direction, net: &str) -> Self {
+        self.connections.push((port.to_string(), direction, Connection::Net(net.to_string())));
+        self
+    }
+
+    pub fn input(self, port: &str, bits: Vec<Bit>) -> Self {
+        self.connect(port, Direction::Input, bits)
+    }
+
+    pub fn output(self, port: &str, bits: Vec<Bit>) -> Self {
+        self.connect(port, Direction::Output, bits)
+    }
+
+    pub fn input_net(self, port: &str, net: &str) -> Self {
+        self.connect_net(port, Direction::Input, net)
+    }
+
+    pub fn output_net(self, port: &str, net: &str) -> Self {
+        self.connect_net(port, Direction::Output, net)
+    }
+}
+
+/// Builds a module, numbering the bits of its ports and nets as they are declared. Every
+/// port gets a net of the same name, as Yosys writes them.
+#[derive(Debug, Clone)]
+pub struct ModuleBuilder {
+    module: Module,
+    next: u64,
+}
+
+impl Default for ModuleBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self {
+        // Yosys reserves 0 and 1 for the constants in some backends, so start numbering at 2.
+        Self { module: Module::new(), next: 2 }
+    }
+
+    pub fn attribute(&mut self, name: &str, value: impl Into<Value>) -> &mut Self {
+        self.module.attributes.insert(name.to_string(), value.into());
+        self
+    }
+
+    fn allocate(&mut self, width: usize) -> Vec<Bit> {
+        self.next += width as u64;
+        (self.next - width as u64..self.next).map(Bit::Signal).collect()
+    }
+
+    /// Declares a net of fresh bits. Names starting with `$` are hidden, like Yosys'
+    /// internal names.
+    pub fn wire(&mut self, name: &str, width: usize) -> Result<Vec<Bit>, BuildError> {
+        if self.module.nets.contains_key(name) {
+            return Err(BuildError::Duplicate(name.to_string()));
+        }
+        let bits = self.allocate(width);
+        let mut net = Net::new(bits.clone());
+        net.hide_name = name.starts_with('$');
+        self.module.nets.insert(name.into(), net);
+        Ok(bits)
+    }
+
+    /// Declares a port and its net, returning the fresh bits of both.
+    pub fn port(&mut self, port: PortBuilder) -> Result<Vec<Bit>, BuildError> {
+        let PortBuilder { name, mut port } = port;
+        if self.module.ports.contains_key(&name) {
+            return Err(BuildError::Duplicate(name));
+        }
+        let bits = self.wire(&name, port.bits.len())?;
+        let net = &mut self.module.nets[&name];
+        (net.offset, net.upto, net.signed) = (port.offset, port.upto, port.signed);
+        port.bits = bits.clone();
+        self.module.ports.insert(name.into(), port);
+        Ok(bits)
+    }
+
+    pub fn input(&mut self, name: &str, width: usize) -> Result<Vec<Bit>, BuildError> {
+        self.port(PortBuilder::input(name, width))
+    }
+
+    pub fn output(&mut self, name: &str, width: usize) -> Result<Vec<Bit>, BuildError> {
+        self.port(PortBuilder::output(name, width))
+    }
+
+    /// Bits of a declared net.
+    pub fn net(&self, name: &str) -> Result<Vec<Bit>, BuildError> {
+        self.module.nets.get(name).map(|net| net.bits.clone()).ok_or_else(|| BuildError::UnknownNet(name.to_string()))
+    }
+
+    pub fn cell(&mut self, name: &str, cell: CellBuilder) -> Result<&mut Self, BuildError> {
+        if self.module.cells.contains_key(name) {
+            return Err(BuildError::Duplicate(name.to_string()));
+        }
+        let CellBuilder { mut cell, connections } = cell;
+        for (port, direction, connection) in connections {
+            let bits = match connection {
+                Connection::Bits(bits) => bits,
+                Connection::Net(net) => self.net(&net)?,
+            };
+            cell = cell.with_connection(&port, direction, bits);
+        }
+        cell.hide_name = name.starts_with('$');
+        self.module.cells.insert(name.into(), cell);
+        Ok(self)
+    }
+
+    pub fn build(self) -> Module {
+        self.module
+    }
+}
+
+impl Netlist {
+    pub fn builder(creator: &str) -> NetlistBuilder {
+        NetlistBuilder::new(creator)
+    }
+}
+
+impl Module {
+    pub fn builder() -> ModuleBuilder {
+        ModuleBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equality::SemanticEq;
+
+    #[test]
+    fn test_builder() {
+        let mut adder = Module::builder();
+        let a = adder.input("a", 4).unwrap();
+        adder.port(PortBuilder::input("b", 4).signed()).unwrap();
+        adder.output("y", 5).unwrap();
+        adder
+            .cell(
+                "$add$1",
+                CellBuilder::new("$add")
+                    .parameter("A_SIGNED", 0)
+                    .parameter("A_WIDTH", 4)
+                    .parameter("B_SIGNED", 0)
+                    .parameter("B_WIDTH", 4)
+                    .parameter("Y_WIDTH", 5)
+                    .input("A", a.clone())
+                    .input_net("B", "b")
+                    .output_net("Y", "y"),
+            )
+            .unwrap();
+        assert_eq!(adder.input("a", 1), Err(BuildError::Duplicate("a".to_string())));
+        assert!(adder.cell("bad", CellBuilder::new("$not").input_net("A", "c")).is_err());
+
+        let netlist = Netlist::builder("test").top("adder", adder).unwrap().build();
+        let module = &netlist.modules["adder"];
+        assert_eq!(module.ports["b"].bits, (6..10).map(Bit::Signal).collect::<Vec<_>>());
+        assert_eq!(module.cells["$add$1"].connections["Y"], module.ports["y"].bits);
+        assert!(module.cells["$add$1"].hide_name);
+        assert!(module.nets["b"].signed);
+
+        let parsed = Netlist::from_str(&netlist.to_string().unwrap()).unwrap();
+        assert!(parsed.semantic_eq(&netlist));
+    }
+}
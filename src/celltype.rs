@@ -0,0 +1,340 @@
+use std::fmt;
+
+use crate::{Cell, Direction};
+
+macro_rules! cell_types {
+    ($($variant:ident => $name:literal,)*) => {
+        /// The Yosys internal cells, as written in the `type` of a cell. Gate-level flip-flops
+        /// and latches keep the polarity letters of their name, `$_DFF_PN0_` being
+        /// `GateDff("PN0")`. Every other type, user modules included, is `User`.
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum CellType {
+            $($variant,)*
+            GateDff(String),
+            GateDffe(String),
+            GateSdff(String),
+            GateSdffe(String),
+            GateSdffce(String),
+            GateDffsr(String),
+            GateDffsre(String),
+            GateAldff(String),
+            GateAldffe(String),
+            GateDlatch(String),
+            GateDlatchsr(String),
+            GateSr(String),
+            User(String),
+        }
+
+        const NAMES: &[(&str, CellType)] = &[$(($name, CellType::$variant),)*];
+
+        impl CellType {
+            fn simple_name(&self) -> Option<&'static str> {
+                match self {
+                    $(Self::$variant => Some($name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+cell_types! {
+    Not => "$not",
+    Pos => "$pos",
+    Neg => "$neg",
+    ReduceAnd => "$reduce_and",
+    ReduceOr => "$reduce_or",
+    ReduceXor => "$reduce_xor",
+    ReduceXnor => "$reduce_xnor",
+    ReduceBool => "$reduce_bool",
+    LogicNot => "$logic_not",
+    And => "$and",
+    Or => "$or",
+    Xor => "$xor",
+    Xnor => "$xnor",
+    Shl => "$shl",
+    Shr => "$shr",
+    Sshl => "$sshl",
+    Sshr => "$sshr",
+    Shift => "$shift",
+    Shiftx => "$shiftx",
+    Lt => "$lt",
+    Le => "$le",
+    Eq => "$eq",
+    Ne => "$ne",
+    Eqx => "$eqx",
+    Nex => "$nex",
+    Ge => "$ge",
+    Gt => "$gt",
+    Add => "$add",
+    Sub => "$sub",
+    Mul => "$mul",
+    Div => "$div",
+    Mod => "$mod",
+    DivFloor => "$divfloor",
+    ModFloor => "$modfloor",
+    Pow => "$pow",
+    LogicAnd => "$logic_and",
+    LogicOr => "$logic_or",
+    Mux => "$mux",
+    Bmux => "$bmux",
+    Pmux => "$pmux",
+    Demux => "$demux",
+    Bwmux => "$bwmux",
+    Tribuf => "$tribuf",
+    Slice => "$slice",
+    Concat => "$concat",
+    Lut => "$lut",
+    Sop => "$sop",
+    Alu => "$alu",
+    Lcu => "$lcu",
+    Macc => "$macc",
+    Fa => "$fa",
+    Sr => "$sr",
+    Ff => "$ff",
+    Dff => "$dff",
+    Dffe => "$dffe",
+    Adff => "$adff",
+    Adffe => "$adffe",
+    Aldff => "$aldff",
+    Aldffe => "$aldffe",
+    Sdff => "$sdff",
+    Sdffe => "$sdffe",
+    Sdffce => "$sdffce",
+    Dffsr => "$dffsr",
+    Dffsre => "$dffsre",
+    Dlatch => "$dlatch",
+    Adlatch => "$adlatch",
+    Dlatchsr => "$dlatchsr",
+    Mem => "$mem",
+    MemV2 => "$mem_v2",
+    Memrd => "$memrd",
+    MemrdV2 => "$memrd_v2",
+    Memwr => "$memwr",
+    MemwrV2 => "$memwr_v2",
+    Meminit => "$meminit",
+    MeminitV2 => "$meminit_v2",
+    Assert => "$assert",
+    Assume => "$assume",
+    Cover => "$cover",
+    Live => "$live",
+    Fair => "$fair",
+    Equiv => "$equiv",
+    Initstate => "$initstate",
+    Anyconst => "$anyconst",
+    Anyseq => "$anyseq",
+    Allconst => "$allconst",
+    Allseq => "$allseq",
+    Print => "$print",
+    Check => "$check",
+    Scopeinfo => "$scopeinfo",
+    Specify2 => "$specify2",
+    Specify3 => "$specify3",
+    Specrule => "$specrule",
+    GateBuf => "$_BUF_",
+    GateNot => "$_NOT_",
+    GateAnd => "$_AND_",
+    GateNand => "$_NAND_",
+    GateOr => "$_OR_",
+    GateNor => "$_NOR_",
+    GateXor => "$_XOR_",
+    GateXnor => "$_XNOR_",
+    GateAndnot => "$_ANDNOT_",
+    GateOrnot => "$_ORNOT_",
+    GateMux => "$_MUX_",
+    GateNmux => "$_NMUX_",
+    GateMux4 => "$_MUX4_",
+    GateMux8 => "$_MUX8_",
+    GateMux16 => "$_MUX16_",
+    GateAoi3 => "$_AOI3_",
+    GateOai3 => "$_OAI3_",
+    GateAoi4 => "$_AOI4_",
+    GateOai4 => "$_OAI4_",
+    GateTbuf => "$_TBUF_",
+    GateFf => "$_FF_",
+}
+
+type Family = (&'static str, fn(String) -> CellType, &'static [usize]);
+
+/// Prefixes of the gate-level storage families, with the numbers of polarity letters each
+/// allows.
+const GATE_STORAGE: &[Family] = &[
+    ("$_DFF_", CellType::GateDff, &[1, 3]),
+    ("$_DFFE_", CellType::GateDffe, &[2, 4]),
+    ("$_SDFF_", CellType::GateSdff, &[3]),
+    ("$_SDFFE_", CellType::GateSdffe, &[4]),
+    ("$_SDFFCE_", CellType::GateSdffce, &[4]),
+    ("$_DFFSR_", CellType::GateDffsr, &[3]),
+    ("$_DFFSRE_", CellType::GateDffsre, &[4]),
+    ("$_ALDFF_", CellType::GateAldff, &[2]),
+    ("$_ALDFFE_", CellType::GateAldffe, &[3]),
+    ("$_DLATCH_", CellType::GateDlatch, &[1, 3]),
+    ("$_DLATCHSR_", CellType::GateDlatchsr, &[3]),
+    ("$_SR_", CellType::GateSr, &[2]),
+];
+
+impl CellType {
+    pub fn from_name(name: &str) -> Self {
+        if let Some((_, cell_type)) = NAMES.iter().find(|(known, _)| *known == name) {
+            return cell_type.clone();
+        }
+        for (prefix, variant, lengths) in GATE_STORAGE {
+            if let Some(polarity) = name.strip_prefix(prefix).and_then(|rest| rest.strip_suffix('_'))
+                && lengths.contains(&polarity.len())
+                && polarity.chars().all(|c| matches!(c, 'P' | 'N' | '0' | '1'))
+            {
+                return variant(polarity.to_string());
+            }
+        }
+        Self::User(name.to_string())
+    }
+
+    /// The type as written in the netlist.
+    pub fn name(&self) -> String {
+        if let Some(name) = self.simple_name() {
+            return name.to_string();
+        }
+        let (family, polarity) = match self {
+            Self::GateDff(polarity) => ("DFF", polarity),
+            Self::GateDffe(polarity) => ("DFFE", polarity),
+            Self::GateSdff(polarity) => ("SDFF", polarity),
+            Self::GateSdffe(polarity) => ("SDFFE", polarity),
+            Self::GateSdffce(polarity) => ("SDFFCE", polarity),
+            Self::GateDffsr(polarity) => ("DFFSR", polarity),
+            Self::GateDffsre(polarity) => ("DFFSRE", polarity),
+            Self::GateAldff(polarity) => ("ALDFF", polarity),
+            Self::GateAldffe(polarity) => ("ALDFFE", polarity),
+            Self::GateDlatch(polarity) => ("DLATCH", polarity),
+            Self::GateDlatchsr(polarity) => ("DLATCHSR", polarity),
+            Self::GateSr(polarity) => ("SR", polarity),
+            Self::User(name) => return name.clone(),
+            _ => unreachable!(),
+        };
+        format!("$_{}_{}_", family, polarity)
+    }
+
+    /// Flip-flops and latches, word-level and gate-level.
+    pub fn is_register(&self) -> bool {
+        matches!(
+            self,
+            Self::Sr
+                | Self::Ff
+                | Self::Dff
+                | Self::Dffe
+                | Self::Adff
+                | Self::Adffe
+                | Self::Aldff
+                | Self::Aldffe
+                | Self::Sdff
+                | Self::Sdffe
+                | Self::Sdffce
+                | Self::Dffsr
+                | Self::Dffsre
+                | Self::Dlatch
+                | Self::Adlatch
+                | Self::Dlatchsr
+                | Self::GateFf
+                | Self::GateDff(_)
+                | Self::GateDffe(_)
+                | Self::GateSdff(_)
+                | Self::GateSdffe(_)
+                | Self::GateSdffce(_)
+                | Self::GateDffsr(_)
+                | Self::GateDffsre(_)
+                | Self::GateAldff(_)
+                | Self::GateAldffe(_)
+                | Self::GateDlatch(_)
+                | Self::GateDlatchsr(_)
+                | Self::GateSr(_)
+        )
+    }
+
+    /// Instances of user modules and of cells this enum does not know.
+    pub fn is_user(&self) -> bool {
+        matches!(self, Self::User(_))
+    }
+
+    /// The output ports of an internal cell, every other port of it being an input. Empty for
+    /// user cells, whose directions are only known from their definition.
+    pub fn output_ports(&self) -> &'static [&'static str] {
+        match self {
+            _ if self.is_register() => &["Q"],
+            Self::Alu => &["X", "Y", "CO"],
+            Self::Lcu => &["CO"],
+            Self::Fa => &["X", "Y"],
+            Self::Mem | Self::MemV2 => &["RD_DATA"],
+            Self::Memrd | Self::MemrdV2 => &["DATA"],
+            Self::Memwr
+            | Self::MemwrV2
+            | Self::Meminit
+            | Self::MeminitV2
+            | Self::Assert
+            | Self::Assume
+            | Self::Cover
+            | Self::Live
+            | Self::Fair
+            | Self::Print
+            | Self::Check
+            | Self::Scopeinfo
+            | Self::Specify2
+            | Self::Specify3
+            | Self::Specrule
+            | Self::User(_) => &[],
+            _ => &["Y"],
+        }
+    }
+}
+
+impl fmt::Display for CellType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+impl Cell {
+    pub fn cell_type(&self) -> CellType {
+        CellType::from_name(&self.module)
+    }
+
+    /// The direction of `port` as declared in `port_directions`, or for internal cells that
+    /// do not declare it, as given by their type. `None` for undeclared ports of user cells.
+    pub fn port_direction(&self, port: &str) -> Option<Direction> {
+        if let Some(direction) = self.port_directions.get(port) {
+            return Some(direction.clone());
+        }
+        let cell_type = self.cell_type();
+        match cell_type.output_ports().contains(&port) {
+            _ if cell_type.is_user() => None,
+            true => Some(Direction::Output),
+            false => Some(Direction::Input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_type() {
+        assert_eq!(Cell::new("$add").cell_type(), CellType::Add);
+        assert_eq!(Cell::new("$mem_v2").cell_type(), CellType::MemV2);
+        assert_eq!(Cell::new("$_MUX_").cell_type(), CellType::GateMux);
+        assert_eq!(Cell::new("$_DFF_PN0_").cell_type(), CellType::GateDff("PN0".to_string()));
+        assert_eq!(Cell::new("$_DFFE_PP_").cell_type(), CellType::GateDffe("PP".to_string()));
+        assert_eq!(Cell::new("$_DFF_PX_").cell_type(), CellType::User("$_DFF_PX_".to_string()));
+        assert_eq!(Cell::new("adder").cell_type(), CellType::User("adder".to_string()));
+        assert!(CellType::GateSr("NP".to_string()).is_register() && !CellType::Mux.is_register());
+
+        for name in ["$add", "$_DFFSRE_PNNP_", "$_SR_NP_", "$_FF_", "$scopeinfo", "top"] {
+            assert_eq!(CellType::from_name(name).to_string(), name);
+        }
+
+        let adder = Cell::new("$alu");
+        assert_eq!((adder.port_direction("CO"), adder.port_direction("BI")), (Some(Direction::Output), Some(Direction::Input)));
+        assert_eq!(Cell::new("$_DFF_P_").port_direction("Q"), Some(Direction::Output));
+        assert_eq!(Cell::new("$_NOT_").port_direction("A"), Some(Direction::Input));
+        let instance = Cell::new("adder").with_connection("S", Direction::Output, Vec::new());
+        assert_eq!((instance.port_direction("S"), instance.port_direction("A")), (Some(Direction::Output), None));
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Bit, Cell, Module};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointError {
+    UnknownRegister(String),
+    UnknownMemory(String),
+    /// A value does not have the width of the register or memory word it is stored into, or
+    /// there are more words than the memory holds.
+    WidthMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidValue(String),
+    /// A register is given a known value on bits no net covers, which could not hold it.
+    Uncovered(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownRegister(name) => write!(f, "no register {}", name),
+            Self::UnknownMemory(name) => write!(f, "no memory cell {}", name),
+            Self::WidthMismatch { name, expected, found } => write!(f, "{} is {} bits wide, found {} bits", name, expected, found),
+            Self::InvalidValue(value) => write!(f, "invalid value {:?}, expected 0, 1 and x digits", value),
+            Self::Uncovered(name) => write!(f, "register {} drives bits without a net to hold their value", name),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// State of a simulation: register and memory contents at a point in time. Values are
+/// written like Yosys constants, most significant bit first with `x` for unknown bits, so
+/// states taken from formal counterexamples can be filled in by hand or by a script.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Simulation time of an [`EventSimulator`](crate::event::EventSimulator), clock cycles of a
+    /// [`Simulator`](crate::sim::Simulator).
+    pub time: u64,
+    /// Values of the `Q` output of every register cell, by cell name.
+    #[serde(default)]
+    pub registers: IndexMap<String, String>,
+    /// Words of every memory cell, by cell name, lowest address first.
+    #[serde(default)]
+    pub memories: IndexMap<String, Vec<String>>,
+}
+
+impl Checkpoint {
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+/// Bits of a Yosys constant, least significant first, `None` for unknown bits.
+pub(crate) fn decode(value: &Value, width: usize) -> Option<Vec<Option<bool>>> {
+    let mut bits: Vec<Option<bool>> = match value {
+        Value::String(digits) => digits
+            .chars()
+            .rev()
+            .map(|digit| match digit {
+                '0' => Some(Some(false)),
+                '1' => Some(Some(true)),
+                'x' | 'X' | 'z' | 'Z' | '-' => Some(None),
+                _ => None,
+            })
+            .collect::<Option<_>>()?,
+        Value::Number(number) => {
+            let number = number.as_u64()?;
+            (0..width.min(64)).map(|index| Some(number >> index & 1 == 1)).collect()
+        }
+        _ => return None,
+    };
+    bits.resize(width, None);
+    Some(bits)
+}
+
+pub(crate) fn encode(bits: &[Option<bool>]) -> String {
+    bits.iter()
+        .rev()
+        .map(|bit| match bit {
+            Some(false) => '0',
+            Some(true) => '1',
+            None => 'x',
+        })
+        .collect()
+}
+
+pub(crate) fn parse(name: &str, value: &str, width: usize) -> Result<Vec<Option<bool>>, CheckpointError> {
+    if value.chars().count() != width {
+        return Err(CheckpointError::WidthMismatch { name: name.to_string(), expected: width, found: value.chars().count() });
+    }
+    decode(&Value::String(value.to_string()), width).ok_or_else(|| CheckpointError::InvalidValue(value.to_string()))
+}
+
+impl Module {
+    /// Initial values of every signal bit, from the `init` attributes of the nets.
+    pub(crate) fn initial_values(&self) -> HashMap<Bit, bool> {
+        let mut values = HashMap::new();
+        for net in self.nets.values() {
+            let Some(bits) = net.attributes.get("init").and_then(|init| decode(init, net.bits.len())) else { continue };
+            for (bit, value) in net.bits.iter().zip(bits) {
+                if let (Bit::Signal(_), Some(value)) = (bit, value) {
+                    values.insert(*bit, value);
+                }
+            }
+        }
+        values
+    }
+
+    /// The state the module starts in: register `init` attributes and memory `INIT`
+    /// parameters, at time 0.
+    pub fn initial_checkpoint(&self) -> Checkpoint {
+        let values = self.initial_values();
+        let mut checkpoint = Checkpoint::default();
+        for (name, cell) in self.cells.iter() {
+            if cell.is_register()
+                && let Some(q) = cell.connections.get("Q")
+            {
+                let bits: Vec<Option<bool>> = q.iter().map(|bit| values.get(bit).copied()).collect();
+                checkpoint.registers.insert(name.to_string(), encode(&bits));
+            } else if let Some(contents) = cell.memory_contents() {
+                let words = (0..contents.depth()).map(|address| encode(&contents.bits(address))).collect();
+                checkpoint.memories.insert(name.to_string(), words);
+            }
+        }
+        checkpoint
+    }
+
+    /// Makes `checkpoint` the initial state: register values become `init` attributes of the
+    /// nets driven by the registers, memory words the `INIT` parameter of the memory cells.
+    /// Registers and memories missing from the checkpoint are left alone. Known bits of a register
+    /// output no net covers are an error, as there is nowhere to keep them.
+    pub fn restore_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), CheckpointError> {
+        let mut values = HashMap::new();
+        for (name, value) in checkpoint.registers.iter() {
+            let q = self
+                .cells
+                .get(name)
+                .filter(|cell| cell.is_register())
+                .and_then(|cell| cell.connections.get("Q"))
+                .ok_or_else(|| CheckpointError::UnknownRegister(name.clone()))?;
+            let bits = parse(name, value, q.len())?;
+            if q.iter().zip(&bits).any(|(bit, value)| value.is_some() && !self.nets.values().any(|net| net.bits.contains(bit))) {
+                return Err(CheckpointError::Uncovered(name.clone()));
+            }
+            values.extend(q.iter().copied().zip(bits));
+        }
+        let mut memories = Vec::new();
+        for (name, words) in checkpoint.memories.iter() {
+            let mut contents =
+                self.cells.get(name).and_then(Cell::memory_contents).ok_or_else(|| CheckpointError::UnknownMemory(name.clone()))?;
+            if words.len() > contents.depth() {
+                return Err(CheckpointError::WidthMismatch { name: name.clone(), expected: contents.depth(), found: words.len() });
+            }
+            for (address, word) in words.iter().enumerate() {
+                contents.set_bits(address, &parse(&format!("{}[{}]", name, address), word, contents.width())?);
+            }
+            memories.push((name, contents));
+        }
+
+        for net in self.nets.values_mut() {
+            if !net.bits.iter().any(|bit| values.contains_key(bit)) {
+                continue;
+            }
+            let mut bits = net.attributes.get("init").and_then(|init| decode(init, net.bits.len())).unwrap_or(vec![None; net.bits.len()]);
+            for (index, bit) in net.bits.iter().enumerate() {
+                if let Some(value) = values.get(bit) {
+                    bits[index] = *value;
+                }
+            }
+            match bits.iter().all(Option::is_none) {
+                true => _ = net.attributes.shift_remove("init"),
+                false => _ = net.attributes.insert("init".to_string(), Value::String(encode(&bits))),
+            }
+        }
+        for (name, contents) in memories {
+            self.cells[name].set_memory_contents(&contents);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, Net};
+
+    #[test]
+    fn test_checkpoint() {
+        let mut module = Module::new();
+        let q = vec![Bit::Signal(4), Bit::Signal(5), Bit::Signal(6)];
+        module
+            .cells
+            .insert("count".into(), Cell::new("$dff").with_parameter("WIDTH", 3).with_connection("Q", Direction::Output, q.clone()));
+        module.cells.insert(
+            "ram".into(),
+            Cell::new("$mem_v2").with_parameter("WIDTH", 2).with_parameter("SIZE", 3).with_parameter("INIT", "xx0110"),
+        );
+        let mut net = Net::new(q);
+        net.attributes.insert("init".to_string(), Value::String("x01".to_string()));
+        module.nets.insert("count".into(), net);
+
+        let mut checkpoint = module.initial_checkpoint();
+        assert_eq!(checkpoint.registers["count"], "x01");
+        assert_eq!(checkpoint.memories["ram"], ["10", "01", "xx"]);
+
+        checkpoint.time = 1000;
+        checkpoint.registers["count"] = "110".to_string();
+        checkpoint.memories["ram"][2] = "11".to_string();
+        let mut json = Vec::new();
+        checkpoint.to_writer(&mut json).unwrap();
+        let checkpoint = Checkpoint::from_reader(json.as_slice()).unwrap();
+        module.restore_checkpoint(&checkpoint).unwrap();
+        assert_eq!(module.nets["count"].attributes["init"], "110");
+        assert_eq!(module.cells["ram"].parameters["INIT"], "110110");
+
+        let narrow = Checkpoint { registers: IndexMap::from([("count".to_string(), "1".to_string())]), ..checkpoint };
+        assert_eq!(
+            module.restore_checkpoint(&narrow),
+            Err(CheckpointError::WidthMismatch { name: "count".to_string(), expected: 3, found: 1 })
+        );
+
+        // Without a net on its output, only an unknown value of a register can be restored.
+        let q = vec![Bit::Signal(9)];
+        module.cells.insert("flag".into(), Cell::new("$dff").with_parameter("WIDTH", 1).with_connection("Q", Direction::Output, q));
+        let flag =
+            |value: &str| Checkpoint { registers: IndexMap::from([("flag".to_string(), value.to_string())]), ..Checkpoint::default() };
+        assert_eq!(module.restore_checkpoint(&flag("1")), Err(CheckpointError::Uncovered("flag".to_string())));
+        assert_eq!(module.restore_checkpoint(&flag("x")), Ok(()));
+    }
+}
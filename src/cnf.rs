@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+
+use indexmap::IndexMap;
+
+use crate::formal::{FormalError, System};
+use crate::sat::{Lit, dimacs};
+use crate::{Bit, Module};
+
+/// A module's combinational logic in conjunctive normal form, by Tseitin encoding: one
+/// variable per bit, and for every gate or LUT one clause per row of its truth table.
+/// Register outputs and undriven bits are free variables like the inputs, so the clauses
+/// relate the values of one cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cnf {
+    pub vars: usize,
+    pub clauses: Vec<Vec<Lit>>,
+    /// Variable of every signal bit: inputs, register outputs, undriven bits, then gate
+    /// outputs in dataflow order.
+    pub bits: IndexMap<Bit, Lit>,
+    /// A literal true when each `$assert` cell holds, that is when its enable is low or its
+    /// condition high. Negate one in a unit clause to search for a violation.
+    pub properties: IndexMap<String, Lit>,
+    truth: Lit,
+}
+
+impl Cnf {
+    pub fn new_var(&mut self) -> Lit {
+        self.vars += 1;
+        Lit::new(self.vars - 1, true)
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        self.clauses.push(lits.to_vec());
+    }
+
+    /// The literal of a bit. Constants map onto a variable fixed to true, `x` and `z` are
+    /// taken as 0, and bits the logic does not touch have none.
+    pub fn lit(&self, bit: &Bit) -> Option<Lit> {
+        match bit {
+            Bit::_1 => Some(self.truth),
+            Bit::Signal(_) => self.bits.get(bit).copied(),
+            _ => Some(!self.truth),
+        }
+    }
+
+    /// DIMACS literals of the bits of every net of `module`, negative when inverted and `None`
+    /// for bits without a variable.
+    pub fn variables(&self, module: &Module) -> IndexMap<String, Vec<Option<i64>>> {
+        (module.nets.iter()).map(|(name, net)| (name.to_string(), net.bits.iter().map(|bit| self.lit(bit).map(dimacs)).collect())).collect()
+    }
+
+    /// Writes the clauses in DIMACS, with `c` comment lines naming the variable of every
+    /// public net of `module`.
+    pub fn to_dimacs(&self, module: &Module, mut writer: impl Write) -> io::Result<()> {
+        for (name, lits) in self.variables(module).into_iter().filter(|(name, _)| !module.nets[name].hide_name) {
+            let lits: Vec<String> = lits.into_iter().map(|lit| lit.map_or("-".to_string(), |lit| lit.to_string())).collect();
+            writeln!(writer, "c {} {}", name, lits.join(" "))?;
+        }
+        writeln!(writer, "p cnf {} {}", self.vars, self.clauses.len())?;
+        for clause in self.clauses.iter() {
+            for lit in clause {
+                write!(writer, "{} ", dimacs(*lit))?;
+            }
+            writeln!(writer, "0")?;
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    /// Encodes the gates and LUTs of the module into CNF, with the `$assume` cells as
+    /// constraints. Modules with cells that are neither, registers nor `$assert` cells fail.
+    pub fn to_cnf(&self) -> Result<Cnf, FormalError> {
+        let system = System::new(self)?;
+        let mut cnf = Cnf { vars: 0, clauses: Vec::new(), bits: IndexMap::new(), properties: IndexMap::new(), truth: Lit::new(0, true) };
+        cnf.truth = cnf.new_var();
+        cnf.add_clause(&[cnf.truth]);
+        let free = system.inputs.iter().flat_map(|(_, bits)| bits).chain(system.registers.iter().map(|register| &register.q));
+        for bit in free.filter(|bit| matches!(bit, Bit::Signal(_))) {
+            if !cnf.bits.contains_key(bit) {
+                let lit = cnf.new_var();
+                cnf.bits.insert(*bit, lit);
+            }
+        }
+        let lit = |cnf: &Cnf, bit: &Bit| cnf.lit(bit).unwrap_or(!cnf.truth);
+        for (inputs, output, table) in system.functions.iter() {
+            let inputs: Vec<Lit> = inputs.iter().map(|bit| lit(&cnf, bit)).collect();
+            let output_lit = cnf.new_var();
+            for (row, value) in table.iter().enumerate() {
+                let mut clause: Vec<Lit> =
+                    inputs.iter().enumerate().map(|(i, input)| if row >> i & 1 == 1 { !*input } else { *input }).collect();
+                clause.push(if *value { output_lit } else { !output_lit });
+                cnf.add_clause(&clause);
+            }
+            cnf.bits.insert(*output, output_lit);
+        }
+        for (a, en) in system.assumes.iter() {
+            let clause = [!lit(&cnf, en), lit(&cnf, a)];
+            cnf.add_clause(&clause);
+        }
+        for (name, a, en) in system.asserts.iter() {
+            let (a, en) = (lit(&cnf, a), lit(&cnf, en));
+            let holds = cnf.new_var();
+            cnf.add_clause(&[!holds, !en, a]);
+            cnf.add_clause(&[holds, en]);
+            cnf.add_clause(&[holds, !a]);
+            cnf.properties.insert(name.clone(), holds);
+        }
+        Ok(cnf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+    use crate::sat::{Cdcl, Solver};
+
+    #[test]
+    fn test_to_cnf() {
+        let mut builder = Module::builder();
+        let [a, b] = ["a", "b"].map(|name| builder.input(name, 1).unwrap());
+        let y = builder.output("y", 1).unwrap();
+        builder.cell("and", CellBuilder::new("$_AND_").input("A", a.clone()).input("B", b).output("Y", y.clone())).unwrap();
+        builder.cell("check", CellBuilder::new("$assert").input("A", y.clone()).input("EN", a.clone())).unwrap();
+        let module = builder.build();
+        let cnf = module.to_cnf().unwrap();
+        assert_eq!(cnf.variables(&module)["y"], [Some(4)]);
+
+        let mut text = Vec::new();
+        cnf.to_dimacs(&module, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("c a 2\nc b 3\nc y 4\np cnf 5 8\n1 0\n2 3 -4 0\n"));
+
+        let mut solver = Cdcl::new();
+        for _ in 0..cnf.vars {
+            solver.new_var();
+        }
+        cnf.clauses.iter().for_each(|clause| solver.add_clause(clause));
+        let (a, y) = (cnf.lit(&a[0]).unwrap(), cnf.lit(&y[0]).unwrap());
+        assert_eq!(solver.solve(&[y, !a]), Ok(false));
+        assert_eq!(solver.solve(&[!cnf.properties["check"]]), Ok(true));
+        assert!(solver.value(a) && !solver.value(y));
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::celltype::CellType;
+use crate::{Bit, Cell, Direction, Module};
+
+/// One bit of a cell port or module port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint<'a> {
+    Cell { cell: &'a str, port: &'a str, index: usize },
+    Port { port: &'a str, index: usize },
+}
+
+/// Drivers and loads of every signal bit of a module, built in one pass so lookups do not
+/// walk the cells. Input ports drive their bits and output ports load them; inout ports and
+/// inout cell ports count as both. Cell ports take their direction from
+/// [`Cell::port_direction`], and count as loads where it is unknown.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectivityIndex<'a> {
+    drivers: HashMap<Bit, Vec<Endpoint<'a>>>,
+    loads: HashMap<Bit, Vec<Endpoint<'a>>>,
+}
+
+impl<'a> ConnectivityIndex<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("index", ports = module.ports.len(), cells = module.cells.len()).entered();
+        let mut index = Self::default();
+        for (name, port) in module.ports.iter() {
+            for (offset, bit) in port.bits.iter().enumerate() {
+                let endpoint = Endpoint::Port { port: name, index: offset };
+                index.add(*bit, endpoint, port.direction != Direction::Output, port.direction != Direction::Input);
+            }
+        }
+        for (name, cell) in module.cells.iter() {
+            for (port, bits) in cell.connections.iter() {
+                let direction = cell.port_direction(port);
+                for (offset, bit) in bits.iter().enumerate() {
+                    let endpoint = Endpoint::Cell { cell: name, port, index: offset };
+                    index.add(*bit, endpoint, direction.as_ref().is_some_and(|d| *d != Direction::Input), direction != Some(Direction::Output));
+                }
+            }
+        }
+        index
+    }
+
+    fn add(&mut self, bit: Bit, endpoint: Endpoint<'a>, drives: bool, loads: bool) {
+        if !matches!(bit, Bit::Signal(_)) {
+            return;
+        }
+        if drives {
+            self.drivers.entry(bit).or_default().push(endpoint);
+        }
+        if loads {
+            self.loads.entry(bit).or_default().push(endpoint);
+        }
+    }
+
+    pub fn drivers_of(&self, bit: &Bit) -> &[Endpoint<'a>] {
+        self.drivers.get(bit).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn loads_of(&self, bit: &Bit) -> &[Endpoint<'a>] {
+        self.loads.get(bit).map_or(&[], Vec::as_slice)
+    }
+
+    /// The driver of a bit, `None` if it has none or several.
+    pub fn driver_of(&self, bit: &Bit) -> Option<&Endpoint<'a>> {
+        match self.drivers_of(bit) {
+            [driver] => Some(driver),
+            _ => None,
+        }
+    }
+
+    /// Bits with more than one driver.
+    pub fn multiply_driven(&self) -> impl Iterator<Item = (&Bit, &[Endpoint<'a>])> {
+        self.drivers.iter().filter(|(_, drivers)| drivers.len() > 1).map(|(bit, drivers)| (bit, drivers.as_slice()))
+    }
+}
+
+/// The combinational cycles found by [`Module::topo_sort_cells`], each the cells of one
+/// strongly connected component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombinationalCycles(pub Vec<Vec<String>>);
+
+impl fmt::Display for CombinationalCycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cycles: Vec<String> = self.0.iter().map(|cells| cells.join(" -> ")).collect();
+        write!(f, "combinational cycles through {}", cycles.join("; "))
+    }
+}
+
+impl std::error::Error for CombinationalCycles {}
+
+/// Flip-flops, latches and memories, whose outputs do not follow their inputs within a cycle.
+fn is_sequential(cell: &Cell) -> bool {
+    let cell_type = cell.cell_type();
+    cell_type.is_register()
+        || matches!(
+            cell_type,
+            CellType::Mem
+                | CellType::MemV2
+                | CellType::Memrd
+                | CellType::MemrdV2
+                | CellType::Memwr
+                | CellType::MemwrV2
+                | CellType::Meminit
+                | CellType::MeminitV2
+        )
+}
+
+impl Module {
+    /// Builds a [`ConnectivityIndex`] of the module.
+    pub fn index(&self) -> ConnectivityIndex<'_> {
+        ConnectivityIndex::new(self)
+    }
+
+    /// Cells in dataflow order: the sequential cells first, since their outputs are ready at
+    /// the start of a cycle, then every combinational cell after the cells driving its
+    /// inputs. Fails with every combinational cycle found.
+    pub fn topo_sort_cells(&self) -> Result<Vec<&str>, CombinationalCycles> {
+        let (order, cycles) = self.dataflow_order();
+        match cycles.is_empty() {
+            true => Ok(order),
+            false => Err(CombinationalCycles(cycles)),
+        }
+    }
+
+    /// The order of [`Module::topo_sort_cells`] even where there are combinational cycles, the
+    /// cells of each cycle listed together after the cells driving it, and the cycles found.
+    pub(crate) fn dataflow_order(&self) -> (Vec<&str>, Vec<Vec<String>>) {
+        let index = self.index();
+        let names: HashMap<&str, usize> = self.cells.keys().enumerate().map(|(id, name)| (name.as_str(), id)).collect();
+        let sequential: Vec<bool> = self.cells.values().map(is_sequential).collect();
+        let fanin: Vec<Vec<usize>> = (self.cells.values())
+            .map(|cell| {
+                let inputs = cell.connections.iter().filter(|(port, _)| cell.port_direction(port) != Some(Direction::Output));
+                let mut fanin: Vec<usize> = (inputs.flat_map(|(_, bits)| bits).flat_map(|bit| index.drivers_of(bit)))
+                    .filter_map(|driver| match driver {
+                        Endpoint::Cell { cell, .. } => Some(names[cell]),
+                        Endpoint::Port { .. } => None,
+                    })
+                    .filter(|driver| !sequential[*driver])
+                    .collect();
+                fanin.sort();
+                fanin.dedup();
+                fanin
+            })
+            .collect();
+
+        // Tarjan's algorithm over the edges from loads to drivers lists the strongly connected
+        // components drivers first.
+        let mut order: Vec<&str> =
+            self.cells.keys().zip(&sequential).filter(|(_, sequential)| **sequential).map(|(name, _)| name.as_str()).collect();
+        let mut cycles = Vec::new();
+        let mut numbers: Vec<Option<usize>> = vec![None; self.cells.len()];
+        let mut lowlinks = vec![0; self.cells.len()];
+        let mut on_stack = vec![false; self.cells.len()];
+        let mut stack = Vec::new();
+        let mut counter = 0;
+        for root in (0..self.cells.len()).filter(|cell| !sequential[*cell]) {
+            if numbers[root].is_some() {
+                continue;
+            }
+            let mut calls = vec![(root, 0)];
+            while let Some((cell, edge)) = calls.pop() {
+                if edge == 0 {
+                    numbers[cell] = Some(counter);
+                    lowlinks[cell] = counter;
+                    counter += 1;
+                    stack.push(cell);
+                    on_stack[cell] = true;
+                } else {
+                    let child = fanin[cell][edge - 1];
+                    if on_stack[child] {
+                        lowlinks[cell] = lowlinks[cell].min(lowlinks[child]);
+                    }
+                }
+                if let Some(&next) = fanin[cell].get(edge) {
+                    calls.push((cell, edge + 1));
+                    if numbers[next].is_none() {
+                        calls.push((next, 0));
+                    }
+                    continue;
+                }
+                if Some(lowlinks[cell]) == numbers[cell] {
+                    let start = stack.iter().rposition(|member| *member == cell).unwrap();
+                    let mut component: Vec<usize> = stack.drain(start..).collect();
+                    component.sort();
+                    component.iter().for_each(|member| on_stack[*member] = false);
+                    if component.len() > 1 || fanin[cell].contains(&cell) {
+                        cycles.push(component.iter().map(|member| self.cells.get_index(*member).unwrap().0.to_string()).collect());
+                    }
+                    order.extend(component.iter().map(|member| self.cells.get_index(*member).unwrap().0.as_str()));
+                }
+            }
+        }
+        (order, cycles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+    use crate::builder::CellBuilder;
+
+    #[test]
+    fn test_connectivity_index() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let module = &netlist.modules["test_and"];
+        let index = module.index();
+        let (name, cell) = module.cells.first().unwrap();
+        let a = module.ports["a"].bits[0];
+        let c = module.ports["c"].bits[0];
+        assert_eq!(index.driver_of(&a), Some(&Endpoint::Port { port: "a", index: 0 }));
+        assert!(index.loads_of(&a).iter().any(|load| matches!(load, Endpoint::Cell { cell, .. } if *cell == *name)));
+        let driver = index.driver_of(&c).unwrap();
+        assert!(
+            matches!(driver, Endpoint::Cell { cell: driver, port, .. } if *driver == *name && cell.port_directions[*port] == Direction::Output)
+        );
+        assert_eq!(index.loads_of(&c), [Endpoint::Port { port: "c", index: 0 }]);
+        assert!(index.drivers_of(&Bit::_0).is_empty());
+        assert_eq!(index.multiply_driven().count(), 0);
+    }
+
+    #[test]
+    fn test_topo_sort_cells() {
+        let mut builder = Module::builder();
+        let a = builder.input("a", 1).unwrap();
+        let y = builder.output("y", 1).unwrap();
+        let [q, n, m] = ["q", "n", "m"].map(|name| builder.wire(name, 1).unwrap());
+        builder.cell("out", CellBuilder::new("$_AND_").input("A", n.clone()).input("B", q.clone()).output("Y", y)).unwrap();
+        builder.cell("inv", CellBuilder::new("$_NOT_").input("A", m.clone()).output("Y", n.clone())).unwrap();
+        builder.cell("reg", CellBuilder::new("$_DFF_P_").input("C", a.clone()).input("D", n).output("Q", q.clone())).unwrap();
+        builder.cell("in", CellBuilder::new("$_XOR_").input("A", a).input("B", q).output("Y", m.clone())).unwrap();
+        let mut module = builder.build();
+        assert_eq!(module.topo_sort_cells().unwrap(), ["reg", "in", "inv", "out"]);
+
+        // Feeding the inverter from its own output closes a cycle through `in` and `inv`.
+        module.cells["in"].connections["B"] = module.nets["n"].bits.clone();
+        assert_eq!(module.topo_sort_cells(), Err(CombinationalCycles(vec![vec!["inv".to_string(), "in".to_string()]])));
+    }
+}
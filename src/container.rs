@@ -0,0 +1,247 @@
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::intern::with_interner;
+use crate::{Module, Netlist};
+
+const MAGIC: &[u8; 4] = b"YJNC";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    /// Raw deflate, requires the `deflate` feature to read or write.
+    Deflate,
+}
+
+#[derive(Debug)]
+pub enum ContainerError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// Not a container, or a version this crate can not read.
+    Format(String),
+    UnknownModule(String),
+    UnsupportedCompression(Compression),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Json(error) => write!(f, "{}", error),
+            Self::Format(message) => write!(f, "invalid container: {}", message),
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::UnsupportedCompression(compression) => write!(f, "{:?} compression is not enabled in this build", compression),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+impl From<io::Error> for ContainerError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ContainerError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+/// Location of a module body, relative to the end of the header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleEntry {
+    pub name: String,
+    pub offset: u64,
+    pub length: u64,
+    pub compression: Compression,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    /// The netlist without its modules.
+    netlist: Netlist,
+    modules: Vec<ModuleEntry>,
+}
+
+fn compress(body: Vec<u8>, compression: Compression) -> Result<Vec<u8>, ContainerError> {
+    match compression {
+        Compression::None => Ok(body),
+        #[cfg(feature = "deflate")]
+        Compression::Deflate => {
+            let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&body)?;
+            Ok(encoder.finish()?)
+        }
+        #[cfg(not(feature = "deflate"))]
+        Compression::Deflate => Err(ContainerError::UnsupportedCompression(compression)),
+    }
+}
+
+fn decompress(body: Vec<u8>, compression: Compression) -> Result<Vec<u8>, ContainerError> {
+    match compression {
+        Compression::None => Ok(body),
+        #[cfg(feature = "deflate")]
+        Compression::Deflate => {
+            let mut decoded = Vec::new();
+            flate2::read::DeflateDecoder::new(body.as_slice()).read_to_end(&mut decoded)?;
+            Ok(decoded)
+        }
+        #[cfg(not(feature = "deflate"))]
+        Compression::Deflate => Err(ContainerError::UnsupportedCompression(compression)),
+    }
+}
+
+impl Netlist {
+    /// Writes the netlist as an indexed container: the magic `YJNC`, a little endian `u32`
+    /// version and `u64` header length, a JSON header with the netlist fields and the location
+    /// of every module, followed by the JSON body of every module.
+    pub fn write_container(&self, mut writer: impl Write, compression: Compression) -> Result<(), ContainerError> {
+        let mut bodies = Vec::new();
+        let mut modules = Vec::new();
+        let mut offset = 0;
+        for (name, module) in self.modules.iter() {
+            let body = compress(serde_json::to_vec(module)?, compression)?;
+            modules.push(ModuleEntry { name: name.to_string(), offset, length: body.len() as u64, compression });
+            offset += body.len() as u64;
+            bodies.push(body);
+        }
+        let netlist =
+            Netlist { creator: self.creator.clone(), modules: IndexMap::new(), extra: self.extra.clone(), interner: Default::default() };
+        let header = serde_json::to_vec(&Header { netlist, modules })?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(header.len() as u64).to_le_bytes())?;
+        writer.write_all(&header)?;
+        bodies.iter().try_for_each(|body| writer.write_all(body))?;
+        Ok(())
+    }
+}
+
+/// An opened container, reading module bodies only when they are loaded.
+pub struct Container<R> {
+    reader: R,
+    header: Header,
+    /// Position of the first module body.
+    start: u64,
+}
+
+impl<R: Read + Seek> Container<R> {
+    pub fn open(mut reader: R) -> Result<Self, ContainerError> {
+        let base = reader.stream_position()?;
+        let mut preamble = [0; 16];
+        reader.read_exact(&mut preamble)?;
+        if &preamble[..4] != MAGIC {
+            return Err(ContainerError::Format("bad magic".to_string()));
+        }
+        let version = u32::from_le_bytes(preamble[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(ContainerError::Format(format!("unsupported version {}", version)));
+        }
+        let length = u64::from_le_bytes(preamble[8..].try_into().unwrap());
+        let mut header = Vec::new();
+        reader.by_ref().take(length).read_to_end(&mut header)?;
+        if header.len() as u64 != length {
+            return Err(ContainerError::Format("truncated header".to_string()));
+        }
+        let header = serde_json::from_slice(&header)?;
+        Ok(Self { reader, header, start: base + preamble.len() as u64 + length })
+    }
+
+    pub fn creator(&self) -> &str {
+        &self.header.netlist.creator
+    }
+
+    pub fn modules(&self) -> &[ModuleEntry] {
+        &self.header.modules
+    }
+
+    pub fn load_module(&mut self, name: &str) -> Result<Module, ContainerError> {
+        let entry = self.header.modules.iter().find(|entry| entry.name == name);
+        let entry = entry.ok_or_else(|| ContainerError::UnknownModule(name.to_string()))?;
+        self.reader.seek(SeekFrom::Start(self.start + entry.offset))?;
+        let mut body = vec![0; entry.length as usize];
+        self.reader.read_exact(&mut body)?;
+        let body = decompress(body, entry.compression)?;
+        Ok(with_interner(&self.header.netlist.interner, || serde_json::from_slice(&body))?)
+    }
+
+    /// Loads a netlist with only the given modules, in container order.
+    pub fn load(&mut self, names: &[&str]) -> Result<Netlist, ContainerError> {
+        if let Some(name) = names.iter().find(|name| !self.header.modules.iter().any(|entry| entry.name == **name)) {
+            return Err(ContainerError::UnknownModule(name.to_string()));
+        }
+        let mut netlist = self.header.netlist.clone();
+        let selected: Vec<String> =
+            self.header.modules.iter().filter(|entry| names.contains(&entry.name.as_str())).map(|entry| entry.name.clone()).collect();
+        for name in selected {
+            let module = self.load_module(&name)?;
+            netlist.modules.insert(netlist.intern(&name), module);
+        }
+        Ok(netlist)
+    }
+
+    pub fn load_all(&mut self) -> Result<Netlist, ContainerError> {
+        let names: Vec<String> = self.header.modules.iter().map(|entry| entry.name.clone()).collect();
+        self.load(&names.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::IdString;
+
+    fn design() -> Netlist {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let mult = Netlist::from_reader(std::fs::File::open("testdata/mult.json").unwrap()).unwrap();
+        netlist.modules.extend(mult.modules);
+        netlist
+    }
+
+    fn roundtrip(compression: Compression) {
+        let netlist = design();
+        let mut data = Vec::new();
+        netlist.write_container(&mut data, compression).unwrap();
+
+        let mut container = Container::open(Cursor::new(data)).unwrap();
+        assert_eq!(container.creator(), netlist.creator);
+        let names: Vec<&str> = container.modules().iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, netlist.modules.keys().map(IdString::as_str).collect::<Vec<_>>());
+
+        let partial = container.load(&["mult", "test_or"]).unwrap();
+        assert_eq!(partial.modules.keys().collect::<Vec<_>>(), ["test_or", "mult"]);
+        assert_eq!(partial.modules["mult"].cells.len(), netlist.modules["mult"].cells.len());
+        assert_eq!(container.load_all().unwrap(), netlist);
+        assert!(matches!(container.load_module("nope"), Err(ContainerError::UnknownModule(_))));
+    }
+
+    #[test]
+    fn test_container() {
+        roundtrip(Compression::None);
+        assert!(matches!(Container::open(Cursor::new(b"{\"creator\": \"test\"}".to_vec())), Err(ContainerError::Format(_))));
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_container_deflate() {
+        roundtrip(Compression::Deflate);
+    }
+
+    #[cfg(not(feature = "deflate"))]
+    #[test]
+    fn test_container_deflate_disabled() {
+        let result = design().write_container(Vec::new(), Compression::Deflate);
+        assert!(matches!(result, Err(ContainerError::UnsupportedCompression(Compression::Deflate))));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::gates::function;
+use crate::{Bit, Cell, Direction, Module};
+
+/// Port values, least significant bit first.
+pub type PortValues = IndexMap<String, Vec<bool>>;
+
+/// Behavior of a cell the crate cannot evaluate itself, typically a vendor primitive or a
+/// blackbox module. Used for one-shot combinational evaluation by [`Module::evaluate`], and
+/// cycle by cycle by [`crate::sim::Simulator::with_models`].
+pub trait Model {
+    /// Computes the outputs of `instance` from the values of its inputs. Sequential models
+    /// keep their state between calls and find clock edges in the inputs they are given.
+    fn eval(&mut self, instance: &str, inputs: &PortValues) -> PortValues;
+}
+
+impl<F: FnMut(&str, &PortValues) -> PortValues> Model for F {
+    fn eval(&mut self, instance: &str, inputs: &PortValues) -> PortValues {
+        self(instance, inputs)
+    }
+}
+
+/// Models bound to cell instances or to every cell of a type. Instance bindings win.
+#[derive(Default)]
+pub struct Models {
+    types: IndexMap<String, Box<dyn Model>>,
+    instances: IndexMap<String, Box<dyn Model>>,
+}
+
+impl Models {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind_type(&mut self, module: &str, model: impl Model + 'static) -> &mut Self {
+        self.types.insert(module.to_string(), Box::new(model));
+        self
+    }
+
+    pub fn bind_instance(&mut self, cell: &str, model: impl Model + 'static) -> &mut Self {
+        self.instances.insert(cell.to_string(), Box::new(model));
+        self
+    }
+
+    pub(crate) fn get(&mut self, cell: &str, module: &str) -> Option<&mut Box<dyn Model>> {
+        match self.instances.contains_key(cell) {
+            true => self.instances.get_mut(cell),
+            false => self.types.get_mut(module),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CosimError {
+    /// A cell is neither a gate, a LUT nor a register, and has no model bound.
+    Unbound { cell: String, module: String },
+    /// A model left an output port out or returned the wrong number of bits for it.
+    BadOutput { cell: String, port: String },
+    /// Cells waiting on each other's outputs.
+    CombinationalLoop(Vec<String>),
+}
+
+impl fmt::Display for CosimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unbound { cell, module } => write!(f, "no model bound for cell {} of type {}", cell, module),
+            Self::BadOutput { cell, port } => write!(f, "model of cell {} returned no value of the right width for {}", cell, port),
+            Self::CombinationalLoop(cells) => write!(f, "combinational loop through {}", cells.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for CosimError {}
+
+enum Pending<'a> {
+    Function(Vec<Bit>, Bit, Vec<bool>),
+    Model(&'a str, &'a Cell),
+}
+
+impl Module {
+    /// Settles the combinational logic for the given input port values and returns the
+    /// output port values. Gates and LUTs are evaluated directly, other cells through the
+    /// bound `models`. Registers hold their `init` value, undriven and unknown bits read as 0.
+    /// Use [`crate::sim::Simulator::with_models`] to clock the module instead.
+    pub fn evaluate(&self, inputs: &PortValues, models: &mut Models) -> Result<PortValues, CosimError> {
+        let mut values: HashMap<Bit, bool> = self.initial_values();
+        values.extend([(Bit::_0, false), (Bit::_1, true), (Bit::X, false), (Bit::Z, false)]);
+        for (name, port) in self.ports.iter().filter(|(_, port)| port.direction != Direction::Output) {
+            let value = inputs.get(name.as_str());
+            for (index, bit) in port.bits.iter().enumerate() {
+                values.insert(*bit, value.and_then(|value| value.get(index)).copied().unwrap_or(false));
+            }
+        }
+        let mut pending = Vec::new();
+        for (name, cell) in self.cells.iter() {
+            if cell.is_register() {
+                for bit in cell.connections.get("Q").into_iter().flatten() {
+                    values.entry(*bit).or_insert(false);
+                }
+            } else if let Some((inputs, output, table)) = function(cell).filter(|_| models.get(name, &cell.module).is_none()) {
+                pending.push(Pending::Function(inputs, output, table));
+            } else if models.get(name, &cell.module).is_some() {
+                pending.push(Pending::Model(name, cell));
+            } else {
+                return Err(CosimError::Unbound { cell: name.to_string(), module: cell.module.to_string() });
+            }
+        }
+        let direction = |cell: &Cell, port: &str| cell.port_directions.get(port).cloned().unwrap_or_default();
+        let driven: HashSet<Bit> = pending
+            .iter()
+            .flat_map(|item| match item {
+                Pending::Function(_, output, _) => vec![*output],
+                Pending::Model(_, cell) => cell
+                    .connections
+                    .iter()
+                    .filter(|(port, _)| direction(cell, port) == Direction::Output)
+                    .flat_map(|(_, bits)| bits.clone())
+                    .collect(),
+            })
+            .collect();
+        for bit in self.cells.values().flat_map(|cell| cell.connections.values().flatten()) {
+            if !driven.contains(bit) {
+                values.entry(*bit).or_insert(false);
+            }
+        }
+
+        while !pending.is_empty() {
+            let before = pending.len();
+            let mut waiting = Vec::new();
+            for item in pending {
+                let ready = match &item {
+                    Pending::Function(inputs, _, _) => inputs.iter().all(|bit| values.contains_key(bit)),
+                    Pending::Model(_, cell) => cell
+                        .connections
+                        .iter()
+                        .filter(|(port, _)| direction(cell, port) != Direction::Output)
+                        .all(|(_, bits)| bits.iter().all(|bit| values.contains_key(bit))),
+                };
+                if !ready {
+                    waiting.push(item);
+                    continue;
+                }
+                match item {
+                    Pending::Function(inputs, output, table) => {
+                        let index = inputs.iter().enumerate().fold(0, |index, (input, bit)| index | (values[bit] as usize) << input);
+                        values.insert(output, table[index]);
+                    }
+                    Pending::Model(name, cell) => {
+                        let inputs: PortValues = cell
+                            .connections
+                            .iter()
+                            .filter(|(port, _)| direction(cell, port) != Direction::Output)
+                            .map(|(port, bits)| (port.to_string(), bits.iter().map(|bit| values[bit]).collect()))
+                            .collect();
+                        let outputs = models.get(name, &cell.module).unwrap().eval(name, &inputs);
+                        for (port, bits) in cell.connections.iter().filter(|(port, _)| direction(cell, port) == Direction::Output) {
+                            let bad_output = || CosimError::BadOutput { cell: name.to_string(), port: port.to_string() };
+                            let value = outputs.get(port).filter(|value| value.len() == bits.len()).ok_or_else(bad_output)?;
+                            values.extend(bits.iter().copied().zip(value.iter().copied()));
+                        }
+                    }
+                }
+            }
+            if waiting.len() == before {
+                let cells = waiting
+                    .iter()
+                    .map(|item| match item {
+                        Pending::Function(_, output, _) => self.bit_name(output),
+                        Pending::Model(name, _) => name.to_string(),
+                    })
+                    .collect();
+                return Err(CosimError::CombinationalLoop(cells));
+            }
+            pending = waiting;
+        }
+
+        Ok(self
+            .ports
+            .iter()
+            .filter(|(_, port)| port.direction != Direction::Input)
+            .map(|(name, port)| (name.to_string(), port.bits.iter().map(|bit| values.get(bit).copied().unwrap_or(false)).collect()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Port;
+
+    #[test]
+    fn test_evaluate_with_model() {
+        let mut module = Module::new();
+        module.ports.insert("a".into(), Port::new(Direction::Input, vec![Bit::Signal(2), Bit::Signal(3)]));
+        module.ports.insert("y".into(), Port::new(Direction::Output, vec![Bit::Signal(5), Bit::Signal(6)]));
+        module.cells.insert(
+            "and".into(),
+            Cell::new("$_AND_")
+                .with_connection("A", Direction::Input, vec![Bit::Signal(2)])
+                .with_connection("B", Direction::Input, vec![Bit::Signal(4)])
+                .with_connection("Y", Direction::Output, vec![Bit::Signal(5)]),
+        );
+        module.cells.insert(
+            "swap".into(),
+            Cell::new("VENDOR_SWAP").with_connection("I", Direction::Input, vec![Bit::Signal(2), Bit::Signal(3)]).with_connection(
+                "O",
+                Direction::Output,
+                vec![Bit::Signal(4), Bit::Signal(6)],
+            ),
+        );
+        let inputs = PortValues::from([("a".to_string(), vec![true, false])]);
+        assert_eq!(
+            module.evaluate(&inputs, &mut Models::new()),
+            Err(CosimError::Unbound { cell: "swap".to_string(), module: "VENDOR_SWAP".to_string() })
+        );
+
+        let mut models = Models::new();
+        models.bind_type("VENDOR_SWAP", |_: &str, inputs: &PortValues| {
+            PortValues::from([("O".to_string(), inputs["I"].iter().rev().copied().collect())])
+        });
+        assert_eq!(module.evaluate(&inputs, &mut models).unwrap()["y"], [false, true]);
+        let inputs = PortValues::from([("a".to_string(), vec![true, true])]);
+        assert_eq!(module.evaluate(&inputs, &mut models).unwrap()["y"], [true, true]);
+
+        models.bind_instance("swap", |_: &str, _: &PortValues| PortValues::new());
+        assert_eq!(module.evaluate(&inputs, &mut models), Err(CosimError::BadOutput { cell: "swap".to_string(), port: "O".to_string() }));
+    }
+
+    /// A counter primitive counting rising edges of `C` while `E` is high.
+    struct Counter {
+        clock: bool,
+        count: u64,
+    }
+
+    impl Model for Counter {
+        fn eval(&mut self, _: &str, inputs: &PortValues) -> PortValues {
+            let clock = inputs["C"][0];
+            if clock && !self.clock && inputs["E"][0] {
+                self.count += 1;
+            }
+            self.clock = clock;
+            PortValues::from([("Q".to_string(), (0..2).map(|index| self.count >> index & 1 == 1).collect())])
+        }
+    }
+
+    #[test]
+    fn test_simulate_with_model() {
+        use crate::sim::{Logic, SimError, Simulator};
+        let mut module = Module::new();
+        module.ports.insert("clk".into(), Port::new(Direction::Input, vec![Bit::Signal(2)]));
+        module.ports.insert("en".into(), Port::new(Direction::Input, vec![Bit::Signal(3)]));
+        module.ports.insert("q".into(), Port::new(Direction::Output, vec![Bit::Signal(4), Bit::Signal(5)]));
+        module.ports.insert("full".into(), Port::new(Direction::Output, vec![Bit::Signal(6)]));
+        module.cells.insert(
+            "cnt".into(),
+            Cell::new("VENDOR_CNT2")
+                .with_connection("C", Direction::Input, vec![Bit::Signal(2)])
+                .with_connection("E", Direction::Input, vec![Bit::Signal(3)])
+                .with_connection("Q", Direction::Output, vec![Bit::Signal(4), Bit::Signal(5)]),
+        );
+        module.cells.insert(
+            "and".into(),
+            Cell::new("$_AND_")
+                .with_connection("A", Direction::Input, vec![Bit::Signal(4)])
+                .with_connection("B", Direction::Input, vec![Bit::Signal(5)])
+                .with_connection("Y", Direction::Output, vec![Bit::Signal(6)]),
+        );
+        assert!(matches!(Simulator::new(&module), Err(SimError::Unsupported { .. })));
+
+        let mut models = Models::new();
+        models.bind_type("VENDOR_CNT2", Counter { clock: false, count: 0 });
+        let mut sim = Simulator::with_models(&module, models).unwrap();
+        sim.poke_u64("en", 1).unwrap();
+        for count in 1..=3 {
+            sim.step("clk").unwrap();
+            assert_eq!(sim.peek_u64("q").unwrap(), Some(count));
+        }
+        assert_eq!(sim.peek("full").unwrap(), [Logic::_1]);
+        sim.poke_u64("en", 0).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(3));
+        sim.poke_u64("en", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(0));
+        assert_eq!(sim.peek("full").unwrap(), [Logic::_0]);
+
+        let mut models = Models::new();
+        models.bind_instance("cnt", |_: &str, _: &PortValues| PortValues::new());
+        assert_eq!(
+            Simulator::with_models(&module, models).err(),
+            Some(SimError::Cosim(CosimError::BadOutput { cell: "cnt".to_string(), port: "Q".to_string() }))
+        );
+    }
+}
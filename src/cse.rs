@@ -0,0 +1,406 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use crate::formal::{ProofResult, ProveOptions};
+use crate::gates::gate;
+use crate::progress::pass_span;
+use crate::provenance::Provenance;
+use crate::rng::{SeedPolicy, SplitMix64};
+use crate::{Bit, Cell, Direction, IdString, Module};
+
+/// Cells that are not plain functions of their inputs, even though they are not registers.
+const OPAQUE_CELLS: &[&str] = &[
+    "$anyconst",
+    "$anyseq",
+    "$allconst",
+    "$allseq",
+    "$anyinit",
+    "$initstate",
+    "$assert",
+    "$assume",
+    "$cover",
+    "$live",
+    "$fair",
+    "$check",
+    "$print",
+    "$scopeinfo",
+    "$get_tag",
+    "$set_tag",
+    "$overwrite_tag",
+    "$original_tag",
+    "$future_ff",
+];
+
+/// Rounds of 64 random patterns simulated to reject a bad merge before proving it.
+const CHECK_ROUNDS: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub cells_before: usize,
+    pub cells_after: usize,
+    /// Removed cells by type.
+    pub merged: IndexMap<String, usize>,
+    /// Whether the merged module was proven equivalent to the original, `None` if the proof
+    /// could not be completed, for instance for cells the checker does not model. The module
+    /// is restored, and nothing counted as merged, when it is `Some(false)`.
+    pub equivalent: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StrashReport {
+    /// Gates replaced by an identical gate with identical inputs.
+    pub merged: usize,
+    /// Inverters of inverters, replaced by the signal inverted twice.
+    pub inverters: usize,
+    pub buffers: usize,
+}
+
+fn is_mergeable(cell: &Cell) -> bool {
+    cell.module.starts_with('$')
+        && !cell.is_register()
+        && !cell.module.starts_with("$mem")
+        && !OPAQUE_CELLS.contains(&cell.module.as_str())
+        && !cell.attributes.contains_key("keep")
+        && cell.connections.keys().all(|port| cell.port_directions.contains_key(port))
+        && cell.port_directions.values().any(|direction| *direction == Direction::Output)
+        && !cell.port_directions.values().any(|direction| *direction == Direction::InOut)
+}
+
+/// Type, parameters and inputs, identical for cells computing the same function of the same
+/// signals.
+fn structure(cell: &Cell) -> String {
+    let mut cell = cell.clone();
+    cell.canonicalize_inputs();
+    let parameters: BTreeMap<&String, &serde_json::Value> = cell.parameters.iter().collect();
+    let inputs: BTreeMap<&IdString, &Vec<Bit>> =
+        cell.connections.iter().filter(|(port, _)| cell.port_directions[*port] == Direction::Input).collect();
+    serde_json::to_string(&(&cell.module, parameters, inputs)).unwrap()
+}
+
+/// Values of every observable bit, output ports and inputs of non-gate cells, with free bits
+/// seeded from their signal number so two versions of a module see the same stimulus.
+fn simulate(module: &Module, seed: u64, round: usize) -> Option<BTreeMap<(String, String, usize), u64>> {
+    let mut values: HashMap<Bit, u64> = HashMap::from([(Bit::_0, 0), (Bit::_1, u64::MAX), (Bit::X, 0), (Bit::Z, 0)]);
+    let mut pending = Vec::new();
+    let mut driven = HashSet::new();
+    for cell in module.cells.values() {
+        match gate(&cell.module) {
+            Some(gate) => {
+                let inputs: Option<Vec<Bit>> = gate.inputs.iter().map(|port| cell.connections.get(*port)?.first().copied()).collect();
+                let output = *cell.connections.get(gate.output)?.first()?;
+                driven.insert(output);
+                pending.push((gate, inputs?, output));
+            }
+            None if cell.is_register() => {}
+            None => return None,
+        }
+    }
+    let mut free = |bit: &Bit| {
+        if let Bit::Signal(signal) = bit
+            && !driven.contains(bit)
+        {
+            values.entry(*bit).or_insert_with(|| SplitMix64::new(seed ^ signal ^ (round as u64) << 48).next_u64());
+        }
+    };
+    module.ports.values().flat_map(|port| port.bits.iter()).for_each(&mut free);
+    module.cells.values().flat_map(|cell| cell.connections.values().flatten()).for_each(&mut free);
+    while !pending.is_empty() {
+        let before = pending.len();
+        pending.retain(|(gate, inputs, output)| {
+            let Some(inputs) = inputs.iter().map(|bit| values.get(bit).copied()).collect::<Option<Vec<u64>>>() else {
+                return true;
+            };
+            values.insert(*output, (gate.eval)(&inputs));
+            false
+        });
+        if pending.len() == before {
+            return None;
+        }
+    }
+    let mut observed = BTreeMap::new();
+    for (name, port) in module.ports.iter().filter(|(_, port)| port.direction != Direction::Input) {
+        for (index, bit) in port.bits.iter().enumerate() {
+            observed.insert((String::new(), name.to_string(), index), values[bit]);
+        }
+    }
+    for (name, cell) in module.cells.iter().filter(|(_, cell)| gate(&cell.module).is_none()) {
+        for (port, bits) in cell.connections.iter().filter(|(port, _)| cell.port_directions.get(*port) != Some(&Direction::Output)) {
+            for (index, bit) in bits.iter().enumerate() {
+                observed.insert((name.to_string(), port.to_string(), index), values[bit]);
+            }
+        }
+    }
+    Some(observed)
+}
+
+impl Module {
+    /// Merges cells of the same type and parameters reading the same signals, keeping the
+    /// first and moving the loads of the others over, until no duplicates are left so whole
+    /// duplicated cones collapse. Registers, memories, cells with side effects and cells
+    /// marked `keep` are left alone. The result is checked against the original with
+    /// [`Module::check_equivalence`], after a quick random simulation of gate-level modules
+    /// with patterns drawn from `seed` under `policy`, and the original is put back if either
+    /// finds a difference.
+    pub fn merge_redundant_logic(&mut self, seed: u64, policy: SeedPolicy) -> MergeReport {
+        self.merge_redundant_logic_with(None, seed, policy)
+    }
+
+    pub(crate) fn merge_redundant_logic_with(
+        &mut self,
+        mut provenance: Option<&mut Provenance>,
+        seed: u64,
+        policy: SeedPolicy,
+    ) -> MergeReport {
+        pass_span!("merge_redundant_logic", cells = self.cells.len(), nets = self.nets.len(), seed);
+        let original = self.clone();
+        let tracked = provenance.as_deref().cloned();
+        let mut report = MergeReport { cells_before: self.cells.len(), ..MergeReport::default() };
+        loop {
+            let mut seen: HashMap<String, String> = HashMap::new();
+            let mut map = HashMap::new();
+            let mut duplicates = Vec::new();
+            for (name, cell) in self.cells.iter().filter(|(_, cell)| is_mergeable(cell)) {
+                let key = structure(cell);
+                let Some(kept) = seen.get(&key) else {
+                    seen.insert(key, name.to_string());
+                    continue;
+                };
+                let kept = &self.cells[kept];
+                for (port, bits) in cell.connections.iter().filter(|(port, _)| cell.port_directions[*port] == Direction::Output) {
+                    for (bit, replacement) in bits.iter().zip(kept.connections[port].iter()) {
+                        if matches!(bit, Bit::Signal(_)) && bit != replacement {
+                            map.insert(*bit, *replacement);
+                        }
+                    }
+                }
+                duplicates.push((name.clone(), seen[&key].clone()));
+            }
+            if duplicates.is_empty() {
+                break;
+            }
+            for (name, kept) in duplicates {
+                let cell = self.cells.shift_remove(&name).unwrap();
+                *report.merged.entry(cell.module.to_string()).or_default() += 1;
+                if let Some(provenance) = provenance.as_deref_mut() {
+                    provenance.merge_cell(&name, Some(&kept));
+                }
+            }
+            self.substitute(&map);
+            if let Some(provenance) = provenance.as_deref_mut() {
+                provenance.substitute(&map);
+            }
+        }
+        report.cells_after = self.cells.len();
+        if report.merged.is_empty() {
+            report.equivalent = Some(true);
+            return report;
+        }
+        report.equivalent = self.check_merge(original, policy.apply(seed));
+        if report.equivalent == Some(false) {
+            if let (Some(provenance), Some(tracked)) = (provenance, tracked) {
+                *provenance = tracked;
+            }
+            report.merged.clear();
+            report.cells_after = report.cells_before;
+        }
+        report
+    }
+}
+
+impl Module {
+    /// Proves this module equivalent to `original`, the same module before merging, after a
+    /// quick random simulation. Puts `original` back and returns `Some(false)` if either finds
+    /// a difference, `None` if the proof could not be completed.
+    fn check_merge(&mut self, original: Module, seed: u64) -> Option<bool> {
+        let mut rounds = (0..CHECK_ROUNDS).map(|round| Some(simulate(&original, seed, round)? == simulate(self, seed, round)?));
+        let equivalent = match rounds.all(|equal| equal != Some(false)) {
+            false => Some(false),
+            true => match original.check_equivalence(self, &ProveOptions { seed, ..ProveOptions::default() }).map(|report| report.result) {
+                Ok(ProofResult::Proven(_)) => Some(true),
+                Ok(ProofResult::Counterexample(_)) => Some(false),
+                Ok(ProofResult::Unknown { .. }) | Err(_) => None,
+            },
+        };
+        if equivalent == Some(false) {
+            *self = original;
+        }
+        equivalent
+    }
+
+    /// Gate-level cells in topological order, the cells of a combinational loop together after
+    /// those driving it.
+    fn gates_in_order(&self) -> Vec<&str> {
+        let (order, _) = self.dataflow_order();
+        order.into_iter().filter(|name| gate(&self.cells[*name].module).is_some()).collect()
+    }
+
+    /// Structural hashing of the gate-level cells in a single topological sweep: buffers are
+    /// bypassed, double inverters collapsed, and a gate with the same type and inputs as an
+    /// earlier one is replaced by it. Cells marked `keep` stay. Faster than
+    /// [`Module::merge_redundant_logic`], but only looks at gate-level primitives.
+    pub fn strash(&mut self) -> StrashReport {
+        self.strash_with(None)
+    }
+
+    pub(crate) fn strash_with(&mut self, provenance: Option<&mut Provenance>) -> StrashReport {
+        pass_span!("strash", cells = self.cells.len(), nets = self.nets.len());
+        let mut report = StrashReport::default();
+        let mut map: HashMap<Bit, Bit> = HashMap::new();
+        let mut table: HashMap<(&str, Vec<Bit>), (Bit, &str)> = HashMap::new();
+        let mut inverted: HashMap<Bit, Bit> = HashMap::new();
+        let mut removed = Vec::new();
+        for name in self.gates_in_order() {
+            let cell = &self.cells[name];
+            let gate = gate(&cell.module).unwrap();
+            let Some(mut inputs) = gate
+                .inputs
+                .iter()
+                .map(|port| cell.connections.get(*port)?.first().map(|bit| *map.get(bit).unwrap_or(bit)))
+                .collect::<Option<Vec<Bit>>>()
+            else {
+                continue;
+            };
+            let Some(&output) = cell.connections.get(gate.output).and_then(|bits| bits.first()) else { continue };
+            if cell.attributes.contains_key("keep") || !matches!(output, Bit::Signal(_)) {
+                continue;
+            }
+            let replacement = match cell.module.as_str() {
+                "$_BUF_" => {
+                    report.buffers += 1;
+                    Some((inputs[0], None))
+                }
+                "$_NOT_" if inverted.contains_key(&inputs[0]) => {
+                    report.inverters += 1;
+                    Some((inverted[&inputs[0]], None))
+                }
+                module => {
+                    if cell.is_commutative() {
+                        inputs.sort();
+                    }
+                    if module == "$_NOT_" {
+                        inverted.insert(output, inputs[0]);
+                    }
+                    match table.get(&(module, inputs.clone())) {
+                        Some((existing, kept)) => {
+                            report.merged += 1;
+                            Some((*existing, Some(*kept)))
+                        }
+                        None => {
+                            table.insert((module, inputs), (output, name));
+                            None
+                        }
+                    }
+                }
+            };
+            if let Some((replacement, kept)) = replacement {
+                map.insert(output, replacement);
+                removed.push((name.to_string(), kept.map(str::to_string)));
+            }
+        }
+        for (name, _) in removed.iter() {
+            self.cells.shift_remove(name);
+        }
+        self.substitute(&map);
+        if let Some(provenance) = provenance {
+            removed.iter().for_each(|(name, kept)| provenance.merge_cell(name, kept.as_deref()));
+            provenance.substitute(&map);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{IdString, Netlist};
+
+    #[test]
+    fn test_merge_redundant_logic() {
+        let gate = |module: &str, a: u64, b: u64, y: u64| json!({ "type": module, "port_directions": { "A": "input", "B": "input", "Y": "output" }, "connections": { "A": [a], "B": [b], "Y": [y] } });
+        let mut netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": { "top": {
+                "ports": {
+                    "a": { "direction": "input", "bits": [2, 3, 4] },
+                    "y": { "direction": "output", "bits": [7, 8, 9] }
+                },
+                "cells": {
+                    "and0": gate("$_AND_", 2, 3, 5),
+                    "and1": gate("$_AND_", 3, 2, 6),
+                    "or0": gate("$_OR_", 5, 4, 7),
+                    "or1": gate("$_OR_", 6, 4, 8),
+                    "xor": gate("$_XOR_", 5, 6, 9),
+                    "kept": { "type": "$_AND_", "attributes": { "keep": 1 }, "port_directions": { "A": "input", "B": "input", "Y": "output" }, "connections": { "A": [2], "B": [3], "Y": [10] } }
+                }
+            } }
+        }))
+        .unwrap();
+        let module = &mut netlist.modules["top"];
+        let report = module.merge_redundant_logic(0, SeedPolicy::Explicit);
+        assert_eq!(report.cells_before, 6);
+        assert_eq!(report.cells_after, 4);
+        assert_eq!(report.merged.into_iter().collect::<Vec<_>>(), [("$_AND_".to_string(), 1), ("$_OR_".to_string(), 1)]);
+        assert_eq!(report.equivalent, Some(true));
+        assert_eq!(module.ports["y"].bits, [Bit::Signal(7), Bit::Signal(7), Bit::Signal(9)]);
+        assert_eq!(module.cells["xor"].connections["B"], [Bit::Signal(5)]);
+        assert!(module.cells.contains_key("kept"));
+    }
+
+    #[test]
+    fn test_strash() {
+        let cell = |module: &str, inputs: &[(&str, u64)], y: u64| {
+            let mut cell = Cell::new(module).with_connection("Y", Direction::Output, vec![Bit::Signal(y)]);
+            for (port, bit) in inputs {
+                cell = cell.with_connection(port, Direction::Input, vec![Bit::Signal(*bit)]);
+            }
+            cell
+        };
+        let mut module = Module::new();
+        module.ports.insert("y".into(), crate::Port::new(Direction::Output, vec![Bit::Signal(9), Bit::Signal(10)]));
+        // Listed out of order, so the sweep has to sort them first.
+        module.cells.insert("or1".into(), cell("$_OR_", &[("A", 8), ("B", 4)], 10));
+        module.cells.insert("or0".into(), cell("$_OR_", &[("A", 7), ("B", 4)], 9));
+        module.cells.insert("and1".into(), cell("$_AND_", &[("A", 6), ("B", 2)], 8));
+        module.cells.insert("and0".into(), cell("$_AND_", &[("A", 2), ("B", 3)], 7));
+        module.cells.insert("not1".into(), cell("$_NOT_", &[("A", 5)], 6));
+        module.cells.insert("not0".into(), cell("$_NOT_", &[("A", 3)], 5));
+
+        let report = module.strash();
+        assert_eq!(report, StrashReport { merged: 2, inverters: 1, buffers: 0 });
+        // The first gate reached in topological order is the one kept.
+        assert_eq!(module.cells.keys().collect::<Vec<_>>(), ["or1", "and1", "not0"]);
+        assert_eq!(module.cells["and1"].connections["A"], [Bit::Signal(3)]);
+        assert_eq!(module.ports["y"].bits, [Bit::Signal(10), Bit::Signal(10)]);
+    }
+
+    #[test]
+    fn test_merge_adder() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let module = &mut netlist.modules["adder"];
+        let mut doubled = module.clone();
+        let copies: Vec<(IdString, Cell)> =
+            module.cells.iter().map(|(name, cell)| (format!("{}_copy", name).into(), cell.clone())).collect();
+        doubled.cells.extend(copies);
+        let report = doubled.merge_redundant_logic(0, SeedPolicy::Explicit);
+        assert_eq!(report.cells_after, module.cells.len());
+        assert_eq!(report.equivalent, Some(true));
+    }
+
+    #[test]
+    fn test_check_merge_restores() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let original = &netlist.modules["adder"];
+        let mut broken = original.clone();
+        let (name, _) = broken.cells.iter().find(|(_, cell)| cell.module == "$_XOR_").unwrap();
+        let name = name.clone();
+        broken.cells[&name].module = "$_XNOR_".into();
+        assert_eq!(broken.check_merge(original.clone(), 0), Some(false));
+        assert_eq!(broken.cells[&name].module, "$_XOR_");
+
+        let mut same = original.clone();
+        assert_eq!(same.check_merge(original.clone(), 0), Some(true));
+    }
+}
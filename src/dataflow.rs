@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::io;
+
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::gates::gate;
+use crate::{Bit, Direction, Module, Netlist};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Input,
+    Output,
+    /// A word-level cell, or a recognized gate-level datapath block.
+    Operator,
+    /// Registers of one bus, or a single word-level register.
+    Register,
+    /// All ports of one memory.
+    Memory,
+    /// An instance of a user module.
+    Instance,
+    /// Connected gate-level cells not belonging to any other node.
+    Logic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataflowNode {
+    pub name: String,
+    pub kind: NodeKind,
+    /// Cell type, datapath block kind or port direction.
+    pub op: String,
+    /// Number of bits the node drives, or receives for outputs.
+    pub width: usize,
+    pub cells: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataflowEdge {
+    pub from: usize,
+    pub to: usize,
+    pub width: usize,
+}
+
+/// Word-level view of a module. Edges connect nodes by index, constants are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataflowGraph {
+    pub nodes: Vec<DataflowNode>,
+    pub edges: Vec<DataflowEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Group {
+    Port(String),
+    Cell(String),
+    Register(String),
+    Memory(String),
+    Block(usize),
+    Logic(String),
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl DataflowGraph {
+    pub fn to_writer(&self, writer: impl io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    /// Writes the graph in Graphviz DOT format, edges labelled with their width.
+    pub fn write_dot(&self, name: &str, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(writer, "digraph \"{}\" {{", escape(name))?;
+        writeln!(writer, "  rankdir=LR;")?;
+        for (index, node) in self.nodes.iter().enumerate() {
+            let shape = match node.kind {
+                NodeKind::Input => "invhouse",
+                NodeKind::Output => "house",
+                NodeKind::Operator => "ellipse",
+                NodeKind::Register => "box",
+                NodeKind::Memory => "box3d",
+                NodeKind::Instance => "component",
+                NodeKind::Logic => "octagon",
+            };
+            let label = format!("{}\\n{} [{}]", escape(&node.name), escape(&node.op), node.width);
+            writeln!(writer, "  n{} [shape={}, label=\"{}\"];", index, shape, label)?;
+        }
+        for edge in self.edges.iter() {
+            writeln!(writer, "  n{} -> n{} [label=\"{}\"];", edge.from, edge.to, edge.width)?;
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+fn find(parent: &mut HashMap<String, String>, name: &str) -> String {
+    let mut root = name.to_string();
+    while let Some(next) = parent.get(&root).filter(|next| **next != root) {
+        root = next.clone();
+    }
+    parent.insert(name.to_string(), root.clone());
+    root
+}
+
+impl Module {
+    /// Assigns every cell to a node, with the name and kind of every datapath block.
+    fn groups(&self) -> (IndexMap<String, Group>, Vec<(String, String)>) {
+        let mut groups = IndexMap::new();
+        let mut blocks = Vec::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for block in self.recognize_datapath().blocks.into_iter().filter(|block| block.cells.len() > 1) {
+            let index = blocks.len();
+            let mut used = false;
+            for cell in block.cells {
+                if !groups.contains_key(&cell) {
+                    groups.insert(cell, Group::Block(index));
+                    used = true;
+                }
+            }
+            if used {
+                let count = counts.entry(block.kind.to_string()).or_default();
+                blocks.push((format!("{}{}", block.kind, count), block.kind.to_string()));
+                *count += 1;
+            }
+        }
+
+        let mut parent: HashMap<String, String> = HashMap::new();
+        let mut logic: HashMap<Bit, Vec<&str>> = HashMap::new();
+        for (name, cell) in self.cells.iter() {
+            if groups.contains_key(name.as_str()) {
+                continue;
+            }
+            let group = if let Some(memid) = cell.parameters.get("MEMID").and_then(|memid| memid.as_str()) {
+                Group::Memory(memid.to_string())
+            } else if cell.is_register() {
+                let q = cell.connections.get("Q").and_then(|bits| bits.first());
+                match q.and_then(|bit| self.bus_of(bit)).filter(|_| cell.module.starts_with("$_")) {
+                    Some(bus) => Group::Register(bus.to_string()),
+                    None => Group::Cell(name.to_string()),
+                }
+            } else if gate(&cell.module).is_some() {
+                for bits in cell.connections.values() {
+                    bits.iter().filter(|bit| matches!(bit, Bit::Signal(_))).for_each(|bit| logic.entry(*bit).or_default().push(name));
+                }
+                parent.insert(name.to_string(), name.to_string());
+                continue;
+            } else {
+                Group::Cell(name.to_string())
+            };
+            groups.insert(name.to_string(), group);
+        }
+
+        for cells in logic.values() {
+            let root = find(&mut parent, cells[0]);
+            for cell in &cells[1..] {
+                let other = find(&mut parent, cell);
+                parent.insert(other, root.clone());
+            }
+        }
+        for name in self.cells.keys() {
+            if parent.contains_key(name.as_str()) {
+                groups.insert(name.to_string(), Group::Logic(find(&mut parent, name)));
+            }
+        }
+        (groups, blocks)
+    }
+
+    /// Builds a word-level dataflow graph. Word-level cells stay as they are, while gate-level
+    /// cells are collapsed into recognized datapath blocks, registers of the same bus and
+    /// clouds of connected logic.
+    pub fn dataflow_graph(&self) -> DataflowGraph {
+        let (groups, blocks) = self.groups();
+        let mut index: IndexMap<Group, DataflowNode> = IndexMap::new();
+        let mut drivers: HashMap<Bit, usize> = HashMap::new();
+        let mut loads: Vec<Vec<Bit>> = Vec::new();
+
+        for (name, port) in self.ports.iter() {
+            let (kind, op) = match port.direction {
+                Direction::Output => (NodeKind::Output, "output"),
+                Direction::Input => (NodeKind::Input, "input"),
+                Direction::InOut => (NodeKind::Input, "inout"),
+            };
+            let node = DataflowNode { name: name.to_string(), kind, op: op.to_string(), width: port.bits.len(), cells: Vec::new() };
+            let (id, _) = index.insert_full(Group::Port(name.to_string()), node);
+            loads.push(Vec::new());
+            match kind {
+                NodeKind::Output => loads[id].extend(port.bits.iter().copied()),
+                _ => port.bits.iter().for_each(|bit| _ = drivers.insert(*bit, id)),
+            }
+        }
+
+        for (name, cell) in self.cells.iter() {
+            let group = groups[name.as_str()].clone();
+            let id = match index.get_index_of(&group) {
+                Some(id) => id,
+                None => {
+                    let (node_name, kind, op) = match &group {
+                        Group::Block(block) => (blocks[*block].0.clone(), NodeKind::Operator, blocks[*block].1.clone()),
+                        Group::Register(bus) => (bus.clone(), NodeKind::Register, cell.module.to_string()),
+                        Group::Memory(memid) => (memid.clone(), NodeKind::Memory, "memory".to_string()),
+                        Group::Logic(_) => (format!("logic{}", index.len()), NodeKind::Logic, "logic".to_string()),
+                        Group::Cell(_) if cell.is_register() => (name.to_string(), NodeKind::Register, cell.module.to_string()),
+                        Group::Cell(_) if !cell.module.starts_with('$') => (name.to_string(), NodeKind::Instance, cell.module.to_string()),
+                        Group::Cell(_) | Group::Port(_) => (name.to_string(), NodeKind::Operator, cell.module.to_string()),
+                    };
+                    loads.push(Vec::new());
+                    index.insert_full(group, DataflowNode { name: node_name, kind, op, width: 0, cells: Vec::new() }).0
+                }
+            };
+            index[id].cells.push(name.to_string());
+            for (port, bits) in cell.connections.iter() {
+                let signals = bits.iter().filter(|bit| matches!(bit, Bit::Signal(_)));
+                match cell.port_directions.get(port) {
+                    Some(Direction::Output) => signals.for_each(|bit| _ = drivers.insert(*bit, id)),
+                    _ => loads[id].extend(signals),
+                }
+            }
+        }
+
+        let mut graph = DataflowGraph { nodes: Vec::new(), edges: Vec::new() };
+        let mut outgoing: Vec<IndexSet<Bit>> = vec![IndexSet::new(); index.len()];
+        for (to, bits) in loads.iter().enumerate() {
+            let mut edges: IndexMap<usize, IndexSet<Bit>> = IndexMap::new();
+            for bit in bits {
+                match drivers.get(bit) {
+                    Some(from) if *from != to => _ = edges.entry(*from).or_default().insert(*bit),
+                    _ => (),
+                }
+            }
+            for (from, bits) in edges {
+                outgoing[from].extend(bits.iter().copied());
+                graph.edges.push(DataflowEdge { from, to, width: bits.len() });
+            }
+        }
+        for ((_, mut node), outgoing) in index.into_iter().zip(outgoing) {
+            if !matches!(node.kind, NodeKind::Input | NodeKind::Output) {
+                node.width = outgoing.len();
+            }
+            graph.nodes.push(node);
+        }
+        graph
+    }
+}
+
+impl Netlist {
+    pub fn dataflow_graphs(&self) -> IndexMap<String, DataflowGraph> {
+        self.modules.iter().map(|(name, module)| (name.to_string(), module.dataflow_graph())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn node<'a>(graph: &'a DataflowGraph, name: &str) -> (usize, &'a DataflowNode) {
+        graph.nodes.iter().enumerate().find(|(_, node)| node.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_word_level() {
+        let dff = |d: u64, q: u64| {
+            json!({
+                "type": "$_DFF_P_",
+                "port_directions": { "C": "input", "D": "input", "Q": "output" },
+                "connections": { "C": [2], "D": [d], "Q": [q] }
+            })
+        };
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "clk": { "direction": "input", "bits": [2] },
+                        "a": { "direction": "input", "bits": [3, 4] },
+                        "y": { "direction": "output", "bits": [7, 8] }
+                    },
+                    "cells": {
+                        "add": {
+                            "type": "$add",
+                            "parameters": { "A_WIDTH": "00000000000000000000000000000010", "B_WIDTH": 2, "Y_WIDTH": 2 },
+                            "port_directions": { "A": "input", "B": "input", "Y": "output" },
+                            "connections": { "A": [3, 4], "B": [7, 8], "Y": [5, 6] }
+                        },
+                        "q0": dff(5, 7),
+                        "q1": dff(6, 8)
+                    },
+                    "netnames": {
+                        "a": { "bits": [3, 4] },
+                        "sum": { "bits": [5, 6] },
+                        "y": { "bits": [7, 8] }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let graph = netlist.modules["top"].dataflow_graph();
+        assert_eq!(graph.nodes.len(), 5);
+        let (add, _) = node(&graph, "add");
+        let output = graph.nodes.iter().position(|node| node.kind == NodeKind::Output).unwrap();
+        let register = graph.nodes.iter().position(|node| node.kind == NodeKind::Register).unwrap();
+        let registers = &graph.nodes[register];
+        assert_eq!((registers.name.as_str(), registers.cells.len(), registers.width), ("y", 2, 2));
+        assert!(graph.edges.contains(&DataflowEdge { from: add, to: register, width: 2 }));
+        assert!(graph.edges.contains(&DataflowEdge { from: register, to: add, width: 2 }));
+        assert!(graph.edges.contains(&DataflowEdge { from: register, to: output, width: 2 }));
+
+        let mut dot = Vec::new();
+        graph.write_dot("top", &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph \"top\" {"));
+        assert!(dot.contains(&format!("n{} -> n{} [label=\"2\"];", add, register)));
+    }
+
+    #[test]
+    fn test_gate_level() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let graph = netlist.modules["adder"].dataflow_graph();
+        let cells: usize = graph.nodes.iter().map(|node| node.cells.len()).sum();
+        assert_eq!(cells, netlist.modules["adder"].cells.len());
+        assert!(graph.nodes.iter().any(|node| node.name == "ripple_adder0" && node.op == "ripple_adder"));
+        assert!(graph.nodes.len() < 10);
+        let (output, _) = node(&graph, "c");
+        assert_eq!(graph.edges.iter().filter(|edge| edge.to == output).map(|edge| edge.width).sum::<usize>(), 17);
+    }
+}
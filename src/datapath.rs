@@ -0,0 +1,700 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::gates::{Gate, gate};
+use crate::param::ParamValue;
+use crate::progress::pass_span;
+use crate::{Bit, Module, Netlist};
+
+/// Number of cuts kept per node, smallest first.
+const MAX_CUTS: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatapathKind {
+    /// A word-level adder or subtractor cell (`$add`, `$sub`, `$alu`, …).
+    Adder,
+    /// A chain of full adders, each stage's carry feeding the next one.
+    RippleAdder,
+    /// Propagate/generate pairs over two buses without a ripple chain, as built by
+    /// carry-lookahead and parallel-prefix adders.
+    PrefixAdder,
+    /// A word-level multiplier, or a grid of partial products over two buses.
+    Multiplier,
+    /// XORs of adjacent bits of one bus selecting multiples of another, as in radix-4 Booth
+    /// multipliers.
+    BoothRecoder,
+    /// A word-level comparison, or XORs of two buses reduced to a single equality bit.
+    Comparator,
+}
+
+impl fmt::Display for DatapathKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Adder => "adder",
+            Self::RippleAdder => "ripple_adder",
+            Self::PrefixAdder => "prefix_adder",
+            Self::Multiplier => "multiplier",
+            Self::BoothRecoder => "booth_recoder",
+            Self::Comparator => "comparator",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatapathBlock {
+    pub kind: DatapathKind,
+    /// Operand width in bits, the wider operand for multipliers.
+    pub width: usize,
+    /// Nets the operands were recognized on, where they are named buses.
+    pub operands: Vec<String>,
+    pub cells: Vec<String>,
+}
+
+/// Arithmetic structures found in a module.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatapathSummary {
+    pub blocks: Vec<DatapathBlock>,
+}
+
+impl DatapathSummary {
+    pub fn count(&self, kind: DatapathKind) -> usize {
+        self.blocks.iter().filter(|block| block.kind == kind).count()
+    }
+
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+}
+
+pub type DesignDatapathSummary = IndexMap<String, DatapathSummary>;
+
+/// A set of at most three leaves and the function of a node over them, one row per bit.
+#[derive(Debug, Clone)]
+struct Cut {
+    leaves: Vec<Bit>,
+    table: u8,
+}
+
+impl Cut {
+    fn leaf(bit: Bit) -> Cut {
+        Cut { leaves: vec![bit], table: 0b10 }
+    }
+
+    /// The table of `self` over `leaves`, a superset of its own leaves.
+    fn expand(&self, leaves: &[Bit]) -> u64 {
+        let positions: Vec<usize> = self.leaves.iter().map(|leaf| leaves.iter().position(|l| l == leaf).unwrap()).collect();
+        let mut table = 0;
+        for row in 0..1 << leaves.len() {
+            let index = positions.iter().enumerate().fold(0, |index, (i, position)| index | (row >> position & 1) << i);
+            table |= (self.table as u64 >> index & 1) << row;
+        }
+        table
+    }
+}
+
+fn majority_tables() -> HashSet<u8> {
+    let mut tables = HashSet::new();
+    for polarity in 0..16u8 {
+        let mut table = 0u8;
+        for row in 0..8 {
+            let inputs = row ^ (polarity & 7);
+            let majority = (inputs & 1) + (inputs >> 1 & 1) + (inputs >> 2 & 1) >= 2;
+            if majority != (polarity & 8 != 0) {
+                table |= 1 << row;
+            }
+        }
+        tables.insert(table);
+    }
+    tables
+}
+
+const XOR3: [u8; 2] = [0x96, 0x69];
+const XOR2: [u8; 2] = [0x6, 0x9];
+const AND2: [u8; 2] = [0x8, 0x7];
+
+const REDUCTION_GATES: &[&str] =
+    &["$_AND_", "$_NAND_", "$_OR_", "$_NOR_", "$_ANDNOT_", "$_ORNOT_", "$_NOT_", "$_AOI3_", "$_OAI3_", "$_AOI4_", "$_OAI4_"];
+
+const WORD_LEVEL: &[(&str, DatapathKind)] = &[
+    ("$add", DatapathKind::Adder),
+    ("$sub", DatapathKind::Adder),
+    ("$neg", DatapathKind::Adder),
+    ("$alu", DatapathKind::Adder),
+    ("$mul", DatapathKind::Multiplier),
+    ("$macc", DatapathKind::Multiplier),
+    ("$lt", DatapathKind::Comparator),
+    ("$le", DatapathKind::Comparator),
+    ("$gt", DatapathKind::Comparator),
+    ("$ge", DatapathKind::Comparator),
+    ("$eq", DatapathKind::Comparator),
+    ("$ne", DatapathKind::Comparator),
+    ("$eqx", DatapathKind::Comparator),
+    ("$nex", DatapathKind::Comparator),
+];
+
+/// Reads an integer parameter, written by Yosys either as a number or as a binary string.
+fn parameter(value: &serde_json::Value) -> Option<usize> {
+    ParamValue::from_value(value)?.as_u64().map(|value| value as usize)
+}
+
+/// Cell names in first-seen order, without the duplicates of overlapping cones.
+fn names(cells: Vec<&str>) -> Vec<String> {
+    cells.into_iter().collect::<IndexSet<&str>>().into_iter().map(str::to_string).collect()
+}
+
+/// Bit positions matched so far, and the cells implementing them.
+type Matches<'a, T> = (HashSet<T>, Vec<&'a str>);
+
+struct Node<'a> {
+    cell: &'a str,
+    module: &'a str,
+    gate: Gate,
+    inputs: Vec<Bit>,
+}
+
+/// A half or full adder: a sum and a carry computed over the same leaves.
+struct Stage {
+    leaves: Vec<Bit>,
+    sum: Bit,
+    carry: Bit,
+}
+
+struct Graph<'a> {
+    nodes: HashMap<Bit, Node<'a>>,
+    readers: HashMap<Bit, Vec<Bit>>,
+    cuts: HashMap<Bit, Vec<Cut>>,
+    /// Order in which nodes were visited, every node after its fanin.
+    order: Vec<Bit>,
+    buses: HashMap<Bit, (&'a str, usize)>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut nodes = HashMap::new();
+        for (name, cell) in module.cells.iter() {
+            let Some(gate) = gate(&cell.module) else {
+                continue;
+            };
+            let single = |port: &str| cell.connections.get(port).filter(|bits| bits.len() == 1).map(|bits| bits[0]);
+            let inputs: Option<Vec<Bit>> = gate.inputs.iter().map(|port| single(port)).collect();
+            if let (Some(inputs), Some(output @ Bit::Signal(_))) = (inputs, single(gate.output)) {
+                nodes.insert(output, Node { cell: name, module: &cell.module, gate, inputs });
+            }
+        }
+        let mut readers: HashMap<Bit, Vec<Bit>> = HashMap::new();
+        for (output, node) in nodes.iter() {
+            for input in node.inputs.iter() {
+                readers.entry(*input).or_default().push(*output);
+            }
+        }
+
+        // Ports name buses best, then public nets, then whatever net comes first.
+        let mut buses: HashMap<Bit, (&str, usize, u8)> = HashMap::new();
+        let named = module.ports.iter().map(|(name, port)| (name, &port.bits, 0));
+        let nets = module.nets.iter().map(|(name, net)| (name, &net.bits, if net.hide_name { 2 } else { 1 }));
+        for (name, bits, rank) in named.chain(nets).filter(|(_, bits, _)| bits.len() > 1) {
+            for (offset, bit) in bits.iter().enumerate() {
+                let entry = buses.entry(*bit).or_insert((name, offset, rank));
+                if rank < entry.2 {
+                    *entry = (name, offset, rank);
+                }
+            }
+        }
+        let buses = buses.into_iter().map(|(bit, (name, offset, _))| (bit, (name, offset))).collect();
+
+        let mut graph = Graph { nodes, readers, cuts: HashMap::new(), order: Vec::new(), buses };
+        let mut roots: Vec<Bit> = graph.nodes.keys().copied().collect();
+        roots.sort();
+        for root in roots {
+            graph.visit(root);
+        }
+        graph
+    }
+
+    /// Computes the cuts of `root` and its fanin, treating nodes on a combinational loop as
+    /// leaves.
+    fn visit(&mut self, root: Bit) {
+        let mut stack = vec![(root, false)];
+        let mut active = HashSet::new();
+        while let Some((bit, expanded)) = stack.pop() {
+            if self.cuts.contains_key(&bit) {
+                continue;
+            }
+            let inputs = self.nodes[&bit].inputs.clone();
+            if !expanded {
+                active.insert(bit);
+                stack.push((bit, true));
+                for input in inputs {
+                    if self.nodes.contains_key(&input) && !self.cuts.contains_key(&input) && !active.contains(&input) {
+                        stack.push((input, false));
+                    }
+                }
+                continue;
+            }
+            active.remove(&bit);
+            let cuts = self.merge(bit, &inputs);
+            self.cuts.insert(bit, cuts);
+            self.order.push(bit);
+        }
+    }
+
+    fn merge(&self, bit: Bit, inputs: &[Bit]) -> Vec<Cut> {
+        let fanin: Vec<Vec<Cut>> =
+            inputs.iter().map(|input| self.cuts.get(input).cloned().unwrap_or_else(|| vec![Cut::leaf(*input)])).collect();
+        let mut merged: BTreeMap<Vec<Bit>, u8> = BTreeMap::new();
+        let mut choice = vec![0; fanin.len()];
+        'product: loop {
+            let cuts: Vec<&Cut> = choice.iter().zip(fanin.iter()).map(|(index, cuts)| &cuts[*index]).collect();
+            let mut leaves: Vec<Bit> = cuts.iter().flat_map(|cut| cut.leaves.iter().copied()).collect();
+            leaves.sort();
+            leaves.dedup();
+            if leaves.len() <= 3 && !merged.contains_key(&leaves) {
+                let tables: Vec<u64> = cuts.iter().map(|cut| cut.expand(&leaves)).collect();
+                let mask = (1u64 << (1 << leaves.len())) - 1;
+                merged.insert(leaves, ((self.nodes[&bit].gate.eval)(&tables) & mask) as u8);
+            }
+            for (index, cuts) in choice.iter_mut().zip(fanin.iter()) {
+                *index += 1;
+                if *index < cuts.len() {
+                    continue 'product;
+                }
+                *index = 0;
+            }
+            break;
+        }
+        let mut cuts: Vec<Cut> = merged.into_iter().map(|(leaves, table)| Cut { leaves, table }).collect();
+        cuts.sort_by_key(|cut| cut.leaves.len());
+        cuts.truncate(MAX_CUTS - 1);
+        cuts.insert(0, Cut::leaf(bit));
+        cuts
+    }
+
+    /// Cells between `root` and `leaves`.
+    fn cone(&self, root: Bit, leaves: &[Bit], cells: &mut Vec<&'a str>) {
+        let mut stack = vec![root];
+        let mut seen = HashSet::new();
+        while let Some(bit) = stack.pop() {
+            if leaves.contains(&bit) || !seen.insert(bit) {
+                continue;
+            }
+            if let Some(node) = self.nodes.get(&bit) {
+                cells.push(node.cell);
+                stack.extend(node.inputs.iter().copied());
+            }
+        }
+    }
+
+    fn cuts_of(&self, size: usize) -> impl Iterator<Item = (Bit, &Cut)> {
+        self.order.iter().flat_map(move |bit| self.cuts[bit].iter().filter(move |cut| cut.leaves.len() == size).map(move |cut| (*bit, cut)))
+    }
+
+    fn operands(&self, leaves: impl IntoIterator<Item = Bit>) -> Vec<String> {
+        let mut operands: Vec<String> = Vec::new();
+        for bit in leaves {
+            if let Some((name, _)) = self.buses.get(&bit)
+                && !operands.iter().any(|operand| operand == name)
+            {
+                operands.push(name.to_string());
+            }
+        }
+        operands
+    }
+
+    fn full_adders(&self) -> Vec<Stage> {
+        let majority = majority_tables();
+        let mut found: IndexMap<&[Bit], (Option<Bit>, Option<Bit>)> = IndexMap::new();
+        for (bit, cut) in self.cuts_of(3) {
+            let entry = found.entry(&cut.leaves).or_default();
+            if XOR3.contains(&cut.table) {
+                entry.0.get_or_insert(bit);
+            } else if majority.contains(&cut.table) {
+                entry.1.get_or_insert(bit);
+            }
+        }
+        found
+            .into_iter()
+            .filter_map(|(leaves, stage)| match stage {
+                (Some(sum), Some(carry)) => Some(Stage { leaves: leaves.to_vec(), sum, carry }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn half_adders(&self, covered: &HashSet<&str>) -> Vec<Stage> {
+        let mut found: IndexMap<&[Bit], (Option<Bit>, Option<Bit>)> = IndexMap::new();
+        for (bit, cut) in self.cuts_of(2) {
+            if covered.contains(self.nodes[&bit].cell) {
+                continue;
+            }
+            let entry = found.entry(&cut.leaves).or_default();
+            if XOR2.contains(&cut.table) {
+                entry.0.get_or_insert(bit);
+            } else if AND2.contains(&cut.table) {
+                entry.1.get_or_insert(bit);
+            }
+        }
+        found
+            .into_iter()
+            .filter_map(|(leaves, stage)| match stage {
+                (Some(sum), Some(carry)) => Some(Stage { leaves: leaves.to_vec(), sum, carry }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn stage_cells(&self, stage: &Stage, cells: &mut Vec<&'a str>) {
+        self.cone(stage.sum, &stage.leaves, cells);
+        self.cone(stage.carry, &stage.leaves, cells);
+    }
+
+    fn adders(&self, blocks: &mut Vec<DatapathBlock>) {
+        let full = self.full_adders();
+        let mut covered = Vec::new();
+        full.iter().for_each(|stage| self.stage_cells(stage, &mut covered));
+        let half = self.half_adders(&covered.into_iter().collect());
+
+        // Half adders only start a chain, full adders continue it.
+        let stages: Vec<&Stage> = half.iter().chain(full.iter()).collect();
+        let mut next: HashMap<usize, usize> = HashMap::new();
+        let mut has_previous = HashSet::new();
+        for (index, stage) in stages.iter().enumerate() {
+            let successor = (half.len()..stages.len())
+                .find(|other| *other != index && !has_previous.contains(other) && stages[*other].leaves.contains(&stage.carry));
+            if let Some(successor) = successor {
+                next.insert(index, successor);
+                has_previous.insert(successor);
+            }
+        }
+
+        let mut chained = HashSet::new();
+        for start in (0..stages.len()).filter(|index| !has_previous.contains(index) && next.contains_key(index)) {
+            let mut chain = vec![start];
+            while let Some(successor) = next.get(chain.last().unwrap()).filter(|successor| !chain.contains(successor)) {
+                chain.push(*successor);
+            }
+            let mut cells = Vec::new();
+            let mut leaves = Vec::new();
+            for (position, index) in chain.iter().enumerate() {
+                self.stage_cells(stages[*index], &mut cells);
+                let carry_in = position.checked_sub(1).map(|previous| stages[chain[previous]].carry);
+                leaves.extend(stages[*index].leaves.iter().copied().filter(|leaf| Some(*leaf) != carry_in));
+            }
+            chained.extend(chain.iter().copied());
+            blocks.push(DatapathBlock {
+                kind: DatapathKind::RippleAdder,
+                width: chain.len(),
+                operands: self.operands(leaves),
+                cells: names(cells),
+            });
+        }
+
+        // Propagate/generate pairs over the same offset of two buses, left over after the
+        // ripple chains.
+        let mut groups: IndexMap<(&str, &str), Vec<&Stage>> = IndexMap::new();
+        for (_, stage) in half.iter().enumerate().filter(|(index, _)| !chained.contains(index)) {
+            let (Some(a), Some(b)) = (self.buses.get(&stage.leaves[0]), self.buses.get(&stage.leaves[1])) else {
+                continue;
+            };
+            if a.0 != b.0 && a.1 == b.1 {
+                groups.entry(if a.0 < b.0 { (a.0, b.0) } else { (b.0, a.0) }).or_default().push(stage);
+            }
+        }
+        for ((a, b), stages) in groups.into_iter().filter(|(_, stages)| stages.len() >= 4) {
+            let mut cells = Vec::new();
+            stages.iter().for_each(|stage| self.stage_cells(stage, &mut cells));
+            blocks.push(DatapathBlock {
+                kind: DatapathKind::PrefixAdder,
+                width: stages.len(),
+                operands: vec![a.to_string(), b.to_string()],
+                cells: names(cells),
+            });
+        }
+    }
+
+    fn multipliers(&self, blocks: &mut Vec<DatapathBlock>) {
+        let mut grids: IndexMap<(&str, &str), Matches<'a, (usize, usize)>> = IndexMap::new();
+        for (bit, cut) in self.cuts_of(2).filter(|(_, cut)| AND2.contains(&cut.table)) {
+            let (Some(a), Some(b)) = (self.buses.get(&cut.leaves[0]), self.buses.get(&cut.leaves[1])) else {
+                continue;
+            };
+            if a.0 == b.0 {
+                continue;
+            }
+            let (a, b) = if a.0 < b.0 { (a, b) } else { (b, a) };
+            let grid = grids.entry((a.0, b.0)).or_default();
+            if grid.0.insert((a.1, b.1)) {
+                self.cone(bit, &cut.leaves, &mut grid.1);
+            }
+        }
+        for ((a, b), (products, cells)) in grids {
+            let rows: HashSet<usize> = products.iter().map(|(row, _)| *row).collect();
+            let columns: HashSet<usize> = products.iter().map(|(_, column)| *column).collect();
+            if rows.len() < 2 || columns.len() < 2 || products.len() * 2 < rows.len() * columns.len() {
+                continue;
+            }
+            blocks.push(DatapathBlock {
+                kind: DatapathKind::Multiplier,
+                width: rows.len().max(columns.len()),
+                operands: vec![a.to_string(), b.to_string()],
+                cells: names(cells),
+            });
+        }
+    }
+
+    fn booth_recoders(&self, blocks: &mut Vec<DatapathBlock>) {
+        let mut recoders: IndexMap<&str, (Matches<'a, usize>, Vec<String>)> = IndexMap::new();
+        for (bit, cut) in self.cuts_of(2).filter(|(_, cut)| XOR2.contains(&cut.table)) {
+            let (Some(a), Some(b)) = (self.buses.get(&cut.leaves[0]), self.buses.get(&cut.leaves[1])) else {
+                continue;
+            };
+            if a.0 != b.0 || a.1.abs_diff(b.1) != 1 {
+                continue;
+            }
+            // The recoded digit has to select bits of another operand.
+            let selected: Vec<&str> = self
+                .readers
+                .get(&bit)
+                .into_iter()
+                .flatten()
+                .flat_map(|reader| self.nodes[reader].inputs.iter())
+                .filter_map(|input| self.buses.get(input))
+                .map(|(name, _)| *name)
+                .filter(|name| *name != a.0)
+                .collect();
+            let Some(multiplicand) = selected.first() else {
+                continue;
+            };
+            let recoder = recoders.entry(a.0).or_default();
+            if recoder.0.0.insert(a.1.min(b.1)) {
+                self.cone(bit, &cut.leaves, &mut recoder.0.1);
+            }
+            if !recoder.1.iter().any(|name| name == multiplicand) {
+                recoder.1.push(multiplicand.to_string());
+            }
+        }
+        for (bus, ((digits, cells), multiplicands)) in recoders.into_iter().filter(|(_, recoder)| recoder.0.0.len() >= 2) {
+            blocks.push(DatapathBlock {
+                kind: DatapathKind::BoothRecoder,
+                width: digits.len(),
+                operands: std::iter::once(bus.to_string()).chain(multiplicands).collect(),
+                cells: names(cells),
+            });
+        }
+    }
+
+    /// Gates that only combine bits towards a single result, without XORing them.
+    fn reduces(&self, bit: &Bit) -> bool {
+        REDUCTION_GATES.contains(&self.nodes[bit].module)
+    }
+
+    fn comparators(&self, blocks: &mut Vec<DatapathBlock>) {
+        let mut groups: IndexMap<(&str, &str), Vec<Bit>> = IndexMap::new();
+        for bit in self.order.iter() {
+            let node = &self.nodes[bit];
+            if !matches!(node.module, "$_XOR_" | "$_XNOR_") {
+                continue;
+            }
+            let (Some(a), Some(b)) = (self.buses.get(&node.inputs[0]), self.buses.get(&node.inputs[1])) else {
+                continue;
+            };
+            let readers = self.readers.get(bit).map(Vec::as_slice).unwrap_or_default();
+            if a.0 == b.0 || a.1 != b.1 || readers.is_empty() || !readers.iter().all(|reader| self.reduces(reader)) {
+                continue;
+            }
+            groups.entry(if a.0 < b.0 { (a.0, b.0) } else { (b.0, a.0) }).or_default().push(*bit);
+        }
+
+        for ((a, b), differences) in groups.into_iter().filter(|(_, differences)| differences.len() >= 2) {
+            let mut common: Option<HashSet<Bit>> = None;
+            for difference in differences.iter() {
+                let mut reached = HashSet::new();
+                let mut stack = vec![*difference];
+                while let Some(bit) = stack.pop() {
+                    for reader in self.readers.get(&bit).into_iter().flatten().filter(|reader| self.reduces(reader)) {
+                        if reached.insert(*reader) {
+                            stack.push(*reader);
+                        }
+                    }
+                }
+                common = Some(match common {
+                    Some(common) => common.intersection(&reached).copied().collect(),
+                    None => reached,
+                });
+            }
+            let common = common.unwrap_or_default();
+            // The comparison result is where all differences first meet.
+            let Some(output) =
+                self.order.iter().find(|bit| common.contains(bit) && !self.nodes[bit].inputs.iter().any(|input| common.contains(input)))
+            else {
+                continue;
+            };
+            let mut cells = Vec::new();
+            self.cone(*output, &differences, &mut cells);
+            cells.extend(differences.iter().map(|difference| self.nodes[difference].cell));
+            blocks.push(DatapathBlock {
+                kind: DatapathKind::Comparator,
+                width: differences.len(),
+                operands: vec![a.to_string(), b.to_string()],
+                cells: names(cells),
+            });
+        }
+    }
+}
+
+impl Module {
+    /// Recognizes adders, multipliers and comparators, both as word-level cells and as mapped
+    /// gate-level structures. Gate-level recognition works on the functions of small cuts, so
+    /// it does not depend on which gates the mapper chose.
+    pub fn recognize_datapath(&self) -> DatapathSummary {
+        let mut blocks = Vec::new();
+        for (name, cell) in self.cells.iter() {
+            let Some((_, kind)) = WORD_LEVEL.iter().find(|(module, _)| *module == cell.module) else {
+                continue;
+            };
+            let width = ["A_WIDTH", "B_WIDTH"].iter().filter_map(|param| cell.parameters.get(*param).and_then(parameter)).max();
+            let operands = ["A", "B"]
+                .iter()
+                .filter_map(|port| {
+                    let bits = cell.connections.get(*port)?;
+                    self.nets.iter().find(|(_, net)| net.bits == *bits).map(|(name, _)| name.to_string())
+                })
+                .collect();
+            blocks.push(DatapathBlock {
+                kind: *kind,
+                width: width.unwrap_or_else(|| cell.connections.get("A").map_or(0, Vec::len)),
+                operands,
+                cells: vec![name.to_string()],
+            });
+        }
+
+        let graph = Graph::new(self);
+        graph.adders(&mut blocks);
+        graph.multipliers(&mut blocks);
+        graph.booth_recoders(&mut blocks);
+        graph.comparators(&mut blocks);
+        DatapathSummary { blocks }
+    }
+
+    /// Like [`Module::recognize_datapath`], and marks every cell of a recognized block with a
+    /// `datapath` attribute naming the block, e.g. `ripple_adder0`. A cell shared by several
+    /// blocks keeps the first name.
+    pub fn annotate_datapath(&mut self) -> DatapathSummary {
+        pass_span!("annotate_datapath", cells = self.cells.len(), nets = self.nets.len());
+        let summary = self.recognize_datapath();
+        let mut counts: HashMap<DatapathKind, usize> = HashMap::new();
+        for block in summary.blocks.iter() {
+            let index = counts.entry(block.kind).or_default();
+            let label = serde_json::Value::String(format!("{}{}", block.kind, index));
+            *index += 1;
+            for name in block.cells.iter() {
+                self.cells.get_mut(name).unwrap().attributes.entry("datapath".to_string()).or_insert_with(|| label.clone());
+            }
+        }
+        summary
+    }
+}
+
+impl Netlist {
+    pub fn recognize_datapath(&self) -> DesignDatapathSummary {
+        self.modules.iter().map(|(name, module)| (name.to_string(), module.recognize_datapath())).collect()
+    }
+
+    pub fn annotate_datapath(&mut self) -> DesignDatapathSummary {
+        (self.modules.iter_mut())
+            .map(|(name, module)| {
+                pass_span!("module", module = name.as_str());
+                (name.to_string(), module.annotate_datapath())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(path: &str) -> Netlist {
+        Netlist::from_reader(std::fs::File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ripple_adder() {
+        let mut netlist = load("testdata/adder.json");
+        let summary = netlist.annotate_datapath().shift_remove("adder").unwrap();
+        // ABC maps the low bits as a lookahead and keeps a ripple chain for the rest.
+        assert_eq!(summary.count(DatapathKind::RippleAdder), 1);
+        assert_eq!(summary.count(DatapathKind::PrefixAdder), 1);
+        assert!(summary.blocks.iter().all(|block| block.operands == ["a", "b"]));
+
+        let adder = summary.blocks.iter().find(|block| block.kind == DatapathKind::RippleAdder).unwrap();
+        let cells = &netlist.modules["adder"].cells;
+        assert!(adder.cells.iter().all(|cell| cells[cell].attributes["datapath"] == "ripple_adder0"));
+    }
+
+    #[test]
+    fn test_equality_comparator() {
+        let gate = |module: &str, a: u64, b: u64, y: u64| {
+            json!({
+                "type": module,
+                "port_directions": { "A": "input", "B": "input", "Y": "output" },
+                "connections": { "A": [a], "B": [b], "Y": [y] }
+            })
+        };
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "a": { "direction": "input", "bits": [2, 3, 4] },
+                        "b": { "direction": "input", "bits": [5, 6, 7] },
+                        "eq": { "direction": "output", "bits": [12] }
+                    },
+                    "cells": {
+                        "x0": gate("$_XOR_", 2, 5, 8),
+                        "x1": gate("$_XOR_", 3, 6, 9),
+                        "x2": gate("$_XOR_", 7, 4, 10),
+                        "or": gate("$_OR_", 8, 9, 11),
+                        "nor": gate("$_NOR_", 11, 10, 12)
+                    },
+                    "netnames": {
+                        "a": { "bits": [2, 3, 4] },
+                        "b": { "bits": [5, 6, 7] },
+                        "eq": { "bits": [12] }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let summary = netlist.modules["top"].recognize_datapath();
+        assert_eq!(
+            summary.blocks,
+            [DatapathBlock {
+                kind: DatapathKind::Comparator,
+                width: 3,
+                operands: vec!["a".to_string(), "b".to_string()],
+                cells: ["nor", "or", "x0", "x1", "x2"].map(String::from).to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_multiplier() {
+        let summary = load("testdata/mult.json").modules["mult"].recognize_datapath();
+        assert_eq!(summary.count(DatapathKind::Multiplier), 1);
+        let multiplier = summary.blocks.iter().find(|block| block.kind == DatapathKind::Multiplier).unwrap();
+        assert_eq!(multiplier.width, 8);
+        assert_eq!(multiplier.operands, ["a", "b"]);
+        assert_eq!(summary.count(DatapathKind::BoothRecoder), 0);
+        assert!(summary.count(DatapathKind::RippleAdder) > 0);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Bit, Cell, Direction, Module, Net, Netlist, Port};
+
+/// Bit names of a module, public nets preferred over hidden ones.
+struct Names<'a> {
+    bits: HashMap<Bit, (&'a str, &'a Net, usize)>,
+}
+
+impl<'a> Names<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut bits = HashMap::new();
+        for hidden in [false, true] {
+            for (name, net) in module.nets.iter().filter(|(_, net)| net.hide_name == hidden) {
+                for (index, bit) in net.bits.iter().enumerate().filter(|(_, bit)| matches!(bit, Bit::Signal(_))) {
+                    bits.entry(*bit).or_insert((name.as_str(), net, index));
+                }
+            }
+        }
+        Self { bits }
+    }
+}
+
+/// `[msb:lsb]` for a net or port with the given width, offset and direction, or nothing for
+/// a plain single bit.
+fn range(width: usize, offset: usize, upto: bool) -> String {
+    match (width, offset, upto) {
+        (1, 0, _) | (0, _, _) => String::new(),
+        (_, _, false) => format!("[{}:{}] ", offset + width - 1, offset),
+        (_, _, true) => format!("[{}:{}] ", offset, offset + width - 1),
+    }
+}
+
+fn constant(bit: &Bit) -> Option<char> {
+    match bit {
+        Bit::_0 => Some('0'),
+        Bit::_1 => Some('1'),
+        Bit::X => Some('x'),
+        Bit::Z => Some('z'),
+        Bit::Signal(_) => None,
+    }
+}
+
+/// Formats bits like a Verilog expression: constants as literals, named bits as net slices,
+/// unnamed signals as `#n`, joined into a concatenation with the most significant part first.
+fn expression(bits: &[Bit], names: Option<&Names>) -> String {
+    let name = |bit: &Bit| names.and_then(|names| names.bits.get(bit));
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < bits.len() {
+        let mut end = start + 1;
+        if constant(&bits[start]).is_some() {
+            while end < bits.len() && constant(&bits[end]).is_some() {
+                end += 1;
+            }
+            let digits: String = bits[start..end].iter().rev().filter_map(constant).collect();
+            parts.push(format!("{}'b{}", end - start, digits));
+        } else if let Some((net_name, net, index)) = name(&bits[start]) {
+            while end < bits.len()
+                && name(&bits[end]).is_some_and(|(other, _, other_index)| other == net_name && *other_index == index + end - start)
+            {
+                end += 1;
+            }
+            let position = |index: usize| match net.upto {
+                0 => net.offset + index,
+                _ => net.offset + net.bits.len() - 1 - index,
+            };
+            let (lsb, msb) = (position(*index), position(index + end - start - 1));
+            parts.push(match (end - start == net.bits.len(), end - start) {
+                (true, _) => net_name.to_string(),
+                (false, 1) => format!("{}[{}]", net_name, lsb),
+                (false, _) => format!("{}[{}:{}]", net_name, msb, lsb),
+            });
+        } else if let Bit::Signal(signal) = bits[start] {
+            parts.push(format!("#{}", signal));
+        }
+        start = end;
+    }
+    match parts.len() {
+        0 => "{}".to_string(),
+        1 => parts.pop().unwrap(),
+        _ => format!("{{{}}}", parts.into_iter().rev().collect::<Vec<_>>().join(", ")),
+    }
+}
+
+fn direction(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::Input => "input",
+        Direction::Output => "output",
+        Direction::InOut => "inout",
+    }
+}
+
+fn write_cell(f: &mut fmt::Formatter<'_>, name: Option<&str>, cell: &Cell, names: Option<&Names>) -> fmt::Result {
+    write!(f, "{}", cell.module)?;
+    if !cell.parameters.is_empty() {
+        let parameters: Vec<String> = cell.parameters.iter().map(|(key, value)| format!(".{}({})", key, value)).collect();
+        write!(f, " #({})", parameters.join(", "))?;
+    }
+    if let Some(name) = name {
+        write!(f, " {}", name)?;
+    }
+    let connections: Vec<String> = cell.connections.iter().map(|(port, bits)| format!(".{}({})", port, expression(bits, names))).collect();
+    write!(f, " ({})", connections.join(", "))
+}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = range(self.bits.len(), self.offset, self.upto != 0);
+        write!(f, "{} {}{}", direction(&self.direction), range, expression(&self.bits, None))
+    }
+}
+
+impl fmt::Display for Net {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wire {}{}", range(self.bits.len(), self.offset, self.upto != 0), expression(&self.bits, None))
+    }
+}
+
+/// Shows connections as raw signals, see [`Module::display_cell`] for net names.
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_cell(f, None, self, None)
+    }
+}
+
+/// A cell with connections shown by net name, returned by [`Module::display_cell`].
+pub struct CellDisplay<'a> {
+    module: &'a Module,
+    name: &'a str,
+    cell: &'a Cell,
+}
+
+impl fmt::Display for CellDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_cell(f, Some(self.name), self.cell, Some(&Names::new(self.module)))
+    }
+}
+
+impl Module {
+    /// Formats `bits` like a Verilog expression using the net names of this module, e.g.
+    /// `{a[3:1], 1'b0}`.
+    pub fn format_bits(&self, bits: &[Bit]) -> String {
+        expression(bits, Some(&Names::new(self)))
+    }
+
+    /// The cell `name` with its connections resolved to net names.
+    pub fn display_cell<'a>(&'a self, name: &'a str) -> Option<CellDisplay<'a>> {
+        self.cells.get(name).map(|cell| CellDisplay { module: self, name, cell })
+    }
+}
+
+/// A Verilog-like listing of the ports, public nets, memories and cells, with connections
+/// resolved to net names.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Names::new(self);
+        for (key, value) in self.attributes.iter() {
+            writeln!(f, "(* {} = {} *)", key, value)?;
+        }
+        for (name, port) in self.ports.iter() {
+            writeln!(f, "{} {}{};", direction(&port.direction), range(port.bits.len(), port.offset, port.upto != 0), name)?;
+        }
+        for (name, net) in self.nets.iter().filter(|(name, net)| !net.hide_name && !self.ports.contains_key(*name)) {
+            writeln!(f, "wire {}{};", range(net.bits.len(), net.offset, net.upto != 0), name)?;
+        }
+        for (name, memory) in self.memories.iter() {
+            writeln!(
+                f,
+                "reg [{}:0] {} [{}:{}];",
+                memory.width.saturating_sub(1),
+                name,
+                memory.start_offset,
+                memory.start_offset + memory.size.saturating_sub(1)
+            )?;
+        }
+        for (name, cell) in self.cells.iter() {
+            write_cell(f, Some(name), cell, Some(&names))?;
+            writeln!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+impl Netlist {
+    /// Every module listed as by its [`Display`](fmt::Display) impl, wrapped in `module` and
+    /// `endmodule` lines.
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+        for (name, module) in self.modules.iter() {
+            dump += &format!("module {};\n", name);
+            for line in module.to_string().lines() {
+                dump += &format!("  {}\n", line);
+            }
+            dump += "endmodule\n";
+        }
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_display() {
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "a": { "direction": "input", "bits": [2, 3, 4, 5] },
+                        "y": { "direction": "output", "bits": [6] }
+                    },
+                    "cells": {
+                        "cmp": {
+                            "type": "$eq",
+                            "parameters": { "A_WIDTH": 6 },
+                            "port_directions": { "A": "input", "B": "input", "Y": "output" },
+                            "connections": { "A": [3, 4, 5, "0", "x", 7], "B": [2, "1", "1", "0", "0", "0"], "Y": [6] }
+                        }
+                    },
+                    "netnames": {
+                        "a": { "bits": [2, 3, 4, 5] },
+                        "y": { "bits": [6] },
+                        "hidden": { "hide_name": 1, "bits": [5, 7] }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let module = &netlist.modules["top"];
+        assert_eq!(module.format_bits(&[Bit::Signal(2), Bit::Signal(3), Bit::Signal(4), Bit::Signal(5)]), "a");
+        assert_eq!(module.format_bits(&[Bit::Signal(8)]), "#8");
+        assert_eq!(
+            module.display_cell("cmp").unwrap().to_string(),
+            "$eq #(.A_WIDTH(6)) cmp (.A({hidden[1], 2'bx0, a[3:1]}), .B({5'b00011, a[0]}), .Y(y))"
+        );
+        assert_eq!(module.cells["cmp"].connections["Y"], [Bit::Signal(6)]);
+        assert_eq!(module.ports["a"].to_string(), "input [3:0] {#5, #4, #3, #2}");
+
+        let listing = netlist.dump();
+        assert!(listing.starts_with("module top;\n  input [3:0] a;\n  output y;\n  $eq"));
+        assert!(listing.ends_with(";\nendmodule\n"));
+        assert!(!listing.contains("wire"));
+    }
+}
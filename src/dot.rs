@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::connectivity::Endpoint;
+use crate::{Bit, Direction, Module};
+
+/// Options of [`Module::to_dot`].
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// Writes constant cell inputs into the port labels instead of drawing a node per constant.
+    pub collapse_constants: bool,
+    /// Leaves the names of `hide_name` nets off the edges.
+    pub hide_internal: bool,
+    /// Groups cells into clusters by the instance path left in their names or `hdlname`
+    /// attributes by flattening.
+    pub cluster: bool,
+}
+
+/// Escapes text for a record label, which also gives `{}|<>` a meaning.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '"' | '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Constant bits as a Verilog style literal, most significant first.
+fn literal(bits: &[Bit]) -> String {
+    let digits: String = bits
+        .iter()
+        .rev()
+        .map(|bit| match bit {
+            Bit::_0 => '0',
+            Bit::_1 => '1',
+            Bit::Z => 'z',
+            _ => 'x',
+        })
+        .collect();
+    format!("{}'{}", bits.len(), digits)
+}
+
+impl Module {
+    /// Instance path of a cell below this module, as left by flattening.
+    fn instance_path(&self, name: &str) -> Vec<String> {
+        let cell = &self.cells[name];
+        let mut path: Vec<String> = match cell.attributes.get("hdlname").and_then(|name| name.as_str()) {
+            Some(hdlname) => hdlname.split_whitespace().map(str::to_string).collect(),
+            None if !name.starts_with('$') => name.split('.').map(str::to_string).collect(),
+            None => Vec::new(),
+        };
+        path.pop();
+        path
+    }
+
+    /// Writes the module as a Graphviz digraph in the style of `yosys show`: ports and cells
+    /// become nodes and every group of bits from one driver pin to one load pin an edge,
+    /// labelled with the net of its first bit.
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let index = self.index();
+        let mut dot = String::from("digraph module {\n  rankdir=\"LR\";\n  remincross=true;\n");
+
+        let ports: HashMap<&str, usize> = self.ports.keys().enumerate().map(|(id, name)| (name.as_str(), id)).collect();
+        let cells: HashMap<&str, usize> = self.cells.keys().enumerate().map(|(id, name)| (name.as_str(), id)).collect();
+        for (name, port) in self.ports.iter() {
+            let shape = match port.direction {
+                Direction::Input => "invhouse",
+                Direction::Output => "house",
+                Direction::InOut => "diamond",
+            };
+            writeln!(dot, "  p{} [shape={}, label=\"{}\"];", ports[name.as_str()], shape, name.replace('"', "\\\"")).unwrap();
+        }
+
+        let mut clusters: BTreeMap<Vec<String>, Vec<String>> = BTreeMap::new();
+        let mut constants = Vec::new();
+        for (id, (name, cell)) in self.cells.iter().enumerate() {
+            let mut inputs = Vec::new();
+            let mut outputs = Vec::new();
+            for (pin, (port, bits)) in cell.connections.iter().enumerate() {
+                let mut label = escape(port);
+                let fixed: Vec<Bit> = bits.iter().copied().filter(|bit| !matches!(bit, Bit::Signal(_))).collect();
+                if cell.port_directions.get(port) == Some(&Direction::Output) {
+                    outputs.push(format!("<p{}> {}", pin, label));
+                    continue;
+                }
+                if !fixed.is_empty() {
+                    match options.collapse_constants {
+                        true => label = format!("{}={}", label, literal(&fixed)),
+                        false => constants.push((literal(&fixed), id, pin)),
+                    }
+                }
+                inputs.push(format!("<p{}> {}", pin, label));
+            }
+            let node = format!(
+                "c{} [shape=record, label=\"{{{{{}}}|{}\\n{}|{{{}}}}}\"];",
+                id,
+                inputs.join("|"),
+                escape(name),
+                escape(&cell.module),
+                outputs.join("|")
+            );
+            let path = if options.cluster { self.instance_path(name) } else { Vec::new() };
+            clusters.entry(path).or_default().push(node);
+        }
+
+        // Sorted paths list every cluster right before the clusters nested in it.
+        let mut open: Vec<String> = Vec::new();
+        for (cluster, (path, nodes)) in clusters.iter().enumerate() {
+            while !path.starts_with(&open) {
+                open.pop();
+                writeln!(dot, "{}}}", "  ".repeat(open.len() + 1)).unwrap();
+            }
+            while open.len() < path.len() {
+                let indent = "  ".repeat(open.len() + 1);
+                open.push(path[open.len()].clone());
+                writeln!(dot, "{}subgraph cluster_{}_{} {{", indent, cluster, open.len()).unwrap();
+                writeln!(dot, "{}  label=\"{}\";", indent, open.last().unwrap().replace('"', "\\\"")).unwrap();
+            }
+            for node in nodes {
+                writeln!(dot, "{}{}", "  ".repeat(open.len() + 1), node).unwrap();
+            }
+        }
+        while !open.is_empty() {
+            open.pop();
+            writeln!(dot, "{}}}", "  ".repeat(open.len() + 1)).unwrap();
+        }
+
+        for (id, (value, cell, pin)) in constants.into_iter().enumerate() {
+            writeln!(dot, "  k{} [shape=plaintext, label=\"{}\"];", id, value).unwrap();
+            writeln!(dot, "  k{} -> c{}:p{}:w;", id, cell, pin).unwrap();
+        }
+
+        let node = |endpoint: &Endpoint, side: &str| match endpoint {
+            Endpoint::Port { port, .. } => format!("p{}", ports[port]),
+            Endpoint::Cell { cell, port, .. } => {
+                format!("c{}:p{}:{}", cells[cell], self.cells[*cell].connections.get_index_of(*port).unwrap(), side)
+            }
+        };
+        let mut edges: BTreeMap<(String, String), Vec<Bit>> = BTreeMap::new();
+        let mut bits: Vec<&Bit> = self.ports.values().flat_map(|port| &port.bits).collect();
+        bits.extend(self.cells.values().flat_map(|cell| cell.connections.values().flatten()));
+        let mut seen = HashSet::new();
+        for bit in bits.into_iter().filter(|bit| seen.insert(**bit)) {
+            for driver in index.drivers_of(bit) {
+                for load in index.loads_of(bit).iter().filter(|load| *load != driver) {
+                    edges.entry((node(driver, "e"), node(load, "w"))).or_default().push(*bit);
+                }
+            }
+        }
+        for ((from, to), bits) in edges {
+            let label = match self.net_of(&bits[0]) {
+                Some((_, net)) if net.hide_name && options.hide_internal => String::new(),
+                Some((name, _)) => name.replace('"', "\\\""),
+                None => String::new(),
+            };
+            let style = if bits.len() > 1 { ", style=\"setlinewidth(3)\"" } else { "" };
+            writeln!(dot, "  {} -> {} [label=\"{}\"{}];", from, to, label, style).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, constant};
+
+    #[test]
+    fn test_dot() {
+        let mut builder = Module::builder();
+        let a = builder.input("a", 2).unwrap();
+        let y = builder.output("y", 2).unwrap();
+        let t = builder.wire("$t", 2).unwrap();
+        builder
+            .cell(
+                "u1.add",
+                CellBuilder::new("$add")
+                    .input("A", a.clone())
+                    .input("B", constant(1, 2))
+                    .output("Y", t.clone())
+                    .attribute("hdlname", "u1 add"),
+            )
+            .unwrap();
+        builder.cell("$not", CellBuilder::new("$not").input("A", t).output("Y", y)).unwrap();
+        let module = builder.build();
+
+        let plain = module.to_dot(&DotOptions::default());
+        assert!(plain.contains("c0 [shape=record, label=\"{{<p0> A|<p1> B}|u1.add\\n$add|{<p2> Y}}\"];"));
+        assert!(plain.contains("k0 [shape=plaintext, label=\"2'01\"];"));
+        assert!(plain.contains("p0 -> c0:p0:w [label=\"a\", style=\"setlinewidth(3)\"];"));
+        assert!(plain.contains("c0:p2:e -> c1:p0:w [label=\"$t\""));
+        assert!(!plain.contains("cluster"));
+
+        let options = DotOptions { collapse_constants: true, hide_internal: true, cluster: true };
+        let dot = module.to_dot(&options);
+        assert!(dot.contains("<p1> B=2'01"));
+        assert!(!dot.contains("k0"));
+        assert!(dot.contains("c0:p2:e -> c1:p0:w [label=\"\""));
+        assert!(dot.contains("subgraph cluster_1_1 {\n    label=\"u1\";\n    c0 "));
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Bit, Cell, Direction, Module};
+
+/// A tree of XOR cells computing the parity of its inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParityTree {
+    /// The cell computing the final parity.
+    pub root: String,
+    pub output: Bit,
+    /// Bits contributing to the parity, in ascending order. Bits reaching the root through an
+    /// even number of paths cancel out and are not listed.
+    pub inputs: Vec<Bit>,
+    /// Every XOR cell of the tree, including shared sub-trees.
+    pub cells: Vec<String>,
+    /// The tree computes odd parity (an odd number of XNOR cells).
+    pub inverted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EccCode {
+    /// Single error correcting Hamming code.
+    Hamming,
+    /// Hamming code with an additional overall parity, single error correcting and double
+    /// error detecting.
+    Secded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EccRole {
+    /// Computes check bits from data bits.
+    Encoder,
+    /// Computes a syndrome from data bits and received check bits.
+    Decoder,
+}
+
+/// A group of parity trees forming a Hamming-style code over a data bus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EccStructure {
+    pub code: EccCode,
+    pub role: EccRole,
+    /// Protected data bits, in ascending order.
+    pub data: Vec<Bit>,
+    /// Received check bits read by a decoder, one per syndrome tree.
+    pub check: Vec<Bit>,
+    /// Root cells of the trees computing the check bits or the syndrome, the overall parity
+    /// of a SECDED code last.
+    pub trees: Vec<String>,
+    /// Outputs of the trees, in the same order: the check bits of an encoder, the syndrome of
+    /// a decoder.
+    pub syndrome: Vec<Bit>,
+}
+
+impl EccStructure {
+    pub fn data_width(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// Parity and error correcting structures found in a module.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EccReport {
+    /// Parity trees that are not part of an error correcting code.
+    pub parity: Vec<ParityTree>,
+    pub codes: Vec<EccStructure>,
+}
+
+fn xor_inputs(cell: &Cell) -> Option<(Vec<Bit>, bool)> {
+    let single = |port: &str| cell.connections.get(port).filter(|bits| bits.len() == 1).map(|bits| bits[0]);
+    let two = || Some(vec![single("A")?, single("B")?]);
+    match cell.module.as_str() {
+        "$_XOR_" => Some((two()?, false)),
+        "$_XNOR_" => Some((two()?, true)),
+        "$xor" | "$xnor" if cell.connections.get("Y")?.len() == 1 => Some((two()?, cell.module == "$xnor")),
+        "$reduce_xor" | "$reduce_xnor" => Some((cell.connections.get("A")?.clone(), cell.module == "$reduce_xnor")),
+        _ => None,
+    }
+}
+
+type Expansion<'a> = (BTreeSet<Bit>, BTreeSet<&'a str>, bool);
+
+/// Parity inputs, cells and inversion of the XOR tree rooted at `cell`, memoized so shared
+/// sub-trees are only expanded once. Inputs reached an even number of times cancel out.
+fn expand<'a>(
+    cell: &'a str,
+    xors: &HashMap<&'a str, (Vec<Bit>, bool, Bit)>,
+    driven_by: &HashMap<Bit, &'a str>,
+    memo: &mut HashMap<&'a str, Expansion<'a>>,
+    visiting: &mut HashSet<&'a str>,
+) -> Expansion<'a> {
+    if let Some(expansion) = memo.get(cell) {
+        return expansion.clone();
+    }
+    visiting.insert(cell);
+    let (cell_inputs, mut inverted, _) = xors[cell].clone();
+    let mut inputs = BTreeSet::new();
+    let mut cells = BTreeSet::from([cell]);
+    for bit in cell_inputs {
+        let (leaves, sub_cells, sub_inverted) = match driven_by.get(&bit) {
+            // Combinational loops through XOR cells are cut by treating the bit as a leaf.
+            Some(driver) if !visiting.contains(driver) => expand(driver, xors, driven_by, memo, visiting),
+            _ => (BTreeSet::from([bit]), BTreeSet::new(), false),
+        };
+        inputs = inputs.symmetric_difference(&leaves).copied().collect();
+        cells.extend(sub_cells);
+        inverted ^= sub_inverted;
+    }
+    visiting.remove(cell);
+    memo.insert(cell, (inputs.clone(), cells.clone(), inverted));
+    (inputs, cells, inverted)
+}
+
+impl Module {
+    /// Finds all parity trees: maximal trees of XOR cells, rooted at XOR cells whose output is
+    /// used by anything but another XOR cell.
+    pub fn parity_trees(&self) -> Vec<ParityTree> {
+        let xors: HashMap<&str, (Vec<Bit>, bool, Bit)> = self
+            .cells
+            .iter()
+            .filter_map(|(name, cell)| {
+                let (inputs, inverted) = xor_inputs(cell)?;
+                Some((name.as_str(), (inputs, inverted, *cell.connections.get("Y")?.first()?)))
+            })
+            .collect();
+        let driven_by: HashMap<Bit, &str> = xors.iter().map(|(name, (_, _, output))| (*output, *name)).collect();
+
+        let mut used_elsewhere: HashSet<Bit> = self.ports.values().flat_map(|port| port.bits.iter().copied()).collect();
+        for cell in self.cells.iter().filter(|(name, _)| !xors.contains_key(name.as_str())).map(|(_, cell)| cell) {
+            for (port, bits) in cell.connections.iter() {
+                if cell.port_directions.get(port) != Some(&Direction::Output) {
+                    used_elsewhere.extend(bits.iter().copied());
+                }
+            }
+        }
+
+        let mut memo = HashMap::new();
+        let mut trees = Vec::new();
+        for (name, _) in self.cells.iter() {
+            let Some((_, _, output)) = xors.get(name.as_str()) else {
+                continue;
+            };
+            if !used_elsewhere.contains(output) {
+                continue;
+            }
+            let (inputs, cells, inverted) = expand(name, &xors, &driven_by, &mut memo, &mut HashSet::new());
+            trees.push(ParityTree {
+                root: name.to_string(),
+                output: *output,
+                inputs: inputs.into_iter().collect(),
+                cells: cells.into_iter().map(str::to_string).collect(),
+                inverted,
+            });
+        }
+        trees
+    }
+
+    /// Recognizes Hamming and SECDED encoders and decoders among the parity trees of this
+    /// module. Trees sharing inputs are grouped; a group is a Hamming code when every data bit
+    /// is covered by a distinct combination of at least two trees.
+    pub fn ecc_structures(&self) -> EccReport {
+        let trees = self.parity_trees();
+        let mut report = EccReport::default();
+
+        // Group trees sharing input bits.
+        let mut group_of: Vec<usize> = (0..trees.len()).collect();
+        fn find(group_of: &mut [usize], index: usize) -> usize {
+            let mut root = index;
+            while group_of[root] != root {
+                root = group_of[root];
+            }
+            group_of[index] = root;
+            root
+        }
+        let mut first_user: HashMap<Bit, usize> = HashMap::new();
+        for (index, tree) in trees.iter().enumerate() {
+            for bit in tree.inputs.iter() {
+                let other = *first_user.entry(*bit).or_insert(index);
+                let (a, b) = (find(&mut group_of, index), find(&mut group_of, other));
+                group_of[a] = b;
+            }
+        }
+        let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for index in 0..trees.len() {
+            groups.entry(find(&mut group_of, index)).or_default().push(index);
+        }
+
+        for members in groups.into_values() {
+            let group: Vec<&ParityTree> = members.iter().map(|index| &trees[*index]).collect();
+            match classify(&group) {
+                Some(code) => report.codes.push(code),
+                None => report.parity.extend(group.into_iter().cloned()),
+            }
+        }
+        report
+    }
+}
+
+fn classify(group: &[&ParityTree]) -> Option<EccStructure> {
+    if group.len() < 2 {
+        return None;
+    }
+    let coverage = |trees: &[&ParityTree]| {
+        let mut coverage: BTreeMap<Bit, BTreeSet<usize>> = BTreeMap::new();
+        for (index, tree) in trees.iter().enumerate() {
+            for bit in tree.inputs.iter() {
+                coverage.entry(*bit).or_default().insert(index);
+            }
+        }
+        coverage
+    };
+
+    // An overall parity covers every bit shared by two or more trees.
+    let shared: BTreeSet<Bit> = coverage(group).into_iter().filter(|(_, trees)| trees.len() >= 2).map(|(bit, _)| bit).collect();
+    let overall = match group.len() >= 3 {
+        true => group.iter().position(|tree| shared.iter().all(|bit| tree.inputs.contains(bit))),
+        false => None,
+    };
+    let mut trees: Vec<&ParityTree> = group.to_vec();
+    let overall = overall.map(|index| trees.remove(index));
+
+    let coverage = coverage(&trees);
+    let single: Vec<Bit> = coverage.iter().filter(|(_, trees)| trees.len() == 1).map(|(bit, _)| *bit).collect();
+    let per_tree = |bits: &[Bit]| {
+        let mut counts = vec![0; trees.len()];
+        bits.iter().for_each(|bit| coverage[bit].iter().for_each(|tree| counts[*tree] += 1));
+        counts
+    };
+    let (role, check) = match !single.is_empty() && per_tree(&single).iter().all(|count| *count == 1) {
+        true => (EccRole::Decoder, single),
+        false => (EccRole::Encoder, Vec::new()),
+    };
+    let data: Vec<Bit> = coverage.keys().filter(|bit| !check.contains(bit)).copied().collect();
+    let patterns: HashSet<&BTreeSet<usize>> = data.iter().map(|bit| &coverage[bit]).collect();
+    if data.len() < 2 || patterns.len() != data.len() || patterns.iter().any(|pattern| pattern.len() < 2) {
+        return None;
+    }
+    // Decoders order the syndrome by the check bit each tree reads.
+    if role == EccRole::Decoder {
+        trees.sort_by_key(|tree| check.iter().position(|bit| tree.inputs.contains(bit)));
+    }
+
+    let code = if overall.is_some() { EccCode::Secded } else { EccCode::Hamming };
+    trees.extend(overall);
+    Some(EccStructure {
+        code,
+        role,
+        data,
+        check,
+        trees: trees.iter().map(|tree| tree.root.clone()).collect(),
+        syndrome: trees.iter().map(|tree| tree.output).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    /// Builds XOR chains computing the parity of each list of bits, numbering internal bits
+    /// from 100 and outputs from 200.
+    fn xor_trees(inputs: &[&[u64]]) -> Module {
+        let mut cells = serde_json::Map::new();
+        let mut next = 100;
+        for (tree, bits) in inputs.iter().enumerate() {
+            let mut acc = bits[0];
+            for (index, bit) in bits[1..].iter().enumerate() {
+                let output = if index == bits.len() - 2 { 200 + tree as u64 } else { next };
+                next += 1;
+                cells.insert(
+                    format!("x{}_{}", tree, index),
+                    json!({
+                        "type": "$_XOR_",
+                        "port_directions": { "A": "input", "B": "input", "Y": "output" },
+                        "connections": { "A": [acc], "B": [bit], "Y": [output] }
+                    }),
+                );
+                acc = output;
+            }
+        }
+        let outputs: Vec<u64> = (0..inputs.len() as u64).map(|tree| 200 + tree).collect();
+        let netlist = crate::Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": { "p": { "direction": "output", "bits": outputs } },
+                    "cells": Value::Object(cells)
+                }
+            }
+        }))
+        .unwrap();
+        netlist.modules["top"].clone()
+    }
+
+    fn bits(bits: &[u64]) -> Vec<Bit> {
+        bits.iter().map(|bit| Bit::Signal(*bit)).collect()
+    }
+
+    #[test]
+    fn test_parity_trees() {
+        let module = xor_trees(&[&[2, 3, 4, 5], &[6, 7]]);
+        let report = module.ecc_structures();
+        assert!(report.codes.is_empty());
+        assert_eq!(report.parity.len(), 2);
+        assert_eq!(report.parity[0].inputs, bits(&[2, 3, 4, 5]));
+        assert_eq!(report.parity[0].output, Bit::Signal(200));
+        assert_eq!(report.parity[0].cells.len(), 3);
+    }
+
+    #[test]
+    fn test_hamming_encoder() {
+        // Hamming(7,4) over data bits 2..=5, plus an overall parity.
+        let module = xor_trees(&[&[2, 3, 5], &[2, 4, 5], &[3, 4, 5], &[2, 3, 4, 5]]);
+        let report = module.ecc_structures();
+        assert!(report.parity.is_empty());
+        let code = &report.codes[0];
+        assert_eq!((code.code, code.role), (EccCode::Secded, EccRole::Encoder));
+        assert_eq!(code.data_width(), 4);
+        assert_eq!(code.trees, ["x0_1", "x1_1", "x2_1", "x3_2"]);
+        assert_eq!(code.syndrome, bits(&[200, 201, 202, 203]));
+    }
+
+    #[test]
+    fn test_hamming_decoder() {
+        let module = xor_trees(&[&[10, 2, 3, 5], &[11, 2, 4, 5], &[12, 3, 4, 5]]);
+        let code = &module.ecc_structures().codes[0];
+        assert_eq!((code.code, code.role), (EccCode::Hamming, EccRole::Decoder));
+        assert_eq!(code.data, bits(&[2, 3, 4, 5]));
+        assert_eq!(code.check, bits(&[10, 11, 12]));
+    }
+}
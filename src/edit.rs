@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::gates::gate;
+use crate::{Bit, Direction, Module, Net};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectError {
+    UnknownCell(String),
+    UnknownPort {
+        cell: String,
+        port: String,
+    },
+    /// Bits not as wide as the port: one bit on gates, the `<port>_WIDTH` parameter where
+    /// the cell has one, or else the width of the connection replaced.
+    Width {
+        cell: String,
+        port: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCell(cell) => write!(f, "unknown cell {:?}", cell),
+            Self::UnknownPort { cell, port } => write!(f, "cell {:?} has no connection {:?}", cell, port),
+            Self::Width { cell, port, expected, found } => {
+                write!(f, "{}.{} is {} bits wide, not {}", cell, port, expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+impl Module {
+    /// `count` signal bits used nowhere in the module and not handed out before, so several
+    /// calls before the bits are connected do not overlap.
+    pub fn alloc_bits(&mut self, count: usize) -> Vec<Bit> {
+        let bits = self.fresh_bits(count);
+        if let Some(Bit::Signal(last)) = bits.last() {
+            self.allocated = last + 1;
+        }
+        bits
+    }
+
+    /// The width a port of `cell` must have, if it is known.
+    fn port_width(&self, cell: &str, port: &str) -> Option<usize> {
+        let cell = &self.cells[cell];
+        if let Some(gate) = gate(&cell.module)
+            && (gate.inputs.contains(&port) || gate.output == port)
+        {
+            return Some(1);
+        }
+        if let Some(width) = cell.param(&format!("{}_WIDTH", port)).and_then(|width| width.as_u64()) {
+            return Some(width as usize);
+        }
+        cell.connections.get(port).map(Vec::len)
+    }
+
+    /// Connects `port` of `cell` to `bits`, replacing any connection it had. Gate ports get
+    /// their direction. Signal bits no net names yet are named by a new hidden net
+    /// `$<cell>$<port>`, as Yosys names every signal.
+    pub fn connect(&mut self, cell: &str, port: &str, bits: Vec<Bit>) -> Result<(), ConnectError> {
+        if !self.cells.contains_key(cell) {
+            return Err(ConnectError::UnknownCell(cell.to_string()));
+        }
+        if let Some(expected) = self.port_width(cell, port)
+            && expected != bits.len()
+        {
+            return Err(ConnectError::Width { cell: cell.to_string(), port: port.to_string(), expected, found: bits.len() });
+        }
+        let named: HashSet<&Bit> = self.nets.values().flat_map(|net| net.bits.iter()).collect();
+        let unnamed: Vec<Bit> = bits.iter().filter(|bit| matches!(bit, Bit::Signal(_)) && !named.contains(bit)).copied().collect();
+        if !unnamed.is_empty() {
+            let mut name = format!("${}${}", cell, port);
+            while self.nets.contains_key(&name) {
+                name.push('_');
+            }
+            let mut net = Net::new(unnamed);
+            net.hide_name = true;
+            self.nets.insert(name.into(), net);
+        }
+
+        let target = &mut self.cells[cell];
+        if let Some(gate) = gate(&target.module) {
+            let direction = if gate.output == port { Direction::Output } else { Direction::Input };
+            target.port_directions.insert(port.into(), direction);
+        }
+        target.connections.insert(port.into(), bits);
+        Ok(())
+    }
+
+    /// Removes the connection of `port` of `cell`, and its direction, returning the bits it
+    /// had. Hidden nets left naming only bits nothing connects any more are removed too.
+    pub fn disconnect(&mut self, cell: &str, port: &str) -> Result<Vec<Bit>, ConnectError> {
+        let target = self.cells.get_mut(cell).ok_or_else(|| ConnectError::UnknownCell(cell.to_string()))?;
+        let bits = (target.connections.shift_remove(port))
+            .ok_or_else(|| ConnectError::UnknownPort { cell: cell.to_string(), port: port.to_string() })?;
+        target.port_directions.shift_remove(port);
+
+        let ports = self.ports.values().flat_map(|port| port.bits.iter());
+        let used: HashSet<&Bit> = ports.chain(self.cells.values().flat_map(|cell| cell.connections.values().flatten())).collect();
+        let removed: HashSet<Bit> = bits.iter().filter(|bit| !used.contains(bit)).copied().collect();
+        let stale = |net: &Net| net.bits.iter().any(|bit| removed.contains(bit)) && !net.bits.iter().any(|bit| used.contains(bit));
+        self.nets.retain(|_, net| !(net.hide_name && stale(net)));
+        Ok(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, NetlistBuilder};
+
+    #[test]
+    fn test_connect() {
+        let mut builder = Module::builder();
+        let a = builder.input("a", 2).unwrap();
+        builder.cell("not", CellBuilder::new("$_NOT_").input("A", vec![a[0]])).unwrap();
+        builder.cell("add", CellBuilder::new("$add").parameter("Y_WIDTH", 3).input("A", a)).unwrap();
+        let mut module = NetlistBuilder::new("test").top("top", builder).unwrap().build().modules.swap_remove("top").unwrap();
+
+        let first = module.alloc_bits(2);
+        let second = module.alloc_bits(3);
+        assert!(first.iter().all(|bit| !second.contains(bit)));
+        assert!(!module.signals().any(|signal| first.contains(&Bit::Signal(signal))));
+
+        assert_eq!(
+            module.connect("not", "Y", first.clone()),
+            Err(ConnectError::Width { cell: "not".to_string(), port: "Y".to_string(), expected: 1, found: 2 })
+        );
+        module.connect("not", "Y", first[..1].to_vec()).unwrap();
+        assert_eq!(module.cells["not"].port_directions["Y"], Direction::Output);
+        assert_eq!(module.nets["$not$Y"].bits, first[..1]);
+        assert!(module.connect("add", "Y", first.clone()).is_err());
+        module.connect("add", "Y", second.clone()).unwrap();
+        assert!(module.connect("missing", "Y", vec![]).is_err());
+
+        assert_eq!(module.disconnect("add", "Y").unwrap(), second);
+        assert!(!module.nets.contains_key("$add$Y") && module.nets.contains_key("$not$Y"));
+        assert_eq!(module.disconnect("add", "Y"), Err(ConnectError::UnknownPort { cell: "add".to_string(), port: "Y".to_string() }));
+        assert!(!second.contains(&module.alloc_bits(1)[0]));
+    }
+}
@@ -0,0 +1,140 @@
+use std::hash::Hash;
+
+use indexmap::IndexMap;
+
+use crate::{Cell, Memory, Module, Net, Netlist, Port};
+
+/// Equality up to representation: maps compare regardless of order and `hide_name` flags are
+/// ignored, so a netlist equals itself after a roundtrip through a format that sorts or drops
+/// them. [`PartialEq`] is the exact flavor, comparing every field, unknown ones included, and
+/// maps in order.
+pub trait SemanticEq {
+    fn semantic_eq(&self, other: &Self) -> bool;
+}
+
+fn ordered<K: PartialEq, V: PartialEq>(a: &IndexMap<K, V>, b: &IndexMap<K, V>) -> bool {
+    a.iter().eq(b.iter())
+}
+
+fn unordered<K: Hash + Eq, V: SemanticEq>(a: &IndexMap<K, V>, b: &IndexMap<K, V>) -> bool {
+    a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).is_some_and(|other| value.semantic_eq(other)))
+}
+
+/// Exact equality: `maps` in order, `fields` with `==`.
+macro_rules! exact_eq {
+    ($type:ty, maps: [$($map:ident),*], fields: [$($field:ident),*]) => {
+        impl PartialEq for $type {
+            fn eq(&self, other: &Self) -> bool {
+                $(ordered(&self.$map, &other.$map) &&)* $(self.$field == other.$field &&)* true
+            }
+        }
+    };
+}
+
+exact_eq!(Netlist, maps: [modules, extra], fields: [creator]);
+exact_eq!(Module, maps: [attributes, ports, cells, memories, nets, extra], fields: []);
+exact_eq!(Port, maps: [extra], fields: [direction, bits, offset, upto, signed]);
+exact_eq!(Cell, maps: [attributes, parameters, port_directions, connections, extra], fields: [hide_name, module]);
+exact_eq!(Memory, maps: [attributes, extra], fields: [hide_name, width, size, start_offset]);
+exact_eq!(Net, maps: [attributes, extra], fields: [hide_name, bits, offset, upto, signed]);
+
+impl SemanticEq for serde_json::Value {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl SemanticEq for crate::Direction {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl SemanticEq for Vec<crate::Bit> {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl SemanticEq for Netlist {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self.creator == other.creator && unordered(&self.modules, &other.modules) && unordered(&self.extra, &other.extra)
+    }
+}
+
+impl SemanticEq for Module {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        unordered(&self.attributes, &other.attributes)
+            && unordered(&self.ports, &other.ports)
+            && unordered(&self.cells, &other.cells)
+            && unordered(&self.memories, &other.memories)
+            && unordered(&self.nets, &other.nets)
+            && unordered(&self.extra, &other.extra)
+    }
+}
+
+impl SemanticEq for Port {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self.direction == other.direction
+            && self.bits == other.bits
+            && self.offset == other.offset
+            && self.upto == other.upto
+            && self.signed == other.signed
+            && unordered(&self.extra, &other.extra)
+    }
+}
+
+impl SemanticEq for Cell {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self.module == other.module
+            && unordered(&self.attributes, &other.attributes)
+            && unordered(&self.parameters, &other.parameters)
+            && unordered(&self.port_directions, &other.port_directions)
+            && unordered(&self.connections, &other.connections)
+            && unordered(&self.extra, &other.extra)
+    }
+}
+
+impl SemanticEq for Memory {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.size == other.size
+            && self.start_offset == other.start_offset
+            && unordered(&self.attributes, &other.attributes)
+            && unordered(&self.extra, &other.extra)
+    }
+}
+
+impl SemanticEq for Net {
+    fn semantic_eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+            && self.offset == other.offset
+            && self.upto == other.upto
+            && self.signed == other.signed
+            && unordered(&self.attributes, &other.attributes)
+            && unordered(&self.extra, &other.extra)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equality() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        assert_eq!(netlist, netlist.clone());
+
+        let mut reordered = netlist.clone();
+        reordered.modules.reverse();
+        reordered.modules["test_and"].nets.values_mut().for_each(|net| net.hide_name = !net.hide_name);
+        assert_ne!(reordered, netlist);
+        assert!(reordered.semantic_eq(&netlist));
+
+        let mut extended = netlist.clone();
+        extended.modules["test_or"].cells.values_mut().next().unwrap().extra.insert("vendor".to_string(), 1.into());
+        assert!(!extended.semantic_eq(&netlist));
+        extended.modules["test_or"].cells.values_mut().next().unwrap().extra.clear();
+        assert_eq!(extended, netlist);
+    }
+}
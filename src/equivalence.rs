@@ -0,0 +1,599 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use crate::formal::{Candidate, Certificate, FormalError, ProofResult, ProveOptions, System};
+use crate::{Bit, Cell, Direction, Module, Net, Port};
+
+/// How two register bits were paired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Their outputs have the same net name.
+    Name,
+    /// Their next state functions have the same shape over matched registers and inputs.
+    Structure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterMatch {
+    pub left: String,
+    pub right: String,
+    pub kind: MatchKind,
+}
+
+/// Outcome of [`Module::check_equivalence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EquivalenceReport {
+    /// Register bits that hold the same value in every reachable state.
+    pub matched: Vec<RegisterMatch>,
+    /// Register bits of each module without a partner, including pairs that were guessed
+    /// but do not always agree.
+    pub unmatched_left: Vec<String>,
+    pub unmatched_right: Vec<String>,
+    /// Whether the outputs agree in every cycle. Traces name inputs by port and registers
+    /// with a `left.` or `right.` prefix.
+    pub result: ProofResult,
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The combinational cone of a register's data input.
+struct Cone {
+    /// Input bits feeding it, hashed from port name and index.
+    inputs: Vec<u64>,
+    /// Registers feeding it.
+    registers: Vec<usize>,
+    /// Hash of the truth tables in it.
+    tables: u64,
+}
+
+fn cones(system: &System) -> Vec<Cone> {
+    let drivers: HashMap<Bit, usize> = system.functions.iter().enumerate().map(|(index, (_, output, _))| (*output, index)).collect();
+    let registers: HashMap<Bit, usize> = system.registers.iter().enumerate().map(|(index, register)| (register.q, index)).collect();
+    let inputs: HashMap<Bit, u64> = (system.inputs.iter())
+        .flat_map(|(name, bits)| bits.iter().enumerate().map(move |(index, bit)| (*bit, hash((name, index)))))
+        .collect();
+    system
+        .registers
+        .iter()
+        .map(|register| {
+            let mut cone = Cone { inputs: Vec::new(), registers: Vec::new(), tables: 0 };
+            let mut tables = Vec::new();
+            let mut seen = HashSet::new();
+            let mut stack = vec![register.d];
+            while let Some(bit) = stack.pop() {
+                if !seen.insert(bit) {
+                    continue;
+                }
+                if let Some(&function) = drivers.get(&bit) {
+                    tables.push(&system.functions[function].2);
+                    stack.extend(system.functions[function].0.iter().copied());
+                } else if let Some(&register) = registers.get(&bit) {
+                    cone.registers.push(register);
+                } else if let Some(&input) = inputs.get(&bit) {
+                    cone.inputs.push(input);
+                } else {
+                    cone.inputs.push(hash(bit));
+                }
+            }
+            cone.inputs.sort();
+            tables.sort();
+            cone.tables = hash(tables);
+            cone
+        })
+        .collect()
+}
+
+/// Pairs register bits by name, then repeatedly by signatures of their cones in which
+/// paired registers are interchangeable, until no unique signatures are left to pair.
+fn infer_matches(left: &System, right: &System) -> Vec<(usize, usize, MatchKind)> {
+    let mut matches = Vec::new();
+    let mut tokens: [HashMap<usize, u64>; 2] = [HashMap::new(), HashMap::new()];
+    let names: HashMap<&str, usize> = right.registers.iter().enumerate().map(|(index, register)| (register.name.as_str(), index)).collect();
+    for (index, register) in left.registers.iter().enumerate() {
+        if let Some(&other) = names.get(register.name.as_str()) {
+            matches.push((index, other, MatchKind::Name));
+            tokens[0].insert(index, hash(("name", &register.name)));
+            tokens[1].insert(other, hash(("name", &register.name)));
+        }
+    }
+
+    let systems = [left, right];
+    let cones = systems.map(cones);
+    let mut signatures: [Vec<u64>; 2] = systems.map(|system| vec![0; system.registers.len()]);
+    loop {
+        for side in 0..2 {
+            signatures[side] = (0..systems[side].registers.len())
+                .map(|index| {
+                    let cone = &cones[side][index];
+                    let mut registers: Vec<u64> = (cone.registers.iter())
+                        .map(|register| tokens[side].get(register).copied().unwrap_or(signatures[side][*register]))
+                        .collect();
+                    registers.sort();
+                    hash((systems[side].registers[index].init, &cone.inputs, cone.tables, registers))
+                })
+                .collect();
+        }
+        let mut counts: HashMap<u64, [Vec<usize>; 2]> = HashMap::new();
+        for side in 0..2 {
+            for (index, signature) in signatures[side].iter().enumerate().filter(|(index, _)| !tokens[side].contains_key(index)) {
+                counts.entry(*signature).or_default()[side].push(index);
+            }
+        }
+        let mut paired: Vec<(usize, usize)> = counts
+            .values()
+            .filter_map(|[left, right]| match (&left[..], &right[..]) {
+                ([left], [right]) => Some((*left, *right)),
+                _ => None,
+            })
+            .collect();
+        if paired.is_empty() {
+            return matches;
+        }
+        paired.sort();
+        for (left, right) in paired {
+            matches.push((left, right, MatchKind::Structure));
+            tokens[0].insert(left, hash(("match", left)));
+            tokens[1].insert(right, hash(("match", left)));
+        }
+    }
+}
+
+/// Both modules side by side, sharing the input ports, with an `$assert` per output bit that
+/// the two agree. Names of the left module get a `left.` prefix and of the right `right.`.
+///
+/// An output with a positive latency is compared against the left output that many cycles
+/// earlier, and one with a negative latency against the right output, through chains of
+/// `$_FF_` cells. Its asserts stay disabled until the chains have filled.
+fn miter(left: &Module, right: &Module, latencies: &IndexMap<String, isize>) -> Result<Module, FormalError> {
+    let mismatch = |name: &str| FormalError::InterfaceMismatch(name.to_string());
+    for (name, port) in left.ports.iter() {
+        let other = right.ports.get(name).ok_or_else(|| mismatch(name))?;
+        if other.direction != port.direction || other.bits.len() != port.bits.len() {
+            return Err(mismatch(name));
+        }
+    }
+    if let Some(name) = right.ports.keys().find(|name| !left.ports.contains_key(*name)) {
+        return Err(mismatch(name));
+    }
+    if let Some(name) = latencies.keys().find(|name| left.ports.get(*name).is_none_or(|port| port.direction == Direction::Input)) {
+        return Err(mismatch(name));
+    }
+
+    let [Bit::Signal(offset)] = left.fresh_bits(1)[..] else { unreachable!() };
+    let mut map: HashMap<Bit, Bit> = right.signals().map(|signal| (Bit::Signal(signal), Bit::Signal(signal + offset))).collect();
+    for (name, port) in right.ports.iter().filter(|(_, port)| port.direction == Direction::Input) {
+        map.extend(port.bits.iter().zip(&left.ports[name].bits).map(|(bit, shared)| (*bit, *shared)));
+    }
+    let mut right = right.clone();
+    right.substitute(&map);
+
+    let mut miter = Module::new();
+    for (name, port) in left.ports.iter().filter(|(_, port)| port.direction == Direction::Input) {
+        miter.ports.insert(name.clone(), port.clone());
+    }
+    for (prefix, module) in [("left.", left), ("right.", &right)] {
+        miter.cells.extend(module.cells.iter().map(|(name, cell)| (format!("{}{}", prefix, name).into(), cell.clone())));
+        miter.nets.extend(module.nets.iter().map(|(name, net)| (format!("{}{}", prefix, name).into(), net.clone())));
+    }
+    // One bit per stage of the valid chain, per stage of each delay chain and per comparison.
+    let depth = latencies.values().map(|latency| latency.unsigned_abs()).max().unwrap_or(0);
+    let compared: usize = (left.ports.iter().filter(|(_, port)| port.direction != Direction::Input))
+        .map(|(name, port)| port.bits.len() * (latencies.get(name.as_str()).map_or(0, |latency| latency.unsigned_abs()) + 1))
+        .sum();
+    let mut bits = miter.alloc_bits(depth + compared).into_iter();
+    let mut fresh = || bits.next().unwrap();
+    let delay = |miter: &mut Module, name: String, d: Bit, q: Bit| {
+        let cell = Cell::new("$_FF_").with_connection("D", Direction::Input, vec![d]).with_connection("Q", Direction::Output, vec![q]);
+        miter.cells.insert(name.into(), cell);
+    };
+
+    // valid[k] rises after k cycles.
+    let mut valid = vec![Bit::_1];
+    for cycle in 1..=depth {
+        let q = fresh();
+        delay(&mut miter, format!("$valid[{}]", cycle), *valid.last().unwrap(), q);
+        let mut net = Net::new(vec![q]);
+        net.hide_name = true;
+        net.attributes.insert("init".to_string(), "0".into());
+        miter.nets.insert(format!("$valid[{}]", cycle).into(), net);
+        valid.push(q);
+    }
+
+    for (name, port) in left.ports.iter().filter(|(_, port)| port.direction != Direction::Input) {
+        let latency = latencies.get(name.as_str()).copied().unwrap_or(0);
+        for (index, (a, b)) in port.bits.iter().zip(&right.ports[name].bits).enumerate() {
+            let (mut a, mut b) = (*a, *b);
+            let delayed = if latency > 0 { &mut a } else { &mut b };
+            for cycle in 1..=latency.unsigned_abs() {
+                let q = fresh();
+                delay(&mut miter, format!("$delay.{}[{}][{}]", name, index, cycle), *delayed, q);
+                *delayed = q;
+            }
+            let equal = fresh();
+            let xnor = Cell::new("$_XNOR_")
+                .with_connection("A", Direction::Input, vec![a])
+                .with_connection("B", Direction::Input, vec![b])
+                .with_connection("Y", Direction::Output, vec![equal]);
+            let assert = Cell::new("$assert").with_connection("A", Direction::Input, vec![equal]).with_connection(
+                "EN",
+                Direction::Input,
+                vec![valid[latency.unsigned_abs()]],
+            );
+            miter.cells.insert(format!("$equal.{}[{}]", name, index).into(), xnor);
+            miter.cells.insert(format!("equal.{}[{}]", name, index).into(), assert);
+        }
+    }
+    Ok(miter)
+}
+
+/// Outcome of [`Module::check_equivalence_with_cut_points`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutPointReport {
+    /// Bits of nets named alike in both modules that were proven equal and cut, as
+    /// `net[index]`.
+    pub cut_points: Vec<String>,
+    /// Bits of nets named alike that could not be proven equal, and were not cut.
+    pub dropped: Vec<String>,
+    /// Output bits, as `port[index]`, that only failed with the cut points in place because
+    /// the free cut points took values the designs never produce, and were proven without.
+    pub false_negatives: Vec<String>,
+    /// Whether the outputs agree, with the invariants of every region. A counterexample
+    /// comes from the uncut designs.
+    pub result: ProofResult,
+}
+
+/// Bits of the nets with the same name and width in both modules, other than ports.
+fn cut_candidates(left: &Module, right: &Module) -> Vec<(String, Bit, Bit)> {
+    let ports: HashSet<Bit> = left.ports.values().chain(right.ports.values()).flat_map(|port| port.bits.iter().copied()).collect();
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+    for (name, net) in left.nets.iter().filter(|(_, net)| !net.hide_name) {
+        let Some(other) = right.nets.get(name).filter(|other| !other.hide_name && other.bits.len() == net.bits.len()) else { continue };
+        for (index, (a, b)) in net.bits.iter().zip(&other.bits).enumerate() {
+            if matches!((a, b), (Bit::Signal(_), Bit::Signal(_))) && !ports.contains(a) && !ports.contains(b) && seen.insert((*a, *b)) {
+                candidates.push((format!("{}[{}]", name, index), *a, *b));
+            }
+        }
+    }
+    candidates
+}
+
+/// The cone of `target` in `module`, cut at the `cuts`, which become `cut.`-prefixed input
+/// ports. The target is the output port `target`; the other ports are the module's inputs.
+fn region(module: &Module, target: Bit, cuts: &[(&str, Bit)]) -> Module {
+    let cuts: Vec<&(&str, Bit)> = cuts.iter().filter(|(_, bit)| *bit != target).collect();
+    let fresh = module.fresh_bits(cuts.len());
+    let map: HashMap<Bit, Bit> = cuts.iter().zip(&fresh).map(|((_, bit), fresh)| (*bit, *fresh)).collect();
+
+    let mut region = Module::new();
+    region.nets = module.nets.clone();
+    for (name, port) in module.ports.iter().filter(|(_, port)| port.direction == Direction::Input) {
+        region.ports.insert(name.clone(), port.clone());
+    }
+    for ((name, _), bit) in cuts.iter().zip(&fresh) {
+        region.ports.insert(format!("cut.{}", name).into(), Port::new(Direction::Input, vec![*bit]));
+    }
+    region.ports.insert("target".into(), Port::new(Direction::Output, vec![target]));
+
+    let mut drivers: HashMap<Bit, &str> = HashMap::new();
+    for (name, cell) in module.cells.iter() {
+        for (port, bits) in cell.connections.iter() {
+            if cell.port_directions.get(port) == Some(&Direction::Output) {
+                drivers.extend(bits.iter().map(|bit| (*bit, name.as_str())));
+            }
+        }
+    }
+    let mut stack = vec![target];
+    let mut seen = HashSet::new();
+    while let Some(bit) = stack.pop() {
+        let Some(&name) = drivers.get(&bit) else { continue };
+        if !seen.insert(name) {
+            continue;
+        }
+        let mut cell = module.cells[name].clone();
+        for (port, bits) in cell.connections.iter_mut() {
+            if cell.port_directions.get(port) != Some(&Direction::Output) {
+                bits.iter_mut().for_each(|bit| *bit = *map.get(bit).unwrap_or(bit));
+                stack.extend(bits.iter().copied());
+            }
+        }
+        region.cells.insert(name.into(), cell);
+    }
+    region
+}
+
+impl Module {
+    /// Checks that this module and `other` produce the same outputs in every cycle for the
+    /// same inputs, starting from their initial states. Register bits are paired by name and
+    /// structure, the pairs that always agree are kept as invariants, and the outputs are
+    /// then proven equal by k-induction up to `options.depth` cycles.
+    pub fn check_equivalence(&self, other: &Module, options: &ProveOptions) -> Result<EquivalenceReport, FormalError> {
+        self.check_retimed_equivalence(other, &IndexMap::new(), options)
+    }
+
+    /// Like [`Module::check_equivalence`] for designs that differ in pipelining: each output
+    /// of `other` is compared against this module's output `latencies[output]` cycles
+    /// earlier, or later if negative, once both have run that long. Register pairs are still
+    /// inferred, but retimed registers are expected to end up unmatched.
+    pub fn check_retimed_equivalence(
+        &self,
+        other: &Module,
+        latencies: &IndexMap<String, isize>,
+        options: &ProveOptions,
+    ) -> Result<EquivalenceReport, FormalError> {
+        let (left, right) = (System::new(self)?, System::new(other)?);
+        let miter = miter(self, other, latencies)?;
+        let system = System::new(&miter)?;
+        let budget = options.limits.start();
+        budget.check_cone(system.size())?;
+
+        let indices: HashMap<&str, usize> =
+            system.registers.iter().enumerate().map(|(index, register)| (register.name.as_str(), index)).collect();
+        let mut pairs = Vec::new();
+        for (a, b, kind) in infer_matches(&left, &right) {
+            let (l, r) = (&left.registers[a].name, &right.registers[b].name);
+            if let (Some(&i), Some(&j)) = (indices.get(format!("left.{}", l).as_str()), indices.get(format!("right.{}", r).as_str())) {
+                pairs.push((Candidate::Equal(i, j), RegisterMatch { left: l.clone(), right: r.clone(), kind }));
+            }
+        }
+        let mut candidates: Vec<Candidate> = pairs.iter().map(|(candidate, _)| *candidate).collect();
+        if options.samples > 0 {
+            let guessed: Vec<Candidate> =
+                system.guess(options).into_iter().filter(|guess| !candidates.contains(guess)).collect();
+            candidates.extend(guessed);
+        }
+        let candidates = system.houdini(candidates, &options.engine, &budget)?;
+
+        let matched: Vec<RegisterMatch> =
+            pairs.into_iter().filter(|(candidate, _)| candidates.contains(candidate)).map(|(_, pair)| pair).collect();
+        let unmatched = |system: &System, paired: HashSet<&String>| {
+            system.registers.iter().map(|register| register.name.clone()).filter(|name| !paired.contains(name)).collect()
+        };
+        Ok(EquivalenceReport {
+            unmatched_left: unmatched(&left, matched.iter().map(|pair| &pair.left).collect()),
+            unmatched_right: unmatched(&right, matched.iter().map(|pair| &pair.right).collect()),
+            result: system.induction(&candidates, options, &budget)?,
+            matched,
+        })
+    }
+}
+
+impl Module {
+    /// Checks equivalence region by region, for designs too large to check at once. Nets
+    /// named alike in both modules are taken as cut points: each is proven equal in its own
+    /// cone, with the other cut points as shared free inputs, and those that fail are dropped
+    /// until the rest hold together. Each output bit is then proven over the remaining cut
+    /// points. An output that fails there is checked again without cut points, which tells a
+    /// real difference from one the free cut points made up.
+    pub fn check_equivalence_with_cut_points(&self, other: &Module, options: &ProveOptions) -> Result<CutPointReport, FormalError> {
+        let mut candidates = cut_candidates(self, other);
+        let mut dropped = Vec::new();
+        let (mut depth, mut invariants);
+        let budget = options.limits.start();
+        let prove = |left: Bit, right: Bit, cuts: &[(String, Bit, Bit)]| {
+            let l: Vec<(&str, Bit)> = cuts.iter().map(|(name, a, _)| (name.as_str(), *a)).collect();
+            let r: Vec<(&str, Bit)> = cuts.iter().map(|(name, _, b)| (name.as_str(), *b)).collect();
+            let options = ProveOptions { limits: budget.remaining(), ..options.clone() };
+            region(self, left, &l).check_equivalence(&region(other, right, &r), &options).map(|report| report.result)
+        };
+
+        // Drops failing cut points until every remaining one holds assuming the others.
+        'cuts: loop {
+            (depth, invariants) = (0, Vec::new());
+            for index in 0..candidates.len() {
+                let (_, a, b) = candidates[index];
+                match prove(a, b, &candidates)? {
+                    ProofResult::Proven(certificate) => {
+                        depth = depth.max(certificate.depth);
+                        invariants.extend(certificate.invariants);
+                    }
+                    _ => {
+                        dropped.push(candidates.remove(index).0);
+                        continue 'cuts;
+                    }
+                }
+            }
+            break;
+        }
+
+        let mut false_negatives = Vec::new();
+        let mut unknown = false;
+        for (name, port) in self.ports.iter().filter(|(_, port)| port.direction != Direction::Input) {
+            let bits = &other.ports.get(name).ok_or_else(|| FormalError::InterfaceMismatch(name.to_string()))?.bits;
+            for (index, (a, b)) in port.bits.iter().zip(bits).enumerate() {
+                let result = match prove(*a, *b, &candidates)? {
+                    ProofResult::Proven(certificate) => ProofResult::Proven(certificate),
+                    _ => match prove(*a, *b, &[])? {
+                        ProofResult::Proven(certificate) => {
+                            false_negatives.push(format!("{}[{}]", name, index));
+                            ProofResult::Proven(certificate)
+                        }
+                        ProofResult::Counterexample(mut trace) => {
+                            trace.failed = vec![format!("equal.{}[{}]", name, index)];
+                            return Ok(CutPointReport {
+                                cut_points: candidates.into_iter().map(|(name, _, _)| name).collect(),
+                                dropped,
+                                false_negatives,
+                                result: ProofResult::Counterexample(trace),
+                            });
+                        }
+                        unknown => unknown,
+                    },
+                };
+                match result {
+                    ProofResult::Proven(certificate) => {
+                        depth = depth.max(certificate.depth);
+                        invariants.extend(certificate.invariants);
+                    }
+                    _ => unknown = true,
+                }
+            }
+        }
+        invariants.dedup();
+        Ok(CutPointReport {
+            cut_points: candidates.into_iter().map(|(name, _, _)| name).collect(),
+            dropped,
+            false_negatives,
+            result: match unknown {
+                true => ProofResult::Unknown { depth: options.depth },
+                false => ProofResult::Proven(Certificate { depth, invariants }),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    /// A register sampling the input and one accumulating the parity of the samples, named
+    /// `state`, driving the output inverted if `invert`, and an optional unused register.
+    fn parity(state: &str, invert: bool, spare: bool) -> Module {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let input = builder.input("in", 1).unwrap();
+        let y = builder.output("y", 1).unwrap();
+        let [a, s, d] = ["a", state, "d"].map(|name| builder.wire(name, 1).unwrap());
+        let dff =
+            |d: &[Bit], q: &[Bit]| CellBuilder::new("$_DFF_P_").input("C", clk.clone()).input("D", d.to_vec()).output("Q", q.to_vec());
+        builder.cell("ra", dff(&input, &a)).unwrap();
+        builder.cell("rs", dff(&d, &s)).unwrap();
+        builder.cell("x", CellBuilder::new("$_XOR_").input("A", a).input("B", s.clone()).output("Y", d)).unwrap();
+        builder.cell("o", CellBuilder::new(if invert { "$_NOT_" } else { "$_BUF_" }).input("A", s).output("Y", y)).unwrap();
+        if spare {
+            let q = builder.wire("spare", 1).unwrap();
+            builder.cell("rq", dff(&input, &q)).unwrap();
+        }
+        let mut module = builder.build();
+        for net in ["a", state] {
+            module.nets[net].attributes.insert("init".to_string(), "0".into());
+        }
+        module
+    }
+
+    #[test]
+    fn test_check_equivalence() {
+        let options = ProveOptions { samples: 0, ..ProveOptions::default() };
+        let report = parity("b", false, false).check_equivalence(&parity("q", false, true), &options).unwrap();
+        assert_eq!(
+            report.matched,
+            [
+                RegisterMatch { left: "a".to_string(), right: "a".to_string(), kind: MatchKind::Name },
+                RegisterMatch { left: "b".to_string(), right: "q".to_string(), kind: MatchKind::Structure },
+            ]
+        );
+        assert!(report.unmatched_left.is_empty());
+        assert_eq!(report.unmatched_right, ["spare"]);
+        assert!(matches!(report.result, ProofResult::Proven(_)));
+
+        let report = parity("b", false, false).check_equivalence(&parity("b", true, false), &options).unwrap();
+        let ProofResult::Counterexample(trace) = report.result else { panic!() };
+        assert_eq!(trace.failed, ["equal.y[0]"]);
+        assert_eq!(trace.inputs.len(), 1);
+
+        let mut other = parity("b", false, false);
+        other.ports.shift_remove("in");
+        assert_eq!(parity("b", false, false).check_equivalence(&other, &options), Err(FormalError::InterfaceMismatch("in".to_string())));
+    }
+
+    #[test]
+    fn test_check_retimed_equivalence() {
+        // The same parity with one more register on the output.
+        let mut pipelined = parity("b", false, false);
+        let y = pipelined.ports["y"].bits.clone();
+        let s = pipelined.fresh_bits(1);
+        pipelined.cells["o"].connections["Y"] = s.clone();
+        pipelined.nets.insert("s".into(), Net::new(s.clone()));
+        pipelined.cells.insert(
+            "ry".into(),
+            Cell::new("$_FF_").with_connection("D", Direction::Input, s).with_connection("Q", Direction::Output, y),
+        );
+
+        let options = ProveOptions { samples: 16, ..ProveOptions::default() };
+        let original = parity("b", false, false);
+        let report = original.check_equivalence(&pipelined, &options).unwrap();
+        assert!(matches!(report.result, ProofResult::Counterexample(_)));
+
+        let latencies = IndexMap::from([("y".to_string(), 1)]);
+        let report = original.check_retimed_equivalence(&pipelined, &latencies, &options).unwrap();
+        assert!(matches!(report.result, ProofResult::Proven(_)));
+        let latencies = IndexMap::from([("y".to_string(), -1)]);
+        let report = pipelined.check_retimed_equivalence(&original, &latencies, &options).unwrap();
+        assert!(matches!(report.result, ProofResult::Proven(_)));
+        let latencies = IndexMap::from([("y".to_string(), 2)]);
+        let report = original.check_retimed_equivalence(&pipelined, &latencies, &options).unwrap();
+        assert!(matches!(report.result, ProofResult::Counterexample(_)));
+
+        let latencies = IndexMap::from([("in".to_string(), 1)]);
+        assert_eq!(
+            original.check_retimed_equivalence(&pipelined, &latencies, &options),
+            Err(FormalError::InterfaceMismatch("in".to_string()))
+        );
+    }
+
+    /// `y = t ^ c` over inputs `a`, `b` and `c`, with `t = a & b` written either directly or
+    /// through De Morgan, and an unrelated net `u` that differs between the two.
+    fn and_xor(de_morgan: bool) -> Module {
+        let mut builder = Module::builder();
+        let [a, b, c] = ["a", "b", "c"].map(|name| builder.input(name, 1).unwrap());
+        let y = builder.output("y", 1).unwrap();
+        let [t, u] = ["t", "u"].map(|name| builder.wire(name, 1).unwrap());
+        let gate = |kind: &str, a: &[Bit], b: &[Bit], y: &[Bit]| {
+            CellBuilder::new(kind).input("A", a.to_vec()).input("B", b.to_vec()).output("Y", y.to_vec())
+        };
+        if de_morgan {
+            let [na, nb, o] = ["$na", "$nb", "$o"].map(|name| builder.wire(name, 1).unwrap());
+            builder.cell("na", CellBuilder::new("$_NOT_").input("A", a.clone()).output("Y", na.clone())).unwrap();
+            builder.cell("nb", CellBuilder::new("$_NOT_").input("A", b.clone()).output("Y", nb.clone())).unwrap();
+            builder.cell("or", gate("$_OR_", &na, &nb, &o)).unwrap();
+            builder.cell("not", CellBuilder::new("$_NOT_").input("A", o).output("Y", t.clone())).unwrap();
+            builder.cell("u", gate("$_OR_", &a, &c, &u)).unwrap();
+        } else {
+            builder.cell("and", gate("$_AND_", &a, &b, &t)).unwrap();
+            builder.cell("u", gate("$_AND_", &a, &c, &u)).unwrap();
+        }
+        builder.cell("xor", gate("$_XOR_", &t, &c, &y)).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn test_check_equivalence_with_cut_points() {
+        let options = ProveOptions { samples: 0, ..ProveOptions::default() };
+        let report = and_xor(false).check_equivalence_with_cut_points(&and_xor(true), &options).unwrap();
+        assert_eq!(report.cut_points, ["t[0]"]);
+        assert_eq!(report.dropped, ["u[0]"]);
+        assert!(report.false_negatives.is_empty());
+        assert!(matches!(report.result, ProofResult::Proven(_)));
+
+        // With t cut loose, y = t ^ a differs from a constant zero.
+        let mut left = and_xor(false);
+        left.cells["and"] = Cell::new("$_BUF_").with_connection("A", Direction::Input, left.ports["a"].bits.clone()).with_connection(
+            "Y",
+            Direction::Output,
+            left.nets["t"].bits.clone(),
+        );
+        let mut right = left.clone();
+        left.cells["xor"].connections["B"] = left.ports["a"].bits.clone();
+        right.cells["xor"] = Cell::new("$_BUF_").with_connection("A", Direction::Input, vec![Bit::_0]).with_connection(
+            "Y",
+            Direction::Output,
+            right.ports["y"].bits.clone(),
+        );
+        let report = left.check_equivalence_with_cut_points(&right, &options).unwrap();
+        assert_eq!(report.cut_points, ["t[0]", "u[0]"]);
+        assert_eq!(report.false_negatives, ["y[0]"]);
+        assert!(matches!(report.result, ProofResult::Proven(_)));
+
+        right.cells["xor"].connections["A"] = vec![Bit::_1];
+        let report = left.check_equivalence_with_cut_points(&right, &options).unwrap();
+        let ProofResult::Counterexample(trace) = report.result else { panic!() };
+        assert_eq!(trace.failed, ["equal.y[0]"]);
+    }
+}
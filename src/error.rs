@@ -0,0 +1,106 @@
+use std::fmt;
+use std::io;
+
+use serde_json::error::Category;
+
+/// Errors reading or writing a netlist.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The input is not well formed JSON.
+    Syntax(serde_json::Error),
+    /// Well formed JSON that does not describe a netlist. `path` leads to the offending
+    /// value, as in `modules.top.cells.$add$x:12.connections.A`.
+    Structure {
+        path: String,
+        error: serde_json::Error,
+    },
+    /// A key the netlist does not know, found by the strict readers. `path` ends in the key.
+    UnknownField {
+        path: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Syntax(error) => write!(f, "invalid JSON: {}", error),
+            Self::Structure { path, error } => write!(f, "{}: {}", path, error),
+            Self::UnknownField { path } => write!(f, "{}: unknown field", path),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Syntax(error) | Self::Structure { error, .. } => Some(error),
+            Self::UnknownField { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl Error {
+    fn classify(path: String, error: serde_json::Error) -> Self {
+        match error.classify() {
+            Category::Io => Self::Io(error.into()),
+            Category::Syntax | Category::Eof => Self::Syntax(error),
+            Category::Data => Self::Structure { path, error },
+        }
+    }
+
+    /// An error in the value at `path`, deserialized apart from the rest of the document.
+    pub(crate) fn within(path: &str, error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let path = match error.path().to_string() {
+            inner if inner == "." => path.to_string(),
+            inner => format!("{}.{}", path, inner),
+        };
+        Self::classify(path, error.into_inner())
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        Self::classify(error.path().to_string(), error.into_inner())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::classify(".".to_string(), error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    #[test]
+    fn test_error_path() {
+        let input =
+            r#"{"creator": "test", "modules": {"top": {"cells": {"$add$x:12": {"type": "$add", "connections": {"A": [2, "q"]}}}}}}"#;
+        match Netlist::from_str(input) {
+            Err(Error::Structure { path, .. }) => assert_eq!(path, "modules.top.cells.$add$x:12.connections.A[1]"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(Netlist::from_str(r#"{"creator": "test", "modules": {]}"#), Err(Error::Syntax(_))));
+        assert!(matches!(Netlist::from_str(r#"{"creator": "test", "modules": {}} trailing"#), Err(Error::Syntax(_))));
+
+        struct Broken;
+        impl io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken"))
+            }
+        }
+        assert!(matches!(Netlist::from_reader(Broken), Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::checkpoint::{Checkpoint, CheckpointError, encode, parse};
+use crate::gates::function;
+use crate::{Bit, Direction, Module};
+
+/// Delta cycles allowed at a single point in time before the logic counts as oscillating.
+pub const MAX_DELTAS: usize = 1000;
+
+/// Propagation delay of cells, in simulation time units. A delay of 0 makes the output change
+/// in the next delta cycle of the same time step.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delays {
+    pub default: u64,
+    /// Delays by cell type.
+    pub types: IndexMap<String, u64>,
+    /// Delays of single cells, taking precedence over their type.
+    pub cells: IndexMap<String, u64>,
+}
+
+impl Delays {
+    pub(crate) fn of(&self, name: &str, module: &str) -> u64 {
+        self.cells.get(name).or_else(|| self.types.get(module)).copied().unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSimError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    UnknownPort(String),
+    /// A clock must drive a single bit input and have a period of at least 2.
+    InvalidClock(String),
+    /// The logic did not settle within [`MAX_DELTAS`] delta cycles.
+    Oscillation {
+        time: u64,
+    },
+    Checkpoint(CheckpointError),
+}
+
+impl fmt::Display for EventSimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot simulate cell {} of type {}", cell, module),
+            Self::UnknownPort(port) => write!(f, "no input port {}", port),
+            Self::InvalidClock(port) => write!(f, "cannot drive a clock on port {}", port),
+            Self::Oscillation { time } => write!(f, "logic does not settle at time {}", time),
+            Self::Checkpoint(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for EventSimError {}
+
+/// A free-running clock on an input port, rising at `phase`, `phase + period`, … and falling
+/// half a period after each rising edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clock {
+    pub port: String,
+    pub period: u64,
+    pub phase: u64,
+}
+
+impl Clock {
+    pub fn new(port: &str, period: u64, phase: u64) -> Self {
+        Self { port: port.to_string(), period, phase }
+    }
+
+    /// Time and value of edge number `edge`, rising edges being the even ones.
+    fn edge(&self, edge: u64) -> (u64, bool) {
+        let start = self.phase + edge / 2 * self.period;
+        match edge % 2 {
+            0 => (start, true),
+            _ => (start + self.period / 2, false),
+        }
+    }
+}
+
+enum Behavior {
+    Function { inputs: Vec<Bit>, table: Vec<bool> },
+    Flop { clock: Bit, data: Bit, edge: bool },
+    Latch { enable: Bit, data: Bit, level: bool },
+}
+
+struct Process {
+    behavior: Behavior,
+    output: Bit,
+    delay: u64,
+}
+
+/// Event-driven simulator for gate-level modules: gates, LUTs and the `$_DFF_P_`, `$_DFF_N_`,
+/// `$_DLATCH_P_` and `$_DLATCH_N_` registers. Changes propagate through cells after their
+/// delay, so feedback latches, several [`Clock`]s and clocks derived through gating or
+/// dividers in the netlist behave like in an HDL simulator.
+pub struct EventSimulator {
+    ports: IndexMap<String, (Direction, Vec<Bit>)>,
+    processes: Vec<Process>,
+    fanout: HashMap<Bit, Vec<usize>>,
+    values: HashMap<Bit, bool>,
+    queue: BTreeMap<u64, Vec<(Bit, bool)>>,
+    time: u64,
+    delta_cycles: usize,
+    /// Clocks with their driven bit and the next edge to schedule.
+    clocks: Vec<(Clock, Bit, u64)>,
+    /// Outputs of the registers, by cell name.
+    registers: IndexMap<String, Bit>,
+}
+
+impl EventSimulator {
+    /// Prepares a simulation starting at time 0, with registers holding their `init` value and
+    /// every other bit 0.
+    pub fn new(module: &Module, delays: &Delays) -> Result<Self, EventSimError> {
+        let mut processes = Vec::new();
+        let mut registers = IndexMap::new();
+        for (name, cell) in module.cells.iter() {
+            let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied();
+            let unsupported = || EventSimError::Unsupported { cell: name.to_string(), module: cell.module.to_string() };
+            let (behavior, output) = match (cell.module.as_str(), function(cell)) {
+                (_, Some((inputs, output, table))) => (Behavior::Function { inputs, table }, output),
+                ("$_DFF_P_" | "$_DFF_N_", _) => {
+                    let (clock, data, output) =
+                        bit("C").zip(bit("D")).zip(bit("Q")).map(|((c, d), q)| (c, d, q)).ok_or_else(unsupported)?;
+                    (Behavior::Flop { clock, data, edge: cell.module == "$_DFF_P_" }, output)
+                }
+                ("$_DLATCH_P_" | "$_DLATCH_N_", _) => {
+                    let (enable, data, output) =
+                        bit("E").zip(bit("D")).zip(bit("Q")).map(|((e, d), q)| (e, d, q)).ok_or_else(unsupported)?;
+                    (Behavior::Latch { enable, data, level: cell.module == "$_DLATCH_P_" }, output)
+                }
+                _ => return Err(unsupported()),
+            };
+            if !matches!(behavior, Behavior::Function { .. }) {
+                registers.insert(name.to_string(), output);
+            }
+            processes.push(Process { behavior, output, delay: delays.of(name, &cell.module) });
+        }
+
+        let mut fanout: HashMap<Bit, Vec<usize>> = HashMap::new();
+        for (index, process) in processes.iter().enumerate() {
+            let inputs = match &process.behavior {
+                Behavior::Function { inputs, .. } => inputs.clone(),
+                Behavior::Flop { clock, .. } => vec![*clock],
+                Behavior::Latch { enable, data, .. } => vec![*enable, *data],
+            };
+            for bit in inputs {
+                fanout.entry(bit).or_default().push(index);
+            }
+        }
+        let mut values = module.initial_values();
+        values.insert(Bit::_1, true);
+        let ports = module.ports.iter().map(|(name, port)| (name.to_string(), (port.direction.clone(), port.bits.clone()))).collect();
+        let queue = BTreeMap::new();
+        let mut simulator = Self { ports, processes, fanout, values, queue, time: 0, delta_cycles: 0, clocks: Vec::new(), registers };
+        for index in 0..simulator.processes.len() {
+            if let Behavior::Function { .. } = simulator.processes[index].behavior {
+                simulator.schedule(index, &HashMap::new());
+            }
+        }
+        Ok(simulator)
+    }
+
+    fn value(&self, bit: &Bit) -> bool {
+        self.values.get(bit).copied().unwrap_or(false)
+    }
+
+    /// Evaluates a process after its inputs changed, `previous` holding the old values of the
+    /// bits changed in this delta cycle.
+    fn schedule(&mut self, index: usize, previous: &HashMap<Bit, bool>) {
+        let process = &self.processes[index];
+        let value = match &process.behavior {
+            Behavior::Function { inputs, table } => {
+                table[inputs.iter().enumerate().fold(0, |index, (input, bit)| index | (self.value(bit) as usize) << input)]
+            }
+            Behavior::Flop { clock, data, edge } => match previous.get(clock) {
+                Some(old) if *old != *edge && self.value(clock) == *edge => self.value(data),
+                _ => return,
+            },
+            Behavior::Latch { enable, data, level } => match self.value(enable) == *level {
+                true => self.value(data),
+                false => return,
+            },
+        };
+        self.queue.entry(self.time + process.delay).or_default().push((process.output, value));
+    }
+
+    /// Runs the delta cycles of the current time step until nothing changes any more.
+    fn settle(&mut self) -> Result<(), EventSimError> {
+        let mut deltas = 0;
+        while let Some(events) = self.queue.remove(&self.time) {
+            deltas += 1;
+            if deltas > MAX_DELTAS {
+                return Err(EventSimError::Oscillation { time: self.time });
+            }
+            self.delta_cycles += 1;
+            let mut previous = HashMap::new();
+            for (bit, value) in events {
+                let old = self.value(&bit);
+                if old != value {
+                    previous.entry(bit).or_insert(old);
+                }
+                self.values.insert(bit, value);
+            }
+            let triggered: BTreeSet<usize> = previous
+                .iter()
+                .filter(|(bit, old)| self.value(bit) != **old)
+                .flat_map(|(bit, _)| self.fanout.get(bit).into_iter().flatten().copied())
+                .collect();
+            for index in triggered {
+                self.schedule(index, &previous);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drives an input port, taking effect at the current time.
+    pub fn set(&mut self, port: &str, value: &[bool]) -> Result<(), EventSimError> {
+        let bits = match self.ports.get(port) {
+            Some((direction, bits)) if *direction != Direction::Output => bits,
+            _ => return Err(EventSimError::UnknownPort(port.to_string())),
+        };
+        let events = bits.iter().zip(value.iter().chain(std::iter::repeat(&false))).map(|(bit, value)| (*bit, *value));
+        self.queue.entry(self.time).or_default().extend(events);
+        Ok(())
+    }
+
+    /// Drives a clock on a single bit input port from the current time on. Edges before the
+    /// current time are skipped.
+    pub fn add_clock(&mut self, clock: Clock) -> Result<(), EventSimError> {
+        let bit = match self.ports.get(&clock.port) {
+            Some((direction, bits)) if *direction != Direction::Output && bits.len() == 1 && clock.period >= 2 => bits[0],
+            Some(_) => return Err(EventSimError::InvalidClock(clock.port)),
+            None => return Err(EventSimError::UnknownPort(clock.port)),
+        };
+        let next = (0..).find(|edge| clock.edge(*edge).0 >= self.time).unwrap();
+        self.clocks.push((clock, bit, next));
+        Ok(())
+    }
+
+    /// Value of a port, least significant bit first.
+    pub fn get(&self, port: &str) -> Option<Vec<bool>> {
+        self.ports.get(port).map(|(_, bits)| bits.iter().map(|bit| self.value(bit)).collect())
+    }
+
+    /// Processes every event up to and including time `until`, then advances to it.
+    pub fn run_until(&mut self, until: u64) -> Result<(), EventSimError> {
+        for (clock, bit, next) in self.clocks.iter_mut() {
+            while let (time, value) = clock.edge(*next)
+                && time <= until
+            {
+                self.queue.entry(time).or_default().push((*bit, value));
+                *next += 1;
+            }
+        }
+        loop {
+            self.settle()?;
+            match self.queue.keys().next() {
+                Some(next) if *next <= until => self.time = *next,
+                _ => break,
+            }
+        }
+        self.time = self.time.max(until);
+        Ok(())
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Delta cycles run so far, over all time steps.
+    pub fn delta_cycles(&self) -> usize {
+        self.delta_cycles
+    }
+
+    /// The current time and the outputs of the registers.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let registers = self.registers.iter().map(|(name, bit)| (name.clone(), encode(&[Some(self.value(bit))])));
+        Checkpoint { time: self.time, registers: registers.collect(), ..Checkpoint::default() }
+    }
+
+    /// Continues from `checkpoint` at its time, with the registers it holds loaded, `x` bits
+    /// as 0. Pending events are dropped, the inputs keep their values and the gates are
+    /// evaluated again, and clocks resume with their first edge from the new time on.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), EventSimError> {
+        if let Some(name) = checkpoint.memories.keys().next() {
+            return Err(EventSimError::Checkpoint(CheckpointError::UnknownMemory(name.clone())));
+        }
+        let mut updates = Vec::new();
+        for (name, value) in checkpoint.registers.iter() {
+            let Some(bit) = self.registers.get(name) else {
+                return Err(EventSimError::Checkpoint(CheckpointError::UnknownRegister(name.clone())));
+            };
+            let value = parse(name, value, 1).map_err(EventSimError::Checkpoint)?;
+            updates.push((*bit, value[0].unwrap_or(false)));
+        }
+
+        self.values.extend(updates);
+        self.queue.clear();
+        self.time = checkpoint.time;
+        for (clock, _, next) in self.clocks.iter_mut() {
+            *next = (0..).find(|edge| clock.edge(*edge).0 >= checkpoint.time).unwrap();
+        }
+        for index in 0..self.processes.len() {
+            if let Behavior::Function { .. } | Behavior::Latch { .. } = self.processes[index].behavior {
+                self.schedule(index, &HashMap::new());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, Port};
+
+    fn cell(module: &str, connections: &[(&str, Direction, u64)]) -> Cell {
+        connections
+            .iter()
+            .fold(Cell::new(module), |cell, (port, direction, bit)| cell.with_connection(port, direction.clone(), vec![Bit::Signal(*bit)]))
+    }
+
+    #[test]
+    fn test_latch_and_clocks() {
+        use Direction::{Input, Output};
+        let mut module = Module::new();
+        for (name, direction, bit) in [("s", Input, 2), ("r", Input, 3), ("q", Output, 4), ("c1", Input, 6), ("c2", Input, 7)] {
+            module.ports.insert(name.into(), Port::new(direction, vec![Bit::Signal(bit)]));
+        }
+        module.ports.insert("count".into(), Port::new(Output, vec![Bit::Signal(8), Bit::Signal(9)]));
+        // Set-reset latch out of two cross-coupled NOR gates.
+        module.cells.insert("nor1".into(), cell("$_NOR_", &[("A", Input, 3), ("B", Input, 5), ("Y", Output, 4)]));
+        module.cells.insert("nor2".into(), cell("$_NOR_", &[("A", Input, 2), ("B", Input, 4), ("Y", Output, 5)]));
+        // Two flops on different clocks, each toggling.
+        module.cells.insert("inv1".into(), cell("$_NOT_", &[("A", Input, 8), ("Y", Output, 10)]));
+        module.cells.insert("ff1".into(), cell("$_DFF_P_", &[("C", Input, 6), ("D", Input, 10), ("Q", Output, 8)]));
+        module.cells.insert("inv2".into(), cell("$_NOT_", &[("A", Input, 9), ("Y", Output, 11)]));
+        module.cells.insert("ff2".into(), cell("$_DFF_N_", &[("C", Input, 7), ("D", Input, 11), ("Q", Output, 9)]));
+
+        let delays = Delays { default: 1, types: IndexMap::from([("$_NOT_".to_string(), 0)]), ..Delays::default() };
+        let mut simulator = EventSimulator::new(&module, &delays).unwrap();
+        simulator.set("s", &[true]).unwrap();
+        simulator.run_until(10).unwrap();
+        simulator.set("s", &[false]).unwrap();
+        simulator.run_until(20).unwrap();
+        assert_eq!(simulator.get("q").unwrap(), [true]);
+        simulator.set("r", &[true]).unwrap();
+        simulator.run_until(30).unwrap();
+        assert_eq!(simulator.get("q").unwrap(), [false]);
+
+        for time in (40..100).step_by(10) {
+            simulator.set("c1", &[time % 20 == 0]).unwrap();
+            simulator.set("c2", &[time % 30 == 10]).unwrap();
+            simulator.run_until(time).unwrap();
+        }
+        // c1 rose at 40, 60 and 80, c2 fell at 50 and 80.
+        assert_eq!(simulator.get("count").unwrap(), [true, false]);
+        assert_eq!(simulator.time(), 90);
+        assert_eq!(simulator.set("q", &[true]), Err(EventSimError::UnknownPort("q".to_string())));
+    }
+
+    #[test]
+    fn test_derived_clocks() {
+        use Direction::{Input, Output};
+        let mut module = Module::new();
+        for (name, direction, bit) in [("clk", Input, 2), ("fast", Input, 3), ("en", Input, 4)] {
+            module.ports.insert(name.into(), Port::new(direction, vec![Bit::Signal(bit)]));
+        }
+        module.ports.insert("div".into(), Port::new(Output, vec![Bit::Signal(10), Bit::Signal(11)]));
+        module.ports.insert("gated".into(), Port::new(Output, vec![Bit::Signal(12)]));
+        module.ports.insert("fast_count".into(), Port::new(Output, vec![Bit::Signal(13)]));
+        // Ripple divider: the second flop is clocked by the output of the first.
+        module.cells.insert("inv0".into(), cell("$_NOT_", &[("A", Input, 10), ("Y", Output, 20)]));
+        module.cells.insert("div0".into(), cell("$_DFF_P_", &[("C", Input, 2), ("D", Input, 20), ("Q", Output, 10)]));
+        module.cells.insert("inv1".into(), cell("$_NOT_", &[("A", Input, 11), ("Y", Output, 21)]));
+        module.cells.insert("div1".into(), cell("$_DFF_N_", &[("C", Input, 10), ("D", Input, 21), ("Q", Output, 11)]));
+        // Flop on a gated clock.
+        module.cells.insert("gate".into(), cell("$_AND_", &[("A", Input, 2), ("B", Input, 4), ("Y", Output, 22)]));
+        module.cells.insert("inv2".into(), cell("$_NOT_", &[("A", Input, 12), ("Y", Output, 23)]));
+        module.cells.insert("gated".into(), cell("$_DFF_P_", &[("C", Input, 22), ("D", Input, 23), ("Q", Output, 12)]));
+        module.cells.insert("inv3".into(), cell("$_NOT_", &[("A", Input, 13), ("Y", Output, 24)]));
+        module.cells.insert("fast".into(), cell("$_DFF_P_", &[("C", Input, 3), ("D", Input, 24), ("Q", Output, 13)]));
+
+        let mut simulator = EventSimulator::new(&module, &Delays { default: 1, ..Delays::default() }).unwrap();
+        simulator.add_clock(Clock::new("clk", 10, 5)).unwrap();
+        simulator.add_clock(Clock::new("fast", 4, 1)).unwrap();
+        simulator.set("en", &[true]).unwrap();
+        simulator.run_until(20).unwrap();
+        assert_eq!(simulator.get("gated").unwrap(), [false]);
+        simulator.set("en", &[false]).unwrap();
+        simulator.run_until(27).unwrap();
+        // clk rose at 5, 15 and 25.
+        assert_eq!(simulator.get("div").unwrap(), [true, true]);
+        assert_eq!(simulator.get("gated").unwrap(), [false]);
+        simulator.run_until(42).unwrap();
+        assert_eq!(simulator.get("div").unwrap(), [false, false]);
+        // fast rose at 1, 5, …, 41.
+        assert_eq!(simulator.get("fast_count").unwrap(), [true]);
+        assert_eq!(simulator.add_clock(Clock::new("div", 10, 0)), Err(EventSimError::InvalidClock("div".to_string())));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        use Direction::{Input, Output};
+        let mut module = Module::new();
+        module.ports.insert("clk".into(), Port::new(Input, vec![Bit::Signal(2)]));
+        module.ports.insert("div".into(), Port::new(Output, vec![Bit::Signal(10), Bit::Signal(11)]));
+        module.cells.insert("inv0".into(), cell("$_NOT_", &[("A", Input, 10), ("Y", Output, 20)]));
+        module.cells.insert("div0".into(), cell("$_DFF_P_", &[("C", Input, 2), ("D", Input, 20), ("Q", Output, 10)]));
+        module.cells.insert("inv1".into(), cell("$_NOT_", &[("A", Input, 11), ("Y", Output, 21)]));
+        module.cells.insert("div1".into(), cell("$_DFF_N_", &[("C", Input, 10), ("D", Input, 21), ("Q", Output, 11)]));
+        let delays = Delays { default: 1, ..Delays::default() };
+
+        let mut simulator = EventSimulator::new(&module, &delays).unwrap();
+        simulator.add_clock(Clock::new("clk", 10, 5)).unwrap();
+        simulator.run_until(27).unwrap();
+        let checkpoint = simulator.checkpoint();
+        assert_eq!(checkpoint.time, 27);
+        assert_eq!(checkpoint.registers, IndexMap::from([("div0".to_string(), "1".to_string()), ("div1".to_string(), "1".to_string())]));
+
+        // The clock resumes with its falling edge at 30.
+        let mut restored = EventSimulator::new(&module, &delays).unwrap();
+        restored.add_clock(Clock::new("clk", 10, 5)).unwrap();
+        restored.restore(&checkpoint).unwrap();
+        assert_eq!(restored.time(), 27);
+        simulator.run_until(62).unwrap();
+        restored.run_until(62).unwrap();
+        assert_eq!(restored.get("div").unwrap(), [false, true]);
+        assert_eq!(restored.checkpoint(), simulator.checkpoint());
+
+        let memory = Checkpoint { memories: IndexMap::from([("ram".to_string(), Vec::new())]), ..Checkpoint::default() };
+        assert_eq!(restored.restore(&memory), Err(EventSimError::Checkpoint(CheckpointError::UnknownMemory("ram".to_string()))));
+    }
+
+    #[test]
+    fn test_oscillation() {
+        let mut module = Module::new();
+        module.cells.insert("ring".into(), cell("$_NOT_", &[("A", Direction::Input, 2), ("Y", Direction::Output, 2)]));
+        let mut simulator = EventSimulator::new(&module, &Delays::default()).unwrap();
+        assert_eq!(simulator.run_until(0), Err(EventSimError::Oscillation { time: 0 }));
+
+        let mut simulator = EventSimulator::new(&module, &Delays { default: 5, ..Delays::default() }).unwrap();
+        simulator.run_until(12).unwrap();
+        assert_eq!(simulator.delta_cycles(), 2);
+    }
+}
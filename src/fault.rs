@@ -0,0 +1,254 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::rng::{SeedPolicy, SplitMix64};
+use crate::{Bit, Direction, IdString, Module};
+
+/// Interchangeable cell types, a gate swap replaces a cell by another member of its family.
+const GATE_FAMILIES: &[&[&str]] = &[
+    &["$_AND_", "$_NAND_", "$_OR_", "$_NOR_", "$_XOR_", "$_XNOR_", "$_ANDNOT_", "$_ORNOT_"],
+    &["$and", "$or", "$xor", "$xnor"],
+    &["$lt", "$le", "$gt", "$ge", "$eq", "$ne"],
+    &["$add", "$sub"],
+    &["$logic_and", "$logic_or"],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultKind {
+    ConstantFlip,
+    GateSwap,
+    ConnectionSwap,
+}
+
+/// A fault that does not fit the module it is applied to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultError {
+    UnknownCell(String),
+    UnknownBit { cell: String, port: String, offset: usize },
+    /// A constant flip on a bit that is not a constant `0` or `1`.
+    NotConstant { cell: String, port: String, offset: usize },
+}
+
+impl fmt::Display for FaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCell(cell) => write!(f, "unknown cell {:?}", cell),
+            Self::UnknownBit { cell, port, offset } => write!(f, "cell {:?} has no bit {} on port {:?}", cell, offset, port),
+            Self::NotConstant { cell, port, offset } => write!(f, "bit {} on port {:?} of cell {:?} is not a constant", offset, port, cell),
+        }
+    }
+}
+
+impl std::error::Error for FaultError {}
+
+/// One injected fault, enough to replay it on the original module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fault {
+    /// Inverts a constant `0`/`1` bit connected to a cell input.
+    ConstantFlip { cell: String, port: String, offset: usize },
+    /// Changes the type of a cell.
+    GateSwap { cell: String, from: String, to: String },
+    /// Exchanges two input bits of the same cell. The `A` and `B` bits at the same offset of a
+    /// commutative cell are never paired, as exchanging them rarely changes what the cell computes.
+    ConnectionSwap { cell: String, first: (String, usize), second: (String, usize) },
+}
+
+impl Fault {
+    pub fn kind(&self) -> FaultKind {
+        match self {
+            Self::ConstantFlip { .. } => FaultKind::ConstantFlip,
+            Self::GateSwap { .. } => FaultKind::GateSwap,
+            Self::ConnectionSwap { .. } => FaultKind::ConnectionSwap,
+        }
+    }
+
+    /// Injects the fault into `module`, which is left unchanged if the cell or a bit the fault
+    /// names is missing, or the bit to flip is not a constant.
+    pub fn apply(&self, module: &mut Module) -> Result<(), FaultError> {
+        let name = match self {
+            Self::ConstantFlip { cell, .. } | Self::GateSwap { cell, .. } | Self::ConnectionSwap { cell, .. } => cell,
+        };
+        let cell = module.cells.get_mut(name).ok_or_else(|| FaultError::UnknownCell(name.clone()))?;
+        let bit = |connections: &IndexMap<IdString, Vec<Bit>>, (port, offset): (&String, usize)| {
+            connections.get(port).and_then(|bits| bits.get(offset)).copied().ok_or_else(|| FaultError::UnknownBit {
+                cell: name.clone(),
+                port: port.clone(),
+                offset,
+            })
+        };
+        match self {
+            Self::ConstantFlip { port, offset, .. } => {
+                let flipped = match bit(&cell.connections, (port, *offset))? {
+                    Bit::_0 => Bit::_1,
+                    Bit::_1 => Bit::_0,
+                    _ => return Err(FaultError::NotConstant { cell: name.clone(), port: port.clone(), offset: *offset }),
+                };
+                cell.connections[port][*offset] = flipped;
+            }
+            Self::GateSwap { to, .. } => cell.module = to.into(),
+            Self::ConnectionSwap { first, second, .. } => {
+                let a = bit(&cell.connections, (&first.0, first.1))?;
+                let b = bit(&cell.connections, (&second.0, second.1))?;
+                cell.connections[&first.0][first.1] = b;
+                cell.connections[&second.0][second.1] = a;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A deterministic selection of faults; the manifest of a campaign.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultCampaign {
+    /// The seed the campaign was asked for.
+    pub seed: u64,
+    /// The seed the faults were drawn with, after the campaign's [`SeedPolicy`]. Running the
+    /// campaign again from this seed under [`SeedPolicy::Explicit`] gives the same faults.
+    pub effective_seed: u64,
+    pub faults: Vec<Fault>,
+}
+
+impl FaultCampaign {
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    /// One mutated copy of `module` per fault, in manifest order, or why the fault does not
+    /// apply to `module`.
+    pub fn mutants<'a>(&'a self, module: &'a Module) -> impl Iterator<Item = (&'a Fault, Result<Module, FaultError>)> + 'a {
+        self.faults.iter().map(move |fault| {
+            let mut mutant = module.clone();
+            (fault, fault.apply(&mut mutant).map(|()| mutant))
+        })
+    }
+}
+
+impl Module {
+    /// Every fault of the given kinds that can be injected into this module, in a stable order.
+    pub fn fault_sites(&self, kinds: &[FaultKind]) -> Vec<Fault> {
+        let mut faults = Vec::new();
+        for (name, cell) in self.cells.iter() {
+            let inputs: Vec<(&IdString, &Vec<Bit>)> =
+                cell.connections.iter().filter(|(port, _)| cell.port_directions.get(*port) == Some(&Direction::Input)).collect();
+            if kinds.contains(&FaultKind::ConstantFlip) {
+                for (port, bits) in inputs.iter() {
+                    for (offset, bit) in bits.iter().enumerate() {
+                        if matches!(bit, Bit::_0 | Bit::_1) {
+                            faults.push(Fault::ConstantFlip { cell: name.to_string(), port: port.to_string(), offset });
+                        }
+                    }
+                }
+            }
+            let family = GATE_FAMILIES.iter().find(|family| family.contains(&cell.module.as_str()));
+            if let Some(family) = family.filter(|_| kinds.contains(&FaultKind::GateSwap)) {
+                for to in family.iter().filter(|to| **to != cell.module) {
+                    faults.push(Fault::GateSwap { cell: name.to_string(), from: cell.module.to_string(), to: to.to_string() });
+                }
+            }
+            if kinds.contains(&FaultKind::ConnectionSwap) {
+                let bits: Vec<(&IdString, usize, &Bit)> =
+                    inputs.iter().flat_map(|(port, bits)| bits.iter().enumerate().map(move |(offset, bit)| (*port, offset, bit))).collect();
+                for (index, (port, offset, bit)) in bits.iter().enumerate() {
+                    for (other_port, other_offset, other_bit) in bits[index + 1..].iter() {
+                        let commuted = cell.is_commutative()
+                            && offset == other_offset
+                            && matches!((port.as_str(), other_port.as_str()), ("A", "B") | ("B", "A"));
+                        if bit != other_bit && !commuted {
+                            faults.push(Fault::ConnectionSwap {
+                                cell: name.to_string(),
+                                first: (port.to_string(), *offset),
+                                second: (other_port.to_string(), *other_offset),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        faults
+    }
+
+    /// Picks up to `count` distinct faults of the given kinds, reproducibly from `seed` under
+    /// `policy`. The campaign records both `seed` and the seed the policy turned it into.
+    pub fn fault_campaign(&self, kinds: &[FaultKind], count: usize, seed: u64, policy: SeedPolicy) -> FaultCampaign {
+        let mut faults = self.fault_sites(kinds);
+        let effective_seed = policy.apply(seed);
+        let mut rng = SplitMix64::new(effective_seed);
+        for index in (1..faults.len()).rev() {
+            faults.swap(index, rng.below(index + 1));
+        }
+        faults.truncate(count);
+        FaultCampaign { seed, effective_seed, faults }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, Netlist};
+
+    #[test]
+    fn test_fault_campaign() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let adder = &netlist.modules["adder"];
+        let kinds = [FaultKind::GateSwap, FaultKind::ConnectionSwap];
+
+        let campaign = adder.fault_campaign(&kinds, 20, 1234, SeedPolicy::Explicit);
+        assert_eq!((campaign.seed, campaign.effective_seed, campaign.faults.len()), (1234, 1234, 20));
+        assert_eq!(campaign, adder.fault_campaign(&kinds, 20, 1234, SeedPolicy::Explicit));
+        assert_ne!(campaign, adder.fault_campaign(&kinds, 20, 4321, SeedPolicy::Explicit));
+        let salted = adder.fault_campaign(&kinds, 20, 1234, SeedPolicy::Salted(9));
+        assert_eq!(salted.faults, adder.fault_campaign(&kinds, 20, salted.effective_seed, SeedPolicy::Explicit).faults);
+        assert_eq!(adder.fault_campaign(&kinds, 20, 4321, SeedPolicy::Fixed(1234)).faults, campaign.faults);
+
+        let mut manifest = Vec::new();
+        campaign.to_writer(&mut manifest).unwrap();
+        assert_eq!(FaultCampaign::from_reader(manifest.as_slice()).unwrap(), campaign);
+
+        for (fault, mutant) in campaign.mutants(adder) {
+            let mutant = mutant.unwrap();
+            match fault {
+                Fault::GateSwap { cell, to, .. } => assert_eq!(&mutant.cells[cell].module, to),
+                Fault::ConnectionSwap { cell, first, .. } => {
+                    assert_ne!(mutant.cells[cell].connections[&first.0], adder.cells[cell].connections[&first.0])
+                }
+                Fault::ConstantFlip { .. } => unreachable!(),
+            }
+        }
+
+        let mut mutant = adder.clone();
+        let missing = Fault::GateSwap { cell: "nope".to_string(), from: "$and".to_string(), to: "$or".to_string() };
+        assert_eq!(missing.apply(&mut mutant), Err(FaultError::UnknownCell("nope".to_string())));
+        let (name, _) = adder.cells.first().unwrap();
+        let beyond = Fault::ConstantFlip { cell: name.to_string(), port: "A".to_string(), offset: 1000 };
+        assert_eq!(beyond.apply(&mut mutant), Err(FaultError::UnknownBit { cell: name.to_string(), port: "A".to_string(), offset: 1000 }));
+        let (port, _) = adder.cells[0].connections.iter().find(|(_, bits)| matches!(bits[0], Bit::Signal(_))).unwrap();
+        let signal = Fault::ConstantFlip { cell: name.to_string(), port: port.to_string(), offset: 0 };
+        assert_eq!(signal.apply(&mut mutant), Err(FaultError::NotConstant { cell: name.to_string(), port: port.to_string(), offset: 0 }));
+        assert_eq!(&mutant, adder);
+    }
+
+    #[test]
+    fn test_commutative_swaps() {
+        let mut module = Module::new();
+        for (name, module_type) in [("and", "$_AND_"), ("andnot", "$_ANDNOT_")] {
+            let cell = Cell::new(module_type)
+                .with_connection("A", Direction::Input, vec![Bit::Signal(2)])
+                .with_connection("B", Direction::Input, vec![Bit::Signal(3)])
+                .with_connection("Y", Direction::Output, vec![Bit::Signal(4)]);
+            module.cells.insert(name.into(), cell);
+        }
+        let sites = module.fault_sites(&[FaultKind::ConnectionSwap]);
+        assert_eq!(
+            sites,
+            [Fault::ConnectionSwap { cell: "andnot".to_string(), first: ("A".to_string(), 0), second: ("B".to_string(), 0) }]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::attrs::Attrs;
+use crate::progress::pass_span;
+use crate::{Bit, Direction, IdString, Module, Netlist};
+
+/// Where a feedthrough output bit comes from: an `(input port, offset)`, or `None` for constants.
+type Source = Option<(String, usize)>;
+
+/// Cell types that just copy their `A` input to their `Y` output.
+const BUFFERS: &[&str] = &["$_BUF_", "$pos"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedthroughReport {
+    pub buffers_removed: usize,
+    /// `(module, port)` pairs of the ports that were removed.
+    pub ports_removed: Vec<(String, String)>,
+}
+
+fn is_buffer(module: &Module, name: &str) -> bool {
+    let cell = &module.cells[name];
+    BUFFERS.contains(&cell.module.as_str())
+        && !cell.keep()
+        && matches!((cell.connections.get("A"), cell.connections.get("Y")), (Some(a), Some(y)) if a.len() == y.len())
+}
+
+fn is_editable(module: &Module) -> bool {
+    !module.blackbox() && !module.keep()
+}
+
+/// Follows `map` until reaching a bit that is not replaced, guarding against buffer loops.
+fn resolve(map: &HashMap<Bit, Bit>, mut bit: Bit) -> Bit {
+    for _ in 0..=map.len() {
+        match map.get(&bit) {
+            Some(next) => bit = *next,
+            None => break,
+        }
+    }
+    bit
+}
+
+fn remove_buffers(module: &mut Module) -> usize {
+    let buffers: Vec<String> = module.cells.keys().filter(|name| is_buffer(module, name)).map(String::from).collect();
+    let mut map = HashMap::new();
+    for name in buffers.iter() {
+        let cell = module.cells.shift_remove(name).unwrap();
+        for (y, a) in cell.connections["Y"].iter().zip(cell.connections["A"].iter()) {
+            if matches!(y, Bit::Signal(_)) && y != a {
+                map.insert(*y, *a);
+            }
+        }
+    }
+    let map = map.keys().map(|bit| (*bit, resolve(&map, *bit))).collect();
+    module.substitute(&map);
+    buffers.len()
+}
+
+impl Netlist {
+    fn instances_of(&self, module: &str) -> Vec<(String, String)> {
+        self.modules
+            .iter()
+            .flat_map(|(parent, definition)| {
+                definition.cells.iter().filter(|(_, cell)| cell.module == module).map(move |(name, _)| (parent.to_string(), name.to_string()))
+            })
+            .collect()
+    }
+
+    /// Output ports of `module` whose bits are all copied straight from input ports (or tied to
+    /// constants), along with the `(input port, offset)` source of every bit.
+    fn feedthrough_ports(&self, module: &str) -> Vec<(String, Vec<Source>)> {
+        let definition = &self.modules[module];
+        let mut sources: HashMap<Bit, (String, usize)> = HashMap::new();
+        for (name, port) in definition.ports.iter().filter(|(_, port)| port.direction == Direction::Input) {
+            for (offset, bit) in port.bits.iter().enumerate() {
+                sources.entry(*bit).or_insert_with(|| (name.to_string(), offset));
+            }
+        }
+        definition
+            .ports
+            .iter()
+            .filter(|(_, port)| port.direction == Direction::Output)
+            .filter(|(_, port)| port.bits.iter().all(|bit| !matches!(bit, Bit::Signal(_)) || sources.contains_key(bit)))
+            .map(|(name, port)| (name.to_string(), port.bits.iter().map(|bit| sources.get(bit).cloned()).collect()))
+            .collect()
+    }
+
+    /// Removes output ports of `module` that are pure feedthroughs, reconnecting every instance
+    /// so its loads see the driver of the corresponding input directly, then the inputs that fed
+    /// only those ports. Returns the removed ports.
+    fn remove_feedthroughs(&mut self, module: &str) -> Vec<String> {
+        let instances = self.instances_of(module);
+        if instances.is_empty() || !is_editable(&self.modules[module]) {
+            return Vec::new();
+        }
+        let feedthroughs = self.feedthrough_ports(module);
+        let mut removed = Vec::new();
+        let mut fed: HashSet<String> = HashSet::new();
+        for (port, sources) in feedthroughs {
+            let constants = self.modules[module].ports[&port].bits.clone();
+            let mut maps: HashMap<String, HashMap<Bit, Bit>> = HashMap::new();
+            for (parent, instance) in instances.iter() {
+                let cell = self.modules.get_mut(parent).unwrap().cells.get_mut(instance).unwrap();
+                cell.port_directions.shift_remove(&port);
+                let Some(outputs) = cell.connections.shift_remove(&port) else {
+                    continue;
+                };
+                let map = maps.entry(parent.clone()).or_default();
+                for (offset, output) in outputs.iter().enumerate() {
+                    let driver = match sources.get(offset) {
+                        Some(Some((input, bit))) => cell.connections.get(input).and_then(|bits| bits.get(*bit)).copied().unwrap_or(Bit::X),
+                        _ => constants.get(offset).copied().unwrap_or(Bit::X),
+                    };
+                    if matches!(output, Bit::Signal(_)) && *output != driver {
+                        map.insert(*output, driver);
+                    }
+                }
+            }
+            for (parent, map) in maps {
+                let map = map.keys().map(|bit| (*bit, resolve(&map, *bit))).collect();
+                self.modules.get_mut(&parent).unwrap().substitute(&map);
+            }
+            self.modules.get_mut(module).unwrap().ports.shift_remove(&port);
+            fed.extend(sources.into_iter().flatten().map(|(input, _)| input));
+            removed.push(port);
+        }
+
+        // Inputs that only existed to feed the removed outputs are now dead.
+        let definition = &self.modules[module];
+        let used: HashSet<Bit> = definition
+            .cells
+            .values()
+            .flat_map(|cell| cell.connections.values().flatten())
+            .chain(definition.ports.values().filter(|port| port.direction != Direction::Input).flat_map(|port| port.bits.iter()))
+            .copied()
+            .collect();
+        let dead: Vec<String> = definition
+            .ports
+            .iter()
+            .filter(|(name, port)| fed.contains(name.as_str()) && !port.bits.iter().any(|bit| used.contains(bit)))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        for port in dead {
+            self.modules.get_mut(module).unwrap().ports.shift_remove(&port);
+            for (parent, instance) in instances.iter() {
+                let cell = self.modules.get_mut(parent).unwrap().cells.get_mut(instance).unwrap();
+                cell.port_directions.shift_remove(&port);
+                cell.connections.shift_remove(&port);
+            }
+            removed.push(port);
+        }
+        removed
+    }
+
+    /// Removes buffer cells and feedthrough ports throughout the design until none are left.
+    /// Modules that are never instantiated keep their interface untouched. Blackboxes and
+    /// modules or cells marked `keep` are left alone.
+    pub fn remove_feedthroughs_and_buffers(&mut self) -> FeedthroughReport {
+        pass_span!("remove_feedthroughs_and_buffers", modules = self.modules.len());
+        let mut report = FeedthroughReport::default();
+        loop {
+            let mut changed = false;
+            for module in self.modules.values_mut().filter(|module| is_editable(module)) {
+                let removed = remove_buffers(module);
+                report.buffers_removed += removed;
+                changed |= removed > 0;
+            }
+            let names: Vec<IdString> = self.modules.keys().cloned().collect();
+            for module in names {
+                for port in self.remove_feedthroughs(&module) {
+                    report.ports_removed.push((module.to_string(), port));
+                    changed = true;
+                }
+            }
+            if !changed {
+                return report;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_remove_feedthroughs() {
+        let mut netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "a": { "direction": "input", "bits": [2] },
+                        "y": { "direction": "output", "bits": [4] }
+                    },
+                    "cells": {
+                        "u_pass": {
+                            "type": "pass",
+                            "port_directions": { "i": "input", "spare": "input", "o": "output" },
+                            "connections": { "i": [2], "spare": [2], "o": [3] }
+                        },
+                        "not": {
+                            "type": "$_NOT_",
+                            "port_directions": { "A": "input", "Y": "output" },
+                            "connections": { "A": [3], "Y": [4] }
+                        }
+                    },
+                    "netnames": {
+                        "a": { "bits": [2] },
+                        "mid": { "bits": [3] },
+                        "y": { "bits": [4] }
+                    }
+                },
+                "pass": {
+                    "ports": {
+                        "i": { "direction": "input", "bits": [2] },
+                        "spare": { "direction": "input", "bits": [4] },
+                        "o": { "direction": "output", "bits": [3] }
+                    },
+                    "cells": {
+                        "buf": {
+                            "type": "$_BUF_",
+                            "port_directions": { "A": "input", "Y": "output" },
+                            "connections": { "A": [2], "Y": [3] }
+                        }
+                    },
+                    "netnames": {
+                        "i": { "bits": [2] },
+                        "o": { "bits": [3] }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let report = netlist.remove_feedthroughs_and_buffers();
+        assert_eq!(report.buffers_removed, 1);
+        assert_eq!(report.ports_removed, [("pass".to_string(), "o".to_string()), ("pass".to_string(), "i".to_string())]);
+
+        let top = &netlist.modules["top"];
+        assert_eq!(top.cells["not"].connections["A"], [Bit::Signal(2)]);
+        assert_eq!(top.cells["u_pass"].connections.keys().collect::<Vec<_>>(), ["spare"]);
+        assert_eq!(top.nets["mid"].bits, [Bit::Signal(2)]);
+        assert_eq!(netlist.modules["pass"].ports.keys().collect::<Vec<_>>(), ["spare"]);
+        assert_eq!(top.ports["y"].bits, [Bit::Signal(4)]);
+    }
+
+    #[test]
+    fn test_keep_buffers() {
+        let buffer = |keep: u32| {
+            json!({ "type": "$_BUF_", "attributes": { "keep": format!("{:032b}", keep) }, "connections": { "A": [2], "Y": [3] } })
+        };
+        let module = |attributes| {
+            json!({
+                "attributes": attributes,
+                "ports": {
+                    "i": { "direction": "input", "bits": [2] },
+                    "o": { "direction": "output", "bits": [3] }
+                },
+                "cells": { "buf": buffer(0) }
+            })
+        };
+        let mut netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": { "a": { "direction": "input", "bits": [2] } },
+                    "cells": {
+                        "kept": buffer(1),
+                        "u_box": { "type": "box", "connections": { "i": [2], "o": [4] } },
+                        "u_kept": { "type": "kept", "connections": { "i": [2], "o": [5] } }
+                    }
+                },
+                "box": module(json!({ "blackbox": "00000000000000000000000000000001" })),
+                "kept": module(json!({ "keep": "00000000000000000000000000000001" })),
+                "plain": module(json!({}))
+            }
+        }))
+        .unwrap();
+
+        let report = netlist.remove_feedthroughs_and_buffers();
+        assert_eq!(report, FeedthroughReport { buffers_removed: 1, ports_removed: Vec::new() });
+        assert!(netlist.modules["top"].cells.contains_key("kept"));
+        assert!(netlist.modules["box"].cells.contains_key("buf"));
+        assert!(netlist.modules["kept"].cells.contains_key("buf"));
+        assert!(netlist.modules["plain"].cells.is_empty());
+        assert_eq!(netlist.modules["top"].cells["u_kept"].connections.keys().collect::<Vec<_>>(), ["i", "o"]);
+    }
+}
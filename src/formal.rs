@@ -0,0 +1,681 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::batch::REGISTERS;
+use crate::gates::{Function, cycle_bits, eval_table, function};
+use crate::limits::{Budget, LimitExceeded, Limits};
+use crate::rng::{SeedPolicy, SplitMix64};
+use crate::sat::{Engine, Lit, Solver, SolverError};
+use crate::{Bit, Direction, Module};
+
+/// Options of [`Module::prove`].
+#[derive(Debug, Clone)]
+pub struct ProveOptions {
+    /// Largest number of cycles unrolled, for both the base case and the induction step.
+    pub depth: usize,
+    /// Cycles of random simulation from the initial state, 64 runs at a time, sampled to
+    /// guess invariants. 0 disables strengthening.
+    pub samples: usize,
+    pub seed: u64,
+    /// Turns `seed` into the seed the sampling draws from.
+    pub seed_policy: SeedPolicy,
+    /// Shrinks counterexamples with [`Module::minimize_trace`].
+    pub minimize: bool,
+    pub engine: Engine,
+    /// Bounds the whole proof, failing with [`FormalError::Limit`] when exceeded. The cone is
+    /// every gate and register bit modeled.
+    pub limits: Limits,
+}
+
+impl Default for ProveOptions {
+    fn default() -> Self {
+        Self {
+            depth: 20,
+            samples: 64,
+            seed: 0,
+            seed_policy: SeedPolicy::Explicit,
+            minimize: true,
+            engine: Engine::Cdcl,
+            limits: Limits::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormalError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    /// A bit on a combinational loop, named by its net.
+    CombinationalLoop(String),
+    /// A port missing from one of two modules compared, or differing in direction or width.
+    InterfaceMismatch(String),
+    /// A trace lacks the value of this register or input, or has one of the wrong width.
+    InvalidTrace(String),
+    Solver(SolverError),
+    /// Cancelled, or stopped by a limit of [`ProveOptions::limits`].
+    Limit(LimitExceeded),
+}
+
+impl fmt::Display for FormalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot model cell {} of type {}", cell, module),
+            Self::CombinationalLoop(bit) => write!(f, "combinational loop through {}", bit),
+            Self::InterfaceMismatch(port) => write!(f, "port {} differs between the modules", port),
+            Self::InvalidTrace(name) => write!(f, "trace has no valid value for {}", name),
+            Self::Solver(error) => write!(f, "solver failed: {}", error),
+            Self::Limit(limit) => write!(f, "proof stopped: {}", limit),
+        }
+    }
+}
+
+impl std::error::Error for FormalError {}
+
+impl From<SolverError> for FormalError {
+    fn from(error: SolverError) -> Self {
+        match error {
+            SolverError::Interrupted(limit) => Self::Limit(limit),
+            error => Self::Solver(error),
+        }
+    }
+}
+
+impl From<LimitExceeded> for FormalError {
+    fn from(limit: LimitExceeded) -> Self {
+        Self::Limit(limit)
+    }
+}
+
+/// A relation between register outputs, named by their nets, that holds in every reachable
+/// state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Invariant {
+    Constant(String, bool),
+    Equal(String, String),
+    Opposite(String, String),
+}
+
+/// Why the properties hold: together with the invariants, every `depth + 1` consecutive
+/// cycles satisfying them are followed by one that does too, and the first `depth` cycles
+/// from the initial state satisfy them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    pub depth: usize,
+    pub invariants: Vec<Invariant>,
+}
+
+/// A run from the initial state to a failing property.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    /// Initial value of every register bit, by net name.
+    pub initial: IndexMap<String, bool>,
+    /// Input ports of every cycle, least significant bit first, followed by undriven bits.
+    pub inputs: Vec<IndexMap<String, Vec<bool>>>,
+    /// The `$assert` cells failing in the last cycle.
+    pub failed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofResult {
+    Proven(Certificate),
+    Counterexample(Trace),
+    /// Neither proven nor refuted within `depth` cycles.
+    Unknown {
+        depth: usize,
+    },
+}
+
+/// One register bit, named by the net of its output.
+#[derive(Debug, Clone)]
+pub(crate) struct Register {
+    pub(crate) name: String,
+    pub(crate) d: Bit,
+    pub(crate) q: Bit,
+    pub(crate) init: Option<bool>,
+}
+
+/// A module as a transition system: boolean functions in dataflow order, registers on one
+/// implicit clock, properties and free inputs.
+#[derive(Debug, Clone)]
+pub(crate) struct System {
+    pub(crate) functions: Vec<Function>,
+    pub(crate) registers: Vec<Register>,
+    /// Cell name, condition and enable.
+    pub(crate) asserts: Vec<(String, Bit, Bit)>,
+    pub(crate) assumes: Vec<(Bit, Bit)>,
+    /// Input ports, then every undriven bit on its own, named by its net.
+    pub(crate) inputs: Vec<(String, Vec<Bit>)>,
+}
+
+impl System {
+    /// Gates and register bits modeled, the cone checked against [`Limits::max_cone`].
+    pub(crate) fn size(&self) -> usize {
+        self.functions.len() + self.registers.len()
+    }
+
+    pub(crate) fn new(module: &Module) -> Result<Self, FormalError> {
+        let initial = module.initial_values();
+        let mut functions = Vec::new();
+        let mut registers = Vec::new();
+        let mut asserts = Vec::new();
+        let mut assumes = Vec::new();
+        let loop_bit = |cycles| FormalError::CombinationalLoop(cycle_bits(module, &cycles).swap_remove(0));
+        for (name, cell) in module.topo_sort_cells().map_err(loop_bit)?.into_iter().map(|name| (name, &module.cells[name])) {
+            let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied().unwrap_or(Bit::_1);
+            if let Some(function) = function(cell) {
+                functions.push(function);
+            } else if let Some((_, d, q)) = REGISTERS.iter().find(|(module, _, _)| *module == cell.module)
+                && let (Some(d), Some(q)) = (cell.connections.get(*d), cell.connections.get(*q))
+            {
+                registers.extend(d.iter().zip(q).map(|(d, q)| Register {
+                    name: module.bit_name(q),
+                    d: *d,
+                    q: *q,
+                    init: initial.get(q).copied(),
+                }));
+            } else if cell.module == "$assert" {
+                asserts.push((name.to_string(), bit("A"), bit("EN")));
+            } else if cell.module == "$assume" {
+                assumes.push((bit("A"), bit("EN")));
+            } else {
+                return Err(FormalError::Unsupported { cell: name.to_string(), module: cell.module.to_string() });
+            }
+        }
+
+        let mut inputs: Vec<(String, Vec<Bit>)> = module
+            .ports
+            .iter()
+            .filter(|(_, port)| port.direction == Direction::Input)
+            .map(|(name, port)| (name.to_string(), port.bits.clone()))
+            .collect();
+        let mut defined: HashSet<Bit> = inputs.iter().flat_map(|(_, bits)| bits.iter().copied()).collect();
+        defined.extend(registers.iter().map(|register| register.q));
+        defined.extend(functions.iter().map(|(_, output, _)| *output));
+
+        // Every other bit read is driven by nothing and becomes an input of its own.
+        let mut reads: Vec<Bit> = functions.iter().flat_map(|(inputs, _, _)| inputs.iter().copied()).collect();
+        reads.extend(registers.iter().map(|register| register.d));
+        reads.extend(asserts.iter().flat_map(|(_, a, en)| [*a, *en]));
+        reads.extend(assumes.iter().flat_map(|(a, en)| [*a, *en]));
+        reads.extend(module.ports.values().filter(|port| port.direction != Direction::Input).flat_map(|port| port.bits.iter().copied()));
+        for bit in reads {
+            if matches!(bit, Bit::Signal(_)) && defined.insert(bit) {
+                inputs.push((module.bit_name(&bit), vec![bit]));
+            }
+        }
+        Ok(Self { functions, registers, asserts, assumes, inputs })
+    }
+
+    /// Simulates 64 random runs for `cycles` cycles from the initial state, returning the
+    /// register values of every cycle in the runs that kept to the assumptions.
+    fn sample(&self, cycles: usize, mut rng: SplitMix64) -> Vec<(u64, Vec<u64>)> {
+        let mut values: HashMap<Bit, u64> = HashMap::from([(Bit::_1, u64::MAX)]);
+        for register in self.registers.iter() {
+            values.insert(register.q, register.init.map_or_else(|| rng.next_u64(), |init| if init { u64::MAX } else { 0 }));
+        }
+        let mut valid = u64::MAX;
+        let mut states = Vec::new();
+        for _ in 0..cycles {
+            states.push((valid, self.registers.iter().map(|register| values[&register.q]).collect()));
+            for bit in self.inputs.iter().flat_map(|(_, bits)| bits) {
+                values.insert(*bit, rng.next_u64());
+            }
+            self.evaluate(&mut values);
+            valid &= self.holding(&values, self.assumes.iter());
+            self.clock(&mut values);
+        }
+        states
+    }
+
+    /// Evaluates the functions on 64 lanes, given the inputs and register outputs.
+    fn evaluate(&self, values: &mut HashMap<Bit, u64>) {
+        for (inputs, output, table) in self.functions.iter() {
+            let inputs: Vec<u64> = inputs.iter().map(|bit| values.get(bit).copied().unwrap_or(0)).collect();
+            values.insert(*output, eval_table(table, &inputs));
+        }
+    }
+
+    /// Lanes in which every property, given as condition and enable, holds.
+    fn holding<'b>(&self, values: &HashMap<Bit, u64>, properties: impl Iterator<Item = &'b (Bit, Bit)>) -> u64 {
+        let read = |bit: &Bit| values.get(bit).copied().unwrap_or(0);
+        properties.fold(u64::MAX, |holding, (a, en)| holding & (read(a) | !read(en)))
+    }
+
+    /// Loads the registers with their data inputs.
+    fn clock(&self, values: &mut HashMap<Bit, u64>) {
+        let next: Vec<u64> = self.registers.iter().map(|register| values.get(&register.d).copied().unwrap_or(0)).collect();
+        for (register, value) in self.registers.iter().zip(next) {
+            values.insert(register.q, value);
+        }
+    }
+
+    /// Replays a run, returning the first cycle in which an `$assert` fails while all
+    /// assumptions held so far, with the indices of the failing asserts.
+    fn replay(&self, initial: &[bool], inputs: &[Vec<Vec<bool>>]) -> Option<(usize, Vec<usize>)> {
+        let word = |value: bool| if value { u64::MAX } else { 0 };
+        let mut values: HashMap<Bit, u64> = HashMap::from([(Bit::_1, u64::MAX)]);
+        for (register, value) in self.registers.iter().zip(initial) {
+            values.insert(register.q, word(*value));
+        }
+        for (cycle, ports) in inputs.iter().enumerate() {
+            for ((_, bits), ports) in self.inputs.iter().zip(ports) {
+                values.extend(bits.iter().zip(ports).map(|(bit, value)| (*bit, word(*value))));
+            }
+            self.evaluate(&mut values);
+            if self.holding(&values, self.assumes.iter()) == 0 {
+                return None;
+            }
+            let failed: Vec<usize> = (0..self.asserts.len())
+                .filter(|index| {
+                    let (_, a, en) = &self.asserts[*index];
+                    self.holding(&values, [(*a, *en)].iter()) == 0
+                })
+                .collect();
+            if !failed.is_empty() {
+                return Some((cycle, failed));
+            }
+            self.clock(&mut values);
+        }
+        None
+    }
+}
+
+impl System {
+    fn trace(&self, initial: &[bool], inputs: &[Vec<Vec<bool>>], failed: &[usize]) -> Trace {
+        Trace {
+            initial: self.registers.iter().zip(initial).map(|(register, value)| (register.name.clone(), *value)).collect(),
+            inputs: inputs
+                .iter()
+                .map(|ports| self.inputs.iter().zip(ports).map(|((name, _), values)| (name.clone(), values.clone())).collect())
+                .collect(),
+            failed: failed.iter().map(|index| self.asserts[*index].0.clone()).collect(),
+        }
+    }
+
+    /// Shrinks a failing run: cuts it after the first failure, then clears free initial
+    /// register values and input bits one at a time, keeping every change that still fails.
+    fn minimize(&self, initial: &mut [bool], inputs: &mut Vec<Vec<Vec<bool>>>) -> Option<Vec<usize>> {
+        let (cycle, mut failed) = self.replay(initial, inputs)?;
+        inputs.truncate(cycle + 1);
+        let mut attempt = |initial: &mut [bool], inputs: &mut Vec<Vec<Vec<bool>>>| match self.replay(initial, inputs) {
+            Some((cycle, now)) => {
+                inputs.truncate(cycle + 1);
+                failed = now;
+                true
+            }
+            None => false,
+        };
+        for register in 0..initial.len() {
+            if self.registers[register].init.is_none() && initial[register] {
+                initial[register] = false;
+                initial[register] = !attempt(initial, inputs);
+            }
+        }
+        let positions: Vec<(usize, usize, usize)> = (inputs.iter().enumerate())
+            .flat_map(|(cycle, ports)| {
+                ports.iter().enumerate().flat_map(move |(port, bits)| (0..bits.len()).map(move |bit| (cycle, port, bit)))
+            })
+            .collect();
+        for (cycle, port, bit) in positions {
+            if cycle < inputs.len() && inputs[cycle][port][bit] {
+                inputs[cycle][port][bit] = false;
+                inputs[cycle][port][bit] = !attempt(initial, inputs);
+            }
+        }
+        Some(failed)
+    }
+}
+
+/// Candidate invariants over register indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Candidate {
+    Constant(usize, bool),
+    Equal(usize, usize),
+    Opposite(usize, usize),
+}
+
+/// Copies of the transition relation for consecutive cycles in one incremental solver.
+pub(crate) struct Unrolling<'a> {
+    pub(crate) system: &'a System,
+    pub(crate) solver: Box<dyn Solver>,
+    truth: Lit,
+    initial: bool,
+    pub(crate) frames: Vec<HashMap<Bit, Lit>>,
+}
+
+impl<'a> Unrolling<'a> {
+    /// Starts an unrolling, from the initial state or from any state.
+    pub(crate) fn new(system: &'a System, initial: bool, engine: &Engine, budget: &Budget) -> Self {
+        let mut solver = engine.solver_within(budget);
+        let truth = solver.new_var();
+        solver.add_clause(&[truth]);
+        Self { system, solver, truth, initial, frames: Vec::new() }
+    }
+
+    pub(crate) fn lit(&self, frame: usize, bit: Bit) -> Lit {
+        match bit {
+            Bit::_1 => self.truth,
+            Bit::Signal(_) => self.frames[frame].get(&bit).copied().unwrap_or(!self.truth),
+            _ => !self.truth,
+        }
+    }
+
+    /// Adds the next cycle, returning its index.
+    pub(crate) fn add_frame(&mut self) -> usize {
+        let frame = self.frames.len();
+        let mut values = HashMap::new();
+        for register in self.system.registers.iter() {
+            let lit = match frame {
+                0 => {
+                    let lit = self.solver.new_var();
+                    if let (true, Some(init)) = (self.initial, register.init) {
+                        self.solver.add_clause(&[if init { lit } else { !lit }]);
+                    }
+                    lit
+                }
+                _ => self.lit(frame - 1, register.d),
+            };
+            values.insert(register.q, lit);
+        }
+        for bit in self.system.inputs.iter().flat_map(|(_, bits)| bits) {
+            values.insert(*bit, self.solver.new_var());
+        }
+        self.frames.push(values);
+        for (inputs, output, table) in self.system.functions.iter() {
+            let inputs: Vec<Lit> = inputs.iter().map(|bit| self.lit(frame, *bit)).collect();
+            let lit = self.solver.new_var();
+            for (row, value) in table.iter().enumerate() {
+                let mut clause: Vec<Lit> =
+                    inputs.iter().enumerate().map(|(i, input)| if row >> i & 1 == 1 { !*input } else { *input }).collect();
+                clause.push(if *value { lit } else { !lit });
+                self.solver.add_clause(&clause);
+            }
+            self.frames[frame].insert(*output, lit);
+        }
+        for (a, en) in self.system.assumes.iter() {
+            let clause = [!self.lit(frame, *en), self.lit(frame, *a)];
+            self.solver.add_clause(&clause);
+        }
+        frame
+    }
+
+    /// A literal true exactly when some `$assert` fails in `frame`, and one per property.
+    pub(crate) fn violations(&mut self, frame: usize) -> Vec<Lit> {
+        let mut violations = Vec::new();
+        for (_, a, en) in self.system.asserts.iter() {
+            let (a, en) = (self.lit(frame, *a), self.lit(frame, *en));
+            let violation = self.solver.new_var();
+            self.solver.add_clause(&[!violation, en]);
+            self.solver.add_clause(&[!violation, !a]);
+            self.solver.add_clause(&[violation, !en, a]);
+            violations.push(violation);
+        }
+        violations
+    }
+
+    /// Requires every `$assert` to hold in `frame`.
+    pub(crate) fn assert_properties(&mut self, frame: usize) {
+        for (_, a, en) in self.system.asserts.iter() {
+            let clause = [!self.lit(frame, *en), self.lit(frame, *a)];
+            self.solver.add_clause(&clause);
+        }
+    }
+
+    fn candidate(&mut self, frame: usize, candidate: Candidate) -> Lit {
+        let register = |index: usize| self.lit(frame, self.system.registers[index].q);
+        let (a, b, equal) = match candidate {
+            Candidate::Constant(index, value) => return if value { register(index) } else { !register(index) },
+            Candidate::Equal(a, b) => (register(a), register(b), true),
+            Candidate::Opposite(a, b) => (register(a), register(b), false),
+        };
+        let b = if equal { b } else { !b };
+        let lit = self.solver.new_var();
+        self.solver.add_clause(&[!lit, !a, b]);
+        self.solver.add_clause(&[!lit, a, !b]);
+        self.solver.add_clause(&[lit, a, b]);
+        self.solver.add_clause(&[lit, !a, !b]);
+        lit
+    }
+
+    /// The run found by the last satisfiable call, failing in its last frame.
+    pub(crate) fn trace(&self, violations: &[Lit]) -> Trace {
+        let initial: Vec<bool> = self.system.registers.iter().map(|register| self.solver.value(self.lit(0, register.q))).collect();
+        let inputs: Vec<Vec<Vec<bool>>> = (0..self.frames.len())
+            .map(|frame| {
+                let values = |bits: &Vec<Bit>| bits.iter().map(|bit| self.solver.value(self.lit(frame, *bit))).collect();
+                self.system.inputs.iter().map(|(_, bits)| values(bits)).collect()
+            })
+            .collect();
+        let failed: Vec<usize> = (0..violations.len()).filter(|index| self.solver.value(violations[*index])).collect();
+        self.system.trace(&initial, &inputs, &failed)
+    }
+}
+
+impl System {
+    /// Guesses invariants over the registers that hold in every state sampled as `options` ask.
+    pub(crate) fn guess(&self, options: &ProveOptions) -> Vec<Candidate> {
+        let states = self.sample(options.samples, SplitMix64::from_policy(options.seed_policy, options.seed));
+        let count = self.registers.len();
+        let mut candidates: Vec<Candidate> = Vec::new();
+        for a in 0..count {
+            candidates.extend([Candidate::Constant(a, false), Candidate::Constant(a, true)]);
+            candidates.extend((a + 1..count).flat_map(|b| [Candidate::Equal(a, b), Candidate::Opposite(a, b)]));
+        }
+        candidates.retain(|candidate| {
+            states.iter().all(|(valid, values)| {
+                let violated = match *candidate {
+                    Candidate::Constant(a, value) => values[a] ^ if value { u64::MAX } else { 0 },
+                    Candidate::Equal(a, b) => values[a] ^ values[b],
+                    Candidate::Opposite(a, b) => !(values[a] ^ values[b]),
+                };
+                violated & valid == 0
+            })
+        });
+        candidates
+    }
+
+    /// Keeps the largest subset of `candidates` that holds in every initial state and is
+    /// preserved by every transition, dropping the ones that fail until none do.
+    pub(crate) fn houdini(&self, mut candidates: Vec<Candidate>, engine: &Engine, budget: &Budget) -> Result<Vec<Candidate>, FormalError> {
+        let mut initial = Unrolling::new(self, true, engine, budget);
+        initial.add_frame();
+        loop {
+            budget.check()?;
+            let lits: Vec<Lit> = candidates.iter().map(|candidate| initial.candidate(0, *candidate)).collect();
+            let activation = initial.solver.new_var();
+            let mut clause: Vec<Lit> = lits.iter().map(|lit| !*lit).collect();
+            clause.push(!activation);
+            initial.solver.add_clause(&clause);
+            if !initial.solver.solve(&[activation])? {
+                break;
+            }
+            candidates =
+                candidates.into_iter().zip(lits).filter(|(_, lit)| initial.solver.value(*lit)).map(|(candidate, _)| candidate).collect();
+        }
+        loop {
+            budget.check()?;
+            let mut step = Unrolling::new(self, false, engine, budget);
+            step.add_frame();
+            step.add_frame();
+            for candidate in candidates.iter() {
+                let lit = step.candidate(0, *candidate);
+                step.solver.add_clause(&[lit]);
+            }
+            let lits: Vec<Lit> = candidates.iter().map(|candidate| step.candidate(1, *candidate)).collect();
+            step.solver.add_clause(&lits.iter().map(|lit| !*lit).collect::<Vec<_>>());
+            if !step.solver.solve(&[])? {
+                return Ok(candidates);
+            }
+            candidates =
+                candidates.into_iter().zip(lits).filter(|(_, lit)| step.solver.value(*lit)).map(|(candidate, _)| candidate).collect();
+        }
+    }
+
+    /// Runs the base case and induction step side by side up to `options.depth` cycles, with
+    /// `candidates` assumed in every step.
+    pub(crate) fn induction(&self, candidates: &[Candidate], options: &ProveOptions, budget: &Budget) -> Result<ProofResult, FormalError> {
+        let mut base = Unrolling::new(self, true, &options.engine, budget);
+        let mut step = Unrolling::new(self, false, &options.engine, budget);
+        for depth in 0..=options.depth {
+            budget.check()?;
+            base.add_frame();
+            let violations = base.violations(depth);
+            let activation = base.solver.new_var();
+            base.solver.add_clause(&[[!activation].as_slice(), &violations].concat());
+            if base.solver.solve(&[activation])? {
+                let trace = base.trace(&violations);
+                return Ok(ProofResult::Counterexample(if options.minimize { self.minimize_trace(&trace)? } else { trace }));
+            }
+            base.solver.add_clause(&[!activation]);
+            base.assert_properties(depth);
+
+            step.add_frame();
+            for candidate in candidates.iter() {
+                let lit = step.candidate(depth, *candidate);
+                step.solver.add_clause(&[lit]);
+            }
+            let violations = step.violations(depth);
+            let activation = step.solver.new_var();
+            step.solver.add_clause(&[[!activation].as_slice(), &violations].concat());
+            if !step.solver.solve(&[activation])? {
+                let register = |index: usize| self.registers[index].name.clone();
+                let invariants = candidates
+                    .iter()
+                    .map(|candidate| match *candidate {
+                        Candidate::Constant(a, value) => Invariant::Constant(register(a), value),
+                        Candidate::Equal(a, b) => Invariant::Equal(register(a), register(b)),
+                        Candidate::Opposite(a, b) => Invariant::Opposite(register(a), register(b)),
+                    })
+                    .collect();
+                return Ok(ProofResult::Proven(Certificate { depth, invariants }));
+            }
+            step.solver.add_clause(&[!activation]);
+            step.assert_properties(depth);
+        }
+        Ok(ProofResult::Unknown { depth: options.depth })
+    }
+
+    pub(crate) fn minimize_trace(&self, trace: &Trace) -> Result<Trace, FormalError> {
+        let invalid = |name: &String| FormalError::InvalidTrace(name.clone());
+        let mut initial: Vec<bool> = self
+            .registers
+            .iter()
+            .map(|register| trace.initial.get(&register.name).copied().ok_or_else(|| invalid(&register.name)))
+            .collect::<Result<_, _>>()?;
+        let mut inputs: Vec<Vec<Vec<bool>>> = trace
+            .inputs
+            .iter()
+            .map(|cycle| {
+                let values = |(name, bits): &(String, Vec<Bit>)| {
+                    cycle.get(name).filter(|values| values.len() == bits.len()).cloned().ok_or_else(|| invalid(name))
+                };
+                self.inputs.iter().map(values).collect::<Result<_, _>>()
+            })
+            .collect::<Result<_, _>>()?;
+        match self.minimize(&mut initial, &mut inputs) {
+            Some(failed) => Ok(self.trace(&initial, &inputs, &failed)),
+            None => Ok(trace.clone()),
+        }
+    }
+}
+
+impl Module {
+    /// Proves the `$assert` cells by k-induction under the `$assume` cells, with registers
+    /// on one implicit clock starting from their `init` values, or any value without one.
+    /// Before the induction steps, invariants guessed by random simulation and shown
+    /// inductive are added to strengthen them.
+    pub fn prove(&self, options: &ProveOptions) -> Result<ProofResult, FormalError> {
+        let system = System::new(self)?;
+        let budget = options.limits.start();
+        budget.check_cone(system.size())?;
+        let candidates = match options.samples {
+            0 => Vec::new(),
+            _ => system.houdini(system.guess(options), &options.engine, &budget)?,
+        };
+        system.induction(&candidates, options, &budget)
+    }
+
+    /// Shrinks a counterexample of this module to make it easier to debug: cuts it after the
+    /// first failing cycle and clears every input bit and uninitialized register it can
+    /// while some `$assert` still fails. Traces that do not fail are returned unchanged.
+    pub fn minimize_trace(&self, trace: &Trace) -> Result<Trace, FormalError> {
+        System::new(self)?.minimize_trace(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    /// Two registers sampling the input, one of them inverted if `invert`, and a sticky flag
+    /// that must stay clear, set when they differ.
+    fn sticky(invert: bool) -> Module {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let input = builder.input("in", 1).unwrap();
+        let [a, b, p, d, x, n, next] = ["a", "b", "p", "d", "x", "n", "next"].map(|name| builder.wire(name, 1).unwrap());
+        let dff =
+            |d: &[Bit], q: &[Bit]| CellBuilder::new("$_DFF_P_").input("C", clk.clone()).input("D", d.to_vec()).output("Q", q.to_vec());
+        builder.cell("ra", dff(&input, &a)).unwrap();
+        builder.cell("rb", dff(&d, &b)).unwrap();
+        builder.cell("rp", dff(&next, &p)).unwrap();
+        let gate = if invert { "$_NOT_" } else { "$_BUF_" };
+        builder.cell("g", CellBuilder::new(gate).input("A", input.clone()).output("Y", d)).unwrap();
+        builder.cell("x", CellBuilder::new("$_XOR_").input("A", a).input("B", b).output("Y", x.clone())).unwrap();
+        builder.cell("o", CellBuilder::new("$_OR_").input("A", p.clone()).input("B", x).output("Y", next)).unwrap();
+        builder.cell("i", CellBuilder::new("$_NOT_").input("A", p).output("Y", n.clone())).unwrap();
+        builder.cell("check", CellBuilder::new("$assert").input("A", n).input("EN", vec![Bit::_1])).unwrap();
+        let mut module = builder.build();
+        for name in ["a", "b", "p"] {
+            module.nets[name].attributes.insert("init".to_string(), "0".into());
+        }
+        module
+    }
+
+    #[test]
+    fn test_prove() {
+        let module = sticky(false);
+        let ProofResult::Proven(certificate) = module.prove(&ProveOptions::default()).unwrap() else { panic!() };
+        assert!(certificate.depth <= 1);
+        assert!(certificate.invariants.contains(&Invariant::Equal("a".to_string(), "b".to_string())));
+        let without = ProveOptions { samples: 0, ..ProveOptions::default() };
+        assert_eq!(module.prove(&without).unwrap(), ProofResult::Proven(Certificate { depth: 2, invariants: Vec::new() }));
+
+        let ProofResult::Counterexample(trace) = sticky(true).prove(&ProveOptions::default()).unwrap() else { panic!() };
+        assert_eq!(trace.inputs.len(), 3);
+        assert!(trace.inputs.iter().all(|cycle| cycle.values().flatten().all(|value| !value)));
+        assert_eq!(trace.failed, ["check"]);
+        assert_eq!(trace.initial, IndexMap::from([("a".to_string(), false), ("b".to_string(), false), ("p".to_string(), false)]));
+
+        let limited = ProveOptions { limits: Limits { max_cone: Some(2), ..Limits::default() }, ..ProveOptions::default() };
+        assert!(matches!(module.prove(&limited), Err(FormalError::Limit(LimitExceeded::Cone { limit: 2, .. }))));
+        let cancelled = ProveOptions::default();
+        cancelled.limits.cancel.cancel();
+        assert_eq!(module.prove(&cancelled), Err(FormalError::Limit(LimitExceeded::Cancelled)));
+    }
+
+    #[test]
+    fn test_minimize_trace() {
+        let module = sticky(true);
+        let cycle = |clk: bool, input: bool| IndexMap::from([("clk".to_string(), vec![clk]), ("in".to_string(), vec![input])]);
+        let trace = Trace {
+            initial: IndexMap::from([("a".to_string(), false), ("b".to_string(), false), ("p".to_string(), false)]),
+            inputs: vec![cycle(true, true), cycle(true, false), cycle(false, true), cycle(true, true), cycle(true, true)],
+            failed: Vec::new(),
+        };
+        let minimized = module.minimize_trace(&trace).unwrap();
+        assert_eq!(minimized.inputs, vec![cycle(false, false); 3]);
+        assert_eq!(minimized.failed, ["check"]);
+        assert_eq!(sticky(false).minimize_trace(&trace).unwrap(), trace);
+        let mut broken = trace.clone();
+        broken.inputs[1].shift_remove("in");
+        assert_eq!(module.minimize_trace(&broken), Err(FormalError::InvalidTrace("in".to_string())));
+    }
+}
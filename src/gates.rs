@@ -0,0 +1,81 @@
+use crate::connectivity::CombinationalCycles;
+use crate::{Bit, Cell, Module};
+
+type Eval = fn(&[u64]) -> u64;
+
+/// A Yosys gate-level primitive (`$_AND_`, `$_MUX_`, …) with a single-bit output.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Gate {
+    pub(crate) inputs: &'static [&'static str],
+    pub(crate) output: &'static str,
+    /// Evaluates the gate bitwise, so one call computes 64 independent patterns.
+    pub(crate) eval: Eval,
+}
+
+const A: &[&str] = &["A"];
+const AB: &[&str] = &["A", "B"];
+const ABS: &[&str] = &["A", "B", "S"];
+const ABC: &[&str] = &["A", "B", "C"];
+const ABCD: &[&str] = &["A", "B", "C", "D"];
+
+pub(crate) fn gate(module: &str) -> Option<Gate> {
+    let (inputs, eval): (&'static [&'static str], Eval) = match module {
+        "$_BUF_" => (A, |v| v[0]),
+        "$_NOT_" => (A, |v| !v[0]),
+        "$_AND_" => (AB, |v| v[0] & v[1]),
+        "$_NAND_" => (AB, |v| !(v[0] & v[1])),
+        "$_OR_" => (AB, |v| v[0] | v[1]),
+        "$_NOR_" => (AB, |v| !(v[0] | v[1])),
+        "$_XOR_" => (AB, |v| v[0] ^ v[1]),
+        "$_XNOR_" => (AB, |v| !(v[0] ^ v[1])),
+        "$_ANDNOT_" => (AB, |v| v[0] & !v[1]),
+        "$_ORNOT_" => (AB, |v| v[0] | !v[1]),
+        "$_MUX_" => (ABS, |v| (v[0] & !v[2]) | (v[1] & v[2])),
+        "$_NMUX_" => (ABS, |v| !((v[0] & !v[2]) | (v[1] & v[2]))),
+        "$_AOI3_" => (ABC, |v| !((v[0] & v[1]) | v[2])),
+        "$_OAI3_" => (ABC, |v| !((v[0] | v[1]) & v[2])),
+        "$_AOI4_" => (ABCD, |v| !((v[0] & v[1]) | (v[2] & v[3]))),
+        "$_OAI4_" => (ABCD, |v| !((v[0] | v[1]) & (v[2] | v[3]))),
+        _ => return None,
+    };
+    Some(Gate { inputs, output: "Y", eval })
+}
+
+/// A single-output boolean function: input bits, output bit and truth table indexed by the
+/// input values with the first input as the least significant bit.
+pub(crate) type Function = (Vec<Bit>, Bit, Vec<bool>);
+
+/// Evaluates a truth table bitwise as the sum of its minterms, so one call computes 64
+/// independent patterns like [`Gate::eval`].
+pub(crate) fn eval_table(table: &[bool], inputs: &[u64]) -> u64 {
+    table.iter().enumerate().filter(|(_, set)| **set).fold(0, |sum, (minterm, _)| {
+        let literal = |(input, value): (usize, &u64)| if minterm >> input & 1 == 1 { *value } else { !*value };
+        sum | inputs.iter().enumerate().map(literal).fold(u64::MAX, |lanes, literal| lanes & literal)
+    })
+}
+
+/// The function of a gate-level primitive or LUT cell.
+pub(crate) fn function(cell: &Cell) -> Option<Function> {
+    if let (Some(inputs), Some(table)) = (cell.lut_inputs(), cell.lut_truth_table()) {
+        let mut outputs = cell.connections.iter().filter(|(_, bits)| bits.len() == 1 && !inputs.contains(&bits[0]));
+        let output = match (outputs.next(), outputs.next()) {
+            (Some((_, bits)), None) => bits[0],
+            _ => return None,
+        };
+        return Some((inputs, output, table));
+    }
+    let gate = gate(&cell.module)?;
+    let inputs = gate.inputs.iter().map(|port| cell.connections.get(*port)?.first().copied()).collect::<Option<Vec<Bit>>>()?;
+    let output = *cell.connections.get(gate.output)?.first()?;
+    let patterns: Vec<u64> = (0..inputs.len()).map(|input| (0..64).filter(|m| m >> input & 1 == 1).fold(0, |p, m| p | 1 << m)).collect();
+    let value = (gate.eval)(&patterns);
+    Some((inputs, output, (0..1 << gate.inputs.len()).map(|m| value >> m & 1 == 1).collect()))
+}
+
+/// Names of the bits driven by the gates and LUTs of `cycles`, and of the other cells on
+/// them, to report a loop by.
+pub(crate) fn cycle_bits(module: &Module, cycles: &CombinationalCycles) -> Vec<String> {
+    (cycles.0.iter().flatten())
+        .map(|name| function(&module.cells[name.as_str()]).map_or(name.clone(), |(_, output, _)| module.bit_name(&output)))
+        .collect()
+}
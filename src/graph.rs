@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use petgraph::graph::{Graph, NodeIndex};
+
+use crate::{Bit, Direction, Module};
+
+/// A node of [`Module::to_petgraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Cell {
+        name: String,
+        cell_type: String,
+    },
+    Port {
+        name: String,
+        direction: Direction,
+    },
+    /// A constant bit read by some cell or output port.
+    Const(Bit),
+}
+
+/// One bit flowing from a driving port to a reading one. Module ports are named after
+/// themselves, constants have an empty port name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub bit: Bit,
+    pub from_port: String,
+    pub to_port: String,
+}
+
+impl Module {
+    /// The module as a directed graph from drivers to readers, with a node for every port,
+    /// then every cell, then every constant read, and an edge for every bit connecting them.
+    /// Inout ports and pins both drive and read. Bits driven by nothing have no edges.
+    pub fn to_petgraph(&self) -> Graph<Node, Edge> {
+        let mut graph = Graph::new();
+        let mut drivers: HashMap<Bit, Vec<(NodeIndex, &str)>> = HashMap::new();
+        let mut readers: Vec<(NodeIndex, &str, Bit)> = Vec::new();
+        for (name, port) in self.ports.iter() {
+            let node = graph.add_node(Node::Port { name: name.to_string(), direction: port.direction.clone() });
+            for bit in port.bits.iter() {
+                if port.direction != Direction::Output {
+                    drivers.entry(*bit).or_default().push((node, name));
+                }
+                if port.direction != Direction::Input {
+                    readers.push((node, name, *bit));
+                }
+            }
+        }
+        for (name, cell) in self.cells.iter() {
+            let node = graph.add_node(Node::Cell { name: name.to_string(), cell_type: cell.module.to_string() });
+            for (port, bits) in cell.connections.iter() {
+                let direction = cell.port_directions.get(port).cloned().unwrap_or(Direction::Input);
+                for bit in bits.iter() {
+                    if direction != Direction::Input {
+                        drivers.entry(*bit).or_default().push((node, port));
+                    }
+                    if direction != Direction::Output {
+                        readers.push((node, port, *bit));
+                    }
+                }
+            }
+        }
+        for (reader, to_port, bit) in readers {
+            if !matches!(bit, Bit::Signal(_)) && !drivers.contains_key(&bit) {
+                let node = graph.add_node(Node::Const(bit));
+                drivers.insert(bit, vec![(node, "")]);
+            }
+            for (driver, from_port) in drivers.get(&bit).into_iter().flatten() {
+                graph.add_edge(*driver, reader, Edge { bit, from_port: from_port.to_string(), to_port: to_port.to_string() });
+            }
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, constant};
+    use petgraph::algo::{is_cyclic_directed, tarjan_scc};
+
+    #[test]
+    fn test_to_petgraph() {
+        let mut builder = Module::builder();
+        let [clk, a] = ["clk", "a"].map(|name| builder.input(name, 1).unwrap());
+        let q = builder.output("q", 1).unwrap();
+        let d = builder.wire("d", 1).unwrap();
+        builder.cell("xor", CellBuilder::new("$_XOR_").input("A", a).input("B", q.clone()).output("Y", d.clone())).unwrap();
+        builder.cell("reg", CellBuilder::new("$_DFF_P_").input("C", clk).input("D", d).output("Q", q)).unwrap();
+        builder
+            .cell("or", CellBuilder::new("$_OR_").input("A", constant(1, 1)).input("B", constant(1, 1)).output("Y", Vec::new()))
+            .unwrap();
+        let graph = builder.build().to_petgraph();
+
+        assert_eq!(graph.node_count(), 7);
+        assert_eq!(graph.edge_count(), 7);
+        assert!(graph.node_weights().any(|node| *node == Node::Const(Bit::_1)));
+        assert!(is_cyclic_directed(&graph));
+        let cycle = tarjan_scc(&graph).into_iter().find(|component| component.len() > 1).unwrap();
+        assert_eq!(cycle.len(), 2);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::builder::{BuildError, CellBuilder, ModuleBuilder, PortBuilder, TRUE, constant};
+use crate::progress::pass_span;
+use crate::{Bit, Direction, Module, Netlist};
+
+/// What the environment drives an input of the wrapped module with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Constraint {
+    /// A free input of the harness.
+    #[default]
+    Free,
+    /// A constant, least significant bit first, bits past 64 zero.
+    Tied(u64),
+    /// A free input assumed to equal the value.
+    Equal(u64),
+    /// A free input assumed to have exactly one bit set.
+    OneHot,
+}
+
+/// The environment of a module: constraints by input port, the default for the rest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvSpec {
+    pub inputs: IndexMap<String, Constraint>,
+    pub default: Constraint,
+}
+
+impl EnvSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn input(mut self, port: &str, constraint: Constraint) -> Self {
+        self.inputs.insert(port.to_string(), constraint);
+        self
+    }
+
+    /// Constrains the inputs not listed, such as [`Constraint::Tied`] to tie them all off.
+    pub fn otherwise(mut self, constraint: Constraint) -> Self {
+        self.default = constraint;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HarnessError {
+    UnknownModule(String),
+    ModuleExists(String),
+    /// A constraint on a port that is not an input of the module.
+    NotAnInput(String),
+    /// A name of the harness clashes with another, such as a port named like the hidden wires.
+    Build(BuildError),
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::ModuleExists(module) => write!(f, "module {:?} already exists", module),
+            Self::NotAnInput(port) => write!(f, "{:?} is not an input port", port),
+            Self::Build(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for HarnessError {}
+
+impl From<BuildError> for HarnessError {
+    fn from(error: BuildError) -> Self {
+        Self::Build(error)
+    }
+}
+
+/// Gate-level logic of a harness, on hidden wires named after the port they constrain.
+struct Logic<'a> {
+    builder: &'a mut ModuleBuilder,
+    port: &'a str,
+    count: usize,
+}
+
+impl Logic<'_> {
+    fn gate(&mut self, gate: &str, inputs: &[Bit]) -> Result<Bit, BuildError> {
+        let name = format!("$env${}${}", self.port, self.count);
+        self.count += 1;
+        let output = self.builder.wire(&name, 1)?;
+        let mut cell = CellBuilder::new(gate);
+        for (port, bit) in ["A", "B"].iter().zip(inputs) {
+            cell = cell.input(port, vec![*bit]);
+        }
+        self.builder.cell(&name, cell.output("Y", output.clone()))?;
+        Ok(output[0])
+    }
+
+    fn and(&mut self, bits: impl IntoIterator<Item = Bit>) -> Result<Bit, BuildError> {
+        let mut bits = bits.into_iter();
+        let Some(mut all) = bits.next() else { return Ok(Bit::_1) };
+        for bit in bits {
+            all = self.gate("$_AND_", &[all, bit])?;
+        }
+        Ok(all)
+    }
+
+    /// Whether `bits` equal `value`.
+    fn equal(&mut self, bits: &[Bit], value: u64) -> Result<Bit, BuildError> {
+        let matches: Vec<Bit> = bits
+            .iter()
+            .zip(constant(value, bits.len()))
+            .map(|(bit, value)| if value == Bit::_1 { Ok(*bit) } else { self.gate("$_NOT_", &[*bit]) })
+            .collect::<Result<_, _>>()?;
+        self.and(matches)
+    }
+
+    /// Whether exactly one of `bits` is set.
+    fn one_hot(&mut self, bits: &[Bit]) -> Result<Bit, BuildError> {
+        let (mut any, mut twice) = (Bit::_0, Bit::_0);
+        for bit in bits {
+            let both = self.gate("$_AND_", &[any, *bit])?;
+            twice = self.gate("$_OR_", &[twice, both])?;
+            any = self.gate("$_OR_", &[any, *bit])?;
+        }
+        let once = self.gate("$_NOT_", &[twice])?;
+        self.and([any, once])
+    }
+
+    fn assume(&mut self, condition: Bit) -> Result<(), BuildError> {
+        let name = format!("$env${}$assume", self.port);
+        self.builder.cell(&name, CellBuilder::new("$assume").input("A", vec![condition]).input("EN", vec![Bit::_1]))?;
+        Ok(())
+    }
+}
+
+impl Netlist {
+    /// Adds a top module `name` instantiating `module` as `dut` in the environment `spec`
+    /// describes: inputs free, tied or assumed to satisfy their constraint, with gate-level
+    /// logic and `$assume` cells ready for formal or random simulation. Outputs and inouts
+    /// become ports of the harness of the same name. `module` stops being marked top.
+    pub fn wrap_environment(&mut self, module: &str, name: &str, spec: &EnvSpec) -> Result<(), HarnessError> {
+        pass_span!("wrap_environment", module, harness = name);
+        let dut = self.modules.get(module).ok_or_else(|| HarnessError::UnknownModule(module.to_string()))?;
+        if self.modules.contains_key(name) {
+            return Err(HarnessError::ModuleExists(name.to_string()));
+        }
+        if let Some(port) = spec.inputs.keys().find(|port| dut.ports.get(*port).is_none_or(|port| port.direction != Direction::Input)) {
+            return Err(HarnessError::NotAnInput(port.clone()));
+        }
+
+        let mut builder = Module::builder();
+        builder.attribute("top", TRUE);
+        let mut instance = CellBuilder::new(module);
+        for (port_name, port) in dut.ports.iter() {
+            let width = port.bits.len();
+            let constraint = spec.inputs.get(port_name.as_str()).unwrap_or(&spec.default);
+            let bits = match (&port.direction, constraint) {
+                (Direction::Input, Constraint::Tied(value)) => constant(*value, width),
+                (direction, constraint) => {
+                    let bits = builder.port(PortBuilder::new(port_name, direction.clone(), width))?;
+                    let mut logic = Logic { builder: &mut builder, port: port_name, count: 0 };
+                    match (direction, constraint) {
+                        (Direction::Input, Constraint::Equal(value)) => {
+                            let condition = logic.equal(&bits, *value)?;
+                            logic.assume(condition)?;
+                        }
+                        (Direction::Input, Constraint::OneHot) => {
+                            let condition = logic.one_hot(&bits)?;
+                            logic.assume(condition)?;
+                        }
+                        _ => {}
+                    }
+                    bits
+                }
+            };
+            instance = instance.connect(port_name, port.direction.clone(), bits);
+        }
+        builder.cell("dut", instance)?;
+
+        self.modules[module].attributes.shift_remove("top");
+        self.modules.insert(name.into(), builder.build());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::NetlistBuilder;
+
+    #[test]
+    fn test_wrap_environment() {
+        let mut builder = Module::builder();
+        let [mode, sel, en] = [("mode", 2), ("sel", 3), ("en", 1)].map(|(name, width)| builder.input(name, width).unwrap());
+        let y = builder.output("y", 1).unwrap();
+        builder.cell("and", CellBuilder::new("$_AND_").input("A", vec![sel[0]]).input("B", vec![mode[0]]).output("Y", y)).unwrap();
+        builder.cell("unused", CellBuilder::new("$_NOT_").input("A", en).output("Y", vec![Bit::X])).unwrap();
+        let mut netlist = NetlistBuilder::new("test").top("top", builder).unwrap().build();
+
+        let spec = EnvSpec::new().input("mode", Constraint::Equal(1)).input("sel", Constraint::OneHot).otherwise(Constraint::Tied(0));
+        netlist.wrap_environment("top", "env", &spec).unwrap();
+        let env = &netlist.modules["env"];
+        assert_eq!(env.ports.keys().collect::<Vec<_>>(), ["mode", "sel", "y"]);
+        assert_eq!(env.cells["dut"].connections["en"], [Bit::_0]);
+        assert_eq!(env.cells.values().filter(|cell| cell.module == "$assume").count(), 2);
+        assert_eq!(netlist.top_modules(), ["env"]);
+        assert!(netlist.flatten("env").unwrap().to_cnf().is_ok());
+
+        let spec = EnvSpec::new().input("y", Constraint::Free);
+        assert_eq!(netlist.wrap_environment("top", "env2", &spec), Err(HarnessError::NotAnInput("y".to_string())));
+        assert_eq!(netlist.wrap_environment("top", "env", &EnvSpec::new()), Err(HarnessError::ModuleExists("env".to_string())));
+
+        // A port named like the hidden wire of another port's constraint.
+        let mut builder = Module::builder();
+        builder.input("sel", 2).unwrap();
+        builder.input("$env$sel$0", 1).unwrap();
+        let mut netlist = NetlistBuilder::new("test").top("top", builder).unwrap().build();
+        let spec = EnvSpec::new().input("sel", Constraint::OneHot);
+        let clash = HarnessError::Build(BuildError::Duplicate("$env$sel$0".to_string()));
+        assert_eq!(netlist.wrap_environment("top", "env", &spec), Err(clash));
+        assert!(!netlist.modules.contains_key("env"));
+    }
+}
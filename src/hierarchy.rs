@@ -0,0 +1,735 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::attrs::Attrs;
+use crate::builder::{PortBuilder, TRUE};
+use crate::progress::pass_span;
+use crate::provenance::{Origin, Provenance};
+use crate::{Bit, Cell, Direction, IdString, Module, Net, Netlist, Port};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HierarchyError {
+    UnknownModule(String),
+    UnknownCell { module: String, cell: String },
+    ModuleExists(String),
+    CellExists { module: String, cell: String },
+    UnknownNet { module: String, net: String },
+    PortExists { module: String, port: String },
+    NetExists { module: String, net: String },
+    EmptySelection,
+    EmptyPath,
+    RecursiveInstance(String),
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::UnknownCell { module, cell } => write!(f, "unknown cell {:?} in module {:?}", cell, module),
+            Self::ModuleExists(module) => write!(f, "module {:?} already exists", module),
+            Self::CellExists { module, cell } => write!(f, "cell {:?} already exists in module {:?}", cell, module),
+            Self::UnknownNet { module, net } => write!(f, "unknown net {:?} in module {:?}", net, module),
+            Self::PortExists { module, port } => write!(f, "port {:?} already exists in module {:?}", port, module),
+            Self::NetExists { module, net } => write!(f, "net {:?} already exists in module {:?}", net, module),
+            Self::EmptySelection => write!(f, "no cells selected"),
+            Self::EmptyPath => write!(f, "empty instance path"),
+            Self::RecursiveInstance(module) => write!(f, "module {:?} instantiates itself", module),
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+#[derive(Default, Clone, Copy)]
+struct Usage {
+    driven: bool,
+    read: bool,
+    bidir: bool,
+}
+
+impl Usage {
+    fn record(&mut self, direction: Option<&Direction>) {
+        match direction {
+            Some(Direction::Output) => self.driven = true,
+            Some(Direction::Input) => self.read = true,
+            Some(Direction::InOut) | None => self.bidir = true,
+        }
+    }
+
+    fn direction(&self) -> Direction {
+        if self.bidir {
+            Direction::InOut
+        } else if self.driven {
+            Direction::Output
+        } else {
+            Direction::Input
+        }
+    }
+}
+
+fn unique_name(taken: &HashSet<IdString>, base: &str) -> IdString {
+    if !taken.contains(base) {
+        return base.into();
+    }
+    (1..).map(|n| format!("{}_{}", base, n)).find(|name| !taken.contains(name.as_str())).unwrap().into()
+}
+
+pub(crate) fn is_blackbox(module: &Module) -> bool {
+    module.blackbox()
+}
+
+/// Renames a cell or net inlined from instance `path`, and records the path in `hdlname` the
+/// way `yosys flatten` does.
+fn inlined(path: &[&str], name: &str, attributes: &mut IndexMap<String, serde_json::Value>) -> String {
+    if path.is_empty() {
+        return name.to_string();
+    }
+    let hdlname = match attributes.get("hdlname").and_then(|hdlname| hdlname.as_str()) {
+        Some(hdlname) => format!("{} {}", path.join(" "), hdlname),
+        None => format!("{} {}", path.join(" "), name),
+    };
+    attributes.insert("hdlname".to_string(), hdlname.into());
+    format!("{}.{}", path.join("."), name)
+}
+
+/// The roots of an analysis over several modules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Tops {
+    /// The modules found by [`Netlist::top_modules`].
+    #[default]
+    Auto,
+    Named(Vec<String>),
+}
+
+/// Which modules of a netlist instantiate which, with modules in netlist order. Only modules
+/// defined in the netlist are nodes, so cells of other types are not edges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleGraph {
+    /// The modules every module instantiates, with their number of instances.
+    pub children: IndexMap<String, IndexMap<String, usize>>,
+}
+
+impl ModuleGraph {
+    pub fn children(&self, module: &str) -> impl Iterator<Item = &str> {
+        self.children.get(module).into_iter().flat_map(|children| children.keys().map(String::as_str))
+    }
+
+    pub fn parents(&self, module: &str) -> impl Iterator<Item = &str> {
+        self.children.iter().filter(move |(_, children)| children.contains_key(module)).map(|(parent, _)| parent.as_str())
+    }
+
+    /// The modules instantiated below `tops`, `tops` included, in netlist order.
+    pub fn below(&self, tops: &[&str]) -> Vec<&str> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = tops.to_vec();
+        while let Some(module) = stack.pop() {
+            if seen.insert(module) {
+                stack.extend(self.children(module));
+            }
+        }
+        self.children.keys().map(String::as_str).filter(|module| seen.contains(module)).collect()
+    }
+
+    /// Every module after the modules it instantiates, in netlist order otherwise, or the
+    /// first module found instantiating itself.
+    pub fn bottom_up(&self) -> Result<Vec<&str>, HierarchyError> {
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        for root in self.children.keys() {
+            let mut stack = vec![(root.as_str(), false)];
+            let mut active: Vec<&str> = Vec::new();
+            while let Some((module, expanded)) = stack.pop() {
+                if expanded {
+                    active.pop();
+                    if done.insert(module) {
+                        order.push(module);
+                    }
+                    continue;
+                }
+                if done.contains(module) {
+                    continue;
+                }
+                if active.contains(&module) {
+                    return Err(HierarchyError::RecursiveInstance(module.to_string()));
+                }
+                active.push(module);
+                stack.push((module, true));
+                stack.extend(self.children(module).collect::<Vec<_>>().into_iter().rev().map(|child| (child, false)));
+            }
+        }
+        Ok(order)
+    }
+}
+
+impl Module {
+    /// A module with the ports of this one and the `blackbox` attribute, and nothing else.
+    pub fn to_blackbox(&self) -> Module {
+        let mut blackbox = Module::new();
+        blackbox.attributes.insert("blackbox".to_string(), TRUE.into());
+        blackbox.ports = self.ports.clone();
+        blackbox
+    }
+}
+
+impl Netlist {
+    pub fn module_graph(&self) -> ModuleGraph {
+        let children = (self.modules.iter())
+            .map(|(name, module)| {
+                let mut children: IndexMap<String, usize> = IndexMap::new();
+                for cell in module.cells.values().filter(|cell| self.modules.contains_key(&cell.module)) {
+                    *children.entry(cell.module.to_string()).or_default() += 1;
+                }
+                (name.to_string(), children)
+            })
+            .collect();
+        ModuleGraph { children }
+    }
+
+    /// The modules marked with the `top` attribute or, when there are none, the modules no
+    /// other module instantiates, blackboxes aside.
+    pub fn top_modules(&self) -> Vec<&str> {
+        let tops: Vec<&str> = self.modules.iter().filter(|(_, module)| module.top()).map(|(name, _)| name.as_str()).collect();
+        if !tops.is_empty() {
+            return tops;
+        }
+        let instantiated: HashSet<&str> =
+            self.modules.values().flat_map(|module| module.cells.values().map(|cell| cell.module.as_str())).collect();
+        (self.modules.iter())
+            .filter(|(name, module)| !instantiated.contains(name.as_str()) && !is_blackbox(module))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// The modules `tops` names, all of which must exist.
+    pub fn resolve_tops(&self, tops: &Tops) -> Result<Vec<&str>, HierarchyError> {
+        match tops {
+            Tops::Auto => Ok(self.top_modules()),
+            Tops::Named(names) => (names.iter())
+                .map(|name| match self.modules.get_key_value(name) {
+                    Some((name, _)) => Ok(name.as_str()),
+                    None => Err(HierarchyError::UnknownModule(name.clone())),
+                })
+                .collect(),
+        }
+    }
+
+    /// Flattens every one of `tops` into a netlist of its own, with the blackboxes the flat
+    /// modules still instantiate.
+    pub fn flatten_tops(&self, tops: &Tops) -> Result<Netlist, HierarchyError> {
+        let tops = self.resolve_tops(tops)?;
+        let mut netlist = Netlist::new(&self.creator);
+        for top in tops.iter() {
+            netlist.modules.insert((*top).into(), self.flatten(top)?);
+        }
+        for name in self.module_graph().below(&tops) {
+            if is_blackbox(&self.modules[name]) && !netlist.modules.contains_key(name) {
+                netlist.modules.insert(name.into(), self.modules[name].clone());
+            }
+        }
+        Ok(netlist)
+    }
+
+    /// Adds an empty blackbox module for every module instantiated but not defined, Yosys'
+    /// internal cell types aside, so the design validates and exports while incomplete. Ports
+    /// are inferred from the instances: as wide as the widest connection, in the direction the
+    /// instances give, or inout when they give none or disagree. Returns the stubs added.
+    pub fn stub_missing_modules(&mut self) -> Vec<String> {
+        pass_span!("stub_missing_modules", modules = self.modules.len());
+        let mut stubs: IndexMap<String, IndexMap<String, (usize, Option<Direction>)>> = IndexMap::new();
+        for cell in self.modules.values().flat_map(|module| module.cells.values()) {
+            let internal = cell.module.starts_with('$') && !cell.module.starts_with("$paramod");
+            if internal || !cell.cell_type().is_user() || self.modules.contains_key(&cell.module) {
+                continue;
+            }
+            let ports = stubs.entry(cell.module.to_string()).or_default();
+            for (port, bits) in cell.connections.iter() {
+                let (width, direction) = ports.entry(port.to_string()).or_insert((0, None));
+                *width = (*width).max(bits.len());
+                *direction = match (direction.take(), cell.port_directions.get(port).cloned()) {
+                    (None, given) | (given, None) => given,
+                    (Some(a), Some(b)) if a == b => Some(a),
+                    _ => Some(Direction::InOut),
+                };
+            }
+        }
+        for (name, ports) in stubs.iter() {
+            let mut builder = Module::builder();
+            builder.attribute("blackbox", TRUE);
+            for (port, (width, direction)) in ports {
+                builder.port(PortBuilder::new(port, direction.clone().unwrap_or(Direction::InOut), *width)).unwrap();
+            }
+            self.modules.insert(name.into(), builder.build());
+        }
+        stubs.into_keys().collect()
+    }
+
+    #[deprecated(note = "renamed to `stub_missing_modules`")]
+    pub fn create_stubs(&mut self) -> Vec<String> {
+        self.stub_missing_modules()
+    }
+
+    /// Inlines every instance of a module of this netlist below `top` into one flat module.
+    /// Blackboxes and cells of unknown types stay instances. Inlined cells and nets are named
+    /// after their instance path, `u0.u1.name`, and internal signals are renumbered past those
+    /// of `top`.
+    pub fn flatten(&self, top: &str) -> Result<Module, HierarchyError> {
+        self.flatten_with(top, None)
+    }
+
+    pub(crate) fn flatten_with(&self, top: &str, provenance: Option<&mut Provenance>) -> Result<Module, HierarchyError> {
+        let module = self.modules.get(top).ok_or_else(|| HierarchyError::UnknownModule(top.to_string()))?;
+        let mut flat = Module::new();
+        flat.attributes = module.attributes.clone();
+        flat.ports = module.ports.clone();
+        // Signals of `top` keep their numbers, those of inlined instances are allocated past them.
+        let [Bit::Signal(first)] = module.fresh_bits(1)[..] else { unreachable!() };
+        flat.allocated = first;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("flatten", module = top).entered();
+        self.inline(&mut flat, top, &mut Vec::new(), &HashMap::new(), provenance)?;
+        #[cfg(feature = "tracing")]
+        tracing::info!(cells = flat.cells.len(), nets = flat.nets.len(), "flattened module");
+        Ok(flat)
+    }
+
+    /// Copies `module` into `flat` below instance `path`, with its bits renamed by `map`.
+    fn inline<'a>(
+        &'a self,
+        flat: &mut Module,
+        module: &'a str,
+        path: &mut Vec<(&'a str, &'a str)>,
+        map: &HashMap<Bit, Bit>,
+        mut provenance: Option<&mut Provenance>,
+    ) -> Result<(), HierarchyError> {
+        let definition = &self.modules[module];
+        let names: Vec<&str> = path.iter().map(|(instance, _)| *instance).collect();
+        let rename = |bits: &[Bit]| -> Vec<Bit> { bits.iter().map(|bit| *map.get(bit).unwrap_or(bit)).collect() };
+
+        for (name, net) in definition.nets.iter() {
+            let mut net = net.clone();
+            net.bits = rename(&net.bits);
+            if let Some(provenance) = provenance.as_deref_mut() {
+                provenance.record_net(module, &names, name, &net);
+            }
+            let name = inlined(&names, name, &mut net.attributes);
+            flat.nets.insert(name.into(), net);
+        }
+        for (name, memory) in definition.memories.iter() {
+            let mut memory = memory.clone();
+            let name = inlined(&names, name, &mut memory.attributes);
+            flat.memories.insert(name.into(), memory);
+        }
+
+        for (name, cell) in definition.cells.iter() {
+            let child = match self.modules.get(&cell.module) {
+                Some(child) if !is_blackbox(child) => child,
+                _ => {
+                    let mut cell = cell.clone();
+                    cell.connections.values_mut().for_each(|bits| *bits = rename(bits));
+                    if let Some(memid) = cell.param("MEMID").and_then(|memid| memid.as_str().map(str::to_string))
+                        && !names.is_empty()
+                    {
+                        let (escape, memory) = memid.strip_prefix('\\').map_or(("", memid.as_str()), |memory| ("\\", memory));
+                        cell.parameters.insert("MEMID".to_string(), format!("{}{}.{}", escape, names.join("."), memory).into());
+                    }
+                    let flat_name = inlined(&names, name, &mut cell.attributes);
+                    if let Some(provenance) = provenance.as_deref_mut() {
+                        let path = names.iter().map(|instance| instance.to_string()).collect();
+                        provenance.record_cell(&flat_name, Origin { module: module.to_string(), path, name: name.to_string() });
+                    }
+                    flat.cells.insert(flat_name.into(), cell);
+                    continue;
+                }
+            };
+            if cell.module == module || path.iter().any(|(_, parent)| *parent == cell.module) {
+                return Err(HierarchyError::RecursiveInstance(cell.module.to_string()));
+            }
+
+            let mut inner = HashMap::new();
+            for (port, definition) in child.ports.iter() {
+                let Some(bits) = cell.connections.get(port.as_str()) else { continue };
+                for (bit, outer) in definition.bits.iter().zip(bits) {
+                    if matches!(bit, Bit::Signal(_)) {
+                        inner.entry(*bit).or_insert(*map.get(outer).unwrap_or(outer));
+                    }
+                }
+            }
+            let internal: Vec<Bit> = child.signals().map(Bit::Signal).filter(|bit| !inner.contains_key(bit)).collect();
+            inner.extend(internal.iter().copied().zip(flat.alloc_bits(internal.len())));
+            path.push((name, module));
+            self.inline(flat, &cell.module, path, &inner, provenance.as_deref_mut())?;
+            path.pop();
+        }
+        Ok(())
+    }
+
+    /// Moves the `cells` of `module` into a new module `wrapper`, and replaces them by a single
+    /// instance named `instance`. Signals crossing the new boundary become ports of `wrapper`,
+    /// named after the nets they belong to.
+    pub fn wrap_cells(&mut self, module: &str, cells: &[&str], wrapper: &str, instance: &str) -> Result<(), HierarchyError> {
+        pass_span!("wrap_cells", module, cells = cells.len(), wrapper);
+        if cells.is_empty() {
+            return Err(HierarchyError::EmptySelection);
+        }
+        if self.modules.contains_key(wrapper) {
+            return Err(HierarchyError::ModuleExists(wrapper.to_string()));
+        }
+        let parent = self.modules.get_mut(module).ok_or_else(|| HierarchyError::UnknownModule(module.to_string()))?;
+        for cell in cells {
+            if !parent.cells.contains_key(*cell) {
+                return Err(HierarchyError::UnknownCell { module: module.to_string(), cell: cell.to_string() });
+            }
+        }
+        if parent.cells.contains_key(instance) && !cells.contains(&instance) {
+            return Err(HierarchyError::CellExists { module: module.to_string(), cell: instance.to_string() });
+        }
+
+        let selected: HashSet<&str> = cells.iter().copied().collect();
+        let mut inside: IndexMap<Bit, Usage> = IndexMap::new();
+        let mut outside: HashSet<Bit> = HashSet::new();
+        for (name, cell) in parent.cells.iter() {
+            for (port, bits) in cell.connections.iter() {
+                let signals = bits.iter().filter(|bit| matches!(bit, Bit::Signal(_)));
+                if selected.contains(name.as_str()) {
+                    for bit in signals {
+                        inside.entry(*bit).or_default().record(cell.port_directions.get(port));
+                    }
+                } else {
+                    outside.extend(signals);
+                }
+            }
+        }
+        for port in parent.ports.values() {
+            outside.extend(port.bits.iter().filter(|bit| matches!(bit, Bit::Signal(_))));
+        }
+
+        // Group the boundary bits into ports, one per net and direction, preferring public nets.
+        let mut boundary: IndexMap<Bit, Direction> = inside
+            .iter()
+            .filter(|(bit, _)| outside.contains(bit))
+            .map(|(bit, usage)| (*bit, usage.direction()))
+            .collect();
+        let mut nets: Vec<(&IdString, &Net)> = parent.nets.iter().collect();
+        nets.sort_by_key(|(_, net)| net.hide_name);
+        let mut taken: HashSet<IdString> = HashSet::new();
+        let mut ports: IndexMap<IdString, Port> = IndexMap::new();
+        for (net_name, net) in nets {
+            let mut groups: IndexMap<Direction, Vec<Bit>> = IndexMap::new();
+            for bit in net.bits.iter() {
+                if let Some(direction) = boundary.shift_remove(bit) {
+                    groups.entry(direction).or_default().push(*bit);
+                }
+            }
+            for (direction, bits) in groups {
+                let name = unique_name(&taken, net_name);
+                taken.insert(name.clone());
+                ports.insert(name, Port::new(direction, bits));
+            }
+        }
+        for (bit, direction) in boundary {
+            if let Bit::Signal(signal) = bit {
+                let name = unique_name(&taken, &format!("n{}", signal));
+                taken.insert(name.clone());
+                ports.insert(name, Port::new(direction, vec![bit]));
+            }
+        }
+
+        let internal: HashSet<Bit> = inside.keys().filter(|bit| !outside.contains(bit)).copied().collect();
+        let all_inside = |net: &Net, bits: &dyn Fn(&Bit) -> bool| {
+            let mut signals = net.bits.iter().filter(|bit| matches!(bit, Bit::Signal(_))).peekable();
+            signals.peek().is_some() && signals.all(bits)
+        };
+
+        let mut inner = Module::new();
+        for (name, cell) in parent.cells.iter() {
+            if selected.contains(name.as_str()) {
+                inner.cells.insert(name.clone(), cell.clone());
+            }
+        }
+        for (name, net) in parent.nets.iter() {
+            if !ports.contains_key(name) && all_inside(net, &|bit| inside.contains_key(bit)) {
+                inner.nets.insert(name.clone(), net.clone());
+            }
+        }
+        for (name, port) in ports.iter() {
+            inner.nets.insert(name.clone(), Net::new(port.bits.clone()));
+        }
+        inner.ports = ports;
+
+        let mut wrapper_cell = Cell::new(wrapper);
+        for (name, port) in inner.ports.iter() {
+            wrapper_cell.port_directions.insert(name.clone(), port.direction.clone());
+            wrapper_cell.connections.insert(name.clone(), port.bits.clone());
+        }
+
+        parent.cells.retain(|name, _| !selected.contains(name.as_str()));
+        parent.nets.retain(|_, net| !all_inside(net, &|bit| internal.contains(bit)));
+        parent.cells.insert(instance.into(), wrapper_cell);
+        self.modules.insert(wrapper.into(), inner);
+        Ok(())
+    }
+
+    /// Exposes `net` of the module instantiated at the end of `path` as an output port named
+    /// `port` on every module along the way, starting at `ancestor`. The instance path is a list
+    /// of cell names, each one inside the module instantiated by the previous one. Returns the
+    /// bits of the new net driven by the probe inside `ancestor`.
+    ///
+    /// Module definitions are shared, so every other instance of the modules along the path
+    /// gains an unconnected output. The port name must be free as a port and a net in every
+    /// module below `ancestor`, except that `net` itself may be punched under its own name.
+    pub fn punch_port(&mut self, ancestor: &str, path: &[&str], net: &str, port: &str) -> Result<Vec<Bit>, HierarchyError> {
+        pass_span!("punch_port", module = ancestor, depth = path.len(), net, port);
+        if path.is_empty() {
+            return Err(HierarchyError::EmptyPath);
+        }
+        let mut modules = vec![ancestor.to_string()];
+        for instance in path {
+            let module = modules.last().unwrap();
+            let parent = self.modules.get(module).ok_or_else(|| HierarchyError::UnknownModule(module.clone()))?;
+            let cell = parent.cells.get(*instance).ok_or_else(|| HierarchyError::UnknownCell { module: module.clone(), cell: instance.to_string() })?;
+            modules.push(cell.module.to_string());
+        }
+        for (level, module) in modules.iter().enumerate().skip(1) {
+            let definition = self.modules.get(module).ok_or_else(|| HierarchyError::UnknownModule(module.clone()))?;
+            if definition.ports.contains_key(port) {
+                return Err(HierarchyError::PortExists { module: module.clone(), port: port.to_string() });
+            }
+            if definition.nets.contains_key(port) && !(level == path.len() && port == net) {
+                return Err(HierarchyError::NetExists { module: module.clone(), net: port.to_string() });
+            }
+        }
+        let target = modules.last().unwrap();
+        let mut bits = match self.modules[target].nets.get(net) {
+            Some(net) => net.bits.clone(),
+            None => return Err(HierarchyError::UnknownNet { module: target.clone(), net: net.to_string() }),
+        };
+
+        for (level, instance) in path.iter().enumerate().rev() {
+            let child = self.modules.get_mut(&modules[level + 1]).unwrap();
+            child.ports.insert(port.into(), Port::new(Direction::Output, bits.clone()));
+            child.nets.entry(port.into()).or_insert_with(|| Net::new(bits.clone()));
+
+            let parent = self.modules.get_mut(&modules[level]).unwrap();
+            bits = parent.fresh_bits(bits.len());
+            let cell = parent.cells.get_mut(*instance).unwrap();
+            cell.port_directions.insert(port.into(), Direction::Output);
+            cell.connections.insert(port.into(), bits.clone());
+        }
+
+        let top = self.modules.get_mut(ancestor).unwrap();
+        let taken: HashSet<IdString> = top.nets.keys().cloned().collect();
+        let name = unique_name(&taken, &format!("{}.{}", path.join("."), net));
+        top.nets.insert(name, Net::new(bits.clone()));
+        Ok(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn and_or() -> Netlist {
+        Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "a": { "direction": "input", "bits": [2] },
+                        "b": { "direction": "input", "bits": [3] },
+                        "c": { "direction": "input", "bits": [4] },
+                        "y": { "direction": "output", "bits": [5] }
+                    },
+                    "cells": {
+                        "and": {
+                            "type": "$_AND_",
+                            "port_directions": { "A": "input", "B": "input", "Y": "output" },
+                            "connections": { "A": [2], "B": [3], "Y": [6] }
+                        },
+                        "or": {
+                            "type": "$_OR_",
+                            "port_directions": { "A": "input", "B": "input", "Y": "output" },
+                            "connections": { "A": [6], "B": [4], "Y": [5] }
+                        }
+                    },
+                    "netnames": {
+                        "a": { "bits": [2] },
+                        "b": { "bits": [3] },
+                        "c": { "bits": [4] },
+                        "y": { "bits": [5] },
+                        "ab": { "bits": [6] }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_wrap_single_cell() {
+        let mut netlist = and_or();
+        netlist.wrap_cells("top", &["and"], "region0", "u_region0").unwrap();
+
+        let region = &netlist.modules["region0"];
+        assert_eq!(region.ports.keys().collect::<Vec<_>>(), ["a", "b", "ab"]);
+        assert_eq!(region.ports["ab"].direction, Direction::Output);
+        assert!(region.cells.contains_key("and"));
+
+        let top = &netlist.modules["top"];
+        assert!(!top.cells.contains_key("and"));
+        assert_eq!(top.cells["u_region0"].module, "region0");
+        assert_eq!(top.cells["u_region0"].connections["ab"], [Bit::Signal(6)]);
+        assert!(top.nets.contains_key("ab"));
+    }
+
+    #[test]
+    fn test_wrap_hides_internal_nets() {
+        let mut netlist = and_or();
+        netlist.wrap_cells("top", &["and", "or"], "region0", "u_region0").unwrap();
+
+        let top = &netlist.modules["top"];
+        assert_eq!(top.cells.len(), 1);
+        assert!(!top.nets.contains_key("ab"));
+        assert!(netlist.modules["region0"].nets.contains_key("ab"));
+        assert_eq!(netlist.modules["region0"].ports.len(), 4);
+    }
+
+    #[test]
+    fn test_punch_port() {
+        let mut netlist = and_or();
+        netlist.wrap_cells("top", &["and", "or"], "inner", "u_inner").unwrap();
+        netlist.wrap_cells("top", &["u_inner"], "outer", "u_outer").unwrap();
+
+        let bits = netlist.punch_port("top", &["u_outer", "u_inner"], "ab", "probe").unwrap();
+        assert_eq!(bits, [Bit::Signal(6)]);
+
+        let inner = &netlist.modules["inner"];
+        assert_eq!(inner.ports["probe"].direction, Direction::Output);
+        assert_eq!(inner.ports["probe"].bits, inner.nets["ab"].bits);
+
+        let outer = &netlist.modules["outer"];
+        let probe = &outer.cells["u_inner"].connections["probe"];
+        assert_eq!(&outer.ports["probe"].bits, probe);
+
+        let top = &netlist.modules["top"];
+        assert_eq!(top.cells["u_outer"].connections["probe"], bits);
+        assert_eq!(top.nets["u_outer.u_inner.ab"].bits, bits);
+
+        assert_eq!(
+            netlist.punch_port("top", &["u_outer", "u_inner"], "ab", "probe"),
+            Err(HierarchyError::PortExists { module: "outer".to_string(), port: "probe".to_string() })
+        );
+
+        let spare = netlist.modules["outer"].fresh_bits(1);
+        netlist.modules.get_mut("outer").unwrap().nets.insert("spare".into(), Net::new(spare));
+        let before = netlist.clone();
+        assert_eq!(
+            netlist.punch_port("top", &["u_outer", "u_inner"], "ab", "spare"),
+            Err(HierarchyError::NetExists { module: "outer".to_string(), net: "spare".to_string() })
+        );
+        assert_eq!(netlist, before);
+        netlist.punch_port("top", &["u_outer", "u_inner"], "ab", "ab").unwrap();
+        assert_eq!(netlist.modules["inner"].ports["ab"].bits, netlist.modules["inner"].nets["ab"].bits);
+    }
+
+    #[test]
+    fn test_flatten() {
+        let mut netlist = and_or();
+        netlist.wrap_cells("top", &["and"], "inner", "u_inner").unwrap();
+        netlist.wrap_cells("top", &["u_inner", "or"], "outer", "u_outer").unwrap();
+        netlist.wrap_cells("top", &["u_outer"], "box", "u_box").unwrap();
+        netlist.modules.get_mut("box").unwrap().attributes.insert("blackbox".to_string(), "00000000000000000000000000000001".into());
+
+        let flat = netlist.flatten("top").unwrap();
+        assert_eq!(flat.cells.keys().collect::<Vec<_>>(), ["u_box"]);
+        netlist.modules.get_mut("box").unwrap().attributes.clear();
+
+        let flat = netlist.flatten("top").unwrap();
+        assert_eq!(flat.ports, netlist.modules["top"].ports);
+        assert_eq!(flat.cells.keys().collect::<Vec<_>>(), ["u_box.u_outer.or", "u_box.u_outer.u_inner.and"]);
+        let and = &flat.cells["u_box.u_outer.u_inner.and"];
+        let or = &flat.cells["u_box.u_outer.or"];
+        assert_eq!(and.attributes["hdlname"], "u_box u_outer u_inner and");
+        assert_eq!(and.connections["A"], [Bit::Signal(2)]);
+        assert_eq!(or.connections["Y"], [Bit::Signal(5)]);
+        assert_eq!(and.connections["Y"], or.connections["A"]);
+        assert_eq!(flat.nets["u_box.u_outer.ab"].bits, or.connections["A"]);
+        assert_eq!(flat.nets["u_box.u_outer.u_inner.ab"].bits, or.connections["A"]);
+
+        // Inlined signals stay clear of bits handed out in `top` and not connected yet.
+        let reserved = netlist.modules.get_mut("top").unwrap().alloc_bits(4);
+        let flat = netlist.flatten("top").unwrap();
+        assert!(!reserved.contains(&flat.cells["u_box.u_outer.or"].connections["A"][0]));
+
+        netlist.modules.get_mut("inner").unwrap().cells["and"].module = "outer".into();
+        assert_eq!(netlist.flatten("top"), Err(HierarchyError::RecursiveInstance("outer".to_string())));
+        assert_eq!(netlist.flatten("nope"), Err(HierarchyError::UnknownModule("nope".to_string())));
+    }
+
+    #[test]
+    fn test_module_graph() {
+        let mut netlist = and_or();
+        netlist.wrap_cells("top", &["and"], "inner", "u_inner").unwrap();
+        netlist.wrap_cells("top", &["u_inner", "or"], "outer", "u_outer").unwrap();
+        let mut cell = Module::new();
+        cell.attributes.insert("blackbox".to_string(), "00000000000000000000000000000001".into());
+        netlist.modules.insert("lib_cell".into(), cell);
+        netlist.modules.insert("spare".into(), Module::new());
+
+        let graph = netlist.module_graph();
+        assert_eq!(graph.children("top").collect::<Vec<_>>(), ["outer"]);
+        assert_eq!(graph.parents("inner").collect::<Vec<_>>(), ["outer"]);
+        assert_eq!(graph.bottom_up().unwrap(), ["inner", "outer", "top", "lib_cell", "spare"]);
+        assert_eq!(netlist.top_modules(), ["top", "spare"]);
+        assert_eq!(graph.below(&["outer", "spare"]), ["inner", "outer", "spare"]);
+        let flat = netlist.flatten_tops(&Tops::Auto).unwrap();
+        assert_eq!(flat.modules.keys().collect::<Vec<_>>(), ["top", "spare"]);
+        assert_eq!(flat.modules["top"].cells.len(), 2);
+        netlist.modules["spare"].attributes.insert("top".to_string(), "00000000000000000000000000000001".into());
+        assert_eq!(netlist.top_modules(), ["spare"]);
+
+        netlist.modules["inner"].cells["and"].module = "outer".into();
+        assert_eq!(netlist.module_graph().bottom_up(), Err(HierarchyError::RecursiveInstance("outer".to_string())));
+    }
+
+    #[test]
+    fn test_stub_missing_modules() {
+        let mut netlist = and_or();
+        let top = &mut netlist.modules["top"];
+        top.cells["and"].module = "AND2".into();
+        top.cells["or"].module = "LIB_GATE".into();
+        top.cells["or"].port_directions.shift_remove("B");
+        top.cells.insert("twin".into(), top.cells["or"].clone());
+        top.cells["twin"].port_directions["A"] = Direction::Output;
+        top.cells["twin"].connections["Y"] = vec![Bit::Signal(5), Bit::Signal(6)];
+
+        assert_eq!(netlist.stub_missing_modules(), ["AND2", "LIB_GATE"]);
+        assert!(is_blackbox(&netlist.modules["AND2"]));
+        let ports = &netlist.modules["LIB_GATE"].ports;
+        assert_eq!((ports["A"].direction.clone(), ports["B"].direction.clone()), (Direction::InOut, Direction::InOut));
+        assert_eq!((ports["Y"].direction.clone(), ports["Y"].bits.len()), (Direction::Output, 2));
+        assert_eq!(netlist.top_modules(), ["top"]);
+        assert!(netlist.stub_missing_modules().is_empty());
+        #[allow(deprecated)]
+        let stubs = netlist.create_stubs();
+        assert!(stubs.is_empty());
+
+        let blackbox = netlist.modules["top"].to_blackbox();
+        assert!(is_blackbox(&blackbox) && blackbox.cells.is_empty() && blackbox.nets.is_empty());
+        assert_eq!(blackbox.ports.keys().collect::<Vec<_>>(), netlist.modules["top"].ports.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_wrap_errors() {
+        let mut netlist = and_or();
+        assert_eq!(netlist.wrap_cells("top", &[], "w", "u"), Err(HierarchyError::EmptySelection));
+        assert_eq!(netlist.wrap_cells("nope", &["and"], "w", "u"), Err(HierarchyError::UnknownModule("nope".to_string())));
+        assert_eq!(netlist.wrap_cells("top", &["and"], "top", "u"), Err(HierarchyError::ModuleExists("top".to_string())));
+        assert_eq!(
+            netlist.wrap_cells("top", &["and"], "w", "or"),
+            Err(HierarchyError::CellExists { module: "top".to_string(), cell: "or".to_string() })
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::connectivity::Endpoint;
+use crate::{Bit, Direction, IdString, Module};
+
+/// Connected cells of a modified module whose cones are not found in the reference.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedRegion {
+    pub cells: Vec<String>,
+    /// Bits read by the region and driven outside it, named by their nets.
+    pub inputs: Vec<String>,
+    /// Bits driven by the region and read outside it.
+    pub outputs: Vec<String>,
+}
+
+/// Regions to re-implement after a change, for incremental place and route or partial
+/// reconfiguration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncrementalReport {
+    pub regions: Vec<ChangedRegion>,
+    /// Cells of the reference whose cones no longer appear in the modified module.
+    pub removed: Vec<String>,
+}
+
+impl IncrementalReport {
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Module {
+    /// Signature of the combinational cone of every cell: its type and parameters and,
+    /// recursively, the signatures of whatever drives its inputs. Cones end at ports,
+    /// constants and register outputs, which are told apart by their net names, so a change
+    /// does not spread past the registers it reaches.
+    fn cone_signatures(&self) -> HashMap<&str, u64> {
+        let index = self.index();
+        let mut signatures: HashMap<&str, u64> = HashMap::new();
+        let mut visiting: HashSet<&str> = HashSet::new();
+        let bit_signature = |bit: &Bit, signatures: &HashMap<&str, u64>| match index.driver_of(bit) {
+            _ if !matches!(bit, Bit::Signal(_)) => hash(("constant", bit)),
+            Some(Endpoint::Port { port, index }) => hash(("port", port, index)),
+            Some(Endpoint::Cell { cell, port, index }) if !self.cells[*cell].is_register() => match signatures.get(cell) {
+                Some(signature) => hash((signature, port, index)),
+                // On a combinational loop.
+                None => hash(("loop", self.bit_name(bit))),
+            },
+            _ => hash(("net", self.bit_name(bit))),
+        };
+        let inputs = |name: &str| {
+            let cell = &self.cells[name];
+            let mut ports: Vec<(&IdString, &Vec<Bit>)> =
+                cell.connections.iter().filter(|(port, _)| cell.port_directions.get(*port) != Some(&Direction::Output)).collect();
+            ports.sort();
+            ports
+        };
+
+        for root in self.cells.keys() {
+            let mut stack = vec![(root.as_str(), false)];
+            while let Some((name, expanded)) = stack.pop() {
+                if signatures.contains_key(name) {
+                    continue;
+                }
+                if !expanded {
+                    if !visiting.insert(name) {
+                        continue;
+                    }
+                    stack.push((name, true));
+                    for (_, bits) in inputs(name) {
+                        for bit in bits {
+                            if let Some(Endpoint::Cell { cell, .. }) = index.driver_of(bit)
+                                && !self.cells[*cell].is_register()
+                                && !visiting.contains(cell)
+                            {
+                                stack.push((cell, false));
+                            }
+                        }
+                    }
+                    continue;
+                }
+                let cell = &self.cells[name];
+                let mut parameters: Vec<(&String, String)> = cell.parameters.iter().map(|(key, value)| (key, value.to_string())).collect();
+                parameters.sort();
+                let ports: Vec<(&IdString, Vec<u64>)> = (inputs(name).into_iter())
+                    .map(|(port, bits)| (port, bits.iter().map(|bit| bit_signature(bit, &signatures)).collect()))
+                    .collect();
+                signatures.insert(name, hash((&cell.module, parameters, ports)));
+            }
+        }
+        signatures
+    }
+
+    /// Compares this module against the `reference` it was derived from, cone by cone. Cells
+    /// whose cones have no unused counterpart in the reference are changed, and connected
+    /// changed cells form one region, reported with the bits crossing its boundary.
+    pub fn changed_regions(&self, reference: &Module) -> IncrementalReport {
+        let mut available: HashMap<u64, Vec<&str>> = HashMap::new();
+        let theirs = reference.cone_signatures();
+        for name in reference.cells.keys() {
+            available.entry(theirs[name.as_str()]).or_default().push(name);
+        }
+        let ours = self.cone_signatures();
+        let mut changed: Vec<&str> = Vec::new();
+        for name in self.cells.keys() {
+            match available.get_mut(&ours[name.as_str()]).and_then(Vec::pop) {
+                Some(_) => {}
+                None => changed.push(name),
+            }
+        }
+        let mut removed: Vec<String> = available.into_values().flatten().map(str::to_string).collect();
+        removed.sort_by_key(|name| reference.cells.get_index_of(name));
+
+        // Groups the changed cells by the bits they share.
+        let changed_set: HashSet<&str> = changed.iter().copied().collect();
+        let index = self.index();
+        let mut region_of: HashMap<&str, usize> = HashMap::new();
+        let mut regions = Vec::new();
+        for start in changed.iter() {
+            if region_of.contains_key(start) {
+                continue;
+            }
+            let id = regions.len();
+            let mut cells = Vec::new();
+            let mut stack = vec![*start];
+            region_of.insert(start, id);
+            while let Some(name) = stack.pop() {
+                cells.push(name);
+                for bit in self.cells[name].connections.values().flatten() {
+                    for endpoint in index.drivers_of(bit).iter().chain(index.loads_of(bit)) {
+                        if let Endpoint::Cell { cell, .. } = endpoint
+                            && changed_set.contains(cell)
+                            && !region_of.contains_key(cell)
+                        {
+                            region_of.insert(cell, id);
+                            stack.push(cell);
+                        }
+                    }
+                }
+            }
+            cells.sort_by_key(|name| self.cells.get_index_of(*name));
+
+            let inside = |endpoint: &Endpoint| matches!(endpoint, Endpoint::Cell { cell, .. } if region_of.get(cell) == Some(&id));
+            let (mut inputs, mut outputs) = (BTreeSet::new(), BTreeSet::new());
+            for bit in cells.iter().flat_map(|name| self.cells[*name].connections.values().flatten()) {
+                let (drivers, loads) = (index.drivers_of(bit), index.loads_of(bit));
+                if drivers.iter().any(inside) && loads.iter().any(|load| !inside(load)) {
+                    outputs.insert(self.bit_name(bit));
+                }
+                if loads.iter().any(inside) && drivers.iter().any(|driver| !inside(driver)) {
+                    inputs.insert(self.bit_name(bit));
+                }
+            }
+            regions.push(ChangedRegion {
+                cells: cells.into_iter().map(str::to_string).collect(),
+                inputs: inputs.into_iter().collect(),
+                outputs: outputs.into_iter().collect(),
+            });
+        }
+        IncrementalReport { regions, removed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    /// Two independent registered gates, `y0 = a & b` and `y1 = c ^ d`, with the first gate
+    /// of kind `first`. Cell names are numbered from `offset` to tell renaming from changes.
+    fn design(first: &str, offset: usize) -> Module {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| builder.input(name, 1).unwrap());
+        let [y0, y1] = ["y0", "y1"].map(|name| builder.output(name, 1).unwrap());
+        let [t0, t1] = ["$t0", "$t1"].map(|name| builder.wire(name, 1).unwrap());
+        let gate = |kind: &str, a, b, y| CellBuilder::new(kind).input("A", a).input("B", b).output("Y", y);
+        let dff = |d, q| CellBuilder::new("$_DFF_P_").input("C", clk.clone()).input("D", d).output("Q", q);
+        builder.cell(&format!("$g{}", offset), gate(first, a, b, t0.clone())).unwrap();
+        builder.cell(&format!("$g{}", offset + 1), gate("$_XOR_", c, d, t1.clone())).unwrap();
+        builder.cell(&format!("$r{}", offset), dff(t0, y0)).unwrap();
+        builder.cell(&format!("$r{}", offset + 1), dff(t1, y1)).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn test_changed_regions() {
+        assert_eq!(design("$_AND_", 5).changed_regions(&design("$_AND_", 0)), IncrementalReport::default());
+
+        let report = design("$_OR_", 5).changed_regions(&design("$_AND_", 0));
+        assert_eq!(
+            report.regions,
+            [ChangedRegion {
+                cells: vec!["$g5".to_string(), "$r5".to_string()],
+                inputs: vec!["a".to_string(), "b".to_string(), "clk".to_string()],
+                outputs: vec!["y0".to_string()],
+            }]
+        );
+        assert_eq!(report.removed, ["$g0", "$r0"]);
+    }
+}
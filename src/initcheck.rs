@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::celltype::CellType;
+use crate::checkpoint::decode;
+use crate::param::ParamValue;
+use crate::{Bit, Cell, Direction, Module, Netlist};
+
+/// A contradiction between initial values and what drives a bit, which makes simulation,
+/// honouring `init`, disagree with hardware, which does not. Bits are named by their nets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitMismatch {
+    /// A register bit whose reset is tied active, so it holds the reset value and never its
+    /// `init` value, from the start when the reset is asynchronous or after the first edge.
+    ResetTied { module: String, cell: String, bit: String, init: bool, reset: bool, asynchronous: bool },
+    /// A constant bit of a net whose `init` gives it the other value.
+    ConstantInit { module: String, net: String, init: bool, constant: bool },
+    /// A bit given different `init` values by the nets it belongs to.
+    ConflictingInit { module: String, bit: String, nets: Vec<String> },
+    /// A bit with an `init` value driven by a cell that is not a register, which synthesis
+    /// drops.
+    CombinationalInit { module: String, bit: String, driver: String },
+}
+
+impl fmt::Display for InitMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ResetTied { module, cell, bit, init, reset, asynchronous } => {
+                let kind = if *asynchronous { "asynchronous" } else { "synchronous" };
+                write!(f, "{}: {} has init {} but {} reset of {} to {} is tied active", module, bit, *init as u8, kind, cell, *reset as u8)
+            }
+            Self::ConstantInit { module, net, init, constant } => {
+                write!(f, "{}: {} has init {} but is constant {}", module, net, *init as u8, *constant as u8)
+            }
+            Self::ConflictingInit { module, bit, nets } => write!(f, "{}: {} has conflicting init in {}", module, bit, nets.join(", ")),
+            Self::CombinationalInit { module, bit, driver } => {
+                write!(f, "{}: {} has init but is driven by {}, which is not a register", module, bit, driver)
+            }
+        }
+    }
+}
+
+/// The reset of a register: its control bit, whether it is active high, its value for every
+/// `Q` bit, and whether it is asynchronous.
+struct Reset {
+    bit: Bit,
+    polarity: bool,
+    value: Vec<Option<bool>>,
+    asynchronous: bool,
+}
+
+fn constant(cell: &Cell, name: &str, width: usize) -> Vec<Option<bool>> {
+    match cell.param(name) {
+        Some(ParamValue::Int(value)) => (0..width).map(|index| Some(index < 64 && value >> index & 1 == 1)).collect(),
+        Some(ParamValue::BitVector(bits)) => (0..width)
+            .map(|index| match bits.get(index).unwrap_or(&Bit::_0) {
+                Bit::_0 => Some(false),
+                Bit::_1 => Some(true),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![None; width],
+    }
+}
+
+fn reset(cell: &Cell) -> Option<Reset> {
+    let width = cell.connections.get("Q")?.len();
+    let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied();
+    let polarity = |name: &str| cell.param(name).and_then(|value| value.as_u64()) != Some(0);
+    let cell_type = cell.cell_type();
+    match &cell_type {
+        CellType::Adff | CellType::Adffe => Some(Reset {
+            bit: bit("ARST")?,
+            polarity: polarity("ARST_POLARITY"),
+            value: constant(cell, "ARST_VALUE", width),
+            asynchronous: true,
+        }),
+        CellType::Sdff | CellType::Sdffe | CellType::Sdffce => Some(Reset {
+            bit: bit("SRST")?,
+            polarity: polarity("SRST_POLARITY"),
+            value: constant(cell, "SRST_VALUE", width),
+            asynchronous: false,
+        }),
+        CellType::GateDff(letters)
+        | CellType::GateDffe(letters)
+        | CellType::GateSdff(letters)
+        | CellType::GateSdffe(letters)
+        | CellType::GateSdffce(letters) => {
+            let letters: Vec<bool> = letters.chars().map(|letter| letter == 'P' || letter == '1').collect();
+            let enabled = matches!(cell_type, CellType::GateDffe(_) | CellType::GateSdffe(_) | CellType::GateSdffce(_));
+            if letters.len() - enabled as usize != 3 {
+                return None;
+            }
+            let asynchronous = matches!(cell_type, CellType::GateDff(_) | CellType::GateDffe(_));
+            Some(Reset { bit: bit("R")?, polarity: letters[1], value: vec![Some(letters[2]); width], asynchronous })
+        }
+        _ => None,
+    }
+}
+
+impl Module {
+    fn init_mismatches(&self, module: &str) -> Vec<InitMismatch> {
+        let mut mismatches = Vec::new();
+        let mut inits: IndexMap<Bit, Vec<(&str, bool)>> = IndexMap::new();
+        for (name, net) in self.nets.iter() {
+            let Some(values) = net.attributes.get("init").and_then(|init| decode(init, net.bits.len())) else { continue };
+            for (index, (bit, value)) in net.bits.iter().zip(values).enumerate() {
+                let Some(value) = value else { continue };
+                let net = || if net.bits.len() > 1 { format!("{}[{}]", name, index + net.offset) } else { name.to_string() };
+                match bit {
+                    Bit::Signal(_) => inits.entry(*bit).or_default().push((name, value)),
+                    Bit::_0 | Bit::_1 if value != (*bit == Bit::_1) => mismatches.push(InitMismatch::ConstantInit {
+                        module: module.to_string(),
+                        net: net(),
+                        init: value,
+                        constant: !value,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        for (bit, values) in inits.iter().filter(|(_, values)| values.iter().any(|(_, value)| *value != values[0].1)) {
+            let nets = values.iter().map(|(net, value)| format!("{}={}", net, *value as u8)).collect();
+            mismatches.push(InitMismatch::ConflictingInit { module: module.to_string(), bit: self.bit_name(bit), nets });
+        }
+
+        let mut drivers: HashMap<Bit, &str> = HashMap::new();
+        for (name, cell) in self.cells.iter() {
+            let outputs = cell.connections.iter().filter(|(port, _)| cell.port_directions.get(*port) == Some(&Direction::Output));
+            for bit in outputs.flat_map(|(_, bits)| bits) {
+                drivers.insert(*bit, name);
+            }
+            if let Some(reset) = reset(cell)
+                && matches!(reset.bit, Bit::_0 | Bit::_1)
+                && (reset.bit == Bit::_1) == reset.polarity
+            {
+                for (bit, value) in cell.connections["Q"].iter().zip(reset.value) {
+                    if let (Some(values), Some(reset_value)) = (inits.get(bit), value)
+                        && values[0].1 != reset_value
+                    {
+                        mismatches.push(InitMismatch::ResetTied {
+                            module: module.to_string(),
+                            cell: name.to_string(),
+                            bit: self.bit_name(bit),
+                            init: values[0].1,
+                            reset: reset_value,
+                            asynchronous: reset.asynchronous,
+                        });
+                    }
+                }
+            }
+        }
+        for bit in inits.keys() {
+            if let Some(driver) = drivers.get(bit)
+                && !self.cells[*driver].is_register()
+                && !self.cells[*driver].cell_type().is_user()
+            {
+                mismatches.push(InitMismatch::CombinationalInit {
+                    module: module.to_string(),
+                    bit: self.bit_name(bit),
+                    driver: driver.to_string(),
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+impl Netlist {
+    /// Contradictions between the `init` attributes of nets, constant drivers and register
+    /// resets in every module, such as a register with init 1 whose reset to 0 is tied
+    /// active. Each is a place where simulation and hardware start out differently.
+    pub fn init_mismatches(&self) -> Vec<InitMismatch> {
+        self.modules.iter().flat_map(|(name, module)| module.init_mismatches(name)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, NetlistBuilder};
+
+    #[test]
+    fn test_init_mismatches() {
+        let mut builder = Module::builder();
+        let [clk, d] = ["clk", "d"].map(|name| builder.input(name, 1).unwrap());
+        let [q, r, y] = ["q", "r", "y"].map(|name| builder.output(name, 1).unwrap());
+        let tied = CellBuilder::new("$_DFF_PP0_").input("C", clk.clone()).input("D", d.clone()).input("R", vec![Bit::_1]).output("Q", q);
+        builder.cell("tied", tied).unwrap();
+        let free = CellBuilder::new("$_DFF_PP0_").input("C", clk).input("D", d.clone()).input("R", vec![Bit::_0]).output("Q", r);
+        builder.cell("free", free).unwrap();
+        builder.cell("inv", CellBuilder::new("$_NOT_").input("A", d).output("Y", y)).unwrap();
+        let mut netlist = NetlistBuilder::new("test").top("top", builder).unwrap().build();
+        let module = netlist.modules.get_mut("top").unwrap();
+        for (net, init) in [("q", "1"), ("r", "1"), ("y", "0")] {
+            module.nets[net].attributes.insert("init".to_string(), init.into());
+        }
+
+        let mismatches = netlist.init_mismatches();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            mismatches[0],
+            InitMismatch::ResetTied {
+                module: "top".to_string(),
+                cell: "tied".to_string(),
+                bit: "q".to_string(),
+                init: true,
+                reset: false,
+                asynchronous: true
+            }
+        );
+        assert_eq!(mismatches[1].to_string(), "top: y has init but is driven by inv, which is not a register");
+    }
+}
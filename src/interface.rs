@@ -0,0 +1,278 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::progress::pass_span;
+use crate::{Bit, Direction, IdString, Module, Net, Netlist, Port};
+
+/// Clock pins of register cells.
+const CLOCK_PINS: &[&str] = &["C", "CLK"];
+
+/// Set and reset pins of register cells.
+const RESET_PINS: &[&str] = &["R", "S", "ARST", "SRST", "SET", "CLR"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceError {
+    UnknownModule(String),
+    UnknownPort(String),
+    PortExists(String),
+}
+
+impl fmt::Display for InterfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::UnknownPort(port) => write!(f, "unknown port {:?}", port),
+            Self::PortExists(port) => write!(f, "port {:?} already exists", port),
+        }
+    }
+}
+
+impl std::error::Error for InterfaceError {}
+
+/// What a port is for, in the order ports are grouped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PortRole {
+    Clock,
+    Reset,
+    Other,
+}
+
+/// Order of the ports of a module, which is the order exporters write them in, like the
+/// AIGER and BTOR writers and the cell connections of instances.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PortOrder {
+    /// Clocks, then resets, then the other ports, each group sorted by name.
+    #[default]
+    ByRole,
+    /// The listed ports in order, then the others as with `ByRole`.
+    Listed(Vec<String>),
+}
+
+/// What instances connect to the bits an input port gains. Output and inout ports always gain
+/// new unconnected bits of the instantiating module, as constants cannot be driven.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PadPolicy {
+    #[default]
+    Undefined,
+    Zero,
+    /// Repeats the most significant bit connected, or pads with `x` if there is none.
+    SignExtend,
+}
+
+/// Whether a lowercase port name is, or has a `_`-separated part, in `words`.
+fn named(name: &str, words: &[&str]) -> bool {
+    let name = name.to_lowercase();
+    name.split(['_', '.']).any(|part| words.contains(&part))
+}
+
+impl Module {
+    /// The role of every port. Input ports reaching the clock pin of a register are clocks,
+    /// those reaching a set or reset pin resets; ports no register reads are recognized by
+    /// names like `clk`, `sys_clk`, `rst` or `reset_n`.
+    pub fn port_roles(&self) -> IndexMap<String, PortRole> {
+        let pins = |pins: &[&str]| -> HashSet<Bit> {
+            (self.cells.values().filter(|cell| cell.is_register()))
+                .flat_map(|cell| pins.iter().filter_map(|pin| cell.connections.get(*pin)).flatten())
+                .copied()
+                .filter(|bit| matches!(bit, Bit::Signal(_)))
+                .collect()
+        };
+        let (clocks, resets) = (pins(CLOCK_PINS), pins(RESET_PINS));
+        let mut roles = IndexMap::new();
+        for (name, port) in self.ports.iter() {
+            let role = match port.direction {
+                Direction::Input if port.bits.iter().any(|bit| clocks.contains(bit)) => PortRole::Clock,
+                Direction::Input if port.bits.iter().any(|bit| resets.contains(bit)) => PortRole::Reset,
+                Direction::Input if named(name, &["clk", "clock"]) => PortRole::Clock,
+                Direction::Input if named(name, &["rst", "reset", "rstn", "resetn", "nrst", "nreset"]) => PortRole::Reset,
+                _ => PortRole::Other,
+            };
+            roles.insert(name.to_string(), role);
+        }
+        roles
+    }
+
+    /// Reorders the ports, leaving their bits and everything else unchanged.
+    pub fn reorder_ports(&mut self, order: &PortOrder) -> Result<(), InterfaceError> {
+        let listed: &[String] = match order {
+            PortOrder::ByRole => &[],
+            PortOrder::Listed(listed) => listed,
+        };
+        if let Some(port) = listed.iter().find(|port| !self.ports.contains_key(*port)) {
+            return Err(InterfaceError::UnknownPort(port.clone()));
+        }
+        let roles = self.port_roles();
+        self.ports.sort_by_cached_key(|name, _| {
+            let position = listed.iter().position(|listed| listed == name);
+            (Reverse(position.is_some()), position, roles[name.as_str()], name.clone())
+        });
+        Ok(())
+    }
+}
+
+impl Netlist {
+    /// Reorders the ports of `module`, and the connections of its instances to match.
+    pub fn reorder_ports(&mut self, module: &str, order: &PortOrder) -> Result<(), InterfaceError> {
+        pass_span!("reorder_ports", module);
+        let definition = self.modules.get_mut(module).ok_or_else(|| InterfaceError::UnknownModule(module.to_string()))?;
+        definition.reorder_ports(order)?;
+        let ports: Vec<IdString> = definition.ports.keys().cloned().collect();
+        let position = |port: &IdString| ports.iter().position(|other| other == port).unwrap_or(ports.len());
+        for cell in self.modules.values_mut().flat_map(|module| module.cells.values_mut()).filter(|cell| cell.module == module) {
+            cell.connections.sort_by_cached_key(|port, _| position(port));
+            cell.port_directions.sort_by_cached_key(|port, _| position(port));
+        }
+        Ok(())
+    }
+
+    /// Resizes the connection of `port` on every instance of `module` to `width`, truncating
+    /// the most significant bits or padding by `policy`, or disconnects it when `width` is `None`.
+    fn resize_instances(&mut self, module: &str, port: &str, direction: Direction, width: Option<usize>, policy: PadPolicy) {
+        for parent in self.modules.values_mut() {
+            let names: Vec<String> =
+                parent.cells.iter().filter(|(_, cell)| cell.module == module).map(|(name, _)| name.to_string()).collect();
+            for name in names {
+                let Some(width) = width else {
+                    let cell = &mut parent.cells[&name];
+                    cell.connections.shift_remove(port);
+                    cell.port_directions.shift_remove(port);
+                    continue;
+                };
+                let mut bits = parent.cells[&name].connections.get(port).cloned().unwrap_or_default();
+                let missing = width.saturating_sub(bits.len());
+                let padding = match (&direction, policy) {
+                    (Direction::Input, PadPolicy::Zero) => vec![Bit::_0; missing],
+                    (Direction::Input, PadPolicy::SignExtend) if !bits.is_empty() => vec![bits[bits.len() - 1]; missing],
+                    (Direction::Input, _) => vec![Bit::X; missing],
+                    _ => parent.fresh_bits(missing),
+                };
+                bits.extend(padding);
+                bits.truncate(width);
+                let cell = &mut parent.cells[&name];
+                cell.connections.insert(port.into(), bits);
+                cell.port_directions.insert(port.into(), direction.clone());
+            }
+        }
+    }
+
+    /// Adds a port of fresh bits and its net to `module`, connecting it on every instance as
+    /// `policy` pads. Returns the bits of the port.
+    pub fn add_port(
+        &mut self,
+        module: &str,
+        port: &str,
+        direction: Direction,
+        width: usize,
+        policy: PadPolicy,
+    ) -> Result<Vec<Bit>, InterfaceError> {
+        let definition = self.modules.get_mut(module).ok_or_else(|| InterfaceError::UnknownModule(module.to_string()))?;
+        if definition.ports.contains_key(port) || definition.nets.contains_key(port) {
+            return Err(InterfaceError::PortExists(port.to_string()));
+        }
+        let bits = definition.fresh_bits(width);
+        definition.ports.insert(port.into(), Port::new(direction.clone(), bits.clone()));
+        definition.nets.insert(port.into(), Net::new(bits.clone()));
+        self.resize_instances(module, port, direction, Some(width), policy);
+        Ok(bits)
+    }
+
+    /// Removes a port of `module` and disconnects it from every instance. Its net stays, as an
+    /// internal wire.
+    pub fn remove_port(&mut self, module: &str, port: &str) -> Result<(), InterfaceError> {
+        pass_span!("remove_port", module, port);
+        let definition = self.modules.get_mut(module).ok_or_else(|| InterfaceError::UnknownModule(module.to_string()))?;
+        let removed = definition.ports.shift_remove(port).ok_or_else(|| InterfaceError::UnknownPort(port.to_string()))?;
+        self.resize_instances(module, port, removed.direction, None, PadPolicy::default());
+        Ok(())
+    }
+
+    /// Resizes a port of `module`, dropping its most significant bits or adding fresh ones, and
+    /// the net of the same name with it. Instances are truncated or padded by `policy`.
+    pub fn resize_port(&mut self, module: &str, port: &str, width: usize, policy: PadPolicy) -> Result<(), InterfaceError> {
+        pass_span!("resize_port", module, port, width);
+        let definition = self.modules.get_mut(module).ok_or_else(|| InterfaceError::UnknownModule(module.to_string()))?;
+        let old = definition.ports.get(port).ok_or_else(|| InterfaceError::UnknownPort(port.to_string()))?.bits.clone();
+        let mut bits = old.clone();
+        bits.extend(definition.fresh_bits(width.saturating_sub(old.len())));
+        bits.truncate(width);
+        if let Some(net) = definition.nets.get_mut(port).filter(|net| net.bits == old) {
+            net.bits = bits.clone();
+        }
+        let definition_port = &mut definition.ports[port];
+        definition_port.bits = bits;
+        let direction = definition_port.direction.clone();
+        self.resize_instances(module, port, direction, Some(width), policy);
+        Ok(())
+    }
+
+    /// Puts the ports of every module in [`PortOrder::ByRole`] order, so exports no longer
+    /// depend on the order of the JSON maps.
+    pub fn normalize_interfaces(&mut self) {
+        pass_span!("normalize_interfaces", modules = self.modules.len());
+        let modules: Vec<IdString> = self.modules.keys().cloned().collect();
+        for module in modules {
+            self.reorder_ports(&module, &PortOrder::ByRole).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    #[test]
+    fn test_reorder_ports() {
+        let mut builder = Module::builder();
+        let [d, q] = ["d", "q"].map(|name| builder.wire(name, 1).unwrap());
+        builder.output("out", 1).unwrap();
+        builder.input("b", 1).unwrap();
+        builder.input("rst_n", 1).unwrap();
+        let clock = builder.input("pixel", 1).unwrap();
+        builder.input("a", 1).unwrap();
+        builder.cell("reg", CellBuilder::new("$_DFF_P_").input("C", clock).input("D", d).output("Q", q)).unwrap();
+        let mut module = builder.build();
+        assert_eq!(module.port_roles()["pixel"], PortRole::Clock);
+
+        module.reorder_ports(&PortOrder::ByRole).unwrap();
+        assert!(module.ports.keys().eq(["pixel", "rst_n", "a", "b", "out"]));
+        module.reorder_ports(&PortOrder::Listed(vec!["out".to_string(), "b".to_string()])).unwrap();
+        assert!(module.ports.keys().eq(["out", "b", "pixel", "rst_n", "a"]));
+        let error = module.reorder_ports(&PortOrder::Listed(vec!["c".to_string()]));
+        assert_eq!(error, Err(InterfaceError::UnknownPort("c".to_string())));
+    }
+
+    #[test]
+    fn test_port_refactoring() {
+        let mut child = Module::builder();
+        child.input("a", 2).unwrap();
+        child.output("y", 1).unwrap();
+        let mut parent = Module::builder();
+        let x = parent.input("x", 2).unwrap();
+        for name in ["u0", "u1"] {
+            parent.cell(name, CellBuilder::new("child").input("a", x.clone()).output("y", vec![])).unwrap();
+        }
+        let mut netlist = Netlist::new("test");
+        netlist.modules.insert("child".into(), child.build());
+        netlist.modules.insert("parent".into(), parent.build());
+        netlist.resize_port("child", "a", 4, PadPolicy::SignExtend).unwrap();
+        assert_eq!(netlist.modules["child"].nets["a"].bits.len(), 4);
+        assert_eq!(netlist.modules["parent"].cells["u1"].connections["a"], [x[0], x[1], x[1], x[1]]);
+        netlist.add_port("child", "en", Direction::Input, 1, PadPolicy::Zero).unwrap();
+        assert_eq!(netlist.modules["parent"].cells["u0"].connections["en"], [Bit::_0]);
+        netlist.resize_port("child", "y", 2, PadPolicy::Zero).unwrap();
+        let (u0, u1) = (&netlist.modules["parent"].cells["u0"], &netlist.modules["parent"].cells["u1"]);
+        assert!(matches!(u0.connections["y"][..], [Bit::Signal(_), Bit::Signal(_)]));
+        assert_ne!(u0.connections["y"], u1.connections["y"]);
+
+        netlist.remove_port("child", "a").unwrap();
+        assert!(!netlist.modules["parent"].cells["u0"].connections.contains_key("a"));
+        assert_eq!(
+            netlist.add_port("child", "en", Direction::Input, 1, PadPolicy::Zero),
+            Err(InterfaceError::PortExists("en".to_string()))
+        );
+    }
+}
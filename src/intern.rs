@@ -0,0 +1,408 @@
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use indexmap::{Equivalent, IndexMap};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Netlist;
+
+/// A shared, immutable identifier naming modules, their ports, cells, memories and nets, cell
+/// types and the ports of cells. Clones share one allocation, and identifiers from the same
+/// [`Interner`] compare by pointer before falling back to their text. Serializes as a string.
+#[derive(Clone)]
+pub struct IdString(Arc<str>);
+
+impl IdString {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for IdString {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for IdString {}
+
+impl PartialEq<str> for IdString {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for IdString {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for IdString {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<IdString> for &str {
+    fn eq(&self, other: &IdString) -> bool {
+        **self == *other.0
+    }
+}
+
+impl PartialEq<IdString> for String {
+    fn eq(&self, other: &IdString) -> bool {
+        **self == *other.0
+    }
+}
+
+impl PartialOrd for IdString {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IdString {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Hashes like the text, so maps keyed by identifiers can be looked up with a `&str`.
+impl Hash for IdString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl Borrow<str> for IdString {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Lets maps keyed by identifiers be looked up with a `&String` as well as a `&str`.
+impl Equivalent<IdString> for String {
+    fn equivalent(&self, key: &IdString) -> bool {
+        **self == *key.0
+    }
+}
+
+/// Lets side tables keyed by `String` be looked up with an identifier.
+impl Equivalent<String> for IdString {
+    fn equivalent(&self, key: &String) -> bool {
+        *self.0 == **key
+    }
+}
+
+impl Default for IdString {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl Equivalent<IdString> for &str {
+    fn equivalent(&self, key: &IdString) -> bool {
+        **self == *key.0
+    }
+}
+
+impl AsRef<str> for IdString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for IdString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for IdString {
+    fn from(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<&String> for IdString {
+    fn from(name: &String) -> Self {
+        Self(name.as_str().into())
+    }
+}
+
+impl From<String> for IdString {
+    fn from(name: String) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<IdString> for String {
+    fn from(id: IdString) -> Self {
+        id.0.to_string()
+    }
+}
+
+impl From<&IdString> for String {
+    fn from(id: &IdString) -> Self {
+        id.0.to_string()
+    }
+}
+
+impl fmt::Debug for IdString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for IdString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for IdString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Hands out identifiers of the interner in scope, or fresh ones outside of [`with_interner`].
+struct IdVisitor;
+
+impl Visitor<'_> for IdVisitor {
+    type Value = IdString;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an identifier")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<IdString, E> {
+        Ok(CURRENT.with(|current| match &*current.borrow() {
+            Some(interner) => interner.intern(name),
+            None => IdString::from(name),
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for IdString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(IdVisitor)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Interner>>> = const { RefCell::new(None) };
+}
+
+/// Puts back the interner that was in scope before, even when deserializing panics.
+struct Restore(Option<Arc<Interner>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Runs `parse` with every [`IdString`] it deserializes on this thread handed out by
+/// `interner`, so names are allocated once while reading instead of re-keyed afterwards.
+pub(crate) fn with_interner<T>(interner: &Arc<Interner>, parse: impl FnOnce() -> T) -> T {
+    let _restore = Restore(CURRENT.with(|current| current.replace(Some(interner.clone()))));
+    parse()
+}
+
+/// The interner of the innermost [`with_interner`], or a new one.
+pub(crate) fn current_interner() -> Arc<Interner> {
+    CURRENT.with(|current| current.borrow().clone()).unwrap_or_default()
+}
+
+/// A set of identifiers handing out one shared [`IdString`] per distinct name. Safe to share
+/// between threads, and shared by a netlist and its clones.
+#[derive(Default)]
+pub struct Interner(Mutex<HashSet<IdString>>);
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interner").field("len", &self.len()).finish()
+    }
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashSet<IdString>> {
+        self.0.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    pub fn intern(&self, name: &str) -> IdString {
+        let mut names = self.lock();
+        if let Some(id) = names.get(name) {
+            return id.clone();
+        }
+        let id = IdString::from(name);
+        names.insert(id.clone());
+        id
+    }
+
+    /// The identifier of `name` if it has been interned.
+    pub fn get(&self, name: &str) -> Option<IdString> {
+        self.lock().get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+}
+
+fn rekey<T>(map: &mut IndexMap<IdString, T>, interner: &Interner) {
+    *map = std::mem::take(map).into_iter().map(|(name, value)| (interner.intern(&name), value)).collect();
+}
+
+impl Netlist {
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    pub fn intern(&self, name: &str) -> IdString {
+        self.interner.intern(name)
+    }
+
+    /// Re-keys the modules, ports, cells, memories, nets and cell connections of the netlist,
+    /// and the cell types, by identifiers of its interner. Every reader interns while parsing
+    /// already, so this is only needed to share names inserted by hand afterwards. Returns how
+    /// many distinct names there are.
+    pub fn intern_identifiers(&mut self) -> usize {
+        let interner = &self.interner;
+        rekey(&mut self.modules, interner);
+        for module in self.modules.values_mut() {
+            rekey(&mut module.ports, interner);
+            rekey(&mut module.cells, interner);
+            rekey(&mut module.memories, interner);
+            rekey(&mut module.nets, interner);
+            for cell in module.cells.values_mut() {
+                cell.module = interner.intern(&cell.module);
+                rekey(&mut cell.port_directions, interner);
+                rekey(&mut cell.connections, interner);
+            }
+        }
+        interner.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(a: &IdString, b: &IdString) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+
+    fn key<'a, T>(map: &'a IndexMap<IdString, T>, name: &str) -> &'a IdString {
+        map.get_key_value(name).unwrap().0
+    }
+
+    /// Checks that names repeated across modules, maps and cells are one allocation of the
+    /// netlist's interner.
+    fn assert_shared(netlist: &Netlist) {
+        let (and, or) = (&netlist.modules["test_and"], &netlist.modules["test_or"]);
+        let a = netlist.interner().get("a").unwrap();
+        assert!(same(key(&and.ports, "a"), &a) && same(key(&and.nets, "a"), &a) && same(key(&or.ports, "a"), &a));
+        assert!(same(key(&netlist.modules, "test_and"), &netlist.intern("test_and")));
+        let (and, or) = (and.cells.values().next().unwrap(), or.cells.values().next().unwrap());
+        assert!(same(key(&and.connections, "A"), key(&or.connections, "A")));
+        assert!(same(key(&and.port_directions, "Y"), key(&or.connections, "Y")));
+        assert!(same(&and.module, &netlist.intern("$_AND_")));
+    }
+
+    fn design() -> Netlist {
+        Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_interner() {
+        let netlist = design();
+        assert_shared(&netlist);
+        let count = netlist.interner().len();
+        let (a, b) = (netlist.intern("fresh"), netlist.intern("fresh"));
+        assert!(same(&a, &b));
+        assert_eq!(netlist.interner().len(), count + 1);
+        assert_eq!(netlist.interner().get("fresh"), Some(a));
+
+        let mut netlist = Netlist::new("test");
+        let mut module = crate::Module::new();
+        module.nets.insert("x".into(), crate::Net::new(vec![crate::Bit::Signal(2)]));
+        module.ports.insert("x".into(), crate::Port::new(crate::Direction::Input, vec![crate::Bit::Signal(2)]));
+        netlist.modules.insert("top".into(), module);
+        let shared = |netlist: &Netlist| same(key(&netlist.modules["top"].nets, "x"), key(&netlist.modules["top"].ports, "x"));
+        assert!(!shared(&netlist));
+        assert_eq!(netlist.intern_identifiers(), 2);
+        assert!(shared(&netlist));
+    }
+
+    #[test]
+    fn test_readers_intern() {
+        let input = std::fs::read_to_string("testdata/modules.json").unwrap();
+        let netlist = design();
+        assert_shared(&Netlist::from_str(&input).unwrap());
+        assert_shared(&Netlist::from_value(serde_json::from_str(&input).unwrap()).unwrap());
+        assert_shared(&crate::lazy::LazyNetlist::from_str(&input).unwrap().into_netlist().unwrap());
+
+        let mut text = Vec::new();
+        netlist.write_text(&mut text).unwrap();
+        assert_shared(&Netlist::read_text(text.as_slice()).unwrap());
+
+        let mut modules = IndexMap::new();
+        let mut streamed = Netlist::read_modules(input.as_bytes(), |name, module| _ = modules.insert(name, module)).unwrap();
+        streamed.modules = modules;
+        assert_shared(&streamed);
+
+        let mut container = Vec::new();
+        netlist.write_container(&mut container, crate::container::Compression::None).unwrap();
+        assert_shared(&crate::container::Container::open(std::io::Cursor::new(container)).unwrap().load_all().unwrap());
+
+        #[cfg(feature = "msgpack")]
+        assert_shared(&Netlist::from_msgpack_slice(&netlist.to_msgpack_vec().unwrap()).unwrap());
+        #[cfg(feature = "cbor")]
+        {
+            let mut cbor = Vec::new();
+            netlist.to_cbor(&mut cbor).unwrap();
+            assert_shared(&Netlist::from_cbor(cbor.as_slice()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let input = std::fs::read_to_string("testdata/modules.json").unwrap();
+        let netlist = Netlist::from_str(&input).unwrap();
+        let output = netlist.to_string().unwrap();
+        assert_eq!(Netlist::from_str(&output).unwrap(), netlist);
+        let input: serde_json::Value = serde_json::from_str(&input).unwrap();
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        for (name, module) in input["modules"].as_object().unwrap() {
+            for map in ["ports", "cells", "netnames"] {
+                let keys = |module: &serde_json::Value| module[map].as_object().map(|map| map.keys().cloned().collect::<Vec<_>>());
+                assert_eq!(keys(&output["modules"][name]), keys(module));
+            }
+        }
+
+        let table = IndexMap::from([(IdString::from("fresh"), 1)]);
+        assert_eq!(table["fresh"], 1);
+        assert_eq!(serde_json::to_string(&table).unwrap(), r#"{"fresh":1}"#);
+        let parsed: IndexMap<IdString, i32> = serde_json::from_str(r#"{"fresh":1}"#).unwrap();
+        assert_eq!(parsed, table);
+    }
+}
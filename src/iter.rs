@@ -0,0 +1,141 @@
+use crate::{Cell, IdString, Memory, Module, Net, Netlist, Port};
+
+/// A design object together with the module it belongs to.
+#[derive(Debug)]
+pub struct InModule<'a, T> {
+    pub module_name: &'a str,
+    pub module: &'a Module,
+    pub name: &'a str,
+    pub object: &'a T,
+}
+
+impl<T> Clone for InModule<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for InModule<'_, T> {}
+
+/// A cell reached by walking the hierarchy down from a top module.
+#[derive(Debug, Clone)]
+pub struct InstanceCell<'a> {
+    /// Instance names from the top module down to the module containing the cell, empty for
+    /// cells of the top module.
+    pub path: Vec<&'a str>,
+    pub cell: InModule<'a, Cell>,
+}
+
+impl InstanceCell<'_> {
+    /// The hierarchical name of the cell, instance names joined with `.`.
+    pub fn hierarchical_name(&self) -> String {
+        self.path.iter().chain(std::iter::once(&self.cell.name)).copied().collect::<Vec<_>>().join(".")
+    }
+}
+
+fn objects<'a, T: 'a>(
+    netlist: &'a Netlist,
+    objects: impl Fn(&'a Module) -> &'a indexmap::IndexMap<IdString, T> + 'a,
+) -> impl Iterator<Item = InModule<'a, T>> + 'a {
+    netlist.modules.iter().flat_map(move |(module_name, module)| {
+        objects(module).iter().map(move |(name, object)| InModule { module_name, module, name: name.as_str(), object })
+    })
+}
+
+impl Netlist {
+    pub fn iter_all_cells(&self) -> impl Iterator<Item = InModule<'_, Cell>> {
+        objects(self, |module| &module.cells)
+    }
+
+    pub fn iter_all_nets(&self) -> impl Iterator<Item = InModule<'_, Net>> {
+        objects(self, |module| &module.nets)
+    }
+
+    pub fn iter_all_ports(&self) -> impl Iterator<Item = InModule<'_, Port>> {
+        objects(self, |module| &module.ports)
+    }
+
+    pub fn iter_all_memories(&self) -> impl Iterator<Item = InModule<'_, Memory>> {
+        objects(self, |module| &module.memories)
+    }
+
+    /// Cells of type `module` in every module, e.g. all `$_DFF_P_` or all instances of `adder`.
+    pub fn cells_of_type<'a>(&'a self, module: &'a str) -> impl Iterator<Item = InModule<'a, Cell>> {
+        self.iter_all_cells().filter(move |cell| cell.object.module == module)
+    }
+
+    pub fn cells_with_attribute<'a>(&'a self, attribute: &'a str) -> impl Iterator<Item = InModule<'a, Cell>> {
+        self.iter_all_cells().filter(move |cell| cell.object.attributes.contains_key(attribute))
+    }
+
+    pub fn nets_with_attribute<'a>(&'a self, attribute: &'a str) -> impl Iterator<Item = InModule<'a, Net>> {
+        self.iter_all_nets().filter(move |net| net.object.attributes.contains_key(attribute))
+    }
+
+    /// Every cell below `top`, walking into instances of modules defined in the netlist depth
+    /// first. Instance cells are yielded before their contents, and a module instantiating
+    /// itself through its own hierarchy is not entered again.
+    pub fn iter_hierarchy<'a>(&'a self, top: &'a str) -> impl Iterator<Item = InstanceCell<'a>> + 'a {
+        let mut stack: Vec<(Vec<&'a str>, Vec<&'a str>, &'a str, usize)> = Vec::new();
+        if self.modules.contains_key(top) {
+            stack.push((Vec::new(), vec![top], top, 0));
+        }
+        std::iter::from_fn(move || {
+            loop {
+                let (path, modules, module_name, index) = stack.last_mut()?;
+                let module = &self.modules[*module_name];
+                let Some((name, cell)) = module.cells.get_index(*index) else {
+                    stack.pop();
+                    continue;
+                };
+                *index += 1;
+                let item = InstanceCell { path: path.clone(), cell: InModule { module_name, module, name, object: cell } };
+                if let Some((child, _)) = self.modules.get_key_value(&cell.module)
+                    && !modules.contains(&child.as_str())
+                {
+                    let path = path.iter().copied().chain(std::iter::once(name.as_str())).collect();
+                    let modules = modules.iter().copied().chain(std::iter::once(child.as_str())).collect();
+                    stack.push((path, modules, child, 0));
+                }
+                return Some(item);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::Netlist;
+
+    #[test]
+    fn test_iterators() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        assert_eq!(netlist.iter_all_cells().count(), 3);
+        assert_eq!(netlist.cells_of_type("$_XOR_").map(|cell| cell.module_name).collect::<Vec<_>>(), ["test_xor"]);
+        assert_eq!(netlist.iter_all_ports().filter(|port| port.name == "c").count(), 3);
+
+        netlist.modules["test_or"].nets["c"].attributes.insert("keep".to_string(), json!(1));
+        let kept: Vec<_> = netlist.nets_with_attribute("keep").map(|net| (net.module_name, net.name)).collect();
+        assert_eq!(kept, [("test_or", "c")]);
+        assert_eq!(netlist.cells_with_attribute("keep").count(), 0);
+    }
+
+    #[test]
+    fn test_iter_hierarchy() {
+        let instance = |module: &str| json!({ "type": module, "connections": {} });
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": { "cells": { "u0": instance("mid"), "u1": instance("leaf"), "g": instance("$_NOT_") } },
+                "mid": { "cells": { "v0": instance("leaf"), "self": instance("mid") } },
+                "leaf": { "cells": { "g": instance("$_AND_") } }
+            }
+        }))
+        .unwrap();
+        let names: Vec<String> = netlist.iter_hierarchy("top").map(|cell| cell.hierarchical_name()).collect();
+        assert_eq!(names, ["u0", "u0.v0", "u0.v0.g", "u0.self", "u1", "u1.g", "g"]);
+        assert_eq!(netlist.iter_hierarchy("nope").count(), 0);
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use indexmap::IndexMap;
+
+use crate::event::Delays;
+use crate::physical::{Parasitics, Pin, Placement, Rect};
+use crate::progress::pass_span;
+use crate::{Bit, Direction, Module};
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl From<io::Error> for LayoutError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Removes the backslashes DEF and SPEF put before special characters in names, so they
+/// compare equal to the names in the netlist.
+fn unescape(name: &str) -> String {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn number(line: usize, token: &str) -> Result<f64, LayoutError> {
+    token.parse().map_err(|_| LayoutError::Syntax { line, message: format!("expected a number, found {:?}", token) })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub cell: String,
+    pub placement: Option<Placement>,
+}
+
+/// The placement in a DEF file, coordinates in micrometers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Def {
+    pub design: String,
+    pub die: Option<Rect>,
+    pub components: IndexMap<String, Component>,
+    pub pins: IndexMap<String, Pin>,
+}
+
+/// Whitespace separated tokens of a DEF file with their line numbers.
+struct Tokens<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Self {
+        let lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.split('#').next().unwrap_or_default()));
+        Self { tokens: lines.flat_map(|(line, text)| text.split_whitespace().map(move |token| (line, token))).collect(), next: 0 }
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.next.saturating_sub(1)).map_or(0, |(line, _)| *line)
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.next)?.1;
+        self.next += 1;
+        Some(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).map(|(_, token)| *token)
+    }
+
+    fn token(&mut self) -> Result<&'a str, LayoutError> {
+        self.next().ok_or_else(|| LayoutError::Syntax { line: self.line(), message: "unexpected end of file".to_string() })
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), LayoutError> {
+        match self.token()? {
+            token if token == expected => Ok(()),
+            found => Err(LayoutError::Syntax { line: self.line(), message: format!("expected {:?}, found {:?}", expected, found) }),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, LayoutError> {
+        let token = self.token()?;
+        number(self.line(), token)
+    }
+
+    fn point(&mut self) -> Result<(f64, f64), LayoutError> {
+        self.expect("(")?;
+        let point = (self.number()?, self.number()?);
+        self.expect(")")?;
+        Ok(point)
+    }
+
+    /// Skips to the end of the statement, returning the tokens in between.
+    fn statement(&mut self) -> Vec<&'a str> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next()
+            && token != ";"
+        {
+            tokens.push(token);
+        }
+        tokens
+    }
+}
+
+impl Def {
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Self, LayoutError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::from_str(&text)
+    }
+
+    /// Reads the design name, units, die area, components and pins, and skips the rest.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(text: &str) -> Result<Self, LayoutError> {
+        let mut def = Def::default();
+        let mut tokens = Tokens::new(text);
+        let mut units = 1.0;
+        let (mut components, mut pins) = (Vec::new(), Vec::new());
+        while let Some(token) = tokens.next() {
+            match token {
+                "DESIGN" => def.design = unescape(tokens.statement().first().copied().unwrap_or_default()),
+                "UNITS" => {
+                    tokens.expect("DISTANCE")?;
+                    tokens.expect("MICRONS")?;
+                    units = tokens.number()?;
+                    tokens.expect(";")?;
+                }
+                "DIEAREA" => {
+                    let mut points = Vec::new();
+                    while tokens.peek() == Some("(") {
+                        points.push(tokens.point()?);
+                    }
+                    tokens.expect(";")?;
+                    let (xs, ys): (Vec<f64>, Vec<f64>) = points.into_iter().unzip();
+                    let min = |values: &[f64]| values.iter().copied().fold(f64::INFINITY, f64::min);
+                    let max = |values: &[f64]| values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                    if !xs.is_empty() {
+                        def.die = Some(Rect { x0: min(&xs), y0: min(&ys), x1: max(&xs), y1: max(&ys) });
+                    }
+                }
+                "COMPONENTS" | "PINS" => {
+                    tokens.statement();
+                    let section = if token == "COMPONENTS" { &mut components } else { &mut pins };
+                    while tokens.token()? == "-" {
+                        section.push((tokens.token()?, tokens.line(), tokens.statement()));
+                    }
+                    tokens.expect(token)?;
+                }
+                "END" => _ = tokens.next(),
+                _ => _ = tokens.statement(),
+            }
+        }
+
+        let scale = |(x, y): (f64, f64)| (x / units, y / units);
+        if let Some(die) = def.die.as_mut() {
+            ((die.x0, die.y0), (die.x1, die.y1)) = (scale((die.x0, die.y0)), scale((die.x1, die.y1)));
+        }
+        let placed = |line: usize, tokens: &[&str], index: usize| match tokens[index + 1..] {
+            ["(", x, y, ")", ..] => Ok(scale((number(line, x)?, number(line, y)?))),
+            _ => Err(LayoutError::Syntax { line, message: format!("expected a point after {}", tokens[index]) }),
+        };
+        for (name, line, tokens) in components {
+            let cell = tokens.first().ok_or_else(|| LayoutError::Syntax { line, message: "component without a cell".to_string() })?;
+            let placement = match tokens.iter().position(|token| matches!(*token, "PLACED" | "FIXED" | "COVER")) {
+                Some(index) => {
+                    let (x, y) = placed(line, &tokens, index)?;
+                    Some(Placement { x, y, fixed: tokens[index] != "PLACED" })
+                }
+                None => None,
+            };
+            def.components.insert(unescape(name), Component { cell: unescape(cell), placement });
+        }
+        for (name, line, tokens) in pins {
+            let Some(index) = tokens.iter().position(|token| matches!(*token, "PLACED" | "FIXED" | "COVER")) else { continue };
+            let (x, y) = placed(line, &tokens, index)?;
+            let layer = tokens.iter().position(|token| *token == "LAYER").and_then(|index| tokens.get(index + 1));
+            def.pins.insert(unescape(name), Pin { x, y, layer: layer.map(|layer| layer.to_string()) });
+        }
+        Ok(def)
+    }
+}
+
+/// The parasitics of the nets in a SPEF file, in picofarads and ohms.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spef {
+    pub design: String,
+    pub nets: IndexMap<String, Parasitics>,
+}
+
+impl Spef {
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Self, LayoutError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::from_str(&text)
+    }
+
+    /// Reads the units, name map and the `*D_NET` sections, totalling the resistors of each net.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(text: &str) -> Result<Self, LayoutError> {
+        let mut spef = Spef::default();
+        let mut names: HashMap<&str, String> = HashMap::new();
+        let (mut capacitance, mut resistance) = (1.0, 1.0);
+        let (mut section, mut net) = ("", None);
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let fields: Vec<&str> = line.split("//").next().unwrap_or_default().split_whitespace().collect();
+            let Some(first) = fields.first() else { continue };
+            let field = |index: usize| {
+                fields
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| LayoutError::Syntax { line: line_number, message: format!("too few fields in {}", first) })
+            };
+            let name = |name: &str| {
+                let name = name.split(':').next().unwrap_or_default();
+                names.get(name).cloned().unwrap_or_else(|| unescape(name))
+            };
+            match *first {
+                "*DESIGN" => spef.design = field(1)?.trim_matches('"').to_string(),
+                "*C_UNIT" | "*R_UNIT" => {
+                    let scale = match field(2)?.to_uppercase().as_str() {
+                        "FF" => 1e-3,
+                        "PF" | "OHM" => 1.0,
+                        "NF" | "KOHM" => 1e3,
+                        unit => return Err(LayoutError::Syntax { line: line_number, message: format!("unknown unit {:?}", unit) }),
+                    };
+                    let unit = number(line_number, field(1)?)? * scale;
+                    if *first == "*C_UNIT" { capacitance = unit } else { resistance = unit }
+                }
+                "*D_NET" => {
+                    let total = number(line_number, field(2)?)? * capacitance;
+                    net = Some((name(field(1)?), Parasitics { capacitance: total, resistance: 0.0 }));
+                    section = first;
+                }
+                "*END" => spef.nets.extend(net.take()),
+                _ if section == "*NAME_MAP" && first.strip_prefix('*').is_some_and(|index| index.parse::<u64>().is_ok()) => {
+                    names.insert(first, unescape(field(1)?));
+                }
+                _ if first.starts_with('*') => section = first,
+                _ if section == "*RES" => {
+                    if let Some((_, parasitics)) = net.as_mut() {
+                        parasitics.resistance += number(line_number, field(3)?)? * resistance;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(spef)
+    }
+}
+
+/// How the names of a layout file match the objects of a module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Correlation {
+    pub matched: usize,
+    /// Names in the file that match no object of the module.
+    pub unknown: Vec<String>,
+    /// Objects of the module the file does not mention.
+    pub missing: Vec<String>,
+}
+
+impl Module {
+    /// Annotates the die, pins and cell placements of a DEF file, matching instances by name.
+    /// Missing are the cells without a component.
+    pub fn apply_def(&mut self, def: &Def) -> Correlation {
+        pass_span!("apply_def", cells = self.cells.len(), nets = self.nets.len(), components = def.components.len());
+        let mut correlation = Correlation::default();
+        if def.die.is_some() {
+            self.set_die(def.die);
+        }
+        for (name, pin) in def.pins.iter() {
+            match self.ports.get_mut(name) {
+                Some(port) => port.set_pin(Some(pin.clone())),
+                None => correlation.unknown.push(name.clone()),
+            }
+        }
+        for (name, component) in def.components.iter() {
+            match self.cells.get_mut(name) {
+                Some(cell) => {
+                    cell.set_placement(component.placement);
+                    correlation.matched += 1;
+                }
+                None => correlation.unknown.push(name.clone()),
+            }
+        }
+        correlation.missing = self.cells.keys().filter(|name| !def.components.contains_key(name.as_str())).map(String::from).collect();
+        correlation
+    }
+
+    /// Annotates the parasitics of a SPEF file on the nets of the same name. Missing are the
+    /// named nets with signal bits the file does not mention.
+    pub fn apply_spef(&mut self, spef: &Spef) -> Correlation {
+        pass_span!("apply_spef", cells = self.cells.len(), nets = self.nets.len(), parasitics = spef.nets.len());
+        let mut correlation = Correlation::default();
+        for (name, parasitics) in spef.nets.iter() {
+            match self.nets.get_mut(name) {
+                Some(net) => {
+                    net.set_parasitics(Some(*parasitics));
+                    correlation.matched += 1;
+                }
+                None => correlation.unknown.push(name.clone()),
+            }
+        }
+        correlation.missing = (self.nets.iter())
+            .filter(|(name, net)| {
+                !net.hide_name && !spef.nets.contains_key(name.as_str()) && net.bits.iter().any(|bit| matches!(bit, Bit::Signal(_)))
+            })
+            .map(|(name, _)| name.to_string())
+            .collect();
+        correlation
+    }
+}
+
+impl Delays {
+    /// These delays plus a wire delay for every cell driving nets with parasitics:
+    /// `time_per_pf` time units per picofarad of the largest load, rounded up.
+    pub fn with_parasitics(&self, module: &Module, time_per_pf: f64) -> Delays {
+        let mut loads: HashMap<Bit, f64> = HashMap::new();
+        for net in module.nets.values() {
+            let Some(parasitics) = net.parasitics() else { continue };
+            for bit in net.bits.iter() {
+                let load = loads.entry(*bit).or_default();
+                *load = load.max(parasitics.capacitance);
+            }
+        }
+        let mut delays = self.clone();
+        for (name, cell) in module.cells.iter() {
+            let outputs = cell.connections.iter().filter(|(port, _)| cell.port_directions.get(*port) == Some(&Direction::Output));
+            let load = outputs.flat_map(|(_, bits)| bits).filter_map(|bit| loads.get(bit)).copied().fold(0.0, f64::max);
+            if load > 0.0 {
+                delays.cells.insert(name.to_string(), self.of(name, &cell.module) + (load * time_per_pf).ceil() as u64);
+            }
+        }
+        delays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, NetlistBuilder};
+
+    const DEF: &str = "VERSION 5.8 ;
+DESIGN top ;
+UNITS DISTANCE MICRONS 1000 ;
+DIEAREA ( 0 0 ) ( 50000 40000 ) ;
+COMPONENTS 3 ;
+- u_and sky130_fd_sc_hd__and2_1 + PLACED ( 1000 2000 ) N ;
+- u\\[0\\] sky130_fd_sc_hd__inv_1
+  + FIXED ( 3000 2000 ) FS ;
+- FILLER_0 sky130_fd_sc_hd__fill_1 + SOURCE DIST + PLACED ( 0 0 ) N ;
+END COMPONENTS
+PINS 1 ;
+- a + NET a + DIRECTION INPUT + USE SIGNAL
+  + PORT + LAYER met2 ( -140 -2000 ) ( 140 2000 ) + PLACED ( 5000 0 ) N ;
+END PINS
+NETS 1 ;
+- a ( PIN a ) ( u_and A ) + USE SIGNAL ;
+END NETS
+END DESIGN
+";
+
+    const SPEF: &str = "*SPEF \"IEEE 1481-1998\"
+*DESIGN \"top\"
+*C_UNIT 1 FF
+*R_UNIT 1 OHM
+*NAME_MAP
+*1 y
+*2 u_and
+*D_NET *1 12.5
+*CONN
+*I *2:X O
+*CAP
+1 *1 12.5
+*RES
+1 *2:X *1:1 10.0
+2 *1:1 *1 2.5
+*END
+*D_NET other 1.0
+*END
+";
+
+    #[test]
+    fn test_apply_layout() {
+        let mut builder = crate::Module::builder();
+        let a = builder.input("a", 1).unwrap();
+        let y = builder.output("y", 1).unwrap();
+        let and = CellBuilder::new("sky130_fd_sc_hd__and2_1").input("A", a.clone()).input("B", a.clone()).output("X", y.clone());
+        builder.cell("u_and", and).unwrap();
+        builder.cell("u[0]", CellBuilder::new("sky130_fd_sc_hd__inv_1").input("A", a).output("Y", vec![Bit::X])).unwrap();
+        builder.cell("u_new", CellBuilder::new("sky130_fd_sc_hd__inv_1").input("A", y)).unwrap();
+        let mut module = NetlistBuilder::new("test").top("top", builder).unwrap().build().modules.swap_remove("top").unwrap();
+
+        let def = Def::from_str(DEF).unwrap();
+        assert_eq!(def.die, Some(Rect { x0: 0.0, y0: 0.0, x1: 50.0, y1: 40.0 }));
+        let correlation = module.apply_def(&def);
+        assert_eq!(correlation, Correlation { matched: 2, unknown: vec!["FILLER_0".to_string()], missing: vec!["u_new".to_string()] });
+        assert_eq!(module.cells["u[0]"].placement(), Some(Placement { x: 3.0, y: 2.0, fixed: true }));
+        assert_eq!(module.ports["a"].pin(), Some(Pin { x: 5.0, y: 0.0, layer: Some("met2".to_string()) }));
+        assert!(module.validate_physical().is_empty());
+
+        let correlation = module.apply_spef(&Spef::from_str(SPEF).unwrap());
+        assert_eq!(correlation, Correlation { matched: 1, unknown: vec!["other".to_string()], missing: vec!["a".to_string()] });
+        let parasitics = module.nets["y"].parasitics().unwrap();
+        assert!((parasitics.capacitance - 0.0125).abs() < 1e-12 && parasitics.resistance == 12.5);
+        let delays = Delays { default: 1, ..Delays::default() }.with_parasitics(&module, 100.0);
+        assert_eq!(delays.cells.into_iter().collect::<Vec<_>>(), [("u_and".to_string(), 3)]);
+
+        assert!(matches!(Def::from_str("UNITS DISTANCE MICRONS x ;"), Err(LayoutError::Syntax { line: 1, .. })));
+    }
+}
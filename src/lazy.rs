@@ -0,0 +1,163 @@
+use std::cell::OnceCell;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use serde::de::{DeserializeOwned, Error as _};
+use serde_json::value::RawValue;
+
+use crate::intern::{Interner, with_interner};
+use crate::{Error, IdString, Module, Netlist};
+
+/// Deserializes a part of the document, with errors located at `path` like the eager readers
+/// report them.
+fn parse<T: DeserializeOwned>(raw: &RawValue, path: &str) -> Result<T, Error> {
+    serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(raw.get())).map_err(|error| Error::within(path, error))
+}
+
+/// A module kept as raw JSON until it is first accessed.
+#[derive(Debug)]
+struct LazyModule {
+    path: String,
+    raw: Box<RawValue>,
+    parsed: OnceCell<Module>,
+}
+
+impl LazyModule {
+    fn get(&self, interner: &Arc<Interner>) -> Result<&Module, Error> {
+        if let Some(module) = self.parsed.get() {
+            return Ok(module);
+        }
+        let module = with_interner(interner, || parse(&self.raw, &self.path))?;
+        Ok(self.parsed.get_or_init(|| module))
+    }
+
+    fn into_module(self, interner: &Arc<Interner>) -> Result<Module, Error> {
+        match self.parsed.into_inner() {
+            Some(module) => Ok(module),
+            None => with_interner(interner, || parse(&self.raw, &self.path)),
+        }
+    }
+}
+
+/// A netlist whose module bodies are only deserialized when first accessed. Parsing still
+/// checks the syntax of the whole document, but skips building the cells and nets of modules
+/// that are never looked at.
+#[derive(Debug)]
+pub struct LazyNetlist {
+    pub creator: String,
+    modules: IndexMap<IdString, LazyModule>,
+    extra: IndexMap<String, serde_json::Value>,
+    interner: Arc<Interner>,
+}
+
+impl LazyNetlist {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &str) -> Result<Self, Error> {
+        let mut fields: IndexMap<String, Box<RawValue>> = serde_json::from_str(input)?;
+        let creator = match fields.shift_remove("creator") {
+            Some(creator) => parse(&creator, "creator")?,
+            None => return Err(serde_json::Error::missing_field("creator").into()),
+        };
+        let interner = Arc::new(Interner::new());
+        let modules: IndexMap<IdString, Box<RawValue>> = match fields.shift_remove("modules") {
+            Some(modules) => with_interner(&interner, || parse(&modules, "modules"))?,
+            None => return Err(serde_json::Error::missing_field("modules").into()),
+        };
+        let modules = (modules.into_iter())
+            .map(|(name, raw)| {
+                let path = format!("modules.{}", name);
+                (name, LazyModule { path, raw, parsed: OnceCell::new() })
+            })
+            .collect();
+        let extra = fields.into_iter().map(|(key, raw)| Ok((key.clone(), parse(&raw, &key)?))).collect::<Result<_, Error>>()?;
+        Ok(Self { creator, modules, extra, interner })
+    }
+
+    pub fn from_slice(input: &[u8]) -> Result<Self, Error> {
+        Self::from_str(std::str::from_utf8(input).map_err(|error| Error::Syntax(serde_json::Error::custom(error)))?)
+    }
+
+    pub fn from_reader(mut reader: impl std::io::Read) -> Result<Self, Error> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        Self::from_str(&input)
+    }
+
+    pub fn module_names(&self) -> impl Iterator<Item = &str> {
+        self.modules.keys().map(IdString::as_str)
+    }
+
+    pub fn contains_module(&self, name: &str) -> bool {
+        self.modules.contains_key(name)
+    }
+
+    /// Whether the body of `name` has been deserialized already.
+    pub fn is_parsed(&self, name: &str) -> bool {
+        self.modules.get(name).is_some_and(|module| module.parsed.get().is_some())
+    }
+
+    /// Deserializes the module on first access. A module that fails to deserialize is retried
+    /// on every access.
+    pub fn module(&self, name: &str) -> Option<Result<&Module, Error>> {
+        self.modules.get(name).map(|module| module.get(&self.interner))
+    }
+
+    pub fn module_mut(&mut self, name: &str) -> Option<Result<&mut Module, Error>> {
+        let module = self.modules.get_mut(name)?;
+        if let Err(error) = module.get(&self.interner) {
+            return Some(Err(error));
+        }
+        module.parsed.get_mut().map(Ok)
+    }
+
+    /// Deserializes every remaining module.
+    pub fn into_netlist(self) -> Result<Netlist, Error> {
+        let interner = self.interner;
+        let modules = self.modules.into_iter().map(|(name, module)| Ok((name, module.into_module(&interner)?))).collect::<Result<_, Error>>()?;
+        Ok(Netlist { creator: self.creator, modules, extra: self.extra, interner })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_modules() {
+        let input = std::fs::read_to_string("testdata/modules.json").unwrap();
+        let mut lazy = LazyNetlist::from_str(&input).unwrap();
+        assert_eq!(lazy.module_names().collect::<Vec<_>>(), ["test_and", "test_or", "test_xor"]);
+        assert!(!lazy.is_parsed("test_or"));
+
+        assert_eq!(lazy.module("test_or").unwrap().unwrap().cells.len(), 1);
+        assert!(lazy.is_parsed("test_or"));
+        assert!(!lazy.is_parsed("test_and"));
+        assert!(lazy.module("nope").is_none());
+
+        lazy.module_mut("test_xor").unwrap().unwrap().cells.clear();
+        let netlist = lazy.into_netlist().unwrap();
+        let mut expected = Netlist::from_str(&input).unwrap();
+        expected.modules["test_xor"].cells.clear();
+        assert_eq!(netlist, expected);
+    }
+
+    #[test]
+    fn test_lazy_errors() {
+        let input = r#"{"creator": "test", "modules": {"good": {}, "bad": {"cells": {"add": {"type": "$add", "connections": {"A": [2, "q"]}}}}}}"#;
+        fn path<T>(result: Result<T, Error>) -> String {
+            match result {
+                Err(Error::Structure { path, .. }) => path,
+                Err(error) => panic!("unexpected {:?}", error),
+                Ok(_) => panic!("no error"),
+            }
+        }
+        let lazy = LazyNetlist::from_str(input).unwrap();
+        assert!(lazy.module("good").unwrap().is_ok());
+        // Errors point into the module like those of the eager parser.
+        assert_eq!(path(lazy.module("bad").unwrap()), "modules.bad.cells.add.connections.A[1]");
+        assert_eq!(path(Netlist::from_str(input)), "modules.bad.cells.add.connections.A[1]");
+        assert_eq!(path(lazy.into_netlist()), "modules.bad.cells.add.connections.A[1]");
+        assert_eq!(path(LazyNetlist::from_str(r#"{"creator": 1, "modules": {}}"#)), "creator");
+        assert!(matches!(LazyNetlist::from_str(r#"{"creator": "test", "modules": {"broken": {]}}"#), Err(Error::Syntax(_))));
+    }
+}
@@ -2,13 +2,121 @@
 use indexmap::IndexMap;
 use serde::{de::{self, Visitor}, Deserialize, Deserializer, Serialize};
 
+use crate::progress::pass_span;
+
+pub mod abstraction;
+pub mod aiger;
+pub mod alias;
+pub mod annotate;
+pub mod arena;
+pub mod attrs;
+pub mod batch;
+pub mod boundary;
+pub mod btor;
+pub mod builder;
+pub mod celltype;
+pub mod checkpoint;
+pub mod connectivity;
+pub mod container;
+pub mod cnf;
+pub mod cosim;
+pub mod cse;
+pub mod dataflow;
+pub mod datapath;
+pub mod display;
+pub mod dot;
+pub mod ecc;
+pub mod edit;
+pub mod equality;
+pub mod equivalence;
+mod error;
+pub mod fault;
+pub mod event;
+pub mod feedthrough;
+pub mod formal;
+mod gates;
+#[cfg(feature = "petgraph")]
+pub mod graph;
+pub mod harness;
+pub mod hierarchy;
+pub mod incremental;
+pub mod initcheck;
+pub mod interface;
+pub mod intern;
+pub mod iter;
+pub mod layout;
+pub mod lazy;
+pub mod limits;
+pub mod locking;
+pub mod memory;
+pub mod metadata;
+pub mod naming;
+pub mod npn;
+pub mod param;
+pub mod pass;
+pub mod permute;
+pub mod physical;
+pub mod progress;
+pub mod provenance;
+pub mod reconfig;
+pub mod retarget;
+pub mod rng;
+pub mod safety;
+pub mod sat;
+pub mod sby;
+pub mod script;
+pub mod signoff;
+pub mod sim;
+pub mod stats;
+pub mod stdcell;
+mod stream;
+mod strict;
+pub mod structural;
+pub mod scoap;
+pub mod summary;
+pub mod svg;
+pub mod tape;
+pub mod testpoint;
+pub mod text;
+pub mod tmr;
+pub mod utilization;
+pub mod validate;
+pub mod vcd;
+pub mod view;
+pub mod watermark;
+mod wire;
+
+pub use error::Error;
+pub use intern::IdString;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct Netlist {
     pub creator: String,
-    pub modules: IndexMap<String, Module>,
+    pub modules: IndexMap<IdString, Module>,
 
     #[serde(flatten)]
     extra: IndexMap<String, serde_json::Value>,
+
+    #[serde(skip)]
+    interner: std::sync::Arc<intern::Interner>,
+}
+
+impl Serialize for Netlist {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Netlist::serialize(self, serializer)
+    }
+}
+
+/// Interns every identifier while reading, so all formats and readers share names through the
+/// interner of the netlist they produce.
+impl<'de> Deserialize<'de> for Netlist {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let interner = intern::current_interner();
+        let mut netlist = intern::with_interner(&interner, || Netlist::deserialize(deserializer))?;
+        netlist.interner = interner;
+        Ok(netlist)
+    }
 }
 
 impl Netlist {
@@ -17,52 +125,188 @@ impl Netlist {
             creator: creator.to_string(),
             modules: IndexMap::new(),
             extra: IndexMap::new(),
+            interner: Default::default(),
         }
     }
 
-    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
-        serde_json::from_reader(reader)
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, Error> {
+        Self::parse(serde_json::Deserializer::from_reader(reader))
+    }
+
+    pub fn from_slice(input: &[u8]) -> Result<Self, Error> {
+        Self::parse(serde_json::Deserializer::from_slice(input))
     }
 
-    pub fn from_slice(input: &[u8]) -> Result<Self, serde_json::Error> {
-        serde_json::from_slice(input)
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &str) -> Result<Self, Error> {
+        Self::parse(serde_json::Deserializer::from_str(input))
     }
 
-    pub fn from_str(input: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(input)
+    pub fn from_value(value: serde_json::Value) -> Result<Self, Error> {
+        pass_span!("parse");
+        let netlist: Netlist = serde_path_to_error::deserialize(value)?;
+        #[cfg(feature = "tracing")]
+        tracing::info!(modules = netlist.modules.len(), "parsed netlist");
+        Ok(netlist)
     }
 
-    pub fn from_value(value: serde_json::Value) -> Result<Self, serde_json::Error> {
-        serde_json::from_value(value)
+    fn parse<'de>(mut deserializer: serde_json::Deserializer<impl serde_json::de::Read<'de>>) -> Result<Self, Error> {
+        pass_span!("parse");
+        let netlist: Netlist = serde_path_to_error::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        #[cfg(feature = "tracing")]
+        tracing::info!(modules = netlist.modules.len(), "parsed netlist");
+        Ok(netlist)
     }
 
-    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
-        serde_json::to_writer(writer, self)
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), Error> {
+        Ok(serde_json::to_writer(writer, self)?)
     }
 
-    pub fn to_string(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(self)
+    pub fn to_string(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn to_writer_pretty(&self, writer: impl std::io::Write) -> Result<(), Error> {
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    pub fn to_string_pretty(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Sorts modules, cells, memories, nets, connections and attribute, parameter and unknown
+    /// keys by name, so equal netlists serialize to the same bytes. Ports keep their order,
+    /// which instances by position depend on.
+    pub fn canonicalize(&mut self) {
+        self.modules.sort_keys();
+        self.extra.sort_keys();
+        for module in self.modules.values_mut() {
+            module.attributes.sort_keys();
+            module.extra.sort_keys();
+            module.cells.sort_keys();
+            module.memories.sort_keys();
+            module.nets.sort_keys();
+            module.ports.values_mut().for_each(|port| port.extra.sort_keys());
+            for cell in module.cells.values_mut() {
+                cell.attributes.sort_keys();
+                cell.parameters.sort_keys();
+                cell.port_directions.sort_keys();
+                cell.connections.sort_keys();
+                cell.extra.sort_keys();
+            }
+            for memory in module.memories.values_mut() {
+                memory.attributes.sort_keys();
+                memory.extra.sort_keys();
+            }
+            for net in module.nets.values_mut() {
+                net.attributes.sort_keys();
+                net.extra.sort_keys();
+            }
+        }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Module {
     #[serde(default)]
     pub attributes: IndexMap<String, serde_json::Value>,
     #[serde(default)]
-    pub ports: IndexMap<String, Port>,
+    pub ports: IndexMap<IdString, Port>,
     #[serde(default)]
-    pub cells: IndexMap<String, Cell>,
+    pub cells: IndexMap<IdString, Cell>,
     #[serde(default)]
-    pub memories: IndexMap<String, Memory>,
+    pub memories: IndexMap<IdString, Memory>,
     #[serde(default, rename="netnames")]
-    pub nets: IndexMap<String, Net>,
+    pub nets: IndexMap<IdString, Net>,
 
     #[serde(flatten)]
-    extra: IndexMap<String, serde_json::Value>
+    extra: IndexMap<String, serde_json::Value>,
+
+    /// Signals below this one have been handed out by [`Module::alloc_bits`].
+    #[serde(skip)]
+    allocated: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Module {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn signals(&self) -> impl Iterator<Item = u64> + '_ {
+        let ports = self.ports.values().flat_map(|port| port.bits.iter());
+        let cells = self.cells.values().flat_map(|cell| cell.connections.values().flatten());
+        let nets = self.nets.values().flat_map(|net| net.bits.iter());
+        ports.chain(cells).chain(nets).filter_map(|bit| match bit {
+            Bit::Signal(signal) => Some(*signal),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn substitute(&mut self, map: &std::collections::HashMap<Bit, Bit>) {
+        let replace = |bits: &mut Vec<Bit>| {
+            for bit in bits.iter_mut() {
+                if let Some(replacement) = map.get(bit) {
+                    *bit = *replacement;
+                }
+            }
+        };
+        self.ports.values_mut().for_each(|port| replace(&mut port.bits));
+        self.cells.values_mut().for_each(|cell| cell.connections.values_mut().for_each(replace));
+        self.nets.values_mut().for_each(|net| replace(&mut net.bits));
+    }
+
+    pub(crate) fn fresh_bits(&self, count: usize) -> Vec<Bit> {
+        // Yosys reserves 0 and 1 for the constants in some backends, so start numbering at 2.
+        let next = self.signals().max().map_or(2, |max| max + 1).max(self.allocated);
+        (next..next + count as u64).map(Bit::Signal).collect()
+    }
+
+    fn net_of(&self, bit: &Bit) -> Option<(&IdString, &Net)> {
+        let mut nets = self.nets.iter().filter(|(_, net)| net.bits.contains(bit));
+        let first = nets.clone().next();
+        nets.find(|(_, net)| !net.hide_name).or(first)
+    }
+
+    /// Name of the public net a bit belongs to, falling back to any net.
+    pub(crate) fn bus_of(&self, bit: &Bit) -> Option<&str> {
+        self.net_of(bit).map(|(name, _)| name.as_str())
+    }
+
+    /// `net[index]` for bits of wide nets, the bare net name for single bit nets.
+    pub(crate) fn bit_name(&self, bit: &Bit) -> String {
+        match self.net_of(bit) {
+            Some((name, net)) if net.bits.len() > 1 => {
+                format!("{}[{}]", name, net.bits.iter().position(|other| other == bit).unwrap() + net.offset)
+            }
+            Some((name, _)) => name.to_string(),
+            None => format!("{:?}", bit),
+        }
+    }
+
+    /// The net a signal bit is best known by, a public one when there is any, and the index
+    /// of the bit in it counted from the net's `offset`.
+    pub fn name_of_bit(&self, bit: &Bit) -> Option<(&str, usize)> {
+        let (name, net) = self.net_of(bit)?;
+        Some((name.as_str(), net.bits.iter().position(|other| other == bit).unwrap() + net.offset))
+    }
+
+    /// [`Module::name_of_bit`] for every signal bit on a net, in one pass over the nets.
+    pub fn bit_names(&self) -> std::collections::HashMap<Bit, (&str, usize)> {
+        let mut names: std::collections::HashMap<Bit, (&str, usize, bool)> = std::collections::HashMap::new();
+        for (name, net) in self.nets.iter() {
+            for (index, bit) in net.bits.iter().enumerate().filter(|(_, bit)| matches!(bit, Bit::Signal(_))) {
+                let entry = names.entry(*bit).or_insert((name, index + net.offset, net.hide_name));
+                if entry.2 && !net.hide_name {
+                    *entry = (name, index + net.offset, false);
+                }
+            }
+        }
+        names.into_iter().map(|(bit, (name, index, _))| (bit, (name, index))).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Port {
     pub direction: Direction,
     pub bits: Vec<Bit>,
@@ -77,27 +321,63 @@ pub struct Port {
     extra: IndexMap<String, serde_json::Value>
 }
 
+impl Port {
+    pub fn new(direction: Direction, bits: Vec<Bit>) -> Self {
+        Self {
+            direction,
+            bits,
+            offset: 0,
+            upto: 0,
+            signed: false,
+            extra: IndexMap::new(),
+        }
+    }
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Cell {
     #[serde(default, serialize_with="serialize_bool_u64", deserialize_with="deserialize_u64_bool")]
     pub hide_name: bool,
     #[serde(rename = "type")]
-    pub module: String,
+    pub module: IdString,
     #[serde(default)]
     pub attributes: IndexMap<String, serde_json::Value>,
     #[serde(default)]
     pub parameters: IndexMap<String, serde_json::Value>,
     #[serde(default)]
-    pub port_directions: IndexMap<String, Direction>,
+    pub port_directions: IndexMap<IdString, Direction>,
     #[serde(default)]
-    pub connections: IndexMap<String, Vec<Bit>>,
+    pub connections: IndexMap<IdString, Vec<Bit>>,
 
     #[serde(flatten)]
     extra: IndexMap<String, serde_json::Value>
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Cell {
+    /// A cell of type `module` without parameters or connections.
+    pub fn new(module: &str) -> Self {
+        Self { module: module.into(), ..Self::default() }
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.parameters.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Connects `port`, declaring its direction as well.
+    pub fn with_connection(mut self, port: &str, direction: Direction, bits: Vec<Bit>) -> Self {
+        self.port_directions.insert(port.into(), direction);
+        self.connections.insert(port.into(), bits);
+        self
+    }
+
+    /// Flip-flops and latches, both the word-level cells and the gate-level `$_DFF_*` family.
+    pub(crate) fn is_register(&self) -> bool {
+        self.cell_type().is_register()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Memory {
     #[serde(default, serialize_with="serialize_bool_u64", deserialize_with="deserialize_u64_bool")]
     pub hide_name: bool,
@@ -112,7 +392,7 @@ pub struct Memory {
     extra: IndexMap<String, serde_json::Value>
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Net {
     #[serde(default, serialize_with="serialize_bool_u64", deserialize_with="deserialize_u64_bool")]
     pub hide_name: bool,
@@ -130,8 +410,68 @@ pub struct Net {
     extra: IndexMap<String, serde_json::Value>
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+impl Memory {
+    /// `size` words of `width` bits, addressed from 0.
+    pub fn new(width: usize, size: usize) -> Self {
+        Self { width, size, ..Self::default() }
+    }
+}
+
+impl Net {
+    pub fn new(bits: Vec<Bit>) -> Self {
+        Self { bits, ..Self::default() }
+    }
+}
+
+macro_rules! extra_fields {
+    ($($type:ty: [$($field:literal),*]),*) => {
+        $(
+            impl $type {
+                /// Fields not known to this crate, written back out unchanged. Keys naming a
+                /// known field must not be added through [`Self::extra_mut`], they would be
+                /// written twice.
+                pub fn extra(&self) -> &IndexMap<String, serde_json::Value> {
+                    &self.extra
+                }
+
+                pub fn extra_mut(&mut self) -> &mut IndexMap<String, serde_json::Value> {
+                    &mut self.extra
+                }
+
+                pub fn take_extra(&mut self) -> IndexMap<String, serde_json::Value> {
+                    std::mem::take(&mut self.extra)
+                }
+
+                /// The unknown field `key` decoded as `T`, `None` if it is not there.
+                pub fn extra_as<T: de::DeserializeOwned>(&self, key: &str) -> Option<Result<T, serde_json::Error>> {
+                    self.extra.get(key).map(T::deserialize)
+                }
+
+                /// Sets the unknown field `key`, returning its previous value. Fails for keys
+                /// naming a known field, which have their own typed field.
+                pub fn set_extra<T: Serialize>(&mut self, key: &str, value: &T) -> Result<Option<serde_json::Value>, serde_json::Error> {
+                    if [$($field),*].contains(&key) {
+                        return Err(serde::ser::Error::custom(format!("{:?} is a known field of {}", key, stringify!($type))));
+                    }
+                    Ok(self.extra.insert(key.to_string(), serde_json::to_value(value)?))
+                }
+            }
+        )*
+    };
+}
+
+extra_fields!(
+    Netlist: ["creator", "modules"],
+    Module: ["attributes", "ports", "cells", "memories", "netnames"],
+    Port: ["direction", "bits", "offset", "upto", "signed"],
+    Cell: ["hide_name", "type", "attributes", "parameters", "port_directions", "connections"],
+    Memory: ["hide_name", "attributes", "width", "size", "start_offset"],
+    Net: ["hide_name", "attributes", "bits", "offset", "upto", "signed"]
+);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Direction {
+    #[default]
     #[serde(rename = "input")]
     Input,
     #[serde(rename = "output")]
@@ -203,9 +543,10 @@ impl<'de> Deserialize<'de> for Bit {
     }
 }
 
+/// Writes a flag the way Yosys does, `1` for set and `0` for clear.
 pub fn serialize_bool_u64<S: serde::Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
     match value {
-        true => serializer.serialize_u64(0),
+        true => serializer.serialize_u64(1),
         false => serializer.serialize_u64(0),
     }
 }
@@ -254,6 +595,21 @@ mod tests {
         assert_eq!(to_value(Bit::X), json!("x"));
     }
 
+    #[test]
+    fn test_flag_roundtrip() {
+        let net: Net = from_value(json!({ "hide_name": 1, "bits": [2, 3], "upto": 1, "signed": 1 }));
+        assert!(net.hide_name && net.signed);
+        assert_eq!(net.upto, 1);
+        let value = to_value(&net);
+        assert_eq!((&value["hide_name"], &value["upto"], &value["signed"]), (&json!(1), &json!(1), &json!(1)));
+        let port: Port = from_value(json!({ "direction": "input", "bits": [2] }));
+        let port = to_value(port);
+        assert_eq!((&port["upto"], &port["signed"]), (&json!(0), &json!(0)));
+        let cell: Cell = from_value(json!({ "hide_name": 1, "type": "$_NOT_" }));
+        let cell: Cell = from_value(to_value(cell));
+        assert!(cell.hide_name);
+    }
+
     #[test]
     fn test_deserialize_bit() {
         assert_eq!(from_value::<Bit>(json!(42)), Bit::Signal(42));
@@ -277,6 +633,84 @@ mod tests {
         assert_eq!(to_value(Direction::InOut), json!("inout"));
     }
 
+    #[test]
+    fn test_constructors() {
+        let mut module = Module::new();
+        module.ports.insert("a".into(), Port::new(Direction::Input, vec![Bit::Signal(2)]));
+        module.ports.insert("y".into(), Port { direction: Direction::Output, bits: vec![Bit::Signal(3)], ..Port::default() });
+        module.memories.insert("ram".into(), Memory::new(8, 16));
+        let cell = Cell::new("$_NOT_")
+            .with_connection("A", Direction::Input, vec![Bit::Signal(2)])
+            .with_connection("Y", Direction::Output, vec![Bit::Signal(3)]);
+        module.cells.insert("inv".into(), cell);
+        module.nets.insert("a".into(), Net::new(vec![Bit::Signal(2)]));
+        assert_eq!(Port::default().direction, Direction::Input);
+        assert_eq!(to_value(&module)["cells"]["inv"], json!({
+            "hide_name": 0, "type": "$_NOT_", "attributes": {}, "parameters": {},
+            "port_directions": { "A": "input", "Y": "output" }, "connections": { "A": [2], "Y": [3] }
+        }));
+        assert_eq!(to_value(&module)["memories"]["ram"], json!({ "hide_name": 0, "attributes": {}, "width": 8, "size": 16, "start_offset": 0 }));
+    }
+
+    #[test]
+    fn test_name_of_bit() {
+        let mut module = Module::new();
+        module.nets.insert("$auto$1".into(), Net { hide_name: true, ..Net::new(vec![Bit::Signal(2), Bit::Signal(3)]) });
+        module.nets.insert("data".into(), Net { offset: 4, ..Net::new(vec![Bit::Signal(3), Bit::Signal(4)]) });
+        assert_eq!(module.name_of_bit(&Bit::Signal(2)), Some(("$auto$1", 0)));
+        assert_eq!(module.name_of_bit(&Bit::Signal(3)), Some(("data", 4)));
+        assert_eq!(module.name_of_bit(&Bit::Signal(9)), None);
+
+        let names = module.bit_names();
+        assert_eq!(names.len(), 3);
+        for bit in [Bit::Signal(2), Bit::Signal(3), Bit::Signal(4)] {
+            assert_eq!(names.get(&bit).copied(), module.name_of_bit(&bit));
+        }
+    }
+
+    #[test]
+    fn test_extra_fields() {
+        let mut netlist: Netlist = from_value(json!({
+            "creator": "test",
+            "vendor": { "tool": "x" },
+            "modules": { "top": { "cells": { "u": { "type": "$_NOT_", "timing": [1, 2] } } } }
+        }));
+        assert_eq!(netlist.extra()["vendor"], json!({ "tool": "x" }));
+        let cell = &mut netlist.modules["top"].cells["u"];
+        assert_eq!(cell.take_extra()["timing"], json!([1, 2]));
+        cell.extra_mut().insert("sidecar".to_string(), json!(true));
+        assert_eq!(cell.set_extra("nextpnr_bel", &"X1/Y2").unwrap(), None);
+        assert_eq!(cell.extra_as::<String>("nextpnr_bel").unwrap().unwrap(), "X1/Y2");
+        assert!(cell.extra_as::<u32>("nextpnr_bel").unwrap().is_err());
+        assert!(netlist.modules["top"].extra_as::<u32>("missing").is_none());
+        let cell = &mut netlist.modules["top"].cells["u"];
+        assert!(cell.set_extra("type", &"$_AND_").is_err());
+        assert!(netlist.modules["top"].set_extra("netnames", &json!({})).is_err());
+        assert_eq!(Netlist::from_str(&netlist.to_string().unwrap()).unwrap(), netlist);
+
+        let value = to_value(&netlist);
+        assert_eq!(value["vendor"]["tool"], "x");
+        assert_eq!(value["modules"]["top"]["cells"]["u"]["sidecar"], true);
+        assert!(value["modules"]["top"]["cells"]["u"].get("timing").is_none());
+        assert_eq!(value["modules"]["top"]["cells"]["u"]["nextpnr_bel"], "X1/Y2");
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let mut shuffled = netlist.clone();
+        shuffled.modules.reverse();
+        shuffled.modules.values_mut().for_each(|module| {
+            module.cells.reverse();
+            module.nets.reverse();
+        });
+        netlist.canonicalize();
+        shuffled.canonicalize();
+        assert_eq!(netlist.to_string_pretty().unwrap(), shuffled.to_string_pretty().unwrap());
+        assert!(netlist.modules.keys().zip(netlist.modules.keys().skip(1)).all(|(a, b)| a < b));
+        assert_eq!(Netlist::from_str(&netlist.to_string_pretty().unwrap()).unwrap(), netlist);
+    }
+
     #[test]
     fn test_circuts() {
         for circut in std::fs::read_dir("testdata").unwrap() {
@@ -297,23 +731,22 @@ mod tests {
                 assert!(! module.nets.is_empty()); // All tests should have some nets
                 // assert!(! module.memories.is_empty()); // We have test without mmeories
                 assert!(! module.ports.is_empty()); // All tests should have some ports
-
-                for (cell_name, cell) in module.cells.iter() {
-                    println!("Checking {:?} cell: {:?}", cell_name, cell.module);
-                    assert!(cell.extra.is_empty());
-                }
-                for (net_name, net) in module.nets.iter() {
-                    println!("Checking {:?} net: {:?}", net_name, net.bits);
-                    assert!(net.extra.is_empty());
-                }
-                for (mem_name, mem) in module.memories.iter() {
-                    println!("Checking {:?} memory", mem_name);
-                    assert!(mem.extra.is_empty());
-                }
-                for (port_name, port) in module.ports.iter() {
-                    println!("Checking {:?} port: {:?}", port_name, port.bits);
-                    assert!(port.extra.is_empty());
-                }
+            }
+            for cell in netlist.iter_all_cells() {
+                println!("Checking {:?} cell: {:?}", cell.name, cell.object.module);
+                assert!(cell.object.extra.is_empty());
+            }
+            for net in netlist.iter_all_nets() {
+                println!("Checking {:?} net: {:?}", net.name, net.object.bits);
+                assert!(net.object.extra.is_empty());
+            }
+            for mem in netlist.iter_all_memories() {
+                println!("Checking {:?} memory", mem.name);
+                assert!(mem.object.extra.is_empty());
+            }
+            for port in netlist.iter_all_ports() {
+                println!("Checking {:?} port: {:?}", port.name, port.object.bits);
+                assert!(port.object.extra.is_empty());
             }
         }
     }
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// A flag shared between an operation and whoever may want to stop it, such as the user
+/// interface thread of an interactive tool. Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks every operation holding the token to stop at its next check.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Bounds on a long-running operation, checked cooperatively: solvers between conflicts and
+/// decisions, proofs before every unrolled cycle, pass managers before every pass. Everything
+/// is unbounded by default.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub cancel: CancelToken,
+    pub max_runtime: Option<Duration>,
+    /// Largest estimate of the memory held by a solver, in bytes.
+    pub max_memory: Option<usize>,
+    /// Largest number of cells in the logic a proof has to model.
+    pub max_cone: Option<usize>,
+}
+
+impl Limits {
+    /// Starts counting the runtime.
+    pub fn start(&self) -> Budget {
+        Budget { limits: self.clone(), deadline: self.max_runtime.map(|runtime| Instant::now() + runtime) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Cancelled,
+    Runtime(Duration),
+    Memory { estimate: usize, limit: usize },
+    Cone { size: usize, limit: usize },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Runtime(runtime) => write!(f, "ran longer than {:.3} s", runtime.as_secs_f64()),
+            Self::Memory { estimate, limit } => write!(f, "needs about {} bytes, more than the limit of {}", estimate, limit),
+            Self::Cone { size, limit } => write!(f, "cone of {} cells is larger than the limit of {}", size, limit),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// [`Limits`] of an operation in progress.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    limits: Limits,
+    deadline: Option<Instant>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Limits::default().start()
+    }
+}
+
+impl Budget {
+    /// Fails once cancelled or out of time.
+    pub fn check(&self) -> Result<(), LimitExceeded> {
+        if self.limits.cancel.is_cancelled() {
+            return Err(LimitExceeded::Cancelled);
+        }
+        match (self.deadline, self.limits.max_runtime) {
+            (Some(deadline), Some(runtime)) if Instant::now() > deadline => Err(LimitExceeded::Runtime(runtime)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_memory(&self, estimate: usize) -> Result<(), LimitExceeded> {
+        match self.limits.max_memory {
+            Some(limit) if estimate > limit => Err(LimitExceeded::Memory { estimate, limit }),
+            _ => self.check(),
+        }
+    }
+
+    pub fn check_cone(&self, size: usize) -> Result<(), LimitExceeded> {
+        match self.limits.max_cone {
+            Some(limit) if size > limit => Err(LimitExceeded::Cone { size, limit }),
+            _ => self.check(),
+        }
+    }
+
+    /// The limits left for a part of the operation, with the runtime that remains.
+    pub fn remaining(&self) -> Limits {
+        let mut limits = self.limits.clone();
+        if let Some(deadline) = self.deadline {
+            limits.max_runtime = Some(deadline.saturating_duration_since(Instant::now()));
+        }
+        limits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let limits = Limits { max_memory: Some(100), max_cone: Some(10), ..Limits::default() };
+        let budget = limits.start();
+        assert_eq!(budget.check_memory(100), Ok(()));
+        assert_eq!(budget.check_memory(101), Err(LimitExceeded::Memory { estimate: 101, limit: 100 }));
+        assert_eq!(budget.check_cone(11), Err(LimitExceeded::Cone { size: 11, limit: 10 }));
+        limits.cancel.cancel();
+        assert_eq!(budget.check(), Err(LimitExceeded::Cancelled));
+
+        let budget = Limits { max_runtime: Some(Duration::ZERO), ..Limits::default() }.start();
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(budget.check(), Err(LimitExceeded::Runtime(Duration::ZERO)));
+        assert_eq!(budget.remaining().max_runtime, Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::formal::{FormalError, ProofResult, ProveOptions};
+use crate::progress::pass_span;
+use crate::rng::{SeedPolicy, SplitMix64};
+use crate::{Bit, Cell, Direction, IdString, Module, Net, Port};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockStyle {
+    /// XOR gates for key bits 0 and XNOR gates for key bits 1.
+    #[default]
+    Xor,
+    /// Multiplexers choosing between the locked signal and a decoy primary input.
+    Mux,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    UnknownNet(String),
+    PortExists(String),
+    UnknownCell(String),
+    NoDecoys,
+    /// A key value that is not one `0` or `1` per bit of the key port.
+    InvalidKey(String),
+    /// A cell port on a locked bit whose direction is unknown, so it may be a load or a driver.
+    UnknownDirection {
+        cell: String,
+        port: String,
+    },
+    Formal(FormalError),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownNet(net) => write!(f, "unknown net {:?}", net),
+            Self::PortExists(port) => write!(f, "port {:?} already exists", port),
+            Self::UnknownCell(cell) => write!(f, "unknown key gate {:?}", cell),
+            Self::NoDecoys => write!(f, "mux locking needs at least one primary input as decoy"),
+            Self::InvalidKey(value) => write!(f, "invalid key value {:?}", value),
+            Self::UnknownDirection { cell, port } => write!(f, "unknown direction of port {:?} of cell {:?}", port, cell),
+            Self::Formal(error) => write!(f, "key check failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+impl From<FormalError> for LockError {
+    fn from(error: FormalError) -> Self {
+        Self::Formal(error)
+    }
+}
+
+/// The secret produced by [`Module::lock`]: the key port, its correct value, and the key gates
+/// it controls. Serialize it to keep it as a key file next to the locked netlist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockKey {
+    pub style: LockStyle,
+    pub port: String,
+    /// Correct key value, one `0` or `1` per key bit, LSB first.
+    pub key: String,
+    /// Key gate of every key bit.
+    pub gates: Vec<String>,
+}
+
+impl LockKey {
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    pub fn bits(&self) -> impl Iterator<Item = bool> + '_ {
+        self.key.chars().map(|c| c == '1')
+    }
+}
+
+fn gate(module: &str, connections: &[(&str, Direction, Bit)]) -> Cell {
+    let mut cell = connections
+        .iter()
+        .fold(Cell::new(module), |cell, (port, direction, bit)| cell.with_connection(port, direction.clone(), vec![*bit]));
+    cell.hide_name = true;
+    cell
+}
+
+impl Module {
+    /// A cell port on one of `bits` whose direction is neither declared nor given by the cell
+    /// type, which [`Module::move_loads`] cannot tell apart from a driver.
+    pub(crate) fn undirected_port(&self, bits: &HashSet<Bit>) -> Option<(String, String)> {
+        self.cells.iter().find_map(|(name, cell)| {
+            let (port, _) = (cell.connections.iter())
+                .find(|(port, connected)| cell.port_direction(port).is_none() && connected.iter().any(|bit| bits.contains(bit)))?;
+            Some((name.to_string(), port.to_string()))
+        })
+    }
+
+    /// Moves every load of `bit` (cell inputs and output ports) over to `load`. Ports of
+    /// unknown direction are left alone, so check for them with [`Module::undirected_port`].
+    pub(crate) fn move_loads(&mut self, bit: Bit, load: Bit) {
+        for cell in self.cells.values_mut() {
+            let loads: Vec<IdString> = (cell.connections.iter())
+                .filter(|(port, bits)| bits.contains(&bit) && cell.port_direction(port) == Some(Direction::Input))
+                .map(|(port, _)| port.clone())
+                .collect();
+            for port in loads {
+                cell.connections[&port].iter_mut().filter(|b| **b == bit).for_each(|b| *b = load);
+            }
+        }
+        for port in self.ports.values_mut().filter(|port| port.direction == Direction::Output) {
+            port.bits.iter_mut().filter(|b| **b == bit).for_each(|b| *b = load);
+        }
+    }
+
+    /// Inserts a key gate on every bit of `nets`, controlled by a new input port `port`. The
+    /// correct key is drawn from `seed` under `policy`. Bits shared by several nets are locked once.
+    pub fn lock(&mut self, nets: &[&str], style: LockStyle, port: &str, seed: u64, policy: SeedPolicy) -> Result<LockKey, LockError> {
+        pass_span!("lock", cells = self.cells.len(), nets = self.nets.len(), locked = nets.len(), seed);
+        if self.ports.contains_key(port) || self.nets.contains_key(port) {
+            return Err(LockError::PortExists(port.to_string()));
+        }
+        let mut locked = Vec::new();
+        let mut seen = HashSet::new();
+        for net in nets {
+            let net = self.nets.get(*net).ok_or_else(|| LockError::UnknownNet(net.to_string()))?;
+            locked.extend(net.bits.iter().filter(|bit| matches!(bit, Bit::Signal(_)) && seen.insert(**bit)));
+        }
+        if let Some((cell, port)) = self.undirected_port(&seen) {
+            return Err(LockError::UnknownDirection { cell, port });
+        }
+        let decoys: Vec<Bit> = self
+            .ports
+            .values()
+            .filter(|port| port.direction == Direction::Input)
+            .flat_map(|port| port.bits.iter().copied())
+            .filter(|bit| matches!(bit, Bit::Signal(_)))
+            .collect();
+        if style == LockStyle::Mux && decoys.is_empty() {
+            return Err(LockError::NoDecoys);
+        }
+
+        let mut rng = SplitMix64::from_policy(policy, seed);
+        let fresh = self.fresh_bits(2 * locked.len());
+        let (key_bits, outputs) = fresh.split_at(locked.len());
+        let mut key = LockKey { style, port: port.to_string(), key: String::new(), gates: Vec::new() };
+        for (index, ((bit, key_bit), output)) in locked.into_iter().zip(key_bits.iter().copied()).zip(outputs.iter().copied()).enumerate() {
+            self.move_loads(bit, output);
+
+            let value = rng.next_bool();
+            let cell = match style {
+                LockStyle::Xor => gate(
+                    if value { "$_XNOR_" } else { "$_XOR_" },
+                    &[("A", Direction::Input, bit), ("B", Direction::Input, key_bit), ("Y", Direction::Output, output)],
+                ),
+                LockStyle::Mux => {
+                    let decoy = decoys[rng.below(decoys.len())];
+                    let (a, b) = if value { (decoy, bit) } else { (bit, decoy) };
+                    gate(
+                        "$_MUX_",
+                        &[
+                            ("A", Direction::Input, a),
+                            ("B", Direction::Input, b),
+                            ("S", Direction::Input, key_bit),
+                            ("Y", Direction::Output, output),
+                        ],
+                    )
+                }
+            };
+            let mut name = format!("$lock${}", index);
+            while self.cells.contains_key(&name) {
+                name.push('_');
+            }
+            self.cells.insert(name.as_str().into(), cell);
+            key.key.push(if value { '1' } else { '0' });
+            key.gates.push(name);
+        }
+        self.ports.insert(port.into(), Port::new(Direction::Input, key_bits.to_vec()));
+        self.nets.insert(port.into(), Net::new(key_bits.to_vec()));
+        Ok(key)
+    }
+
+    /// Ties the key port to `value` and folds the key gates away. With the correct key the module
+    /// is identical to the one before [`Module::lock`], with a wrong key inverters or decoy
+    /// connections remain. The value needs one `0` or `1` per bit of the key port, LSB first.
+    pub fn unlock(&mut self, key: &LockKey, value: &str) -> Result<(), LockError> {
+        pass_span!("unlock", cells = self.cells.len(), nets = self.nets.len(), port = key.port.as_str());
+        let width = self.ports.get(&key.port).ok_or_else(|| LockError::UnknownNet(key.port.clone()))?.bits.len();
+        if value.len() != width || !value.chars().all(|c| c == '0' || c == '1') {
+            return Err(LockError::InvalidKey(value.to_string()));
+        }
+        let port = self.ports.shift_remove(&key.port).unwrap();
+        self.nets.shift_remove(&key.port);
+        let constants: HashMap<Bit, Bit> =
+            port.bits.iter().zip(value.chars()).map(|(bit, value)| (*bit, if value == '1' { Bit::_1 } else { Bit::_0 })).collect();
+        self.substitute(&constants);
+
+        let mut merged = HashMap::new();
+        for name in key.gates.iter() {
+            let mut cell = self.cells.shift_remove(name).ok_or_else(|| LockError::UnknownCell(name.clone()))?;
+            let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied().unwrap_or(Bit::X);
+            let (a, b, y) = (bit("A"), bit("B"), bit("Y"));
+            let source = match (cell.module.as_str(), b, bit("S")) {
+                ("$_XOR_", Bit::_0, _) | ("$_XNOR_", Bit::_1, _) | ("$_MUX_", _, Bit::_0) => Some(a),
+                ("$_MUX_", _, Bit::_1) => Some(b),
+                _ => None,
+            };
+            match source {
+                Some(source) => {
+                    merged.insert(y, source);
+                }
+                None => {
+                    // A wrong key bit leaves the signal inverted or disconnected, keep the gate.
+                    if cell.module != "$_MUX_" {
+                        cell.module = "$_NOT_".into();
+                        cell.connections.shift_remove("B");
+                        cell.port_directions.shift_remove("B");
+                    }
+                    self.cells.insert(name.into(), cell);
+                }
+            }
+        }
+        // A gate may feed another one, so follow every output to the signal at the end of the chain.
+        let resolved: HashMap<Bit, Bit> = merged
+            .keys()
+            .map(|output| {
+                let mut source = merged[output];
+                for _ in 0..merged.len() {
+                    match merged.get(&source) {
+                        Some(next) => source = *next,
+                        None => break,
+                    }
+                }
+                (*output, source)
+            })
+            .collect();
+        self.substitute(&resolved);
+        Ok(())
+    }
+
+    /// Unlocks a copy of `self` with `value` and proves it equivalent to `original` with
+    /// [`Module::check_equivalence`]. Anything short of a proof counts as a wrong key.
+    pub fn verify_key(&self, original: &Module, key: &LockKey, value: &str, options: &ProveOptions) -> Result<bool, LockError> {
+        let mut unlocked = self.clone();
+        unlocked.unlock(key, value)?;
+        Ok(matches!(unlocked.check_equivalence(original, options)?.result, ProofResult::Proven(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    fn adder() -> Module {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        netlist.modules["adder"].clone()
+    }
+
+    #[test]
+    fn test_lock_xor() {
+        let original = adder();
+        let mut locked = original.clone();
+        let key = locked.lock(&["c"], LockStyle::Xor, "key", 42, SeedPolicy::Explicit).unwrap();
+        let options = ProveOptions::default();
+        assert_eq!(key.key.len(), 17);
+        assert_eq!(locked.ports["key"].bits.len(), 17);
+        assert_eq!(locked.cells.len(), original.cells.len() + 17);
+        assert_ne!(locked.ports["c"].bits, original.ports["c"].bits);
+
+        assert!(locked.verify_key(&original, &key, &key.key, &options).unwrap());
+        let wrong: String = key.bits().map(|bit| if bit { '0' } else { '1' }).collect();
+        assert!(!locked.verify_key(&original, &key, &wrong, &options).unwrap());
+        assert_eq!(locked.verify_key(&original, &key, "01", &options), Err(LockError::InvalidKey("01".to_string())));
+        let bad: String = key.key.replacen('1', "x", 1);
+        assert_eq!(locked.clone().unlock(&key, &bad), Err(LockError::InvalidKey(bad.clone())));
+    }
+
+    #[test]
+    fn test_lock_aliased_nets() {
+        let original = adder();
+        let mut aliased = original.clone();
+        let alias = aliased.nets["c"].clone();
+        aliased.nets.insert("c_alias".into(), alias);
+        let mut locked = aliased.clone();
+        let key = locked.lock(&["c", "c_alias", "c"], LockStyle::Xor, "key", 42, SeedPolicy::Explicit).unwrap();
+        assert_eq!(key.key.len(), 17);
+        let options = ProveOptions::default();
+        assert!(locked.verify_key(&aliased, &key, &key.key, &options).unwrap());
+
+        // Gates chained by hand, the second one reading the output of the first.
+        let mut chained = original.clone();
+        let first = chained.lock(&["c"], LockStyle::Xor, "k0", 1, SeedPolicy::Explicit).unwrap();
+        let second = chained.lock(&["c"], LockStyle::Xor, "k1", 2, SeedPolicy::Explicit).unwrap();
+        let merged = LockKey {
+            style: LockStyle::Xor,
+            port: "key".to_string(),
+            key: format!("{}{}", first.key, second.key),
+            gates: first.gates.iter().chain(second.gates.iter()).cloned().collect(),
+        };
+        let mut bits = chained.ports.shift_remove("k0").unwrap().bits;
+        bits.extend(chained.ports.shift_remove("k1").unwrap().bits);
+        chained.nets.shift_remove("k0");
+        chained.nets.shift_remove("k1");
+        chained.ports.insert("key".into(), Port::new(Direction::Input, bits));
+        assert!(chained.verify_key(&original, &merged, &merged.key, &options).unwrap());
+    }
+
+    #[test]
+    fn test_lock_without_directions() {
+        let mut module = Module::new();
+        module.ports.insert("a".into(), Port::new(Direction::Input, vec![Bit::Signal(2)]));
+        module.ports.insert("y".into(), Port::new(Direction::Output, vec![Bit::Signal(4)]));
+        module.nets.insert("n".into(), Net::new(vec![Bit::Signal(3)]));
+        let mut and = Cell::new("$_AND_");
+        let mut not = Cell::new("$_NOT_");
+        and.connections.extend([("A".into(), vec![Bit::Signal(2)]), ("B".into(), vec![Bit::Signal(2)]), ("Y".into(), vec![Bit::Signal(3)])]);
+        not.connections.extend([("A".into(), vec![Bit::Signal(3)]), ("Y".into(), vec![Bit::Signal(4)])]);
+        module.cells.insert("and".into(), and);
+        module.cells.insert("not".into(), not);
+
+        // Gate directions follow from their type.
+        let mut locked = module.clone();
+        let key = locked.lock(&["n"], LockStyle::Xor, "key", 1, SeedPolicy::Explicit).unwrap();
+        let gate = &locked.cells[key.gates[0].as_str()];
+        assert_eq!(locked.cells["and"].connections["Y"], [Bit::Signal(3)]);
+        assert_eq!(locked.cells["not"].connections["A"], gate.connections["Y"]);
+
+        // Undeclared ports of user cells may be drivers as well as loads.
+        let mut sub = Cell::new("sub");
+        sub.connections.insert("I".into(), vec![Bit::Signal(3)]);
+        module.cells.insert("u_sub".into(), sub);
+        let error = LockError::UnknownDirection { cell: "u_sub".to_string(), port: "I".to_string() };
+        assert_eq!(module.clone().lock(&["n"], LockStyle::Xor, "key", 1, SeedPolicy::Explicit), Err(error));
+    }
+
+    #[test]
+    fn test_lock_mux() {
+        let original = adder();
+        let mut locked = original.clone();
+        let key = locked.lock(&["c"], LockStyle::Mux, "key", 7, SeedPolicy::Explicit).unwrap();
+        let mut file = Vec::new();
+        key.to_writer(&mut file).unwrap();
+        let key = LockKey::from_reader(file.as_slice()).unwrap();
+        let options = ProveOptions::default();
+        assert!(locked.verify_key(&original, &key, &key.key, &options).unwrap());
+        assert_eq!(locked.lock(&["a"], LockStyle::Mux, "key", 7, SeedPolicy::Explicit), Err(LockError::PortExists("key".to_string())));
+    }
+}
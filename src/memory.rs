@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::builder::constant;
+use crate::param::ParamValue;
+use crate::{Bit, Cell, Direction};
+
+/// Memory cells holding their contents in an `INIT` parameter.
+pub(crate) const MEMORY_CELLS: &[&str] = &["$mem", "$mem_v2"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemContentsError {
+    /// A word or address in a hex file is not a hexadecimal number.
+    InvalidToken(String),
+    AddressOutOfRange(usize),
+}
+
+impl fmt::Display for MemContentsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidToken(token) => write!(f, "invalid hex token {:?}", token),
+            Self::AddressOutOfRange(address) => write!(f, "address {:#x} is outside the memory", address),
+        }
+    }
+}
+
+impl std::error::Error for MemContentsError {}
+
+/// Contents of a memory of `depth` words of `width` bits. Words are little endian limbs of 64
+/// bits, so any width fits. Words never written hold the default, which is undefined unless
+/// set, so large sparse memories stay small.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemContents {
+    width: usize,
+    depth: usize,
+    default: Option<Vec<u64>>,
+    words: BTreeMap<usize, Vec<u64>>,
+}
+
+fn limbs(width: usize) -> usize {
+    width.div_ceil(64)
+}
+
+impl MemContents {
+    pub fn new(width: usize, depth: usize) -> Self {
+        Self { width, depth, default: None, words: BTreeMap::new() }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Truncates or zero extends a word to the width of the memory.
+    fn fit(&self, word: &[u64]) -> Vec<u64> {
+        let mut word: Vec<u64> = word.iter().copied().chain(std::iter::repeat(0)).take(limbs(self.width)).collect();
+        if let Some(last) = word.last_mut()
+            && !self.width.is_multiple_of(64)
+        {
+            *last &= (1 << (self.width % 64)) - 1;
+        }
+        word
+    }
+
+    pub fn default_word(&self) -> Option<&[u64]> {
+        self.default.as_deref()
+    }
+
+    /// Sets the value of every word not written explicitly, `None` for undefined.
+    pub fn set_default(&mut self, word: Option<&[u64]>) {
+        self.default = word.map(|word| self.fit(word));
+    }
+
+    /// The word at `address`, `None` if it is undefined or out of range.
+    pub fn get(&self, address: usize) -> Option<&[u64]> {
+        match address < self.depth {
+            true => self.words.get(&address).or(self.default.as_ref()).map(Vec::as_slice),
+            false => None,
+        }
+    }
+
+    pub fn get_u64(&self, address: usize) -> Option<u64> {
+        self.get(address).map(|word| word.first().copied().unwrap_or(0))
+    }
+
+    pub fn set(&mut self, address: usize, word: &[u64]) -> Result<(), MemContentsError> {
+        if address >= self.depth {
+            return Err(MemContentsError::AddressOutOfRange(address));
+        }
+        self.words.insert(address, self.fit(word));
+        Ok(())
+    }
+
+    pub fn set_u64(&mut self, address: usize, word: u64) -> Result<(), MemContentsError> {
+        self.set(address, &[word])
+    }
+
+    /// Makes the word at `address` the default again.
+    pub fn clear(&mut self, address: usize) {
+        self.words.remove(&address);
+    }
+
+    /// Words written explicitly, lowest address first.
+    pub fn words(&self) -> impl Iterator<Item = (usize, &[u64])> {
+        self.words.iter().map(|(address, word)| (*address, word.as_slice()))
+    }
+
+    /// Bits of the word at `address`, least significant first, `None` for undefined bits.
+    pub fn bits(&self, address: usize) -> Vec<Option<bool>> {
+        match self.get(address) {
+            Some(word) => (0..self.width).map(|bit| Some(word[bit / 64] >> (bit % 64) & 1 == 1)).collect(),
+            None => vec![None; self.width],
+        }
+    }
+
+    pub(crate) fn set_bits(&mut self, address: usize, bits: &[Option<bool>]) {
+        if bits.iter().all(Option::is_none) {
+            self.clear(address);
+            return;
+        }
+        let mut word = vec![0; limbs(self.width)];
+        for (bit, _) in bits.iter().enumerate().filter(|(_, value)| **value == Some(true)) {
+            word[bit / 64] |= 1 << (bit % 64);
+        }
+        self.words.insert(address, word);
+    }
+
+    /// Decodes an `INIT` parameter: a bit string with the last word first, or a number.
+    /// Undefined bits of a partly defined word read as 0.
+    pub fn from_init(init: &Value, width: usize, depth: usize) -> Option<Self> {
+        let mut contents = Self::new(width, depth);
+        let bits: Vec<Option<bool>> = match init {
+            Value::String(digits) => digits
+                .chars()
+                .rev()
+                .map(|digit| match digit {
+                    '0' => Some(Some(false)),
+                    '1' => Some(Some(true)),
+                    'x' | 'X' | 'z' | 'Z' | '-' => Some(None),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?,
+            Value::Number(number) => {
+                let number = number.as_u64()?;
+                (0..64).map(|bit| Some(number >> bit & 1 == 1)).collect()
+            }
+            _ => return None,
+        };
+        for (address, word) in bits.chunks(width.max(1)).take(depth).enumerate() {
+            let mut word = word.to_vec();
+            word.resize(width, Some(false));
+            contents.set_bits(address, &word);
+        }
+        Some(contents)
+    }
+
+    /// Encodes the contents as an `INIT` parameter, undefined words as `x` bits.
+    pub fn to_init(&self) -> Value {
+        let mut init = String::with_capacity(self.width * self.depth);
+        for address in (0..self.depth).rev() {
+            init.extend(self.bits(address).iter().rev().map(|bit| match bit {
+                Some(false) => '0',
+                Some(true) => '1',
+                None => 'x',
+            }));
+        }
+        Value::String(init)
+    }
+
+    /// Reads words in the `$readmemh` format: whitespace separated hex words, `@address`
+    /// directives and `//` comments. Words made of `x` digits stay undefined.
+    pub fn read_hex(&mut self, text: &str) -> Result<(), MemContentsError> {
+        let mut address = 0;
+        for line in text.lines() {
+            let line = line.split("//").next().unwrap();
+            for token in line.split_whitespace() {
+                let invalid = || MemContentsError::InvalidToken(token.to_string());
+                if let Some(target) = token.strip_prefix('@') {
+                    address = usize::from_str_radix(target, 16).map_err(|_| invalid())?;
+                    continue;
+                }
+                let digits: Vec<char> = token.chars().filter(|c| *c != '_').collect();
+                if digits.iter().all(|c| c.eq_ignore_ascii_case(&'x')) {
+                    if address >= self.depth {
+                        return Err(MemContentsError::AddressOutOfRange(address));
+                    }
+                    self.clear(address);
+                } else {
+                    let mut word = vec![0; limbs(self.width).max(limbs(digits.len() * 4))];
+                    for (index, digit) in digits.iter().rev().enumerate() {
+                        word[index * 4 / 64] |= (digit.to_digit(16).ok_or_else(invalid)? as u64) << (index * 4 % 64);
+                    }
+                    self.set(address, &word)?;
+                }
+                address += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the explicit words in the `$readmemh` format, with an `@address` directive
+    /// before every gap.
+    pub fn write_hex(&self) -> String {
+        let digits = self.width.div_ceil(4).max(1);
+        let mut text = String::new();
+        let mut next = 0;
+        for (address, word) in self.words() {
+            if address != next {
+                text.push_str(&format!("@{:x}\n", address));
+            }
+            let hex: String = word.iter().rev().map(|limb| format!("{:016x}", limb)).collect();
+            text.push_str(&hex[hex.len() - digits..]);
+            text.push('\n');
+            next = address + 1;
+        }
+        text
+    }
+}
+
+pub(crate) fn parameter(cell: &Cell, name: &str) -> Option<usize> {
+    cell.param(name)?.as_u64().map(|value| value as usize)
+}
+
+impl Cell {
+    /// Initial contents of a `$mem` or `$mem_v2` cell.
+    pub fn memory_contents(&self) -> Option<MemContents> {
+        if !MEMORY_CELLS.contains(&self.module.as_str()) {
+            return None;
+        }
+        let (width, depth) = (parameter(self, "WIDTH")?, parameter(self, "SIZE")?);
+        match self.parameters.get("INIT") {
+            Some(init) => MemContents::from_init(init, width, depth),
+            None => Some(MemContents::new(width, depth)),
+        }
+    }
+
+    /// Stores `contents` in the `INIT` parameter of a memory cell.
+    pub fn set_memory_contents(&mut self, contents: &MemContents) {
+        self.parameters.insert("INIT".to_string(), contents.to_init());
+    }
+}
+
+/// A read port of a `$mem_v2` cell. Single bit signals unused by the port are `x`, values
+/// are least significant bit first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemReadPort {
+    pub clocked: bool,
+    pub clock_polarity: bool,
+    pub clock: Bit,
+    pub enable: Bit,
+    pub arst: Bit,
+    pub srst: Bit,
+    pub address: Vec<Bit>,
+    pub data: Vec<Bit>,
+    /// Whether the port sees the data of each write port written on the same edge.
+    pub transparent: Vec<bool>,
+    /// Whether reads colliding with each write port return `x`.
+    pub collision_x: Vec<bool>,
+    /// Whether the port continues the previous one as part of a wide port.
+    pub wide_continuation: bool,
+    /// Whether the synchronous reset only acts while the port is enabled.
+    pub ce_over_srst: bool,
+    pub arst_value: Vec<Bit>,
+    pub srst_value: Vec<Bit>,
+    pub init_value: Vec<Bit>,
+}
+
+/// A write port of a `$mem_v2` cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemWritePort {
+    pub clocked: bool,
+    pub clock_polarity: bool,
+    pub clock: Bit,
+    /// One enable per data bit.
+    pub enable: Vec<Bit>,
+    pub address: Vec<Bit>,
+    pub data: Vec<Bit>,
+    /// Whether this port wins over each write port to the same address on the same edge.
+    pub priority: Vec<bool>,
+    pub wide_continuation: bool,
+}
+
+/// The ports, geometry and contents a `$mem_v2` cell packs into its parameters and
+/// connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryCell {
+    pub memid: String,
+    pub width: usize,
+    pub size: usize,
+    pub offset: i64,
+    pub abits: usize,
+    pub init: MemContents,
+    pub read_ports: Vec<MemReadPort>,
+    pub write_ports: Vec<MemWritePort>,
+}
+
+/// `count` fields of `width` bits packed into a parameter, `x` where it is missing.
+fn fields(cell: &Cell, name: &str, count: usize, width: usize) -> Vec<Vec<Bit>> {
+    let mut bits = match cell.param(name) {
+        Some(ParamValue::BitVector(bits)) => bits,
+        Some(ParamValue::Int(value)) => constant(value as u64, count * width),
+        _ => vec![Bit::X; count * width],
+    };
+    bits.resize(count * width, Bit::_0);
+    (0..count).map(|index| bits[index * width..(index + 1) * width].to_vec()).collect()
+}
+
+fn flags(cell: &Cell, name: &str, count: usize) -> Vec<bool> {
+    fields(cell, name, count, 1).iter().map(|bits| bits == &[Bit::_1]).collect()
+}
+
+fn pack_flags(flags: impl IntoIterator<Item = bool>) -> Value {
+    Value::from(ParamValue::BitVector(flags.into_iter().map(|flag| if flag { Bit::_1 } else { Bit::_0 }).collect()))
+}
+
+fn pack_int(value: i64) -> Value {
+    Value::from(ParamValue::BitVector(constant(value as u64, 32)))
+}
+
+impl Cell {
+    /// Decodes a `$mem_v2` cell, `None` for other cells or where parameters are missing or
+    /// connections do not match them.
+    pub fn as_memory(&self) -> Option<MemoryCell> {
+        if self.module != "$mem_v2" {
+            return None;
+        }
+        let (width, size, abits) = (parameter(self, "WIDTH")?, parameter(self, "SIZE")?, parameter(self, "ABITS")?);
+        let (reads, writes) = (parameter(self, "RD_PORTS")?, parameter(self, "WR_PORTS")?);
+        let port = |name: &str, count: usize, width: usize| -> Option<Vec<Vec<Bit>>> {
+            let bits = self.connections.get(name)?;
+            (bits.len() == count * width).then(|| (0..count).map(|index| bits[index * width..(index + 1) * width].to_vec()).collect())
+        };
+        let bit = |name: &str, count: usize| port(name, count, 1).map(|bits| bits.concat()).unwrap_or_else(|| vec![Bit::X; count]);
+
+        let (clocked, polarity) = (flags(self, "RD_CLK_ENABLE", reads), flags(self, "RD_CLK_POLARITY", reads));
+        let (transparent, collision_x) =
+            (flags(self, "RD_TRANSPARENCY_MASK", reads * writes), flags(self, "RD_COLLISION_X_MASK", reads * writes));
+        let (wide, ce_over_srst) = (flags(self, "RD_WIDE_CONTINUATION", reads), flags(self, "RD_CE_OVER_SRST", reads));
+        let (clock, enable, arst, srst) = (bit("RD_CLK", reads), bit("RD_EN", reads), bit("RD_ARST", reads), bit("RD_SRST", reads));
+        let values = ["RD_ARST_VALUE", "RD_SRST_VALUE", "RD_INIT_VALUE"].map(|name| fields(self, name, reads, width));
+        let (addresses, data) = (port("RD_ADDR", reads, abits)?, port("RD_DATA", reads, width)?);
+        let read_ports = (0..reads)
+            .map(|index| MemReadPort {
+                clocked: clocked[index],
+                clock_polarity: polarity[index],
+                clock: clock[index],
+                enable: enable[index],
+                arst: arst[index],
+                srst: srst[index],
+                address: addresses[index].clone(),
+                data: data[index].clone(),
+                transparent: transparent[index * writes..(index + 1) * writes].to_vec(),
+                collision_x: collision_x[index * writes..(index + 1) * writes].to_vec(),
+                wide_continuation: wide[index],
+                ce_over_srst: ce_over_srst[index],
+                arst_value: values[0][index].clone(),
+                srst_value: values[1][index].clone(),
+                init_value: values[2][index].clone(),
+            })
+            .collect();
+
+        let (clocked, polarity) = (flags(self, "WR_CLK_ENABLE", writes), flags(self, "WR_CLK_POLARITY", writes));
+        let (priority, wide) = (flags(self, "WR_PRIORITY_MASK", writes * writes), flags(self, "WR_WIDE_CONTINUATION", writes));
+        let clock = bit("WR_CLK", writes);
+        let (enables, addresses, data) = (port("WR_EN", writes, width)?, port("WR_ADDR", writes, abits)?, port("WR_DATA", writes, width)?);
+        let write_ports = (0..writes)
+            .map(|index| MemWritePort {
+                clocked: clocked[index],
+                clock_polarity: polarity[index],
+                clock: clock[index],
+                enable: enables[index].clone(),
+                address: addresses[index].clone(),
+                data: data[index].clone(),
+                priority: priority[index * writes..(index + 1) * writes].to_vec(),
+                wide_continuation: wide[index],
+            })
+            .collect();
+
+        Some(MemoryCell {
+            memid: self.param("MEMID").and_then(|memid| memid.as_str().map(str::to_string)).unwrap_or_default(),
+            width,
+            size,
+            offset: self.param("OFFSET").and_then(|offset| offset.as_i64()).unwrap_or(0),
+            abits,
+            init: self.memory_contents()?,
+            read_ports,
+            write_ports,
+        })
+    }
+
+    /// Makes the cell the `$mem_v2` cell `memory` describes, encoding its parameters the way
+    /// Yosys writes them. Attributes are kept.
+    pub fn set_memory(&mut self, memory: &MemoryCell) {
+        let (reads, writes) = (&memory.read_ports, &memory.write_ports);
+        self.module = "$mem_v2".into();
+        self.parameters.clear();
+        let mut parameter = |name: &str, value: Value| _ = self.parameters.insert(name.to_string(), value);
+        parameter("MEMID", ParamValue::String(memory.memid.clone()).into());
+        parameter("SIZE", pack_int(memory.size as i64));
+        parameter("OFFSET", pack_int(memory.offset));
+        parameter("ABITS", pack_int(memory.abits as i64));
+        parameter("WIDTH", pack_int(memory.width as i64));
+        parameter("INIT", memory.init.to_init());
+        parameter("RD_PORTS", pack_int(reads.len() as i64));
+        parameter("RD_WIDE_CONTINUATION", pack_flags(reads.iter().map(|port| port.wide_continuation)));
+        parameter("RD_CLK_ENABLE", pack_flags(reads.iter().map(|port| port.clocked)));
+        parameter("RD_CLK_POLARITY", pack_flags(reads.iter().map(|port| port.clock_polarity)));
+        parameter("RD_TRANSPARENCY_MASK", pack_flags(reads.iter().flat_map(|port| port.transparent.iter().copied())));
+        parameter("RD_COLLISION_X_MASK", pack_flags(reads.iter().flat_map(|port| port.collision_x.iter().copied())));
+        parameter("RD_CE_OVER_SRST", pack_flags(reads.iter().map(|port| port.ce_over_srst)));
+        for (name, value) in [
+            ("RD_ARST_VALUE", reads.iter().flat_map(|port| port.arst_value.iter()).copied().collect()),
+            ("RD_SRST_VALUE", reads.iter().flat_map(|port| port.srst_value.iter()).copied().collect()),
+            ("RD_INIT_VALUE", reads.iter().flat_map(|port| port.init_value.iter()).copied().collect()),
+        ] {
+            parameter(name, ParamValue::BitVector(value).into());
+        }
+        parameter("WR_PORTS", pack_int(writes.len() as i64));
+        parameter("WR_WIDE_CONTINUATION", pack_flags(writes.iter().map(|port| port.wide_continuation)));
+        parameter("WR_CLK_ENABLE", pack_flags(writes.iter().map(|port| port.clocked)));
+        parameter("WR_CLK_POLARITY", pack_flags(writes.iter().map(|port| port.clock_polarity)));
+        parameter("WR_PRIORITY_MASK", pack_flags(writes.iter().flat_map(|port| port.priority.iter().copied())));
+
+        self.connections.clear();
+        self.port_directions.clear();
+        let connections: [(&str, Vec<Bit>); 10] = [
+            ("RD_CLK", reads.iter().map(|port| port.clock).collect()),
+            ("RD_EN", reads.iter().map(|port| port.enable).collect()),
+            ("RD_ARST", reads.iter().map(|port| port.arst).collect()),
+            ("RD_SRST", reads.iter().map(|port| port.srst).collect()),
+            ("RD_ADDR", reads.iter().flat_map(|port| port.address.iter()).copied().collect()),
+            ("RD_DATA", reads.iter().flat_map(|port| port.data.iter()).copied().collect()),
+            ("WR_CLK", writes.iter().map(|port| port.clock).collect()),
+            ("WR_EN", writes.iter().flat_map(|port| port.enable.iter()).copied().collect()),
+            ("WR_ADDR", writes.iter().flat_map(|port| port.address.iter()).copied().collect()),
+            ("WR_DATA", writes.iter().flat_map(|port| port.data.iter()).copied().collect()),
+        ];
+        for (name, bits) in connections {
+            let direction = if name == "RD_DATA" { Direction::Output } else { Direction::Input };
+            self.port_directions.insert(name.into(), direction);
+            self.connections.insert(name.into(), bits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_roundtrip() {
+        let contents = MemContents::from_init(&Value::from("xx0110"), 2, 3).unwrap();
+        assert_eq!(contents.get_u64(0), Some(2));
+        assert_eq!(contents.get_u64(1), Some(1));
+        assert_eq!(contents.get(2), None);
+        assert_eq!(contents.to_init(), "xx0110");
+
+        let mut wide = MemContents::new(100, 1 << 20);
+        wide.set_default(Some(&[0]));
+        wide.set(7, &[u64::MAX, u64::MAX]).unwrap();
+        assert_eq!(wide.get(7).unwrap(), [u64::MAX, (1 << 36) - 1]);
+        assert_eq!(wide.get_u64(8), Some(0));
+        assert_eq!(wide.set_u64(1 << 20, 1), Err(MemContentsError::AddressOutOfRange(1 << 20)));
+    }
+
+    #[test]
+    fn test_hex() {
+        let mut contents = MemContents::new(12, 16);
+        contents.read_hex("// header\nabc 1_23\n@8 fff xxx 4 // tail\n").unwrap();
+        assert_eq!(contents.get_u64(1), Some(0x123));
+        assert_eq!(contents.get_u64(8), Some(0xfff));
+        assert_eq!(contents.get(9), None);
+        assert_eq!(contents.get_u64(10), Some(4));
+        assert_eq!(contents.write_hex(), "abc\n123\n@8\nfff\n@a\n004\n");
+        assert_eq!(contents.read_hex("@10 1"), Err(MemContentsError::AddressOutOfRange(16)));
+        assert_eq!(contents.read_hex("g"), Err(MemContentsError::InvalidToken("g".to_string())));
+    }
+
+    #[test]
+    fn test_memory_cell() {
+        let signals = |start: u64, count: u64| (start..start + count).map(Bit::Signal).collect::<Vec<_>>();
+        let cell = Cell::new("$mem_v2")
+            .with_parameter("MEMID", "\\ram")
+            .with_parameter("WIDTH", 2)
+            .with_parameter("SIZE", 4)
+            .with_parameter("ABITS", "00000000000000000000000000000010")
+            .with_parameter("OFFSET", 0)
+            .with_parameter("INIT", "xxxxxx01")
+            .with_parameter("RD_PORTS", 2)
+            .with_parameter("WR_PORTS", 1)
+            .with_parameter("RD_CLK_ENABLE", "10")
+            .with_parameter("RD_CLK_POLARITY", "10")
+            .with_parameter("RD_TRANSPARENCY_MASK", "10")
+            .with_parameter("RD_ARST_VALUE", "xxxx")
+            .with_parameter("RD_INIT_VALUE", "1100")
+            .with_parameter("WR_CLK_ENABLE", "1")
+            .with_parameter("WR_CLK_POLARITY", "0")
+            .with_connection("RD_CLK", Direction::Input, vec![Bit::X, Bit::Signal(2)])
+            .with_connection("RD_EN", Direction::Input, vec![Bit::_1, Bit::Signal(3)])
+            .with_connection("RD_ADDR", Direction::Input, signals(4, 4))
+            .with_connection("RD_DATA", Direction::Output, signals(8, 4))
+            .with_connection("WR_CLK", Direction::Input, vec![Bit::Signal(2)])
+            .with_connection("WR_EN", Direction::Input, signals(12, 2))
+            .with_connection("WR_ADDR", Direction::Input, signals(14, 2))
+            .with_connection("WR_DATA", Direction::Input, signals(16, 2));
+        let mut memory = cell.as_memory().unwrap();
+        assert_eq!((memory.memid.as_str(), memory.abits, memory.init.get_u64(0)), ("\\ram", 2, Some(1)));
+        let [comb, sync] = &memory.read_ports[..] else { panic!() };
+        assert!(!comb.clocked && sync.clocked && sync.clock_polarity && sync.transparent == [true] && comb.transparent == [false]);
+        assert_eq!((comb.arst, &sync.data[..], &sync.init_value[..]), (Bit::X, &signals(10, 2)[..], &[Bit::_1, Bit::_1][..]));
+        assert!(memory.write_ports[0].clocked && !memory.write_ports[0].clock_polarity);
+        assert_eq!(memory.write_ports[0].priority, [false]);
+
+        memory.write_ports[0].clock_polarity = true;
+        memory.init.set_u64(3, 2).unwrap();
+        let mut edited = cell.clone();
+        edited.set_memory(&memory);
+        assert_eq!(edited.as_memory(), Some(memory));
+        assert_eq!(edited.parameters["WR_CLK_POLARITY"], "1");
+        assert_eq!(edited.parameters["INIT"], "10xxxx01");
+        assert_eq!(edited.port_directions["RD_DATA"], Direction::Output);
+        assert_eq!(Cell::new("$mem").as_memory(), None);
+    }
+}
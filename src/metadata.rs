@@ -0,0 +1,132 @@
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{Cell, Memory, Module, Net, Netlist, Port};
+
+/// The attribute key of `key` in the namespace of `tool`, e.g. `x-mytool:placement`.
+pub fn metadata_key(tool: &str, key: &str) -> String {
+    format!("x-{}:{}", tool, key)
+}
+
+fn prefix(tool: &str) -> String {
+    metadata_key(tool, "")
+}
+
+/// Typed metadata of other tools, stored under `x-<tool>:<key>` so tools annotating the same
+/// netlist cannot clash. Payloads are kept as JSON text, since Yosys only reads back string and
+/// number attributes. Objects with attributes keep metadata there, netlists and ports in their
+/// unknown fields.
+pub trait ToolMetadata {
+    fn metadata_map(&self) -> &IndexMap<String, Value>;
+
+    fn metadata_map_mut(&mut self) -> &mut IndexMap<String, Value>;
+
+    /// `None` if the key is not set, an error if the payload does not decode as `T`.
+    fn metadata<T: DeserializeOwned>(&self, tool: &str, key: &str) -> Option<Result<T, serde_json::Error>> {
+        match self.metadata_map().get(&metadata_key(tool, key))? {
+            Value::String(text) => Some(serde_json::from_str(text)),
+            value => Some(T::deserialize(value)),
+        }
+    }
+
+    /// Setting a payload that serializes to `null` removes the key.
+    fn set_metadata<T: Serialize>(&mut self, tool: &str, key: &str, value: &T) -> Result<(), serde_json::Error> {
+        let key = metadata_key(tool, key);
+        match serde_json::to_value(value)? {
+            Value::Null => _ = self.metadata_map_mut().shift_remove(&key),
+            value => _ = self.metadata_map_mut().insert(key, Value::String(value.to_string())),
+        }
+        Ok(())
+    }
+
+    fn remove_metadata(&mut self, tool: &str, key: &str) -> bool {
+        self.metadata_map_mut().shift_remove(&metadata_key(tool, key)).is_some()
+    }
+
+    /// Keys set by `tool`, without the namespace prefix.
+    fn metadata_keys(&self, tool: &str) -> Vec<&str> {
+        let prefix = prefix(tool);
+        self.metadata_map().keys().filter_map(|key| key.strip_prefix(&prefix)).collect()
+    }
+
+    /// Removes every key of `tool`, returning how many there were.
+    fn clear_metadata(&mut self, tool: &str) -> usize {
+        let prefix = prefix(tool);
+        let map = self.metadata_map_mut();
+        let before = map.len();
+        map.retain(|key, _| !key.starts_with(&prefix));
+        before - map.len()
+    }
+}
+
+macro_rules! metadata_in {
+    ($field:ident: $($type:ty),*) => {
+        $(
+            impl ToolMetadata for $type {
+                fn metadata_map(&self) -> &IndexMap<String, Value> {
+                    &self.$field
+                }
+
+                fn metadata_map_mut(&mut self) -> &mut IndexMap<String, Value> {
+                    &mut self.$field
+                }
+            }
+        )*
+    };
+}
+
+metadata_in!(attributes: Module, Cell, Memory, Net);
+metadata_in!(extra: Netlist, Port);
+
+impl Netlist {
+    /// Removes the metadata of `tool` from the netlist and every object in it, returning the
+    /// number of keys removed.
+    pub fn clear_tool_metadata(&mut self, tool: &str) -> usize {
+        let mut removed = self.clear_metadata(tool);
+        for module in self.modules.values_mut() {
+            removed += module.clear_metadata(tool);
+            removed += module.ports.values_mut().map(|port| port.clear_metadata(tool)).sum::<usize>();
+            removed += module.cells.values_mut().map(|cell| cell.clear_metadata(tool)).sum::<usize>();
+            removed += module.memories.values_mut().map(|memory| memory.clear_metadata(tool)).sum::<usize>();
+            removed += module.nets.values_mut().map(|net| net.clear_metadata(tool)).sum::<usize>();
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Placement {
+        x: i64,
+        y: i64,
+    }
+
+    #[test]
+    fn test_tool_metadata() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let cell = netlist.modules["test_and"].cells.values_mut().next().unwrap();
+        cell.set_metadata("placer", "placement", &Placement { x: 3, y: -1 }).unwrap();
+        cell.set_metadata("router", "layer", &2).unwrap();
+        assert_eq!(cell.attributes["x-placer:placement"], r#"{"x":3,"y":-1}"#);
+        assert_eq!(cell.metadata::<Placement>("placer", "placement").unwrap().unwrap(), Placement { x: 3, y: -1 });
+        assert!(cell.metadata::<Placement>("router", "layer").unwrap().is_err());
+        assert!(cell.metadata::<Placement>("placer", "nope").is_none());
+        assert_eq!(cell.metadata_keys("placer"), ["placement"]);
+
+        cell.set_metadata("router", "layer", &None::<u32>).unwrap();
+        assert!(cell.metadata_keys("router").is_empty());
+        netlist.set_metadata("placer", "version", &1).unwrap();
+        netlist.modules["test_or"].ports["a"].set_metadata("placer", "pin", &"N3").unwrap();
+
+        let mut reparsed = Netlist::from_str(&netlist.to_string().unwrap()).unwrap();
+        assert_eq!(reparsed.modules["test_or"].ports["a"].metadata::<String>("placer", "pin").unwrap().unwrap(), "N3");
+        assert_eq!(reparsed.clear_tool_metadata("placer"), 3);
+        assert_eq!(reparsed, Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap());
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::progress::pass_span;
+use crate::{IdString, Netlist};
+
+/// Identifier rules of an export target.
+#[derive(Debug, Clone)]
+pub struct NameRules {
+    pub max_len: Option<usize>,
+    /// Characters allowed anywhere in an identifier, others are replaced by `_`.
+    pub allowed: fn(char) -> bool,
+    /// Characters allowed as the first character, otherwise the name is prefixed with `prefix`.
+    pub allowed_first: fn(char) -> bool,
+    pub prefix: &'static str,
+    /// Names must stay unique after folding case.
+    pub case_insensitive: bool,
+    /// Forbid `__` and a trailing `_`, as VHDL does.
+    pub strict_underscores: bool,
+    /// Keywords that can not be used as identifiers, compared after case folding if needed.
+    pub reserved: &'static [&'static str],
+}
+
+const VHDL_KEYWORDS: &[&str] = &[
+    "abs", "access", "after", "alias", "all", "and", "architecture", "array", "assert", "attribute", "begin", "block", "body",
+    "buffer", "bus", "case", "component", "configuration", "constant", "disconnect", "downto", "else", "elsif", "end", "entity",
+    "exit", "file", "for", "function", "generate", "generic", "group", "guarded", "if", "impure", "in", "inertial", "inout", "is",
+    "label", "library", "linkage", "literal", "loop", "map", "mod", "nand", "new", "next", "nor", "not", "null", "of", "on",
+    "open", "or", "others", "out", "package", "port", "postponed", "procedure", "process", "pure", "range", "record", "register",
+    "reject", "rem", "report", "return", "rol", "ror", "select", "severity", "signal", "shared", "sla", "sll", "sra", "srl",
+    "subtype", "then", "to", "transport", "type", "unaffected", "units", "until", "use", "variable", "wait", "when", "while",
+    "with", "xnor", "xor",
+];
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_letter(c: char) -> bool {
+    c.is_ascii_alphabetic()
+}
+
+impl NameRules {
+    pub fn edif() -> Self {
+        Self {
+            max_len: Some(255),
+            allowed: is_word,
+            allowed_first: is_letter,
+            prefix: "n",
+            case_insensitive: true,
+            strict_underscores: false,
+            reserved: &[],
+        }
+    }
+
+    pub fn vhdl() -> Self {
+        Self {
+            max_len: None,
+            allowed: is_word,
+            allowed_first: is_letter,
+            prefix: "n",
+            case_insensitive: true,
+            strict_underscores: true,
+            reserved: VHDL_KEYWORDS,
+        }
+    }
+
+    pub fn spice() -> Self {
+        Self {
+            max_len: None,
+            allowed: is_word,
+            allowed_first: is_word,
+            prefix: "n",
+            case_insensitive: true,
+            strict_underscores: false,
+            reserved: &[],
+        }
+    }
+
+    fn fold(&self, name: &str) -> String {
+        match self.case_insensitive {
+            true => name.to_ascii_lowercase(),
+            false => name.to_string(),
+        }
+    }
+
+    pub fn is_legal(&self, name: &str) -> bool {
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) if (self.allowed_first)(first) => (),
+            _ => return false,
+        }
+        chars.all(self.allowed)
+            && self.max_len.is_none_or(|max_len| name.len() <= max_len)
+            && !(self.strict_underscores && (name.contains("__") || name.ends_with('_')))
+            && !self.reserved.contains(&self.fold(name).as_str())
+    }
+
+    fn sanitize(&self, name: &str) -> String {
+        let mut legal: String = name.chars().map(|c| if (self.allowed)(c) { c } else { '_' }).collect();
+        if self.strict_underscores {
+            while legal.contains("__") {
+                legal = legal.replace("__", "_");
+            }
+            legal = legal.trim_end_matches('_').to_string();
+        }
+        if !legal.starts_with(self.allowed_first) {
+            let trimmed = legal.trim_start_matches('_');
+            if trimmed.starts_with(self.allowed_first) {
+                legal = trimmed.to_string();
+            }
+        }
+        if !legal.starts_with(self.allowed_first) || self.reserved.contains(&self.fold(&legal).as_str()) {
+            legal = format!("{}{}", self.prefix, legal);
+        }
+        if let Some(max_len) = self.max_len {
+            legal.truncate(max_len);
+        }
+        legal
+    }
+
+    /// Picks new names for one namespace, keeping names that are already legal and unique.
+    /// Returns the new name of every renamed entry of `names`, by position.
+    fn rename(&self, names: &[&str]) -> Vec<Option<String>> {
+        let mut taken: HashSet<String> = HashSet::new();
+        let keep: Vec<bool> = names.iter().map(|name| self.is_legal(name) && taken.insert(self.fold(name))).collect();
+        names
+            .iter()
+            .zip(keep)
+            .map(|(name, keep)| {
+                if keep {
+                    return None;
+                }
+                let base = self.sanitize(name);
+                let mut candidate = base.clone();
+                for n in 1.. {
+                    if self.is_legal(&candidate) && !taken.contains(&self.fold(&candidate)) {
+                        break;
+                    }
+                    let suffix = format!("_{}", n);
+                    let mut truncated = base.clone();
+                    if let Some(max_len) = self.max_len {
+                        truncated.truncate(max_len.saturating_sub(suffix.len()));
+                    }
+                    candidate = truncated + &suffix;
+                }
+                taken.insert(self.fold(&candidate));
+                Some(candidate)
+            })
+            .collect()
+    }
+}
+
+fn renamed(names: &[&str], renames: Vec<Option<String>>) -> IndexMap<String, String> {
+    names.iter().zip(renames).filter_map(|(name, rename)| Some((name.to_string(), rename?))).collect()
+}
+
+/// Renames applied to the objects of one module. Ports, nets, memories and cells are renamed
+/// in a single namespace, since most export formats put instances and signals in the same
+/// scope: on a clash the wire keeps its name and the memory or cell is renamed. The entries are
+/// only split by the kind of object they rename.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleRenames {
+    #[serde(default)]
+    pub wires: IndexMap<String, String>,
+    #[serde(default)]
+    pub cells: IndexMap<String, String>,
+    /// Renamed memories, whose `MEMID` parameters on memory cells are rewritten to match.
+    #[serde(default)]
+    pub memories: IndexMap<String, String>,
+}
+
+/// Old to new names of everything renamed by [`Netlist::sanitize_names`]. Modules are keyed by
+/// their original name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenameMap {
+    #[serde(default)]
+    pub modules: IndexMap<String, String>,
+    #[serde(default)]
+    pub objects: IndexMap<String, ModuleRenames>,
+}
+
+impl RenameMap {
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+fn rekey<K: Hash + Eq + AsRef<str> + From<String>, V>(map: &mut IndexMap<K, V>, renames: &IndexMap<String, String>) {
+    let rename = |name: K| renames.get(name.as_ref()).map(|new| K::from(new.clone())).unwrap_or(name);
+    *map = std::mem::take(map).into_iter().map(|(name, value)| (rename(name), value)).collect();
+}
+
+impl Netlist {
+    /// Renames modules, ports, nets, memories and cells so every identifier follows `rules`,
+    /// updating the instances of renamed modules and ports and the `MEMID` of memory cells.
+    /// Cell types that are not defined in the netlist, like Yosys primitives, are left alone.
+    pub fn sanitize_names(&mut self, rules: &NameRules) -> RenameMap {
+        pass_span!("sanitize_names", modules = self.modules.len());
+        let mut map = RenameMap::default();
+        let modules: Vec<&str> = self.modules.keys().map(IdString::as_str).collect();
+        map.modules = renamed(&modules, rules.rename(&modules));
+
+        for (name, module) in self.modules.iter() {
+            let mut names: Vec<&str> = module.ports.keys().map(IdString::as_str).collect();
+            names.extend(module.nets.keys().filter(|net| !module.ports.contains_key(*net)).map(IdString::as_str));
+            let wires = names.len();
+            names.extend(module.memories.keys().map(IdString::as_str));
+            let memories = names.len();
+            names.extend(module.cells.keys().map(IdString::as_str));
+            let mut renames = rules.rename(&names);
+            let cells = renamed(&names[memories..], renames.split_off(memories));
+            let memories = renamed(&names[wires..memories], renames.split_off(wires));
+            let wires = renamed(&names[..wires], renames);
+            if !wires.is_empty() || !cells.is_empty() || !memories.is_empty() {
+                map.objects.insert(name.to_string(), ModuleRenames { wires, cells, memories });
+            }
+        }
+
+        for (name, module) in self.modules.iter_mut() {
+            if let Some(renames) = map.objects.get(name) {
+                rekey(&mut module.ports, &renames.wires);
+                rekey(&mut module.nets, &renames.wires);
+                rekey(&mut module.cells, &renames.cells);
+                rekey(&mut module.memories, &renames.memories);
+            }
+            for cell in module.cells.values_mut() {
+                // `MEMID` keeps the backslash of public names that the `memories` keys drop.
+                if let Some(renames) = map.objects.get(name)
+                    && let Some(memid) = cell.parameters.get("MEMID").and_then(|memid| memid.as_str())
+                    && let Some(memory) = renames.memories.get(memid.strip_prefix('\\').unwrap_or(memid))
+                {
+                    cell.parameters.insert("MEMID".to_string(), format!("\\{}", memory).into());
+                }
+                if let Some(renames) = map.objects.get(&cell.module) {
+                    rekey(&mut cell.connections, &renames.wires);
+                    rekey(&mut cell.port_directions, &renames.wires);
+                }
+                if let Some(module) = map.modules.get(&cell.module) {
+                    cell.module = module.into();
+                }
+            }
+        }
+        rekey(&mut self.modules, &map.modules);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_name_rules() {
+        let vhdl = NameRules::vhdl();
+        assert!(vhdl.is_legal("data_out"));
+        assert!(!vhdl.is_legal("data__out"));
+        assert!(!vhdl.is_legal("Signal"));
+        assert!(!vhdl.is_legal("$auto$3"));
+        assert_eq!(vhdl.sanitize("$auto$simplemap.cc:42$3"), "auto_simplemap_cc_42_3");
+        assert_eq!(vhdl.sanitize("signal"), "nsignal");
+        assert_eq!(NameRules::edif().sanitize("1x"), "n1x");
+    }
+
+    #[test]
+    fn test_sanitize_names() {
+        let mut netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": { "a[0]": { "direction": "input", "bits": [2] } },
+                    "memories": {
+                        "mem.ram": { "width": 1, "size": 2 },
+                        "MEM_RAM": { "width": 1, "size": 2 }
+                    },
+                    "cells": {
+                        "rd": { "type": "$memrd_v2", "parameters": { "MEMID": "\\mem.ram" } },
+                        "u.sub": { "type": "\\sub", "connections": { "in": [2], "A": [3] } },
+                        "u_sub": { "type": "$_NOT_", "connections": { "A": [2], "Y": [3] } }
+                    },
+                    "netnames": {
+                        "a[0]": { "bits": [2] },
+                        "A_0_": { "bits": [3] }
+                    }
+                },
+                "\\sub": {
+                    "ports": { "in": { "direction": "input", "bits": [2] }, "A": { "direction": "input", "bits": [3] } },
+                    "netnames": { "in": { "bits": [2] }, "A": { "bits": [3] } }
+                }
+            }
+        }))
+        .unwrap();
+
+        let map = netlist.sanitize_names(&NameRules::vhdl());
+        assert_eq!(map.modules["\\sub"], "sub");
+        assert_eq!(map.objects["top"].wires["a[0]"], "a_0");
+        assert_eq!(map.objects["top"].wires["A_0_"], "A_0_1");
+        assert_eq!(map.objects["top"].cells["u.sub"], "u_sub_1");
+        assert_eq!(map.objects["\\sub"].wires["in"], "nin");
+        assert_eq!(map.objects["top"].memories["mem.ram"], "mem_ram_1");
+        assert!(!map.objects["top"].memories.contains_key("MEM_RAM"));
+
+        let top = &netlist.modules["top"];
+        assert_eq!(top.ports.keys().collect::<Vec<_>>(), ["a_0"]);
+        assert_eq!(top.cells["u_sub_1"].module, "sub");
+        assert_eq!(top.cells["u_sub_1"].connections.keys().collect::<Vec<_>>(), ["nin", "A"]);
+        assert_eq!(top.cells["u_sub"].connections.keys().collect::<Vec<_>>(), ["A", "Y"]);
+        assert!(netlist.modules["sub"].nets.contains_key("nin"));
+        assert_eq!(top.memories.keys().collect::<Vec<_>>(), ["mem_ram_1", "MEM_RAM"]);
+        assert_eq!(top.cells["rd"].parameters["MEMID"], json!("\\mem_ram_1"));
+    }
+
+    #[test]
+    fn test_sanitize_shared_names() {
+        let mut netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "cells": { "x": { "type": "$_NOT_", "connections": { "A": [2], "Y": [3] } } },
+                    "netnames": { "x": { "bits": [3] }, "a": { "bits": [2] } }
+                }
+            }
+        }))
+        .unwrap();
+
+        let map = netlist.sanitize_names(&NameRules::vhdl());
+        assert!(map.objects["top"].wires.is_empty());
+        assert_eq!(map.objects["top"].cells["x"], "x_1");
+        let top = &netlist.modules["top"];
+        assert_eq!(top.nets.keys().collect::<Vec<_>>(), ["x", "a"]);
+        assert_eq!(top.cells.keys().collect::<Vec<_>>(), ["x_1"]);
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::Netlist;
+use crate::gates::function;
+
+/// Functions with more inputs are not classified, the exhaustive search grows as `n! 2^n`.
+pub const MAX_NPN_INPUTS: usize = 6;
+
+/// The canonical representative of an NPN class: the smallest truth table reachable by
+/// permuting and negating the inputs and negating the output, after dropping inputs the
+/// function does not depend on. Entry `m` of the table is bit `m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NpnClass {
+    pub inputs: usize,
+    pub table: u64,
+}
+
+impl fmt::Display for NpnClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = (1usize << self.inputs).div_ceil(4);
+        write!(f, "{}:{:0width$x}", self.inputs, self.table, width = digits)
+    }
+}
+
+fn mask(inputs: usize) -> u64 {
+    match inputs {
+        6 => u64::MAX,
+        _ => (1 << (1 << inputs)) - 1,
+    }
+}
+
+/// Drops the inputs `table` does not depend on.
+fn support(mut table: Vec<bool>) -> Vec<bool> {
+    let mut input = 0;
+    while 1 << input < table.len() {
+        let cofactor = |value: usize| table.iter().enumerate().filter(move |(m, _)| m >> input & 1 == value).map(|(_, set)| *set);
+        if cofactor(0).eq(cofactor(1)) {
+            table = cofactor(0).collect();
+        } else {
+            input += 1;
+        }
+    }
+    table
+}
+
+/// Every permutation of `0..n`, in lexicographic order.
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    let mut permutations = vec![Vec::new()];
+    for _ in 0..n {
+        let mut longer = Vec::new();
+        for prefix in permutations {
+            longer.extend((0..n).filter(|i| !prefix.contains(i)).map(|i| [prefix.as_slice(), &[i]].concat()));
+        }
+        permutations = longer;
+    }
+    permutations
+}
+
+/// Classifies a truth table of `2^n` entries, `None` for more than [`MAX_NPN_INPUTS`] inputs.
+pub fn npn_canonical(table: &[bool]) -> Option<NpnClass> {
+    if !table.len().is_power_of_two() {
+        return None;
+    }
+    let table = support(table.to_vec());
+    let inputs = table.len().trailing_zeros() as usize;
+    if inputs > MAX_NPN_INPUTS {
+        return None;
+    }
+    let table = table.iter().enumerate().fold(0u64, |packed, (m, set)| packed | (*set as u64) << m);
+    let entries = 1usize << inputs;
+    let mut best = u64::MAX;
+    for permutation in permutations(inputs) {
+        for negation in 0..entries {
+            let mut transformed = 0u64;
+            for m in 0..entries {
+                let old = permutation.iter().enumerate().fold(0, |old, (new, from)| old | ((m ^ negation) >> new & 1) << from);
+                transformed |= (table >> old & 1) << m;
+            }
+            best = best.min(transformed).min(!transformed & mask(inputs));
+        }
+    }
+    Some(NpnClass { inputs, table: best })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NpnClassUsage {
+    pub class: NpnClass,
+    /// Cells computing a function of the class.
+    pub cells: usize,
+    /// Distinct truth tables seen within the class.
+    pub functions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NpnReport {
+    /// Most used classes first.
+    pub classes: Vec<NpnClassUsage>,
+    /// LUTs too wide to classify.
+    pub unclassified: usize,
+}
+
+impl NpnReport {
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+impl Netlist {
+    /// Classifies the functions of every LUT and gate-level cell in the netlist.
+    pub fn npn_classes(&self) -> NpnReport {
+        let mut cache: HashMap<Vec<bool>, Option<NpnClass>> = HashMap::new();
+        let mut classes: IndexMap<NpnClass, (usize, Vec<Vec<bool>>)> = IndexMap::new();
+        let mut report = NpnReport::default();
+        for cell in self.iter_all_cells() {
+            let Some((_, _, function)) = function(cell.object) else { continue };
+            let class = *cache.entry(function.clone()).or_insert_with(|| npn_canonical(&function));
+            let Some(class) = class else {
+                report.unclassified += 1;
+                continue;
+            };
+            let (cells, functions) = classes.entry(class).or_default();
+            *cells += 1;
+            if !functions.contains(&function) {
+                functions.push(function);
+            }
+        }
+        report.classes =
+            classes.into_iter().map(|(class, (cells, functions))| NpnClassUsage { class, cells, functions: functions.len() }).collect();
+        report.classes.sort_by(|a, b| b.cells.cmp(&a.cells).then(a.class.cmp(&b.class)));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn class(table: &[u8]) -> NpnClass {
+        npn_canonical(&table.iter().map(|entry| *entry == 1).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_npn_canonical() {
+        let and = class(&[0, 0, 0, 1]);
+        assert_eq!(and, NpnClass { inputs: 2, table: 1 });
+        assert_eq!(class(&[0, 1, 1, 1]), and);
+        assert_eq!(class(&[1, 0, 0, 0]), and);
+        assert_eq!(class(&[0, 0, 1, 0]), and);
+        assert_ne!(class(&[0, 1, 1, 0]), and);
+        // A & B, ignoring a third input.
+        assert_eq!(class(&[0, 0, 0, 1, 0, 0, 0, 1]), and);
+        assert_eq!(class(&[1, 0]), class(&[0, 1]));
+        assert_eq!(class(&[1, 1]), NpnClass { inputs: 0, table: 0 });
+        assert_eq!(and.to_string(), "2:1");
+        assert!(npn_canonical(&[false; 3]).is_none());
+    }
+
+    #[test]
+    fn test_npn_report() {
+        let gate = |module: &str| json!({ "type": module, "connections": { "A": [2], "B": [3], "Y": [4] } });
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": { "top": { "cells": {
+                "a": gate("$_AND_"), "o": gate("$_OR_"), "n": gate("$_NOR_"), "x": gate("$_XOR_"),
+                "l": { "type": "$lut", "parameters": { "LUT": "1000", "WIDTH": 2 }, "connections": { "A": [2, 3], "Y": [5] } }
+            } } }
+        }))
+        .unwrap();
+        let report = netlist.npn_classes();
+        assert_eq!(report.classes.len(), 2);
+        assert_eq!(report.classes[0], NpnClassUsage { class: NpnClass { inputs: 2, table: 1 }, cells: 4, functions: 3 });
+        assert_eq!(report.classes[1].cells, 1);
+    }
+}
@@ -0,0 +1,146 @@
+use serde_json::Value;
+
+use crate::{Bit, Cell};
+
+/// A cell parameter, decoded from the ways Yosys writes them: integers as numbers, constants
+/// as strings of `0`, `1`, `x` and `z` with the most significant bit first, and strings as
+/// strings, with a space appended to those that would read as constants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamValue {
+    Int(i64),
+    /// Bits least significant first, like connections.
+    BitVector(Vec<Bit>),
+    String(String),
+}
+
+pub(crate) fn is_constant(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| matches!(c, '0' | '1' | 'x' | 'z'))
+}
+
+impl ParamValue {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => number.as_i64().map(Self::Int),
+            Value::String(text) if is_constant(text) => Some(Self::BitVector(
+                text.chars()
+                    .rev()
+                    .map(|c| match c {
+                        '0' => Bit::_0,
+                        '1' => Bit::_1,
+                        'x' => Bit::X,
+                        _ => Bit::Z,
+                    })
+                    .collect(),
+            )),
+            Value::String(text) => match text.strip_suffix(' ') {
+                Some(constant) if is_constant(constant) => Some(Self::String(constant.to_string())),
+                _ => Some(Self::String(text.clone())),
+            },
+            _ => None,
+        }
+    }
+
+    /// The value as an unsigned integer, `None` for strings, negative numbers and bit vectors
+    /// with undefined bits or more than 64 significant bits.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Int(value) => u64::try_from(*value).ok(),
+            Self::BitVector(bits) => bits.iter().enumerate().try_fold(0u64, |value, (index, bit)| match bit {
+                Bit::_0 => Some(value),
+                Bit::_1 if index < 64 => Some(value | 1 << index),
+                _ => None,
+            }),
+            Self::String(_) => None,
+        }
+    }
+
+    /// The value as a signed integer, reading bit vectors as two's complement of their width
+    /// since the JSON does not record whether a parameter is signed.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            Self::BitVector(bits) if bits.len() > 64 => {
+                let sign = *bits.last()?;
+                if bits[63..].iter().any(|bit| *bit != sign) {
+                    return None;
+                }
+                Self::BitVector(bits[..64].to_vec()).as_i64()
+            }
+            Self::BitVector(bits) => {
+                let value = self.as_u64()?;
+                match bits.last() {
+                    Some(Bit::_1) if bits.len() < 64 => Some((value | u64::MAX << bits.len()) as i64),
+                    _ => Some(value as i64),
+                }
+            }
+            Self::String(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl From<&ParamValue> for Value {
+    fn from(value: &ParamValue) -> Self {
+        match value {
+            ParamValue::Int(value) => Value::from(*value),
+            ParamValue::BitVector(bits) => Value::String(
+                bits.iter()
+                    .rev()
+                    .map(|bit| match bit {
+                        Bit::_0 => '0',
+                        Bit::_1 => '1',
+                        Bit::Z => 'z',
+                        _ => 'x',
+                    })
+                    .collect(),
+            ),
+            ParamValue::String(text) if is_constant(text) => Value::String(format!("{} ", text)),
+            ParamValue::String(text) => Value::String(text.clone()),
+        }
+    }
+}
+
+impl From<ParamValue> for Value {
+    fn from(value: ParamValue) -> Self {
+        Value::from(&value)
+    }
+}
+
+impl Cell {
+    pub fn param(&self, name: &str) -> Option<ParamValue> {
+        self.parameters.get(name).and_then(ParamValue::from_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_param() {
+        let cell = Cell::new("$add")
+            .with_parameter("A_WIDTH", "00000000000000000000000000100101")
+            .with_parameter("B_WIDTH", 8)
+            .with_parameter("OFFSET", "11111111111111111111111111111110")
+            .with_parameter("INIT", "1x0z")
+            .with_parameter("MODE", "0101 ")
+            .with_parameter("NAME", "fast path");
+        assert_eq!(cell.param("A_WIDTH").unwrap().as_u64(), Some(37));
+        assert_eq!(cell.param("B_WIDTH"), Some(ParamValue::Int(8)));
+        assert_eq!(cell.param("OFFSET").unwrap().as_i64(), Some(-2));
+        assert_eq!(cell.param("INIT"), Some(ParamValue::BitVector(vec![Bit::Z, Bit::_0, Bit::X, Bit::_1])));
+        assert_eq!(cell.param("INIT").unwrap().as_u64(), None);
+        assert_eq!(cell.param("MODE").unwrap().as_str(), Some("0101"));
+        assert_eq!(cell.param("NAME").unwrap().as_str(), Some("fast path"));
+        assert_eq!(cell.param("WIDTH"), None);
+        for name in ["A_WIDTH", "B_WIDTH", "INIT", "MODE", "NAME"] {
+            assert_eq!(Value::from(cell.param(name).unwrap()), cell.parameters[name]);
+        }
+    }
+}
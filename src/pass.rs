@@ -0,0 +1,262 @@
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+
+use crate::limits::{Budget, LimitExceeded, Limits};
+use crate::progress::{Progress, ProgressCallback};
+use crate::{Module, Netlist};
+
+/// A transformation of one module at a time, which a [`PassManager`] runs on many modules
+/// in parallel.
+pub trait Pass: Send + Sync {
+    /// Changes `module` and returns a summary for the log, or why it failed.
+    fn run(&self, module: &mut Module) -> Result<String, String>;
+}
+
+impl<F: Fn(&mut Module) -> Result<String, String> + Send + Sync> Pass for F {
+    fn run(&self, module: &mut Module) -> Result<String, String> {
+        self(module)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassError {
+    UnknownPass(String),
+    /// Passes depending on each other, in registration order.
+    DependencyCycle(Vec<String>),
+    /// A pass failed on a module, which keeps the changes of the passes before it.
+    Failed {
+        pass: String,
+        module: String,
+        message: String,
+    },
+    /// Cancelled or out of time before `pass` could run on `module`.
+    Interrupted {
+        pass: String,
+        module: String,
+        limit: LimitExceeded,
+    },
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPass(pass) => write!(f, "unknown pass {}", pass),
+            Self::DependencyCycle(passes) => write!(f, "passes {} depend on each other", passes.join(", ")),
+            Self::Failed { pass, module, message } => write!(f, "pass {} failed on module {}: {}", pass, module, message),
+            Self::Interrupted { pass, module, limit } => write!(f, "pass {} on module {} not run: {}", pass, module, limit),
+        }
+    }
+}
+
+impl std::error::Error for PassError {}
+
+/// One pass run on one module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassRecord {
+    pub pass: String,
+    pub module: String,
+    pub duration: Duration,
+    pub summary: String,
+}
+
+impl fmt::Display for PassRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}: {} ({:.3} ms)", self.pass, self.module, self.summary, self.duration.as_secs_f64() * 1000.0)
+    }
+}
+
+struct Registered {
+    pass: Box<dyn Pass>,
+    after: Vec<String>,
+}
+
+/// Passes run on every module of a netlist, each module on its own thread. Within a module
+/// passes run one after the other, in registration order except where a declared dependency
+/// moves a pass later.
+pub struct PassManager {
+    passes: IndexMap<String, Registered>,
+    threads: usize,
+    progress: Option<ProgressCallback>,
+    limits: Limits,
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self {
+            passes: IndexMap::new(),
+            threads: std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            progress: None,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `pass` under `name`, replacing any pass of that name but keeping its place.
+    pub fn register(&mut self, name: &str, pass: impl Pass + 'static) -> &mut Self {
+        match self.passes.get_mut(name) {
+            Some(registered) => registered.pass = Box::new(pass),
+            None => {
+                self.passes.insert(name.to_string(), Registered { pass: Box::new(pass), after: Vec::new() });
+            }
+        }
+        self
+    }
+
+    /// Makes `pass` run after `dependency` on every module, as in "clean after const-fold".
+    pub fn after(&mut self, pass: &str, dependency: &str) -> Result<&mut Self, PassError> {
+        if !self.passes.contains_key(dependency) {
+            return Err(PassError::UnknownPass(dependency.to_string()));
+        }
+        let registered = self.passes.get_mut(pass).ok_or_else(|| PassError::UnknownPass(pass.to_string()))?;
+        registered.after.push(dependency.to_string());
+        Ok(self)
+    }
+
+    /// Runs on at most `threads` modules at once, by default as many as the machine has cores.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Calls `callback` whenever a module is done, from the thread that ran its passes.
+    pub fn on_progress(mut self, callback: impl Fn(Progress<'_>) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Stops starting passes once cancelled or out of time. Passes already running finish.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The passes in the order they run.
+    pub fn order(&self) -> Result<Vec<&str>, PassError> {
+        let mut order: Vec<&str> = Vec::new();
+        while order.len() < self.passes.len() {
+            let next = (self.passes.iter())
+                .find(|(name, registered)| {
+                    !order.contains(&name.as_str()) && registered.after.iter().all(|dependency| order.contains(&dependency.as_str()))
+                })
+                .map(|(name, _)| name.as_str());
+            match next {
+                Some(name) => order.push(name),
+                None => {
+                    let cycle = self.passes.keys().filter(|name| !order.contains(&name.as_str())).cloned().collect();
+                    return Err(PassError::DependencyCycle(cycle));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    fn run_module(&self, order: &[&str], name: &str, module: &mut Module, budget: &Budget) -> Result<Vec<PassRecord>, PassError> {
+        let mut records = Vec::new();
+        for pass in order {
+            budget.check().map_err(|limit| PassError::Interrupted { pass: pass.to_string(), module: name.to_string(), limit })?;
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("pass", pass = *pass, module = name).entered();
+            let start = Instant::now();
+            let summary = (self.passes[*pass].pass.run(module)).map_err(|message| PassError::Failed {
+                pass: pass.to_string(),
+                module: name.to_string(),
+                message,
+            })?;
+            let duration = start.elapsed();
+            #[cfg(feature = "tracing")]
+            tracing::info!(?duration, summary = summary.as_str(), "pass done");
+            records.push(PassRecord { pass: pass.to_string(), module: name.to_string(), duration, summary });
+        }
+        Ok(records)
+    }
+
+    /// Runs every pass on every module and returns the log, by module then by pass. After a
+    /// failure the other modules still run to completion, and the error of the first failing
+    /// module is returned.
+    pub fn run(&self, netlist: &mut Netlist) -> Result<Vec<PassRecord>, PassError> {
+        let order = self.order()?;
+        let budget = self.limits.start();
+        let (total, done) = (netlist.modules.len(), AtomicUsize::new(0));
+        let threads = self.threads.min(total);
+        let queue = Mutex::new(netlist.modules.iter_mut().enumerate());
+        let results = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    loop {
+                        let Some((index, (name, module))) = queue.lock().unwrap().next() else { break };
+                        let result = self.run_module(&order, name, module, &budget);
+                        if let Some(progress) = &self.progress {
+                            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                            progress(Progress { operation: "passes", item: Some(name), done, total });
+                        }
+                        results.lock().unwrap().push((index, result));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        let mut records = Vec::new();
+        for (_, result) in results {
+            records.extend(result?);
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A pass marking modules with attribute `name`, which needs every attribute of `needs`.
+    fn mark(name: &'static str, needs: &'static [&'static str]) -> impl Pass {
+        move |module: &mut Module| {
+            if let Some(missing) = needs.iter().find(|need| !module.attributes.contains_key(**need)) {
+                return Err(format!("{} has not run", missing));
+            }
+            module.attributes.insert(name.to_string(), 1.into());
+            Ok(format!("marked {}", name))
+        }
+    }
+
+    #[test]
+    fn test_pass_manager() {
+        let mut netlist = Netlist::new("test");
+        for name in ["a", "b", "c"] {
+            netlist.modules.insert(name.into(), Module::new());
+        }
+        let reported = Arc::new(AtomicUsize::new(0));
+        let counter = reported.clone();
+        let mut manager = PassManager::new().with_threads(2).on_progress(move |progress| {
+            assert_eq!((progress.operation, progress.total), ("passes", 3));
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        manager.register("clean", mark("clean", &["fold"])).register("fold", mark("fold", &[]));
+        assert!(matches!(manager.run(&mut netlist.clone()), Err(PassError::Failed { .. })));
+
+        manager.after("clean", "fold").unwrap();
+        assert_eq!(manager.order().unwrap(), ["fold", "clean"]);
+        let log = manager.run(&mut netlist).unwrap();
+        let runs: Vec<(&str, &str)> = log.iter().map(|record| (record.module.as_str(), record.pass.as_str())).collect();
+        assert_eq!(runs, [("a", "fold"), ("a", "clean"), ("b", "fold"), ("b", "clean"), ("c", "fold"), ("c", "clean")]);
+        assert!(log[0].to_string().starts_with("fold on a: marked fold ("));
+        assert!(netlist.modules.values().all(|module| module.attributes.contains_key("clean")));
+        assert_eq!(reported.load(Ordering::Relaxed), 6);
+
+        manager.after("fold", "clean").unwrap();
+        assert_eq!(manager.order(), Err(PassError::DependencyCycle(vec!["clean".to_string(), "fold".to_string()])));
+        assert_eq!(manager.after("fold", "opt").err(), Some(PassError::UnknownPass("opt".to_string())));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+use std::hash::Hash;
+
+use indexmap::{Equivalent, IndexMap};
+
+use serde_json::Value;
+
+use crate::progress::pass_span;
+use crate::{Bit, Cell, Module};
+
+/// Gate-level cells with interchangeable `A` and `B` inputs.
+const COMMUTATIVE_GATES: &[&str] = &["$_AND_", "$_OR_", "$_XOR_", "$_NAND_", "$_NOR_", "$_XNOR_"];
+
+/// Word-level cells with interchangeable `A` and `B` inputs, together with their `A_*` and
+/// `B_*` parameters.
+const COMMUTATIVE_CELLS: &[&str] = &["$and", "$or", "$xor", "$xnor", "$add", "$mul", "$eq", "$ne", "$logic_and", "$logic_or"];
+
+/// LUT primitives with one port per input, least significant input first, and the parameter
+/// holding their truth table.
+const LUT_PRIMITIVES: &[(&str, &[&str], &str)] = &[
+    ("SB_LUT4", &["I0", "I1", "I2", "I3"], "LUT_INIT"),
+    ("LUT1", &["I0"], "INIT"),
+    ("LUT2", &["I0", "I1"], "INIT"),
+    ("LUT3", &["I0", "I1", "I2"], "INIT"),
+    ("LUT4", &["I0", "I1", "I2", "I3"], "INIT"),
+    ("LUT5", &["I0", "I1", "I2", "I3", "I4"], "INIT"),
+    ("LUT6", &["I0", "I1", "I2", "I3", "I4", "I5"], "INIT"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermuteError {
+    /// The cell type has no interchangeable inputs.
+    NotPermutable(String),
+    /// The permutation does not cover every LUT input exactly once.
+    InvalidPermutation,
+    /// The truth table parameter is missing or does not fit the number of inputs.
+    Malformed(String),
+}
+
+impl fmt::Display for PermuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotPermutable(module) => write!(f, "inputs of {} cells cannot be permuted", module),
+            Self::InvalidPermutation => write!(f, "permutation does not match the LUT inputs"),
+            Self::Malformed(parameter) => write!(f, "malformed truth table in parameter {}", parameter),
+        }
+    }
+}
+
+impl std::error::Error for PermuteError {}
+
+/// A LUT input: the port and the bit within it.
+type LutInput = (String, usize);
+
+/// Reads a truth table of `2^inputs` entries, written by Yosys as a binary string with the
+/// last entry first, or as a number. Entries past the table, as in an `INIT` parameter wider
+/// than the LUT, must be 0.
+pub(crate) fn truth_table(value: &Value, inputs: usize) -> Option<Vec<bool>> {
+    let len = 1usize.checked_shl(inputs as u32)?;
+    match value {
+        Value::String(bits) if bits.chars().all(|c| c == '0' || c == '1') => {
+            let mut table: Vec<bool> = bits.chars().rev().map(|c| c == '1').collect();
+            if table.iter().skip(len).any(|set| *set) {
+                return None;
+            }
+            table.resize(len, false);
+            Some(table)
+        }
+        Value::Number(number) => {
+            let number = number.as_u64()?;
+            (len >= 64 || number >> len == 0).then(|| (0..len).map(|index| index < 64 && number >> index & 1 == 1).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Writes a truth table in the representation of `like`, padded with zeros to its width.
+pub(crate) fn encode_truth_table(table: &[bool], like: &Value) -> Value {
+    match like {
+        Value::Number(_) if table.len() <= 64 => {
+            Value::from(table.iter().enumerate().filter(|(_, set)| **set).fold(0u64, |value, (index, _)| value | 1 << index))
+        }
+        _ => {
+            let padding = like.as_str().map_or(0, |bits| bits.len().saturating_sub(table.len()));
+            Value::String(std::iter::repeat_n('0', padding).chain(table.iter().rev().map(|set| if *set { '1' } else { '0' })).collect())
+        }
+    }
+}
+
+/// The truth table of `table` with its inputs reordered, input `i` of the result being input
+/// `permutation[i]` of `table`.
+pub(crate) fn permute_table(table: &[bool], permutation: &[usize]) -> Vec<bool> {
+    (0..table.len())
+        .map(|index| {
+            let old = permutation.iter().enumerate().fold(0, |old, (new, from)| old | (index >> new & 1) << from);
+            table[old]
+        })
+        .collect()
+}
+
+impl Cell {
+    /// Whether the cell has interchangeable `A` and `B` inputs.
+    pub fn is_commutative(&self) -> bool {
+        COMMUTATIVE_GATES.contains(&self.module.as_str()) || COMMUTATIVE_CELLS.contains(&self.module.as_str())
+    }
+
+    /// Swaps the `A` and `B` inputs of a commutative cell, and their width and signedness
+    /// parameters along with them.
+    pub fn swap_inputs(&mut self) -> Result<(), PermuteError> {
+        if !self.is_commutative() {
+            return Err(PermuteError::NotPermutable(self.module.to_string()));
+        }
+        swap(&mut self.connections, "A", "B");
+        swap(&mut self.parameters, "A_WIDTH", "B_WIDTH");
+        swap(&mut self.parameters, "A_SIGNED", "B_SIGNED");
+        Ok(())
+    }
+
+    /// The inputs and truth table parameter of a `$lut` or a known LUT primitive.
+    fn lut(&self) -> Option<(Vec<LutInput>, &'static str)> {
+        if self.module == "$lut" {
+            let width = self.connections.get("A")?.len();
+            return Some(((0..width).map(|bit| ("A".to_string(), bit)).collect(), "LUT"));
+        }
+        let (_, ports, init) = LUT_PRIMITIVES
+            .iter()
+            .find(|(module, ports, _)| *module == self.module && ports.iter().all(|port| self.connections.contains_key(*port)))?;
+        Some((ports.iter().map(|port| (port.to_string(), 0)).collect(), init))
+    }
+
+    /// The bits driving the inputs of a LUT cell, least significant first.
+    pub fn lut_inputs(&self) -> Option<Vec<Bit>> {
+        let (inputs, _) = self.lut()?;
+        inputs.iter().map(|(port, bit)| self.connections.get(port)?.get(*bit).copied()).collect()
+    }
+
+    /// The truth table of a LUT cell, indexed by the input values with the first input as
+    /// the least significant bit.
+    pub fn lut_truth_table(&self) -> Option<Vec<bool>> {
+        let (inputs, init) = self.lut()?;
+        truth_table(self.parameters.get(init)?, inputs.len())
+    }
+
+    /// Reorders the inputs of a LUT cell, input `i` receiving the signal of input
+    /// `permutation[i]`, and rewrites the truth table so the function stays the same.
+    pub fn permute_lut_inputs(&mut self, permutation: &[usize]) -> Result<(), PermuteError> {
+        let Some((inputs, init)) = self.lut() else {
+            return Err(PermuteError::NotPermutable(self.module.to_string()));
+        };
+        let mut seen = vec![false; inputs.len()];
+        if permutation.len() != inputs.len()
+            || !permutation.iter().all(|from| *from < seen.len() && !std::mem::replace(&mut seen[*from], true))
+        {
+            return Err(PermuteError::InvalidPermutation);
+        }
+        let malformed = || PermuteError::Malformed(init.to_string());
+        let value = self.parameters.get(init).ok_or_else(malformed)?;
+        let table = truth_table(value, inputs.len()).ok_or_else(malformed)?;
+        let value = encode_truth_table(&permute_table(&table, permutation), value);
+        let bits = self.lut_inputs().ok_or_else(malformed)?;
+        for ((port, bit), from) in inputs.iter().zip(permutation) {
+            self.connections.get_mut(port).unwrap()[*bit] = bits[*from];
+        }
+        self.parameters.insert(init.to_string(), value);
+        Ok(())
+    }
+
+    /// Orders the inputs of a commutative or LUT cell by the bits driving them, so cells
+    /// computing the same function of the same signals become identical. Returns whether the
+    /// cell changed.
+    pub fn canonicalize_inputs(&mut self) -> bool {
+        if let Some(bits) = self.lut_inputs() {
+            let mut permutation: Vec<usize> = (0..bits.len()).collect();
+            permutation.sort_by_key(|index| bits[*index]);
+            let changed = permutation.iter().enumerate().any(|(index, from)| index != *from);
+            return changed && self.permute_lut_inputs(&permutation).is_ok();
+        }
+        match (self.connections.get("A"), self.connections.get("B")) {
+            (Some(a), Some(b)) if self.is_commutative() && a > b => self.swap_inputs().is_ok(),
+            _ => false,
+        }
+    }
+}
+
+/// Swaps the values of `a` and `b` in place, renaming the key if only one of them is set.
+fn swap<K: Hash + Eq + for<'a> From<&'a str>, V: Default>(map: &mut IndexMap<K, V>, a: &str, b: &str)
+where
+    str: Equivalent<K>,
+{
+    match (map.get_index_of(a), map.get_index_of(b)) {
+        (Some(first), Some(second)) => {
+            let value = std::mem::take(&mut map[first]);
+            map[first] = std::mem::replace(&mut map[second], value);
+        }
+        (Some(index), None) => rename(map, index, b),
+        (None, Some(index)) => rename(map, index, a),
+        (None, None) => {}
+    }
+}
+
+fn rename<K: Hash + Eq + for<'a> From<&'a str>, V>(map: &mut IndexMap<K, V>, index: usize, key: &str) {
+    let (_, value) = map.shift_remove_index(index).unwrap();
+    map.shift_insert(index, key.into(), value);
+}
+
+impl Module {
+    /// Canonicalizes the inputs of every cell, see [`Cell::canonicalize_inputs`]. Returns the
+    /// number of cells changed.
+    pub fn canonicalize_inputs(&mut self) -> usize {
+        pass_span!("canonicalize_inputs", cells = self.cells.len(), nets = self.nets.len());
+        self.cells.values_mut().map(Cell::canonicalize_inputs).filter(|changed| *changed).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::Direction;
+
+    #[test]
+    fn test_permute_lut() {
+        // I0 & !I1 & I2
+        let mut cell = Cell::new("LUT3")
+            .with_parameter("INIT", "00100000")
+            .with_connection("I0", Direction::Input, vec![Bit::Signal(5)])
+            .with_connection("I1", Direction::Input, vec![Bit::Signal(3)])
+            .with_connection("I2", Direction::Input, vec![Bit::Signal(4)])
+            .with_connection("O", Direction::Output, vec![Bit::Signal(6)]);
+        cell.permute_lut_inputs(&[1, 2, 0]).unwrap();
+        assert_eq!(cell.lut_inputs().unwrap(), [Bit::Signal(3), Bit::Signal(4), Bit::Signal(5)]);
+        // !I0 & I1 & I2
+        assert_eq!(cell.parameters["INIT"], "01000000");
+        assert!(!cell.canonicalize_inputs());
+        assert_eq!(cell.permute_lut_inputs(&[0, 0, 1]), Err(PermuteError::InvalidPermutation));
+
+        // An INIT wider than the LUT keeps its width, as long as the extra bits are 0.
+        cell.parameters.insert("INIT".to_string(), "0000000001000000".into());
+        assert_eq!(cell.lut_truth_table().unwrap(), [false, false, false, false, false, false, true, false]);
+        cell.permute_lut_inputs(&[1, 2, 0]).unwrap();
+        assert_eq!(cell.parameters["INIT"], "0000000000001000");
+        cell.parameters.insert("INIT".to_string(), "1000000001000000".into());
+        assert_eq!(cell.lut_truth_table(), None);
+
+        let mut lut =
+            Cell::new("$lut").with_parameter("LUT", 2).with_connection("A", Direction::Input, vec![Bit::Signal(9), Bit::Signal(8)]);
+        assert!(lut.canonicalize_inputs());
+        assert_eq!(lut.connections["A"], [Bit::Signal(8), Bit::Signal(9)]);
+        assert_eq!(lut.parameters["LUT"], 4);
+        assert_eq!(lut.lut_truth_table().unwrap(), [false, false, true, false]);
+    }
+
+    #[test]
+    fn test_swap_inputs() {
+        let mut add = Cell::new("$add")
+            .with_parameter("A_WIDTH", 4)
+            .with_parameter("B_WIDTH", 2)
+            .with_connection("A", Direction::Input, vec![Bit::Signal(2); 4])
+            .with_connection("B", Direction::Input, vec![Bit::Signal(1); 2]);
+        assert!(add.canonicalize_inputs());
+        assert_eq!(add.connections["A"].len(), 2);
+        assert_eq!(add.parameters["A_WIDTH"], json!(2));
+        assert_eq!(add.parameters["B_WIDTH"], json!(4));
+        assert_eq!(Cell::new("$sub").swap_inputs(), Err(PermuteError::NotPermutable("$sub".to_string())));
+    }
+}
@@ -0,0 +1,224 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::ToolMetadata;
+use crate::{Cell, Module, Net, Port};
+
+/// Namespace of the physical annotations, see [`ToolMetadata`].
+pub const PHYSICAL: &str = "physical";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (self.x0..=self.x1).contains(&x) && (self.y0..=self.y1).contains(&y)
+    }
+}
+
+/// Lower left corner of a cell, in the same units as the die.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    pub x: f64,
+    pub y: f64,
+    /// Fixed cells are not moved by placers.
+    #[serde(default)]
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pin {
+    pub x: f64,
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+}
+
+/// Extracted parasitics of a net, from SPEF.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Parasitics {
+    /// Total capacitance in picofarads.
+    pub capacitance: f64,
+    /// Sum of the resistor values in ohms.
+    pub resistance: f64,
+}
+
+/// A physical annotation that breaks the conventions of this module.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PhysicalIssue {
+    /// An annotation that does not decode, on the named cell or port, or the module for `None`.
+    Malformed {
+        object: Option<String>,
+        key: &'static str,
+    },
+    CellOutsideDie(String),
+    PinOutsideDie(String),
+    UnknownRegion {
+        cell: String,
+        region: String,
+    },
+    CellOutsideRegion {
+        cell: String,
+        region: String,
+    },
+}
+
+fn get<T: serde::de::DeserializeOwned>(
+    object: &impl ToolMetadata,
+    key: &'static str,
+    name: Option<&str>,
+    issues: &mut Vec<PhysicalIssue>,
+) -> Option<T> {
+    match object.metadata(PHYSICAL, key)? {
+        Ok(value) => Some(value),
+        Err(_) => {
+            issues.push(PhysicalIssue::Malformed { object: name.map(str::to_string), key });
+            None
+        }
+    }
+}
+
+impl Cell {
+    /// `None` if the cell is unplaced or the annotation is malformed.
+    pub fn placement(&self) -> Option<Placement> {
+        self.metadata(PHYSICAL, "placement")?.ok()
+    }
+
+    pub fn set_placement(&mut self, placement: Option<Placement>) {
+        self.set_metadata(PHYSICAL, "placement", &placement).unwrap();
+    }
+
+    /// Name of the module region the cell is constrained to.
+    pub fn region(&self) -> Option<String> {
+        self.metadata(PHYSICAL, "region")?.ok()
+    }
+
+    pub fn set_region(&mut self, region: Option<&str>) {
+        self.set_metadata(PHYSICAL, "region", &region).unwrap();
+    }
+}
+
+impl Port {
+    pub fn pin(&self) -> Option<Pin> {
+        self.metadata(PHYSICAL, "pin")?.ok()
+    }
+
+    pub fn set_pin(&mut self, pin: Option<Pin>) {
+        self.set_metadata(PHYSICAL, "pin", &pin).unwrap();
+    }
+}
+
+impl Net {
+    pub fn parasitics(&self) -> Option<Parasitics> {
+        self.metadata(PHYSICAL, "parasitics")?.ok()
+    }
+
+    pub fn set_parasitics(&mut self, parasitics: Option<Parasitics>) {
+        self.set_metadata(PHYSICAL, "parasitics", &parasitics).unwrap();
+    }
+}
+
+impl Module {
+    pub fn die(&self) -> Option<Rect> {
+        self.metadata(PHYSICAL, "die")?.ok()
+    }
+
+    pub fn set_die(&mut self, die: Option<Rect>) {
+        self.set_metadata(PHYSICAL, "die", &die).unwrap();
+    }
+
+    /// Named placement regions, empty if there are none or the annotation is malformed.
+    pub fn regions(&self) -> IndexMap<String, Rect> {
+        self.metadata(PHYSICAL, "regions").and_then(Result::ok).unwrap_or_default()
+    }
+
+    /// Adds or replaces a region, or removes it for `None`.
+    pub fn set_region(&mut self, name: &str, region: Option<Rect>) {
+        let mut regions = self.regions();
+        match region {
+            Some(region) => _ = regions.insert(name.to_string(), region),
+            None => _ = regions.shift_remove(name),
+        }
+        let regions = (!regions.is_empty()).then_some(regions);
+        self.set_metadata(PHYSICAL, "regions", &regions).unwrap();
+    }
+
+    /// Checks that annotations decode, placements and pins lie on the die, and constrained
+    /// cells sit inside known regions.
+    pub fn validate_physical(&self) -> Vec<PhysicalIssue> {
+        let mut issues = Vec::new();
+        let die: Option<Rect> = get(self, "die", None, &mut issues);
+        let regions: IndexMap<String, Rect> = get(self, "regions", None, &mut issues).unwrap_or_default();
+        for (name, port) in self.ports.iter() {
+            let pin: Option<Pin> = get(port, "pin", Some(name), &mut issues);
+            if let (Some(pin), Some(die)) = (pin, die)
+                && !die.contains(pin.x, pin.y)
+            {
+                issues.push(PhysicalIssue::PinOutsideDie(name.to_string()));
+            }
+        }
+        for (name, cell) in self.cells.iter() {
+            let placement: Option<Placement> = get(cell, "placement", Some(name), &mut issues);
+            let region: Option<String> = get(cell, "region", Some(name), &mut issues);
+            if let (Some(placement), Some(die)) = (placement, die)
+                && !die.contains(placement.x, placement.y)
+            {
+                issues.push(PhysicalIssue::CellOutsideDie(name.to_string()));
+            }
+            match region.map(|region| (regions.get(&region).copied(), region)) {
+                Some((None, region)) => issues.push(PhysicalIssue::UnknownRegion { cell: name.to_string(), region }),
+                Some((Some(rect), region)) if placement.is_some_and(|placement| !rect.contains(placement.x, placement.y)) => {
+                    issues.push(PhysicalIssue::CellOutsideRegion { cell: name.to_string(), region })
+                }
+                _ => {}
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    #[test]
+    fn test_physical_annotations() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let module = &mut netlist.modules["test_and"];
+        module.set_die(Some(Rect { x0: 0.0, y0: 0.0, x1: 10.0, y1: 10.0 }));
+        module.set_region("left", Some(Rect { x0: 0.0, y0: 0.0, x1: 5.0, y1: 10.0 }));
+        module.ports["a"].set_pin(Some(Pin { x: 0.0, y: 5.0, layer: Some("M2".to_string()) }));
+        module.ports["b"].set_pin(Some(Pin { x: -1.0, y: 5.0, layer: None }));
+        let name = module.cells.keys().next().unwrap().clone();
+        let cell = &mut module.cells[&name];
+        cell.set_placement(Some(Placement { x: 7.0, y: 2.0, fixed: false }));
+        cell.set_region(Some("left"));
+        assert_eq!(cell.placement().unwrap().x, 7.0);
+        assert_eq!(
+            module.validate_physical(),
+            [
+                PhysicalIssue::PinOutsideDie("b".to_string()),
+                PhysicalIssue::CellOutsideRegion { cell: name.to_string(), region: "left".to_string() }
+            ]
+        );
+
+        module.set_region("left", None);
+        module.ports["b"].set_pin(None);
+        module.cells[&name].attributes.insert("x-physical:placement".to_string(), "{\"x\": 1}".into());
+        assert_eq!(
+            module.validate_physical(),
+            [
+                PhysicalIssue::Malformed { object: Some(name.to_string()), key: "placement" },
+                PhysicalIssue::UnknownRegion { cell: name.to_string(), region: "left".to_string() }
+            ]
+        );
+        assert!(module.regions().is_empty());
+        assert!(!module.attributes.contains_key("x-physical:regions"));
+    }
+}
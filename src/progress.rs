@@ -0,0 +1,104 @@
+// Progress reporting for long-running operations. With the `tracing` feature, parsing,
+// indexing, flattening, every transformation pass, the pass manager and scripts also emit
+// spans and events, carrying module names, counts and durations, to whatever subscriber the
+// host application installs. Passes on a single [`crate::Module`] do not know its name, and
+// rely on the span of the pass manager or script running them to carry it.
+
+use std::io;
+
+/// How far an operation has got, passed to progress callbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    pub operation: &'a str,
+    /// The item just finished, like a module or a pass.
+    pub item: Option<&'a str>,
+    pub done: usize,
+    /// `0` when the total is not known.
+    pub total: usize,
+}
+
+/// A callback that can be shared between the threads of an operation.
+pub type ProgressCallback = Box<dyn Fn(Progress<'_>) + Send + Sync>;
+
+/// Bytes read between two reports of a [`ProgressReader`].
+pub const READ_INTERVAL: usize = 1 << 20;
+
+/// Wraps a reader to report the bytes read so far, every [`READ_INTERVAL`] bytes and at the
+/// end, so parsing a huge netlist with [`crate::Netlist::from_reader`] can show progress.
+pub struct ProgressReader<R, F> {
+    inner: R,
+    total: usize,
+    done: usize,
+    reported: usize,
+    callback: F,
+}
+
+impl<R: io::Read, F: FnMut(Progress<'_>)> ProgressReader<R, F> {
+    /// Reads from `inner`, `total` bytes long if known and `0` otherwise.
+    pub fn new(inner: R, total: usize, callback: F) -> Self {
+        Self { inner, total, done: 0, reported: 0, callback }
+    }
+
+    fn report(&mut self) {
+        self.reported = self.done;
+        (self.callback)(Progress { operation: "read", item: None, done: self.done, total: self.total });
+    }
+}
+
+impl<R: io::Read, F: FnMut(Progress<'_>)> io::Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.done += read;
+        if (read == 0 && self.reported != self.done) || self.done - self.reported >= READ_INTERVAL {
+            self.report();
+        }
+        Ok(read)
+    }
+}
+
+/// An entered span that logs how long it was entered when dropped.
+#[cfg(feature = "tracing")]
+pub(crate) struct TimedSpan {
+    _span: tracing::span::EnteredSpan,
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl TimedSpan {
+    pub(crate) fn enter(span: tracing::Span) -> Self {
+        Self { _span: span.entered(), start: std::time::Instant::now() }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for TimedSpan {
+    fn drop(&mut self) {
+        tracing::info!(duration = ?self.start.elapsed(), "done");
+    }
+}
+
+/// Opens an info span with the given name and fields until the end of the enclosing block,
+/// logging its duration on the way out. Expands to nothing without the `tracing` feature.
+macro_rules! pass_span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = $crate::progress::TimedSpan::enter(tracing::info_span!($name $(, $($fields)*)?));
+    };
+}
+
+pub(crate) use pass_span;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    #[test]
+    fn test_progress_reader() {
+        let input = std::fs::read("testdata/undefined.json").unwrap();
+        let mut reports = Vec::new();
+        let reader = ProgressReader::new(input.as_slice(), input.len(), |progress| reports.push((progress.done, progress.total)));
+        Netlist::from_reader(reader).unwrap();
+        assert_eq!(reports, [(input.len(), input.len())]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::cse::{MergeReport, StrashReport};
+use crate::hierarchy::HierarchyError;
+use crate::rng::SeedPolicy;
+use crate::{Bit, Module, Net, Netlist};
+
+/// An object of the design before transformation: a cell, or a bit named like `net[3]`, of
+/// `module`, reached through the instances of `path` when it was inlined by flattening.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub module: String,
+    pub path: Vec<String>,
+    pub name: String,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instance in self.path.iter() {
+            write!(f, "{}.", instance)?;
+        }
+        write!(f, "{} ({})", self.name, self.module)
+    }
+}
+
+/// Which original cells and bits every cell and signal bit of a transformed module came
+/// from. Cells merged into another and bits replaced by another pass their origins on, so
+/// a survivor may have several.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    pub cells: IndexMap<String, Vec<Origin>>,
+    pub bits: IndexMap<Bit, Vec<Origin>>,
+}
+
+fn add(origins: &mut Vec<Origin>, more: impl IntoIterator<Item = Origin>) {
+    for origin in more {
+        if !origins.contains(&origin) {
+            origins.push(origin);
+        }
+    }
+}
+
+impl Provenance {
+    /// Every cell and signal bit of `module`, named `name`, as its own origin.
+    pub fn new(name: &str, module: &Module) -> Self {
+        let mut provenance = Self::default();
+        for (net_name, net) in module.nets.iter() {
+            provenance.record_net(name, &[], net_name, net);
+        }
+        for cell in module.cells.keys() {
+            provenance.record_cell(cell, Origin { module: name.to_string(), path: Vec::new(), name: cell.to_string() });
+        }
+        provenance
+    }
+
+    pub fn cell(&self, name: &str) -> &[Origin] {
+        self.cells.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn bit(&self, bit: &Bit) -> &[Origin] {
+        self.bits.get(bit).map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn record_cell(&mut self, cell: &str, origin: Origin) {
+        add(self.cells.entry(cell.to_string()).or_default(), [origin]);
+    }
+
+    /// Records the signal bits of `net`, as they are now, as coming from the net `name` of
+    /// `module` below `path`.
+    pub(crate) fn record_net(&mut self, module: &str, path: &[&str], name: &str, net: &Net) {
+        for (index, bit) in net.bits.iter().enumerate().filter(|(_, bit)| matches!(bit, Bit::Signal(_))) {
+            let name = if net.bits.len() > 1 { format!("{}[{}]", name, index + net.offset) } else { name.to_string() };
+            let origin = Origin { module: module.to_string(), path: path.iter().map(|instance| instance.to_string()).collect(), name };
+            add(self.bits.entry(*bit).or_default(), [origin]);
+        }
+    }
+
+    /// Moves the origins of cell `removed` to `kept`, or drops them when nothing takes its place.
+    pub(crate) fn merge_cell(&mut self, removed: &str, kept: Option<&str>) {
+        let origins = self.cells.shift_remove(removed).unwrap_or_default();
+        if let Some(kept) = kept {
+            add(self.cells.entry(kept.to_string()).or_default(), origins);
+        }
+    }
+
+    /// Moves the origins of every bit `map` replaces to its replacement.
+    pub(crate) fn substitute(&mut self, map: &HashMap<Bit, Bit>) {
+        for (bit, replacement) in map.iter() {
+            let origins = self.bits.shift_remove(bit).unwrap_or_default();
+            if matches!(replacement, Bit::Signal(_)) {
+                add(self.bits.entry(*replacement).or_default(), origins);
+            }
+        }
+    }
+}
+
+impl Module {
+    /// [`Module::merge_redundant_logic`], passing the origins of merged cells and bits on to
+    /// those they were merged into.
+    pub fn merge_redundant_logic_tracked(&mut self, provenance: &mut Provenance, seed: u64, policy: SeedPolicy) -> MergeReport {
+        self.merge_redundant_logic_with(Some(provenance), seed, policy)
+    }
+
+    /// [`Module::strash`], passing the origins of removed gates and bits on to their
+    /// replacements.
+    pub fn strash_tracked(&mut self, provenance: &mut Provenance) -> StrashReport {
+        self.strash_with(Some(provenance))
+    }
+}
+
+impl Netlist {
+    /// [`Netlist::flatten`], also returning where every cell and signal bit of the flat module
+    /// came from in the hierarchy.
+    pub fn flatten_tracked(&self, top: &str) -> Result<(Module, Provenance), HierarchyError> {
+        let mut provenance = Provenance::default();
+        let flat = self.flatten_with(top, Some(&mut provenance))?;
+        Ok((flat, provenance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_tracked() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let top = netlist.top_modules()[0].to_string();
+        let (flat, provenance) = netlist.flatten_tracked(&top).unwrap();
+        assert_eq!(provenance.cells.len(), flat.cells.len());
+        for (name, _) in flat.cells.iter() {
+            let origin = &provenance.cell(name)[0];
+            assert_eq!(name, &origin.path.iter().chain([&origin.name]).cloned().collect::<Vec<_>>().join("."));
+        }
+        for bit in flat.ports.values().flat_map(|port| port.bits.iter()).filter(|bit| matches!(bit, Bit::Signal(_))) {
+            assert!(provenance.bit(bit).iter().any(|origin| origin.module == top && origin.path.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_strash_tracked() {
+        let mut netlist = Netlist::from_value(serde_json::json!({
+            "creator": "test",
+            "modules": { "top": {
+                "ports": { "a": { "direction": "input", "bits": [2, 3] }, "y": { "direction": "output", "bits": [4, 5] } },
+                "cells": {
+                    "and0": { "type": "$_AND_", "connections": { "A": [2], "B": [3], "Y": [4] } },
+                    "and1": { "type": "$_AND_", "connections": { "A": [2], "B": [3], "Y": [5] } }
+                },
+                "netnames": { "y": { "bits": [4, 5] } }
+            } }
+        }))
+        .unwrap();
+        let module = netlist.modules.get_mut("top").unwrap();
+        let mut provenance = Provenance::new("top", module);
+        assert_eq!(module.strash_tracked(&mut provenance).merged, 1);
+        let names: Vec<String> = provenance.cell("and0").iter().map(|origin| origin.to_string()).collect();
+        assert_eq!(names, ["and0 (top)", "and1 (top)"]);
+        let bits: Vec<&str> = provenance.bit(&Bit::Signal(4)).iter().map(|origin| origin.name.as_str()).collect();
+        assert_eq!(bits, ["y[0]", "y[1]"]);
+        assert!(provenance.bit(&Bit::Signal(5)).is_empty());
+    }
+}
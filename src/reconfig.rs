@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::hierarchy::HierarchyError;
+use crate::{Bit, Direction, Netlist};
+
+/// A reason a reconfigurable module cannot be swapped into a partition. Paths are instance
+/// paths from the top module, `u0.u1.name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoundaryViolation {
+    /// A port of the static-side wrapper that the reconfigurable module lacks.
+    MissingPort {
+        path: String,
+        port: String,
+    },
+    ExtraPort {
+        path: String,
+        port: String,
+    },
+    /// A port whose direction or width differs, the wrapper's first.
+    PortMismatch {
+        path: String,
+        port: String,
+        expected: (Direction, usize),
+        found: (Direction, usize),
+    },
+    /// An output bit wired straight to an input, a path through the partition with no
+    /// reconfigurable logic on it.
+    Feedthrough {
+        path: String,
+        output: String,
+        input: String,
+    },
+    /// A module instantiated inside the partition that the static design also instantiates,
+    /// so it cannot change with the partition.
+    SharedModule {
+        path: String,
+        module: String,
+    },
+}
+
+impl fmt::Display for BoundaryViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPort { path, port } => write!(f, "{}: port {} of the wrapper is missing", path, port),
+            Self::ExtraPort { path, port } => write!(f, "{}: port {} is not in the wrapper", path, port),
+            Self::PortMismatch { path, port, expected, found } => {
+                write!(f, "{}: port {} is {:?} [{}], the wrapper has {:?} [{}]", path, port, found.0, found.1, expected.0, expected.1)
+            }
+            Self::Feedthrough { path, output, input } => write!(f, "{}: output {} is wired to input {}", path, output, input),
+            Self::SharedModule { path, module } => write!(f, "{}: module {} is also used by the static design", path, module),
+        }
+    }
+}
+
+impl Netlist {
+    /// Checks that `variant` can be loaded into the reconfigurable partition instantiated at
+    /// `path` below `top`: its ports must match the wrapper bit for bit, the module of the
+    /// instance when the netlist defines it and the instance connections otherwise, and none
+    /// of its logic may pass straight through or be shared with the static design.
+    pub fn check_partition(&self, top: &str, path: &[&str], variant: &str) -> Result<Vec<BoundaryViolation>, HierarchyError> {
+        let mut module = self.modules.get(top).ok_or_else(|| HierarchyError::UnknownModule(top.to_string()))?;
+        let (last, parents) = path.split_last().ok_or(HierarchyError::EmptyPath)?;
+        let mut parent = top;
+        for name in parents {
+            let cell = module
+                .cells
+                .get(*name)
+                .ok_or_else(|| HierarchyError::UnknownCell { module: parent.to_string(), cell: name.to_string() })?;
+            parent = &cell.module;
+            module = self.modules.get(parent).ok_or_else(|| HierarchyError::UnknownModule(parent.to_string()))?;
+        }
+        let instance =
+            module.cells.get(*last).ok_or_else(|| HierarchyError::UnknownCell { module: parent.to_string(), cell: last.to_string() })?;
+        let reconfigurable = self.modules.get(variant).ok_or_else(|| HierarchyError::UnknownModule(variant.to_string()))?;
+        let partition = path.join(".");
+        let mut violations = Vec::new();
+
+        let expected: IndexMap<&str, (Direction, usize)> = match self.modules.get(&instance.module) {
+            Some(wrapper) => wrapper.ports.iter().map(|(name, port)| (name.as_str(), (port.direction.clone(), port.bits.len()))).collect(),
+            None => (instance.connections.iter())
+                .map(|(name, bits)| (name.as_str(), (instance.port_directions.get(name).cloned().unwrap_or(Direction::InOut), bits.len())))
+                .collect(),
+        };
+        for (name, expected) in expected.iter() {
+            let path = partition.clone();
+            match reconfigurable.ports.get(*name) {
+                None => violations.push(BoundaryViolation::MissingPort { path, port: name.to_string() }),
+                Some(port) if (&port.direction, port.bits.len()) != (&expected.0, expected.1) => {
+                    let found = (port.direction.clone(), port.bits.len());
+                    violations.push(BoundaryViolation::PortMismatch { path, port: name.to_string(), expected: expected.clone(), found });
+                }
+                Some(_) => {}
+            }
+        }
+        for name in reconfigurable.ports.keys().filter(|name| !expected.contains_key(name.as_str())) {
+            violations.push(BoundaryViolation::ExtraPort { path: partition.clone(), port: name.to_string() });
+        }
+
+        let mut inputs: HashMap<&Bit, String> = HashMap::new();
+        for (name, port) in reconfigurable.ports.iter().filter(|(_, port)| port.direction == Direction::Input) {
+            for (index, bit) in port.bits.iter().enumerate().filter(|(_, bit)| matches!(bit, Bit::Signal(_))) {
+                inputs.entry(bit).or_insert_with(|| format!("{}[{}]", name, index));
+            }
+        }
+        for (name, port) in reconfigurable.ports.iter().filter(|(_, port)| port.direction == Direction::Output) {
+            for (index, bit) in port.bits.iter().enumerate() {
+                if let Some(input) = inputs.get(bit) {
+                    let output = format!("{}[{}]", name, index);
+                    violations.push(BoundaryViolation::Feedthrough { path: partition.clone(), output, input: input.clone() });
+                }
+            }
+        }
+
+        // Modules of the static design, everything below `top` except the partition itself.
+        let mut fixed: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<(&str, Vec<&str>, Vec<&str>)> = vec![(top, Vec::new(), Vec::new())];
+        while let Some((name, prefix, mut ancestors)) = stack.pop() {
+            if ancestors.contains(&name) {
+                continue;
+            }
+            fixed.insert(name);
+            ancestors.push(name);
+            for (cell_name, cell) in self.modules[name].cells.iter().filter(|(_, cell)| self.modules.contains_key(&cell.module)) {
+                let mut prefix = prefix.clone();
+                prefix.push(cell_name.as_str());
+                if prefix != path {
+                    stack.push((cell.module.as_str(), prefix, ancestors.clone()));
+                }
+            }
+        }
+        let mut seen: HashSet<&str> = HashSet::from([variant]);
+        let mut stack = vec![(variant, partition.clone())];
+        while let Some((name, prefix)) = stack.pop() {
+            for (cell_name, cell) in self.modules[name].cells.iter().filter(|(_, cell)| self.modules.contains_key(&cell.module)) {
+                let path = format!("{}.{}", prefix, cell_name);
+                if fixed.contains(cell.module.as_str()) {
+                    violations.push(BoundaryViolation::SharedModule { path, module: cell.module.to_string() });
+                } else if seen.insert(&cell.module) {
+                    stack.push((&cell.module, path));
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+    use crate::{Cell, Module, Port};
+
+    /// A top module with a synchronizer and a partition `u_rp` whose wrapper, a blackbox,
+    /// has a 2-bit input `a` and a 2-bit output `y`.
+    fn design() -> Netlist {
+        let mut netlist = Netlist::new("test");
+        let mut wrapper = Module::new();
+        wrapper.attributes.insert("blackbox".to_string(), "00000000000000000000000000000001".into());
+        wrapper.ports.insert("a".into(), Port::new(Direction::Input, vec![Bit::Signal(2), Bit::Signal(3)]));
+        wrapper.ports.insert("y".into(), Port::new(Direction::Output, vec![Bit::Signal(4), Bit::Signal(5)]));
+        netlist.modules.insert("rp".into(), wrapper);
+        let mut builder = Module::builder();
+        let a = builder.input("a", 1).unwrap();
+        let y = builder.output("y", 1).unwrap();
+        builder.cell("ff", CellBuilder::new("$_FF_").input("D", a).output("Q", y)).unwrap();
+        netlist.modules.insert("sync".into(), builder.build());
+        let mut top = Module::new();
+        top.cells.insert("u_sync".into(), Cell::new("sync"));
+        top.cells.insert("u_rp".into(), Cell::new("rp"));
+        netlist.modules.insert("top".into(), top);
+        netlist
+    }
+
+    #[test]
+    fn test_check_partition() {
+        let mut netlist = design();
+        let mut builder = Module::builder();
+        let a = builder.input("a", 2).unwrap();
+        let y = builder.output("y", 2).unwrap();
+        builder.cell("inv", CellBuilder::new("$not").input("A", a).output("Y", y)).unwrap();
+        netlist.modules.insert("rm_good".into(), builder.build());
+        assert_eq!(netlist.check_partition("top", &["u_rp"], "rm_good").unwrap(), []);
+
+        let mut builder = Module::builder();
+        let a = builder.input("a", 2).unwrap();
+        builder.output("y", 1).unwrap();
+        builder.input("b", 1).unwrap();
+        builder.cell("u_sync", CellBuilder::new("sync")).unwrap();
+        let mut bad = builder.build();
+        bad.ports["y"].bits[0] = a[1];
+        netlist.modules.insert("rm_bad".into(), bad);
+        let path = "u_rp".to_string();
+        assert_eq!(
+            netlist.check_partition("top", &["u_rp"], "rm_bad").unwrap(),
+            [
+                BoundaryViolation::PortMismatch {
+                    path: path.clone(),
+                    port: "y".to_string(),
+                    expected: (Direction::Output, 2),
+                    found: (Direction::Output, 1)
+                },
+                BoundaryViolation::ExtraPort { path: path.clone(), port: "b".to_string() },
+                BoundaryViolation::Feedthrough { path: path.clone(), output: "y[0]".to_string(), input: "a[1]".to_string() },
+                BoundaryViolation::SharedModule { path: "u_rp.u_sync".to_string(), module: "sync".to_string() },
+            ]
+        );
+        assert!(matches!(netlist.check_partition("top", &["u_x"], "rm_bad"), Err(HierarchyError::UnknownCell { .. })));
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+
+use crate::Netlist;
+use crate::gates::gate;
+use crate::progress::pass_span;
+
+/// A cell left with its old type because it connects ports its new type lacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortMismatch {
+    pub module: String,
+    pub cell: String,
+    pub from: String,
+    pub to: String,
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetargetReport {
+    /// Cells rewritten, by old type.
+    pub retargeted: IndexMap<String, usize>,
+    pub mismatches: Vec<PortMismatch>,
+    /// New types whose ports are not known, so their cells were rewritten unchecked.
+    pub unchecked: Vec<String>,
+}
+
+impl Netlist {
+    /// Ports of a cell type: those of the module of that name, of the gate-level primitive,
+    /// or else of the cells of that type already in the design. `None` when there are none.
+    fn known_ports(&self, cell_type: &str) -> Option<HashSet<String>> {
+        if let Some(module) = self.modules.get(cell_type) {
+            return Some(module.ports.keys().map(String::from).collect());
+        }
+        if let Some(gate) = gate(cell_type) {
+            return Some(gate.inputs.iter().chain([&gate.output]).map(|port| port.to_string()).collect());
+        }
+        let cells = self.modules.values().flat_map(|module| module.cells.values()).filter(|cell| cell.module == cell_type);
+        let ports: HashSet<String> = cells.flat_map(|cell| cell.connections.keys().map(String::from)).collect();
+        (!ports.is_empty()).then_some(ports)
+    }
+
+    /// Rewrites the type of every cell in every module according to `map`, from old type to
+    /// new, such as primitive renames between device families. Cells connecting a port the
+    /// new type does not have keep their old type and are reported.
+    pub fn retarget_cells(&mut self, map: &IndexMap<String, String>) -> RetargetReport {
+        pass_span!("retarget_cells", modules = self.modules.len(), types = map.len());
+        let mut report = RetargetReport::default();
+        let targets: IndexMap<&String, Option<HashSet<String>>> = map.values().map(|target| (target, self.known_ports(target))).collect();
+        report.unchecked = targets.iter().filter(|(_, ports)| ports.is_none()).map(|(target, _)| target.to_string()).collect();
+        for (module_name, module) in self.modules.iter_mut() {
+            for (name, cell) in module.cells.iter_mut() {
+                let Some(target) = map.get(&cell.module) else {
+                    continue;
+                };
+                if let Some(ports) = &targets[target] {
+                    let missing: Vec<String> =
+                        cell.connections.keys().filter(|port| !ports.contains(port.as_str())).map(String::from).collect();
+                    if !missing.is_empty() {
+                        report.mismatches.push(PortMismatch {
+                            module: module_name.to_string(),
+                            cell: name.to_string(),
+                            from: cell.module.to_string(),
+                            to: target.clone(),
+                            missing,
+                        });
+                        continue;
+                    }
+                }
+                *report.retargeted.entry(cell.module.to_string()).or_default() += 1;
+                cell.module = target.into();
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Module;
+    use crate::builder::{CellBuilder, NetlistBuilder};
+
+    #[test]
+    fn test_retarget_cells() {
+        let mut builder = Module::builder();
+        let [a, b] = ["a", "b"].map(|name| builder.input(name, 1).unwrap());
+        let [y, z] = ["y", "z"].map(|name| builder.output(name, 1).unwrap());
+        builder.cell("and", CellBuilder::new("AND2").input("A", a.clone()).input("B", b.clone()).output("Y", y)).unwrap();
+        builder.cell("mux", CellBuilder::new("MUX2").input("A", a).input("B", b).input("SEL", vec![]).output("Y", z)).unwrap();
+        let mut netlist = NetlistBuilder::new("test").top("top", builder).unwrap().build();
+
+        let map = IndexMap::from([
+            ("AND2".to_string(), "$_AND_".to_string()),
+            ("MUX2".to_string(), "$_MUX_".to_string()),
+            ("BUF".to_string(), "VENDOR_BUF".to_string()),
+        ]);
+        let report = netlist.retarget_cells(&map);
+        assert_eq!(report.retargeted, IndexMap::from([("AND2".to_string(), 1)]));
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!((report.mismatches[0].cell.as_str(), report.mismatches[0].missing.as_slice()), ("mux", ["SEL".to_string()].as_slice()));
+        assert_eq!(report.unchecked, ["VENDOR_BUF"]);
+        assert_eq!(netlist.modules["top"].cells["and"].module, "$_AND_");
+        assert_eq!(netlist.modules["top"].cells["mux"].module, "MUX2");
+    }
+}
@@ -0,0 +1,83 @@
+//! Every randomized operation takes an explicit seed and draws from the generator below, never
+//! from the operating system or the hasher of a `HashMap`, so the same netlist, options and
+//! seed give the same result on every run, platform and Rust release. The seeded operations
+//! are fault campaigns, logic locking, the random simulation guessing invariants for proofs,
+//! and the random simulation checking merged logic. Each also takes a `SeedPolicy`, through
+//! its options or as an argument, so a CI job can pin or salt the seeds of the operations it
+//! runs without touching the seeds themselves.
+
+/// The splitmix64 finalizer, scrambling every bit of `z` into every bit of the result.
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Small deterministic generator (splitmix64), so seeded passes give the same result on every
+/// platform and Rust release.
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The generator an operation given `seed` draws from under `policy`.
+    pub(crate) fn from_policy(policy: SeedPolicy, seed: u64) -> Self {
+        Self::new(policy.apply(seed))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.0)
+    }
+
+    pub(crate) fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+
+    /// Uniform value in `0..bound`, `bound` must be non-zero.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// How seeded operations turn the seed they are given into the seed they use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeedPolicy {
+    /// Use the given seed.
+    #[default]
+    Explicit,
+    /// Use this seed whatever the caller gives.
+    Fixed(u64),
+    /// Mix this value into the given seed, to try other random choices reproducibly.
+    Salted(u64),
+}
+
+impl SeedPolicy {
+    /// The seed an operation given `seed` uses.
+    pub fn apply(self, seed: u64) -> u64 {
+        match self {
+            Self::Explicit => seed,
+            Self::Fixed(fixed) => fixed,
+            Self::Salted(salt) => SplitMix64::new(seed ^ salt.rotate_left(32)).next_u64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_policy() {
+        assert_eq!(SeedPolicy::default().apply(7), 7);
+        assert_eq!(SeedPolicy::Fixed(3).apply(7), 3);
+        let salted = SeedPolicy::Salted(1);
+        assert_eq!(salted.apply(7), salted.apply(7));
+        assert_ne!(salted.apply(7), salted.apply(8));
+        assert_ne!(salted.apply(7), SeedPolicy::Salted(2).apply(7));
+        assert_eq!(SplitMix64::from_policy(salted, 7).next_u64(), SplitMix64::new(salted.apply(7)).next_u64());
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::{BTreeSet, HashMap};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::ecc::{EccCode, EccRole};
+use crate::{gates, Bit, Cell, Direction, Module};
+
+const COMPARATORS: &[&str] = &["$eq", "$ne", "$eqx", "$nex", "$xor", "$xnor", "$_XOR_", "$_XNOR_"];
+const XOR_CELLS: &[&str] = &["$xor", "$xnor", "$reduce_xor", "$reduce_xnor", "$_XOR_", "$_XNOR_"];
+
+/// A safety mechanism detected around a register or output.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mechanism {
+    /// Part of three registers voted by majority logic.
+    Tmr { registers: Vec<String> },
+    /// Duplicated register checked against its partner by a comparator.
+    Duplication { partner: String, comparator: String },
+    /// Checked against a stored parity bit by a parity tree ending in `checker`.
+    Parity { checker: String },
+    /// Data or check bits of an error correcting code computed by `trees`.
+    Ecc { code: EccCode, role: EccRole, trees: Vec<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    pub name: String,
+    pub mechanisms: Vec<Mechanism>,
+}
+
+impl Coverage {
+    pub fn is_protected(&self) -> bool {
+        !self.mechanisms.is_empty()
+    }
+}
+
+/// Which registers and output bits of a module are covered by a safety mechanism.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetyReport {
+    pub registers: Vec<Coverage>,
+    /// Output bits, named `port[offset]`.
+    pub outputs: Vec<Coverage>,
+}
+
+impl SafetyReport {
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    /// Fraction of registers covered by at least one mechanism.
+    pub fn register_coverage(&self) -> f64 {
+        match self.registers.len() {
+            0 => 1.0,
+            total => self.registers.iter().filter(|coverage| coverage.is_protected()).count() as f64 / total as f64,
+        }
+    }
+}
+
+fn output_bits<'a>(cell: &'a Cell, ports: &'a [&str]) -> impl Iterator<Item = Bit> + 'a {
+    ports.iter().filter_map(|port| cell.connections.get(*port)).flatten().copied().filter(|bit| matches!(bit, Bit::Signal(_)))
+}
+
+fn clock(cell: &Cell) -> Option<&Vec<Bit>> {
+    cell.connections.get("C").or_else(|| cell.connections.get("CLK"))
+}
+
+/// Registers that could be redundant copies of each other: same type, parameters and clock.
+fn similar(a: &Cell, b: &Cell) -> bool {
+    a.module == b.module && a.parameters == b.parameters && clock(a) == clock(b)
+}
+
+/// Truth table of a three input majority function.
+fn majority() -> Vec<bool> {
+    (0..8u32).map(|values| values.count_ones() >= 2).collect()
+}
+
+/// A two input cell computing `function`, given as its truth table.
+fn two_input(cell: &Cell, function: [bool; 4]) -> Option<(Vec<Bit>, Bit)> {
+    let (inputs, output, table) = gates::function(cell)?;
+    (table == function).then_some((inputs, output))
+}
+
+const AND: [bool; 4] = [false, false, false, true];
+const OR: [bool; 4] = [false, true, true, true];
+
+struct Context<'a> {
+    module: &'a Module,
+    /// Register driving every `Q` bit.
+    register_of: HashMap<Bit, &'a str>,
+    /// `D` bit loaded into every `Q` bit.
+    loaded_from: HashMap<Bit, Bit>,
+    /// Cells reading every bit.
+    readers: HashMap<Bit, Vec<&'a str>>,
+    /// Cell driving every bit.
+    driver_of: HashMap<Bit, &'a str>,
+}
+
+impl<'a> Context<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut context =
+            Self { module, register_of: HashMap::new(), loaded_from: HashMap::new(), readers: HashMap::new(), driver_of: HashMap::new() };
+        for (name, cell) in module.cells.iter() {
+            if cell.is_register() {
+                for bit in output_bits(cell, &["Q"]) {
+                    context.register_of.insert(bit, name);
+                }
+                if let (Some(d), Some(q)) = (cell.connections.get("D"), cell.connections.get("Q")) {
+                    context.loaded_from.extend(q.iter().copied().zip(d.iter().copied()).filter(|(q, _)| matches!(q, Bit::Signal(_))));
+                }
+            }
+            for (port, bits) in cell.connections.iter() {
+                for bit in bits.iter().filter(|bit| matches!(bit, Bit::Signal(_))) {
+                    match cell.port_directions.get(port) {
+                        Some(Direction::Output) => {
+                            context.driver_of.insert(*bit, name);
+                        }
+                        _ => context.readers.entry(*bit).or_default().push(name),
+                    }
+                }
+            }
+        }
+        context
+    }
+
+    /// Registers whose outputs `cell` reads directly.
+    fn registers_read_by(&self, cell: &Cell) -> BTreeSet<&'a str> {
+        cell.connections
+            .iter()
+            .filter(|(port, _)| cell.port_directions.get(*port) != Some(&Direction::Output))
+            .flat_map(|(_, bits)| bits.iter())
+            .filter_map(|bit| self.register_of.get(bit).copied())
+            .collect()
+    }
+
+    fn cell(&self, name: &str) -> &'a Cell {
+        &self.module.cells[name]
+    }
+
+    /// Whether two similar registers load the same value: the same `D` bits, or bits driven by
+    /// cells of the same types, as duplicated logic would.
+    fn redundant(&self, a: &str, b: &str) -> bool {
+        let inputs = |cell: &Cell| -> Option<Vec<String>> {
+            output_bits(cell, &["D"]).map(|bit| self.driver_of.get(&bit).map(|driver| self.cell(driver).module.to_string())).collect()
+        };
+        let (a, b) = (self.cell(a), self.cell(b));
+        similar(a, b) && (a.connections.get("D") == b.connections.get("D") || inputs(a).is_some_and(|a| Some(a) == inputs(b)))
+    }
+
+    /// Three distinct registers, redundant copies of each other.
+    fn triplet(&self, registers: &BTreeSet<&'a str>) -> Option<[&'a str; 3]> {
+        let triplet: [&str; 3] = registers.iter().copied().collect::<Vec<_>>().try_into().ok()?;
+        (self.redundant(triplet[0], triplet[1]) && self.redundant(triplet[0], triplet[2])).then_some(triplet)
+    }
+
+    /// Leaves and cells of the tree of OR gates rooted at `cell`.
+    fn or_tree(&self, cell: &'a str, inputs: &[Bit], leaves: &mut BTreeSet<Bit>, cells: &mut Vec<&'a str>) {
+        cells.push(cell);
+        for bit in inputs {
+            let driver = self.driver_of.get(bit).filter(|driver| !cells.contains(driver));
+            match driver.and_then(|driver| Some((*driver, two_input(self.cell(driver), OR)?))) {
+                Some((driver, (inputs, _))) => self.or_tree(driver, &inputs, leaves, cells),
+                None => {
+                    leaves.insert(*bit);
+                }
+            }
+        }
+    }
+
+    /// Triplets of redundant registers voted together, either by a single cell computing the
+    /// majority of their outputs or by `(a & b) | (a & c) | (b & c)` out of AND and OR gates.
+    /// Returns the cells doing the voting, along with the registers they vote.
+    fn tmr(&self, mechanisms: &mut HashMap<&'a str, BTreeSet<Mechanism>>) -> HashMap<&'a str, Vec<String>> {
+        let mut voters = HashMap::new();
+        let mut pairs: HashMap<Bit, ([&str; 2], &str)> = HashMap::new();
+        for (name, cell) in self.module.cells.iter() {
+            let Some((inputs, output, table)) = gates::function(cell) else {
+                continue;
+            };
+            let Some(registers) = inputs.iter().map(|bit| self.register_of.get(bit).copied()).collect::<Option<BTreeSet<&str>>>() else {
+                continue;
+            };
+            if table == majority()
+                && let Some(triplet) = self.triplet(&registers)
+            {
+                add_tmr(mechanisms, &triplet);
+                voters.insert(name.as_str(), triplet.map(str::to_string).to_vec());
+            } else if table == AND
+                && let [a, b] = registers.into_iter().collect::<Vec<_>>()[..]
+                && self.redundant(a, b)
+            {
+                pairs.insert(output, ([a, b], name));
+            }
+        }
+        // Gate-level voters: an OR tree whose leaves are the ANDs of all three pairs of registers.
+        for (name, cell) in self.module.cells.iter() {
+            let Some((inputs, _)) = two_input(cell, OR) else {
+                continue;
+            };
+            let (mut leaves, mut cells) = (BTreeSet::new(), Vec::new());
+            self.or_tree(name, &inputs, &mut leaves, &mut cells);
+            let Some(ands) = leaves.iter().map(|bit| pairs.get(bit)).collect::<Option<Vec<_>>>() else {
+                continue;
+            };
+            let covered: BTreeSet<[&str; 2]> = ands.iter().map(|(pair, _)| *pair).collect();
+            let registers: BTreeSet<&str> = covered.iter().flatten().copied().collect();
+            if let Some(triplet) = self.triplet(&registers).filter(|_| ands.len() == 3 && covered.len() == 3) {
+                add_tmr(mechanisms, &triplet);
+                let registers = triplet.map(str::to_string).to_vec();
+                voters.extend(cells.into_iter().chain(ands.iter().map(|(_, and)| *and)).map(|voter| (voter, registers.clone())));
+            }
+        }
+        voters
+    }
+
+    /// Pairs of redundant registers compared against each other.
+    fn duplication(&self, mechanisms: &mut HashMap<&'a str, BTreeSet<Mechanism>>) {
+        for (name, cell) in self.module.cells.iter().filter(|(_, cell)| COMPARATORS.contains(&cell.module.as_str())) {
+            let registers: Vec<&str> = self.registers_read_by(cell).into_iter().collect();
+            let [a, b] = registers[..] else {
+                continue;
+            };
+            // A comparator feeding further XOR cells is more likely part of a parity tree.
+            if output_bits(cell, &["Y"]).any(|bit| {
+                self.readers.get(&bit).is_some_and(|readers| readers.iter().any(|r| XOR_CELLS.contains(&self.cell(r).module.as_str())))
+            }) {
+                continue;
+            }
+            if self.redundant(a, b) {
+                for (register, partner) in [(a, b), (b, a)] {
+                    let mechanism = Mechanism::Duplication { partner: partner.to_string(), comparator: name.to_string() };
+                    mechanisms.entry(register).or_default().insert(mechanism);
+                }
+            }
+        }
+    }
+
+    /// Registers read by a parity tree that checks them against a stored check bit, or the data
+    /// and check bits of a Hamming/SECDED code. The check bit is one of the registers read by
+    /// the tree, loading the parity of what the others load.
+    fn parity(&self, mechanisms: &mut HashMap<&'a str, BTreeSet<Mechanism>>) {
+        let report = self.module.ecc_structures();
+        let generators: HashMap<Bit, &Vec<Bit>> = report.parity.iter().map(|tree| (tree.output, &tree.inputs)).collect();
+        for tree in report.parity.iter() {
+            let Some(loaded) = tree.inputs.iter().map(|bit| self.loaded_from.get(bit).copied()).collect::<Option<Vec<Bit>>>() else {
+                continue;
+            };
+            let checked = (0..loaded.len()).any(|check| {
+                let mut data: Vec<Bit> = loaded.iter().enumerate().filter(|(index, _)| *index != check).map(|(_, bit)| *bit).collect();
+                data.sort();
+                generators.get(&loaded[check]).is_some_and(|inputs| **inputs == data)
+            });
+            if checked {
+                for register in tree.inputs.iter().filter_map(|bit| self.register_of.get(bit)) {
+                    mechanisms.entry(register).or_default().insert(Mechanism::Parity { checker: tree.root.clone() });
+                }
+            }
+        }
+        for code in report.codes.iter() {
+            for register in code.data.iter().chain(code.check.iter()).filter_map(|bit| self.register_of.get(bit)) {
+                let mechanism = Mechanism::Ecc { code: code.code, role: code.role, trees: code.trees.clone() };
+                mechanisms.entry(register).or_default().insert(mechanism);
+            }
+        }
+    }
+}
+
+fn add_tmr<'a>(mechanisms: &mut HashMap<&'a str, BTreeSet<Mechanism>>, triplet: &[&'a str]) {
+    let registers: Vec<String> = triplet.iter().map(|r| r.to_string()).collect();
+    for register in triplet {
+        mechanisms.entry(register).or_default().insert(Mechanism::Tmr { registers: registers.clone() });
+    }
+}
+
+impl Module {
+    /// Detects triple modular redundancy, duplication with comparison, parity and ECC protection
+    /// around the registers of this module, and reports which registers and output bits are
+    /// covered. Outputs inherit the mechanisms of the register driving them, and count as TMR
+    /// protected when driven by a voter.
+    pub fn safety_report(&self) -> SafetyReport {
+        let context = Context::new(self);
+        let mut mechanisms: HashMap<&str, BTreeSet<Mechanism>> = HashMap::new();
+        let voters = context.tmr(&mut mechanisms);
+        context.duplication(&mut mechanisms);
+        context.parity(&mut mechanisms);
+
+        let registers = self
+            .cells
+            .iter()
+            .filter(|(_, cell)| cell.is_register())
+            .map(|(name, _)| Coverage {
+                name: name.to_string(),
+                mechanisms: mechanisms.get(name.as_str()).map(|set| set.iter().cloned().collect()).unwrap_or_default(),
+            })
+            .collect();
+
+        let mut outputs = Vec::new();
+        for (port_name, port) in self.ports.iter().filter(|(_, port)| port.direction == Direction::Output) {
+            for (offset, bit) in port.bits.iter().enumerate() {
+                let mut covered: BTreeSet<Mechanism> = BTreeSet::new();
+                if let Some(driver) = context.driver_of.get(bit) {
+                    if let Some(register) = mechanisms.get(driver) {
+                        covered.extend(register.iter().cloned());
+                    }
+                    let cell = context.cell(driver);
+                    let fanin: Vec<&str> = cell
+                        .connections
+                        .iter()
+                        .filter(|(port, _)| cell.port_directions.get(*port) != Some(&Direction::Output))
+                        .flat_map(|(_, bits)| bits.iter().filter_map(|bit| context.driver_of.get(bit).copied()))
+                        .chain([*driver])
+                        .collect();
+                    if let Some(registers) = fanin.iter().find_map(|cell| voters.get(cell)) {
+                        covered.insert(Mechanism::Tmr { registers: registers.clone() });
+                    }
+                }
+                outputs.push(Coverage { name: format!("{}[{}]", port_name, offset), mechanisms: covered.into_iter().collect() });
+            }
+        }
+        SafetyReport { registers, outputs }
+    }
+}
+
+/// Per module safety reports of a whole design.
+pub type DesignSafetyReport = IndexMap<String, SafetyReport>;
+
+impl crate::Netlist {
+    pub fn safety_report(&self) -> DesignSafetyReport {
+        self.modules.iter().map(|(name, module)| (name.to_string(), module.safety_report())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmr::Voter;
+    use serde_json::json;
+
+    fn dff(d: u64, q: u64) -> serde_json::Value {
+        json!({
+            "type": "$_DFF_P_",
+            "port_directions": { "C": "input", "D": "input", "Q": "output" },
+            "connections": { "C": [2], "D": [d], "Q": [q] }
+        })
+    }
+
+    fn gate(kind: &str, inputs: &[(&str, u64)], y: u64) -> serde_json::Value {
+        let mut cell = json!({ "type": kind, "port_directions": { "Y": "output" }, "connections": { "Y": [y] } });
+        for (port, bit) in inputs {
+            cell["port_directions"][port] = json!("input");
+            cell["connections"][port] = json!([bit]);
+        }
+        cell
+    }
+
+    fn module(ports: serde_json::Value, cells: serde_json::Value) -> Module {
+        let netlist = crate::Netlist::from_value(json!({
+            "creator": "test",
+            "modules": { "top": { "ports": ports, "cells": cells } }
+        }))
+        .unwrap();
+        netlist.modules["top"].clone()
+    }
+
+    fn registers() -> Module {
+        let xor = |a: u64, b: u64, y: u64| gate("$_XOR_", &[("A", a), ("B", b)], y);
+        module(
+            json!({
+                "clk": { "direction": "input", "bits": [2] },
+                "d": { "direction": "input", "bits": [3, 4, 5] },
+                "q": { "direction": "output", "bits": [10, 11, 12] },
+                "err": { "direction": "output", "bits": [21] },
+                "p": { "direction": "output", "bits": [31] }
+            }),
+            json!({
+                "tmr": dff(3, 10),
+                "dup_a": dff(4, 11),
+                "dup_b": dff(4, 20),
+                "cmp": xor(11, 20, 21),
+                "p0": dff(3, 12),
+                "p1": dff(5, 13),
+                "gen": xor(3, 5, 40),
+                "p2": dff(40, 14),
+                "x0": xor(12, 13, 30),
+                "x1": xor(30, 14, 31)
+            }),
+        )
+    }
+
+    #[test]
+    fn test_safety_report() {
+        let mut module = registers();
+        module.triplicate(&["tmr"], &Voter::Gates).unwrap();
+        let report = module.safety_report();
+
+        let coverage: HashMap<&str, &Vec<Mechanism>> = report.registers.iter().map(|c| (c.name.as_str(), &c.mechanisms)).collect();
+        let tmr = Mechanism::Tmr { registers: vec!["tmr".to_string(), "tmr_tmr1".to_string(), "tmr_tmr2".to_string()] };
+        assert_eq!(coverage["tmr_tmr2"], &vec![tmr.clone()]);
+        assert_eq!(coverage["dup_a"], &vec![Mechanism::Duplication { partner: "dup_b".to_string(), comparator: "cmp".to_string() }]);
+        assert_eq!(coverage["p1"], &vec![Mechanism::Parity { checker: "x1".to_string() }]);
+        assert_eq!(report.register_coverage(), 1.0);
+
+        assert_eq!(report.outputs[0].name, "q[0]");
+        assert_eq!(report.outputs[0].mechanisms, [tmr]);
+        assert!(report.outputs[1].is_protected());
+        assert!(!report.outputs[3].is_protected());
+    }
+
+    #[test]
+    fn test_majority_cell() {
+        let ports = json!({ "clk": { "direction": "input", "bits": [2] }, "d": { "direction": "input", "bits": [3] } });
+        let lut = json!({
+            "type": "$lut",
+            "parameters": { "WIDTH": "00000000000000000000000000000011", "LUT": "11101000" },
+            "port_directions": { "A": "input", "Y": "output" },
+            "connections": { "A": [10, 11, 12], "Y": [13] }
+        });
+        let module = module(ports, json!({ "a": dff(3, 10), "b": dff(3, 11), "c": dff(3, 12), "vote": lut }));
+        let report = module.safety_report();
+        assert_eq!(report.register_coverage(), 1.0);
+        assert_eq!(report.registers[0].mechanisms, [Mechanism::Tmr { registers: vec!["a".into(), "b".into(), "c".into()] }]);
+    }
+
+    #[test]
+    fn test_unrelated_registers_are_not_protected() {
+        let ports = json!({ "clk": { "direction": "input", "bits": [2] }, "d": { "direction": "input", "bits": [3, 4, 5, 6] } });
+        let xor = |a: u64, b: u64, y: u64| gate("$_XOR_", &[("A", a), ("B", b)], y);
+        let module = module(
+            ports,
+            json!({
+                // A multiplexer over three registers is no voter, even with matching inputs.
+                "m0": dff(3, 10),
+                "m1": dff(3, 11),
+                "m2": dff(3, 12),
+                "mux": gate("$_MUX_", &[("A", 10), ("B", 11), ("S", 12)], 13),
+                // Nor is a gate-level majority over registers loading different values.
+                "v0": dff(3, 20),
+                "v1": dff(4, 21),
+                "v2": dff(5, 22),
+                "ab": gate("$_AND_", &[("A", 20), ("B", 21)], 23),
+                "ac": gate("$_AND_", &[("A", 20), ("B", 22)], 24),
+                "bc": gate("$_AND_", &[("A", 21), ("B", 22)], 25),
+                "or0": gate("$_OR_", &[("A", 23), ("B", 24)], 26),
+                "or1": gate("$_OR_", &[("A", 26), ("B", 25)], 27),
+                // The sum of an adder is an XOR of three registers without any stored parity.
+                "s0": dff(4, 30),
+                "s1": dff(5, 31),
+                "s2": dff(6, 32),
+                "x0": xor(30, 31, 33),
+                "x1": xor(33, 32, 34)
+            }),
+        );
+        let report = module.safety_report();
+        assert!(report.registers.iter().all(|coverage| !coverage.is_protected()), "{:?}", report.registers);
+        assert_eq!(report.register_coverage(), 0.0);
+    }
+}
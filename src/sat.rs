@@ -0,0 +1,584 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Not;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::limits::{Budget, LimitExceeded};
+
+/// A literal: a variable or its negation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: usize, positive: bool) -> Self {
+        Self((var as u32) << 1 | !positive as u32)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolverError {
+    /// The solver process could not be run.
+    Process { program: String, error: String },
+    /// The solver answered neither satisfiable nor unsatisfiable, or gave no model.
+    Output { program: String, output: String },
+    /// The solver was stopped by its [`Budget`].
+    Interrupted(LimitExceeded),
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Process { program, error } => write!(f, "cannot run {}: {}", program, error),
+            Self::Output { program, output } => write!(f, "unexpected output from {}: {:?}", program, output),
+            Self::Interrupted(limit) => write!(f, "solver stopped: {}", limit),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+/// A SAT solver, fed clauses between calls to [`Solver::solve`] and asked for the model after
+/// a satisfiable one. Every formal feature of the crate goes through this trait, picking the
+/// implementation by [`Engine`].
+pub trait Solver {
+    fn new_var(&mut self) -> Lit;
+
+    fn add_clause(&mut self, lits: &[Lit]);
+
+    /// Solves the clauses added so far under `assumptions`, which only hold for this call.
+    fn solve(&mut self, assumptions: &[Lit]) -> Result<bool, SolverError>;
+
+    /// Value of a literal in the model of the last satisfiable call.
+    fn value(&self, lit: Lit) -> bool;
+}
+
+/// Which [`Solver`] the formal features use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// The built-in [`Cdcl`] solver.
+    #[default]
+    Cdcl,
+    /// An [`External`] solver process.
+    External { program: String, args: Vec<String> },
+}
+
+impl Engine {
+    pub fn solver(&self) -> Box<dyn Solver> {
+        match self {
+            Self::Cdcl => Box::new(Cdcl::new()),
+            Self::External { program, args } => Box::new(External::new(program, args)),
+        }
+    }
+
+    /// Like [`Engine::solver`], stopping the solver once `budget` runs out.
+    pub fn solver_within(&self, budget: &Budget) -> Box<dyn Solver> {
+        match self {
+            Self::Cdcl => Box::new(Cdcl::new().with_budget(budget.clone())),
+            Self::External { program, args } => Box::new(External::new(program, args).with_budget(budget.clone())),
+        }
+    }
+}
+
+/// Search steps of [`Cdcl`], conflicts and decisions, between two checks of its budget.
+const CHECK_INTERVAL: usize = 1024;
+
+fn value_of(values: &[Option<bool>], lit: Lit) -> Option<bool> {
+    values[lit.var()].map(|value| value == lit.positive())
+}
+
+/// A small conflict driven clause learning solver: two watched literals, first UIP learning,
+/// activity based decisions with phase saving and geometric restarts. Clauses are added
+/// between calls to [`Cdcl::solve`], which takes assumptions, so one instance serves a whole
+/// incremental unrolling.
+#[derive(Debug, Clone, Default)]
+pub struct Cdcl {
+    clauses: Vec<Vec<Lit>>,
+    /// Clauses watching each literal, by literal index.
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    activity: Vec<f64>,
+    phases: Vec<bool>,
+    trail: Vec<Lit>,
+    /// Length of the trail when each decision level started.
+    limits: Vec<usize>,
+    head: usize,
+    increment: f64,
+    model: Vec<bool>,
+    unsat: bool,
+    /// Literals in all clauses, for the memory estimate.
+    literals: usize,
+    budget: Option<Budget>,
+}
+
+impl Cdcl {
+    pub fn new() -> Self {
+        Self { increment: 1.0, ..Self::default() }
+    }
+
+    /// Stops [`Solver::solve`] with [`SolverError::Interrupted`] once `budget` runs out or the
+    /// estimate of the memory held exceeds it.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Rough size of the clause database, watch lists and variable state, in bytes.
+    pub fn memory_estimate(&self) -> usize {
+        let clauses = self.clauses.len() * (size_of::<Vec<Lit>>() + 2 * size_of::<usize>());
+        let vars =
+            self.values.len() * (size_of::<Option<bool>>() + 3 * size_of::<usize>() + size_of::<f64>() + 2 * size_of::<Vec<usize>>());
+        self.literals * size_of::<Lit>() + clauses + vars
+    }
+
+    fn add_var(&mut self) -> Lit {
+        let var = self.values.len();
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phases.push(false);
+        self.watches.extend([Vec::new(), Vec::new()]);
+        Lit::new(var, true)
+    }
+
+    fn level(&self) -> usize {
+        self.limits.len()
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        self.values[lit.var()] = Some(lit.positive());
+        self.levels[lit.var()] = self.level();
+        self.reasons[lit.var()] = reason;
+        self.trail.push(lit);
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0].index()].push(index);
+        self.watches[clause[1].index()].push(index);
+        self.literals += clause.len();
+        self.clauses.push(clause);
+        index
+    }
+
+    fn add(&mut self, lits: &[Lit]) {
+        if self.unsat {
+            return;
+        }
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            match value_of(&self.values, lit) {
+                Some(true) => return,
+                Some(false) => continue,
+                None if clause.contains(&!lit) => return,
+                None if !clause.contains(&lit) => clause.push(lit),
+                None => {}
+            }
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.assign(clause[0], None);
+                self.unsat = self.propagate().is_some();
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+
+    /// Propagates the trail, returning a conflicting clause.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let false_lit = !self.trail[self.head];
+            self.head += 1;
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if value_of(&self.values, first) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                if let Some(k) = (2..clause.len()).find(|k| value_of(&self.values, clause[*k]) != Some(false)) {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                if value_of(&self.values, first) == Some(false) {
+                    conflict = Some(index);
+                    break;
+                }
+                self.assign(first, Some(index));
+                i += 1;
+            }
+            watchers.append(&mut self.watches[false_lit.index()]);
+            self.watches[false_lit.index()] = watchers;
+            if conflict.is_some() {
+                self.head = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.increment;
+        if self.activity[var] > 1e100 {
+            self.activity.iter_mut().for_each(|activity| *activity *= 1e-100);
+            self.increment *= 1e-100;
+        }
+    }
+
+    /// Derives the first UIP clause of a conflict, asserting literal first, and the level to
+    /// jump back to.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut implied = None;
+        let mut index = self.trail.len();
+        loop {
+            for lit in self.clauses[clause].clone() {
+                let var = lit.var();
+                if Some(lit) == implied || seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump(var);
+                match self.levels[var] >= self.level() {
+                    true => pending += 1,
+                    false => learnt.push(lit),
+                }
+            }
+            loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            clause = self.reasons[lit.var()].unwrap();
+            implied = Some(lit);
+        }
+        let mut level = 0;
+        if let Some(second) = (1..learnt.len()).max_by_key(|i| self.levels[learnt[*i].var()]) {
+            learnt.swap(1, second);
+            level = self.levels[learnt[1].var()];
+        }
+        (learnt, level)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if level >= self.level() {
+            return;
+        }
+        for lit in self.trail.drain(self.limits[level]..) {
+            self.values[lit.var()] = None;
+            self.phases[lit.var()] = lit.positive();
+        }
+        self.limits.truncate(level);
+        self.head = self.trail.len();
+    }
+
+    fn search(&mut self, assumptions: &[Lit]) -> Result<bool, LimitExceeded> {
+        if self.unsat {
+            return Ok(false);
+        }
+        let (mut conflicts, mut restart, mut steps) = (0, 100, 0);
+        let satisfiable = loop {
+            steps += 1;
+            if let Some(budget) = &self.budget
+                && steps % CHECK_INTERVAL == 0
+                && let Err(exceeded) = budget.check_memory(self.memory_estimate())
+            {
+                self.backtrack(0);
+                return Err(exceeded);
+            }
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    self.unsat = true;
+                    break false;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                match learnt.len() {
+                    1 => self.assign(learnt[0], None),
+                    _ => {
+                        let asserting = learnt[0];
+                        let index = self.attach(learnt);
+                        self.assign(asserting, Some(index));
+                    }
+                }
+                self.increment /= 0.95;
+                conflicts += 1;
+                if conflicts == restart {
+                    (conflicts, restart) = (0, restart * 3 / 2);
+                    self.backtrack(0);
+                }
+                continue;
+            }
+            if let Some(&lit) = assumptions.get(self.level()) {
+                match value_of(&self.values, lit) {
+                    Some(false) => break false,
+                    Some(true) => self.limits.push(self.trail.len()),
+                    None => {
+                        self.limits.push(self.trail.len());
+                        self.assign(lit, None);
+                    }
+                }
+                continue;
+            }
+            let free = (0..self.values.len()).filter(|var| self.values[*var].is_none());
+            match free.max_by(|a, b| self.activity[*a].total_cmp(&self.activity[*b])) {
+                Some(var) => {
+                    self.limits.push(self.trail.len());
+                    self.assign(Lit::new(var, self.phases[var]), None);
+                }
+                None => {
+                    self.model = self.values.iter().map(|value| value.unwrap()).collect();
+                    break true;
+                }
+            }
+        };
+        self.backtrack(0);
+        Ok(satisfiable)
+    }
+}
+
+impl Solver for Cdcl {
+    fn new_var(&mut self) -> Lit {
+        self.add_var()
+    }
+
+    fn add_clause(&mut self, lits: &[Lit]) {
+        self.add(lits)
+    }
+
+    fn solve(&mut self, assumptions: &[Lit]) -> Result<bool, SolverError> {
+        self.search(assumptions).map_err(SolverError::Interrupted)
+    }
+
+    fn value(&self, lit: Lit) -> bool {
+        self.model.get(lit.var()).is_some_and(|value| *value == lit.positive())
+    }
+}
+
+/// A solver run as a separate process for every call, such as `kissat`, `cadical`,
+/// `minisat /dev/stdin /dev/stdout` or `z3 -dimacs -in`. It is given the clauses in DIMACS
+/// on standard input, with the assumptions as unit clauses, and answers on standard output
+/// in the SAT competition format: an `s SATISFIABLE` or `s UNSATISFIABLE` line and the model
+/// in `v` lines. The bare `SAT` and `UNSAT` of MiniSat with the model on a plain line are
+/// accepted too.
+#[derive(Debug, Clone)]
+pub struct External {
+    program: String,
+    args: Vec<String>,
+    vars: usize,
+    clauses: Vec<Vec<Lit>>,
+    model: Vec<bool>,
+    budget: Option<Budget>,
+}
+
+/// How often a running [`External`] solver is checked against its budget.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub(crate) fn dimacs(lit: Lit) -> i64 {
+    match lit.positive() {
+        true => lit.var() as i64 + 1,
+        false => -(lit.var() as i64 + 1),
+    }
+}
+
+impl External {
+    pub fn new(program: &str, args: &[String]) -> Self {
+        Self { program: program.to_string(), args: args.to_vec(), vars: 0, clauses: Vec::new(), model: Vec::new(), budget: None }
+    }
+
+    /// Kills the solver process once `budget` runs out, failing with
+    /// [`SolverError::Interrupted`].
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    fn run(&self, input: &str) -> io::Result<Result<String, LimitExceeded>> {
+        let mut child =
+            Command::new(&self.program).args(&self.args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+        child.stdin.take().unwrap().write_all(input.as_bytes())?;
+        let Some(budget) = &self.budget else {
+            let output = child.wait_with_output()?;
+            return Ok(Ok(String::from_utf8_lossy(&output.stdout).into_owned()));
+        };
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = std::thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+        while child.try_wait()?.is_none() {
+            if let Err(exceeded) = budget.check() {
+                child.kill()?;
+                child.wait()?;
+                return Ok(Err(exceeded));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        let output = reader.join().unwrap()?;
+        Ok(Ok(String::from_utf8_lossy(&output).into_owned()))
+    }
+}
+
+impl Solver for External {
+    fn new_var(&mut self) -> Lit {
+        self.vars += 1;
+        Lit::new(self.vars - 1, true)
+    }
+
+    fn add_clause(&mut self, lits: &[Lit]) {
+        self.clauses.push(lits.to_vec());
+    }
+
+    fn solve(&mut self, assumptions: &[Lit]) -> Result<bool, SolverError> {
+        let mut input = format!("p cnf {} {}\n", self.vars, self.clauses.len() + assumptions.len());
+        for clause in self.clauses.iter().map(Vec::as_slice).chain(assumptions.iter().map(std::slice::from_ref)) {
+            for lit in clause {
+                input.push_str(&dimacs(*lit).to_string());
+                input.push(' ');
+            }
+            input.push_str("0\n");
+        }
+        if let Some(budget) = &self.budget {
+            budget.check_memory(input.len()).map_err(SolverError::Interrupted)?;
+        }
+        let output = self.run(&input).map_err(|error| SolverError::Process { program: self.program.clone(), error: error.to_string() })?;
+        let output = output.map_err(SolverError::Interrupted)?;
+
+        let malformed = || SolverError::Output { program: self.program.clone(), output: output.clone() };
+        let mut satisfiable = None;
+        let mut model = vec![false; self.vars];
+        for line in output.lines().map(str::trim).filter(|line| !line.starts_with('c')) {
+            match line.strip_prefix("s ").unwrap_or(line) {
+                "SATISFIABLE" | "SAT" | "sat" => satisfiable = Some(true),
+                "UNSATISFIABLE" | "UNSAT" | "unsat" => satisfiable = Some(false),
+                _ if satisfiable == Some(true) => {
+                    for token in line.strip_prefix('v').unwrap_or(line).split_whitespace() {
+                        let value: i64 = token.parse().map_err(|_| malformed())?;
+                        if let Some(slot) = value.unsigned_abs().checked_sub(1).and_then(|var| model.get_mut(var as usize)) {
+                            *slot = value > 0;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        match satisfiable {
+            Some(satisfiable) => {
+                self.model = model;
+                Ok(satisfiable)
+            }
+            None => Err(malformed()),
+        }
+    }
+
+    fn value(&self, lit: Lit) -> bool {
+        self.model.get(lit.var()).is_some_and(|value| *value == lit.positive())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limits;
+
+    #[test]
+    fn test_cdcl() {
+        // Four pigeons do not fit in three holes.
+        let mut solver = Cdcl::new();
+        let holes: Vec<Vec<Lit>> = (0..4).map(|_| (0..3).map(|_| solver.new_var()).collect()).collect();
+        for pigeon in &holes {
+            solver.add_clause(pigeon);
+        }
+        for (a, first) in holes.iter().enumerate() {
+            for second in &holes[a + 1..] {
+                for hole in 0..3 {
+                    solver.add_clause(&[!first[hole], !second[hole]]);
+                }
+            }
+        }
+        assert_eq!(solver.solve(&[]), Ok(false));
+
+        // Chained implications under assumptions, then a model.
+        let mut solver = Cdcl::new();
+        let vars: Vec<Lit> = (0..6).map(|_| solver.new_var()).collect();
+        for pair in vars.windows(2) {
+            solver.add_clause(&[!pair[0], pair[1]]);
+        }
+        assert_eq!(solver.solve(&[vars[0], !vars[5]]), Ok(false));
+        assert_eq!(solver.solve(&[vars[1]]), Ok(true));
+        assert!(!solver.value(!vars[4]) && solver.value(vars[5]));
+        solver.add_clause(&[!vars[5]]);
+        assert_eq!(solver.solve(&[]), Ok(true));
+        assert!(vars.iter().all(|var| !solver.value(*var)));
+    }
+
+    #[test]
+    fn test_external() {
+        // Stands in for a solver, answering with a fixed model.
+        let script = "cat > /dev/null; echo 'c fake'; echo 's SATISFIABLE'; echo 'v -1 2'; echo 'v 0'";
+        let mut solver = Engine::External { program: "sh".to_string(), args: vec!["-c".to_string(), script.to_string()] }.solver();
+        let (a, b) = (solver.new_var(), solver.new_var());
+        solver.add_clause(&[a, b]);
+        assert_eq!(solver.solve(&[!a]), Ok(true));
+        assert!(!solver.value(a) && solver.value(b) && solver.value(!a));
+
+        let mut solver = External::new("sh", &["-c".to_string(), "cat > /dev/null; echo UNSAT".to_string()]);
+        assert_eq!(solver.solve(&[]), Ok(false));
+        let mut solver = External::new("sh", &["-c".to_string(), "cat > /dev/null; echo INDETERMINATE".to_string()]);
+        assert!(matches!(solver.solve(&[]), Err(SolverError::Output { .. })));
+        let mut solver = External::new("/nonexistent/solver", &[]);
+        assert!(matches!(solver.solve(&[]), Err(SolverError::Process { .. })));
+
+        let limits = Limits { max_runtime: Some(Duration::from_millis(50)), ..Limits::default() };
+        let mut solver = External::new("sh", &["-c".to_string(), "cat > /dev/null; echo UNSAT".to_string()]).with_budget(limits.start());
+        assert_eq!(solver.solve(&[]), Ok(false));
+        let mut solver = External::new("sh", &["-c".to_string(), "cat > /dev/null; sleep 10".to_string()]).with_budget(limits.start());
+        assert!(matches!(solver.solve(&[]), Err(SolverError::Interrupted(LimitExceeded::Runtime(_)))));
+    }
+}
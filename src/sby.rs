@@ -0,0 +1,172 @@
+use std::fmt::{self, Write as _};
+use std::io;
+use std::path::Path;
+
+use indexmap::IndexMap;
+
+use crate::Netlist;
+
+/// What SymbiYosys checks, the `mode` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SbyMode {
+    /// Bounded model check of the asserts up to the depth.
+    Bmc,
+    /// Unbounded proof of the asserts.
+    #[default]
+    Prove,
+    /// Traces reaching every `$cover` cell.
+    Cover,
+    /// Liveness of the `$live` cells under the `$fair` cells.
+    Live,
+}
+
+impl SbyMode {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Bmc => "bmc",
+            Self::Prove => "prove",
+            Self::Cover => "cover",
+            Self::Live => "live",
+        }
+    }
+
+    /// The cells the mode needs at least one of.
+    fn properties(self) -> &'static [&'static str] {
+        match self {
+            Self::Bmc | Self::Prove => &["$assert"],
+            Self::Cover => &["$cover"],
+            Self::Live => &["$live"],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SbyOptions {
+    pub mode: SbyMode,
+    pub depth: usize,
+    /// Lines of the `[engines]` section.
+    pub engines: Vec<String>,
+    /// Sets `multiclock on`, needed when registers run on more than one clock.
+    pub multiclock: bool,
+}
+
+impl Default for SbyOptions {
+    fn default() -> Self {
+        Self { mode: SbyMode::Prove, depth: 20, engines: vec!["smtbmc".to_string()], multiclock: false }
+    }
+}
+
+#[derive(Debug)]
+pub enum SbyError {
+    UnknownModule(String),
+    /// Nothing below the top module for the mode to check, with the cell types looked for.
+    NoProperties {
+        top: String,
+        cells: Vec<String>,
+    },
+    Netlist(crate::Error),
+}
+
+impl fmt::Display for SbyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModule(module) => write!(f, "unknown module {:?}", module),
+            Self::NoProperties { top, cells } => write!(f, "no {} cells below {:?}", cells.join(" or "), top),
+            Self::Netlist(error) => write!(f, "cannot write the netlist: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SbyError {}
+
+impl From<crate::Error> for SbyError {
+    fn from(error: crate::Error) -> Self {
+        Self::Netlist(error)
+    }
+}
+
+/// The files of a SymbiYosys project, by name: the `.sby` file and the netlist it reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbyProject {
+    pub files: IndexMap<String, String>,
+}
+
+impl SbyProject {
+    /// Writes every file into `directory`, which must exist. Run the project there with
+    /// `sby -f <top>.sby`.
+    pub fn write_to(&self, directory: &Path) -> io::Result<()> {
+        for (name, contents) in self.files.iter() {
+            std::fs::write(directory.join(name), contents)?;
+        }
+        Ok(())
+    }
+}
+
+impl Netlist {
+    /// Builds a SymbiYosys project checking the formal cells below `top`: the netlist as
+    /// `<top>.json`, read back by Yosys with `read_json`, and `<top>.sby` running `options`.
+    pub fn to_sby(&self, top: &str, options: &SbyOptions) -> Result<SbyProject, SbyError> {
+        if !self.modules.contains_key(top) {
+            return Err(SbyError::UnknownModule(top.to_string()));
+        }
+        let graph = self.module_graph();
+        let below = graph.below(&[top]);
+        let mut cells = below.iter().flat_map(|name| self.modules[*name].cells.values());
+        if !cells.any(|cell| options.mode.properties().contains(&cell.module.as_str())) {
+            let cells = options.mode.properties().iter().map(|cell| cell.to_string()).collect();
+            return Err(SbyError::NoProperties { top: top.to_string(), cells });
+        }
+
+        let json = format!("{}.json", top);
+        let mut sby = String::new();
+        writeln!(sby, "[options]\nmode {}\ndepth {}", options.mode.name(), options.depth).unwrap();
+        if options.multiclock {
+            writeln!(sby, "multiclock on").unwrap();
+        }
+        writeln!(sby, "\n[engines]").unwrap();
+        for engine in options.engines.iter() {
+            writeln!(sby, "{}", engine).unwrap();
+        }
+        writeln!(sby, "\n[script]\nread_json {}\nprep -top {}", json, top).unwrap();
+        writeln!(sby, "\n[files]\n{}", json).unwrap();
+
+        let files = IndexMap::from([(format!("{}.sby", top), sby), (json, self.to_string()?)]);
+        Ok(SbyProject { files })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+    use crate::{Bit, Cell, Module};
+
+    #[test]
+    fn test_to_sby() {
+        let mut builder = Module::builder();
+        let a = builder.input("a", 1).unwrap();
+        builder.cell("check", CellBuilder::new("$assert").input("A", a).input("EN", vec![Bit::_1])).unwrap();
+        let mut netlist = Netlist::new("test");
+        netlist.modules.insert("checker".into(), builder.build());
+        netlist.modules.insert("top".into(), Module::new());
+        netlist.modules["top"].cells.insert("u_checker".into(), Cell::new("checker"));
+
+        let options = SbyOptions { depth: 8, ..SbyOptions::default() };
+        let dir = std::env::temp_dir().join(format!("sby-{}", std::process::id()));
+        let project = netlist.to_sby("top", &options).unwrap();
+        assert_eq!(project.files.keys().collect::<Vec<_>>(), ["top.sby", "top.json"]);
+        assert_eq!(
+            project.files["top.sby"],
+            "[options]\nmode prove\ndepth 8\n\n[engines]\nsmtbmc\n\n[script]\nread_json top.json\nprep -top top\n\n[files]\ntop.json\n"
+        );
+        assert!(Netlist::from_str(&project.files["top.json"]).unwrap().modules.contains_key("checker"));
+        std::fs::create_dir_all(&dir).unwrap();
+        project.write_to(&dir).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("top.sby")).unwrap(), project.files["top.sby"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let options = SbyOptions { mode: SbyMode::Cover, ..SbyOptions::default() };
+        assert!(matches!(netlist.to_sby("top", &options), Err(SbyError::NoProperties { .. })));
+        assert!(matches!(netlist.to_sby("checker2", &options), Err(SbyError::UnknownModule(_))));
+    }
+}
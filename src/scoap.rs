@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::gates::function;
+use crate::metadata::ToolMetadata;
+use crate::progress::pass_span;
+use crate::{Bit, Direction, Module};
+
+/// Cost of a value that cannot be set or a bit that cannot be observed.
+pub const UNREACHABLE: u32 = u32::MAX;
+
+/// Functions with more inputs are treated like registers, the rule tables grow as `3^n`.
+const MAX_INPUTS: usize = 6;
+
+/// SCOAP combinational controllability to 0 and 1 and observability of a bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Testability {
+    pub cc0: u32,
+    pub cc1: u32,
+    pub co: u32,
+}
+
+impl Testability {
+    /// Cost of detecting the harder of the two stuck-at faults on the bit.
+    pub fn difficulty(&self) -> u32 {
+        self.cc0.max(self.cc1).saturating_add(self.co)
+    }
+}
+
+/// Input cubes of a truth table, as `(care, value)` masks over the inputs.
+struct Rules {
+    /// Cubes forcing the output, with the value they force.
+    implicants: Vec<(u32, u32, bool)>,
+    /// Per input, cubes over the other inputs making the output follow that input.
+    sensitizing: Vec<Vec<(u32, u32)>>,
+}
+
+fn cubes(inputs: usize) -> impl Iterator<Item = (u32, u32)> {
+    (0..3u32.pow(inputs as u32)).map(move |mut index| {
+        let (mut care, mut value) = (0, 0);
+        for input in 0..inputs {
+            match index % 3 {
+                1 => care |= 1 << input,
+                2 => (care, value) = (care | 1 << input, value | 1 << input),
+                _ => {}
+            }
+            index /= 3;
+        }
+        (care, value)
+    })
+}
+
+impl Rules {
+    fn new(table: &[bool]) -> Self {
+        let inputs = table.len().trailing_zeros() as usize;
+        let completions = |care: u32, value: u32| (0..table.len() as u32).filter(move |m| m & care == value);
+        let mut implicants = Vec::new();
+        for (care, value) in cubes(inputs) {
+            let mut outputs = completions(care, value).map(|m| table[m as usize]);
+            let first = outputs.next().unwrap();
+            if outputs.all(|output| output == first) {
+                implicants.push((care, value, first));
+            }
+        }
+        let sensitizing = (0..inputs)
+            .map(|input| {
+                cubes(inputs)
+                    .filter(|(care, _)| care & 1 << input == 0)
+                    .filter(|&(care, value)| completions(care, value).all(|m| table[m as usize] != table[(m ^ 1 << input) as usize]))
+                    .collect()
+            })
+            .collect();
+        Self { implicants, sensitizing }
+    }
+}
+
+fn cost(care: u32, value: u32, inputs: &[Bit], cc: &HashMap<Bit, (u32, u32)>) -> u32 {
+    inputs.iter().enumerate().filter(|(input, _)| care >> input & 1 == 1).fold(0u32, |sum, (input, bit)| {
+        let (cc0, cc1) = cc[bit];
+        sum.saturating_add(if value >> input & 1 == 1 { cc1 } else { cc0 })
+    })
+}
+
+impl Module {
+    /// SCOAP measures of every bit, assuming full scan: register outputs and the outputs of
+    /// cells other than gates and LUTs count as inputs, their inputs as observation points.
+    pub fn scoap(&self) -> HashMap<Bit, Testability> {
+        let mut rules: HashMap<Vec<bool>, Rc<Rules>> = HashMap::new();
+        let functions: Vec<(Vec<Bit>, Bit, Rc<Rules>)> = self
+            .cells
+            .values()
+            .filter_map(function)
+            .filter(|(inputs, output, _)| inputs.len() <= MAX_INPUTS && matches!(output, Bit::Signal(_)))
+            .map(|(inputs, output, table)| {
+                let rules = rules.entry(table).or_insert_with_key(|table| Rc::new(Rules::new(table))).clone();
+                (inputs, output, rules)
+            })
+            .collect();
+        let driven: HashSet<Bit> = functions.iter().map(|(_, output, _)| *output).collect();
+
+        let mut cc: HashMap<Bit, (u32, u32)> = HashMap::from([
+            (Bit::_0, (0, UNREACHABLE)),
+            (Bit::_1, (UNREACHABLE, 0)),
+            (Bit::X, (UNREACHABLE, UNREACHABLE)),
+            (Bit::Z, (UNREACHABLE, UNREACHABLE)),
+        ]);
+        for bit in self.signals().map(Bit::Signal) {
+            let free = if driven.contains(&bit) { UNREACHABLE } else { 1 };
+            cc.insert(bit, (free, free));
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (inputs, output, rules) in functions.iter() {
+                let mut best = cc[output];
+                for &(care, value, forced) in rules.implicants.iter() {
+                    let cost = cost(care, value, inputs, &cc).saturating_add(1);
+                    let slot = if forced { &mut best.1 } else { &mut best.0 };
+                    *slot = (*slot).min(cost);
+                }
+                if best != cc[output] {
+                    cc.insert(*output, best);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut co: HashMap<Bit, u32> = cc.keys().map(|bit| (*bit, UNREACHABLE)).collect();
+        let observed = self.ports.values().filter(|port| port.direction != Direction::Input).flat_map(|port| port.bits.iter());
+        let boundary =
+            self.cells.values().filter(|cell| function(cell).is_none_or(|(inputs, _, _)| inputs.len() > MAX_INPUTS)).flat_map(|cell| {
+                cell.connections
+                    .iter()
+                    .filter(|(port, _)| cell.port_directions.get(*port) != Some(&Direction::Output))
+                    .flat_map(|(_, bits)| bits.iter())
+            });
+        for bit in observed.chain(boundary) {
+            co.insert(*bit, 0);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (inputs, output, rules) in functions.iter() {
+                let observability = co[output];
+                if observability == UNREACHABLE {
+                    continue;
+                }
+                for (input, bit) in inputs.iter().enumerate() {
+                    let best = rules.sensitizing[input]
+                        .iter()
+                        .map(|&(care, value)| cost(care, value, inputs, &cc).saturating_add(observability).saturating_add(1))
+                        .min()
+                        .unwrap_or(UNREACHABLE);
+                    if best < co[bit] {
+                        co.insert(*bit, best);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        cc.into_iter()
+            .filter(|(bit, _)| matches!(bit, Bit::Signal(_)))
+            .map(|(bit, (cc0, cc1))| (bit, Testability { cc0, cc1, co: co[&bit] }))
+            .collect()
+    }
+
+    /// The `count` bits hardest to test, named by their nets, hardest first.
+    pub fn hardest_to_test(&self, count: usize) -> Vec<(String, Testability)> {
+        let mut bits: Vec<(Bit, Testability)> = self.scoap().into_iter().collect();
+        bits.sort_by_key(|(bit, testability)| (std::cmp::Reverse(testability.difficulty()), *bit));
+        bits.into_iter().take(count).map(|(bit, testability)| (self.bit_name(&bit), testability)).collect()
+    }
+
+    /// Stores the measures of every net bit in the net, under the `scoap` metadata namespace.
+    pub fn annotate_scoap(&mut self) {
+        pass_span!("annotate_scoap", cells = self.cells.len(), nets = self.nets.len());
+        let scoap = self.scoap();
+        for net in self.nets.values_mut() {
+            let measures: Option<Vec<Testability>> = net.bits.iter().map(|bit| scoap.get(bit).copied()).collect();
+            net.set_metadata("scoap", "testability", &measures).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, Net, Port};
+
+    fn gate(module: &str, inputs: &[(&str, u64)], y: u64) -> Cell {
+        inputs
+            .iter()
+            .fold(Cell::new(module), |cell, (port, bit)| cell.with_connection(port, Direction::Input, vec![Bit::Signal(*bit)]))
+            .with_connection("Y", Direction::Output, vec![Bit::Signal(y)])
+    }
+
+    #[test]
+    fn test_scoap() {
+        let mut module = Module::new();
+        module.ports.insert("a".into(), Port::new(Direction::Input, vec![Bit::Signal(2), Bit::Signal(3), Bit::Signal(4)]));
+        module.ports.insert("y".into(), Port::new(Direction::Output, vec![Bit::Signal(7)]));
+        module.cells.insert("and".into(), gate("$_AND_", &[("A", 2), ("B", 3)], 5));
+        module.cells.insert("not".into(), gate("$_NOT_", &[("A", 4)], 6));
+        module.cells.insert("or".into(), gate("$_OR_", &[("A", 5), ("B", 6)], 7));
+        module.nets.insert("a".into(), Net::new(vec![Bit::Signal(2), Bit::Signal(3), Bit::Signal(4)]));
+
+        let scoap = module.scoap();
+        assert_eq!(scoap[&Bit::Signal(5)], Testability { cc0: 2, cc1: 3, co: 3 });
+        assert_eq!(scoap[&Bit::Signal(6)], Testability { cc0: 2, cc1: 2, co: 3 });
+        assert_eq!(scoap[&Bit::Signal(7)], Testability { cc0: 5, cc1: 3, co: 0 });
+        assert_eq!(scoap[&Bit::Signal(2)], Testability { cc0: 1, cc1: 1, co: 5 });
+        assert_eq!(module.hardest_to_test(1)[0].0, "a[0]");
+
+        module.annotate_scoap();
+        let measures: Vec<Testability> = module.nets["a"].metadata("scoap", "testability").unwrap().unwrap();
+        assert_eq!(measures[0], Testability { cc0: 1, cc1: 1, co: 5 });
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Netlist;
+use crate::alias::{AliasAction, AliasPolicy};
+use crate::hierarchy::{HierarchyError, Tops};
+use crate::naming::NameRules;
+use crate::progress::Progress;
+use crate::rng::SeedPolicy;
+
+/// Export target whose identifier rules [`Step::SanitizeNames`] applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameTarget {
+    Edif,
+    Vhdl,
+    Spice,
+}
+
+impl NameTarget {
+    pub fn rules(self) -> NameRules {
+        match self {
+            Self::Edif => NameRules::edif(),
+            Self::Vhdl => NameRules::vhdl(),
+            Self::Spice => NameRules::spice(),
+        }
+    }
+}
+
+/// One built-in pass and its options, written as an object whose `pass` field names the pass
+/// in snake case, e.g. `{"pass": "sanitize_names", "target": "vhdl"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "pass", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    ConsolidateAliases {
+        #[serde(default)]
+        policy: AliasPolicy,
+        #[serde(default)]
+        action: AliasAction,
+    },
+    MergeRedundantLogic {
+        #[serde(default)]
+        seed: u64,
+    },
+    Strash,
+    RemoveFeedthroughsAndBuffers,
+    SanitizeNames {
+        target: NameTarget,
+    },
+    ClearToolMetadata {
+        tool: String,
+    },
+    AnnotateDatapath,
+    /// Replaces the netlist by its flattened `tops`, the top modules found by
+    /// [`Netlist::top_modules`] when empty.
+    Flatten {
+        #[serde(default)]
+        tops: Vec<String>,
+    },
+}
+
+impl Step {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ConsolidateAliases { .. } => "consolidate_aliases",
+            Self::MergeRedundantLogic { .. } => "merge_redundant_logic",
+            Self::Strash => "strash",
+            Self::RemoveFeedthroughsAndBuffers => "remove_feedthroughs_and_buffers",
+            Self::SanitizeNames { .. } => "sanitize_names",
+            Self::ClearToolMetadata { .. } => "clear_tool_metadata",
+            Self::AnnotateDatapath => "annotate_datapath",
+            Self::Flatten { .. } => "flatten",
+        }
+    }
+
+    /// Runs the pass on `netlist` and summarizes what it changed.
+    pub fn run(&self, netlist: &mut Netlist) -> Result<String, HierarchyError> {
+        Ok(match self {
+            Self::ConsolidateAliases { policy, action } => {
+                let report = netlist.consolidate_aliases(*policy, *action);
+                format!("folded {} aliases", report.values().flat_map(|aliases| aliases.values()).map(Vec::len).sum::<usize>())
+            }
+            Self::MergeRedundantLogic { seed } => {
+                let reports: Vec<_> =
+                    netlist.modules.values_mut().map(|module| module.merge_redundant_logic(*seed, SeedPolicy::Explicit)).collect();
+                format!("removed {} cells", reports.iter().map(|report| report.cells_before - report.cells_after).sum::<usize>())
+            }
+            Self::Strash => {
+                let reports: Vec<_> = netlist.modules.values_mut().map(|module| module.strash()).collect();
+                let total = |count: fn(&crate::cse::StrashReport) -> usize| reports.iter().map(count).sum::<usize>();
+                format!(
+                    "merged {} gates, {} inverter pairs and {} buffers",
+                    total(|report| report.merged),
+                    total(|report| report.inverters),
+                    total(|report| report.buffers)
+                )
+            }
+            Self::RemoveFeedthroughsAndBuffers => {
+                let report = netlist.remove_feedthroughs_and_buffers();
+                format!("removed {} buffers and {} ports", report.buffers_removed, report.ports_removed.len())
+            }
+            Self::SanitizeNames { target } => {
+                let map = netlist.sanitize_names(&target.rules());
+                let objects: usize = map.objects.values().map(|renames| renames.wires.len() + renames.cells.len()).sum();
+                format!("renamed {} modules and {} objects", map.modules.len(), objects)
+            }
+            Self::ClearToolMetadata { tool } => format!("removed {} keys", netlist.clear_tool_metadata(tool)),
+            Self::AnnotateDatapath => {
+                let summary = netlist.annotate_datapath();
+                format!("found {} blocks", summary.values().map(|summary| summary.blocks.len()).sum::<usize>())
+            }
+            Self::Flatten { tops } => {
+                let tops = match tops.is_empty() {
+                    true => Tops::Auto,
+                    false => Tops::Named(tops.clone()),
+                };
+                *netlist = netlist.flatten_tops(&tops)?;
+                format!("flattened into {} modules", netlist.modules.len())
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    #[cfg(feature = "toml")]
+    Toml(toml::de::Error),
+    /// A script file whose extension is neither `.json` nor, with the `toml` feature, `.toml`.
+    UnsupportedFormat(String),
+    /// A step failed, which keeps the changes of the steps before it.
+    Failed {
+        step: usize,
+        pass: &'static str,
+        error: HierarchyError,
+    },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Json(error) => write!(f, "{}", error),
+            #[cfg(feature = "toml")]
+            Self::Toml(error) => write!(f, "{}", error),
+            Self::UnsupportedFormat(path) => write!(f, "unsupported script format of {}", path),
+            Self::Failed { step, pass, error } => write!(f, "step {} ({}) failed: {}", step, pass, error),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<io::Error> for ScriptError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ScriptError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::de::Error> for ScriptError {
+    fn from(error: toml::de::Error) -> Self {
+        Self::Toml(error)
+    }
+}
+
+/// One step run by [`Script::run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepRecord {
+    pub pass: &'static str,
+    pub duration: Duration,
+    pub summary: String,
+}
+
+impl fmt::Display for StepRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({:.3} ms)", self.pass, self.summary, self.duration.as_secs_f64() * 1000.0)
+    }
+}
+
+/// A flow of built-in passes run one after the other, kept in a file so that experiments can
+/// be repeated. In JSON it is `{"steps": [{"pass": "strash"}, ...]}`, and in TOML a `[[steps]]`
+/// table per step.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Script {
+    pub steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn from_json(text: &str) -> Result<Self, ScriptError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self, ScriptError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Reads a script, in TOML or JSON by the extension of `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ScriptError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&std::fs::read_to_string(path)?),
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml(&std::fs::read_to_string(path)?),
+            _ => Err(ScriptError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    /// Runs every step in order and returns the log, stopping at the first step that fails.
+    pub fn run(&self, netlist: &mut Netlist) -> Result<Vec<StepRecord>, ScriptError> {
+        self.run_with_progress(netlist, |_| ())
+    }
+
+    /// Like [`Script::run`], calling `callback` after every step.
+    pub fn run_with_progress(&self, netlist: &mut Netlist, mut callback: impl FnMut(Progress<'_>)) -> Result<Vec<StepRecord>, ScriptError> {
+        let mut records = Vec::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("step", index, pass = step.name()).entered();
+            let start = Instant::now();
+            let summary = step.run(netlist).map_err(|error| ScriptError::Failed { step: index, pass: step.name(), error })?;
+            let duration = start.elapsed();
+            #[cfg(feature = "tracing")]
+            tracing::info!(?duration, summary = summary.as_str(), "step done");
+            records.push(StepRecord { pass: step.name(), duration, summary });
+            callback(Progress { operation: "script", item: Some(step.name()), done: index + 1, total: self.steps.len() });
+        }
+        Ok(records)
+    }
+}
+
+impl Netlist {
+    /// Reads the script at `path` and runs it, see [`Script`].
+    pub fn run_script(&mut self, path: impl AsRef<Path>) -> Result<Vec<StepRecord>, ScriptError> {
+        Script::from_path(path)?.run(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Module;
+    use crate::builder::CellBuilder;
+
+    /// A module computing `y = !!a` with two inverters, and a cell named illegally for VHDL.
+    fn design() -> Netlist {
+        let mut builder = Module::builder();
+        let a = builder.input("a", 1).unwrap();
+        let y = builder.output("y", 1).unwrap();
+        let t = builder.wire("t", 1).unwrap();
+        builder.cell("not_1", CellBuilder::new("$_NOT_").input("A", a).output("Y", t.clone())).unwrap();
+        builder.cell("not__2", CellBuilder::new("$_NOT_").input("A", t).output("Y", y)).unwrap();
+        let mut netlist = Netlist::new("test");
+        netlist.modules.insert("top".into(), builder.build());
+        netlist
+    }
+
+    #[test]
+    fn test_script() {
+        let script = Script::from_json(r#"{"steps": [{"pass": "strash"}, {"pass": "sanitize_names", "target": "vhdl"}]}"#).unwrap();
+        assert_eq!(script.steps, [Step::Strash, Step::SanitizeNames { target: NameTarget::Vhdl }]);
+        let mut netlist = design();
+        let log = script.run(&mut netlist).unwrap();
+        assert_eq!(log.iter().map(|record| record.pass).collect::<Vec<_>>(), ["strash", "sanitize_names"]);
+        assert!(log[0].to_string().starts_with("strash: merged 0 gates, 1 inverter pairs and 0 buffers ("));
+
+        let merge = Script::from_json(r#"{"steps": [{"pass": "merge_redundant_logic"}, {"pass": "merge_redundant_logic", "seed": 5}]}"#);
+        assert_eq!(merge.unwrap().steps, [Step::MergeRedundantLogic { seed: 0 }, Step::MergeRedundantLogic { seed: 5 }]);
+        assert!(Script::from_json(r#"{"steps": [{"pass": "opt"}]}"#).is_err());
+        assert!(Script::from_json(r#"{"steps": [{"pass": "flatten", "top": "cpu"}]}"#).is_err());
+        let flatten = Script { steps: vec![Step::Flatten { tops: vec!["missing".to_string()] }] };
+        assert!(matches!(flatten.run(&mut netlist), Err(ScriptError::Failed { step: 0, pass: "flatten", .. })));
+        assert!(matches!(Script::from_path("flow.tcl"), Err(ScriptError::UnsupportedFormat(_))));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_script() {
+        let script = Script::from_toml(
+            "[[steps]]\npass = \"consolidate_aliases\"\naction = \"annotate\"\n\n[[steps]]\npass = \"clear_tool_metadata\"\ntool = \"vivado\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            script.steps,
+            [
+                Step::ConsolidateAliases { policy: AliasPolicy::PreferPublic, action: AliasAction::Annotate },
+                Step::ClearToolMetadata { tool: "vivado".to_string() },
+            ]
+        );
+    }
+}
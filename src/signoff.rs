@@ -0,0 +1,159 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Module;
+use crate::formal::{FormalError, Invariant, ProofResult, ProveOptions, Trace};
+use crate::sat::Engine;
+
+/// Version of the report format, raised whenever a field changes meaning or goes away.
+pub const REPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyStatus {
+    Proven,
+    Failed,
+    /// Neither proven nor refuted, including properties still holding when another failed.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PropertyReport {
+    pub name: String,
+    pub status: PropertyStatus,
+    /// Cycles behind the status: the induction depth of a proof, the cycle of a failure
+    /// and the bound reached otherwise.
+    pub depth: usize,
+}
+
+/// Formal results of one module in a stable JSON form for dashboards and sign-off documents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormalReport {
+    pub version: u32,
+    pub module: String,
+    /// `cdcl` for the built-in solver, else the external program.
+    pub engine: String,
+    /// Largest depth unrolled.
+    pub bound: usize,
+    pub runtime_seconds: f64,
+    pub properties: Vec<PropertyReport>,
+    pub invariants: Vec<Invariant>,
+    pub counterexample: Option<Trace>,
+    /// Where the counterexample was written for viewers, if anywhere.
+    pub trace_file: Option<String>,
+}
+
+impl FormalReport {
+    /// Reports `result` for the `properties` of `module`, checked with `options` in `runtime`.
+    pub fn new(module: &str, properties: &[String], options: &ProveOptions, result: &ProofResult, runtime: Duration) -> Self {
+        let property = |name: &String, status, depth| PropertyReport { name: name.clone(), status, depth };
+        let (properties, invariants, counterexample) = match result {
+            ProofResult::Proven(certificate) => (
+                properties.iter().map(|name| property(name, PropertyStatus::Proven, certificate.depth)).collect(),
+                certificate.invariants.clone(),
+                None,
+            ),
+            ProofResult::Counterexample(trace) => (
+                (properties.iter())
+                    .map(|name| match trace.failed.contains(name) {
+                        true => property(name, PropertyStatus::Failed, trace.inputs.len().saturating_sub(1)),
+                        false => property(name, PropertyStatus::Unknown, trace.inputs.len().saturating_sub(1)),
+                    })
+                    .collect(),
+                Vec::new(),
+                Some(trace.clone()),
+            ),
+            ProofResult::Unknown { depth } => {
+                (properties.iter().map(|name| property(name, PropertyStatus::Unknown, *depth)).collect(), Vec::new(), None)
+            }
+        };
+        Self {
+            version: REPORT_VERSION,
+            module: module.to_string(),
+            engine: match &options.engine {
+                Engine::Cdcl => "cdcl".to_string(),
+                Engine::External { program, .. } => program.clone(),
+            },
+            bound: options.depth,
+            runtime_seconds: runtime.as_secs_f64(),
+            properties,
+            invariants,
+            counterexample,
+            trace_file: None,
+        }
+    }
+
+    pub fn with_trace_file(mut self, path: &str) -> Self {
+        self.trace_file = Some(path.to_string());
+        self
+    }
+
+    pub fn is_proven(&self) -> bool {
+        self.properties.iter().all(|property| property.status == PropertyStatus::Proven)
+    }
+
+    pub fn to_writer(&self, writer: impl io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+}
+
+impl Module {
+    /// Runs [`Module::prove`] and reports every `$assert` cell, under the module name `name`.
+    pub fn prove_report(&self, name: &str, options: &ProveOptions) -> Result<FormalReport, FormalError> {
+        let start = Instant::now();
+        let result = self.prove(options)?;
+        let properties: Vec<String> =
+            self.cells.iter().filter(|(_, cell)| cell.module == "$assert").map(|(name, _)| name.to_string()).collect();
+        Ok(FormalReport::new(name, &properties, options, &result, start.elapsed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Bit;
+    use crate::builder::CellBuilder;
+
+    /// A register holding its initial zero, asserted to stay at `expected`, and another
+    /// assertion that always holds.
+    fn hold(expected: bool) -> Module {
+        let mut builder = Module::builder();
+        let q = builder.wire("q", 1).unwrap();
+        let ok = builder.wire("ok", 1).unwrap();
+        builder.cell("r", CellBuilder::new("$_FF_").input("D", q.clone()).output("Q", q.clone())).unwrap();
+        builder.cell("check", CellBuilder::new(if expected { "$_BUF_" } else { "$_NOT_" }).input("A", q).output("Y", ok.clone())).unwrap();
+        builder.cell("held", CellBuilder::new("$assert").input("A", ok).input("EN", vec![Bit::_1])).unwrap();
+        builder.cell("always", CellBuilder::new("$assert").input("A", vec![Bit::_1]).input("EN", vec![Bit::_1])).unwrap();
+        let mut module = builder.build();
+        module.nets["q"].attributes.insert("init".to_string(), "0".into());
+        module
+    }
+
+    #[test]
+    fn test_prove_report() {
+        let report = hold(false).prove_report("hold", &ProveOptions::default()).unwrap();
+        assert!(report.is_proven());
+        assert_eq!(report.engine, "cdcl");
+        assert_eq!(report.properties.len(), 2);
+        assert!(report.counterexample.is_none());
+
+        let report = hold(true).prove_report("hold", &ProveOptions::default()).unwrap().with_trace_file("hold.vcd");
+        assert!(!report.is_proven());
+        assert_eq!(report.properties[0], PropertyReport { name: "held".to_string(), status: PropertyStatus::Failed, depth: 0 });
+        assert_eq!(report.properties[1].status, PropertyStatus::Unknown);
+
+        let mut json = Vec::new();
+        report.to_writer(&mut json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["version"], REPORT_VERSION);
+        assert_eq!(value["properties"][0]["status"], "failed");
+        assert_eq!(value["trace_file"], "hold.vcd");
+        assert_eq!(FormalReport::from_reader(json.as_slice()).unwrap(), report);
+    }
+}
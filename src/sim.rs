@@ -0,0 +1,1338 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::celltype::CellType;
+use crate::checkpoint::{Checkpoint, CheckpointError, encode, parse};
+use crate::cosim::{CosimError, Models, PortValues};
+use crate::gates::function;
+use crate::memory::{MemContents, parameter};
+use crate::param::ParamValue;
+use crate::{Bit, Cell, Direction, IdString, Module};
+
+/// Cell evaluations allowed per cell while settling before the logic counts as oscillating.
+pub const MAX_EVALUATIONS: usize = 1000;
+
+/// A value of 4-state logic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Logic {
+    _0,
+    _1,
+    #[default]
+    X,
+    Z,
+}
+
+impl Logic {
+    pub fn known(self) -> Option<bool> {
+        match self {
+            Self::_0 => Some(false),
+            Self::_1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn not(self) -> Self {
+        self.known().map_or(Self::X, |value| Self::from(!value))
+    }
+
+    fn and(self, other: Self) -> Self {
+        match (self.known(), other.known()) {
+            (Some(false), _) | (_, Some(false)) => Self::_0,
+            (Some(true), Some(true)) => Self::_1,
+            _ => Self::X,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self.known(), other.known()) {
+            (Some(true), _) | (_, Some(true)) => Self::_1,
+            (Some(false), Some(false)) => Self::_0,
+            _ => Self::X,
+        }
+    }
+
+    fn xor(self, other: Self) -> Self {
+        match (self.known(), other.known()) {
+            (Some(a), Some(b)) => Self::from(a ^ b),
+            _ => Self::X,
+        }
+    }
+
+    fn xnor(self, other: Self) -> Self {
+        self.xor(other).not()
+    }
+}
+
+impl From<bool> for Logic {
+    fn from(value: bool) -> Self {
+        if value { Self::_1 } else { Self::_0 }
+    }
+}
+
+impl fmt::Display for Logic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Self::_0 => '0',
+            Self::_1 => '1',
+            Self::X => 'x',
+            Self::Z => 'z',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    UnknownPort(String),
+    WidthMismatch {
+        port: String,
+        expected: usize,
+        found: usize,
+    },
+    /// The logic did not settle within [`MAX_EVALUATIONS`] evaluations per cell.
+    Oscillation,
+    /// A bound model returned bad outputs.
+    Cosim(CosimError),
+    Checkpoint(CheckpointError),
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot simulate cell {} of type {}", cell, module),
+            Self::UnknownPort(port) => write!(f, "no port {}", port),
+            Self::WidthMismatch { port, expected, found } => write!(f, "port {} has {} bits, not {}", port, expected, found),
+            Self::Oscillation => write!(f, "logic does not settle"),
+            Self::Cosim(error) => write!(f, "{}", error),
+            Self::Checkpoint(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
+
+type Word = Vec<Logic>;
+
+fn unknown(width: usize) -> Word {
+    vec![Logic::X; width]
+}
+
+fn known(word: &[Logic]) -> Option<Vec<bool>> {
+    word.iter().map(|bit| bit.known()).collect()
+}
+
+fn word(bits: &[bool]) -> Word {
+    bits.iter().map(|bit| Logic::from(*bit)).collect()
+}
+
+/// `word` truncated or extended to `width`, with its sign bit when `signed`.
+fn extend(word: &[Logic], width: usize, signed: bool) -> Word {
+    let fill = if signed { word.last().copied().unwrap_or(Logic::_0) } else { Logic::_0 };
+    (0..width).map(|index| word.get(index).copied().unwrap_or(fill)).collect()
+}
+
+/// Bits equal in both words, `x` elsewhere.
+fn merge(a: &[Logic], b: &[Logic]) -> Word {
+    a.iter().zip(b).map(|(a, b)| if a == b { *a } else { Logic::X }).collect()
+}
+
+/// Sum and the carry out of every bit.
+fn add(a: &[bool], b: &[bool], mut carry: bool) -> (Vec<bool>, Vec<bool>) {
+    let mut carries = Vec::with_capacity(a.len());
+    let sum = (a.iter().zip(b))
+        .map(|(a, b)| {
+            let sum = a ^ b ^ carry;
+            carry = (a & b) | (carry & (a ^ b));
+            carries.push(carry);
+            sum
+        })
+        .collect();
+    (sum, carries)
+}
+
+fn invert(a: &[bool]) -> Vec<bool> {
+    a.iter().map(|bit| !bit).collect()
+}
+
+fn subtract(a: &[bool], b: &[bool]) -> Vec<bool> {
+    add(a, &invert(b), true).0
+}
+
+fn negate(a: &[bool]) -> Vec<bool> {
+    add(&invert(a), &vec![false; a.len()], true).0
+}
+
+fn multiply(a: &[bool], b: &[bool]) -> Vec<bool> {
+    let mut product = vec![false; a.len()];
+    for (shift, _) in b.iter().enumerate().filter(|(_, bit)| **bit) {
+        let shifted: Vec<bool> = (0..a.len()).map(|index| index >= shift && a[index - shift]).collect();
+        product = add(&product, &shifted, false).0;
+    }
+    product
+}
+
+/// Whether `a < b` for words of the same width.
+fn less(a: &[bool], b: &[bool], signed: bool) -> bool {
+    if signed && a.last() != b.last() {
+        return a.last() == Some(&true);
+    }
+    for (a, b) in a.iter().zip(b).rev() {
+        if a != b {
+            return *b;
+        }
+    }
+    false
+}
+
+/// Quotient and remainder of words of the same width, truncating towards zero. `b` must not
+/// be zero.
+fn divide(a: &[bool], b: &[bool], signed: bool) -> (Vec<bool>, Vec<bool>) {
+    let negative = |word: &[bool]| signed && word.last() == Some(&true);
+    let magnitude = |word: &[bool]| if negative(word) { negate(word) } else { word.to_vec() };
+    let (dividend, divisor) = (magnitude(a), magnitude(b));
+    let divisor: Vec<bool> = divisor.into_iter().chain([false]).collect();
+    let mut quotient = vec![false; a.len()];
+    let mut remainder = vec![false; a.len() + 1];
+    for index in (0..a.len()).rev() {
+        remainder.pop();
+        remainder.insert(0, dividend[index]);
+        if !less(&remainder, &divisor, false) {
+            remainder = subtract(&remainder, &divisor);
+            quotient[index] = true;
+        }
+    }
+    remainder.pop();
+    let quotient = if negative(a) != negative(b) { negate(&quotient) } else { quotient };
+    let remainder = if negative(a) { negate(&remainder) } else { remainder };
+    (quotient, remainder)
+}
+
+/// An unsigned amount, saturating far past any word width.
+fn amount(bits: &[bool]) -> usize {
+    (bits.iter().enumerate().filter(|(_, bit)| **bit))
+        .map(|(index, _)| if index < 32 { 1 << index } else { usize::MAX / 2 })
+        .fold(0, usize::saturating_add)
+}
+
+fn compare(cell_type: &CellType, a: &[Logic], b: &[Logic], signed: bool) -> Logic {
+    match cell_type {
+        CellType::Eqx => return Logic::from(a == b),
+        CellType::Nex => return Logic::from(a != b),
+        CellType::Eq | CellType::Ne => {
+            let equal = match a.iter().zip(b).any(|(a, b)| a.known().is_some() && b.known().is_some() && a != b) {
+                true => Logic::_0,
+                false if known(a).is_some() && known(b).is_some() => Logic::_1,
+                false => Logic::X,
+            };
+            return if *cell_type == CellType::Eq { equal } else { equal.not() };
+        }
+        _ => {}
+    }
+    let (Some(a), Some(b)) = (known(a), known(b)) else { return Logic::X };
+    let (lt, eq) = (less(&a, &b, signed), a == b);
+    Logic::from(match cell_type {
+        CellType::Lt => lt,
+        CellType::Le => lt || eq,
+        CellType::Gt => !lt && !eq,
+        _ => !lt,
+    })
+}
+
+fn flag(cell: &Cell, name: &str) -> bool {
+    cell.param(name).and_then(|value| value.as_u64()) == Some(1)
+}
+
+/// A constant parameter as a word of `width` bits.
+fn constant(cell: &Cell, name: &str, width: usize) -> Word {
+    match cell.param(name) {
+        Some(ParamValue::Int(value)) => (0..width).map(|index| Logic::from(index < 64 && value >> index & 1 == 1)).collect(),
+        Some(ParamValue::BitVector(bits)) => {
+            let word: Word = bits.iter().map(|bit| logic(bit, &HashMap::new())).collect();
+            extend(&word, width, false)
+        }
+        _ => unknown(width),
+    }
+}
+
+/// The bits of a parameter packing one flag per memory port.
+fn flags(cell: &Cell, name: &str, count: usize) -> Vec<bool> {
+    match cell.param(name) {
+        Some(ParamValue::Int(value)) => (0..count).map(|index| index < 64 && value >> index & 1 == 1).collect(),
+        Some(ParamValue::BitVector(bits)) => (0..count).map(|index| bits.get(index) == Some(&Bit::_1)).collect(),
+        _ => vec![false; count],
+    }
+}
+
+fn logic(bit: &Bit, values: &HashMap<Bit, Logic>) -> Logic {
+    match bit {
+        Bit::_0 => Logic::_0,
+        Bit::_1 => Logic::_1,
+        Bit::X => Logic::X,
+        Bit::Z => Logic::Z,
+        Bit::Signal(_) => values.get(bit).copied().unwrap_or_default(),
+    }
+}
+
+/// Outputs of a combinational word-level cell computed from its inputs, `None` for cells of
+/// other types.
+fn combinational(cell_type: &CellType, cell: &Cell, input: &dyn Fn(&str) -> Word) -> Option<Vec<(&'static str, Word)>> {
+    let width = |port: &str| cell.connections.get(port).map_or(0, Vec::len);
+    let (a_signed, b_signed) = (flag(cell, "A_SIGNED"), flag(cell, "B_SIGNED"));
+    let signed = a_signed && b_signed;
+    let y_width = width("Y");
+    let bitwise = |op: fn(Logic, Logic) -> Logic| -> Word {
+        let (a, b) = (extend(&input("A"), y_width, signed), extend(&input("B"), y_width, signed));
+        a.into_iter().zip(b).map(|(a, b)| op(a, b)).collect()
+    };
+    let first = |port: &str| input(port).first().copied().unwrap_or(Logic::X);
+    let boolean = |value: Logic| extend(&[value], y_width, false);
+    let any = |word: Word| word.into_iter().fold(Logic::_0, Logic::or);
+    let y = match cell_type {
+        CellType::Not => extend(&input("A"), y_width, a_signed).into_iter().map(Logic::not).collect(),
+        CellType::Pos => extend(&input("A"), y_width, a_signed),
+        CellType::Neg => known(&extend(&input("A"), y_width, a_signed)).map_or(unknown(y_width), |a| word(&negate(&a))),
+        CellType::And => bitwise(Logic::and),
+        CellType::Or => bitwise(Logic::or),
+        CellType::Xor => bitwise(Logic::xor),
+        CellType::Xnor => bitwise(Logic::xnor),
+        CellType::ReduceAnd => boolean(input("A").into_iter().fold(Logic::_1, Logic::and)),
+        CellType::ReduceOr | CellType::ReduceBool => boolean(any(input("A"))),
+        CellType::ReduceXor => boolean(input("A").into_iter().fold(Logic::_0, Logic::xor)),
+        CellType::ReduceXnor => boolean(input("A").into_iter().fold(Logic::_0, Logic::xor).not()),
+        CellType::LogicNot => boolean(any(input("A")).not()),
+        CellType::LogicAnd => boolean(any(input("A")).and(any(input("B")))),
+        CellType::LogicOr => boolean(any(input("A")).or(any(input("B")))),
+        CellType::Eq | CellType::Ne | CellType::Eqx | CellType::Nex | CellType::Lt | CellType::Le | CellType::Gt | CellType::Ge => {
+            let common = width("A").max(width("B"));
+            let (a, b) = (extend(&input("A"), common, signed), extend(&input("B"), common, signed));
+            boolean(compare(cell_type, &a, &b, signed))
+        }
+        CellType::Add | CellType::Sub | CellType::Mul => {
+            let (a, b) = (extend(&input("A"), y_width, signed), extend(&input("B"), y_width, signed));
+            match (known(&a), known(&b)) {
+                (Some(a), Some(b)) => word(&match cell_type {
+                    CellType::Add => add(&a, &b, false).0,
+                    CellType::Sub => subtract(&a, &b),
+                    _ => multiply(&a, &b),
+                }),
+                _ => unknown(y_width),
+            }
+        }
+        CellType::Div | CellType::Mod => {
+            let common = width("A").max(width("B")).max(y_width);
+            let (a, b) = (extend(&input("A"), common, signed), extend(&input("B"), common, signed));
+            match (known(&a), known(&b)) {
+                (Some(a), Some(b)) if b.contains(&true) => {
+                    let (quotient, remainder) = divide(&a, &b, signed);
+                    extend(&word(if *cell_type == CellType::Div { &quotient } else { &remainder }), y_width, signed)
+                }
+                _ => unknown(y_width),
+            }
+        }
+        CellType::Shl | CellType::Sshl | CellType::Shr | CellType::Sshr => {
+            let Some(shift) = known(&input("B")).map(|b| amount(&b)) else { return Some(vec![("Y", unknown(y_width))]) };
+            let common = y_width.max(width("A"));
+            let a = extend(&input("A"), common, a_signed);
+            let fill = match cell_type {
+                CellType::Sshr if a_signed => a.last().copied().unwrap_or(Logic::_0),
+                _ => Logic::_0,
+            };
+            let shifted: Word = match cell_type {
+                CellType::Shl | CellType::Sshl => {
+                    (0..common).map(|index| index.checked_sub(shift).map_or(Logic::_0, |from| a[from])).collect()
+                }
+                _ => (0..common).map(|index| index.checked_add(shift).and_then(|from| a.get(from).copied()).unwrap_or(fill)).collect(),
+            };
+            extend(&shifted, y_width, false)
+        }
+        CellType::Shift | CellType::Shiftx => {
+            let Some(b) = known(&input("B")) else { return Some(vec![("Y", unknown(y_width))]) };
+            let negative = b_signed && b.last() == Some(&true);
+            let shift = amount(&if negative { negate(&b) } else { b });
+            let fill = if *cell_type == CellType::Shift { Logic::_0 } else { Logic::X };
+            let a = input("A");
+            (0..y_width)
+                .map(|index| match negative {
+                    true => index.checked_sub(shift),
+                    false => index.checked_add(shift),
+                })
+                .map(|from| from.and_then(|from| a.get(from).copied()).unwrap_or(fill))
+                .collect()
+        }
+        CellType::Mux => match first("S") {
+            Logic::_0 => input("A"),
+            Logic::_1 => input("B"),
+            _ => merge(&input("A"), &input("B")),
+        },
+        CellType::Pmux => {
+            let (a, b, s) = (input("A"), input("B"), input("S"));
+            match (known(&s), s.iter().filter(|bit| **bit == Logic::_1).count()) {
+                (Some(_), 0) => a,
+                (Some(_), 1) => {
+                    let index = s.iter().position(|bit| *bit == Logic::_1).unwrap();
+                    b.get(index * y_width..(index + 1) * y_width).map_or(unknown(y_width), <[Logic]>::to_vec)
+                }
+                _ => unknown(y_width),
+            }
+        }
+        CellType::Bmux => match known(&input("S")) {
+            Some(s) => {
+                let (a, index) = (input("A"), amount(&s));
+                (0..y_width).map(|bit| a.get(index * y_width + bit).copied().unwrap_or(Logic::X)).collect()
+            }
+            None => unknown(y_width),
+        },
+        CellType::Bwmux => {
+            let (a, b, s) = (input("A"), input("B"), input("S"));
+            (0..y_width)
+                .map(|index| match (s.get(index), a.get(index), b.get(index)) {
+                    (Some(Logic::_0), Some(a), _) => *a,
+                    (Some(Logic::_1), _, Some(b)) => *b,
+                    (_, Some(a), Some(b)) if a == b => *a,
+                    _ => Logic::X,
+                })
+                .collect()
+        }
+        CellType::Tribuf => match first("EN") {
+            Logic::_1 => input("A"),
+            Logic::_0 => vec![Logic::Z; y_width],
+            _ => unknown(y_width),
+        },
+        CellType::Slice => {
+            let (a, offset) = (input("A"), parameter(cell, "OFFSET").unwrap_or(0));
+            (0..y_width).map(|index| a.get(offset + index).copied().unwrap_or(Logic::X)).collect()
+        }
+        CellType::Concat => input("A").into_iter().chain(input("B")).collect(),
+        CellType::Alu => {
+            let a = extend(&input("A"), y_width, signed);
+            let invert = first("BI");
+            let b: Word = extend(&input("B"), y_width, signed).into_iter().map(|bit| bit.xor(invert)).collect();
+            let x: Word = a.iter().zip(&b).map(|(a, b)| a.xor(*b)).collect();
+            let (y, co) = match (known(&a), known(&b), first("CI").known()) {
+                (Some(a), Some(b), Some(carry)) => {
+                    let (sum, carries) = add(&a, &b, carry);
+                    (word(&sum), word(&carries))
+                }
+                _ => (unknown(y_width), unknown(y_width)),
+            };
+            return Some(vec![("X", x), ("Y", y), ("CO", co)]);
+        }
+        _ => return None,
+    };
+    Some(vec![("Y", y)])
+}
+
+/// An edge-triggered register, optionally with an enable and a synchronous or asynchronous
+/// reset.
+struct Flop {
+    clock: (Bit, bool),
+    last: Logic,
+    d: Vec<Bit>,
+    q: Vec<Bit>,
+    enable: Option<(Bit, bool)>,
+    sync_reset: Option<(Bit, bool, Word)>,
+    async_reset: Option<(Bit, bool, Word)>,
+    /// Whether the synchronous reset works while the register is disabled.
+    reset_over_enable: bool,
+}
+
+impl Flop {
+    fn new(cell_type: &CellType, cell: &Cell) -> Option<Self> {
+        let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied();
+        let polarity = |name: &str| cell.param(name).and_then(|value| value.as_u64()) != Some(0);
+        let (d, q) = (cell.connections.get("D")?.clone(), cell.connections.get("Q")?.clone());
+        let width = q.len();
+        let mut flop = Flop {
+            clock: (Bit::X, true),
+            last: Logic::X,
+            d,
+            q,
+            enable: None,
+            sync_reset: None,
+            async_reset: None,
+            reset_over_enable: true,
+        };
+        match cell_type {
+            CellType::Dff | CellType::Dffe | CellType::Adff | CellType::Adffe | CellType::Sdff | CellType::Sdffe | CellType::Sdffce => {
+                flop.clock = (bit("CLK")?, polarity("CLK_POLARITY"));
+                if matches!(cell_type, CellType::Dffe | CellType::Adffe | CellType::Sdffe | CellType::Sdffce) {
+                    flop.enable = Some((bit("EN")?, polarity("EN_POLARITY")));
+                }
+                if matches!(cell_type, CellType::Adff | CellType::Adffe) {
+                    flop.async_reset = Some((bit("ARST")?, polarity("ARST_POLARITY"), constant(cell, "ARST_VALUE", width)));
+                }
+                if matches!(cell_type, CellType::Sdff | CellType::Sdffe | CellType::Sdffce) {
+                    flop.sync_reset = Some((bit("SRST")?, polarity("SRST_POLARITY"), constant(cell, "SRST_VALUE", width)));
+                }
+                flop.reset_over_enable = *cell_type != CellType::Sdffce;
+            }
+            CellType::GateDff(letters)
+            | CellType::GateDffe(letters)
+            | CellType::GateSdff(letters)
+            | CellType::GateSdffe(letters)
+            | CellType::GateSdffce(letters) => {
+                let letters: Vec<bool> = letters.chars().map(|letter| letter == 'P' || letter == '1').collect();
+                let enabled = matches!(cell_type, CellType::GateDffe(_) | CellType::GateSdffe(_) | CellType::GateSdffce(_));
+                flop.clock = (bit("C")?, letters[0]);
+                if enabled {
+                    flop.enable = Some((bit("E")?, *letters.last()?));
+                }
+                match (letters.len() - enabled as usize, cell_type) {
+                    (1, CellType::GateDff(_) | CellType::GateDffe(_)) => {}
+                    (3, CellType::GateDff(_) | CellType::GateDffe(_)) => {
+                        flop.async_reset = Some((bit("R")?, letters[1], word(&[letters[2]])))
+                    }
+                    (3, _) => flop.sync_reset = Some((bit("R")?, letters[1], word(&[letters[2]]))),
+                    _ => return None,
+                }
+                flop.reset_over_enable = !matches!(cell_type, CellType::GateSdffce(_));
+            }
+            _ => return None,
+        }
+        Some(flop)
+    }
+}
+
+/// Whether a control input with the given polarity is active.
+fn active(bit: &Bit, polarity: bool, values: &HashMap<Bit, Logic>) -> Logic {
+    if polarity { logic(bit, values) } else { logic(bit, values).not() }
+}
+
+/// Records the clock value and tells whether it made an edge of the given polarity.
+fn edge(last: &mut Logic, now: Logic, polarity: bool) -> bool {
+    let edge = (*last, now) == if polarity { (Logic::_0, Logic::_1) } else { (Logic::_1, Logic::_0) };
+    *last = now;
+    edge
+}
+
+struct ReadPort {
+    clock: Option<(Bit, bool)>,
+    last: Logic,
+    enable: Bit,
+    address: Vec<Bit>,
+    data: Vec<Bit>,
+    /// Asynchronous and synchronous reset of a synchronous port, active high, and the value
+    /// they load.
+    async_reset: Option<(Bit, Word)>,
+    sync_reset: Option<(Bit, Word)>,
+    /// Whether the synchronous reset only works while the port is enabled.
+    enable_over_reset: bool,
+    /// Value of the data output before the first read, `x` where undefined.
+    init: Word,
+    /// Per write port: whether a write on the same edge to the address read is seen by the
+    /// read, and whether it makes the written bits `x` instead.
+    transparent: Vec<bool>,
+    collision_x: Vec<bool>,
+}
+
+struct WritePort {
+    clock: (Bit, bool),
+    last: Logic,
+    enable: Vec<Bit>,
+    address: Vec<Bit>,
+    data: Vec<Bit>,
+}
+
+/// A `$mem` or `$mem_v2` cell. Synchronous reads return the contents from before the writes
+/// of the same edge, unless the read port is transparent to the write port, and writes to
+/// undefined addresses are dropped.
+struct Memory {
+    width: usize,
+    size: usize,
+    offset: i64,
+    initial: MemContents,
+    written: HashMap<usize, Word>,
+    reads: Vec<ReadPort>,
+    writes: Vec<WritePort>,
+}
+
+impl Memory {
+    fn new(cell: &Cell) -> Option<Self> {
+        let (width, size, abits) = (parameter(cell, "WIDTH")?, parameter(cell, "SIZE")?, parameter(cell, "ABITS")?);
+        let (read_ports, write_ports) = (parameter(cell, "RD_PORTS")?, parameter(cell, "WR_PORTS")?);
+        let port = |name: &str, index: usize, width: usize| -> Option<Vec<Bit>> {
+            cell.connections.get(name)?.get(index * width..(index + 1) * width).map(<[Bit]>::to_vec)
+        };
+        let (read_clocked, read_polarity) = (flags(cell, "RD_CLK_ENABLE", read_ports), flags(cell, "RD_CLK_POLARITY", read_ports));
+        // `$mem` has one transparency flag per read port, `$mem_v2` one per pair of ports.
+        let pairs = read_ports * write_ports;
+        let (transparent, collision_x) = match cell.cell_type() {
+            CellType::Mem => {
+                let transparent = flags(cell, "RD_TRANSPARENT", read_ports);
+                (transparent.iter().flat_map(|flag| vec![*flag; write_ports]).collect(), vec![false; pairs])
+            }
+            _ => (flags(cell, "RD_TRANSPARENCY_MASK", pairs), flags(cell, "RD_COLLISION_X_MASK", pairs)),
+        };
+        let ce_over_srst = flags(cell, "RD_CE_OVER_SRST", read_ports);
+        let value = |name: &str, index: usize| constant(cell, name, read_ports * width)[index * width..(index + 1) * width].to_vec();
+        let reset = |name: &str, index: usize| match port(name, index, 1)?[0] {
+            Bit::_0 => None,
+            bit => Some((bit, value(&format!("{}_VALUE", name), index))),
+        };
+        let reads = (0..read_ports)
+            .map(|index| {
+                let clocked = read_clocked[index];
+                Some(ReadPort {
+                    clock: match clocked {
+                        true => Some((port("RD_CLK", index, 1)?[0], read_polarity[index])),
+                        false => None,
+                    },
+                    last: Logic::X,
+                    enable: port("RD_EN", index, 1).map_or(Bit::_1, |bits| bits[0]),
+                    address: port("RD_ADDR", index, abits)?,
+                    data: port("RD_DATA", index, width)?,
+                    async_reset: reset("RD_ARST", index).filter(|_| clocked),
+                    sync_reset: reset("RD_SRST", index).filter(|_| clocked),
+                    enable_over_reset: ce_over_srst[index],
+                    init: if clocked { value("RD_INIT_VALUE", index) } else { unknown(width) },
+                    transparent: transparent[index * write_ports..(index + 1) * write_ports].to_vec(),
+                    collision_x: collision_x[index * write_ports..(index + 1) * write_ports].to_vec(),
+                })
+            })
+            .collect::<Option<_>>()?;
+        let (write_clocked, write_polarity) = (flags(cell, "WR_CLK_ENABLE", write_ports), flags(cell, "WR_CLK_POLARITY", write_ports));
+        let writes = (0..write_ports)
+            .map(|index| {
+                write_clocked[index].then_some(())?;
+                Some(WritePort {
+                    clock: (port("WR_CLK", index, 1)?[0], write_polarity[index]),
+                    last: Logic::X,
+                    enable: port("WR_EN", index, width)?,
+                    address: port("WR_ADDR", index, abits)?,
+                    data: port("WR_DATA", index, width)?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(Memory {
+            width,
+            size,
+            offset: cell.param("OFFSET").and_then(|value| value.as_i64()).unwrap_or(0),
+            initial: cell.memory_contents()?,
+            written: HashMap::new(),
+            reads,
+            writes,
+        })
+    }
+
+    fn index(&self, address: &[Logic]) -> Option<usize> {
+        let index = amount(&known(address)?) as i64 - self.offset;
+        (0..self.size as i64).contains(&index).then_some(index as usize)
+    }
+
+    fn word(&self, index: usize) -> Word {
+        match self.written.get(&index) {
+            Some(word) => word.clone(),
+            None => self.initial.bits(index).into_iter().map(|bit| bit.map_or(Logic::X, Logic::from)).collect(),
+        }
+    }
+
+    fn read(&self, address: &[Logic]) -> Word {
+        self.index(address).map_or(unknown(self.width), |index| self.word(index))
+    }
+}
+
+enum Model {
+    Function {
+        inputs: Vec<Bit>,
+        output: Bit,
+        table: Vec<bool>,
+    },
+    Cell {
+        cell_type: CellType,
+        cell: Box<Cell>,
+    },
+    Flop(Flop),
+    /// A level-sensitive latch: enable, its polarity, data and output.
+    Latch(Bit, bool, Vec<Bit>, Vec<Bit>),
+    Memory(Memory),
+    /// A cell evaluated by the model bound to it, by name.
+    Bound(String, Box<Cell>),
+}
+
+/// Simulator for modules of Yosys internal cells, word-level and gate-level, over 4-state
+/// values. Every bit starts as `x` except registers with an `init` value and initialized
+/// memories. Changes propagate event by event through the combinational logic, and clock
+/// edges trigger the registers and memories they reach.
+pub struct Simulator {
+    ports: IndexMap<String, (Direction, Vec<Bit>)>,
+    models: Vec<Model>,
+    bound: Models,
+    fanout: HashMap<Bit, Vec<usize>>,
+    values: HashMap<Bit, Logic>,
+    pending: VecDeque<usize>,
+    queued: Vec<bool>,
+    /// Models of the register and memory cells, by cell name.
+    state: IndexMap<String, usize>,
+    /// Clock cycles run by [`step`](Self::step).
+    time: u64,
+}
+
+impl Simulator {
+    pub fn new(module: &Module) -> Result<Self, SimError> {
+        Self::with_models(module, Models::new())
+    }
+
+    /// A simulator evaluating the cells `models` are bound to, blackboxes and vendor
+    /// primitives for instance, through their models instead of rejecting them. A model is
+    /// called with the inputs of its cell whenever one changes, `x` and `z` reading as 0, so
+    /// a sequential model keeps its own state and watches its clock input for edges.
+    pub fn with_models(module: &Module, mut bound: Models) -> Result<Self, SimError> {
+        let mut models = Vec::new();
+        let mut fanout: HashMap<Bit, Vec<usize>> = HashMap::new();
+        let mut state = IndexMap::new();
+        for (name, cell) in module.cells.iter() {
+            let cell_type = cell.cell_type();
+            let width = |port: &str| cell.connections.get(port).map_or(0, Vec::len);
+            let bit = |port: &str| cell.connections.get(port).and_then(|bits| bits.first()).copied();
+            let model = if bound.get(name, &cell.module).is_some() {
+                Model::Bound(name.to_string(), Box::new(cell.clone()))
+            } else if let Some((inputs, output, table)) = function(cell) {
+                Model::Function { inputs, output, table }
+            } else if let Some(flop) = Flop::new(&cell_type, cell) {
+                Model::Flop(flop)
+            } else if let (CellType::Dlatch, Some(enable)) = (&cell_type, bit("EN")) {
+                let polarity = cell.param("EN_POLARITY").and_then(|value| value.as_u64()) != Some(0);
+                Model::Latch(
+                    enable,
+                    polarity,
+                    cell.connections.get("D").cloned().unwrap_or_default(),
+                    cell.connections.get("Q").cloned().unwrap_or_default(),
+                )
+            } else if let (CellType::GateDlatch(letters), Some(enable)) = (&cell_type, bit("E"))
+                && letters.len() == 1
+            {
+                let (d, q) =
+                    (cell.connections.get("D").cloned().unwrap_or_default(), cell.connections.get("Q").cloned().unwrap_or_default());
+                Model::Latch(enable, letters == "P", d, q)
+            } else if let (CellType::Mem | CellType::MemV2, Some(memory)) = (&cell_type, Memory::new(cell)) {
+                Model::Memory(memory)
+            } else if combinational(&cell_type, cell, &|port| unknown(width(port))).is_some() {
+                Model::Cell { cell_type, cell: Box::new(cell.clone()) }
+            } else if !cell.port_directions.values().any(|direction| *direction == Direction::Output) {
+                // Checks, prints and other cells without outputs do not affect the values.
+                continue;
+            } else {
+                return Err(SimError::Unsupported { cell: name.to_string(), module: cell.module.to_string() });
+            };
+            let inputs: Vec<&Bit> = match &model {
+                Model::Function { inputs, .. } => inputs.iter().collect(),
+                _ => (cell.connections.iter())
+                    .filter(|(port, _)| cell.port_directions.get(*port) != Some(&Direction::Output))
+                    .flat_map(|(_, bits)| bits)
+                    .collect(),
+            };
+            for bit in inputs.into_iter().filter(|bit| matches!(bit, Bit::Signal(_))) {
+                fanout.entry(*bit).or_default().push(models.len());
+            }
+            if let Model::Flop(_) | Model::Latch(..) | Model::Memory(_) = model {
+                state.insert(name.to_string(), models.len());
+            }
+            models.push(model);
+        }
+
+        let mut values: HashMap<Bit, Logic> = module.initial_values().into_iter().map(|(bit, value)| (bit, Logic::from(value))).collect();
+        for model in models.iter() {
+            if let Model::Memory(memory) = model {
+                for read in memory.reads.iter() {
+                    values.extend(read.data.iter().copied().zip(read.init.iter().copied()).filter(|(_, value)| *value != Logic::X));
+                }
+            }
+        }
+        let ports = module.ports.iter().map(|(name, port)| (name.to_string(), (port.direction.clone(), port.bits.clone()))).collect();
+        let (pending, queued) = ((0..models.len()).collect(), vec![true; models.len()]);
+        let mut simulator = Simulator { ports, pending, queued, models, bound, fanout, values, state, time: 0 };
+        simulator.settle()?;
+        Ok(simulator)
+    }
+
+    fn set(&mut self, bit: Bit, value: Logic) {
+        if !matches!(bit, Bit::Signal(_)) || self.values.get(&bit).copied().unwrap_or_default() == value {
+            return;
+        }
+        self.values.insert(bit, value);
+        for model in self.fanout.get(&bit).into_iter().flatten() {
+            if !self.queued[*model] {
+                self.queued[*model] = true;
+                self.pending.push_back(*model);
+            }
+        }
+    }
+
+    fn schedule(&mut self, model: usize) {
+        if !self.queued[model] {
+            self.queued[model] = true;
+            self.pending.push_back(model);
+        }
+    }
+
+    /// New values of the outputs of a model from its inputs.
+    fn evaluate(&mut self, model: usize) -> Result<Vec<(Bit, Logic)>, SimError> {
+        let values = &self.values;
+        let word = |bits: &[Bit]| -> Word { bits.iter().map(|bit| logic(bit, values)).collect() };
+        Ok(match &self.models[model] {
+            Model::Function { inputs, output, table } => {
+                let inputs = word(inputs);
+                let unknown: Vec<usize> = (0..inputs.len()).filter(|index| inputs[*index].known().is_none()).collect();
+                let fixed =
+                    (inputs.iter().enumerate()).fold(0, |row, (index, value)| row | ((value.known() == Some(true)) as usize) << index);
+                let value = match unknown.len() {
+                    0..=8 => {
+                        let mut rows = (0..1usize << unknown.len()).map(|pattern| {
+                            let row = (unknown.iter().enumerate()).fold(fixed, |row, (bit, index)| row | (pattern >> bit & 1) << index);
+                            table[row]
+                        });
+                        let first = rows.next().unwrap();
+                        if rows.all(|row| row == first) { Logic::from(first) } else { Logic::X }
+                    }
+                    _ => Logic::X,
+                };
+                vec![(*output, value)]
+            }
+            Model::Cell { cell_type, cell } => {
+                let input = |port: &str| word(cell.connections.get(port).map_or(&[][..], Vec::as_slice));
+                let outputs = combinational(cell_type, cell, &input).unwrap_or_default();
+                (outputs.into_iter())
+                    .filter_map(|(port, value)| Some(cell.connections.get(port)?.iter().copied().zip(value)))
+                    .flatten()
+                    .collect()
+            }
+            Model::Flop(flop) => match &flop.async_reset {
+                Some((reset, polarity, value)) => match active(reset, *polarity, values) {
+                    Logic::_1 => flop.q.iter().copied().zip(value.iter().copied()).collect(),
+                    Logic::_0 => Vec::new(),
+                    _ => flop.q.iter().copied().zip(merge(&word(&flop.q), value)).collect(),
+                },
+                None => Vec::new(),
+            },
+            Model::Latch(enable, polarity, d, q) => match active(enable, *polarity, values) {
+                Logic::_1 => q.iter().copied().zip(word(d)).collect(),
+                Logic::_0 => Vec::new(),
+                _ => q.iter().copied().zip(merge(&word(q), &word(d))).collect(),
+            },
+            Model::Memory(memory) => (memory.reads.iter())
+                .flat_map(|read| match (&read.clock, &read.async_reset) {
+                    (None, _) => read.data.iter().copied().zip(memory.read(&word(&read.address))).collect(),
+                    (Some(_), Some((reset, value))) => match logic(reset, values) {
+                        Logic::_1 => read.data.iter().copied().zip(value.iter().copied()).collect(),
+                        Logic::_0 => Vec::new(),
+                        _ => read.data.iter().copied().zip(merge(&word(&read.data), value)).collect(),
+                    },
+                    (Some(_), None) => Vec::new(),
+                })
+                .collect(),
+            Model::Bound(name, cell) => {
+                let outputs = |(port, _): &(&IdString, &Vec<Bit>)| cell.port_directions.get(*port) == Some(&Direction::Output);
+                let inputs: PortValues = (cell.connections.iter())
+                    .filter(|connection| !outputs(connection))
+                    .map(|(port, bits)| (port.to_string(), bits.iter().map(|bit| logic(bit, values) == Logic::_1).collect()))
+                    .collect();
+                let values = self.bound.get(name, &cell.module).unwrap().eval(name, &inputs);
+                let mut changes = Vec::new();
+                for (port, bits) in cell.connections.iter().filter(outputs) {
+                    let bad_output = || SimError::Cosim(CosimError::BadOutput { cell: name.clone(), port: port.to_string() });
+                    let value = values.get(port).filter(|value| value.len() == bits.len()).ok_or_else(bad_output)?;
+                    changes.extend(bits.iter().copied().zip(value.iter().map(|value| Logic::from(*value))));
+                }
+                changes
+            }
+        })
+    }
+
+    /// Samples the registers and memories whose clock made an edge since the last call, and
+    /// returns the register updates and the memories written.
+    fn clock_edges(&mut self) -> (Vec<(Bit, Logic)>, Vec<usize>) {
+        let values = &self.values;
+        let word = |bits: &[Bit]| -> Word { bits.iter().map(|bit| logic(bit, values)).collect() };
+        let (mut updates, mut written) = (Vec::new(), Vec::new());
+        for (index, model) in self.models.iter_mut().enumerate() {
+            match model {
+                Model::Flop(flop) => {
+                    if !edge(&mut flop.last, logic(&flop.clock.0, values), flop.clock.1) {
+                        continue;
+                    }
+                    if let Some((reset, polarity, _)) = &flop.async_reset
+                        && active(reset, *polarity, values) != Logic::_0
+                    {
+                        continue;
+                    }
+                    let enable = flop.enable.as_ref().map_or(Logic::_1, |(enable, polarity)| active(enable, *polarity, values));
+                    let d = word(&flop.d);
+                    let reset = flop
+                        .sync_reset
+                        .as_ref()
+                        .map_or((Logic::_0, &d), |(reset, polarity, value)| (active(reset, *polarity, values), value));
+                    let next = match (reset, enable) {
+                        ((Logic::_1, value), _) if flop.reset_over_enable => value.clone(),
+                        ((Logic::_0, _), Logic::_0) => continue,
+                        ((_, value), Logic::_0) if flop.reset_over_enable => merge(value, &word(&flop.q)),
+                        (_, Logic::_0) => continue,
+                        ((Logic::_1, value), _) => value.clone(),
+                        ((Logic::_0, _), _) => d.clone(),
+                        ((_, value), _) => merge(value, &d),
+                    };
+                    let next = if enable == Logic::_1 { next } else { merge(&word(&flop.q), &next) };
+                    updates.extend(flop.q.iter().copied().zip(next));
+                }
+                Model::Memory(memory) => {
+                    let triggered: Vec<usize> = (memory.reads.iter_mut().enumerate())
+                        .filter_map(|(port, read)| {
+                            let (clock, polarity) = read.clock?;
+                            edge(&mut read.last, logic(&clock, values), polarity).then_some(port)
+                        })
+                        .collect();
+                    let mut stores = Vec::new();
+                    for (port, write) in memory.writes.iter_mut().enumerate() {
+                        if edge(&mut write.last, logic(&write.clock.0, values), write.clock.1) {
+                            stores.push((port, word(&write.address), word(&write.enable), word(&write.data)));
+                        }
+                    }
+                    for read in triggered.into_iter().map(|port| &memory.reads[port]) {
+                        if let Some((reset, _)) = &read.async_reset
+                            && logic(reset, values) != Logic::_0
+                        {
+                            continue;
+                        }
+                        let address = word(&read.address);
+                        let mut data = memory.read(&address);
+                        for (port, write_address, enable, write_data) in stores.iter() {
+                            if !(read.transparent[*port] || read.collision_x[*port])
+                                || memory.index(&address).is_none()
+                                || memory.index(&address) != memory.index(write_address)
+                            {
+                                continue;
+                            }
+                            let new = if read.collision_x[*port] { unknown(memory.width) } else { write_data.clone() };
+                            for (bit, (enable, new)) in data.iter_mut().zip(enable.iter().zip(new)) {
+                                match enable {
+                                    Logic::_1 => *bit = new,
+                                    Logic::_0 => {}
+                                    _ => *bit = merge(&[*bit], &[new])[0],
+                                }
+                            }
+                        }
+                        let current = word(&read.data);
+                        let enable = logic(&read.enable, values);
+                        let loaded = match enable {
+                            Logic::_1 => data,
+                            Logic::_0 => current,
+                            _ => merge(&current, &data),
+                        };
+                        let next = match &read.sync_reset {
+                            Some((reset, value)) => {
+                                let reset = logic(reset, values);
+                                match if read.enable_over_reset { reset.and(enable) } else { reset } {
+                                    Logic::_1 => value.clone(),
+                                    Logic::_0 => loaded,
+                                    _ => merge(value, &loaded),
+                                }
+                            }
+                            None => loaded,
+                        };
+                        updates.extend(read.data.iter().copied().zip(next));
+                    }
+                    for (_, address, enable, data) in stores {
+                        let Some(address) = memory.index(&address) else { continue };
+                        let mut contents = memory.word(address);
+                        for (bit, (enable, data)) in contents.iter_mut().zip(enable.into_iter().zip(data)) {
+                            match enable {
+                                Logic::_1 => *bit = data,
+                                Logic::_0 => {}
+                                _ => *bit = merge(&[*bit], &[data])[0],
+                            }
+                        }
+                        memory.written.insert(address, contents);
+                        written.push(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        (updates, written)
+    }
+
+    /// Propagates changes until the logic settles, triggering registers on clock edges.
+    fn settle(&mut self) -> Result<(), SimError> {
+        let limit = MAX_EVALUATIONS * self.models.len().max(1);
+        let mut evaluations = 0;
+        loop {
+            while let Some(model) = self.pending.pop_front() {
+                self.queued[model] = false;
+                evaluations += 1;
+                if evaluations > limit {
+                    return Err(SimError::Oscillation);
+                }
+                for (bit, value) in self.evaluate(model)? {
+                    self.set(bit, value);
+                }
+            }
+            let (updates, written) = self.clock_edges();
+            if updates.is_empty() && written.is_empty() {
+                return Ok(());
+            }
+            for (bit, value) in updates {
+                self.set(bit, value);
+            }
+            for model in written {
+                self.schedule(model);
+            }
+        }
+    }
+
+    /// Drives an input port, least significant bit first, and lets the logic settle.
+    pub fn poke(&mut self, port: &str, value: &[Logic]) -> Result<(), SimError> {
+        let bits = match self.ports.get(port) {
+            Some((Direction::Input | Direction::InOut, bits)) => bits.clone(),
+            _ => return Err(SimError::UnknownPort(port.to_string())),
+        };
+        if bits.len() != value.len() {
+            return Err(SimError::WidthMismatch { port: port.to_string(), expected: bits.len(), found: value.len() });
+        }
+        for (bit, value) in bits.into_iter().zip(value) {
+            self.set(bit, *value);
+        }
+        self.settle()
+    }
+
+    pub fn poke_u64(&mut self, port: &str, value: u64) -> Result<(), SimError> {
+        let width = self.ports.get(port).map_or(0, |(_, bits)| bits.len());
+        let value: Word = (0..width).map(|index| Logic::from(index < 64 && value >> index & 1 == 1)).collect();
+        self.poke(port, &value)
+    }
+
+    /// The value of a single bit, as for sampling nets.
+    pub fn value(&self, bit: &Bit) -> Logic {
+        logic(bit, &self.values)
+    }
+
+    /// The value of any port, least significant bit first.
+    pub fn peek(&self, port: &str) -> Result<Vec<Logic>, SimError> {
+        let (_, bits) = self.ports.get(port).ok_or_else(|| SimError::UnknownPort(port.to_string()))?;
+        Ok(bits.iter().map(|bit| logic(bit, &self.values)).collect())
+    }
+
+    /// The value of a port as an integer, `None` when a bit is `x` or `z` or the port is
+    /// wider than 64 bits.
+    pub fn peek_u64(&self, port: &str) -> Result<Option<u64>, SimError> {
+        let value = self.peek(port)?;
+        Ok(known(&value).filter(|bits| bits.len() <= 64).map(|bits| bits.iter().rev().fold(0, |value, bit| value << 1 | *bit as u64)))
+    }
+
+    /// Runs one cycle of the clock on input port `clock`: drives it low, then high. Registers
+    /// on the rising edge trigger at the end of the step, those on the falling edge at the
+    /// start of the next one.
+    pub fn step(&mut self, clock: &str) -> Result<(), SimError> {
+        self.poke(clock, &[Logic::_0])?;
+        self.poke(clock, &[Logic::_1])?;
+        self.time += 1;
+        Ok(())
+    }
+
+    /// Clock cycles run by [`step`](Self::step), or restored from a checkpoint.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// The current state: the outputs of the registers, the memory contents and the cycles run.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let encode = |word: &[Logic]| encode(&word.iter().map(|value| value.known()).collect::<Vec<_>>());
+        let mut checkpoint = Checkpoint { time: self.time, ..Checkpoint::default() };
+        for (name, model) in self.state.iter() {
+            match &self.models[*model] {
+                Model::Flop(Flop { q, .. }) | Model::Latch(_, _, _, q) => {
+                    let value: Word = q.iter().map(|bit| self.value(bit)).collect();
+                    checkpoint.registers.insert(name.clone(), encode(&value));
+                }
+                Model::Memory(memory) => {
+                    let words = (0..memory.size).map(|index| encode(&memory.word(index))).collect();
+                    checkpoint.memories.insert(name.clone(), words);
+                }
+                _ => unreachable!(),
+            }
+        }
+        checkpoint
+    }
+
+    /// Continues from `checkpoint`: loads the registers and memories it holds and the cycles
+    /// run, then lets the logic settle. Registers and memories missing from it keep their
+    /// state, and nothing changes when it does not fit the module.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), SimError> {
+        let logic = |bits: Vec<Option<bool>>| -> Word { bits.into_iter().map(|bit| bit.map_or(Logic::X, Logic::from)).collect() };
+        let mut updates = Vec::new();
+        for (name, value) in checkpoint.registers.iter() {
+            let q = match self.state.get(name).map(|model| &self.models[*model]) {
+                Some(Model::Flop(Flop { q, .. }) | Model::Latch(_, _, _, q)) => q,
+                _ => return Err(SimError::Checkpoint(CheckpointError::UnknownRegister(name.clone()))),
+            };
+            let value = parse(name, value, q.len()).map_err(SimError::Checkpoint)?;
+            updates.extend(q.iter().copied().zip(logic(value)));
+        }
+        let mut stores = Vec::new();
+        for (name, words) in checkpoint.memories.iter() {
+            let (model, memory) = match self.state.get(name).map(|model| (*model, &self.models[*model])) {
+                Some((model, Model::Memory(memory))) => (model, memory),
+                _ => return Err(SimError::Checkpoint(CheckpointError::UnknownMemory(name.clone()))),
+            };
+            if words.len() > memory.size {
+                let error = CheckpointError::WidthMismatch { name: name.clone(), expected: memory.size, found: words.len() };
+                return Err(SimError::Checkpoint(error));
+            }
+            for (address, word) in words.iter().enumerate() {
+                let value = parse(&format!("{}[{}]", name, address), word, memory.width).map_err(SimError::Checkpoint)?;
+                stores.push((model, address, logic(value)));
+            }
+        }
+
+        for (bit, value) in updates {
+            self.set(bit, value);
+        }
+        for (model, address, word) in stores {
+            if let Model::Memory(memory) = &mut self.models[model] {
+                memory.written.insert(address, word);
+            }
+            self.schedule(model);
+        }
+        self.time = checkpoint.time;
+        self.settle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    /// A 4-bit counter with a synchronous reset and a flag raised at 5.
+    fn counter() -> Module {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let rst = builder.input("rst", 1).unwrap();
+        let count = builder.output("count", 4).unwrap();
+        let five = builder.output("five", 1).unwrap();
+        let next = builder.wire("$next", 4).unwrap();
+        let constant = |value: u64| (0..4).map(|index| if value >> index & 1 == 1 { Bit::_1 } else { Bit::_0 }).collect::<Vec<_>>();
+        builder.cell("add", CellBuilder::new("$add").input("A", count.clone()).input("B", constant(1)).output("Y", next.clone())).unwrap();
+        let reg = CellBuilder::new("$sdff")
+            .parameter("CLK_POLARITY", 1)
+            .parameter("SRST_POLARITY", 1)
+            .parameter("SRST_VALUE", "0000")
+            .input("CLK", clk)
+            .input("SRST", rst)
+            .input("D", next)
+            .output("Q", count.clone());
+        builder.cell("reg", reg).unwrap();
+        builder.cell("eq", CellBuilder::new("$eq").input("A", count).input("B", constant(5)).output("Y", five)).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn test_counter() {
+        let mut sim = Simulator::new(&counter()).unwrap();
+        assert_eq!(sim.peek("count").unwrap(), [Logic::X; 4]);
+        assert_eq!(sim.peek_u64("five").unwrap(), None);
+        sim.poke_u64("rst", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("count").unwrap(), Some(0));
+        sim.poke_u64("rst", 0).unwrap();
+        for _ in 0..5 {
+            sim.step("clk").unwrap();
+        }
+        assert_eq!(sim.peek_u64("count").unwrap(), Some(5));
+        assert_eq!(sim.peek_u64("five").unwrap(), Some(1));
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("five").unwrap(), Some(0));
+        assert_eq!(sim.poke_u64("count", 1), Err(SimError::UnknownPort("count".to_string())));
+    }
+
+    #[test]
+    fn test_memory_and_x() {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let we = builder.input("we", 1).unwrap();
+        let addr = builder.input("addr", 2).unwrap();
+        let data = builder.input("data", 4).unwrap();
+        let q = builder.output("q", 4).unwrap();
+        let mem = CellBuilder::new("$mem_v2")
+            .parameter("WIDTH", 4)
+            .parameter("SIZE", 4)
+            .parameter("ABITS", 2)
+            .parameter("OFFSET", 0)
+            .parameter("RD_PORTS", 1)
+            .parameter("WR_PORTS", 1)
+            .parameter("RD_CLK_ENABLE", "0")
+            .parameter("RD_CLK_POLARITY", "0")
+            .parameter("WR_CLK_ENABLE", "1")
+            .parameter("WR_CLK_POLARITY", "1")
+            .parameter("INIT", "0011001000010000")
+            .input("RD_CLK", vec![Bit::X])
+            .input("RD_EN", vec![Bit::_1])
+            .input("RD_ADDR", addr.clone())
+            .output("RD_DATA", q)
+            .input("WR_CLK", clk)
+            .input("WR_EN", vec![we[0]; 4])
+            .input("WR_ADDR", addr)
+            .input("WR_DATA", data);
+        builder.cell("mem", mem).unwrap();
+        let mut sim = Simulator::new(&builder.build()).unwrap();
+
+        assert_eq!(sim.peek_u64("q").unwrap(), None);
+        sim.poke_u64("addr", 2).unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(2));
+        sim.poke_u64("we", 1).unwrap();
+        sim.poke_u64("data", 9).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(9));
+        sim.poke("addr", &[Logic::X, Logic::_0]).unwrap();
+        assert_eq!(sim.peek("q").unwrap(), [Logic::X; 4]);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        // Stop the counter at 3 and a memory after a write, then continue both in new simulators.
+        let mut sim = Simulator::new(&counter()).unwrap();
+        sim.poke_u64("rst", 1).unwrap();
+        sim.step("clk").unwrap();
+        sim.poke_u64("rst", 0).unwrap();
+        for _ in 0..3 {
+            sim.step("clk").unwrap();
+        }
+        let checkpoint = sim.checkpoint();
+        assert_eq!(checkpoint.time, 4);
+        assert_eq!(checkpoint.registers["reg"], "0011");
+        let mut json = Vec::new();
+        checkpoint.to_writer(&mut json).unwrap();
+        let mut restored = Simulator::new(&counter()).unwrap();
+        restored.poke_u64("rst", 0).unwrap();
+        restored.restore(&Checkpoint::from_reader(json.as_slice()).unwrap()).unwrap();
+        assert_eq!((restored.time(), restored.peek_u64("count").unwrap()), (4, Some(3)));
+        for _ in 0..2 {
+            sim.step("clk").unwrap();
+            restored.step("clk").unwrap();
+        }
+        assert_eq!(restored.peek_u64("five").unwrap(), Some(1));
+        assert_eq!(restored.checkpoint(), sim.checkpoint());
+
+        let module = block_ram("0", "0", "0");
+        let mut sim = Simulator::new(&module).unwrap();
+        reset(&mut sim);
+        sim.poke_u64("we", 1).unwrap();
+        sim.step("clk").unwrap();
+        let checkpoint = sim.checkpoint();
+        assert_eq!(checkpoint.memories["mem"], ["0000", "1001", "0010", "0011"]);
+        let mut restored = Simulator::new(&module).unwrap();
+        reset(&mut restored);
+        restored.restore(&checkpoint).unwrap();
+        restored.poke_u64("we", 0).unwrap();
+        restored.step("clk").unwrap();
+        assert_eq!(restored.peek_u64("q").unwrap(), Some(9));
+
+        let misplaced = Checkpoint { registers: IndexMap::from([("mem".to_string(), "0".to_string())]), ..Checkpoint::default() };
+        assert_eq!(restored.restore(&misplaced), Err(SimError::Checkpoint(CheckpointError::UnknownRegister("mem".to_string()))));
+    }
+
+    /// A 4x4 memory with one synchronous read port on the write clock, `RD_ARST` and `RD_SRST`
+    /// wired to the `arst` and `srst` inputs.
+    fn block_ram(transparent: &str, collision_x: &str, ce_over_srst: &str) -> Module {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let we = builder.input("we", 1).unwrap();
+        let re = builder.input("re", 1).unwrap();
+        let arst = builder.input("arst", 1).unwrap();
+        let srst = builder.input("srst", 1).unwrap();
+        let addr = builder.input("addr", 2).unwrap();
+        let data = builder.input("data", 4).unwrap();
+        let q = builder.output("q", 4).unwrap();
+        let mem = CellBuilder::new("$mem_v2")
+            .parameter("WIDTH", 4)
+            .parameter("SIZE", 4)
+            .parameter("ABITS", 2)
+            .parameter("OFFSET", 0)
+            .parameter("RD_PORTS", 1)
+            .parameter("WR_PORTS", 1)
+            .parameter("RD_CLK_ENABLE", "1")
+            .parameter("RD_CLK_POLARITY", "1")
+            .parameter("RD_TRANSPARENCY_MASK", transparent)
+            .parameter("RD_COLLISION_X_MASK", collision_x)
+            .parameter("RD_CE_OVER_SRST", ce_over_srst)
+            .parameter("RD_ARST_VALUE", "1111")
+            .parameter("RD_SRST_VALUE", "0101")
+            .parameter("RD_INIT_VALUE", "1010")
+            .parameter("WR_CLK_ENABLE", "1")
+            .parameter("WR_CLK_POLARITY", "1")
+            .parameter("INIT", "0011001000010000")
+            .input("RD_CLK", clk.clone())
+            .input("RD_EN", re)
+            .input("RD_ARST", arst)
+            .input("RD_SRST", srst)
+            .input("RD_ADDR", addr.clone())
+            .output("RD_DATA", q)
+            .input("WR_CLK", clk)
+            .input("WR_EN", vec![we[0]; 4])
+            .input("WR_ADDR", addr)
+            .input("WR_DATA", data);
+        builder.cell("mem", mem).unwrap();
+        builder.build()
+    }
+
+    fn reset(sim: &mut Simulator) {
+        for (port, value) in [("we", 0), ("re", 1), ("arst", 0), ("srst", 0), ("addr", 1), ("data", 9)] {
+            sim.poke_u64(port, value).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_memory_read_ports() {
+        // Read-first: the read on the writing edge returns the old word.
+        // `arst` starts as `x`, so only the bits where the init and reset values agree are known.
+        let mut sim = Simulator::new(&block_ram("0", "0", "0")).unwrap();
+        assert_eq!(sim.peek("q").unwrap(), [Logic::X, Logic::_1, Logic::X, Logic::_1]);
+        reset(&mut sim);
+        sim.poke_u64("we", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(1));
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(9));
+
+        // Write-first: the read sees the word being written.
+        let mut sim = Simulator::new(&block_ram("1", "0", "0")).unwrap();
+        reset(&mut sim);
+        sim.poke_u64("we", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(9));
+
+        // Collisions read as `x`.
+        let mut sim = Simulator::new(&block_ram("0", "1", "0")).unwrap();
+        reset(&mut sim);
+        sim.poke_u64("we", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek("q").unwrap(), [Logic::X; 4]);
+
+        // The synchronous reset works while disabled unless the enable comes first.
+        let mut sim = Simulator::new(&block_ram("0", "0", "0")).unwrap();
+        reset(&mut sim);
+        sim.poke_u64("re", 0).unwrap();
+        sim.poke_u64("srst", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(0b0101));
+        let mut sim = Simulator::new(&block_ram("0", "0", "1")).unwrap();
+        reset(&mut sim);
+        sim.step("clk").unwrap();
+        sim.poke_u64("re", 0).unwrap();
+        sim.poke_u64("srst", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(1));
+
+        // The asynchronous reset acts at once and holds against the clock.
+        sim.poke_u64("arst", 1).unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(0b1111));
+        sim.poke_u64("srst", 0).unwrap();
+        sim.poke_u64("re", 1).unwrap();
+        sim.step("clk").unwrap();
+        assert_eq!(sim.peek_u64("q").unwrap(), Some(0b1111));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::hierarchy::{HierarchyError, Tops};
+use crate::{Module, Netlist};
+
+/// Size of a design in the terms of `yosys stat`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub wires: usize,
+    pub wire_bits: usize,
+    /// Wires whose name is not hidden.
+    pub public_wires: usize,
+    pub public_wire_bits: usize,
+    pub ports: usize,
+    pub port_bits: usize,
+    /// Memories in `memories` and `$mem` cells.
+    pub memories: usize,
+    pub memory_bits: usize,
+    pub cells: usize,
+    pub cell_types: BTreeMap<String, usize>,
+    /// Register bits: the width of the `Q` output of every flip-flop and latch.
+    pub flops: usize,
+}
+
+impl Stats {
+    /// Adds `count` copies of `other`.
+    fn add(&mut self, other: &Stats, count: usize) {
+        self.wires += other.wires * count;
+        self.wire_bits += other.wire_bits * count;
+        self.public_wires += other.public_wires * count;
+        self.public_wire_bits += other.public_wire_bits * count;
+        self.ports += other.ports * count;
+        self.port_bits += other.port_bits * count;
+        self.memories += other.memories * count;
+        self.memory_bits += other.memory_bits * count;
+        self.cells += other.cells * count;
+        self.flops += other.flops * count;
+        for (cell_type, cells) in other.cell_types.iter() {
+            *self.cell_types.entry(cell_type.clone()).or_default() += cells * count;
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("wires", self.wires),
+            ("wire bits", self.wire_bits),
+            ("public wires", self.public_wires),
+            ("public wire bits", self.public_wire_bits),
+            ("ports", self.ports),
+            ("port bits", self.port_bits),
+            ("memories", self.memories),
+            ("memory bits", self.memory_bits),
+            ("cells", self.cells),
+        ];
+        for (label, count) in rows {
+            writeln!(f, "   {:<30}{:>6}", format!("Number of {}:", label), count)?;
+        }
+        for (cell_type, count) in self.cell_types.iter() {
+            writeln!(f, "     {:<28}{:>6}", cell_type, count)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "   {:<30}{:>6}", "Estimated number of flops:", self.flops)
+    }
+}
+
+impl Module {
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            wires: self.nets.len(),
+            wire_bits: self.nets.values().map(|net| net.bits.len()).sum(),
+            public_wires: self.nets.values().filter(|net| !net.hide_name).count(),
+            public_wire_bits: self.nets.values().filter(|net| !net.hide_name).map(|net| net.bits.len()).sum(),
+            ports: self.ports.len(),
+            port_bits: self.ports.values().map(|port| port.bits.len()).sum(),
+            memories: self.memories.len(),
+            memory_bits: self.memories.values().map(|memory| memory.width * memory.size).sum(),
+            cells: self.cells.len(),
+            ..Stats::default()
+        };
+        for cell in self.cells.values() {
+            *stats.cell_types.entry(cell.module.to_string()).or_default() += 1;
+            if cell.is_register() {
+                stats.flops += cell.connections.get("Q").map_or(1, Vec::len);
+            }
+            if matches!(cell.module.as_str(), "$mem" | "$mem_v2") {
+                let size = |name| cell.param(name).and_then(|value| value.as_u64()).unwrap_or(0) as usize;
+                stats.memories += 1;
+                stats.memory_bits += size("WIDTH") * size("SIZE");
+            }
+        }
+        stats
+    }
+}
+
+impl Netlist {
+    /// Statistics of the whole design, like the `design hierarchy` section of `yosys stat`:
+    /// every module counted once per instance below the [top modules](Netlist::top_modules),
+    /// with the instances themselves replaced by their contents.
+    pub fn stats(&self) -> Stats {
+        self.stats_below(&self.top_modules())
+    }
+
+    /// Statistics of the design below `tops`, counted like [`Netlist::stats`].
+    pub fn stats_for(&self, tops: &Tops) -> Result<Stats, HierarchyError> {
+        Ok(self.stats_below(&self.resolve_tops(tops)?))
+    }
+
+    fn stats_below(&self, tops: &[&str]) -> Stats {
+        let per_module: HashMap<&str, Stats> = self.modules.iter().map(|(name, module)| (name.as_str(), module.stats())).collect();
+        let mut stats = Stats::default();
+        let mut stack: Vec<(&str, usize, Vec<&str>)> = tops.iter().map(|name| (*name, 1, Vec::new())).collect();
+        while let Some((name, count, mut path)) = stack.pop() {
+            if path.contains(&name) {
+                continue;
+            }
+            stats.add(&per_module[name], count);
+            path.push(name);
+            for cell in self.modules[name].cells.values().filter(|cell| self.modules.contains_key(&cell.module)) {
+                stack.push((cell.module.as_str(), count, path.clone()));
+            }
+        }
+        for name in self.modules.keys() {
+            if let Some(cells) = stats.cell_types.remove(name.as_str()) {
+                stats.cells -= cells;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CellBuilder;
+
+    /// A register after an adder wrapped in a module of its own.
+    fn netlist() -> Netlist {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let a = builder.input("a", 4).unwrap();
+        let y = builder.output("y", 4).unwrap();
+        let sum = builder.wire("$sum", 4).unwrap();
+        builder.cell("add", CellBuilder::new("$add").input("A", a.clone()).input("B", a).output("Y", sum.clone())).unwrap();
+        builder.cell("reg", CellBuilder::new("$dff").input("CLK", clk).input("D", sum).output("Q", y)).unwrap();
+        let mut netlist = Netlist::new("test");
+        netlist.modules.insert("top".into(), builder.build());
+        netlist.wrap_cells("top", &["add"], "adder", "u_adder").unwrap();
+        netlist
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut netlist = netlist();
+        let top = netlist.modules["top"].stats();
+        assert_eq!((top.wires, top.wire_bits, top.public_wires, top.public_wire_bits), (4, 13, 3, 9));
+        assert_eq!((top.ports, top.port_bits, top.cells, top.flops), (3, 9, 2, 4));
+        assert_eq!(top.cell_types, BTreeMap::from([("$dff".to_string(), 1), ("adder".to_string(), 1)]));
+
+        let instance = netlist.modules["top"].cells["u_adder"].clone();
+        netlist.modules["top"].cells.insert("u_adder2".into(), instance);
+        let design = netlist.stats();
+        assert_eq!(design.cells, 3);
+        assert_eq!(design.cell_types, BTreeMap::from([("$add".to_string(), 2), ("$dff".to_string(), 1)]));
+        let text = design.to_string();
+        assert!(text.contains("   Number of cells:                   3\n"));
+        assert!(text.contains("     $add                             2\n"));
+        assert!(text.ends_with("   Estimated number of flops:         4\n"));
+        assert_eq!(netlist.stats_for(&Tops::Auto).unwrap(), design);
+        assert_eq!(netlist.stats_for(&Tops::Named(vec!["adder".to_string()])).unwrap().cell_types.len(), 1);
+        assert_eq!(netlist.stats_for(&Tops::Named(vec!["nope".to_string()])), Err(HierarchyError::UnknownModule("nope".to_string())));
+    }
+}
@@ -0,0 +1,363 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::annotate::matches_pattern;
+use crate::gates::gate;
+use crate::progress::pass_span;
+use crate::{Direction, Module, Netlist};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellClass {
+    /// Flip-flops and latches.
+    Sequential,
+    Combinational,
+    /// Constant drivers.
+    Tie,
+    /// Filler, decap, tap, endcap and antenna cells, which have no logic function.
+    Fill,
+}
+
+/// The Yosys gate-level primitive a library cell computes, with its ports as `(library,
+/// primitive)` pairs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellFunction {
+    pub gate: String,
+    pub ports: Vec<(String, String)>,
+}
+
+/// Class and function of every cell whose type matches `cell`, exactly or as a prefix when it
+/// ends in `*`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StdCellRule {
+    pub cell: String,
+    pub class: CellClass,
+    #[serde(default)]
+    pub function: Option<CellFunction>,
+}
+
+/// Cell classification, areas and functions of a standard cell library. The first matching
+/// rule counts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StdCellLibrary {
+    pub name: String,
+    pub rules: Vec<StdCellRule>,
+    /// Area of a cell type in square micrometers.
+    #[serde(default)]
+    pub areas: IndexMap<String, f64>,
+}
+
+/// Rules for a library with cell names `<prefix><name>`, from `(name, class, primitive,
+/// ports)` where ports alternate library and primitive names.
+fn rules(prefix: &str, table: &[(&str, CellClass, &str, &[&str])]) -> Vec<StdCellRule> {
+    (table.iter())
+        .map(|(cell, class, gate, ports)| StdCellRule {
+            cell: format!("{}{}", prefix, cell),
+            class: *class,
+            function: (!gate.is_empty()).then(|| CellFunction {
+                gate: gate.to_string(),
+                ports: ports.chunks(2).map(|pair| (pair[0].to_string(), pair[1].to_string())).collect(),
+            }),
+        })
+        .collect()
+}
+
+fn areas(prefix: &str, table: &[(&str, f64)]) -> IndexMap<String, f64> {
+    table.iter().map(|(cell, area)| (format!("{}{}", prefix, cell), *area)).collect()
+}
+
+impl StdCellLibrary {
+    /// SkyWater sky130 high density, `sky130_fd_sc_hd`, with the areas of the common cells.
+    pub fn sky130() -> Self {
+        use CellClass::*;
+        let prefix = "sky130_fd_sc_hd__";
+        let rules = rules(
+            prefix,
+            &[
+                ("fill_*", Fill, "", &[]),
+                ("decap_*", Fill, "", &[]),
+                ("tapvpwrvgnd_*", Fill, "", &[]),
+                ("diode_*", Fill, "", &[]),
+                ("conb_*", Tie, "", &[]),
+                ("dfxtp_*", Sequential, "$_DFF_P_", &["CLK", "C", "D", "D", "Q", "Q"]),
+                ("dfrtp_*", Sequential, "$_DFF_PN0_", &["CLK", "C", "D", "D", "RESET_B", "R", "Q", "Q"]),
+                ("dfstp_*", Sequential, "$_DFF_PN1_", &["CLK", "C", "D", "D", "SET_B", "R", "Q", "Q"]),
+                ("df*", Sequential, "", &[]),
+                ("dl*", Sequential, "", &[]),
+                ("sdf*", Sequential, "", &[]),
+                ("edf*", Sequential, "", &[]),
+                ("inv_*", Combinational, "$_NOT_", &["A", "A", "Y", "Y"]),
+                ("clkinv_*", Combinational, "$_NOT_", &["A", "A", "Y", "Y"]),
+                ("buf_*", Combinational, "$_BUF_", &["A", "A", "X", "Y"]),
+                ("clkbuf_*", Combinational, "$_BUF_", &["A", "A", "X", "Y"]),
+                ("and2_*", Combinational, "$_AND_", &["A", "A", "B", "B", "X", "Y"]),
+                ("or2_*", Combinational, "$_OR_", &["A", "A", "B", "B", "X", "Y"]),
+                ("nand2_*", Combinational, "$_NAND_", &["A", "A", "B", "B", "Y", "Y"]),
+                ("nor2_*", Combinational, "$_NOR_", &["A", "A", "B", "B", "Y", "Y"]),
+                ("xor2_*", Combinational, "$_XOR_", &["A", "A", "B", "B", "X", "Y"]),
+                ("xnor2_*", Combinational, "$_XNOR_", &["A", "A", "B", "B", "Y", "Y"]),
+                ("mux2_*", Combinational, "$_MUX_", &["A0", "A", "A1", "B", "S", "S", "X", "Y"]),
+                ("*", Combinational, "", &[]),
+            ],
+        );
+        let areas = areas(
+            prefix,
+            &[
+                ("fill_1", 1.2512),
+                ("fill_2", 2.5024),
+                ("fill_4", 5.0048),
+                ("fill_8", 10.0096),
+                ("decap_3", 3.7536),
+                ("decap_4", 5.0048),
+                ("decap_8", 10.0096),
+                ("tapvpwrvgnd_1", 1.2512),
+                ("diode_2", 2.5024),
+                ("conb_1", 3.7536),
+                ("inv_1", 3.7536),
+                ("buf_1", 3.7536),
+                ("buf_2", 5.0048),
+                ("nand2_1", 3.7536),
+                ("nor2_1", 3.7536),
+                ("and2_1", 6.256),
+                ("or2_1", 6.256),
+                ("xor2_1", 8.7584),
+                ("xnor2_1", 8.7584),
+                ("mux2_1", 11.2608),
+                ("dfxtp_1", 20.0192),
+                ("dfrtp_1", 25.024),
+            ],
+        );
+        Self { name: "sky130_fd_sc_hd".to_string(), rules, areas }
+    }
+
+    /// GlobalFoundries gf180mcu 7-track, `gf180mcu_fd_sc_mcu7t5v0`. Areas are not included.
+    pub fn gf180() -> Self {
+        use CellClass::*;
+        let rules = rules(
+            "gf180mcu_fd_sc_mcu7t5v0__",
+            &[
+                ("fill*", Fill, "", &[]),
+                ("endcap", Fill, "", &[]),
+                ("antenna", Fill, "", &[]),
+                ("tieh", Tie, "", &[]),
+                ("tiel", Tie, "", &[]),
+                ("dffq_*", Sequential, "$_DFF_P_", &["CLK", "C", "D", "D", "Q", "Q"]),
+                ("dffrnq_*", Sequential, "$_DFF_PN0_", &["CLK", "C", "D", "D", "RN", "R", "Q", "Q"]),
+                ("dffsnq_*", Sequential, "$_DFF_PN1_", &["CLK", "C", "D", "D", "SETN", "R", "Q", "Q"]),
+                ("dff*", Sequential, "", &[]),
+                ("sdff*", Sequential, "", &[]),
+                ("lat*", Sequential, "", &[]),
+                ("inv_*", Combinational, "$_NOT_", &["I", "A", "ZN", "Y"]),
+                ("buf_*", Combinational, "$_BUF_", &["I", "A", "Z", "Y"]),
+                ("and2_*", Combinational, "$_AND_", &["A1", "A", "A2", "B", "Z", "Y"]),
+                ("or2_*", Combinational, "$_OR_", &["A1", "A", "A2", "B", "Z", "Y"]),
+                ("nand2_*", Combinational, "$_NAND_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("nor2_*", Combinational, "$_NOR_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("xor2_*", Combinational, "$_XOR_", &["A1", "A", "A2", "B", "Z", "Y"]),
+                ("xnor2_*", Combinational, "$_XNOR_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("mux2_*", Combinational, "$_MUX_", &["I0", "A", "I1", "B", "S", "S", "Z", "Y"]),
+                ("*", Combinational, "", &[]),
+            ],
+        );
+        Self { name: "gf180mcu_fd_sc_mcu7t5v0".to_string(), rules, areas: IndexMap::new() }
+    }
+
+    /// Nangate 45 nm Open Cell Library, with the areas of the common cells.
+    pub fn nangate45() -> Self {
+        use CellClass::*;
+        let mut rules = rules(
+            "",
+            &[
+                ("FILLCELL_*", Fill, "", &[]),
+                ("TAPCELL_*", Fill, "", &[]),
+                ("ANTENNA_*", Fill, "", &[]),
+                ("LOGIC0_*", Tie, "", &[]),
+                ("LOGIC1_*", Tie, "", &[]),
+                ("DFF_*", Sequential, "$_DFF_P_", &["CK", "C", "D", "D", "Q", "Q"]),
+                ("DFFR_*", Sequential, "$_DFF_PN0_", &["CK", "C", "D", "D", "RN", "R", "Q", "Q"]),
+                ("DFFS_*", Sequential, "$_DFF_PN1_", &["CK", "C", "D", "D", "SN", "R", "Q", "Q"]),
+                ("INV_*", Combinational, "$_NOT_", &["A", "A", "ZN", "Y"]),
+                ("BUF_*", Combinational, "$_BUF_", &["A", "A", "Z", "Y"]),
+                ("CLKBUF_*", Combinational, "$_BUF_", &["A", "A", "Z", "Y"]),
+                ("AND2_*", Combinational, "$_AND_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("OR2_*", Combinational, "$_OR_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("NAND2_*", Combinational, "$_NAND_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("NOR2_*", Combinational, "$_NOR_", &["A1", "A", "A2", "B", "ZN", "Y"]),
+                ("XOR2_*", Combinational, "$_XOR_", &["A", "A", "B", "B", "Z", "Y"]),
+                ("XNOR2_*", Combinational, "$_XNOR_", &["A", "A", "B", "B", "ZN", "Y"]),
+                ("MUX2_*", Combinational, "$_MUX_", &["A", "A", "B", "B", "S", "S", "Z", "Y"]),
+            ],
+        );
+        let sequential = ["DFF*", "SDFF*", "TLAT*", "DLH*", "DLL*"];
+        let combinational = ["AND*", "OR*", "NAND*", "NOR*", "XOR*", "XNOR*", "MUX*", "AOI*", "OAI*", "FA_*", "HA_*"];
+        for (patterns, class) in [(&sequential[..], Sequential), (&combinational[..], Combinational)] {
+            rules.extend(patterns.iter().map(|cell| StdCellRule { cell: cell.to_string(), class, function: None }));
+        }
+        let areas = areas(
+            "",
+            &[
+                ("FILLCELL_X1", 0.266),
+                ("FILLCELL_X2", 0.532),
+                ("FILLCELL_X4", 1.064),
+                ("FILLCELL_X8", 2.128),
+                ("FILLCELL_X16", 4.256),
+                ("FILLCELL_X32", 8.512),
+                ("TAPCELL_X1", 0.266),
+                ("ANTENNA_X1", 0.266),
+                ("LOGIC0_X1", 0.532),
+                ("LOGIC1_X1", 0.532),
+                ("INV_X1", 0.532),
+                ("BUF_X1", 0.798),
+                ("NAND2_X1", 0.798),
+                ("NOR2_X1", 0.798),
+                ("AND2_X1", 1.064),
+                ("OR2_X1", 1.064),
+                ("AOI21_X1", 1.064),
+                ("OAI21_X1", 1.064),
+                ("XOR2_X1", 1.596),
+                ("XNOR2_X1", 1.596),
+                ("MUX2_X1", 1.862),
+                ("DFF_X1", 4.522),
+                ("DFFR_X1", 5.32),
+                ("DFFS_X1", 5.32),
+            ],
+        );
+        Self { name: "nangate45".to_string(), rules, areas }
+    }
+
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    pub fn rule(&self, cell_type: &str) -> Option<&StdCellRule> {
+        self.rules.iter().find(|rule| matches_pattern(&rule.cell, cell_type))
+    }
+
+    pub fn classify(&self, cell_type: &str) -> Option<CellClass> {
+        self.rule(cell_type).map(|rule| rule.class)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AreaReport {
+    pub library: String,
+    pub cells: IndexMap<CellClass, usize>,
+    /// Area by class, of the cells whose area the library gives.
+    pub area: IndexMap<CellClass, f64>,
+    pub total: f64,
+    /// Counts of cell types matching no rule.
+    pub unmapped: IndexMap<String, usize>,
+    /// Counts of classified cell types without an area.
+    pub missing_area: IndexMap<String, usize>,
+}
+
+impl Netlist {
+    /// Counts and area of the library cells in `top` with everything below it flattened, one
+    /// count per instance of every module.
+    pub fn estimate_area(&self, top: &str, library: &StdCellLibrary) -> AreaReport {
+        let mut report = AreaReport {
+            library: library.name.clone(),
+            cells: IndexMap::new(),
+            area: IndexMap::new(),
+            total: 0.0,
+            unmapped: IndexMap::new(),
+            missing_area: IndexMap::new(),
+        };
+        for cell in self.iter_hierarchy(top).map(|cell| cell.cell.object).filter(|cell| !self.modules.contains_key(&cell.module)) {
+            let Some(class) = library.classify(&cell.module) else {
+                *report.unmapped.entry(cell.module.to_string()).or_default() += 1;
+                continue;
+            };
+            *report.cells.entry(class).or_default() += 1;
+            match library.areas.get(&cell.module) {
+                Some(area) => {
+                    *report.area.entry(class).or_default() += area;
+                    report.total += area;
+                }
+                None => *report.missing_area.entry(cell.module.to_string()).or_default() += 1,
+            }
+        }
+        report
+    }
+}
+
+impl Module {
+    /// Replaces every library cell with a known function by the Yosys gate-level primitive
+    /// it computes, so simulation and formal work on mapped netlists. Cells with a connected
+    /// port the function does not cover, such as an inverted output, are left alone. Returns
+    /// how many cells were replaced.
+    pub fn map_to_gates(&mut self, library: &StdCellLibrary) -> usize {
+        pass_span!("map_to_gates", cells = self.cells.len(), nets = self.nets.len());
+        let mut count = 0;
+        for cell in self.cells.values_mut() {
+            let Some(function) = library.rule(&cell.module).and_then(|rule| rule.function.as_ref()) else { continue };
+            let Some(primitive) = gate(&function.gate) else { continue };
+            if cell.connections.keys().any(|port| function.ports.iter().all(|(from, _)| from != port)) {
+                continue;
+            }
+            let connections = std::mem::take(&mut cell.connections);
+            cell.port_directions.clear();
+            for (from, to) in function.ports.iter() {
+                if let Some(bits) = connections.get(from) {
+                    let direction = if primitive.output == to { Direction::Output } else { Direction::Input };
+                    cell.port_directions.insert(to.into(), direction);
+                    cell.connections.insert(to.into(), bits.clone());
+                }
+            }
+            cell.module = function.gate.as_str().into();
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_estimate_area() {
+        let cell = |module: &str| json!({ "type": module, "connections": {} });
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": { "cells": { "u0": cell("sub"), "u1": cell("sub"), "f": cell("sky130_fd_sc_hd__fill_1"), "g": cell("$_AND_") } },
+                "sub": { "cells": { "n": cell("sky130_fd_sc_hd__nand2_1"), "q": cell("sky130_fd_sc_hd__dfxtp_1"), "a": cell("sky130_fd_sc_hd__a21oi_2") } }
+            }
+        }))
+        .unwrap();
+        let library = StdCellLibrary::sky130();
+        let report = netlist.estimate_area("top", &library);
+        assert_eq!(report.cells[&CellClass::Sequential], 2);
+        assert_eq!(report.cells[&CellClass::Combinational], 4);
+        assert_eq!(report.cells[&CellClass::Fill], 1);
+        assert!((report.total - (1.2512 + 2.0 * (3.7536 + 20.0192))).abs() < 1e-9);
+        assert_eq!(report.unmapped.into_iter().collect::<Vec<_>>(), [("$_AND_".to_string(), 1)]);
+        assert_eq!(report.missing_area["sky130_fd_sc_hd__a21oi_2"], 2);
+        assert_eq!(StdCellLibrary::nangate45().classify("DFFR_X2"), Some(CellClass::Sequential));
+        assert_eq!(StdCellLibrary::gf180().classify("gf180mcu_fd_sc_mcu7t5v0__filltie"), Some(CellClass::Fill));
+    }
+
+    #[test]
+    fn test_map_to_gates() {
+        let mut netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": { "top": {
+                "ports": { "a": { "direction": "input", "bits": [2, 3] }, "y": { "direction": "output", "bits": [4, 5] } },
+                "cells": {
+                    "g": { "type": "NAND2_X1", "connections": { "A1": [2], "A2": [3], "ZN": [4] } },
+                    "q": { "type": "DFF_X1", "connections": { "CK": [2], "D": [4], "Q": [5], "QN": [6] } }
+                }
+            } }
+        }))
+        .unwrap();
+        let module = &mut netlist.modules["top"];
+        assert_eq!(module.map_to_gates(&StdCellLibrary::nangate45()), 1);
+        assert_eq!(module.cells["g"].module, "$_NAND_");
+        assert_eq!(module.cells["g"].connections["B"], [crate::Bit::Signal(3)]);
+        assert_eq!(module.cells["g"].port_directions["Y"], Direction::Output);
+        assert_eq!(module.cells["q"].module, "DFF_X1");
+    }
+}
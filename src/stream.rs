@@ -0,0 +1,119 @@
+use std::fmt;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, Visitor};
+
+use crate::intern::{Interner, current_interner, with_interner};
+use crate::{Error, IdString, Module, Netlist};
+
+/// The `modules` map, handing every module to `visit` as soon as it is read.
+struct Modules<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(IdString, Module)> DeserializeSeed<'de> for Modules<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(IdString, Module)> Visitor<'de> for Modules<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of modules")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<IdString>()? {
+            let module = map.next_value::<Module>()?;
+            (self.0)(name, module);
+        }
+        Ok(())
+    }
+}
+
+/// The whole document, collecting everything but the modules into a netlist.
+struct Document<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(IdString, Module)> DeserializeSeed<'de> for Document<'_, F> {
+    type Value = Netlist;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Netlist, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(IdString, Module)> Visitor<'de> for Document<'_, F> {
+    type Value = Netlist;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a netlist")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Netlist, A::Error> {
+        let (mut creator, mut modules, mut extra) = (None, false, IndexMap::new());
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "creator" => creator = Some(map.next_value()?),
+                "modules" => {
+                    map.next_value_seed(Modules(&mut *self.0))?;
+                    modules = true;
+                }
+                _ => _ = extra.insert(key, map.next_value()?),
+            }
+        }
+        match (creator, modules) {
+            (Some(creator), true) => Ok(Netlist { creator, modules: IndexMap::new(), extra, interner: current_interner() }),
+            (None, _) => Err(de::Error::missing_field("creator")),
+            (_, false) => Err(de::Error::missing_field("modules")),
+        }
+    }
+}
+
+impl Netlist {
+    /// Reads a netlist one module at a time, handing each to `visit` as soon as it is parsed
+    /// and never holding more than one, so netlists larger than memory can be processed.
+    /// Returns the rest of the netlist, without modules. Modules visited before an error in a
+    /// later part of the input have been visited all the same.
+    pub fn read_modules(reader: impl std::io::Read, mut visit: impl FnMut(IdString, Module)) -> Result<Netlist, Error> {
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let mut track = serde_path_to_error::Track::new();
+        let interner = Arc::new(Interner::new());
+        let document = with_interner(&interner, || {
+            Document(&mut visit).deserialize(serde_path_to_error::Deserializer::new(&mut deserializer, &mut track))
+        });
+        let netlist = match document {
+            Ok(netlist) => netlist,
+            Err(error) => return Err(serde_path_to_error::Error::new(track.path(), error).into()),
+        };
+        deserializer.end()?;
+        Ok(netlist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_modules() {
+        let expected = Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        let mut modules = IndexMap::new();
+        let mut netlist = Netlist::read_modules(std::fs::File::open("testdata/modules.json").unwrap(), |name, module| {
+            modules.insert(name, module);
+        })
+        .unwrap();
+        assert!(netlist.modules.is_empty());
+        netlist.modules = modules;
+        assert_eq!(netlist, expected);
+
+        let input = r#"{"creator": "test", "modules": {"top": {"cells": {"c": {"type": 1}}}}}"#;
+        match Netlist::read_modules(input.as_bytes(), |_, _| {}) {
+            Err(Error::Structure { path, .. }) => assert_eq!(path, "modules.top.cells.c.type"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(Netlist::read_modules(r#"{"modules": {}}"#.as_bytes(), |_, _| {}), Err(Error::Structure { .. })));
+    }
+}
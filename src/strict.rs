@@ -0,0 +1,70 @@
+use indexmap::IndexMap;
+
+use crate::{Error, Netlist};
+
+/// The first key of `extra`, if any, as an unknown field below `path`.
+fn reject(path: &str, extra: &IndexMap<String, serde_json::Value>) -> Result<(), Error> {
+    match extra.keys().next() {
+        Some(field) if path.is_empty() => Err(Error::UnknownField { path: field.clone() }),
+        Some(field) => Err(Error::UnknownField { path: format!("{}.{}", path, field) }),
+        None => Ok(()),
+    }
+}
+
+impl Netlist {
+    /// Reads a netlist like [`Netlist::from_reader`], but fails on the first key the netlist
+    /// does not know, such as one emitted by a newer Yosys, with the path to it. Attributes
+    /// and parameters may hold anything.
+    pub fn from_reader_strict(reader: impl std::io::Read) -> Result<Self, Error> {
+        let netlist = Self::from_reader(reader)?;
+        netlist.reject_unknown_fields()?;
+        Ok(netlist)
+    }
+
+    pub fn from_str_strict(input: &str) -> Result<Self, Error> {
+        let netlist = Self::from_str(input)?;
+        netlist.reject_unknown_fields()?;
+        Ok(netlist)
+    }
+
+    fn reject_unknown_fields(&self) -> Result<(), Error> {
+        reject("", self.extra())?;
+        for (name, module) in self.modules.iter() {
+            let path = format!("modules.{}", name);
+            reject(&path, module.extra())?;
+            for (port, value) in module.ports.iter() {
+                reject(&format!("{}.ports.{}", path, port), value.extra())?;
+            }
+            for (cell, value) in module.cells.iter() {
+                reject(&format!("{}.cells.{}", path, cell), value.extra())?;
+            }
+            for (memory, value) in module.memories.iter() {
+                reject(&format!("{}.memories.{}", path, memory), value.extra())?;
+            }
+            for (net, value) in module.nets.iter() {
+                reject(&format!("{}.netnames.{}", path, net), value.extra())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_reader_strict() {
+        let netlist = Netlist::from_reader_strict(std::fs::File::open("testdata/modules.json").unwrap()).unwrap();
+        assert_eq!(netlist, Netlist::from_reader(std::fs::File::open("testdata/modules.json").unwrap()).unwrap());
+
+        let input = r#"{"creator": "test", "modules": {"top": {"cells": {"c": {"type": "$and", "attributes": {"new": 1}, "new": 1}}}}}"#;
+        match Netlist::from_str_strict(input) {
+            Err(Error::UnknownField { path }) => assert_eq!(path, "modules.top.cells.c.new"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Netlist::from_str(input).is_ok());
+        let input = r#"{"creator": "test", "modules": {}, "version": 2}"#;
+        assert_eq!(Netlist::from_str_strict(input).unwrap_err().to_string(), "version: unknown field");
+    }
+}
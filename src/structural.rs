@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use crate::{Bit, Cell, IdString, Module};
+
+/// Why two modules are not structurally equivalent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// A port missing from one module, or with another direction or width.
+    Port(String),
+    CellCount {
+        cell_type: String,
+        left: usize,
+        right: usize,
+    },
+    /// A cell of the left module without a counterpart in the right one.
+    Unmatched(String),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Port(port) => write!(f, "port {} differs", port),
+            Self::CellCount { cell_type, left, right } => write!(f, "{} cells of type {} against {}", left, cell_type, right),
+            Self::Unmatched(cell) => write!(f, "no counterpart for cell {}", cell),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EquivResult {
+    /// The name of the matching cell of the right module for every cell of the left one.
+    Equivalent(IndexMap<String, String>),
+    Different(Difference),
+}
+
+impl EquivResult {
+    pub fn is_equivalent(&self) -> bool {
+        matches!(self, Self::Equivalent(_))
+    }
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Type, parameters and port directions of a cell.
+fn signature(cell: &Cell) -> u64 {
+    let parameters: BTreeMap<&String, &serde_json::Value> = cell.parameters.iter().collect();
+    let directions: BTreeMap<&IdString, _> = cell.port_directions.iter().collect();
+    hash((&cell.module, serde_json::to_string(&parameters).unwrap(), format!("{:?}", directions)))
+}
+
+/// One module of the comparison, with colors of cells and signal bits that are refined until
+/// equally colored elements have equally colored neighbourhoods.
+struct Side<'a> {
+    cells: Vec<(&'a IdString, &'a Cell)>,
+    signatures: Vec<u64>,
+    /// Distinguishes cells already matched to each other.
+    salts: Vec<u64>,
+    cell_colors: Vec<u64>,
+    /// Cell, port and index of every connection of a signal bit.
+    uses: HashMap<Bit, Vec<(usize, &'a str, usize)>>,
+    bit_colors: HashMap<Bit, u64>,
+}
+
+impl<'a> Side<'a> {
+    fn new(module: &'a Module) -> Self {
+        let cells: Vec<(&IdString, &Cell)> = module.cells.iter().collect();
+        let mut uses: HashMap<Bit, Vec<(usize, &str, usize)>> = HashMap::new();
+        for (index, (_, cell)) in cells.iter().enumerate() {
+            for (port, bits) in cell.connections.iter() {
+                for (position, bit) in bits.iter().enumerate().filter(|(_, bit)| matches!(bit, Bit::Signal(_))) {
+                    uses.entry(*bit).or_default().push((index, port.as_str(), position));
+                }
+            }
+        }
+        // Port bits start out colored by name and index, anchoring the comparison.
+        let mut anchors: HashMap<Bit, Vec<(&str, usize)>> = uses.keys().map(|bit| (*bit, Vec::new())).collect();
+        for (name, port) in module.ports.iter() {
+            for (index, bit) in port.bits.iter().enumerate().filter(|(_, bit)| matches!(bit, Bit::Signal(_))) {
+                anchors.entry(*bit).or_default().push((name.as_str(), index));
+            }
+        }
+        let bit_colors = (anchors.into_iter())
+            .map(|(bit, mut anchors)| {
+                anchors.sort();
+                (bit, hash(anchors))
+            })
+            .collect();
+        Self {
+            signatures: cells.iter().map(|(_, cell)| signature(cell)).collect(),
+            salts: vec![0; cells.len()],
+            cell_colors: vec![0; cells.len()],
+            cells,
+            uses,
+            bit_colors,
+        }
+    }
+
+    fn color(&self, bit: &Bit) -> u64 {
+        match bit {
+            Bit::Signal(_) => self.bit_colors[bit],
+            constant => hash(constant),
+        }
+    }
+
+    fn refine_once(&mut self) {
+        for (index, (_, cell)) in self.cells.iter().enumerate() {
+            let connections: BTreeMap<&IdString, Vec<u64>> =
+                cell.connections.iter().map(|(port, bits)| (port, bits.iter().map(|bit| self.color(bit)).collect())).collect();
+            self.cell_colors[index] = hash((self.signatures[index], self.salts[index], connections));
+        }
+        for (bit, color) in self.bit_colors.iter_mut() {
+            let mut neighbours: Vec<(u64, &str, usize)> =
+                self.uses.get(bit).into_iter().flatten().map(|(cell, port, index)| (self.cell_colors[*cell], *port, *index)).collect();
+            neighbours.sort();
+            *color = hash((*color, neighbours));
+        }
+    }
+
+    fn classes(&self) -> usize {
+        let mut colors: Vec<u64> = self.cell_colors.iter().chain(self.bit_colors.values()).copied().collect();
+        colors.sort();
+        colors.dedup();
+        colors.len()
+    }
+
+    fn histogram(&self) -> HashMap<u64, usize> {
+        let mut histogram = HashMap::new();
+        for color in self.cell_colors.iter() {
+            *histogram.entry(*color).or_default() += 1;
+        }
+        histogram
+    }
+}
+
+/// Refines both sides together until neither partition splits further.
+fn refine(left: &mut Side, right: &mut Side) {
+    let mut classes = None;
+    loop {
+        left.refine_once();
+        right.refine_once();
+        let next = Some((left.classes(), right.classes()));
+        if next == classes {
+            return;
+        }
+        classes = next;
+    }
+}
+
+/// The first cell of `left` whose color is not as frequent in `right`.
+fn unmatched(left: &Side, right: &Side) -> Option<Difference> {
+    let (counts, others) = (left.histogram(), right.histogram());
+    let index = left.cell_colors.iter().position(|color| counts[color] != others.get(color).copied().unwrap_or(0))?;
+    Some(Difference::Unmatched(left.cells[index].0.to_string()))
+}
+
+impl Module {
+    /// Checks that `other` is this module with cells and nets renamed and bits renumbered:
+    /// the same ports, and a one-to-one matching of cells with equal types, parameters and
+    /// port directions, connected through matching bits. Attributes and net names are
+    /// ignored. Cells are told apart by color refinement on the cell and bit graph, anchored
+    /// at the ports, and symmetric cells are matched greedily, so highly regular modules that
+    /// are equivalent only under one particular matching may be reported as different.
+    pub fn structurally_equivalent(&self, other: &Module) -> EquivResult {
+        let ports = self.ports.keys().chain(other.ports.keys());
+        for name in ports {
+            match (self.ports.get(name), other.ports.get(name)) {
+                (Some(a), Some(b)) if a.direction == b.direction && a.bits.len() == b.bits.len() => {}
+                _ => return EquivResult::Different(Difference::Port(name.to_string())),
+            }
+        }
+        let mut types: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        self.cells.values().for_each(|cell| types.entry(&cell.module).or_default().0 += 1);
+        other.cells.values().for_each(|cell| types.entry(&cell.module).or_default().1 += 1);
+        if let Some((cell_type, (left, right))) = types.into_iter().find(|(_, (left, right))| left != right) {
+            return EquivResult::Different(Difference::CellCount { cell_type: cell_type.to_string(), left, right });
+        }
+
+        let (mut left, mut right) = (Side::new(self), Side::new(other));
+        refine(&mut left, &mut right);
+        let mut salt = 0;
+        loop {
+            if let Some(difference) = unmatched(&left, &right) {
+                return EquivResult::Different(difference);
+            }
+            let counts = left.histogram();
+            let Some(index) = left.cell_colors.iter().position(|color| counts[color] > 1) else {
+                break;
+            };
+            let color = left.cell_colors[index];
+            let other_index = right.cell_colors.iter().position(|other| *other == color).unwrap();
+            salt += 1;
+            (left.salts[index], right.salts[other_index]) = (salt, salt);
+            refine(&mut left, &mut right);
+        }
+
+        let cells: HashMap<u64, usize> = right.cell_colors.iter().enumerate().map(|(index, color)| (*color, index)).collect();
+        let mut bits: HashMap<u64, Vec<Bit>> = HashMap::new();
+        for (bit, color) in right.bit_colors.iter() {
+            bits.entry(*color).or_default().push(*bit);
+        }
+        bits.values_mut().for_each(|bits| bits.sort_by(|a, b| b.cmp(a)));
+        let mut map: HashMap<Bit, Bit> = HashMap::new();
+        let mut sorted: Vec<(&Bit, &u64)> = left.bit_colors.iter().collect();
+        sorted.sort();
+        for (bit, color) in sorted {
+            if let Some(other) = bits.get_mut(color).and_then(|bits| bits.pop()) {
+                map.insert(*bit, other);
+            }
+        }
+        let same = |a: &Vec<Bit>, b: &Vec<Bit>| {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(a, b)| match a {
+                    Bit::Signal(_) => map.get(a) == Some(b),
+                    constant => constant == b,
+                })
+        };
+        if let Some((name, _)) = self.ports.iter().find(|(name, port)| !same(&port.bits, &other.ports[*name].bits)) {
+            return EquivResult::Different(Difference::Port(name.to_string()));
+        }
+
+        let mut matching = IndexMap::new();
+        for (index, (name, cell)) in left.cells.iter().enumerate() {
+            let (other_name, other_cell) = right.cells[cells[&left.cell_colors[index]]];
+            let equal = cell.module == other_cell.module
+                && cell.parameters.len() == other_cell.parameters.len()
+                && cell.parameters.iter().all(|(key, value)| other_cell.parameters.get(key) == Some(value))
+                && cell.port_directions.len() == other_cell.port_directions.len()
+                && cell.port_directions.iter().all(|(port, direction)| other_cell.port_directions.get(port) == Some(direction))
+                && cell.connections.len() == other_cell.connections.len()
+                && cell.connections.iter().all(|(port, bits)| other_cell.connections.get(port).is_some_and(|other| same(bits, other)));
+            if !equal {
+                return EquivResult::Different(Difference::Unmatched(name.to_string()));
+            }
+            matching.insert(name.to_string(), other_name.to_string());
+        }
+        EquivResult::Equivalent(matching)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+    use crate::builder::CellBuilder;
+
+    /// `y = (a & b) | (a & b)` with two symmetric AND gates, built in either order.
+    fn design(reversed: bool) -> Module {
+        let mut builder = Module::builder();
+        let [a, b] = ["a", "b"].map(|name| builder.input(name, 1).unwrap());
+        let y = builder.output("y", 1).unwrap();
+        let mut names = ["p", "q"];
+        if reversed {
+            builder.wire("unused", 3).unwrap();
+            names.reverse();
+        }
+        let [p, q] = names.map(|name| builder.wire(name, 1).unwrap());
+        for (name, out) in [("and0", &p), ("and1", &q)] {
+            let name = if reversed { format!("{}_renamed", name) } else { name.to_string() };
+            builder.cell(&name, CellBuilder::new("$_AND_").input("A", a.clone()).input("B", b.clone()).output("Y", out.clone())).unwrap();
+        }
+        builder.cell("or", CellBuilder::new("$_OR_").input("A", p).input("B", q).output("Y", y)).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn test_structurally_equivalent() {
+        let (left, right) = (design(false), design(true));
+        let EquivResult::Equivalent(matching) = left.structurally_equivalent(&right) else { panic!() };
+        assert_eq!(matching.len(), 3);
+        assert_eq!(matching["or"], "or");
+
+        let mut changed = right.clone();
+        changed.cells["or"].module = "$_AND_".into();
+        let expected = Difference::CellCount { cell_type: "$_AND_".to_string(), left: 2, right: 3 };
+        assert_eq!(left.structurally_equivalent(&changed), EquivResult::Different(expected));
+
+        let mut changed = right.clone();
+        let a = changed.ports["a"].bits.clone();
+        changed.cells["or"].connections["A"] = a;
+        assert!(matches!(left.structurally_equivalent(&changed), EquivResult::Different(Difference::Unmatched(_))));
+
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let reparsed = Netlist::from_str(&netlist.to_string().unwrap()).unwrap();
+        for (name, module) in netlist.modules.iter() {
+            assert!(module.structurally_equivalent(&reparsed.modules[name]).is_equivalent());
+        }
+    }
+}
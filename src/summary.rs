@@ -0,0 +1,393 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use indexmap::IndexMap;
+
+use crate::{Bit, Cell, Direction, IdString, Module, Netlist};
+
+/// Number of rows kept in the fanout and path tables.
+const TOP: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocumentFormat {
+    #[default]
+    Markdown,
+    /// A single HTML page with inline styles and no external resources.
+    Html,
+}
+
+enum Block {
+    Heading(&'static str),
+    Paragraph(String),
+    Table(&'static [&'static str], Vec<Vec<String>>),
+    /// Items with their nesting depth, in depth-first order.
+    Tree(Vec<(usize, String)>),
+}
+
+fn markdown(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|").replace('*', "\\*").replace('_', "\\_").replace('`', "\\`")
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn write_markdown(blocks: &[Block], mut writer: impl io::Write) -> io::Result<()> {
+    writeln!(writer, "# Design summary")?;
+    for block in blocks {
+        writeln!(writer)?;
+        match block {
+            Block::Heading(text) => writeln!(writer, "## {}", text)?,
+            Block::Paragraph(text) => writeln!(writer, "{}", markdown(text))?,
+            Block::Table(headers, rows) => {
+                writeln!(writer, "| {} |", headers.join(" | "))?;
+                writeln!(writer, "|{}", " --- |".repeat(headers.len()))?;
+                for row in rows {
+                    let row: Vec<String> = row.iter().map(|cell| markdown(cell)).collect();
+                    writeln!(writer, "| {} |", row.join(" | "))?;
+                }
+            }
+            Block::Tree(items) => {
+                for (depth, text) in items {
+                    writeln!(writer, "{}- {}", "  ".repeat(*depth), markdown(text))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; } \
+    table { border-collapse: collapse; } \
+    th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; } \
+    th { background: #eee; }";
+
+fn write_html(blocks: &[Block], mut writer: impl io::Write) -> io::Result<()> {
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Design summary</title>")?;
+    writeln!(writer, "<style>{}</style>\n</head>\n<body>\n<h1>Design summary</h1>", STYLE)?;
+    for block in blocks {
+        match block {
+            Block::Heading(text) => writeln!(writer, "<h2>{}</h2>", html(text))?,
+            Block::Paragraph(text) => writeln!(writer, "<p>{}</p>", html(text))?,
+            Block::Table(headers, rows) => {
+                writeln!(writer, "<table>")?;
+                let headers: Vec<String> = headers.iter().map(|header| format!("<th>{}</th>", html(header))).collect();
+                writeln!(writer, "<tr>{}</tr>", headers.concat())?;
+                for row in rows {
+                    let row: Vec<String> = row.iter().map(|cell| format!("<td>{}</td>", html(cell))).collect();
+                    writeln!(writer, "<tr>{}</tr>", row.concat())?;
+                }
+                writeln!(writer, "</table>")?;
+            }
+            Block::Tree(items) => {
+                let mut open = 0;
+                for (depth, text) in items {
+                    while open <= *depth {
+                        writeln!(writer, "<ul>")?;
+                        open += 1;
+                    }
+                    while open > depth + 1 {
+                        writeln!(writer, "</ul>")?;
+                        open -= 1;
+                    }
+                    writeln!(writer, "<li>{}</li>", html(text))?;
+                }
+                (0..open).try_for_each(|_| writeln!(writer, "</ul>"))?;
+            }
+        }
+    }
+    writeln!(writer, "</body>\n</html>")
+}
+
+fn table(blocks: &mut Vec<Block>, headers: &'static [&'static str], rows: Vec<Vec<String>>) {
+    match rows.is_empty() {
+        true => blocks.push(Block::Paragraph("None.".to_string())),
+        false => blocks.push(Block::Table(headers, rows)),
+    }
+}
+
+/// Cells a combinational path can not pass through.
+fn is_sequential(cell: &Cell) -> bool {
+    cell.is_register() || cell.module.starts_with("$mem") || !cell.module.starts_with('$')
+}
+
+fn clock(cell: &Cell) -> Option<&Bit> {
+    ["C", "CLK"].iter().find_map(|port| cell.connections.get(*port)).and_then(|bits| bits.first())
+}
+
+struct Path {
+    depth: usize,
+    from: Bit,
+    to: Bit,
+}
+
+impl Module {
+    fn fanouts(&self) -> HashMap<Bit, usize> {
+        let mut fanouts = HashMap::new();
+        for cell in self.cells.values() {
+            for (port, bits) in cell.connections.iter() {
+                if cell.port_directions.get(port) != Some(&Direction::Output) {
+                    bits.iter().for_each(|bit| *fanouts.entry(*bit).or_default() += 1);
+                }
+            }
+        }
+        for port in self.ports.values().filter(|port| port.direction != Direction::Input) {
+            port.bits.iter().for_each(|bit| *fanouts.entry(*bit).or_default() += 1);
+        }
+        fanouts.retain(|bit, _| matches!(bit, Bit::Signal(_)));
+        fanouts
+    }
+
+    /// The deepest combinational path to every endpoint, counted in cells. Paths start at ports,
+    /// registers, memories and instances, and a combinational loop is cut where it closes.
+    fn longest_paths(&self) -> Vec<Path> {
+        let cells: Vec<&Cell> = self.cells.values().filter(|cell| !is_sequential(cell)).collect();
+        let ports = |cell: &Cell, direction: bool| -> Vec<Bit> {
+            let bits =
+                cell.connections.iter().filter(|(port, _)| (cell.port_directions.get(*port) == Some(&Direction::Output)) == direction);
+            bits.flat_map(|(_, bits)| bits.iter().copied()).filter(|bit| matches!(bit, Bit::Signal(_))).collect()
+        };
+        let mut drivers: HashMap<Bit, usize> = HashMap::new();
+        let mut read = HashSet::new();
+        for (index, cell) in cells.iter().enumerate() {
+            ports(cell, true).into_iter().for_each(|bit| _ = drivers.insert(bit, index));
+            read.extend(ports(cell, false));
+        }
+
+        // Depth of every cell and the bit its deepest path starts from.
+        let mut paths: Vec<Option<(usize, Bit)>> = vec![None; cells.len()];
+        let mut active = vec![false; cells.len()];
+        for root in 0..cells.len() {
+            let mut stack = vec![root];
+            while let Some(&index) = stack.last() {
+                if paths[index].is_some() {
+                    stack.pop();
+                    continue;
+                }
+                active[index] = true;
+                let inputs = ports(cells[index], false);
+                let pending: Vec<usize> = inputs
+                    .iter()
+                    .filter_map(|bit| drivers.get(bit).copied())
+                    .filter(|driver| paths[*driver].is_none() && !active[*driver])
+                    .collect();
+                if !pending.is_empty() {
+                    stack.extend(pending);
+                    continue;
+                }
+                let deepest = inputs
+                    .iter()
+                    .map(|bit| match drivers.get(bit).and_then(|driver| paths[*driver]) {
+                        Some((depth, from)) => (depth, from),
+                        None => (0, *bit),
+                    })
+                    .max_by_key(|(depth, _)| *depth);
+                let (depth, from) = deepest.unwrap_or((0, Bit::X));
+                paths[index] = Some((depth + 1, from));
+                active[index] = false;
+                stack.pop();
+            }
+        }
+
+        let mut endpoints = Vec::new();
+        for (index, cell) in cells.iter().enumerate() {
+            let (depth, from) = paths[index].unwrap();
+            for to in ports(cell, true).into_iter().filter(|bit| !read.contains(bit)) {
+                endpoints.push(Path { depth, from, to });
+            }
+        }
+        endpoints
+    }
+}
+
+impl Netlist {
+    fn hierarchy(&self, items: &mut Vec<(usize, String)>, module: &str, stack: &mut Vec<String>) {
+        let Some(definition) = self.modules.get(module) else {
+            return;
+        };
+        stack.push(module.to_string());
+        for (name, cell) in definition.cells.iter().filter(|(_, cell)| !cell.module.starts_with('$')) {
+            let recursive = stack.iter().any(|parent| *parent == cell.module);
+            let note = match self.modules.contains_key(&cell.module) {
+                true if recursive => ", recursive",
+                true => "",
+                false => ", not defined",
+            };
+            items.push((stack.len(), format!("{} ({}{})", name, cell.module, note)));
+            if !recursive {
+                self.hierarchy(items, &cell.module, stack);
+            }
+        }
+        stack.pop();
+    }
+
+    fn summary_blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block::Paragraph(format!("Created by {}.", self.creator))];
+
+        blocks.push(Block::Heading("Hierarchy"));
+        let instantiated: HashSet<&str> = self.iter_all_cells().map(|cell| cell.object.module.as_str()).collect();
+        let mut items = Vec::new();
+        for top in self.modules.keys().filter(|name| !instantiated.contains(name.as_str())) {
+            items.push((0, top.to_string()));
+            self.hierarchy(&mut items, top, &mut Vec::new());
+        }
+        match items.is_empty() {
+            true => blocks.push(Block::Paragraph("No top module, every module is instantiated.".to_string())),
+            false => blocks.push(Block::Tree(items)),
+        }
+
+        blocks.push(Block::Heading("Statistics"));
+        let mut rows = Vec::new();
+        let mut types: IndexMap<&str, usize> = IndexMap::new();
+        for (name, module) in self.modules.iter() {
+            let registers = module.cells.values().filter(|cell| cell.is_register()).count();
+            let instances = module.cells.values().filter(|cell| !cell.module.starts_with('$')).count();
+            let counts = [module.ports.len(), module.nets.len(), module.cells.len(), registers, module.memories.len(), instances];
+            rows.push(std::iter::once(name.to_string()).chain(counts.iter().map(usize::to_string)).collect());
+            module.cells.values().for_each(|cell| *types.entry(cell.module.as_str()).or_default() += 1);
+        }
+        table(&mut blocks, &["Module", "Ports", "Nets", "Cells", "Registers", "Memories", "Instances"], rows);
+        types.sort_by(|a, count_a, b, count_b| count_b.cmp(count_a).then(a.cmp(b)));
+        let rows = types.into_iter().map(|(module, count)| vec![module.to_string(), count.to_string()]).collect();
+        table(&mut blocks, &["Cell type", "Count"], rows);
+
+        blocks.push(Block::Heading("Clock domains"));
+        let mut rows = Vec::new();
+        for (name, module) in self.modules.iter() {
+            let mut domains: IndexMap<Bit, usize> = IndexMap::new();
+            for clock in module.cells.values().filter(|cell| cell.is_register()).filter_map(clock) {
+                *domains.entry(*clock).or_default() += 1;
+            }
+            for (clock, registers) in domains {
+                rows.push(vec![name.to_string(), module.bit_name(&clock), registers.to_string()]);
+            }
+        }
+        table(&mut blocks, &["Module", "Clock", "Registers"], rows);
+
+        blocks.push(Block::Heading("Memories"));
+        let mut rows = Vec::new();
+        for (name, module) in self.modules.iter() {
+            for (memory, info) in module.memories.iter() {
+                let cells = [info.width, info.size, info.width * info.size].map(|count| count.to_string());
+                rows.push([name.to_string(), memory.to_string()].into_iter().chain(cells).collect());
+            }
+        }
+        table(&mut blocks, &["Module", "Memory", "Width", "Depth", "Bits"], rows);
+
+        blocks.push(Block::Heading("Datapath"));
+        let mut rows = Vec::new();
+        for (name, summary) in self.recognize_datapath() {
+            for block in summary.blocks {
+                rows.push(vec![name.clone(), block.kind.to_string(), block.width.to_string(), block.operands.join(", ")]);
+            }
+        }
+        table(&mut blocks, &["Module", "Kind", "Width", "Operands"], rows);
+
+        blocks.push(Block::Heading("Top fanouts"));
+        let mut fanouts: Vec<(usize, &IdString, &Module, Bit)> = Vec::new();
+        for (name, module) in self.modules.iter() {
+            fanouts.extend(module.fanouts().into_iter().map(|(bit, fanout)| (fanout, name, module, bit)));
+        }
+        fanouts.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)).then(a.3.cmp(&b.3)));
+        let rows =
+            fanouts.into_iter().take(TOP).map(|(fanout, name, module, bit)| vec![name.to_string(), module.bit_name(&bit), fanout.to_string()]);
+        table(&mut blocks, &["Module", "Net", "Fanout"], rows.collect());
+
+        blocks.push(Block::Heading("Longest paths"));
+        let mut paths: Vec<(&IdString, &Module, Path)> = Vec::new();
+        for (name, module) in self.modules.iter() {
+            paths.extend(module.longest_paths().into_iter().map(|path| (name, module, path)));
+        }
+        paths.sort_by(|a, b| b.2.depth.cmp(&a.2.depth).then(a.0.cmp(b.0)).then(a.2.to.cmp(&b.2.to)));
+        let rows = paths
+            .into_iter()
+            .take(TOP)
+            .map(|(name, module, path)| vec![name.to_string(), path.depth.to_string(), module.bit_name(&path.from), module.bit_name(&path.to)]);
+        table(&mut blocks, &["Module", "Depth", "From", "To"], rows.collect());
+        blocks
+    }
+
+    /// Writes a self-contained summary of the design: the hierarchy, statistics, clock domains,
+    /// memories, datapath blocks, highest fanout nets and deepest combinational paths.
+    pub fn write_summary(&self, format: DocumentFormat, writer: impl io::Write) -> io::Result<()> {
+        let blocks = self.summary_blocks();
+        match format {
+            DocumentFormat::Markdown => write_markdown(&blocks, writer),
+            DocumentFormat::Html => write_html(&blocks, writer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn summary(netlist: &Netlist, format: DocumentFormat) -> String {
+        let mut document = Vec::new();
+        netlist.write_summary(format, &mut document).unwrap();
+        String::from_utf8(document).unwrap()
+    }
+
+    #[test]
+    fn test_summary_markdown() {
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "clk": { "direction": "input", "bits": [2] },
+                        "d": { "direction": "input", "bits": [3] },
+                        "q": { "direction": "output", "bits": [5] }
+                    },
+                    "cells": {
+                        "u0": { "type": "inv", "connections": { "a": [3], "y": [4] } },
+                        "u1": { "type": "blackbox", "connections": {} },
+                        "ff": {
+                            "type": "$_DFF_P_",
+                            "port_directions": { "C": "input", "D": "input", "Q": "output" },
+                            "connections": { "C": [2], "D": [4], "Q": [5] }
+                        }
+                    },
+                    "memories": { "ram": { "width": 8, "size": 16 } },
+                    "netnames": { "clk": { "bits": [2] }, "q": { "bits": [5] } }
+                },
+                "inv": {
+                    "ports": {
+                        "a": { "direction": "input", "bits": [2] },
+                        "y": { "direction": "output", "bits": [3] }
+                    },
+                    "cells": {
+                        "not": {
+                            "type": "$_NOT_",
+                            "port_directions": { "A": "input", "Y": "output" },
+                            "connections": { "A": [2], "Y": [3] }
+                        }
+                    },
+                    "netnames": { "a": { "bits": [2] }, "y": { "bits": [3] } }
+                }
+            }
+        }))
+        .unwrap();
+        let document = summary(&netlist, DocumentFormat::Markdown);
+        assert!(document.starts_with("# Design summary\n\nCreated by test.\n"));
+        assert!(document.contains("\n- top\n  - u0 (inv)\n  - u1 (blackbox, not defined)\n"));
+        assert!(document.contains("| top | 3 | 2 | 3 | 1 | 1 | 2 |"));
+        assert!(document.contains("| top | clk | 1 |"));
+        assert!(document.contains("| top | ram | 8 | 16 | 128 |"));
+        assert!(document.contains("| inv | 1 | a | y |"));
+        assert!(document.contains("## Datapath\n\nNone.\n"));
+    }
+
+    #[test]
+    fn test_summary_html() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let document = summary(&netlist, DocumentFormat::Html);
+        assert!(document.starts_with("<!DOCTYPE html>"));
+        assert!(document.ends_with("</body>\n</html>\n"));
+        assert_eq!(document.matches("<table>").count(), document.matches("</table>").count());
+        assert_eq!(document.matches("<ul>").count(), 1);
+        assert!(document.contains("<tr><td>adder</td><td>ripple_adder</td>"));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use crate::physical::Rect;
+use crate::{Bit, Direction, Module};
+
+/// Drawing parameters of [`Module::write_svg`].
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    /// Pixels per placement unit.
+    pub scale: f64,
+    /// Edge length of the square drawn for every cell, in placement units.
+    pub cell_size: f64,
+    /// Number of congestion bins along each side of the die, 0 to leave out the heatmap.
+    pub bins: usize,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self { scale: 20.0, cell_size: 1.0, bins: 16 }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A placed cell or pin, with its centre.
+struct Endpoint<'a> {
+    name: &'a str,
+    pin: bool,
+    x: f64,
+    y: f64,
+}
+
+impl Module {
+    fn endpoints(&self, cell_size: f64) -> Vec<Endpoint<'_>> {
+        let cells = self.cells.iter().filter_map(|(name, cell)| {
+            let placement = cell.placement()?;
+            Some(Endpoint { name, pin: false, x: placement.x + cell_size / 2.0, y: placement.y + cell_size / 2.0 })
+        });
+        let pins = self.ports.iter().filter_map(|(name, port)| port.pin().map(|pin| Endpoint { name, pin: true, x: pin.x, y: pin.y }));
+        cells.chain(pins).collect()
+    }
+
+    /// Flight-lines between endpoint indices, from the driver of every bit to its loads.
+    fn flight_lines(&self, endpoints: &[Endpoint]) -> Vec<(usize, usize)> {
+        let index: HashMap<(bool, &str), usize> =
+            endpoints.iter().enumerate().map(|(index, endpoint)| ((endpoint.pin, endpoint.name), index)).collect();
+        let mut bits: HashMap<Bit, (Option<usize>, Vec<usize>)> = HashMap::new();
+        let mut connect = |bit: &Bit, endpoint: usize, driver: bool| {
+            if !matches!(bit, Bit::Signal(_)) {
+                return;
+            }
+            let (drivers, loads) = bits.entry(*bit).or_default();
+            match driver && drivers.is_none() {
+                true => *drivers = Some(endpoint),
+                false => loads.push(endpoint),
+            }
+        };
+        for (name, cell) in self.cells.iter() {
+            let Some(&endpoint) = index.get(&(false, name.as_str())) else { continue };
+            for (port, bits) in cell.connections.iter() {
+                let driver = cell.port_directions.get(port) == Some(&Direction::Output);
+                bits.iter().for_each(|bit| connect(bit, endpoint, driver));
+            }
+        }
+        for (name, port) in self.ports.iter() {
+            let Some(&endpoint) = index.get(&(true, name.as_str())) else { continue };
+            port.bits.iter().for_each(|bit| connect(bit, endpoint, port.direction == Direction::Input));
+        }
+        let mut lines = HashSet::new();
+        for (driver, loads) in bits.into_values() {
+            let Some((&source, rest)) = driver.as_ref().map(|driver| (driver, &loads[..])).or_else(|| loads.split_first()) else {
+                continue;
+            };
+            lines.extend(rest.iter().filter(|load| **load != source).map(|load| (source.min(*load), source.max(*load))));
+        }
+        let mut lines: Vec<_> = lines.into_iter().collect();
+        lines.sort();
+        lines
+    }
+
+    /// Draws the placed cells, pins and flight-lines of the module, over a heatmap counting the
+    /// flight-line bounding boxes covering every bin of the die. Without a die annotation the
+    /// bounding box of the placement is used. Unplaced cells are left out.
+    pub fn write_svg(&self, options: &SvgOptions, mut writer: impl io::Write) -> io::Result<()> {
+        let endpoints = self.endpoints(options.cell_size);
+        let lines = self.flight_lines(&endpoints);
+        let die = self.die().unwrap_or_else(|| {
+            let bounds = endpoints.iter().fold(None, |bounds: Option<Rect>, endpoint| {
+                let (x, y, half) = (endpoint.x, endpoint.y, options.cell_size / 2.0);
+                Some(match bounds {
+                    None => Rect { x0: x - half, y0: y - half, x1: x + half, y1: y + half },
+                    Some(r) => Rect { x0: r.x0.min(x - half), y0: r.y0.min(y - half), x1: r.x1.max(x + half), y1: r.y1.max(y + half) },
+                })
+            });
+            bounds.unwrap_or(Rect { x0: 0.0, y0: 0.0, x1: 1.0, y1: 1.0 })
+        });
+        let scale = options.scale;
+        let (width, height) = ((die.x1 - die.x0) * scale, (die.y1 - die.y0) * scale);
+        let px = |x: f64| (x - die.x0) * scale;
+        let py = |y: f64| (die.y1 - y) * scale;
+
+        writeln!(writer, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.1}" height="{:.1}">"#, width, height)?;
+        writeln!(writer, r#"<rect x="0" y="0" width="{:.1}" height="{:.1}" fill="white" stroke="black"/>"#, width, height)?;
+
+        if options.bins > 0 && !lines.is_empty() {
+            let bins = options.bins;
+            let (bin_width, bin_height) = ((die.x1 - die.x0) / bins as f64, (die.y1 - die.y0) / bins as f64);
+            let bin = |value: f64, origin: f64, size: f64| (((value - origin) / size).floor().max(0.0) as usize).min(bins - 1);
+            let mut demand = vec![0usize; bins * bins];
+            for &(from, to) in lines.iter() {
+                let (a, b) = (&endpoints[from], &endpoints[to]);
+                let (x0, x1) = (bin(a.x.min(b.x), die.x0, bin_width), bin(a.x.max(b.x), die.x0, bin_width));
+                let (y0, y1) = (bin(a.y.min(b.y), die.y0, bin_height), bin(a.y.max(b.y), die.y0, bin_height));
+                for y in y0..=y1 {
+                    demand[y * bins + x0..=y * bins + x1].iter_mut().for_each(|count| *count += 1);
+                }
+            }
+            let max = *demand.iter().max().unwrap();
+            for (index, count) in demand.iter().enumerate().filter(|(_, count)| **count > 0) {
+                let (x, y) = (die.x0 + (index % bins) as f64 * bin_width, die.y0 + (index / bins + 1) as f64 * bin_height);
+                writeln!(
+                    writer,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="red" fill-opacity="{:.2}"/>"#,
+                    px(x),
+                    py(y),
+                    bin_width * scale,
+                    bin_height * scale,
+                    0.6 * *count as f64 / max as f64
+                )?;
+            }
+        }
+
+        for &(from, to) in lines.iter() {
+            let (a, b) = (&endpoints[from], &endpoints[to]);
+            writeln!(
+                writer,
+                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="steelblue" stroke-width="0.5"/>"#,
+                px(a.x),
+                py(a.y),
+                px(b.x),
+                py(b.y)
+            )?;
+        }
+        let size = options.cell_size * scale;
+        for endpoint in endpoints.iter() {
+            match endpoint.pin {
+                false => writeln!(
+                    writer,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="lightgray" stroke="black"><title>{}</title></rect>"#,
+                    px(endpoint.x) - size / 2.0,
+                    py(endpoint.y) - size / 2.0,
+                    size,
+                    size,
+                    escape(endpoint.name)
+                )?,
+                true => writeln!(
+                    writer,
+                    r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="orange"><title>{}</title></circle>"#,
+                    px(endpoint.x),
+                    py(endpoint.y),
+                    size / 4.0,
+                    escape(endpoint.name)
+                )?,
+            }
+        }
+        writeln!(writer, "</svg>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+    use crate::physical::{Pin, Placement};
+
+    #[test]
+    fn test_write_svg() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let module = &mut netlist.modules["adder"];
+        module.set_die(Some(Rect { x0: 0.0, y0: 0.0, x1: 20.0, y1: 20.0 }));
+        for (index, cell) in module.cells.values_mut().enumerate() {
+            cell.set_placement(Some(Placement { x: (index % 19) as f64, y: (index / 19) as f64, fixed: false }));
+        }
+        module.ports["a"].set_pin(Some(Pin { x: 0.0, y: 10.0, layer: None }));
+        let cells = module.cells.len();
+
+        let mut svg = Vec::new();
+        module.write_svg(&SvgOptions::default(), &mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="400.0" height="400.0">"#));
+        assert_eq!(svg.matches("fill=\"lightgray\"").count(), cells);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains("<line"));
+        assert!(svg.contains("fill-opacity=\"0.60\""));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::batch::REGISTERS;
+use crate::gates::{cycle_bits, function};
+use crate::{Bit, Module};
+
+/// Slot always holding 0 in every lane.
+pub const ZERO: u32 = 0;
+/// Slot always holding 1 in every lane.
+pub const ONE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Buf,
+    Not,
+    And,
+    Or,
+    Xor,
+    Nand,
+    Nor,
+    Xnor,
+    /// `a & !b`
+    AndNot,
+    /// `a | !b`
+    OrNot,
+    /// `c ? b : a`
+    Mux,
+}
+
+impl Op {
+    pub fn arity(&self) -> usize {
+        match self {
+            Self::Buf | Self::Not => 1,
+            Self::Mux => 3,
+            _ => 2,
+        }
+    }
+
+    /// Applies the operation bitwise, so one call computes 64 lanes.
+    pub fn eval(&self, a: u64, b: u64, c: u64) -> u64 {
+        match self {
+            Self::Buf => a,
+            Self::Not => !a,
+            Self::And => a & b,
+            Self::Or => a | b,
+            Self::Xor => a ^ b,
+            Self::Nand => !(a & b),
+            Self::Nor => !(a | b),
+            Self::Xnor => !(a ^ b),
+            Self::AndNot => a & !b,
+            Self::OrNot => a | !b,
+            Self::Mux => (a & !c) | (b & c),
+        }
+    }
+}
+
+/// One operation reading up to three slots and writing one. Unused sources are [`ZERO`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub op: Op,
+    pub sources: [u32; 3],
+    pub destination: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TapeError {
+    Unsupported {
+        cell: String,
+        module: String,
+    },
+    /// Bits on a combinational loop, named by their nets.
+    CombinationalLoop(Vec<String>),
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { cell, module } => write!(f, "cannot compile cell {} of type {}", cell, module),
+            Self::CombinationalLoop(bits) => write!(f, "combinational loop through {}", bits.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for TapeError {}
+
+/// A gate-level module lowered to a flat list of word operations on numbered slots, ordered
+/// by logic level: the instructions of a level only read slots written by earlier levels, so
+/// each level can run as one SIMD or GPU dispatch. Every slot holds 64 independent lanes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tape {
+    pub slots: usize,
+    pub instructions: Vec<Instruction>,
+    /// Index of the first instruction of every level.
+    pub levels: Vec<usize>,
+    /// Slots of the bits of every port, least significant first.
+    pub ports: IndexMap<String, Vec<u32>>,
+    /// Data input and output slot of every register.
+    pub registers: Vec<(u32, u32)>,
+    /// Register output slots whose `init` attribute is 1, set in every lane by [`Tape::state`].
+    #[serde(default)]
+    pub initial: Vec<u32>,
+}
+
+struct Compiler {
+    slots: HashMap<Bit, u32>,
+    next: u32,
+    instructions: Vec<Instruction>,
+}
+
+impl Compiler {
+    fn slot(&mut self, bit: Bit) -> u32 {
+        match bit {
+            Bit::_1 => ONE,
+            Bit::Signal(_) => {
+                let next = &mut self.next;
+                *self.slots.entry(bit).or_insert_with(|| {
+                    *next += 1;
+                    *next - 1
+                })
+            }
+            _ => ZERO,
+        }
+    }
+
+    fn temporary(&mut self) -> u32 {
+        self.next += 1;
+        self.next - 1
+    }
+
+    fn emit(&mut self, op: Op, sources: &[u32], destination: u32) -> u32 {
+        let mut padded = [ZERO; 3];
+        padded[..sources.len()].copy_from_slice(sources);
+        self.instructions.push(Instruction { op, sources: padded, destination });
+        destination
+    }
+
+    fn emit_temporary(&mut self, op: Op, sources: &[u32]) -> u32 {
+        let destination = self.temporary();
+        self.emit(op, sources, destination)
+    }
+
+    /// Lowers a truth table to a tree of multiplexers on its inputs, returning the slot of
+    /// the result.
+    fn table(&mut self, inputs: &[u32], table: &[bool]) -> u32 {
+        let Some((select, rest)) = inputs.split_last() else {
+            return if table[0] { ONE } else { ZERO };
+        };
+        let (low, high) = table.split_at(table.len() / 2);
+        let low = self.table(rest, low);
+        let high = self.table(rest, high);
+        match (low, high) {
+            _ if low == high => low,
+            (ZERO, ONE) => *select,
+            (ONE, ZERO) => self.emit_temporary(Op::Not, &[*select]),
+            _ => self.emit_temporary(Op::Mux, &[low, high, *select]),
+        }
+    }
+
+    fn gate(&mut self, module: &str, inputs: &[u32], output: u32) -> Option<()> {
+        let direct = match module {
+            "$_BUF_" => Some(Op::Buf),
+            "$_NOT_" => Some(Op::Not),
+            "$_AND_" => Some(Op::And),
+            "$_OR_" => Some(Op::Or),
+            "$_XOR_" => Some(Op::Xor),
+            "$_NAND_" => Some(Op::Nand),
+            "$_NOR_" => Some(Op::Nor),
+            "$_XNOR_" => Some(Op::Xnor),
+            "$_ANDNOT_" => Some(Op::AndNot),
+            "$_ORNOT_" => Some(Op::OrNot),
+            "$_MUX_" => Some(Op::Mux),
+            _ => None,
+        };
+        if let Some(op) = direct {
+            self.emit(op, inputs, output);
+            return Some(());
+        }
+        let (inner, outer) = match module {
+            "$_NMUX_" => {
+                let mux = self.emit_temporary(Op::Mux, inputs);
+                self.emit(Op::Not, &[mux], output);
+                return Some(());
+            }
+            "$_AOI3_" | "$_AOI4_" => (Op::And, Op::Nor),
+            "$_OAI3_" | "$_OAI4_" => (Op::Or, Op::Nand),
+            _ => return None,
+        };
+        let first = self.emit_temporary(inner, &inputs[..2]);
+        let second = match inputs.len() {
+            4 => self.emit_temporary(inner, &inputs[2..]),
+            _ => inputs[2],
+        };
+        self.emit(outer, &[first, second], output);
+        Some(())
+    }
+}
+
+impl Module {
+    /// Lowers the gates and LUTs of the module to a [`Tape`]. Registers become state slots
+    /// copied by [`Tape::step`], all on one implicit clock.
+    pub fn compile_tape(&self) -> Result<Tape, TapeError> {
+        let mut compiler = Compiler { slots: HashMap::new(), next: 2, instructions: Vec::new() };
+        let mut registers = Vec::new();
+        let mut initial = Vec::new();
+        let values = self.initial_values();
+        let order = self.topo_sort_cells().map_err(|cycles| TapeError::CombinationalLoop(cycle_bits(self, &cycles)))?;
+        for (name, cell) in order.into_iter().map(|name| (name, &self.cells[name])) {
+            if let Some((inputs, output, table)) = function(cell) {
+                let inputs: Vec<u32> = inputs.into_iter().map(|bit| compiler.slot(bit)).collect();
+                let output = compiler.slot(output);
+                if compiler.gate(&cell.module, &inputs, output).is_none() {
+                    let result = compiler.table(&inputs, &table);
+                    compiler.emit(Op::Buf, &[result], output);
+                }
+            } else if let Some((_, d, q)) = REGISTERS.iter().find(|(module, _, _)| *module == cell.module)
+                && let (Some(d), Some(q)) = (cell.connections.get(*d), cell.connections.get(*q))
+            {
+                for (d, q) in d.iter().zip(q) {
+                    registers.push((compiler.slot(*d), compiler.slot(*q)));
+                    if values.get(q) == Some(&true) {
+                        initial.push(compiler.slot(*q));
+                    }
+                }
+            } else {
+                return Err(TapeError::Unsupported { cell: name.to_string(), module: cell.module.to_string() });
+            }
+        }
+        let ports =
+            self.ports.iter().map(|(name, port)| (name.to_string(), port.bits.iter().map(|bit| compiler.slot(*bit)).collect())).collect();
+        let Compiler { next, instructions, .. } = compiler;
+
+        // Level of every slot: 0 for inputs, one more than the deepest source for the rest. The
+        // cells were compiled in dataflow order, so sources come before the instructions they feed.
+        let mut driver: HashMap<u32, usize> = HashMap::new();
+        let mut level = Vec::with_capacity(instructions.len());
+        for (index, instruction) in instructions.iter().enumerate() {
+            let sources = &instruction.sources[..instruction.op.arity()];
+            let deepest = sources.iter().filter_map(|source| driver.get(source)).map(|source| level[*source] + 1).max();
+            level.push(deepest.unwrap_or(0));
+            driver.insert(instruction.destination, index);
+        }
+        let mut order: Vec<usize> = (0..instructions.len()).collect();
+        order.sort_by_key(|index| level[*index]);
+        let mut levels = Vec::new();
+        for (position, index) in order.iter().enumerate() {
+            if position == 0 || level[order[position - 1]] != level[*index] {
+                levels.push(position);
+            }
+        }
+        let instructions = order.into_iter().map(|index| instructions[index]).collect();
+        Ok(Tape { slots: next as usize, instructions, levels, ports, registers, initial })
+    }
+}
+
+impl Tape {
+    /// Slots with every lane 0, except the [`ONE`] slot and registers initialized to 1.
+    pub fn state(&self) -> Vec<u64> {
+        let mut state = vec![0; self.slots];
+        state[ONE as usize] = u64::MAX;
+        for slot in self.initial.iter() {
+            state[*slot as usize] = u64::MAX;
+        }
+        state
+    }
+
+    /// Runs every instruction once, settling the combinational logic.
+    pub fn eval(&self, state: &mut [u64]) {
+        for Instruction { op, sources: [a, b, c], destination } in self.instructions.iter() {
+            state[*destination as usize] = op.eval(state[*a as usize], state[*b as usize], state[*c as usize]);
+        }
+    }
+
+    /// Settles the logic, clocks every register once and settles the logic again.
+    pub fn step(&self, state: &mut [u64]) {
+        self.eval(state);
+        let next: Vec<u64> = self.registers.iter().map(|(d, _)| state[*d as usize]).collect();
+        for ((_, q), value) in self.registers.iter().zip(next) {
+            state[*q as usize] = value;
+        }
+        self.eval(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::BatchSimulator;
+    use crate::rng::SplitMix64;
+    use crate::{Cell, Direction, Net, Netlist, Port};
+
+    #[test]
+    fn test_tape_matches_batch() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/mult.json").unwrap()).unwrap();
+        let module = &netlist.modules["mult"];
+        let tape = module.compile_tape().unwrap();
+        assert!(tape.levels.len() > 1);
+        let mut simulator = BatchSimulator::new(module).unwrap();
+        let mut state = tape.state();
+        let mut rng = SplitMix64::new(1);
+        for port in ["a", "b"] {
+            let lanes: Vec<u64> = (0..8).map(|_| rng.next_u64()).collect();
+            simulator.set(port, &lanes).unwrap();
+            for (slot, value) in tape.ports[port].iter().zip(lanes) {
+                state[*slot as usize] = value;
+            }
+        }
+        simulator.eval();
+        tape.eval(&mut state);
+        let product: Vec<u64> = tape.ports["c"].iter().map(|slot| state[*slot as usize]).collect();
+        assert_eq!(product, simulator.get("c").unwrap());
+    }
+
+    #[test]
+    fn test_tape_lut() {
+        let mut module = Module::new();
+        module.ports.insert("a".into(), Port::new(Direction::Input, vec![Bit::Signal(2), Bit::Signal(3), Bit::Signal(4)]));
+        module.ports.insert("y".into(), Port::new(Direction::Output, vec![Bit::Signal(5)]));
+        // Majority of three.
+        module.cells.insert(
+            "lut".into(),
+            Cell::new("$lut").with_parameter("LUT", "11101000").with_parameter("WIDTH", 3).with_connection(
+                "A",
+                Direction::Input,
+                vec![Bit::Signal(2), Bit::Signal(3), Bit::Signal(4)],
+            ),
+        );
+        module.cells["lut"].connections.insert("Y".into(), vec![Bit::Signal(5)]);
+        let tape = module.compile_tape().unwrap();
+        let mut state = tape.state();
+        for (input, slot) in tape.ports["a"].iter().enumerate() {
+            state[*slot as usize] = (0..8).filter(|m| m >> input & 1 == 1).fold(0, |lanes, m| lanes | 1 << m);
+        }
+        tape.eval(&mut state);
+        assert_eq!(state[tape.ports["y"][0] as usize], 0b11101000);
+    }
+
+    #[test]
+    fn test_tape_initial_values() {
+        let mut module = Module::new();
+        module.cells.insert(
+            "inv".into(),
+            Cell::new("$_NOT_").with_connection("A", Direction::Input, vec![Bit::Signal(2)]).with_connection(
+                "Y",
+                Direction::Output,
+                vec![Bit::Signal(3)],
+            ),
+        );
+        module.cells.insert(
+            "ff".into(),
+            Cell::new("$_DFF_P_").with_connection("D", Direction::Input, vec![Bit::Signal(3)]).with_connection(
+                "Q",
+                Direction::Output,
+                vec![Bit::Signal(2)],
+            ),
+        );
+        module.ports.insert("q".into(), Port::new(Direction::Output, vec![Bit::Signal(2)]));
+        let mut net = Net::new(vec![Bit::Signal(2)]);
+        net.attributes.insert("init".to_string(), "1".into());
+        module.nets.insert("q".into(), net);
+
+        let tape = module.compile_tape().unwrap();
+        let mut simulator = BatchSimulator::new(&module).unwrap();
+        let mut state = tape.state();
+        tape.eval(&mut state);
+        assert_eq!(state[tape.ports["q"][0] as usize], u64::MAX);
+        for _ in 0..3 {
+            simulator.step();
+            tape.step(&mut state);
+            assert_eq!([state[tape.ports["q"][0] as usize]], simulator.get("q").unwrap()[..]);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::progress::pass_span;
+use crate::scoap::Testability;
+use crate::{Bit, Cell, Direction, Module, Net, Port};
+
+/// Input port enabling the control points.
+pub const ENABLE_PORT: &str = "tp_enable";
+/// Input port with the value forced by every control point.
+pub const CONTROL_PORT: &str = "tp_control";
+/// Output port carrying the XOR of all observe points.
+pub const OBSERVE_PORT: &str = "tp_observe";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestPointError {
+    PortExists(String),
+    /// A cell port on a controlled bit whose direction is unknown, so it may be a load or a
+    /// driver.
+    UnknownDirection {
+        cell: String,
+        port: String,
+    },
+}
+
+impl fmt::Display for TestPointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PortExists(port) => write!(f, "port {:?} already exists", port),
+            Self::UnknownDirection { cell, port } => write!(f, "unknown direction of port {:?} of cell {:?}", port, cell),
+        }
+    }
+}
+
+impl std::error::Error for TestPointError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestPointReport {
+    /// Bits given a control point, by name.
+    pub control: Vec<String>,
+    /// Bits given an observe point, by name.
+    pub observe: Vec<String>,
+    /// Sum of [`Testability::difficulty`] over the bits of the original module.
+    pub difficulty_before: u64,
+    pub difficulty_after: u64,
+}
+
+fn total(bits: &[Bit], scoap: &std::collections::HashMap<Bit, Testability>) -> u64 {
+    bits.iter().filter_map(|bit| scoap.get(bit)).map(|testability| testability.difficulty() as u64).sum()
+}
+
+impl Module {
+    fn add_test_cell(&mut self, module: &str, connections: &[(&str, Direction, Bit)]) {
+        let cell = connections
+            .iter()
+            .fold(Cell::new(module), |cell, (port, direction, bit)| cell.with_connection(port, direction.clone(), vec![*bit]));
+        let mut name = format!("$tp${}", self.cells.len());
+        while self.cells.contains_key(&name) {
+            name.push('_');
+        }
+        self.cells.insert(name.into(), cell);
+    }
+
+    /// Inserts control points on the `control` bits hardest to set and observe points on the
+    /// `observe` bits hardest to observe, by SCOAP. A control point is a multiplexer replacing
+    /// the bit by its [`CONTROL_PORT`] bit while [`ENABLE_PORT`] is high. Observe points are
+    /// XORed together into [`OBSERVE_PORT`], so they cost a single scan cell.
+    pub fn insert_test_points(&mut self, control: usize, observe: usize) -> Result<TestPointReport, TestPointError> {
+        pass_span!("insert_test_points", cells = self.cells.len(), nets = self.nets.len(), control, observe);
+        for port in [ENABLE_PORT, CONTROL_PORT, OBSERVE_PORT] {
+            if self.ports.contains_key(port) || self.nets.contains_key(port) {
+                return Err(TestPointError::PortExists(port.to_string()));
+            }
+        }
+        let scoap = self.scoap();
+        let inputs: HashSet<Bit> =
+            self.ports.values().filter(|port| port.direction != Direction::Output).flat_map(|port| port.bits.iter().copied()).collect();
+        let mut bits: Vec<Bit> = scoap.keys().copied().filter(|bit| !inputs.contains(bit)).collect();
+        bits.sort();
+        let original: Vec<Bit> = scoap.keys().copied().collect();
+        let mut report = TestPointReport { difficulty_before: total(&original, &scoap), ..TestPointReport::default() };
+
+        let mut controlled = bits.clone();
+        controlled.sort_by_key(|bit| std::cmp::Reverse(scoap[bit].cc0.max(scoap[bit].cc1)));
+        controlled.truncate(control);
+        let mut observed = bits;
+        observed.retain(|bit| scoap[bit].co > 0);
+        observed.sort_by_key(|bit| std::cmp::Reverse(scoap[bit].co));
+        observed.truncate(observe);
+        if let Some((cell, port)) = self.undirected_port(&controlled.iter().copied().collect()) {
+            return Err(TestPointError::UnknownDirection { cell, port });
+        }
+
+        if !controlled.is_empty() {
+            let fresh = self.fresh_bits(1 + 2 * controlled.len());
+            let (enable, rest) = fresh.split_first().unwrap();
+            let (values, outputs) = rest.split_at(controlled.len());
+            self.ports.insert(ENABLE_PORT.into(), Port::new(Direction::Input, vec![*enable]));
+            self.nets.insert(ENABLE_PORT.into(), Net::new(vec![*enable]));
+            self.ports.insert(CONTROL_PORT.into(), Port::new(Direction::Input, values.to_vec()));
+            self.nets.insert(CONTROL_PORT.into(), Net::new(values.to_vec()));
+            for ((bit, value), output) in controlled.iter().zip(values).zip(outputs) {
+                report.control.push(self.bit_name(bit));
+                self.move_loads(*bit, *output);
+                self.add_test_cell(
+                    "$_MUX_",
+                    &[
+                        ("A", Direction::Input, *bit),
+                        ("B", Direction::Input, *value),
+                        ("S", Direction::Input, *enable),
+                        ("Y", Direction::Output, *output),
+                    ],
+                );
+            }
+        }
+
+        if !observed.is_empty() {
+            report.observe = observed.iter().map(|bit| self.bit_name(bit)).collect();
+            let mut tap = observed[0];
+            for bit in observed[1..].iter() {
+                let output = self.fresh_bits(1)[0];
+                self.add_test_cell(
+                    "$_XOR_",
+                    &[("A", Direction::Input, tap), ("B", Direction::Input, *bit), ("Y", Direction::Output, output)],
+                );
+                tap = output;
+            }
+            self.ports.insert(OBSERVE_PORT.into(), Port::new(Direction::Output, vec![tap]));
+            self.nets.insert(OBSERVE_PORT.into(), Net::new(vec![tap]));
+        }
+
+        report.difficulty_after = total(&original, &self.scoap());
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    #[test]
+    fn test_insert_test_points() {
+        let mut netlist = Netlist::from_reader(std::fs::File::open("testdata/mult.json").unwrap()).unwrap();
+        let module = &mut netlist.modules["mult"];
+        let cells = module.cells.len();
+        let report = module.insert_test_points(4, 6).unwrap();
+        assert_eq!(report.control.len(), 4);
+        assert_eq!(report.observe.len(), 6);
+        assert!(report.difficulty_after < report.difficulty_before);
+        assert_eq!(module.cells.len(), cells + 4 + 5);
+        assert_eq!(module.ports[CONTROL_PORT].bits.len(), 4);
+        assert_eq!(module.ports[OBSERVE_PORT].bits.len(), 1);
+        assert_eq!(module.insert_test_points(1, 1), Err(TestPointError::PortExists(ENABLE_PORT.to_string())));
+    }
+}
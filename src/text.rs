@@ -0,0 +1,270 @@
+use std::fmt;
+use std::io;
+
+use serde_json::{Map, Value};
+
+use crate::Netlist;
+
+/// Fields left out of a line when they hold their default value.
+const DEFAULTS: &[(&str, u64)] = &[("hide_name", 0), ("offset", 0), ("upto", 0), ("signed", 0), ("start_offset", 0)];
+
+/// Kinds of module objects, in the order they are written.
+const OBJECTS: &[(&str, &str)] = &[("port", "ports"), ("cell", "cells"), ("memory", "memories"), ("net", "netnames")];
+
+#[derive(Debug)]
+pub enum TextError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+    Netlist(crate::Error),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            Self::Netlist(error) => write!(f, "invalid netlist: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+impl From<io::Error> for TextError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Names are written bare unless they contain characters the line syntax relies on.
+fn name(text: &str, key: bool) -> String {
+    let special = |c: char| c.is_whitespace() || c == '"' || c == '=' || (key && c == '.');
+    match text.is_empty() || text.contains(special) {
+        true => Value::String(text.to_string()).to_string(),
+        false => text.to_string(),
+    }
+}
+
+/// Appends the fields of `object` as `key=value` tokens, one token per entry of nested maps so
+/// a changed attribute or connection only changes that token.
+fn fields(line: &mut String, object: &Map<String, Value>) {
+    let mut keys: Vec<&String> = object.keys().collect();
+    keys.sort();
+    for key in keys {
+        let value = &object[key];
+        if DEFAULTS.iter().any(|(field, default)| field == key && value.as_u64() == Some(*default)) {
+            continue;
+        }
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+                entries.sort_by_key(|(entry, _)| *entry);
+                for (entry, value) in entries {
+                    line.push_str(&format!(" {}.{}={}", name(key, true), name(entry, true), value));
+                }
+            }
+            _ => line.push_str(&format!(" {}={}", name(key, true), value)),
+        }
+    }
+}
+
+/// Splits a line at spaces outside of JSON strings.
+fn tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ' ' if !quoted => {
+                if index > start {
+                    tokens.push(&line[start..index]);
+                }
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    if line.len() > start {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// Reads a bare or quoted name from the start of `token`, returning it with the rest.
+fn parse_name(token: &str) -> Result<(String, &str), String> {
+    if !token.starts_with('"') {
+        let end = token.find(['=', '.']).unwrap_or(token.len());
+        return Ok((token[..end].to_string(), &token[end..]));
+    }
+    let mut stream = serde_json::Deserializer::from_str(token).into_iter::<String>();
+    match stream.next() {
+        Some(Ok(name)) => Ok((name, &token[stream.byte_offset()..])),
+        _ => Err(format!("invalid name {}", token)),
+    }
+}
+
+/// Reads a whole token as a name, dots included.
+fn parse_identifier(token: &str) -> Result<String, String> {
+    match token.starts_with('"') {
+        true => serde_json::from_str(token).map_err(|_| format!("invalid name {}", token)),
+        false => Ok(token.to_string()),
+    }
+}
+
+fn parse_field(token: &str, object: &mut Map<String, Value>) -> Result<(), String> {
+    let (key, rest) = parse_name(token)?;
+    let (entry, rest) = match rest.strip_prefix('.') {
+        Some(rest) => parse_name(rest).map(|(entry, rest)| (Some(entry), rest))?,
+        None => (None, rest),
+    };
+    let value: Value = match rest.strip_prefix('=') {
+        Some(value) => serde_json::from_str(value).map_err(|error| format!("invalid value {}: {}", value, error))?,
+        None => return Err(format!("expected key=value, found {}", token)),
+    };
+    match entry {
+        Some(entry) => match object.entry(key).or_insert_with(|| Value::Object(Map::new())) {
+            Value::Object(map) => _ = map.insert(entry, value),
+            _ => return Err(format!("{} is not a map", token)),
+        },
+        None => _ = object.insert(key, value),
+    }
+    Ok(())
+}
+
+impl Netlist {
+    /// Writes the netlist as plain text with one object per line, meant for diffing. Modules
+    /// and their cells, memories and nets are sorted by name, while ports keep their order
+    /// since it defines the module interface.
+    pub fn write_text(&self, mut writer: impl io::Write) -> io::Result<()> {
+        let Value::Object(mut netlist) = serde_json::to_value(self).map_err(io::Error::other)? else {
+            unreachable!();
+        };
+        let Some(Value::Object(modules)) = netlist.remove("modules") else {
+            unreachable!();
+        };
+        let mut line = "netlist".to_string();
+        fields(&mut line, &netlist);
+        writeln!(writer, "{}", line)?;
+
+        let mut modules: Vec<(String, Value)> = modules.into_iter().collect();
+        modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (module_name, module) in modules {
+            let Value::Object(mut module) = module else {
+                unreachable!();
+            };
+            let mut objects = Vec::new();
+            for (kind, key) in OBJECTS {
+                if let Some(Value::Object(map)) = module.remove(*key) {
+                    let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+                    if *kind != "port" {
+                        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    }
+                    objects.extend(entries.into_iter().map(|(name, object)| (*kind, name, object)));
+                }
+            }
+            let mut line = format!("module {}", name(&module_name, false));
+            fields(&mut line, &module);
+            writeln!(writer, "{}", line)?;
+            for (kind, object_name, object) in objects {
+                let mut line = format!("{} {} {}", kind, name(&module_name, false), name(&object_name, false));
+                fields(&mut line, object.as_object().unwrap());
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the format written by [`Netlist::write_text`]. Empty lines and lines starting
+    /// with `#` are ignored.
+    pub fn read_text(reader: impl io::BufRead) -> Result<Netlist, TextError> {
+        let mut netlist = Map::new();
+        let mut modules = Map::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let syntax = |message: String| TextError::Syntax { line: index + 1, message };
+            let tokens = tokens(&line);
+            let Some((kind, tokens)) = tokens.split_first().filter(|_| !line.starts_with('#')) else {
+                continue;
+            };
+            let mut names = Vec::new();
+            let mut object = Map::new();
+            for token in tokens {
+                match names.len() < 2 && kind != &"netlist" && (names.is_empty() || kind != &"module") {
+                    true => names.push(parse_identifier(token).map_err(syntax)?),
+                    false => parse_field(token, &mut object).map_err(syntax)?,
+                }
+            }
+            let expected = match *kind {
+                "netlist" => 0,
+                "module" => 1,
+                _ => 2,
+            };
+            if names.len() != expected {
+                return Err(syntax(format!("expected {} names after {}", expected, kind)));
+            }
+            if *kind == "netlist" {
+                netlist.extend(object);
+                continue;
+            }
+            let module = modules.entry(names[0].clone()).or_insert_with(|| Value::Object(Map::new())).as_object_mut().unwrap();
+            match OBJECTS.iter().find(|(object, _)| object == kind) {
+                Some((_, key)) => {
+                    let objects = module.entry(*key).or_insert_with(|| Value::Object(Map::new())).as_object_mut().unwrap();
+                    objects.insert(names[1].clone(), Value::Object(object));
+                }
+                None if *kind == "module" => module.extend(object),
+                None => return Err(syntax(format!("unknown object kind {}", kind))),
+            }
+        }
+        netlist.insert("modules".to_string(), Value::Object(modules));
+        Netlist::from_value(Value::Object(netlist)).map_err(TextError::Netlist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equality::SemanticEq;
+
+    fn text(netlist: &Netlist) -> String {
+        let mut text = Vec::new();
+        netlist.write_text(&mut text).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let dump = text(&netlist);
+        assert!(dump.starts_with("netlist creator=\"Yosys"));
+        assert!(dump.contains("\nmodule adder attributes.src=\"adder.v:1.1-7.10\"\n"));
+        assert!(dump.contains("\nport adder a bits=[2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18] direction=\"input\"\n"));
+
+        let parsed = Netlist::read_text(dump.as_bytes()).unwrap();
+        assert_eq!(text(&parsed), dump);
+        assert!(parsed.semantic_eq(&netlist));
+        assert_eq!(parsed.modules["adder"].ports.keys().collect::<Vec<_>>(), netlist.modules["adder"].ports.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_text_names() {
+        let input = "# comment\n\
+            netlist creator=\"test\"\n\
+            module \"odd name\"\n\
+            cell \"odd name\" \"u 0\" type=\"$_NOT_\" connections.A=[2] connections.Y=[\"x\"] attributes.\"a.b\"=\"c d\"\n";
+        let netlist = Netlist::read_text(input.as_bytes()).unwrap();
+        let cell = &netlist.modules["odd name"].cells["u 0"];
+        assert_eq!(cell.attributes["a.b"], "c d");
+        assert_eq!(
+            text(&netlist),
+            "netlist creator=\"test\"\n\
+            module \"odd name\"\n\
+            cell \"odd name\" \"u 0\" attributes.\"a.b\"=\"c d\" connections.A=[2] connections.Y=[\"x\"] type=\"$_NOT_\"\n"
+        );
+
+        let error = Netlist::read_text("cell top\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "line 1: expected 2 names after cell");
+    }
+}
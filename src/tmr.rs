@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::progress::pass_span;
+use crate::{Bit, Cell, Direction, Module};
+
+/// How majority voters are built.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Voter {
+    /// `(a & b) | (a & c) | (b & c)` out of `$_AND_` and `$_OR_` gates.
+    #[default]
+    Gates,
+    /// A single cell of the given type with inputs `A`, `B`, `C` and output `Y`, e.g. a
+    /// majority cell of a radiation-hardened library.
+    Cell(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TmrError {
+    UnknownCell(String),
+    /// The name a copy of a selected cell would get is already taken.
+    NameTaken(String),
+    /// A cell was selected more than once.
+    DuplicateCell(String),
+    /// A port of a selected cell is neither declared in `port_directions` nor known from the
+    /// cell type, so it is unknown whether the copies drive it.
+    UnknownDirection { cell: String, port: String },
+}
+
+impl fmt::Display for TmrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCell(cell) => write!(f, "unknown cell {:?}", cell),
+            Self::NameTaken(cell) => write!(f, "cell {:?} already exists", cell),
+            Self::DuplicateCell(cell) => write!(f, "cell {:?} selected more than once", cell),
+            Self::UnknownDirection { cell, port } => write!(f, "unknown direction of port {:?} of cell {:?}", port, cell),
+        }
+    }
+}
+
+impl std::error::Error for TmrError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TmrReport {
+    /// Triplicated cells, by their original name.
+    pub protected: Vec<String>,
+    /// Cells left as a single copy.
+    pub unprotected: Vec<String>,
+    pub protected_registers: usize,
+    pub unprotected_registers: usize,
+    /// Number of voted bits.
+    pub voters: usize,
+}
+
+fn connect(module: &str, connections: &[(&str, Direction, Bit)]) -> Cell {
+    let mut cell = connections
+        .iter()
+        .fold(Cell::new(module), |cell, (port, direction, bit)| cell.with_connection(port, direction.clone(), vec![*bit]));
+    cell.hide_name = true;
+    cell
+}
+
+impl Module {
+    /// Adds a voter under the first `$tmr$voter$<index>` name from `next` on whose cells are
+    /// all free, so voters of earlier passes are kept.
+    fn add_voter(&mut self, voter: &Voter, next: &mut usize, inputs: [Bit; 3], output: Bit) {
+        use Direction::{Input, Output};
+        let [a, b, c] = inputs;
+        let suffixes: &[&str] = match voter {
+            Voter::Cell(_) => &[""],
+            Voter::Gates => &["$and_ab", "$and_ac", "$and_bc", "$or0", "$or1"],
+        };
+        let free = |index: &usize| suffixes.iter().all(|suffix| !self.cells.contains_key(&format!("$tmr$voter${}{}", index, suffix)));
+        let index = (*next..).find(free).unwrap();
+        *next = index + 1;
+        let name = |suffix: &str| format!("$tmr$voter${}{}", index, suffix);
+        match voter {
+            Voter::Cell(module) => {
+                let cell = connect(module, &[("A", Input, a), ("B", Input, b), ("C", Input, c), ("Y", Output, output)]);
+                self.cells.insert(name("").into(), cell);
+            }
+            Voter::Gates => {
+                let fresh = self.fresh_bits(4);
+                let gates = [
+                    ("$and_ab", "$_AND_", a, b, fresh[0]),
+                    ("$and_ac", "$_AND_", a, c, fresh[1]),
+                    ("$and_bc", "$_AND_", b, c, fresh[2]),
+                    ("$or0", "$_OR_", fresh[0], fresh[1], fresh[3]),
+                    ("$or1", "$_OR_", fresh[3], fresh[2], output),
+                ];
+                for (suffix, module, a, b, y) in gates {
+                    self.cells.insert(name(suffix).into(), connect(module, &[("A", Input, a), ("B", Input, b), ("Y", Output, y)]));
+                }
+            }
+        }
+    }
+
+    /// Triplicates `cells` and votes every signal they drive for the rest of the module. Signals
+    /// between selected cells stay in their own redundancy domain, so voting happens once at the
+    /// boundary. The first copy keeps the original name, the others get a `_tmr1`/`_tmr2`
+    /// suffix. Selected submodule instances are triplicated like any other cell. Ports of
+    /// internal cells without `port_directions` take their direction from the cell type.
+    pub fn triplicate(&mut self, cells: &[&str], voter: &Voter) -> Result<TmrReport, TmrError> {
+        pass_span!("triplicate", cells = self.cells.len(), nets = self.nets.len(), selected = cells.len());
+        let mut selected: HashSet<&str> = HashSet::new();
+        for cell in cells {
+            if !self.cells.contains_key(*cell) {
+                return Err(TmrError::UnknownCell(cell.to_string()));
+            }
+            if !selected.insert(cell) {
+                return Err(TmrError::DuplicateCell(cell.to_string()));
+            }
+            if let Some(copy) = (1..3).map(|domain| format!("{}_tmr{}", cell, domain)).find(|copy| self.cells.contains_key(copy)) {
+                return Err(TmrError::NameTaken(copy));
+            }
+        }
+
+        let mut driven: Vec<Bit> = Vec::new();
+        let mut loads: HashSet<Bit> = HashSet::new();
+        for (name, cell) in self.cells.iter() {
+            for (port, bits) in cell.connections.iter() {
+                let direction = cell.port_direction(port);
+                let signals = bits.iter().filter(|bit| matches!(bit, Bit::Signal(_)));
+                if selected.contains(name.as_str()) {
+                    match direction {
+                        Some(Direction::Output) => driven.extend(signals),
+                        Some(_) => {}
+                        None => return Err(TmrError::UnknownDirection { cell: name.to_string(), port: port.to_string() }),
+                    }
+                } else if direction != Some(Direction::Output) {
+                    loads.extend(signals);
+                }
+            }
+        }
+        for port in self.ports.values().filter(|port| port.direction != Direction::Input) {
+            loads.extend(port.bits.iter());
+        }
+
+        let fresh = self.fresh_bits(3 * driven.len());
+        let domains: Vec<HashMap<Bit, Bit>> =
+            (0..3).map(|domain| driven.iter().copied().zip(fresh[domain * driven.len()..].iter().copied()).collect()).collect();
+
+        let mut report = TmrReport::default();
+        let mut triplicated = Vec::new();
+        for name in cells {
+            let original = &self.cells[*name];
+            for (domain, map) in domains.iter().enumerate() {
+                let mut copy = original.clone();
+                for bits in copy.connections.values_mut() {
+                    bits.iter_mut().for_each(|bit| *bit = map.get(bit).copied().unwrap_or(*bit));
+                }
+                let copy_name = match domain {
+                    0 => name.to_string(),
+                    _ => format!("{}_tmr{}", name, domain),
+                };
+                triplicated.push((copy_name, copy));
+            }
+            if original.is_register() {
+                report.protected_registers += 1;
+            }
+            report.protected.push(name.to_string());
+        }
+        for name in cells {
+            self.cells.shift_remove(*name);
+        }
+        let mut copies = HashSet::new();
+        for (name, copy) in triplicated {
+            copies.insert(name.clone());
+            self.cells.insert(name.into(), copy);
+        }
+
+        let mut next = 0;
+        for bit in driven.iter().filter(|bit| loads.contains(bit)) {
+            let inputs = [domains[0][bit], domains[1][bit], domains[2][bit]];
+            self.add_voter(voter, &mut next, inputs, *bit);
+            report.voters += 1;
+        }
+
+        for (name, cell) in self.cells.iter() {
+            if !copies.contains(name.as_str()) && !name.starts_with("$tmr$voter$") {
+                report.unprotected.push(name.to_string());
+                if cell.is_register() {
+                    report.unprotected_registers += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn counter() -> Module {
+        let netlist = crate::Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": {
+                    "ports": {
+                        "clk": { "direction": "input", "bits": [2] },
+                        "q": { "direction": "output", "bits": [3] },
+                        "p": { "direction": "output", "bits": [5] }
+                    },
+                    "cells": {
+                        "ff": {
+                            "type": "$_DFF_P_",
+                            "port_directions": { "C": "input", "D": "input", "Q": "output" },
+                            "connections": { "C": [2], "D": [4], "Q": [3] }
+                        },
+                        "not": {
+                            "type": "$_NOT_",
+                            "port_directions": { "A": "input", "Y": "output" },
+                            "connections": { "A": [3], "Y": [4] }
+                        },
+                        "parity": {
+                            "type": "$_DFF_P_",
+                            "port_directions": { "C": "input", "D": "input", "Q": "output" },
+                            "connections": { "C": [2], "D": [3], "Q": [5] }
+                        }
+                    },
+                    "netnames": {
+                        "clk": { "bits": [2] },
+                        "q": { "bits": [3] },
+                        "p": { "bits": [5] }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        netlist.modules["top"].clone()
+    }
+
+    #[test]
+    fn test_triplicate() {
+        let mut module = counter();
+        let report = module.triplicate(&["ff", "not"], &Voter::Gates).unwrap();
+        assert_eq!(report.protected, ["ff", "not"]);
+        assert_eq!(report.unprotected, ["parity"]);
+        assert_eq!((report.protected_registers, report.unprotected_registers), (1, 1));
+        // Only `q` leaves the triplicated region, the feedback through `not` stays per domain.
+        assert_eq!(report.voters, 1);
+        assert_eq!(module.cells.len(), 6 + 1 + 5);
+
+        let ff1 = &module.cells["ff_tmr1"];
+        let not1 = &module.cells["not_tmr1"];
+        assert_eq!(not1.connections["A"], ff1.connections["Q"]);
+        assert_eq!(ff1.connections["D"], not1.connections["Y"]);
+        assert_eq!(ff1.connections["C"], [Bit::Signal(2)]);
+        assert_eq!(module.cells["$tmr$voter$0$or1"].connections["Y"], [Bit::Signal(3)]);
+    }
+
+    #[test]
+    fn test_triplicate_voter_cell() {
+        let mut module = counter();
+        let report = module.triplicate(&["ff"], &Voter::Cell("MAJ3".to_string())).unwrap();
+        assert_eq!(report.voters, 1);
+        let voter = &module.cells["$tmr$voter$0"];
+        assert_eq!(voter.module, "MAJ3");
+        assert_eq!(voter.connections["Y"], [Bit::Signal(3)]);
+        assert_eq!(module.triplicate(&["nope"], &Voter::Gates), Err(TmrError::UnknownCell("nope".to_string())));
+        let mut module = counter();
+        assert_eq!(module.triplicate(&["ff", "not", "ff"], &Voter::Gates), Err(TmrError::DuplicateCell("ff".to_string())));
+        assert_eq!(module.cells.len(), 3);
+    }
+
+    #[test]
+    fn test_triplicate_without_directions() {
+        let mut module = counter();
+        module.cells["not"].port_directions.clear();
+        module.triplicate(&["ff", "not"], &Voter::Gates).unwrap();
+        let outputs: HashSet<&Bit> = ["not", "not_tmr1", "not_tmr2"].iter().map(|name| &module.cells[*name].connections["Y"][0]).collect();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(module.cells["ff_tmr2"].connections["D"], module.cells["not_tmr2"].connections["Y"]);
+
+        let mut module = counter();
+        module.cells["not"].module = "inverter".into();
+        module.cells["not"].port_directions.clear();
+        let error = TmrError::UnknownDirection { cell: "not".to_string(), port: "A".to_string() };
+        assert_eq!(module.triplicate(&["ff", "not"], &Voter::Gates), Err(error));
+        assert_eq!(module.cells.len(), 3);
+    }
+
+    #[test]
+    fn test_triplicate_without_outputs() {
+        let mut module = counter();
+        let check = Cell::new("$assert").with_connection("A", Direction::Input, vec![Bit::Signal(3)]);
+        module.cells.insert("check".into(), check.with_connection("EN", Direction::Input, vec![Bit::_1]));
+        let report = module.triplicate(&["check"], &Voter::Gates).unwrap();
+        assert_eq!((report.protected, report.voters), (vec!["check".to_string()], 0));
+        for name in ["check", "check_tmr1", "check_tmr2"] {
+            assert_eq!(module.cells[name].connections["A"], [Bit::Signal(3)]);
+        }
+    }
+
+    #[test]
+    fn test_triplicate_twice() {
+        let mut netlist = crate::Netlist::from_value(json!({ "creator": "test", "modules": { "top": counter() } })).unwrap();
+        let module = &mut netlist.modules["top"];
+        module.triplicate(&["ff", "not"], &Voter::Gates).unwrap();
+        let report = module.triplicate(&["parity"], &Voter::Gates).unwrap();
+        assert_eq!(report.voters, 1);
+        assert_eq!(module.cells.len(), 6 + 3 + 2 * 5);
+        assert!(module.cells.contains_key("$tmr$voter$0$or1") && module.cells.contains_key("$tmr$voter$1$or1"));
+        assert_eq!(netlist.validate(), []);
+        let module = &mut netlist.modules["top"];
+        assert_eq!(module.triplicate(&["ff"], &Voter::Gates), Err(TmrError::NameTaken("ff_tmr1".to_string())));
+    }
+}
@@ -0,0 +1,209 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::Netlist;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    Lut,
+    FlipFlop,
+    Carry,
+    /// Block RAM, counted in the smallest block of the family.
+    Bram,
+    Dsp,
+    Io,
+}
+
+/// Resources used by every cell whose type matches `cell`, exactly or as a prefix when it ends
+/// in `*`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceRule {
+    pub cell: String,
+    pub resources: Vec<(Resource, usize)>,
+}
+
+impl ResourceRule {
+    fn new(cell: &str, resources: &[(Resource, usize)]) -> Self {
+        Self { cell: cell.to_string(), resources: resources.to_vec() }
+    }
+
+    fn matches(&self, cell: &str) -> bool {
+        match self.cell.strip_suffix('*') {
+            Some(prefix) => cell.starts_with(prefix),
+            None => cell == self.cell,
+        }
+    }
+}
+
+/// The cell-to-resource table of an FPGA family. The first matching rule counts, cells
+/// matching no rule are reported as unmapped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FamilyTable {
+    pub name: String,
+    pub rules: Vec<ResourceRule>,
+    /// LUTs and flip-flops in one logic slice, the unit of packing.
+    pub luts_per_slice: usize,
+    pub flip_flops_per_slice: usize,
+    /// Resources available on the target device, to report utilization against.
+    #[serde(default)]
+    pub capacity: IndexMap<Resource, usize>,
+}
+
+impl FamilyTable {
+    /// Lattice iCE40, as mapped by `synth_ice40`.
+    pub fn ice40() -> Self {
+        use Resource::*;
+        Self {
+            name: "ice40".to_string(),
+            rules: vec![
+                ResourceRule::new("SB_LUT4", &[(Lut, 1)]),
+                ResourceRule::new("SB_DFF*", &[(FlipFlop, 1)]),
+                ResourceRule::new("SB_CARRY", &[(Carry, 1)]),
+                ResourceRule::new("SB_RAM40_4K*", &[(Bram, 1)]),
+                ResourceRule::new("SB_MAC16", &[(Dsp, 1)]),
+                ResourceRule::new("SB_IO*", &[(Io, 1)]),
+                ResourceRule::new("SB_GB*", &[]),
+            ],
+            luts_per_slice: 8,
+            flip_flops_per_slice: 8,
+            capacity: IndexMap::new(),
+        }
+    }
+
+    /// Lattice ECP5, as mapped by `synth_ecp5`. Block RAM is counted in 18 kbit blocks.
+    pub fn ecp5() -> Self {
+        use Resource::*;
+        Self {
+            name: "ecp5".to_string(),
+            rules: vec![
+                ResourceRule::new("LUT4", &[(Lut, 1)]),
+                ResourceRule::new("PFUMX", &[]),
+                ResourceRule::new("L6MUX21", &[]),
+                ResourceRule::new("TRELLIS_FF", &[(FlipFlop, 1)]),
+                ResourceRule::new("CCU2C", &[(Lut, 2), (Carry, 1)]),
+                ResourceRule::new("TRELLIS_DPR16X4", &[(Lut, 4)]),
+                ResourceRule::new("DP16KD", &[(Bram, 1)]),
+                ResourceRule::new("PDPW16KD", &[(Bram, 1)]),
+                ResourceRule::new("MULT18X18D", &[(Dsp, 1)]),
+                ResourceRule::new("ALU54B", &[(Dsp, 1)]),
+                ResourceRule::new("TRELLIS_IO", &[(Io, 1)]),
+            ],
+            luts_per_slice: 2,
+            flip_flops_per_slice: 2,
+            capacity: IndexMap::new(),
+        }
+    }
+
+    /// AMD/Xilinx 7-series, as mapped by `synth_xilinx`. Block RAM is counted in 18 kbit halves.
+    pub fn xilinx7() -> Self {
+        use Resource::*;
+        Self {
+            name: "xilinx7".to_string(),
+            rules: vec![
+                ResourceRule::new("LUT*", &[(Lut, 1)]),
+                ResourceRule::new("MUXF*", &[]),
+                ResourceRule::new("FD*", &[(FlipFlop, 1)]),
+                ResourceRule::new("LD*", &[(FlipFlop, 1)]),
+                ResourceRule::new("CARRY4", &[(Carry, 1)]),
+                ResourceRule::new("SRL*", &[(Lut, 1)]),
+                ResourceRule::new("RAM32M", &[(Lut, 4)]),
+                ResourceRule::new("RAM64M", &[(Lut, 4)]),
+                ResourceRule::new("RAM32X1*", &[(Lut, 1)]),
+                ResourceRule::new("RAM64X1*", &[(Lut, 1)]),
+                ResourceRule::new("RAMB18E1", &[(Bram, 1)]),
+                ResourceRule::new("RAMB36E1", &[(Bram, 2)]),
+                ResourceRule::new("DSP48E1", &[(Dsp, 1)]),
+                ResourceRule::new("IBUF*", &[(Io, 1)]),
+                ResourceRule::new("OBUF*", &[(Io, 1)]),
+                ResourceRule::new("IOBUF*", &[(Io, 1)]),
+                ResourceRule::new("BUFG*", &[]),
+            ],
+            luts_per_slice: 4,
+            flip_flops_per_slice: 8,
+            capacity: IndexMap::new(),
+        }
+    }
+
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Utilization {
+    pub family: String,
+    pub used: IndexMap<Resource, usize>,
+    /// Logic slices needed if LUTs and flip-flops pack perfectly, a lower bound.
+    pub slices: usize,
+    /// Used resources as a fraction of the capacity given in the table.
+    pub fraction: IndexMap<Resource, f64>,
+    /// Counts of cell types matching no rule, e.g. unmapped `$_AND_` gates.
+    pub unmapped: IndexMap<String, usize>,
+}
+
+impl Utilization {
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+impl Netlist {
+    /// Estimates the resources used by `top` with everything below it flattened, one count
+    /// per instance of every module.
+    pub fn estimate_utilization(&self, top: &str, table: &FamilyTable) -> Utilization {
+        let mut used: IndexMap<Resource, usize> = IndexMap::new();
+        let mut unmapped: IndexMap<String, usize> = IndexMap::new();
+        for cell in self.iter_hierarchy(top).map(|cell| cell.cell.object).filter(|cell| !self.modules.contains_key(&cell.module)) {
+            match table.rules.iter().find(|rule| rule.matches(&cell.module)) {
+                Some(rule) => rule.resources.iter().for_each(|(resource, count)| *used.entry(*resource).or_default() += count),
+                None => *unmapped.entry(cell.module.to_string()).or_default() += 1,
+            }
+        }
+        unmapped.sort_by(|a, an, b, bn| bn.cmp(an).then(a.cmp(b)));
+        let count = |resource| used.get(&resource).copied().unwrap_or(0);
+        let slices = count(Resource::Lut)
+            .div_ceil(table.luts_per_slice.max(1))
+            .max(count(Resource::FlipFlop).div_ceil(table.flip_flops_per_slice.max(1)));
+        let fraction = table
+            .capacity
+            .iter()
+            .filter(|(_, capacity)| **capacity > 0)
+            .map(|(resource, capacity)| (*resource, count(*resource) as f64 / *capacity as f64))
+            .collect();
+        Utilization { family: table.name.clone(), used, slices, fraction, unmapped }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_estimate_utilization() {
+        let cell = |module: &str| json!({ "type": module, "connections": {} });
+        let netlist = Netlist::from_value(json!({
+            "creator": "test",
+            "modules": {
+                "top": { "cells": { "u0": cell("sub"), "u1": cell("sub"), "ram": cell("RAMB36E1"), "g": cell("$_AND_") } },
+                "sub": { "cells": { "l": cell("LUT6"), "f": cell("FDRE"), "m": cell("MUXF7"), "c": cell("CARRY4") } }
+            }
+        }))
+        .unwrap();
+        let mut table = FamilyTable::xilinx7();
+        table.capacity.insert(Resource::Bram, 8);
+        let utilization = netlist.estimate_utilization("top", &table);
+        assert_eq!(utilization.used[&Resource::Lut], 2);
+        assert_eq!(utilization.used[&Resource::FlipFlop], 2);
+        assert_eq!(utilization.used[&Resource::Carry], 2);
+        assert_eq!(utilization.used[&Resource::Bram], 2);
+        assert_eq!(utilization.fraction[&Resource::Bram], 0.25);
+        assert_eq!(utilization.slices, 1);
+        assert_eq!(utilization.unmapped.into_iter().collect::<Vec<_>>(), [("$_AND_".to_string(), 1)]);
+
+        let ecp5 = netlist.estimate_utilization("top", &FamilyTable::ecp5());
+        assert_eq!(ecp5.unmapped.len(), 6);
+    }
+}
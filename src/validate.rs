@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::connectivity::Endpoint;
+use crate::hierarchy::{HierarchyError, Tops};
+use crate::{Bit, Direction, Module, Netlist};
+
+/// A structural problem found by [`Netlist::validate`]. Bits are named by their nets, and
+/// cell pins as `cell.port[index]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintIssue {
+    MultipleDrivers {
+        module: String,
+        bit: String,
+        drivers: Vec<String>,
+    },
+    /// A cell output none of whose bits is read.
+    DanglingOutput {
+        module: String,
+        cell: String,
+        port: String,
+    },
+    UndrivenLoad {
+        module: String,
+        bit: String,
+        loads: Vec<String>,
+    },
+    /// A cell of a type that is neither a Yosys internal cell nor a module of the netlist.
+    UndefinedModule {
+        module: String,
+        cell: String,
+        cell_type: String,
+    },
+    /// A cell port whose direction differs from the port of the instantiated module, which
+    /// is `None` when the module has no such port.
+    PortDirection {
+        module: String,
+        cell: String,
+        port: String,
+        declared: Direction,
+        expected: Option<Direction>,
+    },
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MultipleDrivers { module, bit, drivers } => {
+                write!(f, "{}: {} is driven by {}", module, bit, drivers.join(", "))
+            }
+            Self::DanglingOutput { module, cell, port } => write!(f, "{}: output {}.{} is not read", module, cell, port),
+            Self::UndrivenLoad { module, bit, loads } => write!(f, "{}: {} is read by {} but not driven", module, bit, loads.join(", ")),
+            Self::UndefinedModule { module, cell, cell_type } => write!(f, "{}: cell {} has undefined type {}", module, cell, cell_type),
+            Self::PortDirection { module, cell, port, declared, expected: Some(expected) } => {
+                write!(f, "{}: port {}.{} is declared {:?} but the module port is {:?}", module, cell, port, declared, expected)
+            }
+            Self::PortDirection { module, cell, port, .. } => {
+                write!(f, "{}: cell {} connects {}, which its module lacks", module, cell, port)
+            }
+        }
+    }
+}
+
+fn describe(endpoint: &Endpoint) -> String {
+    match endpoint {
+        Endpoint::Cell { cell, port, index } => format!("{}.{}[{}]", cell, port, index),
+        Endpoint::Port { port, index } => format!("port {}[{}]", port, index),
+    }
+}
+
+impl Netlist {
+    fn validate_module(&self, name: &str, module: &Module, issues: &mut Vec<LintIssue>) {
+        let index = module.index();
+        let mut bits: Vec<&Bit> = module.ports.values().flat_map(|port| &port.bits).collect();
+        bits.extend(module.cells.values().flat_map(|cell| cell.connections.values().flatten()));
+        let mut seen = HashSet::new();
+        for bit in bits.into_iter().filter(|bit| matches!(bit, Bit::Signal(_)) && seen.insert(**bit)) {
+            let (drivers, loads) = (index.drivers_of(bit), index.loads_of(bit));
+            if drivers.len() > 1 {
+                let drivers = drivers.iter().map(describe).collect();
+                issues.push(LintIssue::MultipleDrivers { module: name.to_string(), bit: module.bit_name(bit), drivers });
+            }
+            if drivers.is_empty() && !loads.is_empty() {
+                let loads = loads.iter().map(describe).collect();
+                issues.push(LintIssue::UndrivenLoad { module: name.to_string(), bit: module.bit_name(bit), loads });
+            }
+        }
+
+        for (cell_name, cell) in module.cells.iter() {
+            for (port, bits) in cell.connections.iter().filter(|(port, _)| cell.port_directions.get(*port) == Some(&Direction::Output)) {
+                let read = bits
+                    .iter()
+                    .any(|bit| index.loads_of(bit).iter().any(|load| !matches!(load, Endpoint::Cell { cell, .. } if cell == cell_name)));
+                if !bits.is_empty() && !read {
+                    issues.push(LintIssue::DanglingOutput { module: name.to_string(), cell: cell_name.to_string(), port: port.to_string() });
+                }
+            }
+
+            let Some(definition) = self.modules.get(&cell.module) else {
+                if !cell.module.starts_with('$') {
+                    let (module, cell_type) = (name.to_string(), cell.module.to_string());
+                    issues.push(LintIssue::UndefinedModule { module, cell: cell_name.to_string(), cell_type });
+                }
+                continue;
+            };
+            for (port, declared) in cell.port_directions.iter() {
+                let expected = definition.ports.get(port).map(|port| port.direction.clone());
+                if expected != Some(declared.clone()) {
+                    let (module, cell, port) = (name.to_string(), cell_name.to_string(), port.to_string());
+                    issues.push(LintIssue::PortDirection { module, cell, port, declared: declared.clone(), expected });
+                }
+            }
+        }
+    }
+
+    /// Checks every module for multiply driven bits, unread cell outputs, read bits without a
+    /// driver, instances of undefined modules and instance ports that disagree with the
+    /// module instantiated.
+    pub fn validate(&self) -> Vec<LintIssue> {
+        let mut issues = Vec::new();
+        for (name, module) in self.modules.iter() {
+            self.validate_module(name, module, &mut issues);
+        }
+        issues
+    }
+
+    /// Checks the modules below `tops` like [`Netlist::validate`], leaving out the rest.
+    pub fn validate_tops(&self, tops: &Tops) -> Result<Vec<LintIssue>, HierarchyError> {
+        let tops = self.resolve_tops(tops)?;
+        let mut issues = Vec::new();
+        for name in self.module_graph().below(&tops) {
+            self.validate_module(name, &self.modules[name], &mut issues);
+        }
+        Ok(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Port;
+    use crate::builder::CellBuilder;
+
+    #[test]
+    fn test_validate() {
+        let mut builder = Module::builder();
+        let a = builder.input("a", 1).unwrap();
+        let y = builder.output("y", 1).unwrap();
+        let [floating, unused] = ["floating", "unused"].map(|name| builder.wire(name, 1).unwrap());
+        builder.cell("and", CellBuilder::new("$_AND_").input("A", a.clone()).input("B", floating).output("Y", y.clone())).unwrap();
+        builder.cell("or", CellBuilder::new("$_OR_").input("A", a.clone()).input("B", a.clone()).output("Y", y)).unwrap();
+        builder.cell("not", CellBuilder::new("$_NOT_").input("A", a.clone()).output("Y", unused)).unwrap();
+        builder.cell("sub", CellBuilder::new("child").input("I", a.clone()).input("Q", a)).unwrap();
+        builder.cell("ext", CellBuilder::new("vendor_cell")).unwrap();
+        let mut netlist = Netlist::new("test");
+        netlist.modules.insert("top".into(), builder.build());
+        let mut child = Module::new();
+        child.ports.insert("I".into(), Port::new(Direction::Input, vec![Bit::Signal(2)]));
+        child.ports.insert("O".into(), Port::new(Direction::Output, vec![Bit::Signal(2)]));
+        netlist.modules.insert("child".into(), child);
+
+        let module = "top".to_string();
+        assert_eq!(
+            netlist.validate(),
+            [
+                LintIssue::MultipleDrivers {
+                    module: module.clone(),
+                    bit: "y".to_string(),
+                    drivers: vec!["and.Y[0]".to_string(), "or.Y[0]".to_string()]
+                },
+                LintIssue::UndrivenLoad { module: module.clone(), bit: "floating".to_string(), loads: vec!["and.B[0]".to_string()] },
+                LintIssue::DanglingOutput { module: module.clone(), cell: "not".to_string(), port: "Y".to_string() },
+                LintIssue::PortDirection {
+                    module: module.clone(),
+                    cell: "sub".to_string(),
+                    port: "Q".to_string(),
+                    declared: Direction::Input,
+                    expected: None
+                },
+                LintIssue::UndefinedModule { module: module.clone(), cell: "ext".to_string(), cell_type: "vendor_cell".to_string() },
+            ]
+        );
+        assert_eq!(netlist.validate()[0].to_string(), "top: y is driven by and.Y[0], or.Y[0]");
+        assert_eq!(netlist.validate_tops(&Tops::Auto).unwrap(), netlist.validate());
+        assert_eq!(netlist.validate_tops(&Tops::Named(vec!["child".to_string()])).unwrap(), []);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use indexmap::IndexMap;
+
+use crate::sim::Logic;
+use crate::{Bit, Module};
+
+#[derive(Debug)]
+pub enum VcdError {
+    Io(io::Error),
+    UnknownNet(String),
+    /// A sample at a time before the previous one.
+    TimeReversed {
+        previous: u64,
+        time: u64,
+    },
+}
+
+impl fmt::Display for VcdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::UnknownNet(net) => write!(f, "unknown net {}", net),
+            Self::TimeReversed { previous, time } => write!(f, "sample at {} after a sample at {}", time, previous),
+        }
+    }
+}
+
+impl std::error::Error for VcdError {}
+
+impl From<io::Error> for VcdError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Short identifier of the `index`th variable, in printable ASCII.
+fn identifier(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    vars: Vec<usize>,
+    scopes: IndexMap<String, Scope>,
+}
+
+struct Var {
+    id: String,
+    name: String,
+    bits: Vec<Bit>,
+    range: String,
+}
+
+/// Writes nets of a module to a VCD waveform, one sampled state at a time. Public net names
+/// with dots, like those of a flattened module, are split into nested scopes below the scope
+/// of the module, `u0.u1.q` becoming variable `q` in scope `u1` in scope `u0`.
+pub struct VcdWriter<W: Write> {
+    writer: W,
+    vars: Vec<Var>,
+    last: Vec<Option<String>>,
+    time: Option<u64>,
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Writes the header declaring `nets` of `module`, or all nets with public names when
+    /// `nets` is empty, in a scope named `top`. `timescale` is the unit of the sample times,
+    /// like `1ns`.
+    pub fn new(mut writer: W, module: &Module, top: &str, nets: &[&str], timescale: &str) -> Result<Self, VcdError> {
+        let names: Vec<&str> = match nets.is_empty() {
+            true => module.nets.iter().filter(|(_, net)| !net.hide_name).map(|(name, _)| name.as_str()).collect(),
+            false => nets.to_vec(),
+        };
+        let mut root = Scope::default();
+        let mut vars = Vec::new();
+        for name in names {
+            let net = module.nets.get(name).ok_or_else(|| VcdError::UnknownNet(name.to_string()))?;
+            let mut path: Vec<&str> = match name.starts_with('$') {
+                true => vec![name],
+                false => name.split('.').collect(),
+            };
+            let leaf = path.pop().unwrap();
+            let scope = path.into_iter().fold(&mut root, |scope, name| scope.scopes.entry(name.to_string()).or_default());
+            scope.vars.push(vars.len());
+            let (msb, lsb) = (net.offset + net.bits.len().max(1) - 1, net.offset);
+            let range = match (net.bits.len(), net.upto) {
+                (1, _) => String::new(),
+                (_, 0) => format!(" [{}:{}]", msb, lsb),
+                _ => format!(" [{}:{}]", lsb, msb),
+            };
+            vars.push(Var { id: identifier(vars.len()), name: leaf.replace(' ', "_"), bits: net.bits.clone(), range });
+        }
+
+        writeln!(writer, "$version {} {} $end", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))?;
+        writeln!(writer, "$timescale {} $end", timescale)?;
+        let mut stack = vec![(Some(top), &root)];
+        while let Some((name, scope)) = stack.pop() {
+            let Some(name) = name else {
+                writeln!(writer, "$upscope $end")?;
+                continue;
+            };
+            writeln!(writer, "$scope module {} $end", name)?;
+            for var in scope.vars.iter().map(|index| &vars[*index]) {
+                writeln!(writer, "$var wire {} {} {}{} $end", var.bits.len(), var.id, var.name, var.range)?;
+            }
+            stack.push((None, scope));
+            stack.extend(scope.scopes.iter().rev().map(|(name, scope)| (Some(name.as_str()), scope)));
+        }
+        writeln!(writer, "$enddefinitions $end")?;
+        let last = vec![None; vars.len()];
+        Ok(Self { writer, vars, last, time: None })
+    }
+
+    /// Writes the state at `time`, with the value of every bit given by `value`, such as
+    /// [`crate::sim::Simulator::value`]. Only the variables that changed since the previous
+    /// sample are written, and all of them on the first.
+    pub fn sample(&mut self, time: u64, value: impl Fn(&Bit) -> Logic) -> Result<(), VcdError> {
+        if let Some(previous) = self.time
+            && time < previous
+        {
+            return Err(VcdError::TimeReversed { previous, time });
+        }
+        let mut changes = Vec::new();
+        for (var, last) in self.vars.iter().zip(self.last.iter_mut()) {
+            let bits: String = var.bits.iter().rev().map(|bit| value(bit).to_string()).collect();
+            if last.as_ref() != Some(&bits) {
+                changes.push(match var.bits.len() {
+                    1 => format!("{}{}", bits, var.id),
+                    _ => format!("b{} {}", bits, var.id),
+                });
+                *last = Some(bits);
+            }
+        }
+        let first = self.time.is_none();
+        if self.time != Some(time) {
+            writeln!(self.writer, "#{}", time)?;
+            self.time = Some(time);
+        }
+        if first {
+            writeln!(self.writer, "$dumpvars")?;
+        }
+        for change in changes {
+            writeln!(self.writer, "{}", change)?;
+        }
+        if first {
+            writeln!(self.writer, "$end")?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the waveform and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, VcdError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{CellBuilder, constant};
+    use crate::sim::Simulator;
+
+    #[test]
+    fn test_vcd() {
+        let mut builder = Module::builder();
+        let clk = builder.input("clk", 1).unwrap();
+        let q = builder.output("q", 2).unwrap();
+        let next = builder.wire("u0.next", 2).unwrap();
+        builder.cell("inc", CellBuilder::new("$add").input("A", q.clone()).input("B", constant(1, 2)).output("Y", next.clone())).unwrap();
+        builder
+            .cell("reg", CellBuilder::new("$dff").parameter("CLK_POLARITY", 1).input("CLK", clk).input("D", next).output("Q", q))
+            .unwrap();
+        let mut module = builder.build();
+        module.nets["q"].attributes.insert("init".to_string(), "00".into());
+
+        let mut simulator = Simulator::new(&module).unwrap();
+        let mut vcd = VcdWriter::new(Vec::new(), &module, "counter", &[], "1ns").unwrap();
+        for time in 0..3 {
+            simulator.step("clk").unwrap();
+            vcd.sample(time * 10, |bit| simulator.value(bit)).unwrap();
+        }
+        vcd.sample(25, |bit| simulator.value(bit)).unwrap();
+        assert!(matches!(vcd.sample(5, |_| Logic::X), Err(VcdError::TimeReversed { previous: 25, time: 5 })));
+        let text = String::from_utf8(vcd.finish().unwrap()).unwrap();
+        let expected = "$timescale 1ns $end
+$scope module counter $end
+$var wire 1 ! clk $end
+$var wire 2 \" q [1:0] $end
+$scope module u0 $end
+$var wire 2 # next [1:0] $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+1!
+b01 \"
+b10 #
+$end
+#10
+b10 \"
+b11 #
+#20
+b11 \"
+b00 #
+#25
+";
+        assert_eq!(text.split_once('\n').unwrap().1, expected);
+        assert!(matches!(VcdWriter::new(Vec::new(), &module, "counter", &["d"], "1ns"), Err(VcdError::UnknownNet(_))));
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+
+use crate::{Bit, Cell, Direction, Module, Net, Port};
+
+/// Read access shared by [`Module`] and [`ModuleView`], so analyses can run on either.
+pub trait ModuleRead {
+    fn port(&self, name: &str) -> Option<&Port>;
+    fn cell(&self, name: &str) -> Option<&Cell>;
+    fn net(&self, name: &str) -> Option<&Net>;
+    fn ports(&self) -> impl Iterator<Item = (&str, &Port)>;
+    fn cells(&self) -> impl Iterator<Item = (&str, &Cell)>;
+    fn nets(&self) -> impl Iterator<Item = (&str, &Net)>;
+}
+
+impl ModuleRead for Module {
+    fn port(&self, name: &str) -> Option<&Port> {
+        self.ports.get(name)
+    }
+
+    fn cell(&self, name: &str) -> Option<&Cell> {
+        self.cells.get(name)
+    }
+
+    fn net(&self, name: &str) -> Option<&Net> {
+        self.nets.get(name)
+    }
+
+    fn ports(&self) -> impl Iterator<Item = (&str, &Port)> {
+        self.ports.iter().map(|(name, port)| (name.as_str(), port))
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (&str, &Cell)> {
+        self.cells.iter().map(|(name, cell)| (name.as_str(), cell))
+    }
+
+    fn nets(&self) -> impl Iterator<Item = (&str, &Net)> {
+        self.nets.iter().map(|(name, net)| (name.as_str(), net))
+    }
+}
+
+/// A selection of cells and nets of a module, borrowed instead of copied. Ports are the ones
+/// connected to a selected cell.
+#[derive(Debug, Clone)]
+pub struct ModuleView<'a> {
+    module: &'a Module,
+    cells: Vec<bool>,
+    nets: Vec<bool>,
+}
+
+impl<'a> ModuleView<'a> {
+    fn touched(&self) -> HashSet<Bit> {
+        self.cells().flat_map(|(_, cell)| cell.connections.values().flatten().copied()).collect()
+    }
+
+    /// Selects the nets connected to the selected cells.
+    fn select_nets(mut self) -> Self {
+        let touched = self.touched();
+        self.nets = self.module.nets.values().map(|net| net.bits.iter().any(|bit| touched.contains(bit))).collect();
+        self
+    }
+
+    pub fn module(&self) -> &'a Module {
+        self.module
+    }
+
+    /// Keeps the selected cells matching `predicate`, and the nets still connected to them.
+    pub fn filter_cells(mut self, predicate: impl Fn(&str, &Cell) -> bool) -> Self {
+        for (selected, (name, cell)) in self.cells.iter_mut().zip(self.module.cells.iter()) {
+            *selected &= predicate(name, cell);
+        }
+        self.select_nets()
+    }
+
+    /// Keeps the selected nets matching `predicate`, leaving cells alone.
+    pub fn filter_nets(mut self, predicate: impl Fn(&str, &Net) -> bool) -> Self {
+        for (selected, (name, net)) in self.nets.iter_mut().zip(self.module.nets.iter()) {
+            *selected &= predicate(name, net);
+        }
+        self
+    }
+
+    /// Bits read by selected cells but driven elsewhere.
+    pub fn inputs(&self) -> Vec<Bit> {
+        let driven: HashSet<Bit> = self.driven().collect();
+        let mut inputs = Vec::new();
+        for (_, cell) in self.cells() {
+            for (port, bits) in cell.connections.iter() {
+                if cell.port_directions.get(port) == Some(&Direction::Output) {
+                    continue;
+                }
+                for bit in bits.iter().filter(|bit| matches!(bit, Bit::Signal(_)) && !driven.contains(bit)) {
+                    if !inputs.contains(bit) {
+                        inputs.push(*bit);
+                    }
+                }
+            }
+        }
+        inputs
+    }
+
+    /// Bits driven by selected cells and read outside the selection or by output ports.
+    pub fn outputs(&self) -> Vec<Bit> {
+        let mut read = HashSet::new();
+        for (cell, _) in self.module.cells.values().zip(self.cells.iter()).filter(|(_, selected)| !**selected) {
+            for (port, bits) in cell.connections.iter() {
+                if cell.port_directions.get(port) != Some(&Direction::Output) {
+                    read.extend(bits.iter().copied());
+                }
+            }
+        }
+        for port in self.module.ports.values().filter(|port| port.direction != Direction::Input) {
+            read.extend(port.bits.iter().copied());
+        }
+        let mut outputs = Vec::new();
+        for bit in self.driven().filter(|bit| read.contains(bit)) {
+            if !outputs.contains(&bit) {
+                outputs.push(bit);
+            }
+        }
+        outputs
+    }
+
+    fn driven(&self) -> impl Iterator<Item = Bit> + '_ {
+        self.cells().flat_map(|(_, cell)| {
+            cell.connections
+                .iter()
+                .filter(|(port, _)| cell.port_directions.get(*port) == Some(&Direction::Output))
+                .flat_map(|(_, bits)| bits.iter().copied())
+                .filter(|bit| matches!(bit, Bit::Signal(_)))
+        })
+    }
+
+    /// Copies the selection into a module of its own.
+    pub fn to_module(&self) -> Module {
+        let mut module = Module::new();
+        module.attributes = self.module.attributes.clone();
+        module.ports = self.ports().map(|(name, port)| (name.into(), port.clone())).collect();
+        module.cells = self.cells().map(|(name, cell)| (name.into(), cell.clone())).collect();
+        module.nets = self.nets().map(|(name, net)| (name.into(), net.clone())).collect();
+        module
+    }
+}
+
+impl ModuleRead for ModuleView<'_> {
+    fn port(&self, name: &str) -> Option<&Port> {
+        self.ports().find(|(port, _)| *port == name).map(|(_, port)| port)
+    }
+
+    fn cell(&self, name: &str) -> Option<&Cell> {
+        let (index, _, cell) = self.module.cells.get_full(name)?;
+        self.cells[index].then_some(cell)
+    }
+
+    fn net(&self, name: &str) -> Option<&Net> {
+        let (index, _, net) = self.module.nets.get_full(name)?;
+        self.nets[index].then_some(net)
+    }
+
+    fn ports(&self) -> impl Iterator<Item = (&str, &Port)> {
+        let touched = self.touched();
+        self.module
+            .ports
+            .iter()
+            .filter(move |(_, port)| port.bits.iter().any(|bit| touched.contains(bit)))
+            .map(|(name, port)| (name.as_str(), port))
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (&str, &Cell)> {
+        self.module.cells.iter().zip(self.cells.iter()).filter(|(_, selected)| **selected).map(|((name, cell), _)| (name.as_str(), cell))
+    }
+
+    fn nets(&self) -> impl Iterator<Item = (&str, &Net)> {
+        self.module.nets.iter().zip(self.nets.iter()).filter(|(_, selected)| **selected).map(|((name, net), _)| (name.as_str(), net))
+    }
+}
+
+impl Module {
+    /// A view of the whole module.
+    pub fn view(&self) -> ModuleView<'_> {
+        ModuleView { module: self, cells: vec![true; self.cells.len()], nets: vec![true; self.nets.len()] }
+    }
+
+    /// A view of the named cells and the nets connected to them. Unknown names are ignored.
+    pub fn select<'a>(&'a self, cells: impl IntoIterator<Item = &'a str>) -> ModuleView<'a> {
+        let mut selected = vec![false; self.cells.len()];
+        for index in cells.into_iter().filter_map(|name| self.cells.get_index_of(name)) {
+            selected[index] = true;
+        }
+        ModuleView { module: self, cells: selected, nets: Vec::new() }.select_nets()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    fn count_gates(module: &impl ModuleRead, kind: &str) -> usize {
+        module.cells().filter(|(_, cell)| cell.module == kind).count()
+    }
+
+    #[test]
+    fn test_view() {
+        let netlist = Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap();
+        let module = &netlist.modules["adder"];
+        assert_eq!(count_gates(&module.view(), "$_XOR_"), count_gates(module, "$_XOR_"));
+        assert_eq!(module.view().nets().count(), module.nets.len());
+
+        let xors = module.view().filter_cells(|_, cell| cell.module == "$_XOR_");
+        assert_eq!(count_gates(&xors, "$_AND_"), 0);
+        assert!(count_gates(&xors, "$_XOR_") > 0);
+        let (name, _) = module.cells.iter().find(|(_, cell)| cell.module == "$_AND_").unwrap();
+        assert!(xors.cell(name).is_none());
+        assert!(module.view().cell(name).is_some());
+
+        let (name, cell) = module.cells.iter().find(|(_, cell)| cell.connections["A"][0] == Bit::Signal(2)).unwrap();
+        let single = module.select([name.as_str()]);
+        assert_eq!(single.cells().count(), 1);
+        assert!(single.port("a").is_some());
+        let mut inputs = single.inputs();
+        inputs.sort();
+        let mut expected = vec![cell.connections["A"][0], cell.connections["B"][0]];
+        expected.sort();
+        assert_eq!(inputs, expected);
+        assert_eq!(single.outputs(), cell.connections["Y"]);
+
+        let copy = single.to_module();
+        assert_eq!(copy.cells.len(), 1);
+        assert!(copy.nets.values().all(|net| net.bits.iter().any(|bit| cell.connections.values().flatten().any(|b| b == bit))));
+        assert!(single.clone().filter_nets(|_, _| false).nets().next().is_none());
+    }
+}
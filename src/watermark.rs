@@ -0,0 +1,206 @@
+//! The mark lives in the order of the inputs of commutative cells, so it survives passes that
+//! keep cells and their connections, as well as deleting or adding a few cells. Optimization
+//! passes erase it: `Module::canonicalize_inputs` sorts the inputs of every carrier, which
+//! overwrites the whole mark, and `Module::strash` and `Module::merge_redundant_logic` delete
+//! every cell identical to another up to input order, whatever order it carried, so on
+//! redundant logic they remove carriers wholesale. Run them before embedding, not after.
+
+use std::fmt;
+
+use crate::formal::{FormalError, ProofResult, ProveOptions};
+use crate::progress::pass_span;
+use crate::rng;
+use crate::{Bit, Cell, Module};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatermarkError {
+    Capacity {
+        needed: usize,
+        available: usize,
+    },
+    /// No carrier cell is left to take this payload bit under the key.
+    Uncovered(usize),
+}
+
+impl fmt::Display for WatermarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Capacity { needed, available } => {
+                write!(f, "watermark needs {} carrier cells, module only has {}", needed, available)
+            }
+            Self::Uncovered(bit) => write!(f, "no carrier cell left for watermark bit {} under this key", bit),
+        }
+    }
+}
+
+impl std::error::Error for WatermarkError {}
+
+fn inputs(cell: &Cell) -> Option<(&Vec<Bit>, &Vec<Bit>)> {
+    Some((cell.connections.get("A")?, cell.connections.get("B")?))
+}
+
+/// FNV-1a followed by the splitmix64 finalizer, so the carrier order is stable across platforms
+/// and Rust releases.
+fn keyed_hash(key: u64, name: &str) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in key.to_le_bytes().iter().chain(name.as_bytes()) {
+        hash = (hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+    }
+    rng::mix(hash)
+}
+
+/// The payload bit of every carrier. Each carrier takes the bit its name hashes to under `key`,
+/// so deleting or adding cells leaves the others where they were. A bit no name hashes to
+/// borrows the carrier with the lowest hash under a key salted with that bit, from the bits
+/// with more than one carrier. Returns the first bit left without a carrier on failure.
+fn assign(key: u64, carriers: &[String], needed: usize) -> Result<Vec<usize>, usize> {
+    let mut bits: Vec<usize> = carriers.iter().map(|name| (keyed_hash(key, name) % needed as u64) as usize).collect();
+    let mut count = vec![0usize; needed];
+    bits.iter().for_each(|bit| count[*bit] += 1);
+    for uncovered in 0..needed {
+        if count[uncovered] > 0 {
+            continue;
+        }
+        let salted = key ^ (uncovered as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+        let donor = (0..carriers.len()).filter(|index| count[bits[*index]] > 1).min_by_key(|index| keyed_hash(salted, &carriers[*index]));
+        let donor = donor.ok_or(uncovered)?;
+        count[bits[donor]] -= 1;
+        bits[donor] = uncovered;
+        count[uncovered] = 1;
+    }
+    Ok(bits)
+}
+
+impl Module {
+    /// Cells whose input order can carry a watermark bit.
+    fn carriers(&self) -> Vec<String> {
+        (self.cells.iter())
+            .filter(|(_, cell)| cell.is_commutative() && inputs(cell).is_some_and(|(a, b)| a != b))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Embeds `payload` by ordering the inputs of commutative cells, which leaves the function
+    /// of the module unchanged. `key` assigns every carrier cell a payload bit, mostly by its
+    /// name alone, so each bit is repeated over several carriers and removing or adding cells
+    /// leaves the others readable. Returns the number of carrier cells used. Use
+    /// [`Module::verify_watermark`] to prove the marked module equivalent to the original.
+    pub fn embed_watermark(&mut self, key: u64, payload: &[u8]) -> Result<usize, WatermarkError> {
+        pass_span!("embed_watermark", cells = self.cells.len(), nets = self.nets.len(), payload = payload.len());
+        let carriers = self.carriers();
+        let needed = payload.len() * 8;
+        if carriers.len() < needed || needed == 0 {
+            return Err(WatermarkError::Capacity { needed, available: carriers.len() });
+        }
+        let bits = assign(key, &carriers, needed).map_err(WatermarkError::Uncovered)?;
+        for (name, bit) in carriers.iter().zip(bits) {
+            let swapped = payload[bit / 8] >> (bit % 8) & 1 == 1;
+            let cell = self.cells.get_mut(name).unwrap();
+            let (a, b) = inputs(cell).unwrap();
+            if (a > b) != swapped {
+                cell.swap_inputs().unwrap();
+            }
+        }
+        Ok(carriers.len())
+    }
+
+    /// Reads back a watermark of `len` bytes embedded with `key`, every bit decided by a
+    /// majority vote of its carriers. Returns `None` if a bit has no carrier left.
+    pub fn detect_watermark(&self, key: u64, len: usize) -> Option<Vec<u8>> {
+        let needed = len * 8;
+        if needed == 0 {
+            return None;
+        }
+        let carriers = self.carriers();
+        let bits = assign(key, &carriers, needed).ok()?;
+        let mut votes = vec![None; needed];
+        for (name, bit) in carriers.iter().zip(bits) {
+            let (a, b) = inputs(&self.cells[name]).unwrap();
+            let vote = votes[bit].get_or_insert(0isize);
+            *vote += if a > b { 1 } else { -1 };
+        }
+        let mut payload = vec![0u8; len];
+        for (bit, vote) in votes.into_iter().enumerate() {
+            if vote? > 0 {
+                payload[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        Some(payload)
+    }
+
+    /// Proves with [`Module::check_equivalence`] that this watermarked module computes the same
+    /// function as `original`. Anything short of a proof counts as a difference.
+    pub fn verify_watermark(&self, original: &Module, options: &ProveOptions) -> Result<bool, FormalError> {
+        Ok(matches!(self.check_equivalence(original, options)?.result, ProofResult::Proven(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Netlist;
+
+    fn adder() -> Netlist {
+        Netlist::from_reader(std::fs::File::open("testdata/adder.json").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_watermark_roundtrip() {
+        let original = adder();
+        let mut marked = original.clone();
+        let module = marked.modules.get_mut("adder").unwrap();
+        let used = module.embed_watermark(3, b"kj").unwrap();
+        assert_eq!(used, 72);
+
+        assert_eq!(module.detect_watermark(3, 2).unwrap(), b"kj");
+        assert_ne!(module.detect_watermark(1, 2).unwrap(), b"kj");
+        let options = ProveOptions::default();
+        assert!(module.verify_watermark(&original.modules["adder"], &options).unwrap());
+
+        let mut broken = module.clone();
+        let (_, cell) = broken.cells.iter_mut().find(|(_, cell)| cell.module == "$_AND_" || cell.module == "$_XOR_").unwrap();
+        cell.module = if cell.module == "$_AND_" { "$_OR_" } else { "$_XNOR_" }.into();
+        assert!(!broken.verify_watermark(&original.modules["adder"], &options).unwrap());
+    }
+
+    #[test]
+    fn test_watermark_borrowed_carriers() {
+        let mut netlist = adder();
+        let module = netlist.modules.get_mut("adder").unwrap();
+        // No carrier name hashes to some bits of this payload under this key.
+        let carriers = module.carriers();
+        let mut covered = [false; 16];
+        carriers.iter().for_each(|name| covered[(keyed_hash(0xc0ffee, name) % 16) as usize] = true);
+        assert!(covered.contains(&false));
+
+        module.embed_watermark(0xc0ffee, b"kj").unwrap();
+        assert_eq!(module.detect_watermark(0xc0ffee, 2).unwrap(), b"kj");
+    }
+
+    #[test]
+    fn test_watermark_capacity() {
+        let mut netlist = adder();
+        let module = netlist.modules.get_mut("adder").unwrap();
+        assert!(module.embed_watermark(1, &[0; 64]).is_err());
+        assert!(module.detect_watermark(1, 64).is_none());
+    }
+
+    #[test]
+    fn test_watermark_survives_deletions() {
+        let mut netlist = adder();
+        let module = netlist.modules.get_mut("adder").unwrap();
+        module.embed_watermark(3, b"wm").unwrap();
+        for name in module.cells.keys() {
+            let mut edited = module.clone();
+            edited.cells.shift_remove(name);
+            assert_eq!(edited.detect_watermark(3, 2).unwrap(), b"wm", "after deleting {}", name);
+        }
+        let mut edited = module.clone();
+        let mut index = 0;
+        edited.cells.retain(|_, _| {
+            index += 1;
+            index % 10 != 0
+        });
+        assert_eq!(edited.detect_watermark(3, 2).unwrap(), b"wm");
+    }
+}
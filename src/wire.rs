@@ -0,0 +1,69 @@
+// Binary encodings of the netlist for exchanging designs over the network. Both go through
+// the same serde implementations as JSON, so bits and flags are encoded exactly like Yosys
+// writes them: signals as integers, constants as the strings `"0"`, `"1"`, `"x"` and `"z"`,
+// and flags as `0`/`1`.
+
+#[cfg(feature = "msgpack")]
+impl crate::Netlist {
+    pub fn from_msgpack(reader: impl std::io::Read) -> Result<Self, rmp_serde::decode::Error> {
+        rmp_serde::from_read(reader)
+    }
+
+    pub fn from_msgpack_slice(input: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(input)
+    }
+
+    /// Writes structs as maps with field names, so the encoding maps one to one onto the JSON
+    /// format.
+    pub fn to_msgpack(&self, mut writer: impl std::io::Write) -> Result<(), rmp_serde::encode::Error> {
+        rmp_serde::encode::write_named(&mut writer, self)
+    }
+
+    pub fn to_msgpack_vec(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(self)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl crate::Netlist {
+    pub fn from_cbor(reader: impl std::io::Read) -> Result<Self, ciborium::de::Error<std::io::Error>> {
+        ciborium::from_reader(reader)
+    }
+
+    pub fn to_cbor(&self, writer: impl std::io::Write) -> Result<(), ciborium::ser::Error<std::io::Error>> {
+        ciborium::into_writer(self, writer)
+    }
+}
+
+#[cfg(all(test, any(feature = "msgpack", feature = "cbor")))]
+mod tests {
+    use crate::Netlist;
+
+    fn netlist() -> Netlist {
+        Netlist::from_reader(std::fs::File::open("testdata/undefined.json").unwrap()).unwrap()
+    }
+
+    fn assert_same(decoded: &Netlist, original: &Netlist) {
+        assert_eq!(decoded, original);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_roundtrip() {
+        let original = netlist();
+        let encoded = original.to_msgpack_vec().unwrap();
+        assert!(encoded.len() < original.to_string().unwrap().len());
+        assert_same(&Netlist::from_msgpack_slice(&encoded).unwrap(), &original);
+        assert_same(&Netlist::from_msgpack(encoded.as_slice()).unwrap(), &original);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_roundtrip() {
+        let original = netlist();
+        let mut encoded = Vec::new();
+        original.to_cbor(&mut encoded).unwrap();
+        assert!(encoded.len() < original.to_string().unwrap().len());
+        assert_same(&Netlist::from_cbor(encoded.as_slice()).unwrap(), &original);
+    }
+}